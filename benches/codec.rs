@@ -0,0 +1,133 @@
+use std::hint::black_box;
+use std::net::{IpAddr, Ipv4Addr};
+use std::collections::BTreeMap;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use uuid::Uuid;
+use codecrafters_kafka::api::api_versions::ApiVersionsResponse;
+use codecrafters_kafka::api::metadata::MetadataResponse;
+use codecrafters_kafka::api::request::{ApiRequest, KafkaRequest};
+use codecrafters_kafka::api::response::ResponseMessage;
+use codecrafters_kafka::broker::Broker;
+use codecrafters_kafka::broker::config::BrokerConfig;
+use codecrafters_kafka::broker::topics::NewTopic;
+
+const CLIENT_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+/// A compact length, which is stored as length + 1
+fn compact_length(length: usize) -> Vec<u8> {
+    let mut value = length as u32 + 1;
+    let mut bytes = Vec::new();
+    while value >= 0b1000_0000 {
+        bytes.push((value as u8 & 0b0111_1111) | 0b1000_0000);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+    bytes
+}
+
+fn compact_string(string: &str) -> Vec<u8> {
+    [compact_length(string.len()), string.as_bytes().to_vec()].concat()
+}
+
+/// A request with a v2 header, starting with its size
+fn request(api_key: i16, api_version: i16, body: Vec<u8>) -> Vec<u8> {
+    let mut message = Vec::new();
+    message.extend(api_key.to_be_bytes());
+    message.extend(api_version.to_be_bytes());
+    message.extend(7i32.to_be_bytes());
+    message.extend(5i16.to_be_bytes());
+    message.extend(b"bench");
+    message.push(0);
+    message.extend(body);
+    [(message.len() as i32).to_be_bytes().to_vec(), message].concat()
+}
+
+/// A v9 produce request with a single partition holding the given number of bytes of records
+fn produce_request(records_size: usize) -> Vec<u8> {
+    let mut body = vec![0];
+    body.extend((-1i16).to_be_bytes());
+    body.extend(30_000i32.to_be_bytes());
+    body.extend(compact_length(1));
+    body.extend(compact_string("bench-topic"));
+    body.extend(compact_length(1));
+    body.extend(0i32.to_be_bytes());
+    body.extend(compact_length(records_size));
+    body.extend((0..records_size).map(|byte| byte as u8));
+    body.extend([0, 0, 0]);
+    request(0, 9, body)
+}
+
+/// A v12 metadata request for the given number of topics
+fn metadata_request(topics: usize) -> Vec<u8> {
+    let mut body = compact_length(topics);
+    for topic in 0..topics {
+        body.extend([0u8; 16]);
+        body.extend(compact_string(&format!("bench-topic-{topic}")));
+        body.push(0);
+    }
+    body.extend([1, 0, 0]);
+    request(3, 12, body)
+}
+
+fn decode_requests(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let requests = [
+        ("produce 16KiB", produce_request(16 * 1024)),
+        ("metadata 100 topics", metadata_request(100)),
+    ];
+
+    let mut group = c.benchmark_group("decode");
+    for (name, bytes) in &requests {
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        // reading from a connection, which reads the whole frame and then parses it
+        group.bench_function(format!("read {name}"), |b| b.iter(|| {
+            let request = runtime.block_on(KafkaRequest::try_read_from(&mut bytes.as_slice(), CLIENT_ADDRESS, i32::MAX));
+            black_box(request.unwrap())
+        }));
+        // parsing a frame that's already in memory, skipping the message size
+        group.bench_function(format!("parse {name}"), |b| b.iter(|| {
+            black_box(KafkaRequest::parse(&mut &bytes[size_of::<i32>()..], CLIENT_ADDRESS).unwrap())
+        }));
+    }
+    group.finish();
+}
+
+/// Encoding responses into the message that's written to the connection, not including building the response
+fn encode_responses(c: &mut Criterion) {
+    let log_dir = std::env::temp_dir().join(format!("codec-bench-{}", Uuid::new_v4().simple()));
+    let config = BrokerConfig::from_properties(&format!("log.dirs={}", log_dir.display())).unwrap();
+    let broker = Broker::open(config).unwrap();
+    for topic in 0..100 {
+        let new_topic = NewTopic {
+            name: format!("bench-topic-{topic}"),
+            num_partitions: 4,
+            replication_factor: 1,
+            assignments: BTreeMap::new(),
+            configs: BTreeMap::new(),
+        };
+        broker.create_topic(&new_topic, false).unwrap();
+    }
+    let metadata_request = KafkaRequest::parse(&mut &metadata_request(100)[size_of::<i32>()..], CLIENT_ADDRESS).unwrap();
+    let ApiRequest::Metadata(metadata) = metadata_request.api_request() else {
+        unreachable!("the request is a metadata request");
+    };
+    let api_versions_request = KafkaRequest::parse(&mut &request(18, 3, [compact_string("bench"), compact_string("1.0"), vec![0]].concat())[size_of::<i32>()..], CLIENT_ADDRESS).unwrap();
+
+    let mut group = c.benchmark_group("encode");
+    group.bench_function("metadata 100 topics", |b| b.iter_batched(
+        || MetadataResponse::process_request(&metadata_request, metadata, &broker),
+        |response| black_box(ResponseMessage::encode(response)),
+        BatchSize::SmallInput,
+    ));
+    group.bench_function("api versions", |b| b.iter_batched(
+        || ApiVersionsResponse::process_request(&api_versions_request),
+        |response| black_box(ResponseMessage::encode(response)),
+        BatchSize::SmallInput,
+    ));
+    group.finish();
+    drop(broker);
+    let _ = std::fs::remove_dir_all(log_dir);
+}
+
+criterion_group!(benches, decode_requests, encode_responses);
+criterion_main!(benches);
@@ -1,6 +1,45 @@
+pub mod add_offsets_to_txn;
+pub mod add_partitions_to_txn;
+pub mod alter_configs;
+pub mod alter_user_scram_credentials;
+pub mod api_key;
 pub mod api_versions;
+pub mod consumer_group_describe;
+pub mod consumer_group_heartbeat;
+pub mod create_partitions;
+pub mod create_topics;
+pub mod delete_groups;
+pub mod delete_topics;
+pub mod describe_groups;
+pub mod describe_configs;
+pub mod describe_producers;
+pub mod describe_transactions;
+pub mod describe_user_scram_credentials;
+pub mod end_txn;
+pub mod error_code;
+pub mod fetch;
+pub mod find_coordinator;
+pub mod handler;
+pub mod heartbeat;
+pub mod incremental_alter_configs;
+pub mod init_producer_id;
+pub mod join_group;
+pub mod leave_group;
+pub mod list_groups;
+pub mod list_offsets;
+pub mod list_transactions;
+pub mod metadata;
+pub mod offset_commit;
+pub mod offset_delete;
+pub mod offset_fetch;
+pub mod produce;
 pub mod request;
 pub mod response;
+pub mod sasl_authenticate;
+pub mod sasl_handshake;
 pub mod server;
-mod api_key;
+pub mod sync_group;
+pub mod txn_offset_commit;
+mod config_resource;
 mod correlation_id;
+mod isolation_level;
@@ -0,0 +1,78 @@
+use bytes::{Buf, BufMut};
+use tracing::error;
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::coordinator::transaction::TransactionError;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+#[derive(Debug)]
+pub struct AddOffsetsToTxnRequest {
+    transactional_id: String,
+    producer_id: i64,
+    producer_epoch: i16,
+    /// The group whose offsets the producer is going to commit in its transaction
+    group_id: String,
+}
+
+impl ReadVersionedKafkaBytes for AddOffsetsToTxnRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let transactional_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let producer_id = i64::read_kafka_bytes(buf)?;
+        let producer_epoch = i16::read_kafka_bytes(buf)?;
+        let group_id = String::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(AddOffsetsToTxnRequest { transactional_id, producer_id, producer_epoch, group_id })
+    }
+}
+
+#[derive(Debug)]
+pub struct AddOffsetsToTxnResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    error_code: ErrorCode,
+}
+
+impl AddOffsetsToTxnResponse {
+    pub fn process_request(request: &KafkaRequest, add_offsets: &AddOffsetsToTxnRequest, broker: &Broker) -> Self {
+        // the group's partition of the offsets topic is added to the transaction, so it gets the transaction's marker
+        let coordinator = broker.transaction_coordinator();
+        let result = coordinator.offsets_partition(&add_offsets.group_id).and_then(|offsets_partition| {
+            coordinator.add_partitions(&add_offsets.transactional_id, add_offsets.producer_id, add_offsets.producer_epoch, vec![offsets_partition])
+        });
+        let error_code = match result {
+            Ok(()) => ErrorCode::NoError,
+            Err(err) => {
+                if let TransactionError::Storage(err) = &err {
+                    error!(transactional_id = %add_offsets.transactional_id, %err, "Failed to add offsets to transaction");
+                }
+                ErrorCode::from(&err).or_invalid_producer_epoch(request.api_version() >= 2)
+            }
+        };
+
+        AddOffsetsToTxnResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            error_code,
+        }
+    }
+}
+
+impl ApiResponse for AddOffsetsToTxnResponse {
+    fn error_code(&self) -> Option<ErrorCode> {
+        Some(self.error_code)
+    }
+}
+
+impl ToKafkaBytes for AddOffsetsToTxnResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        self.base_response.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, self.version);
+    }
+}
@@ -0,0 +1,151 @@
+use bytes::{Buf, BufMut};
+use tracing::error;
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::coordinator::transaction::TransactionError;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+use crate::storage::topic_partition::TopicPartition;
+
+#[derive(Debug)]
+pub struct AddPartitionsToTxnRequest {
+    transactional_id: String,
+    producer_id: i64,
+    producer_epoch: i16,
+    topics: Vec<AddPartitionsToTxnTopic>,
+}
+
+impl ReadVersionedKafkaBytes for AddPartitionsToTxnRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let transactional_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let producer_id = i64::read_kafka_bytes(buf)?;
+        let producer_epoch = i16::read_kafka_bytes(buf)?;
+        let topics = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(AddPartitionsToTxnRequest { transactional_id, producer_id, producer_epoch, topics })
+    }
+}
+
+#[derive(Debug)]
+struct AddPartitionsToTxnTopic {
+    name: String,
+    partitions: Vec<i32>,
+}
+
+impl ReadVersionedKafkaBytes for AddPartitionsToTxnTopic {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let name = String::read_versioned_kafka_bytes(buf, version)?;
+        let partitions = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(AddPartitionsToTxnTopic { name, partitions })
+    }
+}
+
+#[derive(Debug)]
+pub struct AddPartitionsToTxnResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    results: Vec<AddPartitionsToTxnTopicResult>,
+}
+
+impl AddPartitionsToTxnResponse {
+    pub fn process_request(request: &KafkaRequest, add_partitions: &AddPartitionsToTxnRequest, broker: &Broker) -> Self {
+        let unknown: Vec<TopicPartition> = {
+            let image = broker.metadata().image();
+            add_partitions.topics.iter()
+                .flat_map(|topic| {
+                    let metadata = image.topic(&topic.name);
+                    topic.partitions.iter()
+                        .filter(move |partition| metadata.and_then(|metadata| metadata.partition(**partition)).is_none())
+                        .map(|partition| TopicPartition::new(topic.name.clone(), *partition))
+                })
+                .collect()
+        };
+
+        // the partitions are added together, so if any of them can't be added none of them are
+        let error_code = match unknown.is_empty() {
+            true => {
+                let partitions = add_partitions.topics.iter()
+                    .flat_map(|topic| topic.partitions.iter().map(|partition| TopicPartition::new(topic.name.clone(), *partition)))
+                    .collect();
+                let result = broker.transaction_coordinator()
+                    .add_partitions(&add_partitions.transactional_id, add_partitions.producer_id, add_partitions.producer_epoch, partitions);
+                Some(result.map_or_else(|err| transaction_error_code(request, &add_partitions.transactional_id, &err), |_| ErrorCode::NoError))
+            }
+            false => None,
+        };
+
+        let results = add_partitions.topics.iter()
+            .map(|topic| AddPartitionsToTxnTopicResult {
+                name: topic.name.clone(),
+                results: topic.partitions.iter()
+                    .map(|partition| AddPartitionsToTxnPartitionResult {
+                        partition_index: *partition,
+                        error_code: match error_code {
+                            Some(error_code) => error_code,
+                            None if unknown.contains(&TopicPartition::new(topic.name.clone(), *partition)) => ErrorCode::UnknownTopicOrPartition,
+                            None => ErrorCode::OperationNotAttempted,
+                        },
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        AddPartitionsToTxnResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            results,
+        }
+    }
+}
+
+fn transaction_error_code(request: &KafkaRequest, transactional_id: &str, err: &TransactionError) -> ErrorCode {
+    if let TransactionError::Storage(err) = err {
+        error!(%transactional_id, %err, "Failed to add partitions to transaction");
+    }
+    ErrorCode::from(err).or_invalid_producer_epoch(request.api_version() >= 2)
+}
+
+impl ApiResponse for AddPartitionsToTxnResponse {}
+
+impl ToKafkaBytes for AddPartitionsToTxnResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        self.results.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct AddPartitionsToTxnTopicResult {
+    name: String,
+    results: Vec<AddPartitionsToTxnPartitionResult>,
+}
+
+impl ToVersionedKafkaBytes for AddPartitionsToTxnTopicResult {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.name.write_versioned_kafka_bytes(buf, version);
+        self.results.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct AddPartitionsToTxnPartitionResult {
+    partition_index: i32,
+    error_code: ErrorCode,
+}
+
+impl ToVersionedKafkaBytes for AddPartitionsToTxnPartitionResult {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.partition_index.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, version);
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::{BTreeMap, HashSet};
+use bytes::{Buf, BufMut};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::config_resource::config_resource;
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+#[derive(Debug)]
+pub struct AlterConfigsRequest {
+    resources: Vec<AlterConfigsResource>,
+    validate_only: bool,
+}
+
+impl ReadVersionedKafkaBytes for AlterConfigsRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let resources = Vec::read_versioned_kafka_bytes(buf, version)?;
+        let validate_only = bool::read_kafka_bytes(buf)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(AlterConfigsRequest { resources, validate_only })
+    }
+}
+
+#[derive(Debug)]
+struct AlterConfigsResource {
+    resource_type: i8,
+    resource_name: String,
+    configs: Vec<AlterableConfig>,
+}
+
+impl ReadVersionedKafkaBytes for AlterConfigsResource {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let resource = AlterConfigsResource {
+            resource_type: i8::read_kafka_bytes(buf)?,
+            resource_name: String::read_versioned_kafka_bytes(buf, version)?,
+            configs: Vec::read_versioned_kafka_bytes(buf, version)?,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(resource)
+    }
+}
+
+#[derive(Debug)]
+struct AlterableConfig {
+    name: String,
+    value: Option<String>,
+}
+
+impl ReadVersionedKafkaBytes for AlterableConfig {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let name = String::read_versioned_kafka_bytes(buf, version)?;
+        let value = Option::<String>::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(AlterableConfig { name, value })
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterConfigsResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    responses: Vec<AlterConfigsResourceResponse>,
+}
+
+impl AlterConfigsResponse {
+    pub fn process_request(request: &KafkaRequest, alter_configs: &AlterConfigsRequest, broker: &Broker) -> Self {
+        let mut seen = HashSet::new();
+        let duplicates: HashSet<(i8, &str)> = alter_configs.resources.iter()
+            .map(|resource| (resource.resource_type, resource.resource_name.as_str()))
+            .filter(|resource| !seen.insert(*resource))
+            .collect();
+
+        let responses = alter_configs.resources.iter()
+            .map(|resource| {
+                let result = match duplicates.contains(&(resource.resource_type, resource.resource_name.as_str())) {
+                    true => Err((ErrorCode::InvalidRequest, format!("Duplicate resource in request: {}", resource.resource_name))),
+                    false => config_resource(resource.resource_type, &resource.resource_name)
+                        .map_err(|message| (ErrorCode::InvalidRequest, message)),
+                };
+                // every config of the resource is replaced, so the ones without a value are left unset
+                let configs: BTreeMap<String, String> = resource.configs.iter()
+                    .filter_map(|config| Some((config.name.clone(), config.value.clone()?)))
+                    .collect();
+                let result = result.and_then(|config_resource| broker.alter_configs(config_resource, configs, alter_configs.validate_only)
+                    .map_err(|err| (ErrorCode::from(&err), err.to_string())));
+                let (error_code, error_message) = match result {
+                    Ok(()) => (ErrorCode::NoError, None),
+                    Err((error_code, message)) => (error_code, Some(message)),
+                };
+                AlterConfigsResourceResponse {
+                    error_code,
+                    error_message,
+                    resource_type: resource.resource_type,
+                    resource_name: resource.resource_name.clone(),
+                }
+            })
+            .collect();
+
+        AlterConfigsResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            responses,
+        }
+    }
+}
+
+impl ApiResponse for AlterConfigsResponse {}
+
+impl ToKafkaBytes for AlterConfigsResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        self.responses.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct AlterConfigsResourceResponse {
+    error_code: ErrorCode,
+    error_message: Option<String>,
+    resource_type: i8,
+    resource_name: String,
+}
+
+impl ToVersionedKafkaBytes for AlterConfigsResourceResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.error_code.write_kafka_bytes(buf);
+        self.error_message.write_versioned_kafka_bytes(buf, version);
+        self.resource_type.write_kafka_bytes(buf);
+        self.resource_name.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
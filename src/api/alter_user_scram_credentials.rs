@@ -0,0 +1,157 @@
+use std::collections::HashSet;
+use bytes::{Buf, BufMut};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::broker::scram_credentials::{ScramCredentialAlteration, ScramCredentialError};
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+#[derive(Debug)]
+pub struct AlterUserScramCredentialsRequest {
+    deletions: Vec<ScramCredentialDeletion>,
+    upsertions: Vec<ScramCredentialUpsertion>,
+}
+
+impl ReadVersionedKafkaBytes for AlterUserScramCredentialsRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let deletions = Vec::read_versioned_kafka_bytes(buf, version)?;
+        let upsertions = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(AlterUserScramCredentialsRequest { deletions, upsertions })
+    }
+}
+
+#[derive(Debug)]
+struct ScramCredentialDeletion {
+    name: String,
+    mechanism: i8,
+}
+
+impl ReadVersionedKafkaBytes for ScramCredentialDeletion {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let deletion = ScramCredentialDeletion {
+            name: String::read_versioned_kafka_bytes(buf, version)?,
+            mechanism: i8::read_kafka_bytes(buf)?,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(deletion)
+    }
+}
+
+#[derive(Debug)]
+struct ScramCredentialUpsertion {
+    name: String,
+    mechanism: i8,
+    iterations: i32,
+    salt: Vec<u8>,
+    salted_password: Vec<u8>,
+}
+
+impl ReadVersionedKafkaBytes for ScramCredentialUpsertion {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let upsertion = ScramCredentialUpsertion {
+            name: String::read_versioned_kafka_bytes(buf, version)?,
+            mechanism: i8::read_kafka_bytes(buf)?,
+            iterations: i32::read_kafka_bytes(buf)?,
+            salt: Vec::read_versioned_kafka_bytes(buf, version)?,
+            salted_password: Vec::read_versioned_kafka_bytes(buf, version)?,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(upsertion)
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterUserScramCredentialsResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    results: Vec<AlterUserScramCredentialsResult>,
+}
+
+impl AlterUserScramCredentialsResponse {
+    pub fn process_request(request: &KafkaRequest, alter: &AlterUserScramCredentialsRequest, broker: &Broker) -> Self {
+        let deletions = alter.deletions.iter()
+            .map(|deletion| (&deletion.name, ScramCredentialAlteration::Delete { mechanism: deletion.mechanism }));
+        let upsertions = alter.upsertions.iter()
+            .map(|upsertion| (&upsertion.name, ScramCredentialAlteration::Upsert {
+                mechanism: upsertion.mechanism,
+                iterations: upsertion.iterations,
+                salt: upsertion.salt.clone(),
+                salted_password: upsertion.salted_password.clone(),
+            }));
+
+        // each user gets one result for all of their alterations, in the order the users first appear in the request
+        let mut users: Vec<(String, Vec<ScramCredentialAlteration>)> = Vec::new();
+        let mut seen = HashSet::new();
+        let mut duplicates = HashSet::new();
+        for (user, alteration) in deletions.chain(upsertions) {
+            let mechanism = match alteration {
+                ScramCredentialAlteration::Delete { mechanism } | ScramCredentialAlteration::Upsert { mechanism, .. } => mechanism,
+            };
+            if !seen.insert((user, mechanism)) {
+                duplicates.insert(user.as_str());
+            }
+            match users.iter_mut().find(|(existing, _)| existing == user) {
+                Some((_, alterations)) => alterations.push(alteration),
+                None => users.push((user.clone(), vec![alteration])),
+            }
+        }
+
+        let altered: Vec<_> = users.iter()
+            .filter(|(user, _)| !duplicates.contains(user.as_str()))
+            .cloned()
+            .collect();
+        let mut altered_results = broker.alter_scram_credentials(&altered).into_iter();
+        let results = users.iter()
+            .map(|(user, _)| {
+                let result = match duplicates.contains(user.as_str()) {
+                    true => Err(ScramCredentialError::DuplicateResource("A user credential cannot be altered twice in the same request".to_string())),
+                    false => altered_results.next().expect("every user that's altered has a result"),
+                };
+                let (error_code, error_message) = match result {
+                    Ok(()) => (ErrorCode::NoError, None),
+                    Err(err) => (ErrorCode::from(&err), Some(err.to_string())),
+                };
+                AlterUserScramCredentialsResult { user: user.clone(), error_code, error_message }
+            })
+            .collect();
+
+        AlterUserScramCredentialsResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            results,
+        }
+    }
+}
+
+impl ApiResponse for AlterUserScramCredentialsResponse {}
+
+impl ToKafkaBytes for AlterUserScramCredentialsResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        self.results.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct AlterUserScramCredentialsResult {
+    user: String,
+    error_code: ErrorCode,
+    error_message: Option<String>,
+}
+
+impl ToVersionedKafkaBytes for AlterUserScramCredentialsResult {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.user.write_versioned_kafka_bytes(buf, version);
+        self.error_code.write_kafka_bytes(buf);
+        self.error_message.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
@@ -1,16 +1,182 @@
+use std::ops::RangeInclusive;
 use thiserror::Error;
-use tokio::io::AsyncRead;
+use bytes::{Buf, BufMut};
 use crate::api::request::KafkaRequestParseError;
 use crate::serialisation::{ReadKafkaBytes, ToKafkaBytes};
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum ApiKey {
     Produce,
     Fetch,
+    ListOffsets,
+    Metadata,
+    OffsetCommit,
+    OffsetFetch,
+    FindCoordinator,
+    JoinGroup,
+    Heartbeat,
+    LeaveGroup,
+    SyncGroup,
+    DescribeGroups,
+    ListGroups,
+    SaslHandshake,
     ApiVersions,
+    CreateTopics,
+    DeleteTopics,
+    InitProducerId,
+    AddPartitionsToTxn,
+    AddOffsetsToTxn,
+    EndTxn,
+    TxnOffsetCommit,
+    DescribeConfigs,
+    AlterConfigs,
+    SaslAuthenticate,
+    CreatePartitions,
+    DeleteGroups,
+    IncrementalAlterConfigs,
+    OffsetDelete,
+    DescribeProducers,
+    DescribeTransactions,
+    ListTransactions,
+    ConsumerGroupHeartbeat,
+    ConsumerGroupDescribe,
+    DescribeUserScramCredentials,
+    AlterUserScramCredentials,
     DescribeTopicPartitions
 }
 
+impl ApiKey {
+    pub const ALL: [ApiKey; 37] = [
+        ApiKey::Produce,
+        ApiKey::Fetch,
+        ApiKey::ListOffsets,
+        ApiKey::Metadata,
+        ApiKey::OffsetCommit,
+        ApiKey::OffsetFetch,
+        ApiKey::FindCoordinator,
+        ApiKey::JoinGroup,
+        ApiKey::Heartbeat,
+        ApiKey::LeaveGroup,
+        ApiKey::SyncGroup,
+        ApiKey::DescribeGroups,
+        ApiKey::ListGroups,
+        ApiKey::SaslHandshake,
+        ApiKey::ApiVersions,
+        ApiKey::CreateTopics,
+        ApiKey::DeleteTopics,
+        ApiKey::InitProducerId,
+        ApiKey::AddPartitionsToTxn,
+        ApiKey::AddOffsetsToTxn,
+        ApiKey::EndTxn,
+        ApiKey::TxnOffsetCommit,
+        ApiKey::DescribeConfigs,
+        ApiKey::AlterConfigs,
+        ApiKey::SaslAuthenticate,
+        ApiKey::CreatePartitions,
+        ApiKey::DeleteGroups,
+        ApiKey::IncrementalAlterConfigs,
+        ApiKey::OffsetDelete,
+        ApiKey::DescribeProducers,
+        ApiKey::DescribeTransactions,
+        ApiKey::ListTransactions,
+        ApiKey::ConsumerGroupHeartbeat,
+        ApiKey::ConsumerGroupDescribe,
+        ApiKey::DescribeUserScramCredentials,
+        ApiKey::AlterUserScramCredentials,
+        ApiKey::DescribeTopicPartitions,
+    ];
+
+    /// The versions of this API that the server can handle, or None if the server doesn't support it
+    pub fn supported_versions(&self) -> Option<RangeInclusive<i16>> {
+        match self {
+            ApiKey::Produce => Some(3..=11),
+            ApiKey::Fetch => Some(4..=16),
+            ApiKey::ListOffsets => Some(0..=9),
+            ApiKey::Metadata => Some(0..=12),
+            ApiKey::OffsetCommit => Some(0..=9),
+            ApiKey::OffsetFetch => Some(0..=9),
+            ApiKey::FindCoordinator => Some(0..=4),
+            ApiKey::JoinGroup => Some(0..=9),
+            ApiKey::Heartbeat => Some(0..=4),
+            ApiKey::LeaveGroup => Some(0..=5),
+            ApiKey::SyncGroup => Some(0..=5),
+            ApiKey::DescribeGroups => Some(0..=5),
+            ApiKey::ListGroups => Some(0..=5),
+            // version 0 sends the SASL tokens without SaslAuthenticate, which only clients from before kafka 1.0 use
+            ApiKey::SaslHandshake => Some(1..=1),
+            ApiKey::ApiVersions => Some(0..=4),
+            ApiKey::CreateTopics => Some(0..=7),
+            ApiKey::DeleteTopics => Some(0..=6),
+            ApiKey::InitProducerId => Some(0..=5),
+            ApiKey::AddPartitionsToTxn => Some(0..=3),
+            ApiKey::AddOffsetsToTxn => Some(0..=3),
+            ApiKey::EndTxn => Some(0..=3),
+            ApiKey::TxnOffsetCommit => Some(0..=3),
+            ApiKey::DescribeConfigs => Some(0..=4),
+            ApiKey::AlterConfigs => Some(0..=2),
+            ApiKey::SaslAuthenticate => Some(0..=2),
+            ApiKey::CreatePartitions => Some(0..=3),
+            ApiKey::DeleteGroups => Some(0..=2),
+            ApiKey::IncrementalAlterConfigs => Some(0..=1),
+            ApiKey::OffsetDelete => Some(0..=0),
+            ApiKey::DescribeUserScramCredentials => Some(0..=0),
+            ApiKey::AlterUserScramCredentials => Some(0..=0),
+            ApiKey::DescribeProducers => Some(0..=0),
+            ApiKey::DescribeTransactions => Some(0..=0),
+            ApiKey::ListTransactions => Some(0..=1),
+            ApiKey::ConsumerGroupHeartbeat => Some(0..=1),
+            ApiKey::ConsumerGroupDescribe => Some(0..=0),
+            ApiKey::DescribeTopicPartitions => Some(0..=0),
+        }
+    }
+
+    /// Whether the given version of this API uses the flexible encoding from KIP-482
+    pub fn is_flexible(&self, version: i16) -> bool {
+        let first_flexible_version = match self {
+            ApiKey::Produce => 9,
+            ApiKey::Fetch => 12,
+            ApiKey::ListOffsets => 6,
+            ApiKey::Metadata => 9,
+            ApiKey::OffsetCommit => 8,
+            ApiKey::OffsetFetch => 6,
+            ApiKey::FindCoordinator => 3,
+            ApiKey::JoinGroup => 6,
+            ApiKey::Heartbeat => 4,
+            ApiKey::LeaveGroup => 4,
+            ApiKey::SyncGroup => 4,
+            ApiKey::DescribeGroups => 5,
+            ApiKey::ListGroups => 3,
+            // SaslHandshake has no flexible versions
+            ApiKey::SaslHandshake => i16::MAX,
+            ApiKey::ApiVersions => 3,
+            ApiKey::CreateTopics => 5,
+            ApiKey::DeleteTopics => 4,
+            ApiKey::InitProducerId => 2,
+            ApiKey::AddPartitionsToTxn => 3,
+            ApiKey::AddOffsetsToTxn => 3,
+            ApiKey::EndTxn => 3,
+            ApiKey::TxnOffsetCommit => 3,
+            ApiKey::DescribeConfigs => 4,
+            ApiKey::AlterConfigs => 2,
+            ApiKey::SaslAuthenticate => 2,
+            ApiKey::CreatePartitions => 2,
+            ApiKey::DeleteGroups => 2,
+            ApiKey::IncrementalAlterConfigs => 1,
+            // OffsetDelete has no flexible versions
+            ApiKey::OffsetDelete => i16::MAX,
+            ApiKey::DescribeUserScramCredentials => 0,
+            ApiKey::AlterUserScramCredentials => 0,
+            ApiKey::DescribeProducers => 0,
+            ApiKey::DescribeTransactions => 0,
+            ApiKey::ListTransactions => 0,
+            ApiKey::ConsumerGroupHeartbeat => 0,
+            ApiKey::ConsumerGroupDescribe => 0,
+            ApiKey::DescribeTopicPartitions => 0,
+        };
+        version >= first_flexible_version
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ParseApiKeyError {
     #[error("Invalid Api Key: {0}")]
@@ -24,28 +190,93 @@ impl TryFrom<i16> for ApiKey {
         match value {
             0 => Ok(ApiKey::Produce),
             1 => Ok(ApiKey::Fetch),
+            2 => Ok(ApiKey::ListOffsets),
+            3 => Ok(ApiKey::Metadata),
+            8 => Ok(ApiKey::OffsetCommit),
+            9 => Ok(ApiKey::OffsetFetch),
+            10 => Ok(ApiKey::FindCoordinator),
+            11 => Ok(ApiKey::JoinGroup),
+            12 => Ok(ApiKey::Heartbeat),
+            13 => Ok(ApiKey::LeaveGroup),
+            14 => Ok(ApiKey::SyncGroup),
+            15 => Ok(ApiKey::DescribeGroups),
+            16 => Ok(ApiKey::ListGroups),
+            17 => Ok(ApiKey::SaslHandshake),
             18 => Ok(ApiKey::ApiVersions),
+            19 => Ok(ApiKey::CreateTopics),
+            20 => Ok(ApiKey::DeleteTopics),
+            22 => Ok(ApiKey::InitProducerId),
+            24 => Ok(ApiKey::AddPartitionsToTxn),
+            25 => Ok(ApiKey::AddOffsetsToTxn),
+            26 => Ok(ApiKey::EndTxn),
+            28 => Ok(ApiKey::TxnOffsetCommit),
+            32 => Ok(ApiKey::DescribeConfigs),
+            33 => Ok(ApiKey::AlterConfigs),
+            36 => Ok(ApiKey::SaslAuthenticate),
+            37 => Ok(ApiKey::CreatePartitions),
+            42 => Ok(ApiKey::DeleteGroups),
+            44 => Ok(ApiKey::IncrementalAlterConfigs),
+            47 => Ok(ApiKey::OffsetDelete),
+            50 => Ok(ApiKey::DescribeUserScramCredentials),
+            51 => Ok(ApiKey::AlterUserScramCredentials),
+            61 => Ok(ApiKey::DescribeProducers),
+            65 => Ok(ApiKey::DescribeTransactions),
+            66 => Ok(ApiKey::ListTransactions),
+            68 => Ok(ApiKey::ConsumerGroupHeartbeat),
+            69 => Ok(ApiKey::ConsumerGroupDescribe),
             _ => Err(ParseApiKeyError::InvalidKey(value)),
         }
     }
 }
 
 impl ReadKafkaBytes for ApiKey {
-    async fn read_kafka_bytes<T: AsyncRead + Unpin>(reader: &mut T) -> Result<Self, KafkaRequestParseError> {
-        i16::read_kafka_bytes(reader)
-            .await
+    fn read_kafka_bytes<B: Buf>(buf: &mut B) -> Result<Self, KafkaRequestParseError> {
+        i16::read_kafka_bytes(buf)
             .and_then(|int| Ok(int.try_into()?))
     }
 }
 
 impl ToKafkaBytes for ApiKey {
-    fn to_kafka_bytes(self) -> impl IntoIterator<Item = u8> {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
         let int_repr: i16 = match self {
             ApiKey::Produce => 0,
             ApiKey::Fetch => 1,
+            ApiKey::ListOffsets => 2,
+            ApiKey::Metadata => 3,
+            ApiKey::OffsetCommit => 8,
+            ApiKey::OffsetFetch => 9,
+            ApiKey::FindCoordinator => 10,
+            ApiKey::JoinGroup => 11,
+            ApiKey::Heartbeat => 12,
+            ApiKey::LeaveGroup => 13,
+            ApiKey::SyncGroup => 14,
+            ApiKey::DescribeGroups => 15,
+            ApiKey::ListGroups => 16,
+            ApiKey::SaslHandshake => 17,
             ApiKey::ApiVersions => 18,
+            ApiKey::CreateTopics => 19,
+            ApiKey::DeleteTopics => 20,
+            ApiKey::InitProducerId => 22,
+            ApiKey::AddPartitionsToTxn => 24,
+            ApiKey::AddOffsetsToTxn => 25,
+            ApiKey::EndTxn => 26,
+            ApiKey::TxnOffsetCommit => 28,
+            ApiKey::DescribeConfigs => 32,
+            ApiKey::AlterConfigs => 33,
+            ApiKey::SaslAuthenticate => 36,
+            ApiKey::CreatePartitions => 37,
+            ApiKey::DeleteGroups => 42,
+            ApiKey::IncrementalAlterConfigs => 44,
+            ApiKey::OffsetDelete => 47,
+            ApiKey::DescribeUserScramCredentials => 50,
+            ApiKey::AlterUserScramCredentials => 51,
+            ApiKey::DescribeProducers => 61,
+            ApiKey::DescribeTransactions => 65,
+            ApiKey::ListTransactions => 66,
+            ApiKey::ConsumerGroupHeartbeat => 68,
+            ApiKey::ConsumerGroupDescribe => 69,
             ApiKey::DescribeTopicPartitions => 75
         };
-        int_repr.to_kafka_bytes()
+        int_repr.write_kafka_bytes(buf)
     }
 }
@@ -1,43 +1,68 @@
-use super::response::BaseKafkaResponse;
-use crate::api::request::KafkaRequest;
+use bytes::{Buf, BufMut};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
 use crate::api::api_key::ApiKey;
-use crate::serialisation::ToKafkaBytes;
+use crate::api::error_code::ErrorCode;
+use crate::serialisation::{MessageVersion, ReadVersionedKafkaBytes, ToKafkaBytes};
+use crate::serialisation::versioned::skip_tagged_fields;
 
-/// Dummy struct for now, we could later handle the parameters of the ApiVersions API
-#[derive(Debug)]
-pub struct ApiVersionsRequest;
+/// The ApiVersions request, the client only describes itself in versions 3+
+#[derive(Debug, Default)]
+pub struct ApiVersionsRequest {
+    client_software_name: Option<String>,
+    client_software_version: Option<String>,
+}
+
+impl ApiVersionsRequest {
+    pub fn client_software_name(&self) -> Option<&str> {
+        self.client_software_name.as_deref()
+    }
+
+    pub fn client_software_version(&self) -> Option<&str> {
+        self.client_software_version.as_deref()
+    }
+}
+
+impl ReadVersionedKafkaBytes for ApiVersionsRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        if version.version() < 3 {
+            return Ok(ApiVersionsRequest::default());
+        }
+        let client_software_name = String::read_versioned_kafka_bytes(buf, version)?;
+        let client_software_version = String::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(ApiVersionsRequest {
+            client_software_name: Some(client_software_name),
+            client_software_version: Some(client_software_version),
+        })
+    }
+}
 
 #[derive(Debug)]
 pub struct ApiVersionsResponse {
     base_response: BaseKafkaResponse,
-    error_code: ApiVersionsErrorCode,
+    error_code: ErrorCode,
     api_keys: Vec<ApiVersionInfo>,
     throttle_time_ms: i32,
 }
 
 impl ApiVersionsResponse {
-    // in future this should take a specific ApiVersionsRequest
     pub fn process_request(request: &KafkaRequest) -> Self {
         let base_response = BaseKafkaResponse::new(request);
-        let error_code = match request.api_version() {
-            0..=4 => ApiVersionsErrorCode::NoError,
-            _ => ApiVersionsErrorCode::UnsupportedVersion,
+        let error_code = match request.api_key().supported_versions() {
+            Some(versions) if versions.contains(&request.api_version()) => ErrorCode::NoError,
+            _ => ErrorCode::UnsupportedVersion,
         };
         let api_keys = match error_code {
-            ApiVersionsErrorCode::UnsupportedVersion => Vec::new(),
-            // in future this shouldn't be hardcoded
-            ApiVersionsErrorCode::NoError => vec![
-                ApiVersionInfo {
-                    api_key: ApiKey::ApiVersions,
-                    min_version: 0,
-                    max_version: 4,
-                },
-                ApiVersionInfo {
-                    api_key: ApiKey::DescribeTopicPartitions,
-                    min_version: 0,
-                    max_version: 0
-                }
-            ],
+            ErrorCode::NoError => ApiKey::ALL
+                .into_iter()
+                .filter_map(|api_key| api_key.supported_versions().map(|versions| ApiVersionInfo {
+                    api_key,
+                    min_version: *versions.start(),
+                    max_version: *versions.end(),
+                }))
+                .collect(),
+            _ => Vec::new(),
         };
         ApiVersionsResponse {
             base_response,
@@ -48,16 +73,20 @@ impl ApiVersionsResponse {
     }
 }
 
+impl ApiResponse for ApiVersionsResponse {
+    fn error_code(&self) -> Option<ErrorCode> {
+        Some(self.error_code)
+    }
+}
+
 impl ToKafkaBytes for ApiVersionsResponse {
-    fn to_kafka_bytes(self) -> impl IntoIterator<Item = u8> {
-        self.base_response
-            .to_kafka_bytes()
-            .into_iter()
-            .chain(self.error_code.to_kafka_bytes())
-            .chain(self.api_keys.to_kafka_bytes())
-            .chain(self.throttle_time_ms.to_kafka_bytes())
-            // if we support tags in the future, then we shouldn't hardcode the number of tags as 0 here
-            .chain(0u8.to_kafka_bytes())
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        self.base_response.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        self.api_keys.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        // if we support tags in the future, then we shouldn't hardcode the number of tags as 0 here
+        0u8.write_kafka_bytes(buf);
     }
 }
 
@@ -69,29 +98,11 @@ pub struct ApiVersionInfo {
 }
 
 impl ToKafkaBytes for ApiVersionInfo {
-    fn to_kafka_bytes(self) -> impl IntoIterator<Item = u8> {
-        self.api_key
-            .to_kafka_bytes()
-            .into_iter()
-            .chain(self.min_version.to_kafka_bytes())
-            .chain(self.max_version.to_kafka_bytes())
-            // if we support tags in the future, then we shouldn't hardcode the number of tags as 0 here
-            .chain(0u8.to_kafka_bytes())
-    }
-}
-
-#[derive(Debug)]
-enum ApiVersionsErrorCode {
-    NoError,
-    UnsupportedVersion,
-}
-
-impl ToKafkaBytes for ApiVersionsErrorCode {
-    fn to_kafka_bytes(self) -> impl IntoIterator<Item = u8> {
-        let error_code: i16 = match self {
-            ApiVersionsErrorCode::NoError => 0,
-            ApiVersionsErrorCode::UnsupportedVersion => 35,
-        };
-        error_code.to_kafka_bytes()
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        self.api_key.write_kafka_bytes(buf);
+        self.min_version.write_kafka_bytes(buf);
+        self.max_version.write_kafka_bytes(buf);
+        // if we support tags in the future, then we shouldn't hardcode the number of tags as 0 here
+        0u8.write_kafka_bytes(buf);
     }
 }
@@ -0,0 +1,11 @@
+use crate::broker::configs::ConfigResource;
+use crate::metadata::records::{BROKER_RESOURCE_TYPE, TOPIC_RESOURCE_TYPE};
+
+/// The resource a config request is for, from its resource type and name
+pub fn config_resource(resource_type: i8, resource_name: &str) -> Result<ConfigResource<'_>, String> {
+    match resource_type {
+        TOPIC_RESOURCE_TYPE => Ok(ConfigResource::Topic(resource_name)),
+        BROKER_RESOURCE_TYPE => Ok(ConfigResource::Broker(resource_name)),
+        _ => Err(format!("Unsupported resource type {resource_type} for resource {resource_name}")),
+    }
+}
@@ -0,0 +1,218 @@
+use bytes::{Buf, BufMut};
+use uuid::Uuid;
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::describe_groups::GROUP_AUTHORIZED_OPERATIONS;
+use crate::api::error_code::ErrorCode;
+use crate::api::metadata::AUTHORIZED_OPERATIONS_OMITTED;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::coordinator::group::consumer_group::Assignment;
+use crate::coordinator::group::{DescribedConsumerGroup, DescribedConsumerMember};
+use crate::metadata::image::MetadataImage;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+#[derive(Debug)]
+pub struct ConsumerGroupDescribeRequest {
+    group_ids: Vec<String>,
+    include_authorized_operations: bool,
+}
+
+impl ReadVersionedKafkaBytes for ConsumerGroupDescribeRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let group_ids = Vec::read_versioned_kafka_bytes(buf, version)?;
+        let include_authorized_operations = bool::read_kafka_bytes(buf)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(ConsumerGroupDescribeRequest { group_ids, include_authorized_operations })
+    }
+}
+
+#[derive(Debug)]
+pub struct ConsumerGroupDescribeResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    groups: Vec<DescribedConsumerGroupResponse>,
+}
+
+impl ConsumerGroupDescribeResponse {
+    pub fn process_request(request: &KafkaRequest, describe: &ConsumerGroupDescribeRequest, broker: &Broker) -> Self {
+        let authorized_operations = match describe.include_authorized_operations {
+            true => GROUP_AUTHORIZED_OPERATIONS,
+            false => AUTHORIZED_OPERATIONS_OMITTED,
+        };
+        let image = broker.metadata().image();
+        let groups = describe.group_ids.iter()
+            .map(|group_id| match broker.group_coordinator().describe_consumer_group(group_id) {
+                Ok(group) => DescribedConsumerGroupResponse::new(group_id, group, &image, authorized_operations),
+                Err(err) => DescribedConsumerGroupResponse::error(group_id, ErrorCode::from(&err), err.to_string()),
+            })
+            .collect();
+
+        ConsumerGroupDescribeResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            groups,
+        }
+    }
+}
+
+impl ApiResponse for ConsumerGroupDescribeResponse {}
+
+impl ToKafkaBytes for ConsumerGroupDescribeResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        self.groups.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct DescribedConsumerGroupResponse {
+    error_code: ErrorCode,
+    error_message: Option<String>,
+    group_id: String,
+    group_state: String,
+    group_epoch: i32,
+    assignment_epoch: i32,
+    assignor_name: String,
+    members: Vec<DescribedConsumerMemberResponse>,
+    authorized_operations: i32,
+}
+
+impl DescribedConsumerGroupResponse {
+    fn new(group_id: &str, group: DescribedConsumerGroup, image: &MetadataImage, authorized_operations: i32) -> Self {
+        DescribedConsumerGroupResponse {
+            error_code: ErrorCode::NoError,
+            error_message: None,
+            group_id: group_id.to_string(),
+            group_state: group.state.name().to_string(),
+            group_epoch: group.group_epoch,
+            assignment_epoch: group.assignment_epoch,
+            assignor_name: group.assignor_name,
+            members: group.members.into_iter().map(|member| DescribedConsumerMemberResponse::new(member, image)).collect(),
+            authorized_operations,
+        }
+    }
+
+    fn error(group_id: &str, error_code: ErrorCode, error_message: String) -> Self {
+        DescribedConsumerGroupResponse {
+            error_code,
+            error_message: Some(error_message),
+            group_id: group_id.to_string(),
+            group_state: String::new(),
+            group_epoch: -1,
+            assignment_epoch: -1,
+            assignor_name: String::new(),
+            members: Vec::new(),
+            authorized_operations: AUTHORIZED_OPERATIONS_OMITTED,
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for DescribedConsumerGroupResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.error_code.write_kafka_bytes(buf);
+        self.error_message.write_versioned_kafka_bytes(buf, version);
+        self.group_id.write_versioned_kafka_bytes(buf, version);
+        self.group_state.write_versioned_kafka_bytes(buf, version);
+        self.group_epoch.write_kafka_bytes(buf);
+        self.assignment_epoch.write_kafka_bytes(buf);
+        self.assignor_name.write_versioned_kafka_bytes(buf, version);
+        self.members.write_versioned_kafka_bytes(buf, version);
+        self.authorized_operations.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct DescribedConsumerMemberResponse {
+    member_id: String,
+    instance_id: Option<String>,
+    rack_id: Option<String>,
+    member_epoch: i32,
+    client_id: String,
+    client_host: String,
+    subscribed_topic_names: Vec<String>,
+    subscribed_topic_regex: Option<String>,
+    assignment: AssignmentResponse,
+    target_assignment: AssignmentResponse,
+}
+
+impl DescribedConsumerMemberResponse {
+    fn new(member: DescribedConsumerMember, image: &MetadataImage) -> Self {
+        DescribedConsumerMemberResponse {
+            member_id: member.member_id,
+            instance_id: member.group_instance_id,
+            rack_id: member.rack_id,
+            member_epoch: member.member_epoch,
+            client_id: member.client_id,
+            client_host: member.client_host,
+            subscribed_topic_names: member.subscribed_topic_names,
+            subscribed_topic_regex: member.subscribed_topic_regex,
+            assignment: AssignmentResponse::new(member.assignment, image),
+            target_assignment: AssignmentResponse::new(member.target_assignment, image),
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for DescribedConsumerMemberResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.member_id.write_versioned_kafka_bytes(buf, version);
+        self.instance_id.write_versioned_kafka_bytes(buf, version);
+        self.rack_id.write_versioned_kafka_bytes(buf, version);
+        self.member_epoch.write_kafka_bytes(buf);
+        self.client_id.write_versioned_kafka_bytes(buf, version);
+        self.client_host.write_versioned_kafka_bytes(buf, version);
+        self.subscribed_topic_names.write_versioned_kafka_bytes(buf, version);
+        self.subscribed_topic_regex.write_versioned_kafka_bytes(buf, version);
+        self.assignment.write_versioned_kafka_bytes(buf, version);
+        self.target_assignment.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct AssignmentResponse {
+    topic_partitions: Vec<TopicPartitionsResponse>,
+}
+
+impl AssignmentResponse {
+    /// The assignment with the names of its topics, where topics that have since been deleted have no name
+    fn new(assignment: Assignment, image: &MetadataImage) -> Self {
+        let topic_partitions = assignment.into_iter()
+            .map(|(topic_id, partitions)| TopicPartitionsResponse {
+                topic_id,
+                topic_name: image.topic_by_id(&topic_id).map(|topic| topic.name().to_string()).unwrap_or_default(),
+                partitions: partitions.into_iter().collect(),
+            })
+            .collect();
+        AssignmentResponse { topic_partitions }
+    }
+}
+
+impl ToVersionedKafkaBytes for AssignmentResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.topic_partitions.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct TopicPartitionsResponse {
+    topic_id: Uuid,
+    topic_name: String,
+    partitions: Vec<i32>,
+}
+
+impl ToVersionedKafkaBytes for TopicPartitionsResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.topic_id.write_kafka_bytes(buf);
+        self.topic_name.write_versioned_kafka_bytes(buf, version);
+        self.partitions.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
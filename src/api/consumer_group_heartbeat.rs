@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+use bytes::{Buf, BufMut};
+use uuid::Uuid;
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::coordinator::group::consumer_group::{Assignment, TopicInfo};
+use crate::coordinator::group::{ConsumerGroupHeartbeatParams, ConsumerGroupHeartbeatResult};
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+#[derive(Debug)]
+pub struct ConsumerGroupHeartbeatRequest {
+    group_id: String,
+    member_id: String,
+    member_epoch: i32,
+    instance_id: Option<String>,
+    rack_id: Option<String>,
+    /// -1 if it hasn't changed since the last heartbeat
+    rebalance_timeout_ms: i32,
+    /// Null if they haven't changed since the last heartbeat
+    subscribed_topic_names: Option<Vec<String>>,
+    subscribed_topic_regex: Option<String>,
+    server_assignor: Option<String>,
+    /// Null if they haven't changed since the last heartbeat
+    topic_partitions: Option<Vec<TopicPartitions>>,
+}
+
+impl ReadVersionedKafkaBytes for ConsumerGroupHeartbeatRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let group_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let member_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let member_epoch = i32::read_kafka_bytes(buf)?;
+        let instance_id = Option::<String>::read_versioned_kafka_bytes(buf, version)?;
+        let rack_id = Option::<String>::read_versioned_kafka_bytes(buf, version)?;
+        let rebalance_timeout_ms = i32::read_kafka_bytes(buf)?;
+        let subscribed_topic_names = Option::<Vec<String>>::read_versioned_kafka_bytes(buf, version)?;
+        let subscribed_topic_regex = match version.version() {
+            1.. => Option::<String>::read_versioned_kafka_bytes(buf, version)?,
+            _ => None,
+        };
+        let server_assignor = Option::<String>::read_versioned_kafka_bytes(buf, version)?;
+        let topic_partitions = Option::<Vec<TopicPartitions>>::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(ConsumerGroupHeartbeatRequest {
+            group_id,
+            member_id,
+            member_epoch,
+            instance_id,
+            rack_id,
+            rebalance_timeout_ms,
+            subscribed_topic_names,
+            subscribed_topic_regex,
+            server_assignor,
+            topic_partitions,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct TopicPartitions {
+    topic_id: Uuid,
+    partitions: Vec<i32>,
+}
+
+impl ReadVersionedKafkaBytes for TopicPartitions {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let topic_id = Uuid::read_kafka_bytes(buf)?;
+        let partitions = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(TopicPartitions { topic_id, partitions })
+    }
+}
+
+impl ToVersionedKafkaBytes for TopicPartitions {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.topic_id.write_kafka_bytes(buf);
+        self.partitions.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+pub struct ConsumerGroupHeartbeatResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    error_code: ErrorCode,
+    error_message: Option<String>,
+    member_id: Option<String>,
+    member_epoch: i32,
+    heartbeat_interval_ms: i32,
+    /// Null if the member's assignment hasn't changed
+    assignment: Option<Vec<TopicPartitions>>,
+}
+
+impl ConsumerGroupHeartbeatResponse {
+    pub fn process_request(request: &KafkaRequest, heartbeat: &ConsumerGroupHeartbeatRequest, broker: &Broker) -> Self {
+        let base_response = BaseKafkaResponse::new(request);
+        let version = request.message_version();
+        // from v1 members choose their own member id when they first join
+        if request.api_version() >= 1 && heartbeat.member_id.is_empty() {
+            return Self::error(base_response, version, ErrorCode::InvalidRequest, "MemberId can't be empty.".to_string());
+        }
+        let params = ConsumerGroupHeartbeatParams {
+            group_id: heartbeat.group_id.clone(),
+            member_id: heartbeat.member_id.clone(),
+            member_epoch: heartbeat.member_epoch,
+            group_instance_id: heartbeat.instance_id.clone(),
+            rack_id: heartbeat.rack_id.clone(),
+            rebalance_timeout_ms: heartbeat.rebalance_timeout_ms,
+            subscribed_topic_names: heartbeat.subscribed_topic_names.clone(),
+            subscribed_topic_regex: heartbeat.subscribed_topic_regex.clone(),
+            server_assignor: heartbeat.server_assignor.clone(),
+            owned_partitions: heartbeat.topic_partitions.as_ref().map(|topics| {
+                topics.iter()
+                    .map(|topic| (topic.topic_id, topic.partitions.iter().copied().collect()))
+                    .collect()
+            }),
+            client_id: request.client_id().as_str().unwrap_or_default().to_string(),
+            // formatted the way java formats addresses, which is how kafka describes the client's host
+            client_host: format!("/{}", request.client_address()),
+        };
+        let topics: BTreeMap<String, TopicInfo> = broker.metadata().image()
+            .topics()
+            .map(|topic| {
+                let info = TopicInfo { topic_id: topic.topic_id(), num_partitions: topic.num_partitions() as i32 };
+                (topic.name().to_string(), info)
+            })
+            .collect();
+
+        match broker.group_coordinator().consumer_group_heartbeat(params, &topics) {
+            Ok(result) => Self::heartbeat(base_response, version, result),
+            Err(err) => Self::error(base_response, version, ErrorCode::from(&err), err.to_string()),
+        }
+    }
+
+    fn heartbeat(base_response: BaseKafkaResponse, version: MessageVersion, result: ConsumerGroupHeartbeatResult) -> Self {
+        ConsumerGroupHeartbeatResponse {
+            base_response,
+            version,
+            throttle_time_ms: 0,
+            error_code: ErrorCode::NoError,
+            error_message: None,
+            member_id: Some(result.member_id),
+            member_epoch: result.member_epoch,
+            heartbeat_interval_ms: result.heartbeat_interval_ms,
+            assignment: result.assignment.map(topic_partitions),
+        }
+    }
+
+    fn error(base_response: BaseKafkaResponse, version: MessageVersion, error_code: ErrorCode, error_message: String) -> Self {
+        ConsumerGroupHeartbeatResponse {
+            base_response,
+            version,
+            throttle_time_ms: 0,
+            error_code,
+            error_message: Some(error_message),
+            member_id: None,
+            member_epoch: 0,
+            heartbeat_interval_ms: 0,
+            assignment: None,
+        }
+    }
+}
+
+fn topic_partitions(assignment: Assignment) -> Vec<TopicPartitions> {
+    assignment.into_iter()
+        .map(|(topic_id, partitions)| TopicPartitions { topic_id, partitions: partitions.into_iter().collect() })
+        .collect()
+}
+
+impl ApiResponse for ConsumerGroupHeartbeatResponse {
+    fn error_code(&self) -> Option<ErrorCode> {
+        Some(self.error_code)
+    }
+}
+
+impl ToKafkaBytes for ConsumerGroupHeartbeatResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        self.error_message.write_versioned_kafka_bytes(buf, version);
+        self.member_id.write_versioned_kafka_bytes(buf, version);
+        self.member_epoch.write_kafka_bytes(buf);
+        self.heartbeat_interval_ms.write_kafka_bytes(buf);
+        // the assignment is a nullable struct, which is prefixed with -1 when it's null and 1 when it's not
+        match self.assignment {
+            Some(topic_partitions) => {
+                1i8.write_kafka_bytes(buf);
+                topic_partitions.write_versioned_kafka_bytes(buf, version);
+                write_empty_tagged_fields(buf, version);
+            }
+            None => (-1i8).write_kafka_bytes(buf),
+        }
+        write_empty_tagged_fields(buf, version);
+    }
+}
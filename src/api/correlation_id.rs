@@ -1,4 +1,4 @@
-use tokio::io::AsyncRead;
+use bytes::{Buf, BufMut};
 use crate::api::request::KafkaRequestParseError;
 use crate::serialisation::{ReadKafkaBytes, ToKafkaBytes};
 
@@ -11,16 +11,21 @@ impl From<i32> for CorrelationId {
     }
 }
 
+impl From<CorrelationId> for i32 {
+    fn from(value: CorrelationId) -> Self {
+        value.0
+    }
+}
+
 impl ReadKafkaBytes for CorrelationId {
-    async fn read_kafka_bytes<T: AsyncRead + Unpin>(reader: &mut T) -> Result<Self, KafkaRequestParseError> {
-        i32::read_kafka_bytes(reader)
-            .await
+    fn read_kafka_bytes<B: Buf>(buf: &mut B) -> Result<Self, KafkaRequestParseError> {
+        i32::read_kafka_bytes(buf)
             .map(|int| int.into())
     }
 }
 
 impl ToKafkaBytes for CorrelationId {
-    fn to_kafka_bytes(self) -> impl IntoIterator<Item=u8> {
-        self.0.to_kafka_bytes()
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        self.0.write_kafka_bytes(buf)
     }
 }
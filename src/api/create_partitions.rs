@@ -0,0 +1,192 @@
+use std::collections::HashSet;
+use std::time::Instant;
+use bytes::{Buf, BufMut};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+use crate::time::deadline_after;
+
+#[derive(Debug)]
+pub struct CreatePartitionsRequest {
+    topics: Vec<CreatePartitionsTopic>,
+    timeout_ms: i32,
+    validate_only: bool,
+}
+
+impl ReadVersionedKafkaBytes for CreatePartitionsRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let request = CreatePartitionsRequest {
+            topics: Vec::read_versioned_kafka_bytes(buf, version)?,
+            timeout_ms: i32::read_kafka_bytes(buf)?,
+            validate_only: bool::read_kafka_bytes(buf)?,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(request)
+    }
+}
+
+#[derive(Debug)]
+struct CreatePartitionsTopic {
+    name: String,
+    count: i32,
+    /// The replicas of each new partition, or null to assign them automatically
+    assignments: Option<Vec<CreatePartitionsAssignment>>,
+}
+
+impl ReadVersionedKafkaBytes for CreatePartitionsTopic {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let topic = CreatePartitionsTopic {
+            name: String::read_versioned_kafka_bytes(buf, version)?,
+            count: i32::read_kafka_bytes(buf)?,
+            assignments: Option::<Vec<_>>::read_versioned_kafka_bytes(buf, version)?,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(topic)
+    }
+}
+
+#[derive(Debug)]
+struct CreatePartitionsAssignment {
+    broker_ids: Vec<i32>,
+}
+
+impl ReadVersionedKafkaBytes for CreatePartitionsAssignment {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let broker_ids = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(CreatePartitionsAssignment { broker_ids })
+    }
+}
+
+#[derive(Debug)]
+pub struct CreatePartitionsResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    results: Vec<CreatePartitionsTopicResult>,
+}
+
+impl CreatePartitionsResponse {
+    pub fn process_request(request: &KafkaRequest, create_partitions: &CreatePartitionsRequest, broker: &Broker) -> Self {
+        let deadline = deadline_after(create_partitions.timeout_ms);
+        let mut seen = HashSet::new();
+        let duplicates: HashSet<&str> = create_partitions.topics.iter()
+            .map(|topic| topic.name.as_str())
+            .filter(|name| !seen.insert(*name))
+            .collect();
+
+        let results = create_partitions.topics.iter()
+            .map(|topic| {
+                if duplicates.contains(topic.name.as_str()) {
+                    return CreatePartitionsTopicResult::error(&topic.name, ErrorCode::InvalidRequest, "Duplicate topic name.".to_string());
+                }
+                if Instant::now() >= deadline {
+                    let message = "Timed out before the partitions could be created.".to_string();
+                    return CreatePartitionsTopicResult::error(&topic.name, ErrorCode::RequestTimedOut, message);
+                }
+                let assignments = topic.assignments.as_ref()
+                    .map(|assignments| assignments.iter().map(|assignment| assignment.broker_ids.clone()).collect::<Vec<_>>());
+                match broker.create_partitions(&topic.name, topic.count, assignments.as_deref(), create_partitions.validate_only) {
+                    Ok(()) => CreatePartitionsTopicResult {
+                        name: topic.name.clone(),
+                        error_code: ErrorCode::NoError,
+                        error_message: None,
+                    },
+                    Err(err) => CreatePartitionsTopicResult::error(&topic.name, ErrorCode::from(&err), err.to_string()),
+                }
+            })
+            .collect();
+
+        CreatePartitionsResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            results,
+        }
+    }
+}
+
+impl ApiResponse for CreatePartitionsResponse {}
+
+impl ToKafkaBytes for CreatePartitionsResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        self.results.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct CreatePartitionsTopicResult {
+    name: String,
+    error_code: ErrorCode,
+    error_message: Option<String>,
+}
+
+impl CreatePartitionsTopicResult {
+    fn error(name: &str, error_code: ErrorCode, message: String) -> Self {
+        CreatePartitionsTopicResult {
+            name: name.to_string(),
+            error_code,
+            error_message: Some(message),
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for CreatePartitionsTopicResult {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.name.write_versioned_kafka_bytes(buf, version);
+        self.error_code.write_kafka_bytes(buf);
+        self.error_message.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::api_key::ApiKey;
+    use crate::api::request::ApiRequest;
+    use crate::testing::{open_broker, parse_request};
+
+    /// A v0 request growing each topic to its count, assigning the new partitions automatically
+    fn create_partitions(broker: &Broker, topics: &[(&str, i32)], validate_only: bool) -> CreatePartitionsResponse {
+        let mut body = (topics.len() as i32).to_be_bytes().to_vec();
+        for (name, count) in topics {
+            body.extend((name.len() as i16).to_be_bytes());
+            body.extend(name.as_bytes());
+            body.extend(count.to_be_bytes());
+            body.extend((-1i32).to_be_bytes());
+        }
+        body.extend(30_000i32.to_be_bytes());
+        body.push(validate_only as u8);
+        let request = parse_request(ApiKey::CreatePartitions, 0, &body);
+        let ApiRequest::CreatePartitions(create_partitions) = request.api_request() else {
+            panic!("expected a CreatePartitions request, got {:?}", request.api_request());
+        };
+        CreatePartitionsResponse::process_request(&request, create_partitions, broker)
+    }
+
+    #[test]
+    fn test_create_partitions() {
+        let (broker, _log_dir) = open_broker("");
+        broker.auto_create_topic("events").unwrap();
+        broker.auto_create_topic("clicks").unwrap();
+
+        let response = create_partitions(&broker, &[("events", 2), ("clicks", 2), ("events", 3)], false);
+        let results: Vec<_> = response.results.iter().map(|result| (result.name.as_str(), result.error_code)).collect();
+        assert_eq!(results, vec![("events", ErrorCode::InvalidRequest), ("clicks", ErrorCode::NoError), ("events", ErrorCode::InvalidRequest)]);
+
+        let response = create_partitions(&broker, &[("events", 4), ("clicks", 1)], true);
+        let results: Vec<_> = response.results.iter().map(|result| (result.name.as_str(), result.error_code)).collect();
+        assert_eq!(results, vec![("events", ErrorCode::NoError), ("clicks", ErrorCode::InvalidPartitions)]);
+        let image = broker.metadata().image();
+        assert_eq!(image.topic("events").unwrap().num_partitions(), 1);
+        assert_eq!(image.topic("clicks").unwrap().num_partitions(), 2);
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::HashSet;
+use std::time::Instant;
+use bytes::{Buf, BufMut};
+use uuid::Uuid;
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::broker::configs::{ConfigSource, DescribedConfig};
+use crate::broker::topics::NewTopic;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+use crate::time::deadline_after;
+
+#[derive(Debug)]
+pub struct CreateTopicsRequest {
+    topics: Vec<CreatableTopic>,
+    timeout_ms: i32,
+    validate_only: bool,
+}
+
+impl ReadVersionedKafkaBytes for CreateTopicsRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let topics = Vec::read_versioned_kafka_bytes(buf, version)?;
+        let timeout_ms = i32::read_kafka_bytes(buf)?;
+        let validate_only = match version.version() {
+            1.. => bool::read_kafka_bytes(buf)?,
+            _ => false,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(CreateTopicsRequest { topics, timeout_ms, validate_only })
+    }
+}
+
+#[derive(Debug)]
+struct CreatableTopic {
+    name: String,
+    num_partitions: i32,
+    replication_factor: i16,
+    assignments: Vec<CreatableReplicaAssignment>,
+    configs: Vec<CreatableTopicConfig>,
+}
+
+impl ReadVersionedKafkaBytes for CreatableTopic {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let topic = CreatableTopic {
+            name: String::read_versioned_kafka_bytes(buf, version)?,
+            num_partitions: i32::read_kafka_bytes(buf)?,
+            replication_factor: i16::read_kafka_bytes(buf)?,
+            assignments: Vec::read_versioned_kafka_bytes(buf, version)?,
+            configs: Vec::read_versioned_kafka_bytes(buf, version)?,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(topic)
+    }
+}
+
+impl CreatableTopic {
+    fn to_new_topic(&self) -> NewTopic {
+        NewTopic {
+            name: self.name.clone(),
+            num_partitions: self.num_partitions,
+            replication_factor: self.replication_factor,
+            assignments: self.assignments.iter()
+                .map(|assignment| (assignment.partition_index, assignment.broker_ids.clone()))
+                .collect(),
+            configs: self.configs.iter()
+                .map(|config| (config.name.clone(), config.value.clone()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CreatableReplicaAssignment {
+    partition_index: i32,
+    broker_ids: Vec<i32>,
+}
+
+impl ReadVersionedKafkaBytes for CreatableReplicaAssignment {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let partition_index = i32::read_kafka_bytes(buf)?;
+        let broker_ids = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(CreatableReplicaAssignment { partition_index, broker_ids })
+    }
+}
+
+#[derive(Debug)]
+struct CreatableTopicConfig {
+    name: String,
+    value: Option<String>,
+}
+
+impl ReadVersionedKafkaBytes for CreatableTopicConfig {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let name = String::read_versioned_kafka_bytes(buf, version)?;
+        let value = Option::<String>::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(CreatableTopicConfig { name, value })
+    }
+}
+
+#[derive(Debug)]
+pub struct CreateTopicsResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    topics: Vec<CreatableTopicResult>,
+}
+
+impl CreateTopicsResponse {
+    pub fn process_request(request: &KafkaRequest, create_topics: &CreateTopicsRequest, broker: &Broker) -> Self {
+        let deadline = deadline_after(create_topics.timeout_ms);
+        let mut seen = HashSet::new();
+        let duplicates: HashSet<&str> = create_topics.topics.iter()
+            .map(|topic| topic.name.as_str())
+            .filter(|name| !seen.insert(*name))
+            .collect();
+
+        let topics = create_topics.topics.iter()
+            .map(|topic| {
+                if duplicates.contains(topic.name.as_str()) {
+                    let message = format!("Create topics request from client `{}` contains multiple entries for the following topics: {}",
+                                          request.client_id().as_str().unwrap_or_default(), topic.name);
+                    return CreatableTopicResult::error(&topic.name, ErrorCode::InvalidRequest, message);
+                }
+                if Instant::now() >= deadline {
+                    let message = "Timed out before the topic could be created.".to_string();
+                    return CreatableTopicResult::error(&topic.name, ErrorCode::RequestTimedOut, message);
+                }
+                match broker.create_topic(&topic.to_new_topic(), create_topics.validate_only) {
+                    Ok(created) => CreatableTopicResult {
+                        name: topic.name.clone(),
+                        topic_id: created.topic_id,
+                        error_code: ErrorCode::NoError,
+                        error_message: None,
+                        num_partitions: created.num_partitions,
+                        replication_factor: created.replication_factor,
+                        configs: Some(broker.describe_topic_configs(&created.configs)
+                            .into_iter()
+                            .map(CreatableTopicConfigs::from)
+                            .collect()),
+                    },
+                    Err(err) => CreatableTopicResult::error(&topic.name, ErrorCode::from(&err), err.to_string()),
+                }
+            })
+            .collect();
+
+        CreateTopicsResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            topics,
+        }
+    }
+}
+
+impl ApiResponse for CreateTopicsResponse {}
+
+impl ToKafkaBytes for CreateTopicsResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        if version.version() >= 2 {
+            self.throttle_time_ms.write_kafka_bytes(buf);
+        }
+        self.topics.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct CreatableTopicResult {
+    name: String,
+    topic_id: Uuid,
+    error_code: ErrorCode,
+    error_message: Option<String>,
+    num_partitions: i32,
+    replication_factor: i16,
+    /// Null when the topic wasn't created
+    configs: Option<Vec<CreatableTopicConfigs>>,
+}
+
+impl CreatableTopicResult {
+    fn error(name: &str, error_code: ErrorCode, message: String) -> Self {
+        CreatableTopicResult {
+            name: name.to_string(),
+            topic_id: Uuid::nil(),
+            error_code,
+            error_message: Some(message),
+            num_partitions: -1,
+            replication_factor: -1,
+            configs: None,
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for CreatableTopicResult {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.name.write_versioned_kafka_bytes(buf, version);
+        if version.version() >= 7 {
+            self.topic_id.write_kafka_bytes(buf);
+        }
+        self.error_code.write_kafka_bytes(buf);
+        if version.version() >= 1 {
+            self.error_message.write_versioned_kafka_bytes(buf, version);
+        }
+        if version.version() >= 5 {
+            self.num_partitions.write_kafka_bytes(buf);
+            self.replication_factor.write_kafka_bytes(buf);
+            self.configs.write_versioned_kafka_bytes(buf, version);
+        }
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct CreatableTopicConfigs {
+    name: String,
+    value: Option<String>,
+    read_only: bool,
+    config_source: ConfigSource,
+    is_sensitive: bool,
+}
+
+impl From<DescribedConfig> for CreatableTopicConfigs {
+    fn from(config: DescribedConfig) -> Self {
+        CreatableTopicConfigs {
+            name: config.name,
+            value: config.value,
+            read_only: config.read_only,
+            config_source: config.source,
+            is_sensitive: config.is_sensitive,
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for CreatableTopicConfigs {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.name.write_versioned_kafka_bytes(buf, version);
+        self.value.write_versioned_kafka_bytes(buf, version);
+        self.read_only.write_kafka_bytes(buf);
+        self.config_source.write_kafka_bytes(buf);
+        self.is_sensitive.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::api_key::ApiKey;
+    use crate::storage::topic_partition::TopicPartition;
+    use crate::testing::{open_broker, parse_request};
+
+    fn topic(name: &str, num_partitions: i32, replication_factor: i16) -> CreatableTopic {
+        CreatableTopic { name: name.to_string(), num_partitions, replication_factor, assignments: Vec::new(), configs: Vec::new() }
+    }
+
+    fn create_topics(broker: &Broker, topics: Vec<CreatableTopic>, validate_only: bool) -> Vec<CreatableTopicResult> {
+        // the header comes from a request without any topics, since only the version and client id are used from it
+        let body = [0i32.to_be_bytes().as_slice(), 30_000i32.to_be_bytes().as_slice(), &[0]].concat();
+        let request = parse_request(ApiKey::CreateTopics, 4, &body);
+        let create_topics = CreateTopicsRequest { topics, timeout_ms: 30_000, validate_only };
+        CreateTopicsResponse::process_request(&request, &create_topics, broker).topics
+    }
+
+    fn error_codes(results: &[CreatableTopicResult]) -> Vec<ErrorCode> {
+        results.iter().map(|result| result.error_code).collect()
+    }
+
+    #[test]
+    fn test_create_topic() {
+        let (broker, _log_dir) = open_broker("");
+        let mut events = topic("events", 2, -1);
+        events.configs.push(CreatableTopicConfig { name: "retention.ms".to_string(), value: Some("1000".to_string()) });
+        let [result] = create_topics(&broker, vec![events], false).try_into().unwrap();
+        assert_eq!(result.error_code, ErrorCode::NoError);
+        assert_eq!((result.num_partitions, result.replication_factor), (2, 1));
+
+        let image = broker.metadata().image();
+        let created = image.topic("events").unwrap();
+        assert_eq!(result.topic_id, created.topic_id());
+        assert_eq!(created.configs().get("retention.ms").map(String::as_str), Some("1000"));
+        let configs = result.configs.unwrap();
+        let retention = configs.iter().find(|config| config.name == "retention.ms").unwrap();
+        assert_eq!(retention.value.as_deref(), Some("1000"));
+        assert!(matches!(retention.config_source, ConfigSource::TopicConfig));
+        let cleanup_policy = configs.iter().find(|config| config.name == "cleanup.policy").unwrap();
+        assert_eq!(cleanup_policy.value.as_deref(), Some("delete"));
+        assert!(matches!(cleanup_policy.config_source, ConfigSource::DefaultConfig));
+        for partition in 0..2 {
+            assert!(broker.log_manager().get_log(&TopicPartition::new("events", partition)).unwrap().is_some());
+        }
+    }
+
+    #[test]
+    fn test_validate_only() {
+        let (broker, _log_dir) = open_broker("");
+        let [result] = create_topics(&broker, vec![topic("events", 3, 1)], true).try_into().unwrap();
+        assert_eq!(result.error_code, ErrorCode::NoError);
+        assert_eq!(result.num_partitions, 3);
+        assert!(broker.metadata().image().topic("events").is_none());
+        assert!(!broker.config().log_dir().join("events-0").exists());
+
+        // nothing was created, so the topic can still be created for real
+        let results = create_topics(&broker, vec![topic("events", 3, 1)], false);
+        assert_eq!(error_codes(&results), vec![ErrorCode::NoError]);
+    }
+
+    #[test]
+    fn test_topic_already_exists() {
+        let (broker, _log_dir) = open_broker("");
+        create_topics(&broker, vec![topic("events", 1, 1), topic("my.topic", 1, 1)], false);
+        let results = create_topics(&broker, vec![topic("events", 1, 1), topic("my_topic", 1, 1), topic("clicks", 1, 1), topic("clicks", 1, 1)], false);
+        assert_eq!(error_codes(&results), vec![
+            ErrorCode::TopicAlreadyExists,
+            // '.' and '_' collide in metric names
+            ErrorCode::InvalidTopicException,
+            ErrorCode::InvalidRequest,
+            ErrorCode::InvalidRequest,
+        ]);
+        assert!(results.iter().all(|result| result.configs.is_none() && result.topic_id.is_nil()));
+        assert!(broker.metadata().image().topic("my_topic").is_none());
+        assert!(broker.metadata().image().topic("clicks").is_none());
+    }
+
+    #[test]
+    fn test_invalid_partitions_and_replication_factor() {
+        let (broker, _log_dir) = open_broker("");
+        let results = create_topics(&broker, vec![topic("a", 0, 1), topic("b", -2, 1), topic("c", 1, 0), topic("d", 1, 2)], false);
+        assert_eq!(error_codes(&results), vec![
+            ErrorCode::InvalidPartitions,
+            ErrorCode::InvalidPartitions,
+            ErrorCode::InvalidReplicationFactor,
+            ErrorCode::InvalidReplicationFactor,
+        ]);
+        assert_eq!(broker.metadata().image().topics().filter(|topic| ["a", "b", "c", "d"].contains(&topic.name())).count(), 0);
+    }
+}
@@ -0,0 +1,82 @@
+use bytes::{Buf, BufMut};
+use tracing::error;
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::coordinator::group::GroupError;
+use crate::serialisation::{MessageVersion, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+#[derive(Debug)]
+pub struct DeleteGroupsRequest {
+    groups_names: Vec<String>,
+}
+
+impl ReadVersionedKafkaBytes for DeleteGroupsRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let groups_names = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(DeleteGroupsRequest { groups_names })
+    }
+}
+
+#[derive(Debug)]
+pub struct DeleteGroupsResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    results: Vec<DeletableGroupResult>,
+}
+
+impl DeleteGroupsResponse {
+    pub fn process_request(request: &KafkaRequest, delete_groups: &DeleteGroupsRequest, broker: &Broker) -> Self {
+        let results = broker.group_coordinator()
+            .delete_groups(&delete_groups.groups_names)
+            .into_iter()
+            .zip(&delete_groups.groups_names)
+            .map(|(result, group_id)| {
+                if let Err(GroupError::Storage(err)) = &result {
+                    error!(%group_id, %err, "Failed to delete group");
+                }
+                DeletableGroupResult {
+                    group_id: group_id.clone(),
+                    error_code: result.as_ref().err().map_or(ErrorCode::NoError, ErrorCode::from),
+                }
+            })
+            .collect();
+
+        DeleteGroupsResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            results,
+        }
+    }
+}
+
+impl ApiResponse for DeleteGroupsResponse {}
+
+impl ToKafkaBytes for DeleteGroupsResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        self.results.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct DeletableGroupResult {
+    group_id: String,
+    error_code: ErrorCode,
+}
+
+impl ToVersionedKafkaBytes for DeletableGroupResult {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.group_id.write_versioned_kafka_bytes(buf, version);
+        self.error_code.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, version);
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashSet;
+use std::time::Instant;
+use bytes::{Buf, BufMut};
+use uuid::Uuid;
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::broker::topics::TopicRef;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+use crate::time::deadline_after;
+
+#[derive(Debug)]
+pub struct DeleteTopicsRequest {
+    /// Before v6 topics can only be deleted by name, so their ids are all nil
+    topics: Vec<DeleteTopicState>,
+    timeout_ms: i32,
+}
+
+impl ReadVersionedKafkaBytes for DeleteTopicsRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let topics = match version.version() {
+            6.. => Vec::read_versioned_kafka_bytes(buf, version)?,
+            _ => Vec::<String>::read_versioned_kafka_bytes(buf, version)?
+                .into_iter()
+                .map(|name| DeleteTopicState { name: Some(name), topic_id: Uuid::nil() })
+                .collect(),
+        };
+        let timeout_ms = i32::read_kafka_bytes(buf)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(DeleteTopicsRequest { topics, timeout_ms })
+    }
+}
+
+#[derive(Debug)]
+struct DeleteTopicState {
+    name: Option<String>,
+    topic_id: Uuid,
+}
+
+impl ReadVersionedKafkaBytes for DeleteTopicState {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let name = Option::<String>::read_versioned_kafka_bytes(buf, version)?;
+        let topic_id = Uuid::read_kafka_bytes(buf)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(DeleteTopicState { name, topic_id })
+    }
+}
+
+impl DeleteTopicState {
+    fn topic_ref(&self) -> Result<TopicRef<'_>, &'static str> {
+        match (&self.name, self.topic_id.is_nil()) {
+            (Some(_), false) => Err("You may not specify both topic name and topic id."),
+            (Some(name), true) => Ok(TopicRef::Name(name)),
+            (None, false) => Ok(TopicRef::Id(self.topic_id)),
+            (None, true) => Err("Neither topic name nor id were specified."),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DeleteTopicsResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    responses: Vec<DeletableTopicResult>,
+}
+
+impl DeleteTopicsResponse {
+    pub fn process_request(request: &KafkaRequest, delete_topics: &DeleteTopicsRequest, broker: &Broker) -> Self {
+        let deadline = deadline_after(delete_topics.timeout_ms);
+        let mut seen_names = HashSet::new();
+        let duplicate_names: HashSet<&str> = delete_topics.topics.iter()
+            .filter_map(|topic| topic.name.as_deref())
+            .filter(|name| !seen_names.insert(*name))
+            .collect();
+        let mut seen_ids = HashSet::new();
+        let duplicate_ids: HashSet<Uuid> = delete_topics.topics.iter()
+            .map(|topic| topic.topic_id)
+            .filter(|topic_id| !topic_id.is_nil() && !seen_ids.insert(*topic_id))
+            .collect();
+
+        let responses = delete_topics.topics.iter()
+            .map(|topic| {
+                let error = |error_code, message: &str| DeletableTopicResult {
+                    name: topic.name.clone(),
+                    topic_id: topic.topic_id,
+                    error_code,
+                    error_message: Some(message.to_string()),
+                };
+                let topic_ref = match topic.topic_ref() {
+                    Ok(topic_ref) => topic_ref,
+                    Err(message) => return error(ErrorCode::InvalidRequest, message),
+                };
+                if topic.name.as_deref().is_some_and(|name| duplicate_names.contains(name)) {
+                    return error(ErrorCode::InvalidRequest, "Duplicate topic name.");
+                }
+                if duplicate_ids.contains(&topic.topic_id) {
+                    return error(ErrorCode::InvalidRequest, "Duplicate topic id.");
+                }
+                if Instant::now() >= deadline {
+                    return error(ErrorCode::RequestTimedOut, "Timed out before the topic could be deleted.");
+                }
+                match broker.delete_topic(topic_ref) {
+                    Ok(deleted) => DeletableTopicResult {
+                        name: Some(deleted.name),
+                        topic_id: deleted.topic_id,
+                        error_code: ErrorCode::NoError,
+                        error_message: None,
+                    },
+                    Err(err) => error(ErrorCode::from(&err), &err.to_string()),
+                }
+            })
+            .collect();
+
+        DeleteTopicsResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            responses,
+        }
+    }
+}
+
+impl ApiResponse for DeleteTopicsResponse {}
+
+impl ToKafkaBytes for DeleteTopicsResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        if version.version() >= 1 {
+            self.throttle_time_ms.write_kafka_bytes(buf);
+        }
+        self.responses.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct DeletableTopicResult {
+    /// Only null from v6, when a topic that doesn't exist was deleted by id
+    name: Option<String>,
+    topic_id: Uuid,
+    error_code: ErrorCode,
+    error_message: Option<String>,
+}
+
+impl ToVersionedKafkaBytes for DeletableTopicResult {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.name.write_versioned_kafka_bytes(buf, version);
+        if version.version() >= 6 {
+            self.topic_id.write_kafka_bytes(buf);
+        }
+        self.error_code.write_kafka_bytes(buf);
+        if version.version() >= 5 {
+            self.error_message.write_versioned_kafka_bytes(buf, version);
+        }
+        write_empty_tagged_fields(buf, version);
+    }
+}
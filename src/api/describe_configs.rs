@@ -0,0 +1,204 @@
+use bytes::{Buf, BufMut};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::config_resource::config_resource;
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::broker::config_def::ConfigType;
+use crate::broker::configs::{ConfigSource, ConfigSynonym, DescribedConfig};
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+#[derive(Debug)]
+pub struct DescribeConfigsRequest {
+    resources: Vec<DescribeConfigsResource>,
+    include_synonyms: bool,
+    include_documentation: bool,
+}
+
+impl ReadVersionedKafkaBytes for DescribeConfigsRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let resources = Vec::read_versioned_kafka_bytes(buf, version)?;
+        let include_synonyms = match version.version() {
+            1.. => bool::read_kafka_bytes(buf)?,
+            _ => false,
+        };
+        let include_documentation = match version.version() {
+            3.. => bool::read_kafka_bytes(buf)?,
+            _ => false,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(DescribeConfigsRequest { resources, include_synonyms, include_documentation })
+    }
+}
+
+#[derive(Debug)]
+struct DescribeConfigsResource {
+    resource_type: i8,
+    resource_name: String,
+    /// The configs to describe, or None for all of them
+    configuration_keys: Option<Vec<String>>,
+}
+
+impl ReadVersionedKafkaBytes for DescribeConfigsResource {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let resource = DescribeConfigsResource {
+            resource_type: i8::read_kafka_bytes(buf)?,
+            resource_name: String::read_versioned_kafka_bytes(buf, version)?,
+            configuration_keys: Option::<Vec<_>>::read_versioned_kafka_bytes(buf, version)?,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(resource)
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeConfigsResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    results: Vec<DescribeConfigsResult>,
+}
+
+impl DescribeConfigsResponse {
+    pub fn process_request(request: &KafkaRequest, describe_configs: &DescribeConfigsRequest, broker: &Broker) -> Self {
+        let results = describe_configs.resources.iter()
+            .map(|resource| {
+                let described = config_resource(resource.resource_type, &resource.resource_name)
+                    .map_err(|message| (ErrorCode::InvalidRequest, message))
+                    .and_then(|config_resource| broker.describe_configs(config_resource)
+                        .map_err(|err| (ErrorCode::from(&err), err.to_string())));
+                let (error_code, error_message, configs) = match described {
+                    Ok(configs) => (ErrorCode::NoError, None, configs),
+                    Err((error_code, message)) => (error_code, Some(message), Vec::new()),
+                };
+                let configs = configs.into_iter()
+                    .filter(|config| resource.configuration_keys.as_ref()
+                        .map_or(true, |keys| keys.contains(&config.name)))
+                    .map(|config| DescribeConfigsResourceResult::new(config, describe_configs))
+                    .collect();
+                DescribeConfigsResult {
+                    error_code,
+                    error_message,
+                    resource_type: resource.resource_type,
+                    resource_name: resource.resource_name.clone(),
+                    configs,
+                }
+            })
+            .collect();
+
+        DescribeConfigsResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            results,
+        }
+    }
+}
+
+impl ApiResponse for DescribeConfigsResponse {}
+
+impl ToKafkaBytes for DescribeConfigsResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        self.results.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct DescribeConfigsResult {
+    error_code: ErrorCode,
+    error_message: Option<String>,
+    resource_type: i8,
+    resource_name: String,
+    configs: Vec<DescribeConfigsResourceResult>,
+}
+
+impl ToVersionedKafkaBytes for DescribeConfigsResult {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.error_code.write_kafka_bytes(buf);
+        self.error_message.write_versioned_kafka_bytes(buf, version);
+        self.resource_type.write_kafka_bytes(buf);
+        self.resource_name.write_versioned_kafka_bytes(buf, version);
+        self.configs.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct DescribeConfigsResourceResult {
+    name: String,
+    value: Option<String>,
+    read_only: bool,
+    config_source: ConfigSource,
+    is_sensitive: bool,
+    synonyms: Vec<DescribeConfigsSynonym>,
+    config_type: ConfigType,
+    documentation: Option<String>,
+}
+
+impl DescribeConfigsResourceResult {
+    fn new(config: DescribedConfig, request: &DescribeConfigsRequest) -> Self {
+        let synonyms = match request.include_synonyms {
+            true => config.synonyms.into_iter().map(DescribeConfigsSynonym::from).collect(),
+            false => Vec::new(),
+        };
+        DescribeConfigsResourceResult {
+            name: config.name,
+            value: config.value,
+            read_only: config.read_only,
+            config_source: config.source,
+            is_sensitive: config.is_sensitive,
+            synonyms,
+            config_type: config.config_type,
+            documentation: Some(config.documentation.to_string()).filter(|_| request.include_documentation),
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for DescribeConfigsResourceResult {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.name.write_versioned_kafka_bytes(buf, version);
+        self.value.write_versioned_kafka_bytes(buf, version);
+        self.read_only.write_kafka_bytes(buf);
+        match version.version() {
+            // v0 can only tell whether the config is using its default
+            0 => (self.config_source == ConfigSource::DefaultConfig).write_kafka_bytes(buf),
+            _ => self.config_source.write_kafka_bytes(buf),
+        }
+        self.is_sensitive.write_kafka_bytes(buf);
+        if version.version() >= 1 {
+            self.synonyms.write_versioned_kafka_bytes(buf, version);
+        }
+        if version.version() >= 3 {
+            self.config_type.write_kafka_bytes(buf);
+            self.documentation.write_versioned_kafka_bytes(buf, version);
+        }
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct DescribeConfigsSynonym {
+    name: String,
+    value: Option<String>,
+    source: ConfigSource,
+}
+
+impl From<ConfigSynonym> for DescribeConfigsSynonym {
+    fn from(synonym: ConfigSynonym) -> Self {
+        DescribeConfigsSynonym { name: synonym.name, value: synonym.value, source: synonym.source }
+    }
+}
+
+impl ToVersionedKafkaBytes for DescribeConfigsSynonym {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.name.write_versioned_kafka_bytes(buf, version);
+        self.value.write_versioned_kafka_bytes(buf, version);
+        self.source.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, version);
+    }
+}
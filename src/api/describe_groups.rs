@@ -0,0 +1,153 @@
+use bytes::{Buf, BufMut};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::metadata::AUTHORIZED_OPERATIONS_OMITTED;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::coordinator::group::{DescribedGroup, DescribedMember};
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+/// There's no authorizer, so every operation on groups is allowed: READ, DELETE and DESCRIBE
+pub const GROUP_AUTHORIZED_OPERATIONS: i32 = 1 << 3 | 1 << 6 | 1 << 8;
+
+#[derive(Debug)]
+pub struct DescribeGroupsRequest {
+    groups: Vec<String>,
+    include_authorized_operations: bool,
+}
+
+impl ReadVersionedKafkaBytes for DescribeGroupsRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let groups = Vec::read_versioned_kafka_bytes(buf, version)?;
+        let include_authorized_operations = match version.version() {
+            3.. => bool::read_kafka_bytes(buf)?,
+            _ => false,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(DescribeGroupsRequest { groups, include_authorized_operations })
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeGroupsResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    groups: Vec<DescribedGroupResponse>,
+}
+
+impl DescribeGroupsResponse {
+    pub fn process_request(request: &KafkaRequest, describe_groups: &DescribeGroupsRequest, broker: &Broker) -> Self {
+        let authorized_operations = match describe_groups.include_authorized_operations {
+            true => GROUP_AUTHORIZED_OPERATIONS,
+            false => AUTHORIZED_OPERATIONS_OMITTED,
+        };
+        let groups = describe_groups.groups.iter()
+            .map(|group_id| {
+                let group = broker.group_coordinator().describe_group(group_id);
+                DescribedGroupResponse::new(group_id, group, authorized_operations)
+            })
+            .collect();
+
+        DescribeGroupsResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            groups,
+        }
+    }
+}
+
+impl ApiResponse for DescribeGroupsResponse {}
+
+impl ToKafkaBytes for DescribeGroupsResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        if version.version() >= 1 {
+            self.throttle_time_ms.write_kafka_bytes(buf);
+        }
+        self.groups.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct DescribedGroupResponse {
+    error_code: ErrorCode,
+    group_id: String,
+    group_state: String,
+    protocol_type: String,
+    protocol_data: String,
+    members: Vec<DescribedMemberResponse>,
+    authorized_operations: i32,
+}
+
+impl DescribedGroupResponse {
+    fn new(group_id: &str, group: DescribedGroup, authorized_operations: i32) -> Self {
+        DescribedGroupResponse {
+            // groups that don't exist are described as dead, rather than with an error
+            error_code: ErrorCode::NoError,
+            group_id: group_id.to_string(),
+            group_state: group.state.name().to_string(),
+            protocol_type: group.protocol_type,
+            protocol_data: group.protocol_name,
+            members: group.members.into_iter().map(DescribedMemberResponse::from).collect(),
+            authorized_operations,
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for DescribedGroupResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.error_code.write_kafka_bytes(buf);
+        self.group_id.write_versioned_kafka_bytes(buf, version);
+        self.group_state.write_versioned_kafka_bytes(buf, version);
+        self.protocol_type.write_versioned_kafka_bytes(buf, version);
+        self.protocol_data.write_versioned_kafka_bytes(buf, version);
+        self.members.write_versioned_kafka_bytes(buf, version);
+        if version.version() >= 3 {
+            self.authorized_operations.write_kafka_bytes(buf);
+        }
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct DescribedMemberResponse {
+    member_id: String,
+    group_instance_id: Option<String>,
+    client_id: String,
+    client_host: String,
+    /// Bytes are encoded the same way as an array of u8
+    member_metadata: Vec<u8>,
+    member_assignment: Vec<u8>,
+}
+
+impl From<DescribedMember> for DescribedMemberResponse {
+    fn from(member: DescribedMember) -> Self {
+        DescribedMemberResponse {
+            member_id: member.member_id,
+            group_instance_id: member.group_instance_id,
+            client_id: member.client_id,
+            client_host: member.client_host,
+            member_metadata: member.metadata,
+            member_assignment: member.assignment,
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for DescribedMemberResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.member_id.write_versioned_kafka_bytes(buf, version);
+        if version.version() >= 4 {
+            self.group_instance_id.write_versioned_kafka_bytes(buf, version);
+        }
+        self.client_id.write_versioned_kafka_bytes(buf, version);
+        self.client_host.write_versioned_kafka_bytes(buf, version);
+        self.member_metadata.write_versioned_kafka_bytes(buf, version);
+        self.member_assignment.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
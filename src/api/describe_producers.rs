@@ -0,0 +1,199 @@
+use bytes::{Buf, BufMut};
+use tracing::error;
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::serialisation::{MessageVersion, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+use crate::storage::producer_state::ActiveProducer;
+use crate::storage::topic_partition::TopicPartition;
+
+#[derive(Debug)]
+pub struct DescribeProducersRequest {
+    topics: Vec<DescribeProducersTopic>,
+}
+
+impl ReadVersionedKafkaBytes for DescribeProducersRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let topics = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(DescribeProducersRequest { topics })
+    }
+}
+
+#[derive(Debug)]
+struct DescribeProducersTopic {
+    name: String,
+    partition_indexes: Vec<i32>,
+}
+
+impl ReadVersionedKafkaBytes for DescribeProducersTopic {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let name = String::read_versioned_kafka_bytes(buf, version)?;
+        let partition_indexes = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(DescribeProducersTopic { name, partition_indexes })
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeProducersResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    topics: Vec<DescribeProducersTopicResponse>,
+}
+
+impl DescribeProducersResponse {
+    pub fn process_request(request: &KafkaRequest, describe_producers: &DescribeProducersRequest, broker: &Broker) -> Self {
+        let topics = describe_producers.topics.iter()
+            .map(|topic| DescribeProducersTopicResponse {
+                name: topic.name.clone(),
+                partitions: topic.partition_indexes.iter()
+                    .map(|partition_index| describe_partition_producers(broker, TopicPartition::new(topic.name.clone(), *partition_index)))
+                    .collect(),
+            })
+            .collect();
+
+        DescribeProducersResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            topics,
+        }
+    }
+}
+
+fn describe_partition_producers(broker: &Broker, topic_partition: TopicPartition) -> DescribeProducersPartitionResponse {
+    let (error_code, active_producers) = match broker.log_manager().get_log(&topic_partition) {
+        Ok(Some(log)) => (ErrorCode::NoError, log.active_producers().into_iter().map(ProducerState::from).collect()),
+        Ok(None) => (ErrorCode::UnknownTopicOrPartition, Vec::new()),
+        Err(err) => {
+            error!(%topic_partition, %err, "Failed to open log");
+            (ErrorCode::KafkaStorageError, Vec::new())
+        }
+    };
+    DescribeProducersPartitionResponse {
+        partition_index: topic_partition.partition(),
+        error_code,
+        active_producers,
+    }
+}
+
+impl ApiResponse for DescribeProducersResponse {}
+
+impl ToKafkaBytes for DescribeProducersResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        self.topics.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct DescribeProducersTopicResponse {
+    name: String,
+    partitions: Vec<DescribeProducersPartitionResponse>,
+}
+
+impl ToVersionedKafkaBytes for DescribeProducersTopicResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.name.write_versioned_kafka_bytes(buf, version);
+        self.partitions.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct DescribeProducersPartitionResponse {
+    partition_index: i32,
+    error_code: ErrorCode,
+    active_producers: Vec<ProducerState>,
+}
+
+impl ToVersionedKafkaBytes for DescribeProducersPartitionResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.partition_index.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        // the error message
+        None::<String>.write_versioned_kafka_bytes(buf, version);
+        self.active_producers.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct ProducerState {
+    producer_id: i64,
+    producer_epoch: i32,
+    last_sequence: i32,
+    last_timestamp: i64,
+    coordinator_epoch: i32,
+    current_txn_start_offset: i64,
+}
+
+impl From<ActiveProducer> for ProducerState {
+    fn from(producer: ActiveProducer) -> Self {
+        ProducerState {
+            producer_id: producer.producer_id(),
+            producer_epoch: producer.producer_epoch() as i32,
+            last_sequence: producer.last_sequence(),
+            last_timestamp: producer.last_timestamp(),
+            // the coordinator epoch of the producer's last marker isn't tracked
+            coordinator_epoch: -1,
+            current_txn_start_offset: producer.current_txn_first_offset().unwrap_or(-1),
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for ProducerState {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.producer_id.write_kafka_bytes(buf);
+        self.producer_epoch.write_kafka_bytes(buf);
+        self.last_sequence.write_kafka_bytes(buf);
+        self.last_timestamp.write_kafka_bytes(buf);
+        self.coordinator_epoch.write_kafka_bytes(buf);
+        self.current_txn_start_offset.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::record_batch::RecordBatchBuilder;
+    use crate::testing::open_broker;
+
+    #[tokio::test]
+    async fn test_describe_producer_in_ongoing_transaction() {
+        let (broker, _log_dir) = open_broker("");
+        broker.auto_create_topic("events").unwrap();
+        let topic_partition = TopicPartition::new("events", 0);
+        let (producer_id, producer_epoch) = broker.transaction_coordinator()
+            .init_producer_id("txn", 60_000, None, || broker.producer_ids().generate(broker.metadata()))
+            .unwrap();
+        broker.transaction_coordinator().add_partitions("txn", producer_id, producer_epoch, vec![topic_partition.clone()]).unwrap();
+        let log = broker.log_manager().get_log(&topic_partition).unwrap().unwrap();
+        log.append(RecordBatchBuilder::new().add_record(1000, None, Some(b"before".to_vec())).build(), 0).unwrap();
+        let batch = RecordBatchBuilder::new()
+            .transactional(producer_id, producer_epoch)
+            .add_record(2000, None, Some(b"in the transaction".to_vec()))
+            .build();
+        log.append(batch, 0).unwrap();
+
+        let response = describe_partition_producers(&broker, topic_partition);
+        assert_eq!(response.error_code, ErrorCode::NoError);
+        let [producer] = response.active_producers.as_slice() else {
+            panic!("expected one active producer, got {:?}", response.active_producers);
+        };
+        assert_eq!((producer.producer_id, producer.producer_epoch), (producer_id, producer_epoch as i32));
+        assert_eq!(producer.last_timestamp, 2000);
+        assert_eq!(producer.current_txn_start_offset, 1);
+
+        let unknown = describe_partition_producers(&broker, TopicPartition::new("unknown", 0));
+        assert_eq!(unknown.error_code, ErrorCode::UnknownTopicOrPartition);
+    }
+}
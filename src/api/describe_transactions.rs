@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+use bytes::{Buf, BufMut};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::coordinator::transaction::DescribedTransaction;
+use crate::serialisation::{MessageVersion, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+#[derive(Debug)]
+pub struct DescribeTransactionsRequest {
+    transactional_ids: Vec<String>,
+}
+
+impl ReadVersionedKafkaBytes for DescribeTransactionsRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let transactional_ids = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(DescribeTransactionsRequest { transactional_ids })
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeTransactionsResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    transaction_states: Vec<TransactionStateResponse>,
+}
+
+impl DescribeTransactionsResponse {
+    pub fn process_request(request: &KafkaRequest, describe_transactions: &DescribeTransactionsRequest, broker: &Broker) -> Self {
+        let transaction_states = describe_transactions.transactional_ids.iter()
+            .map(|transactional_id| match broker.transaction_coordinator().describe_transaction(transactional_id) {
+                Ok(transaction) => TransactionStateResponse::new(transactional_id.clone(), transaction),
+                Err(err) => TransactionStateResponse::error(transactional_id.clone(), ErrorCode::from(&err)),
+            })
+            .collect();
+
+        DescribeTransactionsResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            transaction_states,
+        }
+    }
+}
+
+impl ApiResponse for DescribeTransactionsResponse {}
+
+impl ToKafkaBytes for DescribeTransactionsResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        self.transaction_states.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct TransactionStateResponse {
+    error_code: ErrorCode,
+    transactional_id: String,
+    transaction_state: String,
+    transaction_timeout_ms: i32,
+    transaction_start_time_ms: i64,
+    producer_id: i64,
+    producer_epoch: i16,
+    topics: Vec<TopicDataResponse>,
+}
+
+impl TransactionStateResponse {
+    fn new(transactional_id: String, transaction: DescribedTransaction) -> Self {
+        // the partitions are grouped by topic, in topic order
+        let mut topics: BTreeMap<String, Vec<i32>> = BTreeMap::new();
+        for partition in &transaction.partitions {
+            topics.entry(partition.topic().to_string()).or_default().push(partition.partition());
+        }
+        TransactionStateResponse {
+            error_code: ErrorCode::NoError,
+            transactional_id,
+            transaction_state: transaction.state.name().to_string(),
+            transaction_timeout_ms: transaction.timeout_ms,
+            transaction_start_time_ms: transaction.start_timestamp,
+            producer_id: transaction.producer_id,
+            producer_epoch: transaction.producer_epoch,
+            topics: topics.into_iter().map(|(topic, partitions)| TopicDataResponse { topic, partitions }).collect(),
+        }
+    }
+
+    fn error(transactional_id: String, error_code: ErrorCode) -> Self {
+        TransactionStateResponse {
+            error_code,
+            transactional_id,
+            transaction_state: String::new(),
+            transaction_timeout_ms: 0,
+            transaction_start_time_ms: -1,
+            producer_id: -1,
+            producer_epoch: -1,
+            topics: Vec::new(),
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for TransactionStateResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.error_code.write_kafka_bytes(buf);
+        self.transactional_id.write_versioned_kafka_bytes(buf, version);
+        self.transaction_state.write_versioned_kafka_bytes(buf, version);
+        self.transaction_timeout_ms.write_kafka_bytes(buf);
+        self.transaction_start_time_ms.write_kafka_bytes(buf);
+        self.producer_id.write_kafka_bytes(buf);
+        self.producer_epoch.write_kafka_bytes(buf);
+        self.topics.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct TopicDataResponse {
+    topic: String,
+    partitions: Vec<i32>,
+}
+
+impl ToVersionedKafkaBytes for TopicDataResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.topic.write_versioned_kafka_bytes(buf, version);
+        self.partitions.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::topic_partition::TopicPartition;
+    use crate::testing::open_broker;
+
+    #[tokio::test]
+    async fn test_describe_ongoing_transaction() {
+        let (broker, _log_dir) = open_broker("");
+        broker.auto_create_topic("events").unwrap();
+        broker.auto_create_topic("clicks").unwrap();
+        let coordinator = broker.transaction_coordinator();
+        let (producer_id, producer_epoch) = coordinator
+            .init_producer_id("txn", 60_000, None, || broker.producer_ids().generate(broker.metadata()))
+            .unwrap();
+        let partitions = vec![TopicPartition::new("events", 0), TopicPartition::new("clicks", 0)];
+        coordinator.add_partitions("txn", producer_id, producer_epoch, partitions).unwrap();
+
+        let response = TransactionStateResponse::new("txn".to_string(), coordinator.describe_transaction("txn").unwrap());
+        assert_eq!(response.transaction_state, "Ongoing");
+        assert_eq!(response.transaction_timeout_ms, 60_000);
+        assert!(response.transaction_start_time_ms > 0);
+        assert_eq!((response.producer_id, response.producer_epoch), (producer_id, producer_epoch));
+        let topics: Vec<_> = response.topics.iter().map(|topic| (topic.topic.as_str(), topic.partitions.clone())).collect();
+        assert_eq!(topics, vec![("clicks", vec![0]), ("events", vec![0])]);
+
+        let unknown = coordinator.describe_transaction("unknown").unwrap_err();
+        assert_eq!(ErrorCode::from(&unknown), ErrorCode::TransactionalIdNotFound);
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashSet;
+use bytes::{Buf, BufMut};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::broker::scram_credentials::ScramCredentialError;
+use crate::serialisation::{MessageVersion, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+#[derive(Debug)]
+pub struct DescribeUserScramCredentialsRequest {
+    /// The users to describe, or every user with credentials if this is null or empty
+    users: Option<Vec<UserName>>,
+}
+
+impl ReadVersionedKafkaBytes for DescribeUserScramCredentialsRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let users = Option::<Vec<UserName>>::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(DescribeUserScramCredentialsRequest { users })
+    }
+}
+
+#[derive(Debug)]
+struct UserName {
+    name: String,
+}
+
+impl ReadVersionedKafkaBytes for UserName {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let name = String::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(UserName { name })
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeUserScramCredentialsResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    error_code: ErrorCode,
+    error_message: Option<String>,
+    results: Vec<DescribeUserScramCredentialsResult>,
+}
+
+impl DescribeUserScramCredentialsResponse {
+    pub fn process_request(request: &KafkaRequest, describe: &DescribeUserScramCredentialsRequest, broker: &Broker) -> Self {
+        let users: Vec<String> = match &describe.users {
+            Some(users) if !users.is_empty() => users.iter().map(|user| user.name.clone()).collect(),
+            _ => broker.scram_users(),
+        };
+        let mut seen = HashSet::new();
+        let duplicates: HashSet<&str> = users.iter()
+            .map(String::as_str)
+            .filter(|user| !seen.insert(*user))
+            .collect();
+
+        let mut described = HashSet::new();
+        let results = users.iter()
+            // a duplicated user only gets one result
+            .filter(|user| described.insert(user.as_str()))
+            .map(|user| {
+                let result = match duplicates.contains(user.as_str()) {
+                    true => Err(ScramCredentialError::DuplicateResource(
+                        "Cannot describe SCRAM credentials for the same user twice in a single request".to_string()
+                    )),
+                    false => broker.describe_scram_credentials(user),
+                };
+                let (error_code, error_message, credential_infos) = match result {
+                    Ok(credentials) => {
+                        let credential_infos = credentials.into_iter()
+                            .map(|(mechanism, iterations)| CredentialInfo { mechanism: mechanism.mechanism_type(), iterations })
+                            .collect();
+                        (ErrorCode::NoError, None, credential_infos)
+                    }
+                    Err(err) => (ErrorCode::from(&err), Some(err.to_string()), Vec::new()),
+                };
+                DescribeUserScramCredentialsResult { user: user.clone(), error_code, error_message, credential_infos }
+            })
+            .collect();
+
+        DescribeUserScramCredentialsResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            error_code: ErrorCode::NoError,
+            error_message: None,
+            results,
+        }
+    }
+}
+
+impl ApiResponse for DescribeUserScramCredentialsResponse {
+    fn error_code(&self) -> Option<ErrorCode> {
+        Some(self.error_code)
+    }
+}
+
+impl ToKafkaBytes for DescribeUserScramCredentialsResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        self.error_message.write_versioned_kafka_bytes(buf, version);
+        self.results.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct DescribeUserScramCredentialsResult {
+    user: String,
+    error_code: ErrorCode,
+    error_message: Option<String>,
+    credential_infos: Vec<CredentialInfo>,
+}
+
+impl ToVersionedKafkaBytes for DescribeUserScramCredentialsResult {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.user.write_versioned_kafka_bytes(buf, version);
+        self.error_code.write_kafka_bytes(buf);
+        self.error_message.write_versioned_kafka_bytes(buf, version);
+        self.credential_infos.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+/// The mechanism of one of a user's credentials, as its type in the protocol, and its iteration count
+#[derive(Debug)]
+struct CredentialInfo {
+    mechanism: i8,
+    iterations: i32,
+}
+
+impl ToVersionedKafkaBytes for CredentialInfo {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.mechanism.write_kafka_bytes(buf);
+        self.iterations.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, version);
+    }
+}
@@ -0,0 +1,75 @@
+use bytes::{Buf, BufMut};
+use tracing::error;
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::coordinator::transaction::TransactionError;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+#[derive(Debug)]
+pub struct EndTxnRequest {
+    transactional_id: String,
+    producer_id: i64,
+    producer_epoch: i16,
+    /// True to commit the transaction, false to abort it
+    committed: bool,
+}
+
+impl ReadVersionedKafkaBytes for EndTxnRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let transactional_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let producer_id = i64::read_kafka_bytes(buf)?;
+        let producer_epoch = i16::read_kafka_bytes(buf)?;
+        let committed = bool::read_kafka_bytes(buf)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(EndTxnRequest { transactional_id, producer_id, producer_epoch, committed })
+    }
+}
+
+#[derive(Debug)]
+pub struct EndTxnResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    error_code: ErrorCode,
+}
+
+impl EndTxnResponse {
+    pub fn process_request(request: &KafkaRequest, end_txn: &EndTxnRequest, broker: &Broker) -> Self {
+        let result = broker.transaction_coordinator()
+            .end_transaction(&end_txn.transactional_id, end_txn.producer_id, end_txn.producer_epoch, end_txn.committed);
+        let error_code = match result {
+            Ok(()) => ErrorCode::NoError,
+            Err(err) => {
+                if let TransactionError::Storage(err) = &err {
+                    error!(transactional_id = %end_txn.transactional_id, %err, "Failed to end transaction");
+                }
+                ErrorCode::from(&err).or_invalid_producer_epoch(request.api_version() >= 2)
+            }
+        };
+
+        EndTxnResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            error_code,
+        }
+    }
+}
+
+impl ApiResponse for EndTxnResponse {
+    fn error_code(&self) -> Option<ErrorCode> {
+        Some(self.error_code)
+    }
+}
+
+impl ToKafkaBytes for EndTxnResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        self.base_response.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, self.version);
+    }
+}
@@ -0,0 +1,255 @@
+use bytes::BufMut;
+use crate::broker::configs::ConfigsError;
+use crate::broker::fetch_session::FetchSessionError;
+use crate::broker::scram_credentials::ScramCredentialError;
+use crate::broker::topics::TopicError;
+use crate::coordinator::group::GroupError;
+use crate::coordinator::transaction::TransactionError;
+use crate::security::sasl::SaslError;
+use crate::serialisation::ToKafkaBytes;
+
+/// Error codes that can be returned in Kafka API responses
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    NoError,
+    OffsetOutOfRange,
+    CorruptMessage,
+    UnknownTopicOrPartition,
+    LeaderNotAvailable,
+    RequestTimedOut,
+    MessageTooLarge,
+    OffsetMetadataTooLarge,
+    CoordinatorNotAvailable,
+    InvalidTopicException,
+    NotEnoughReplicas,
+    InvalidRequiredAcks,
+    IllegalGeneration,
+    InconsistentGroupProtocol,
+    InvalidGroupId,
+    UnknownMemberId,
+    InvalidSessionTimeout,
+    RebalanceInProgress,
+    UnsupportedSaslMechanism,
+    IllegalSaslState,
+    UnsupportedVersion,
+    TopicAlreadyExists,
+    InvalidPartitions,
+    InvalidReplicationFactor,
+    InvalidReplicaAssignment,
+    InvalidConfig,
+    InvalidRequest,
+    OutOfOrderSequenceNumber,
+    InvalidProducerEpoch,
+    InvalidTxnState,
+    InvalidProducerIdMapping,
+    InvalidTransactionTimeout,
+    ConcurrentTransactions,
+    OperationNotAttempted,
+    KafkaStorageError,
+    SaslAuthenticationFailed,
+    NonEmptyGroup,
+    GroupIdNotFound,
+    FetchSessionIdNotFound,
+    InvalidFetchSessionEpoch,
+    MemberIdRequired,
+    GroupMaxSizeReached,
+    FencedInstanceId,
+    GroupSubscribedToTopic,
+    InvalidRecord,
+    UnstableOffsetCommit,
+    ProducerFenced,
+    ResourceNotFound,
+    DuplicateResource,
+    UnacceptableCredential,
+    UnknownTopicId,
+    TransactionalIdNotFound,
+    FencedMemberEpoch,
+    UnreleasedInstanceId,
+    UnsupportedAssignor,
+    StaleMemberEpoch,
+    InvalidRegularExpression,
+}
+
+impl ErrorCode {
+    /// Older versions of the transactional APIs don't have ProducerFenced, so fenced producers are told their epoch is invalid
+    pub fn or_invalid_producer_epoch(self, supports_producer_fenced: bool) -> ErrorCode {
+        match self {
+            ErrorCode::ProducerFenced if !supports_producer_fenced => ErrorCode::InvalidProducerEpoch,
+            error_code => error_code,
+        }
+    }
+
+    pub fn code(self) -> i16 {
+        match self {
+            ErrorCode::NoError => 0,
+            ErrorCode::OffsetOutOfRange => 1,
+            ErrorCode::CorruptMessage => 2,
+            ErrorCode::UnknownTopicOrPartition => 3,
+            ErrorCode::LeaderNotAvailable => 5,
+            ErrorCode::RequestTimedOut => 7,
+            ErrorCode::MessageTooLarge => 10,
+            ErrorCode::OffsetMetadataTooLarge => 12,
+            ErrorCode::CoordinatorNotAvailable => 15,
+            ErrorCode::InvalidTopicException => 17,
+            ErrorCode::NotEnoughReplicas => 19,
+            ErrorCode::InvalidRequiredAcks => 21,
+            ErrorCode::IllegalGeneration => 22,
+            ErrorCode::InconsistentGroupProtocol => 23,
+            ErrorCode::InvalidGroupId => 24,
+            ErrorCode::UnknownMemberId => 25,
+            ErrorCode::InvalidSessionTimeout => 26,
+            ErrorCode::RebalanceInProgress => 27,
+            ErrorCode::UnsupportedSaslMechanism => 33,
+            ErrorCode::IllegalSaslState => 34,
+            ErrorCode::UnsupportedVersion => 35,
+            ErrorCode::TopicAlreadyExists => 36,
+            ErrorCode::InvalidPartitions => 37,
+            ErrorCode::InvalidReplicationFactor => 38,
+            ErrorCode::InvalidReplicaAssignment => 39,
+            ErrorCode::InvalidConfig => 40,
+            ErrorCode::InvalidRequest => 42,
+            ErrorCode::OutOfOrderSequenceNumber => 45,
+            ErrorCode::InvalidProducerEpoch => 47,
+            ErrorCode::InvalidTxnState => 48,
+            ErrorCode::InvalidProducerIdMapping => 49,
+            ErrorCode::InvalidTransactionTimeout => 50,
+            ErrorCode::ConcurrentTransactions => 51,
+            ErrorCode::OperationNotAttempted => 55,
+            ErrorCode::KafkaStorageError => 56,
+            ErrorCode::SaslAuthenticationFailed => 58,
+            ErrorCode::NonEmptyGroup => 68,
+            ErrorCode::GroupIdNotFound => 69,
+            ErrorCode::FetchSessionIdNotFound => 70,
+            ErrorCode::InvalidFetchSessionEpoch => 71,
+            ErrorCode::MemberIdRequired => 79,
+            ErrorCode::GroupMaxSizeReached => 81,
+            ErrorCode::FencedInstanceId => 82,
+            ErrorCode::GroupSubscribedToTopic => 86,
+            ErrorCode::InvalidRecord => 87,
+            ErrorCode::UnstableOffsetCommit => 88,
+            ErrorCode::ProducerFenced => 90,
+            ErrorCode::ResourceNotFound => 91,
+            ErrorCode::DuplicateResource => 92,
+            ErrorCode::UnacceptableCredential => 93,
+            ErrorCode::UnknownTopicId => 100,
+            ErrorCode::TransactionalIdNotFound => 105,
+            ErrorCode::FencedMemberEpoch => 110,
+            ErrorCode::UnreleasedInstanceId => 111,
+            ErrorCode::UnsupportedAssignor => 112,
+            ErrorCode::StaleMemberEpoch => 113,
+            ErrorCode::InvalidRegularExpression => 130,
+        }
+    }
+}
+
+impl ToKafkaBytes for ErrorCode {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        self.code().write_kafka_bytes(buf)
+    }
+}
+
+impl From<&TopicError> for ErrorCode {
+    fn from(error: &TopicError) -> Self {
+        match error {
+            TopicError::InvalidTopic(_) => ErrorCode::InvalidTopicException,
+            TopicError::TopicAlreadyExists(_) => ErrorCode::TopicAlreadyExists,
+            TopicError::UnknownTopic(_) => ErrorCode::UnknownTopicOrPartition,
+            TopicError::UnknownTopicId(_) => ErrorCode::UnknownTopicId,
+            TopicError::InvalidPartitions(_) => ErrorCode::InvalidPartitions,
+            TopicError::InvalidReplicationFactor(_) => ErrorCode::InvalidReplicationFactor,
+            TopicError::InvalidReplicaAssignment(_) => ErrorCode::InvalidReplicaAssignment,
+            TopicError::InvalidConfig(_) => ErrorCode::InvalidConfig,
+            TopicError::InvalidRequest(_) => ErrorCode::InvalidRequest,
+            TopicError::Storage(_) => ErrorCode::KafkaStorageError,
+        }
+    }
+}
+
+impl From<&ConfigsError> for ErrorCode {
+    fn from(error: &ConfigsError) -> Self {
+        match error {
+            ConfigsError::InvalidRequest(_) => ErrorCode::InvalidRequest,
+            ConfigsError::InvalidConfig(_) => ErrorCode::InvalidConfig,
+            ConfigsError::UnknownTopic(_) => ErrorCode::UnknownTopicOrPartition,
+            ConfigsError::Storage(_) => ErrorCode::KafkaStorageError,
+        }
+    }
+}
+
+impl From<&GroupError> for ErrorCode {
+    fn from(error: &GroupError) -> Self {
+        match error {
+            GroupError::InvalidGroupId => ErrorCode::InvalidGroupId,
+            GroupError::CoordinatorNotAvailable => ErrorCode::CoordinatorNotAvailable,
+            GroupError::UnknownMemberId => ErrorCode::UnknownMemberId,
+            GroupError::IllegalGeneration => ErrorCode::IllegalGeneration,
+            GroupError::InconsistentGroupProtocol => ErrorCode::InconsistentGroupProtocol,
+            GroupError::InvalidSessionTimeout => ErrorCode::InvalidSessionTimeout,
+            GroupError::RebalanceInProgress => ErrorCode::RebalanceInProgress,
+            GroupError::MemberIdRequired(_) => ErrorCode::MemberIdRequired,
+            GroupError::GroupMaxSizeReached => ErrorCode::GroupMaxSizeReached,
+            GroupError::FencedInstanceId => ErrorCode::FencedInstanceId,
+            GroupError::OffsetMetadataTooLarge => ErrorCode::OffsetMetadataTooLarge,
+            GroupError::NonEmptyGroup => ErrorCode::NonEmptyGroup,
+            GroupError::GroupIdNotFound => ErrorCode::GroupIdNotFound,
+            GroupError::GroupSubscribedToTopic => ErrorCode::GroupSubscribedToTopic,
+            GroupError::InvalidRequest(_) => ErrorCode::InvalidRequest,
+            GroupError::UnsupportedAssignor => ErrorCode::UnsupportedAssignor,
+            GroupError::UnreleasedInstanceId => ErrorCode::UnreleasedInstanceId,
+            GroupError::FencedMemberEpoch => ErrorCode::FencedMemberEpoch,
+            GroupError::StaleMemberEpoch => ErrorCode::StaleMemberEpoch,
+            GroupError::InvalidRegularExpression(_) => ErrorCode::InvalidRegularExpression,
+            GroupError::UnstableOffsetCommit => ErrorCode::UnstableOffsetCommit,
+            // clients retry when the coordinator isn't available, rather than giving up
+            GroupError::Storage(_) => ErrorCode::CoordinatorNotAvailable,
+        }
+    }
+}
+
+impl From<&TransactionError> for ErrorCode {
+    fn from(error: &TransactionError) -> Self {
+        match error {
+            TransactionError::InvalidRequest(_) => ErrorCode::InvalidRequest,
+            TransactionError::InvalidTransactionTimeout => ErrorCode::InvalidTransactionTimeout,
+            TransactionError::InvalidProducerIdMapping => ErrorCode::InvalidProducerIdMapping,
+            TransactionError::ProducerFenced => ErrorCode::ProducerFenced,
+            TransactionError::ConcurrentTransactions => ErrorCode::ConcurrentTransactions,
+            TransactionError::InvalidTxnState => ErrorCode::InvalidTxnState,
+            TransactionError::CoordinatorNotAvailable => ErrorCode::CoordinatorNotAvailable,
+            TransactionError::TransactionalIdNotFound => ErrorCode::TransactionalIdNotFound,
+            // clients retry when the coordinator isn't available, rather than giving up
+            TransactionError::Storage(_) => ErrorCode::CoordinatorNotAvailable,
+        }
+    }
+}
+
+impl From<&SaslError> for ErrorCode {
+    fn from(error: &SaslError) -> Self {
+        match error {
+            SaslError::UnsupportedMechanism(_) => ErrorCode::UnsupportedSaslMechanism,
+            SaslError::IllegalState(_) => ErrorCode::IllegalSaslState,
+            SaslError::AuthenticationFailed(_) => ErrorCode::SaslAuthenticationFailed,
+        }
+    }
+}
+
+impl From<&ScramCredentialError> for ErrorCode {
+    fn from(error: &ScramCredentialError) -> Self {
+        match error {
+            ScramCredentialError::UnsupportedMechanism => ErrorCode::UnsupportedSaslMechanism,
+            ScramCredentialError::UnacceptableCredential(_) => ErrorCode::UnacceptableCredential,
+            ScramCredentialError::ResourceNotFound(_) => ErrorCode::ResourceNotFound,
+            ScramCredentialError::DuplicateResource(_) => ErrorCode::DuplicateResource,
+            ScramCredentialError::Storage(_) => ErrorCode::KafkaStorageError,
+        }
+    }
+}
+
+impl From<&FetchSessionError> for ErrorCode {
+    fn from(error: &FetchSessionError) -> Self {
+        match error {
+            FetchSessionError::SessionIdNotFound(_) => ErrorCode::FetchSessionIdNotFound,
+            FetchSessionError::InvalidEpoch { .. } => ErrorCode::InvalidFetchSessionEpoch,
+        }
+    }
+}
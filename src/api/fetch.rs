@@ -0,0 +1,592 @@
+use std::time::Duration;
+use bytes::{Buf, BufMut};
+use uuid::Uuid;
+use tracing::{error, info};
+use super::response::{BaseKafkaResponse, ResponseMessage};
+use crate::api::error_code::ErrorCode;
+use crate::api::isolation_level::IsolationLevel;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::fetch_session::{FetchSessionError, SessionPartition, FINAL_EPOCH, INITIAL_EPOCH, INVALID_SESSION_ID};
+use crate::broker::Broker;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields, write_length_prefix};
+use crate::storage::file_records::FileRecords;
+use crate::storage::topic_partition::TopicPartition;
+
+#[derive(Debug)]
+pub struct FetchRequest {
+    replica_id: i32,
+    /// How long to wait for min_bytes of records to be available before responding with whatever there is
+    max_wait_ms: i32,
+    min_bytes: i32,
+    max_bytes: i32,
+    isolation_level: IsolationLevel,
+    session_id: i32,
+    session_epoch: i32,
+    /// In a full fetch every partition to read is listed, while an incremental fetch only lists what's changed in its session
+    topics: Vec<FetchTopic>,
+    /// The partitions to remove from an incremental fetch's session
+    forgotten_topics: Vec<ForgottenTopic>,
+}
+
+impl ReadVersionedKafkaBytes for FetchRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        // from v15 only followers send a replica id, in a tagged field, and there are no followers
+        let replica_id = match version.version() {
+            ..15 => i32::read_kafka_bytes(buf)?,
+            _ => -1,
+        };
+        let max_wait_ms = i32::read_kafka_bytes(buf)?;
+        let min_bytes = i32::read_kafka_bytes(buf)?;
+        let max_bytes = i32::read_kafka_bytes(buf)?;
+        let isolation_level = IsolationLevel::read_kafka_bytes(buf)?;
+        let (session_id, session_epoch) = match version.version() {
+            7.. => (i32::read_kafka_bytes(buf)?, i32::read_kafka_bytes(buf)?),
+            _ => (INVALID_SESSION_ID, FINAL_EPOCH),
+        };
+        let topics = Vec::read_versioned_kafka_bytes(buf, version)?;
+        let forgotten_topics = match version.version() {
+            7.. => Vec::read_versioned_kafka_bytes(buf, version)?,
+            _ => Vec::new(),
+        };
+        if version.version() >= 11 {
+            // this broker is the only replica, so there's never a closer one in the client's rack to read from
+            let _rack_id = String::read_versioned_kafka_bytes(buf, version)?;
+        }
+        skip_tagged_fields(buf, version)?;
+        Ok(FetchRequest {
+            replica_id,
+            max_wait_ms,
+            min_bytes,
+            max_bytes,
+            isolation_level,
+            session_id,
+            session_epoch,
+            topics,
+            forgotten_topics,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct FetchTopic {
+    /// Empty from v13, when topics are requested by id
+    name: String,
+    /// Nil before v13
+    topic_id: Uuid,
+    partitions: Vec<FetchPartition>,
+}
+
+impl ReadVersionedKafkaBytes for FetchTopic {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let (name, topic_id) = match version.version() {
+            13.. => (String::new(), Uuid::read_kafka_bytes(buf)?),
+            _ => (String::read_versioned_kafka_bytes(buf, version)?, Uuid::nil()),
+        };
+        let partitions = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(FetchTopic { name, topic_id, partitions })
+    }
+}
+
+#[derive(Debug)]
+struct FetchPartition {
+    partition: i32,
+    fetch_offset: i64,
+    partition_max_bytes: i32,
+}
+
+impl ReadVersionedKafkaBytes for FetchPartition {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let partition = i32::read_kafka_bytes(buf)?;
+        if version.version() >= 9 {
+            // the client's leader epoch isn't checked, since this broker is always the partition's leader
+            let _current_leader_epoch = i32::read_kafka_bytes(buf)?;
+        }
+        let fetch_offset = i64::read_kafka_bytes(buf)?;
+        if version.version() >= 12 {
+            // followers send the epoch they last fetched to find where their log diverged, which can't happen with one replica
+            let _last_fetched_epoch = i32::read_kafka_bytes(buf)?;
+        }
+        if version.version() >= 5 {
+            let _log_start_offset = i64::read_kafka_bytes(buf)?;
+        }
+        let partition_max_bytes = i32::read_kafka_bytes(buf)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(FetchPartition { partition, fetch_offset, partition_max_bytes })
+    }
+}
+
+#[derive(Debug)]
+struct ForgottenTopic {
+    /// Empty from v13, when topics are forgotten by id
+    name: String,
+    /// Nil before v13
+    topic_id: Uuid,
+    partitions: Vec<i32>,
+}
+
+impl ReadVersionedKafkaBytes for ForgottenTopic {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let (name, topic_id) = match version.version() {
+            13.. => (String::new(), Uuid::read_kafka_bytes(buf)?),
+            _ => (String::read_versioned_kafka_bytes(buf, version)?, Uuid::nil()),
+        };
+        let partitions = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(ForgottenTopic { name, topic_id, partitions })
+    }
+}
+
+#[derive(Debug)]
+pub struct FetchResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    error_code: ErrorCode,
+    /// The fetch session the response is part of, or 0 if it isn't part of one
+    session_id: i32,
+    responses: Vec<FetchTopicResponse>,
+}
+
+impl FetchResponse {
+    /// Respond once min_bytes of records can be read, waiting up to max_wait_ms for them to be produced.
+    /// It doesn't wait if there's an error to report, since waiting won't fix it, or if no partitions were fetched
+    pub async fn process_request(request: &KafkaRequest, fetch: &FetchRequest, broker: &Broker) -> Self {
+        let version = request.message_version();
+        let mut response = FetchResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version,
+            throttle_time_ms: 0,
+            error_code: ErrorCode::NoError,
+            session_id: INVALID_SESSION_ID,
+            responses: Vec::new(),
+        };
+        let (context, partitions) = match fetch_context(broker, fetch, version) {
+            Ok(context) => context,
+            Err(err) => {
+                response.error_code = ErrorCode::from(&err);
+                return response;
+            }
+        };
+        response.session_id = context.session_id();
+
+        let watched: Vec<TopicPartition> = partitions.iter()
+            .filter_map(|partition| match partition {
+                PartitionToRead::Known(partition) => Some(partition.topic_partition().clone()),
+                PartitionToRead::UnknownTopicId(..) => None,
+            })
+            .collect();
+        let min_bytes = fetch.min_bytes.max(0) as usize;
+        let max_wait = Duration::from_millis(fetch.max_wait_ms.max(0) as u64);
+        let partition_responses = broker.fetch_purgatory()
+            .try_complete_else_watch(&watched, max_wait, || {
+                let responses = fetch_partitions(broker, fetch, &partitions);
+                let bytes: usize = responses.iter().map(|partition| partition.records.size()).sum();
+                let has_error = responses.iter().any(|partition| partition.error_code != ErrorCode::NoError);
+                (bytes >= min_bytes || has_error || watched.is_empty()).then_some(responses)
+            })
+            .await
+            .unwrap_or_else(|| fetch_partitions(broker, fetch, &partitions));
+
+        for (partition, partition_response) in partitions.into_iter().zip(partition_responses) {
+            // only the records that are actually returned count, not those of attempts that waited for more
+            if let PartitionToRead::Known(partition) = &partition {
+                broker.metrics().record_bytes_out(partition.topic_partition().topic(), partition_response.records.size());
+            }
+            if !context.must_respond(broker, &partition, &partition_response) {
+                continue;
+            }
+            let (topic, topic_id) = match partition {
+                PartitionToRead::Known(partition) => (partition.topic_partition().topic().to_string(), partition.topic_id()),
+                PartitionToRead::UnknownTopicId(topic_id, _) => (String::new(), topic_id),
+            };
+            // consecutive partitions of the same topic are grouped together
+            match response.responses.last_mut().filter(|last| last.topic == topic && last.topic_id == topic_id) {
+                Some(last) => last.partitions.push(partition_response),
+                None => response.responses.push(FetchTopicResponse { topic, topic_id, partitions: vec![partition_response] }),
+            }
+        }
+        response
+    }
+}
+
+/// A partition a fetch reads, as the request listed it or as the fetch session remembers it.
+/// A partition of a topic that couldn't be found by its id is only in the response to report that
+#[derive(Debug)]
+enum PartitionToRead {
+    Known(SessionPartition),
+    UnknownTopicId(Uuid, i32),
+}
+
+/// The fetch session a fetch is part of, which decides which partitions are in its response
+#[derive(Debug)]
+enum FetchContext {
+    /// Outside of any session, or closing its session, so it reads the partitions it lists and responds with all of them
+    Sessionless,
+    /// Creating a session with the partitions it lists and responding with all of them,
+    /// where the session id is 0 if there wasn't room for another session
+    Full(i32),
+    /// Reading every partition in its session, but only responding with the partitions that have something new
+    Incremental(i32),
+}
+
+impl FetchContext {
+    fn session_id(&self) -> i32 {
+        match self {
+            FetchContext::Sessionless => INVALID_SESSION_ID,
+            FetchContext::Full(session_id) | FetchContext::Incremental(session_id) => *session_id,
+        }
+    }
+
+    /// Whether the partition has to be in the response, remembering what the session's client is told about it
+    fn must_respond(&self, broker: &Broker, partition: &PartitionToRead, response: &FetchPartitionResponse) -> bool {
+        let PartitionToRead::Known(partition) = partition else {
+            return true;
+        };
+        let recorded = match self {
+            FetchContext::Sessionless => return true,
+            FetchContext::Full(session_id) | FetchContext::Incremental(session_id) => broker.fetch_sessions().record_response(
+                *session_id,
+                partition.topic_partition(),
+                response.high_watermark,
+                response.last_stable_offset,
+                response.log_start_offset,
+                !response.records.is_empty() || response.error_code != ErrorCode::NoError,
+            ),
+        };
+        recorded || matches!(self, FetchContext::Full(_))
+    }
+}
+
+/// Find the fetch session the fetch is part of, creating, updating or closing it as the session id and epoch ask,
+/// along with the partitions to read
+fn fetch_context(broker: &Broker, fetch: &FetchRequest, version: MessageVersion) -> Result<(FetchContext, Vec<PartitionToRead>), FetchSessionError> {
+    let sessions = broker.fetch_sessions();
+    let (known, unknown): (Vec<PartitionToRead>, Vec<PartitionToRead>) = requested_partitions(broker, fetch, version)
+        .into_iter()
+        .partition(|partition| matches!(partition, PartitionToRead::Known(_)));
+    let session_partitions = |partitions: &[PartitionToRead]| partitions.iter()
+        .filter_map(|partition| match partition {
+            PartitionToRead::Known(partition) => Some(partition.clone()),
+            PartitionToRead::UnknownTopicId(..) => None,
+        })
+        .collect::<Vec<SessionPartition>>();
+
+    match fetch.session_epoch {
+        FINAL_EPOCH => {
+            if fetch.session_id != INVALID_SESSION_ID && sessions.close_session(fetch.session_id) {
+                info!("Closed fetch session {}", fetch.session_id);
+            }
+            Ok((FetchContext::Sessionless, known.into_iter().chain(unknown).collect()))
+        }
+        INITIAL_EPOCH => {
+            if fetch.session_id != INVALID_SESSION_ID {
+                sessions.close_session(fetch.session_id);
+            }
+            let session_id = sessions.create_session(session_partitions(&known));
+            Ok((FetchContext::Full(session_id), known.into_iter().chain(unknown).collect()))
+        }
+        epoch => {
+            let forgotten = forgotten_partitions(broker, fetch, version);
+            let session = sessions.update_session(fetch.session_id, epoch, session_partitions(&known), &forgotten)?;
+            // topics that couldn't be found aren't added to the session, but the client is still told about them
+            let partitions = session.into_iter().map(PartitionToRead::Known).chain(unknown).collect();
+            Ok((FetchContext::Incremental(fetch.session_id), partitions))
+        }
+    }
+}
+
+/// The partitions the fetch lists, in the order it lists them, looking up the names of topics requested by id
+fn requested_partitions(broker: &Broker, fetch: &FetchRequest, version: MessageVersion) -> Vec<PartitionToRead> {
+    let image = broker.metadata().image();
+    fetch.topics.iter()
+        .flat_map(|topic| {
+            let name = match version.version() {
+                13.. => image.topic_by_id(&topic.topic_id).map(|metadata| metadata.name().to_string()),
+                _ => Some(topic.name.clone()),
+            };
+            topic.partitions.iter().map(move |partition| match &name {
+                Some(name) => {
+                    let topic_partition = TopicPartition::new(name.as_str(), partition.partition);
+                    let max_bytes = partition.partition_max_bytes;
+                    PartitionToRead::Known(SessionPartition::new(topic_partition, topic.topic_id, partition.fetch_offset, max_bytes))
+                }
+                None => PartitionToRead::UnknownTopicId(topic.topic_id, partition.partition),
+            })
+        })
+        .collect()
+}
+
+/// The partitions the fetch removes from its session, ignoring topics that couldn't be found by their id
+fn forgotten_partitions(broker: &Broker, fetch: &FetchRequest, version: MessageVersion) -> Vec<TopicPartition> {
+    let image = broker.metadata().image();
+    fetch.forgotten_topics.iter()
+        .filter_map(|topic| {
+            let name = match version.version() {
+                13.. => image.topic_by_id(&topic.topic_id).map(|metadata| metadata.name().to_string()),
+                _ => Some(topic.name.clone()),
+            };
+            name.map(|name| (name, topic))
+        })
+        .flat_map(|(name, topic)| topic.partitions.iter().map(move |partition| TopicPartition::new(name.as_str(), *partition)))
+        .collect()
+}
+
+/// Read each of the partitions, which share the fetch's max bytes in order
+fn fetch_partitions(broker: &Broker, fetch: &FetchRequest, partitions: &[PartitionToRead]) -> Vec<FetchPartitionResponse> {
+    let mut bytes_remaining = fetch.max_bytes.max(0) as usize;
+    let mut has_records = false;
+    partitions.iter()
+        .map(|partition| {
+            let response = match partition {
+                PartitionToRead::Known(partition) => fetch_partition(broker, fetch, partition, bytes_remaining, !has_records),
+                PartitionToRead::UnknownTopicId(_, partition_index) => FetchPartitionResponse::new(*partition_index, ErrorCode::UnknownTopicId),
+            };
+            bytes_remaining = bytes_remaining.saturating_sub(response.records.size());
+            has_records |= !response.records.is_empty();
+            response
+        })
+        .collect()
+}
+
+/// Read the partition's records from the fetch offset, up to the offset the client is allowed to see.
+/// The first partition with records gets at least one batch, even if it's bigger than the limits,
+/// so consumers can always make progress
+fn fetch_partition(
+    broker: &Broker,
+    fetch: &FetchRequest,
+    partition: &SessionPartition,
+    bytes_remaining: usize,
+    min_one_batch: bool,
+) -> FetchPartitionResponse {
+    let topic_partition = partition.topic_partition();
+    let partition_index = topic_partition.partition();
+    let log = match broker.log_manager().get_log(topic_partition) {
+        Ok(Some(log)) => log,
+        Ok(None) => return FetchPartitionResponse::new(partition_index, ErrorCode::UnknownTopicOrPartition),
+        Err(err) => {
+            error!(%topic_partition, %err, "Failed to open log");
+            return FetchPartitionResponse::new(partition_index, ErrorCode::KafkaStorageError);
+        }
+    };
+
+    let log_start_offset = log.log_start_offset();
+    let high_watermark = log.high_watermark();
+    let last_stable_offset = log.last_stable_offset();
+    if partition.fetch_offset() < log_start_offset || partition.fetch_offset() > log.log_end_offset() {
+        return FetchPartitionResponse::new(partition_index, ErrorCode::OffsetOutOfRange);
+    }
+
+    // only followers (which have a replica id) can see past the high watermark
+    let is_follower = fetch.replica_id >= 0;
+    let read_committed = !is_follower && fetch.isolation_level == IsolationLevel::ReadCommitted;
+    let max_offset = match (is_follower, read_committed) {
+        (true, _) => log.log_end_offset(),
+        (false, true) => last_stable_offset,
+        (false, false) => high_watermark,
+    };
+    let max_bytes = (partition.max_bytes().max(0) as usize).min(bytes_remaining);
+    let (records, next_offset) = match log.read(partition.fetch_offset(), max_offset, max_bytes, min_one_batch) {
+        Ok(read) => read,
+        Err(err) => {
+            error!(%topic_partition, %err, "Failed to read from the log");
+            return FetchPartitionResponse::new(partition_index, ErrorCode::KafkaStorageError);
+        }
+    };
+    // read_committed consumers use the aborted transactions to filter out the records they returned
+    let aborted_transactions = read_committed.then(|| {
+        log.aborted_transactions(partition.fetch_offset(), next_offset)
+            .into_iter()
+            .map(|txn| AbortedTransaction { producer_id: txn.producer_id(), first_offset: txn.first_offset() })
+            .collect()
+    });
+
+    FetchPartitionResponse {
+        high_watermark,
+        last_stable_offset,
+        log_start_offset,
+        aborted_transactions,
+        records,
+        ..FetchPartitionResponse::new(partition_index, ErrorCode::NoError)
+    }
+}
+
+impl FetchResponse {
+    /// Encode the response, leaving the records in the segment files they were read from so they can be sent from there
+    pub fn to_response_message(self) -> ResponseMessage {
+        let version = self.version;
+        let mut message = ResponseMessage::default().with_error_code(self.error_code);
+        let buf = message.buffer();
+        self.base_response.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        if version.version() >= 7 {
+            self.error_code.write_kafka_bytes(buf);
+            self.session_id.write_kafka_bytes(buf);
+        }
+        write_length_prefix(buf, self.responses.len(), version);
+        for topic in self.responses {
+            topic.write_to(&mut message, version);
+        }
+        write_empty_tagged_fields(message.buffer(), version);
+        message.with_size()
+    }
+}
+
+#[derive(Debug)]
+struct FetchTopicResponse {
+    topic: String,
+    topic_id: Uuid,
+    partitions: Vec<FetchPartitionResponse>,
+}
+
+impl FetchTopicResponse {
+    fn write_to(self, message: &mut ResponseMessage, version: MessageVersion) {
+        let buf = message.buffer();
+        match version.version() {
+            13.. => self.topic_id.write_kafka_bytes(buf),
+            _ => self.topic.write_versioned_kafka_bytes(buf, version),
+        }
+        write_length_prefix(buf, self.partitions.len(), version);
+        for partition in self.partitions {
+            partition.write_to(message, version);
+        }
+        write_empty_tagged_fields(message.buffer(), version);
+    }
+}
+
+#[derive(Debug)]
+struct FetchPartitionResponse {
+    partition_index: i32,
+    error_code: ErrorCode,
+    high_watermark: i64,
+    last_stable_offset: i64,
+    log_start_offset: i64,
+    /// Only returned to read_committed consumers
+    aborted_transactions: Option<Vec<AbortedTransaction>>,
+    records: FileRecords,
+}
+
+impl FetchPartitionResponse {
+    /// A response without any records or offsets, which is all there is for errors
+    fn new(partition_index: i32, error_code: ErrorCode) -> Self {
+        FetchPartitionResponse {
+            partition_index,
+            error_code,
+            high_watermark: -1,
+            last_stable_offset: -1,
+            log_start_offset: -1,
+            aborted_transactions: None,
+            records: FileRecords::new(),
+        }
+    }
+
+    fn write_to(self, message: &mut ResponseMessage, version: MessageVersion) {
+        let buf = message.buffer();
+        self.partition_index.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        self.high_watermark.write_kafka_bytes(buf);
+        self.last_stable_offset.write_kafka_bytes(buf);
+        if version.version() >= 5 {
+            self.log_start_offset.write_kafka_bytes(buf);
+        }
+        self.aborted_transactions.write_versioned_kafka_bytes(buf, version);
+        if version.version() >= 11 {
+            // there are no other replicas to prefer reading from
+            buf.put_i32(-1);
+        }
+        write_length_prefix(buf, self.records.size(), version);
+        message.push_records(self.records);
+        write_empty_tagged_fields(message.buffer(), version);
+    }
+}
+
+#[derive(Debug)]
+struct AbortedTransaction {
+    producer_id: i64,
+    first_offset: i64,
+}
+
+impl ToVersionedKafkaBytes for AbortedTransaction {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.producer_id.write_kafka_bytes(buf);
+        self.first_offset.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::api_key::ApiKey;
+    use crate::api::request::ApiRequest;
+    use crate::storage::record_batch::{end_transaction_marker, ControlRecordType, RecordBatchBuilder};
+    use crate::testing::{open_broker, parse_request};
+
+    /// Fetch partition 0 of `events` from its start with a v4 request
+    async fn fetch(broker: &Broker, isolation_level: i8, max_wait_ms: i32, min_bytes: i32) -> FetchResponse {
+        let mut body = Vec::new();
+        body.extend((-1i32).to_be_bytes());
+        body.extend(max_wait_ms.to_be_bytes());
+        body.extend(min_bytes.to_be_bytes());
+        body.extend(i32::MAX.to_be_bytes());
+        body.push(isolation_level as u8);
+        body.extend(1i32.to_be_bytes());
+        body.extend(6i16.to_be_bytes());
+        body.extend(b"events");
+        body.extend(1i32.to_be_bytes());
+        body.extend(0i32.to_be_bytes());
+        body.extend(0i64.to_be_bytes());
+        body.extend(1_048_576i32.to_be_bytes());
+        let request = parse_request(ApiKey::Fetch, 4, &body);
+        let ApiRequest::Fetch(fetch) = request.api_request() else {
+            panic!("expected a fetch request, got {:?}", request.api_request());
+        };
+        FetchResponse::process_request(&request, fetch, broker).await
+    }
+
+    #[tokio::test]
+    async fn test_long_poll_counts_bytes_out_once() {
+        let (broker, _log_dir) = open_broker("");
+        broker.auto_create_topic("events").unwrap();
+        let batch = RecordBatchBuilder::new().add_record(0, None, Some(b"value".to_vec())).build();
+        let batch_size = batch.len();
+        broker.log_manager().get_log(&TopicPartition::new("events", 0)).unwrap().unwrap().append(batch, 0).unwrap();
+
+        // there's never min_bytes of records, so the partition is read before and after watching it, and again once the wait is over
+        let response = fetch(&broker, 0, 50, 1_000_000).await;
+        assert_eq!(response.responses[0].partitions[0].records.size(), batch_size);
+        let metrics = broker.metrics().render(&broker);
+        assert!(metrics.contains(&format!("kafka_server_bytes_out_total{{topic=\"events\"}} {batch_size}\n")), "{metrics}");
+    }
+
+    #[tokio::test]
+    async fn test_read_committed() {
+        let (broker, _log_dir) = open_broker("");
+        broker.auto_create_topic("events").unwrap();
+        let log = broker.log_manager().get_log(&TopicPartition::new("events", 0)).unwrap().unwrap();
+        // producer 7's transaction is aborted, while producer 8's is still open
+        let aborted = RecordBatchBuilder::new().transactional(7, 0).add_record(0, None, Some(b"aborted".to_vec())).build();
+        let marker = end_transaction_marker(7, 0, 0, ControlRecordType::Abort, 0);
+        let open = RecordBatchBuilder::new().transactional(8, 0).add_record(0, None, Some(b"open".to_vec())).build();
+        let stable_size = aborted.len() + marker.len();
+        let total_size = stable_size + open.len();
+        for batch in [aborted, marker, open] {
+            log.append(batch, 0).unwrap();
+        }
+
+        let response = fetch(&broker, 1, 0, 1).await;
+        let partition = &response.responses[0].partitions[0];
+        assert_eq!((partition.high_watermark, partition.last_stable_offset), (3, 2));
+        assert_eq!(partition.records.size(), stable_size);
+        let aborted_transactions: Vec<_> = partition.aborted_transactions.as_ref().unwrap().iter()
+            .map(|txn| (txn.producer_id, txn.first_offset))
+            .collect();
+        assert_eq!(aborted_transactions, vec![(7, 0)]);
+
+        let response = fetch(&broker, 0, 0, 1).await;
+        let partition = &response.responses[0].partitions[0];
+        assert_eq!(partition.records.size(), total_size);
+        assert!(partition.aborted_transactions.is_none());
+    }
+}
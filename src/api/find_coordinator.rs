@@ -0,0 +1,134 @@
+use bytes::{Buf, BufMut};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+const GROUP_KEY_TYPE: i8 = 0;
+const TRANSACTION_KEY_TYPE: i8 = 1;
+
+#[derive(Debug)]
+pub struct FindCoordinatorRequest {
+    key_type: i8,
+    /// Before v4 a single key is looked up, after that they're batched
+    coordinator_keys: Vec<String>,
+}
+
+impl ReadVersionedKafkaBytes for FindCoordinatorRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let key = match version.version() {
+            0..=3 => Some(String::read_versioned_kafka_bytes(buf, version)?),
+            _ => None,
+        };
+        let key_type = match version.version() {
+            1.. => i8::read_kafka_bytes(buf)?,
+            _ => GROUP_KEY_TYPE,
+        };
+        let coordinator_keys = match key {
+            Some(key) => vec![key],
+            None => Vec::read_versioned_kafka_bytes(buf, version)?,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(FindCoordinatorRequest { key_type, coordinator_keys })
+    }
+}
+
+#[derive(Debug)]
+pub struct FindCoordinatorResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    coordinators: Vec<Coordinator>,
+}
+
+impl FindCoordinatorResponse {
+    pub fn process_request(request: &KafkaRequest, find_coordinator: &FindCoordinatorRequest, broker: &Broker) -> Self {
+        let endpoint = broker.config().advertised_listener();
+        let coordinators = find_coordinator.coordinator_keys.iter()
+            .map(|key| {
+                let error = match find_coordinator.key_type {
+                    _ if key.is_empty() => Some(format!("Invalid coordinator key: {key:?}")),
+                    // this is the only broker, so it's the coordinator of everything
+                    GROUP_KEY_TYPE | TRANSACTION_KEY_TYPE => None,
+                    key_type => Some(format!("Unknown coordinator key type: {key_type}")),
+                };
+                match error {
+                    None => Coordinator {
+                        key: key.clone(),
+                        node_id: broker.config().node_id(),
+                        host: endpoint.advertised_host().to_string(),
+                        port: endpoint.port() as i32,
+                        error_code: ErrorCode::NoError,
+                        error_message: None,
+                    },
+                    Some(message) => Coordinator {
+                        key: key.clone(),
+                        node_id: -1,
+                        host: String::new(),
+                        port: -1,
+                        error_code: ErrorCode::InvalidRequest,
+                        error_message: Some(message),
+                    },
+                }
+            })
+            .collect();
+
+        FindCoordinatorResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            coordinators,
+        }
+    }
+}
+
+impl ApiResponse for FindCoordinatorResponse {}
+
+impl ToKafkaBytes for FindCoordinatorResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        if version.version() >= 1 {
+            self.throttle_time_ms.write_kafka_bytes(buf);
+        }
+        match version.version() {
+            4.. => self.coordinators.write_versioned_kafka_bytes(buf, version),
+            // older versions only ask for one coordinator, with its fields at the top level of the response
+            _ => {
+                let coordinator = self.coordinators.into_iter().next().expect("one key is always read before v4");
+                coordinator.error_code.write_kafka_bytes(buf);
+                if version.version() >= 1 {
+                    coordinator.error_message.write_versioned_kafka_bytes(buf, version);
+                }
+                coordinator.node_id.write_kafka_bytes(buf);
+                coordinator.host.write_versioned_kafka_bytes(buf, version);
+                coordinator.port.write_kafka_bytes(buf);
+            }
+        }
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct Coordinator {
+    key: String,
+    node_id: i32,
+    host: String,
+    port: i32,
+    error_code: ErrorCode,
+    error_message: Option<String>,
+}
+
+impl ToVersionedKafkaBytes for Coordinator {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.key.write_versioned_kafka_bytes(buf, version);
+        self.node_id.write_kafka_bytes(buf);
+        self.host.write_versioned_kafka_bytes(buf, version);
+        self.port.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        self.error_message.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
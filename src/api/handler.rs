@@ -0,0 +1,128 @@
+use tracing::{debug, trace};
+use crate::api::add_offsets_to_txn::AddOffsetsToTxnResponse;
+use crate::api::add_partitions_to_txn::AddPartitionsToTxnResponse;
+use crate::api::alter_configs::AlterConfigsResponse;
+use crate::api::alter_user_scram_credentials::AlterUserScramCredentialsResponse;
+use crate::api::api_versions::ApiVersionsResponse;
+use crate::api::consumer_group_describe::ConsumerGroupDescribeResponse;
+use crate::api::consumer_group_heartbeat::ConsumerGroupHeartbeatResponse;
+use crate::api::create_partitions::CreatePartitionsResponse;
+use crate::api::create_topics::CreateTopicsResponse;
+use crate::api::delete_groups::DeleteGroupsResponse;
+use crate::api::delete_topics::DeleteTopicsResponse;
+use crate::api::describe_groups::DescribeGroupsResponse;
+use crate::api::describe_configs::DescribeConfigsResponse;
+use crate::api::describe_producers::DescribeProducersResponse;
+use crate::api::describe_transactions::DescribeTransactionsResponse;
+use crate::api::describe_user_scram_credentials::DescribeUserScramCredentialsResponse;
+use crate::api::end_txn::EndTxnResponse;
+use crate::api::fetch::FetchResponse;
+use crate::api::find_coordinator::FindCoordinatorResponse;
+use crate::api::heartbeat::HeartbeatResponse;
+use crate::api::incremental_alter_configs::IncrementalAlterConfigsResponse;
+use crate::api::init_producer_id::InitProducerIdResponse;
+use crate::api::join_group::JoinGroupResponse;
+use crate::api::leave_group::LeaveGroupResponse;
+use crate::api::list_groups::ListGroupsResponse;
+use crate::api::list_offsets::ListOffsetsResponse;
+use crate::api::list_transactions::ListTransactionsResponse;
+use crate::api::metadata::MetadataResponse;
+use crate::api::offset_commit::OffsetCommitResponse;
+use crate::api::offset_delete::OffsetDeleteResponse;
+use crate::api::offset_fetch::OffsetFetchResponse;
+use crate::api::produce::ProduceResponse;
+use crate::api::request::{ApiRequest, KafkaRequest};
+use crate::api::response::{ApiResponse, ResponseMessage};
+use crate::api::sasl_authenticate::SaslAuthenticateResponse;
+use crate::api::sasl_handshake::SaslHandshakeResponse;
+use crate::api::sync_group::SyncGroupResponse;
+use crate::api::txn_offset_commit::TxnOffsetCommitResponse;
+use crate::broker::Broker;
+use crate::logging::REQUEST_LOGGER;
+
+/// Process the request with the API it was sent to, returning the response message,
+/// or None if the client isn't expecting a response, such as producing without acks.
+/// Some requests wait for other requests before they can respond, such as joining a group waiting for the other members
+pub async fn handle_request(request: &KafkaRequest, broker: &Broker) -> Option<ResponseMessage> {
+    let response = match request.api_request() {
+        ApiRequest::Produce(produce) => {
+            let response = ProduceResponse::process_request(request, produce, broker).await;
+            if !produce.expects_response() {
+                debug!(?response, "Not sending a response to a produce request without acks");
+                return None;
+            }
+            encode_response(response)
+        }
+        ApiRequest::ApiVersions(_) => encode_response(ApiVersionsResponse::process_request(request)),
+        ApiRequest::Fetch(fetch) => {
+            let response = FetchResponse::process_request(request, fetch, broker).await;
+            trace!(target: REQUEST_LOGGER, ?response, "Sending response");
+            response.to_response_message()
+        }
+        ApiRequest::ListOffsets(list_offsets) => encode_response(ListOffsetsResponse::process_request(request, list_offsets, broker)),
+        ApiRequest::Metadata(metadata) => encode_response(MetadataResponse::process_request(request, metadata, broker)),
+        ApiRequest::OffsetCommit(offset_commit) => encode_response(OffsetCommitResponse::process_request(request, offset_commit, broker)),
+        ApiRequest::OffsetFetch(offset_fetch) => encode_response(OffsetFetchResponse::process_request(request, offset_fetch, broker)),
+        ApiRequest::FindCoordinator(find_coordinator) => {
+            encode_response(FindCoordinatorResponse::process_request(request, find_coordinator, broker))
+        }
+        ApiRequest::JoinGroup(join_group) => encode_response(JoinGroupResponse::process_request(request, join_group, broker).await),
+        ApiRequest::Heartbeat(heartbeat) => encode_response(HeartbeatResponse::process_request(request, heartbeat, broker)),
+        ApiRequest::LeaveGroup(leave_group) => encode_response(LeaveGroupResponse::process_request(request, leave_group, broker)),
+        ApiRequest::SyncGroup(sync_group) => encode_response(SyncGroupResponse::process_request(request, sync_group, broker).await),
+        ApiRequest::DescribeGroups(describe_groups) => encode_response(DescribeGroupsResponse::process_request(request, describe_groups, broker)),
+        ApiRequest::ListGroups(list_groups) => encode_response(ListGroupsResponse::process_request(request, list_groups, broker)),
+        ApiRequest::SaslHandshake(handshake) => encode_response(SaslHandshakeResponse::process_request(request, handshake, broker)),
+        ApiRequest::CreateTopics(create_topics) => encode_response(CreateTopicsResponse::process_request(request, create_topics, broker)),
+        ApiRequest::DeleteTopics(delete_topics) => encode_response(DeleteTopicsResponse::process_request(request, delete_topics, broker)),
+        ApiRequest::InitProducerId(init_producer_id) => {
+            encode_response(InitProducerIdResponse::process_request(request, init_producer_id, broker))
+        }
+        ApiRequest::AddPartitionsToTxn(add_partitions) => {
+            encode_response(AddPartitionsToTxnResponse::process_request(request, add_partitions, broker))
+        }
+        ApiRequest::AddOffsetsToTxn(add_offsets) => encode_response(AddOffsetsToTxnResponse::process_request(request, add_offsets, broker)),
+        ApiRequest::EndTxn(end_txn) => encode_response(EndTxnResponse::process_request(request, end_txn, broker)),
+        ApiRequest::TxnOffsetCommit(txn_offset_commit) => {
+            encode_response(TxnOffsetCommitResponse::process_request(request, txn_offset_commit, broker))
+        }
+        ApiRequest::DescribeConfigs(describe_configs) => encode_response(DescribeConfigsResponse::process_request(request, describe_configs, broker)),
+        ApiRequest::AlterConfigs(alter_configs) => encode_response(AlterConfigsResponse::process_request(request, alter_configs, broker)),
+        ApiRequest::SaslAuthenticate(authenticate) => {
+            encode_response(SaslAuthenticateResponse::process_request(request, authenticate, broker))
+        }
+        ApiRequest::CreatePartitions(create_partitions) => encode_response(CreatePartitionsResponse::process_request(request, create_partitions, broker)),
+        ApiRequest::DeleteGroups(delete_groups) => encode_response(DeleteGroupsResponse::process_request(request, delete_groups, broker)),
+        ApiRequest::OffsetDelete(offset_delete) => encode_response(OffsetDeleteResponse::process_request(request, offset_delete, broker)),
+        ApiRequest::IncrementalAlterConfigs(alter_configs) => {
+            encode_response(IncrementalAlterConfigsResponse::process_request(request, alter_configs, broker))
+        }
+        ApiRequest::DescribeUserScramCredentials(describe) => {
+            encode_response(DescribeUserScramCredentialsResponse::process_request(request, describe, broker))
+        }
+        ApiRequest::AlterUserScramCredentials(alter) => {
+            encode_response(AlterUserScramCredentialsResponse::process_request(request, alter, broker))
+        }
+        ApiRequest::DescribeProducers(describe_producers) => {
+            encode_response(DescribeProducersResponse::process_request(request, describe_producers, broker))
+        }
+        ApiRequest::DescribeTransactions(describe_transactions) => {
+            encode_response(DescribeTransactionsResponse::process_request(request, describe_transactions, broker))
+        }
+        ApiRequest::ListTransactions(list_transactions) => {
+            encode_response(ListTransactionsResponse::process_request(request, list_transactions, broker))
+        }
+        ApiRequest::ConsumerGroupHeartbeat(heartbeat) => {
+            encode_response(ConsumerGroupHeartbeatResponse::process_request(request, heartbeat, broker))
+        }
+        ApiRequest::ConsumerGroupDescribe(describe) => {
+            encode_response(ConsumerGroupDescribeResponse::process_request(request, describe, broker))
+        }
+    };
+    Some(response)
+}
+
+fn encode_response<T: ApiResponse>(response: T) -> ResponseMessage {
+    trace!(target: REQUEST_LOGGER, ?response, "Sending response");
+    ResponseMessage::encode(response)
+}
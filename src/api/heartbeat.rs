@@ -0,0 +1,68 @@
+use bytes::{Buf, BufMut};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+#[derive(Debug)]
+pub struct HeartbeatRequest {
+    group_id: String,
+    generation_id: i32,
+    member_id: String,
+    group_instance_id: Option<String>,
+}
+
+impl ReadVersionedKafkaBytes for HeartbeatRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let group_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let generation_id = i32::read_kafka_bytes(buf)?;
+        let member_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let group_instance_id = match version.version() {
+            3.. => Option::<String>::read_versioned_kafka_bytes(buf, version)?,
+            _ => None,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(HeartbeatRequest { group_id, generation_id, member_id, group_instance_id })
+    }
+}
+
+#[derive(Debug)]
+pub struct HeartbeatResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    error_code: ErrorCode,
+}
+
+impl HeartbeatResponse {
+    pub fn process_request(request: &KafkaRequest, heartbeat: &HeartbeatRequest, broker: &Broker) -> Self {
+        let result = broker.group_coordinator()
+            .heartbeat(&heartbeat.group_id, &heartbeat.member_id, heartbeat.group_instance_id.as_deref(), heartbeat.generation_id);
+        HeartbeatResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            error_code: result.as_ref().err().map_or(ErrorCode::NoError, ErrorCode::from),
+        }
+    }
+}
+
+impl ApiResponse for HeartbeatResponse {
+    fn error_code(&self) -> Option<ErrorCode> {
+        Some(self.error_code)
+    }
+}
+
+impl ToKafkaBytes for HeartbeatResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        if version.version() >= 1 {
+            self.throttle_time_ms.write_kafka_bytes(buf);
+        }
+        self.error_code.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, version);
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashSet;
+use bytes::{Buf, BufMut};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::config_resource::config_resource;
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::broker::configs::AlterConfigOp;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+#[derive(Debug)]
+pub struct IncrementalAlterConfigsRequest {
+    resources: Vec<AlterConfigsResource>,
+    validate_only: bool,
+}
+
+impl ReadVersionedKafkaBytes for IncrementalAlterConfigsRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let resources = Vec::read_versioned_kafka_bytes(buf, version)?;
+        let validate_only = bool::read_kafka_bytes(buf)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(IncrementalAlterConfigsRequest { resources, validate_only })
+    }
+}
+
+#[derive(Debug)]
+struct AlterConfigsResource {
+    resource_type: i8,
+    resource_name: String,
+    configs: Vec<AlterableConfig>,
+}
+
+impl ReadVersionedKafkaBytes for AlterConfigsResource {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let resource = AlterConfigsResource {
+            resource_type: i8::read_kafka_bytes(buf)?,
+            resource_name: String::read_versioned_kafka_bytes(buf, version)?,
+            configs: Vec::read_versioned_kafka_bytes(buf, version)?,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(resource)
+    }
+}
+
+#[derive(Debug)]
+struct AlterableConfig {
+    name: String,
+    config_operation: i8,
+    value: Option<String>,
+}
+
+impl ReadVersionedKafkaBytes for AlterableConfig {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let config = AlterableConfig {
+            name: String::read_versioned_kafka_bytes(buf, version)?,
+            config_operation: i8::read_kafka_bytes(buf)?,
+            value: Option::<String>::read_versioned_kafka_bytes(buf, version)?,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(config)
+    }
+}
+
+impl AlterableConfig {
+    fn to_op(&self) -> Result<(String, AlterConfigOp), String> {
+        let value = || self.value.clone()
+            .ok_or_else(|| format!("Null value not supported for: {}", self.name));
+        let op = match self.config_operation {
+            0 => AlterConfigOp::Set(value()?),
+            1 => AlterConfigOp::Delete,
+            2 => AlterConfigOp::Append(value()?),
+            3 => AlterConfigOp::Subtract(value()?),
+            operation => return Err(format!("Unknown alter config operation {operation} for: {}", self.name)),
+        };
+        Ok((self.name.clone(), op))
+    }
+}
+
+#[derive(Debug)]
+pub struct IncrementalAlterConfigsResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    responses: Vec<AlterConfigsResourceResponse>,
+}
+
+impl IncrementalAlterConfigsResponse {
+    pub fn process_request(request: &KafkaRequest, alter_configs: &IncrementalAlterConfigsRequest, broker: &Broker) -> Self {
+        let mut seen = HashSet::new();
+        let duplicates: HashSet<(i8, &str)> = alter_configs.resources.iter()
+            .map(|resource| (resource.resource_type, resource.resource_name.as_str()))
+            .filter(|resource| !seen.insert(*resource))
+            .collect();
+
+        let responses = alter_configs.resources.iter()
+            .map(|resource| {
+                let result = Self::alter_resource(resource, &duplicates, alter_configs.validate_only, broker);
+                let (error_code, error_message) = match result {
+                    Ok(()) => (ErrorCode::NoError, None),
+                    Err((error_code, message)) => (error_code, Some(message)),
+                };
+                AlterConfigsResourceResponse {
+                    error_code,
+                    error_message,
+                    resource_type: resource.resource_type,
+                    resource_name: resource.resource_name.clone(),
+                }
+            })
+            .collect();
+
+        IncrementalAlterConfigsResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            responses,
+        }
+    }
+
+    fn alter_resource(resource: &AlterConfigsResource, duplicates: &HashSet<(i8, &str)>, validate_only: bool, broker: &Broker) -> Result<(), (ErrorCode, String)> {
+        let invalid_request = |message: String| (ErrorCode::InvalidRequest, message);
+        if duplicates.contains(&(resource.resource_type, resource.resource_name.as_str())) {
+            return Err(invalid_request(format!("Duplicate resource in request: {}", resource.resource_name)));
+        }
+        let config_resource = config_resource(resource.resource_type, &resource.resource_name).map_err(invalid_request)?;
+        let mut names = HashSet::new();
+        if !resource.configs.iter().all(|config| names.insert(config.name.as_str())) {
+            return Err(invalid_request(format!("Error due to duplicate config keys for resource {}", resource.resource_name)));
+        }
+        let ops = resource.configs.iter()
+            .map(AlterableConfig::to_op)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(invalid_request)?;
+        broker.incremental_alter_configs(config_resource, &ops, validate_only)
+            .map_err(|err| (ErrorCode::from(&err), err.to_string()))
+    }
+}
+
+impl ApiResponse for IncrementalAlterConfigsResponse {}
+
+impl ToKafkaBytes for IncrementalAlterConfigsResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        self.responses.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct AlterConfigsResourceResponse {
+    error_code: ErrorCode,
+    error_message: Option<String>,
+    resource_type: i8,
+    resource_name: String,
+}
+
+impl ToVersionedKafkaBytes for AlterConfigsResourceResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.error_code.write_kafka_bytes(buf);
+        self.error_message.write_versioned_kafka_bytes(buf, version);
+        self.resource_type.write_kafka_bytes(buf);
+        self.resource_name.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
@@ -0,0 +1,130 @@
+use bytes::{Buf, BufMut};
+use tracing::error;
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::coordinator::transaction::TransactionError;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+/// The producer id and epoch a producer sends before it's been given one
+const NO_PRODUCER_ID: i64 = -1;
+const NO_PRODUCER_EPOCH: i16 = -1;
+
+#[derive(Debug)]
+pub struct InitProducerIdRequest {
+    /// Null for idempotent producers that don't use transactions
+    transactional_id: Option<String>,
+    /// How long the producer's transactions can be ongoing before they're aborted
+    transaction_timeout_ms: i32,
+    /// The producer's current id and epoch, which it sends from v3 when it's reinitialising after an error
+    producer_id: i64,
+    producer_epoch: i16,
+}
+
+impl ReadVersionedKafkaBytes for InitProducerIdRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let transactional_id = Option::<String>::read_versioned_kafka_bytes(buf, version)?;
+        let transaction_timeout_ms = i32::read_kafka_bytes(buf)?;
+        let (producer_id, producer_epoch) = match version.version() {
+            3.. => (i64::read_kafka_bytes(buf)?, i16::read_kafka_bytes(buf)?),
+            _ => (NO_PRODUCER_ID, NO_PRODUCER_EPOCH),
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(InitProducerIdRequest { transactional_id, transaction_timeout_ms, producer_id, producer_epoch })
+    }
+}
+
+#[derive(Debug)]
+pub struct InitProducerIdResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    error_code: ErrorCode,
+    producer_id: i64,
+    producer_epoch: i16,
+}
+
+impl InitProducerIdResponse {
+    pub fn process_request(request: &KafkaRequest, init_producer_id: &InitProducerIdRequest, broker: &Broker) -> Self {
+        let base_response = BaseKafkaResponse::new(request);
+        let version = request.message_version();
+        if (init_producer_id.producer_id == NO_PRODUCER_ID) != (init_producer_id.producer_epoch == NO_PRODUCER_EPOCH) {
+            return Self::error(base_response, version, ErrorCode::InvalidRequest);
+        }
+        if let Some(transactional_id) = &init_producer_id.transactional_id {
+            let expected = Some((init_producer_id.producer_id, init_producer_id.producer_epoch))
+                .filter(|(producer_id, _)| *producer_id != NO_PRODUCER_ID);
+            let result = broker.transaction_coordinator().init_producer_id(
+                transactional_id,
+                init_producer_id.transaction_timeout_ms,
+                expected,
+                || broker.producer_ids().generate(broker.metadata()),
+            );
+            return match result {
+                Ok((producer_id, producer_epoch)) => InitProducerIdResponse {
+                    base_response,
+                    version,
+                    throttle_time_ms: 0,
+                    error_code: ErrorCode::NoError,
+                    producer_id,
+                    producer_epoch,
+                },
+                Err(err) => {
+                    if let TransactionError::Storage(err) = &err {
+                        error!(%transactional_id, %err, "Failed to initialise transactional producer");
+                    }
+                    let error_code = ErrorCode::from(&err).or_invalid_producer_epoch(version.version() >= 4);
+                    Self::error(base_response, version, error_code)
+                }
+            };
+        }
+
+        // an idempotent producer's state is only kept by the partitions it writes to,
+        // so it's always given a new id, even when it sends its current one
+        match broker.producer_ids().generate(broker.metadata()) {
+            Ok(producer_id) => InitProducerIdResponse {
+                base_response,
+                version,
+                throttle_time_ms: 0,
+                error_code: ErrorCode::NoError,
+                producer_id,
+                producer_epoch: 0,
+            },
+            Err(err) => {
+                error!(%err, "Failed to allocate producer ids");
+                // clients retry when the coordinator isn't available, rather than giving up
+                Self::error(base_response, version, ErrorCode::CoordinatorNotAvailable)
+            }
+        }
+    }
+
+    fn error(base_response: BaseKafkaResponse, version: MessageVersion, error_code: ErrorCode) -> Self {
+        InitProducerIdResponse {
+            base_response,
+            version,
+            throttle_time_ms: 0,
+            error_code,
+            producer_id: NO_PRODUCER_ID,
+            producer_epoch: NO_PRODUCER_EPOCH,
+        }
+    }
+}
+
+impl ApiResponse for InitProducerIdResponse {
+    fn error_code(&self) -> Option<ErrorCode> {
+        Some(self.error_code)
+    }
+}
+
+impl ToKafkaBytes for InitProducerIdResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        self.base_response.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        self.producer_id.write_kafka_bytes(buf);
+        self.producer_epoch.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, self.version);
+    }
+}
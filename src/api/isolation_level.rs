@@ -0,0 +1,21 @@
+use bytes::Buf;
+use crate::api::request::KafkaRequestParseError;
+use crate::serialisation::ReadKafkaBytes;
+
+/// Controls whether consumers can see records from transactions that haven't been committed
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum IsolationLevel {
+    #[default]
+    ReadUncommitted,
+    ReadCommitted,
+}
+
+impl ReadKafkaBytes for IsolationLevel {
+    fn read_kafka_bytes<B: Buf>(buf: &mut B) -> Result<Self, KafkaRequestParseError> {
+        match i8::read_kafka_bytes(buf)? {
+            1 => Ok(IsolationLevel::ReadCommitted),
+            // kafka treats anything else as read uncommitted
+            _ => Ok(IsolationLevel::ReadUncommitted),
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use bytes::{Buf, BufMut};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::coordinator::group::{GroupError, JoinGroupParams, JoinedGroup, JoinedMember};
+use crate::coordinator::group::member::JoinProtocol;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+#[derive(Debug)]
+pub struct JoinGroupRequest {
+    group_id: String,
+    session_timeout_ms: i32,
+    rebalance_timeout_ms: i32,
+    member_id: String,
+    group_instance_id: Option<String>,
+    protocol_type: String,
+    protocols: Vec<JoinGroupRequestProtocol>,
+    reason: Option<String>,
+}
+
+impl ReadVersionedKafkaBytes for JoinGroupRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let group_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let session_timeout_ms = i32::read_kafka_bytes(buf)?;
+        // before v1 the session timeout was also used as the rebalance timeout
+        let rebalance_timeout_ms = match version.version() {
+            1.. => i32::read_kafka_bytes(buf)?,
+            _ => session_timeout_ms,
+        };
+        let member_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let group_instance_id = match version.version() {
+            5.. => Option::<String>::read_versioned_kafka_bytes(buf, version)?,
+            _ => None,
+        };
+        let protocol_type = String::read_versioned_kafka_bytes(buf, version)?;
+        let protocols = Vec::read_versioned_kafka_bytes(buf, version)?;
+        let reason = match version.version() {
+            8.. => Option::<String>::read_versioned_kafka_bytes(buf, version)?,
+            _ => None,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(JoinGroupRequest {
+            group_id,
+            session_timeout_ms,
+            rebalance_timeout_ms,
+            member_id,
+            group_instance_id,
+            protocol_type,
+            protocols,
+            reason,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct JoinGroupRequestProtocol {
+    name: String,
+    /// Bytes are encoded the same way as an array of u8
+    metadata: Vec<u8>,
+}
+
+impl ReadVersionedKafkaBytes for JoinGroupRequestProtocol {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let name = String::read_versioned_kafka_bytes(buf, version)?;
+        let metadata = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(JoinGroupRequestProtocol { name, metadata })
+    }
+}
+
+#[derive(Debug)]
+pub struct JoinGroupResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    error_code: ErrorCode,
+    generation_id: i32,
+    protocol_type: Option<String>,
+    protocol_name: Option<String>,
+    leader: String,
+    skip_assignment: bool,
+    member_id: String,
+    members: Vec<JoinGroupResponseMember>,
+}
+
+impl JoinGroupResponse {
+    /// Join the group, which only responds once the group has rebalanced
+    pub async fn process_request(request: &KafkaRequest, join_group: &JoinGroupRequest, broker: &Broker) -> Self {
+        let join = JoinGroupParams {
+            group_id: join_group.group_id.clone(),
+            member_id: join_group.member_id.clone(),
+            group_instance_id: join_group.group_instance_id.clone(),
+            client_id: request.client_id().as_str().unwrap_or_default().to_string(),
+            // formatted the way java formats addresses, which is how kafka describes the client's host
+            client_host: format!("/{}", request.client_address()),
+            session_timeout_ms: join_group.session_timeout_ms,
+            rebalance_timeout_ms: join_group.rebalance_timeout_ms,
+            protocol_type: join_group.protocol_type.clone(),
+            protocols: join_group.protocols.iter()
+                .map(|protocol| JoinProtocol { name: protocol.name.clone(), metadata: protocol.metadata.clone() })
+                .collect(),
+            // from v4 new members are given a member id, which they have to rejoin with
+            require_known_member_id: request.api_version() >= 4,
+            reason: join_group.reason.clone(),
+        };
+        let result = broker.group_coordinator().join_group(join).await;
+
+        let base_response = BaseKafkaResponse::new(request);
+        let version = request.message_version();
+        match result {
+            Ok(joined) => JoinGroupResponse::joined(base_response, version, joined),
+            Err(err) => {
+                let member_id = match &err {
+                    GroupError::MemberIdRequired(member_id) => member_id.clone(),
+                    _ => join_group.member_id.clone(),
+                };
+                JoinGroupResponse {
+                    base_response,
+                    version,
+                    throttle_time_ms: 0,
+                    error_code: ErrorCode::from(&err),
+                    generation_id: -1,
+                    protocol_type: None,
+                    protocol_name: None,
+                    leader: String::new(),
+                    skip_assignment: false,
+                    member_id,
+                    members: Vec::new(),
+                }
+            }
+        }
+    }
+
+    fn joined(base_response: BaseKafkaResponse, version: MessageVersion, joined: JoinedGroup) -> Self {
+        JoinGroupResponse {
+            base_response,
+            version,
+            throttle_time_ms: 0,
+            error_code: ErrorCode::NoError,
+            generation_id: joined.generation_id,
+            protocol_type: joined.protocol_type,
+            protocol_name: joined.protocol_name,
+            leader: joined.leader,
+            skip_assignment: joined.skip_assignment,
+            member_id: joined.member_id,
+            members: joined.members.into_iter().map(JoinGroupResponseMember::from).collect(),
+        }
+    }
+}
+
+impl ApiResponse for JoinGroupResponse {
+    fn error_code(&self) -> Option<ErrorCode> {
+        Some(self.error_code)
+    }
+}
+
+impl ToKafkaBytes for JoinGroupResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        if version.version() >= 2 {
+            self.throttle_time_ms.write_kafka_bytes(buf);
+        }
+        self.error_code.write_kafka_bytes(buf);
+        self.generation_id.write_kafka_bytes(buf);
+        match version.version() {
+            7.. => {
+                self.protocol_type.write_versioned_kafka_bytes(buf, version);
+                self.protocol_name.write_versioned_kafka_bytes(buf, version);
+            }
+            // the protocol name isn't nullable before v7
+            _ => self.protocol_name.unwrap_or_default().write_versioned_kafka_bytes(buf, version),
+        }
+        self.leader.write_versioned_kafka_bytes(buf, version);
+        if version.version() >= 9 {
+            self.skip_assignment.write_kafka_bytes(buf);
+        }
+        self.member_id.write_versioned_kafka_bytes(buf, version);
+        self.members.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct JoinGroupResponseMember {
+    member_id: String,
+    group_instance_id: Option<String>,
+    metadata: Vec<u8>,
+}
+
+impl From<JoinedMember> for JoinGroupResponseMember {
+    fn from(member: JoinedMember) -> Self {
+        JoinGroupResponseMember {
+            member_id: member.member_id,
+            group_instance_id: member.group_instance_id,
+            metadata: member.metadata,
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for JoinGroupResponseMember {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.member_id.write_versioned_kafka_bytes(buf, version);
+        if version.version() >= 5 {
+            self.group_instance_id.write_versioned_kafka_bytes(buf, version);
+        }
+        self.metadata.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
@@ -0,0 +1,136 @@
+use bytes::{Buf, BufMut};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::coordinator::group::LeavingMember;
+use crate::serialisation::{MessageVersion, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+#[derive(Debug)]
+pub struct LeaveGroupRequest {
+    group_id: String,
+    /// Before v3 a single member leaves, after that members can leave in batches
+    members: Vec<MemberIdentity>,
+}
+
+impl ReadVersionedKafkaBytes for LeaveGroupRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let group_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let members = match version.version() {
+            3.. => Vec::read_versioned_kafka_bytes(buf, version)?,
+            _ => vec![MemberIdentity {
+                member_id: String::read_versioned_kafka_bytes(buf, version)?,
+                group_instance_id: None,
+                reason: None,
+            }],
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(LeaveGroupRequest { group_id, members })
+    }
+}
+
+#[derive(Debug)]
+struct MemberIdentity {
+    member_id: String,
+    group_instance_id: Option<String>,
+    reason: Option<String>,
+}
+
+impl ReadVersionedKafkaBytes for MemberIdentity {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let member_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let group_instance_id = Option::<String>::read_versioned_kafka_bytes(buf, version)?;
+        let reason = match version.version() {
+            5.. => Option::<String>::read_versioned_kafka_bytes(buf, version)?,
+            _ => None,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(MemberIdentity { member_id, group_instance_id, reason })
+    }
+}
+
+#[derive(Debug)]
+pub struct LeaveGroupResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    error_code: ErrorCode,
+    members: Vec<MemberResponse>,
+}
+
+impl LeaveGroupResponse {
+    pub fn process_request(request: &KafkaRequest, leave_group: &LeaveGroupRequest, broker: &Broker) -> Self {
+        let leaving: Vec<LeavingMember> = leave_group.members.iter()
+            .map(|member| LeavingMember {
+                member_id: member.member_id.clone(),
+                group_instance_id: member.group_instance_id.clone(),
+                reason: member.reason.clone(),
+            })
+            .collect();
+        let (error_code, members) = match broker.group_coordinator().leave_group(&leave_group.group_id, &leaving) {
+            Ok(results) => {
+                let members: Vec<MemberResponse> = leave_group.members.iter()
+                    .zip(results)
+                    .map(|(member, result)| MemberResponse {
+                        member_id: member.member_id.clone(),
+                        group_instance_id: member.group_instance_id.clone(),
+                        error_code: result.as_ref().err().map_or(ErrorCode::NoError, ErrorCode::from),
+                    })
+                    .collect();
+                // before v3 there's only one member, whose error is the error of the whole request
+                let error_code = match request.api_version() {
+                    0..=2 => members.first().map_or(ErrorCode::NoError, |member| member.error_code),
+                    _ => ErrorCode::NoError,
+                };
+                (error_code, members)
+            }
+            Err(err) => (ErrorCode::from(&err), Vec::new()),
+        };
+
+        LeaveGroupResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            error_code,
+            members,
+        }
+    }
+}
+
+impl ApiResponse for LeaveGroupResponse {
+    fn error_code(&self) -> Option<ErrorCode> {
+        Some(self.error_code)
+    }
+}
+
+impl ToKafkaBytes for LeaveGroupResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        if version.version() >= 1 {
+            self.throttle_time_ms.write_kafka_bytes(buf);
+        }
+        self.error_code.write_kafka_bytes(buf);
+        if version.version() >= 3 {
+            self.members.write_versioned_kafka_bytes(buf, version);
+        }
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct MemberResponse {
+    member_id: String,
+    group_instance_id: Option<String>,
+    error_code: ErrorCode,
+}
+
+impl ToVersionedKafkaBytes for MemberResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.member_id.write_versioned_kafka_bytes(buf, version);
+        self.group_instance_id.write_versioned_kafka_bytes(buf, version);
+        self.error_code.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, version);
+    }
+}
@@ -0,0 +1,114 @@
+use bytes::{Buf, BufMut};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::coordinator::group::ListedGroup;
+use crate::serialisation::{MessageVersion, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+#[derive(Debug)]
+pub struct ListGroupsRequest {
+    /// Only list groups in these states, or every group if it's empty
+    states_filter: Vec<String>,
+    /// Only list groups of these types, or every group if it's empty
+    types_filter: Vec<String>,
+}
+
+impl ReadVersionedKafkaBytes for ListGroupsRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let states_filter = match version.version() {
+            4.. => Vec::read_versioned_kafka_bytes(buf, version)?,
+            _ => Vec::new(),
+        };
+        let types_filter = match version.version() {
+            5.. => Vec::read_versioned_kafka_bytes(buf, version)?,
+            _ => Vec::new(),
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(ListGroupsRequest { states_filter, types_filter })
+    }
+}
+
+#[derive(Debug)]
+pub struct ListGroupsResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    error_code: ErrorCode,
+    groups: Vec<ListedGroupResponse>,
+}
+
+impl ListGroupsResponse {
+    pub fn process_request(request: &KafkaRequest, list_groups: &ListGroupsRequest, broker: &Broker) -> Self {
+        // the filters aren't case sensitive
+        let matches = |filter: &[String], value: &str| filter.is_empty() || filter.iter().any(|filter| filter.eq_ignore_ascii_case(value));
+        let groups = broker.group_coordinator()
+            .list_groups()
+            .into_iter()
+            .filter(|group| matches(&list_groups.states_filter, group.state))
+            .filter(|group| matches(&list_groups.types_filter, group.group_type.name()))
+            .map(ListedGroupResponse::from)
+            .collect();
+
+        ListGroupsResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            error_code: ErrorCode::NoError,
+            groups,
+        }
+    }
+}
+
+impl ApiResponse for ListGroupsResponse {
+    fn error_code(&self) -> Option<ErrorCode> {
+        Some(self.error_code)
+    }
+}
+
+impl ToKafkaBytes for ListGroupsResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        if version.version() >= 1 {
+            self.throttle_time_ms.write_kafka_bytes(buf);
+        }
+        self.error_code.write_kafka_bytes(buf);
+        self.groups.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct ListedGroupResponse {
+    group_id: String,
+    protocol_type: String,
+    group_state: String,
+    group_type: String,
+}
+
+impl From<ListedGroup> for ListedGroupResponse {
+    fn from(group: ListedGroup) -> Self {
+        ListedGroupResponse {
+            group_id: group.group_id,
+            protocol_type: group.protocol_type,
+            group_state: group.state.to_string(),
+            group_type: group.group_type.name().to_string(),
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for ListedGroupResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.group_id.write_versioned_kafka_bytes(buf, version);
+        self.protocol_type.write_versioned_kafka_bytes(buf, version);
+        if version.version() >= 4 {
+            self.group_state.write_versioned_kafka_bytes(buf, version);
+        }
+        if version.version() >= 5 {
+            self.group_type.write_versioned_kafka_bytes(buf, version);
+        }
+        write_empty_tagged_fields(buf, version);
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::HashSet;
+use bytes::{Buf, BufMut};
+use tracing::error;
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::isolation_level::IsolationLevel;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+use crate::storage::log::{Log, TimestampAndOffset};
+use crate::storage::topic_partition::TopicPartition;
+
+/// Special timestamps that request an offset, rather than searching for a timestamp
+const LATEST_TIMESTAMP: i64 = -1;
+const EARLIEST_TIMESTAMP: i64 = -2;
+const MAX_TIMESTAMP: i64 = -3;
+const EARLIEST_LOCAL_TIMESTAMP: i64 = -4;
+const LATEST_TIERED_TIMESTAMP: i64 = -5;
+
+#[derive(Debug)]
+pub struct ListOffsetsRequest {
+    replica_id: i32,
+    isolation_level: IsolationLevel,
+    topics: Vec<ListOffsetsTopic>,
+}
+
+impl ReadVersionedKafkaBytes for ListOffsetsRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let replica_id = i32::read_kafka_bytes(buf)?;
+        let isolation_level = match version.version() {
+            2.. => IsolationLevel::read_kafka_bytes(buf)?,
+            _ => IsolationLevel::ReadUncommitted,
+        };
+        let topics = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(ListOffsetsRequest { replica_id, isolation_level, topics })
+    }
+}
+
+#[derive(Debug)]
+struct ListOffsetsTopic {
+    name: String,
+    partitions: Vec<ListOffsetsPartition>,
+}
+
+impl ReadVersionedKafkaBytes for ListOffsetsTopic {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let name = String::read_versioned_kafka_bytes(buf, version)?;
+        let partitions = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(ListOffsetsTopic { name, partitions })
+    }
+}
+
+#[derive(Debug)]
+struct ListOffsetsPartition {
+    partition_index: i32,
+    timestamp: i64,
+    max_num_offsets: i32,
+}
+
+impl ReadVersionedKafkaBytes for ListOffsetsPartition {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let partition_index = i32::read_kafka_bytes(buf)?;
+        if version.version() >= 4 {
+            // the client's leader epoch isn't checked, since this broker is always the partition's leader
+            let _current_leader_epoch = i32::read_kafka_bytes(buf)?;
+        }
+        let timestamp = i64::read_kafka_bytes(buf)?;
+        let max_num_offsets = match version.version() {
+            0 => i32::read_kafka_bytes(buf)?,
+            _ => 1,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(ListOffsetsPartition { partition_index, timestamp, max_num_offsets })
+    }
+}
+
+#[derive(Debug)]
+pub struct ListOffsetsResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    topics: Vec<ListOffsetsTopicResponse>,
+}
+
+impl ListOffsetsResponse {
+    pub fn process_request(request: &KafkaRequest, list_offsets: &ListOffsetsRequest, broker: &Broker) -> Self {
+        let version = request.message_version();
+        // kafka rejects partitions that are requested more than once, rather than picking one of them
+        let mut seen = HashSet::new();
+        let duplicates: HashSet<(&str, i32)> = list_offsets.topics.iter()
+            .flat_map(|topic| topic.partitions.iter().map(|partition| (topic.name.as_str(), partition.partition_index)))
+            .filter(|topic_partition| !seen.insert(*topic_partition))
+            .collect();
+
+        let topics = list_offsets.topics.iter()
+            .map(|topic| ListOffsetsTopicResponse {
+                name: topic.name.clone(),
+                partitions: topic.partitions.iter()
+                    .map(|partition| match duplicates.contains(&(topic.name.as_str(), partition.partition_index)) {
+                        true => ListOffsetsPartitionResponse::new(partition.partition_index, ErrorCode::InvalidRequest),
+                        false => list_partition_offset(broker, version, list_offsets, &topic.name, partition),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        ListOffsetsResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version,
+            throttle_time_ms: 0,
+            topics,
+        }
+    }
+}
+
+fn list_partition_offset(
+    broker: &Broker,
+    version: MessageVersion,
+    request: &ListOffsetsRequest,
+    topic: &str,
+    partition: &ListOffsetsPartition,
+) -> ListOffsetsPartitionResponse {
+    let partition_index = partition.partition_index;
+    let topic_partition = TopicPartition::new(topic, partition_index);
+    let log = match broker.log_manager().get_log(&topic_partition) {
+        Ok(Some(log)) => log,
+        Ok(None) => return ListOffsetsPartitionResponse::new(partition_index, ErrorCode::UnknownTopicOrPartition),
+        Err(err) => {
+            error!(%topic_partition, %err, "Failed to open log");
+            return ListOffsetsPartitionResponse::new(partition_index, ErrorCode::KafkaStorageError);
+        }
+    };
+
+    if version.version() == 0 {
+        let max_num_offsets = partition.max_num_offsets.max(0) as usize;
+        return ListOffsetsPartitionResponse {
+            old_style_offsets: log.legacy_offsets_before(partition.timestamp, max_num_offsets),
+            ..ListOffsetsPartitionResponse::new(partition_index, ErrorCode::NoError)
+        };
+    }
+
+    let minimum_version = match partition.timestamp {
+        MAX_TIMESTAMP => 7,
+        EARLIEST_LOCAL_TIMESTAMP => 8,
+        LATEST_TIERED_TIMESTAMP => 9,
+        _ => 0,
+    };
+    if version.version() < minimum_version {
+        return ListOffsetsPartitionResponse::new(partition_index, ErrorCode::UnsupportedVersion);
+    }
+
+    // only followers (which have a replica id) can see past the high watermark
+    let is_follower = request.replica_id >= 0;
+    match find_offset(&log, partition.timestamp, request.isolation_level, is_follower) {
+        Ok(found) => ListOffsetsPartitionResponse {
+            timestamp: found.map_or(-1, |found| found.timestamp()),
+            offset: found.map_or(-1, |found| found.offset()),
+            leader_epoch: found.and_then(|found| found.leader_epoch()).unwrap_or(-1),
+            ..ListOffsetsPartitionResponse::new(partition_index, ErrorCode::NoError)
+        },
+        Err(err) => {
+            error!(%topic_partition, %err, "Failed to list offsets");
+            ListOffsetsPartitionResponse::new(partition_index, ErrorCode::KafkaStorageError)
+        }
+    }
+}
+
+/// Find the offset for the timestamp, which is either one of the special timestamps or a time to search for
+fn find_offset(log: &Log, timestamp: i64, isolation_level: IsolationLevel, is_follower: bool) -> std::io::Result<Option<TimestampAndOffset>> {
+    match timestamp {
+        // there's no remote storage, so the local log starts at the same offset as the whole log
+        EARLIEST_TIMESTAMP | EARLIEST_LOCAL_TIMESTAMP => {
+            let offset = log.log_start_offset();
+            Ok(Some(TimestampAndOffset::new(-1, offset, log.epoch_for_offset(offset)?)))
+        }
+        LATEST_TIMESTAMP => {
+            let offset = match (is_follower, isolation_level) {
+                (true, _) => log.log_end_offset(),
+                (false, IsolationLevel::ReadCommitted) => log.last_stable_offset(),
+                (false, IsolationLevel::ReadUncommitted) => log.high_watermark(),
+            };
+            Ok(Some(TimestampAndOffset::new(-1, offset, log.latest_epoch()?)))
+        }
+        MAX_TIMESTAMP => log.offset_of_max_timestamp(),
+        // nothing is ever tiered
+        LATEST_TIERED_TIMESTAMP => Ok(None),
+        _ => {
+            let found = log.find_offset_by_timestamp(timestamp)?;
+            // consumers shouldn't be told about offsets they aren't allowed to read yet
+            let max_offset = match isolation_level {
+                IsolationLevel::ReadCommitted => log.last_stable_offset(),
+                IsolationLevel::ReadUncommitted => log.high_watermark(),
+            };
+            Ok(found.filter(|found| is_follower || found.offset() < max_offset))
+        }
+    }
+}
+
+impl ApiResponse for ListOffsetsResponse {}
+
+impl ToKafkaBytes for ListOffsetsResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        if version.version() >= 2 {
+            self.throttle_time_ms.write_kafka_bytes(buf);
+        }
+        self.topics.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct ListOffsetsTopicResponse {
+    name: String,
+    partitions: Vec<ListOffsetsPartitionResponse>,
+}
+
+impl ToVersionedKafkaBytes for ListOffsetsTopicResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.name.write_versioned_kafka_bytes(buf, version);
+        self.partitions.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct ListOffsetsPartitionResponse {
+    partition_index: i32,
+    error_code: ErrorCode,
+    /// Only used in version 0, which can return multiple offsets
+    old_style_offsets: Vec<i64>,
+    timestamp: i64,
+    offset: i64,
+    leader_epoch: i32,
+}
+
+impl ListOffsetsPartitionResponse {
+    /// A response without any offsets, which is all there is for errors
+    fn new(partition_index: i32, error_code: ErrorCode) -> Self {
+        ListOffsetsPartitionResponse {
+            partition_index,
+            error_code,
+            old_style_offsets: Vec::new(),
+            timestamp: -1,
+            offset: -1,
+            leader_epoch: -1,
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for ListOffsetsPartitionResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.partition_index.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        if version.version() == 0 {
+            self.old_style_offsets.write_versioned_kafka_bytes(buf, version);
+        } else {
+            self.timestamp.write_kafka_bytes(buf);
+            self.offset.write_kafka_bytes(buf);
+        }
+        if version.version() >= 4 {
+            self.leader_epoch.write_kafka_bytes(buf);
+        }
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::api_key::ApiKey;
+    use crate::api::request::ApiRequest;
+    use crate::storage::record_batch::RecordBatchBuilder;
+    use crate::testing::{open_broker, parse_request, TempDir};
+
+    /// List the offsets of partitions of `events` for their timestamps, where v0 asks for up to 10 offsets
+    fn list_offsets(broker: &Broker, version: i16, isolation_level: i8, partitions: &[(i32, i64)]) -> Vec<ListOffsetsPartitionResponse> {
+        let flexible = version >= 6;
+        let mut body = (-1i32).to_be_bytes().to_vec();
+        if version >= 2 {
+            body.push(isolation_level as u8);
+        }
+        // flexible versions have compact arrays and strings, whose lengths are varints of one more than the length
+        match flexible {
+            true => body.extend([2, 7]),
+            false => {
+                body.extend(1i32.to_be_bytes());
+                body.extend(6i16.to_be_bytes());
+            }
+        }
+        body.extend(b"events");
+        match flexible {
+            true => body.push(partitions.len() as u8 + 1),
+            false => body.extend((partitions.len() as i32).to_be_bytes()),
+        }
+        for (partition, timestamp) in partitions {
+            body.extend(partition.to_be_bytes());
+            if version >= 4 {
+                body.extend((-1i32).to_be_bytes());
+            }
+            body.extend(timestamp.to_be_bytes());
+            if version == 0 {
+                body.extend(10i32.to_be_bytes());
+            }
+            if flexible {
+                body.push(0);
+            }
+        }
+        if flexible {
+            body.extend([0, 0]);
+        }
+        let request = parse_request(ApiKey::ListOffsets, version, &body);
+        let ApiRequest::ListOffsets(list_offsets) = request.api_request() else {
+            panic!("expected a ListOffsets request, got {:?}", request.api_request());
+        };
+        let mut response = ListOffsetsResponse::process_request(&request, list_offsets, broker);
+        response.topics.remove(0).partitions
+    }
+
+    /// A broker where `events` has records at offsets 0, 1 and 2 with the timestamps 1000, 3000 and 2000
+    fn open_broker_with_records() -> (Broker, TempDir) {
+        let (broker, log_dir) = open_broker("");
+        broker.auto_create_topic("events").unwrap();
+        let log = broker.log_manager().get_log(&TopicPartition::new("events", 0)).unwrap().unwrap();
+        for timestamp in [1000, 3000, 2000] {
+            log.append(RecordBatchBuilder::new().add_record(timestamp, None, Some(b"value".to_vec())).build(), 0).unwrap();
+        }
+        (broker, log_dir)
+    }
+
+    /// List the offset of partition 0 for the timestamp, as the only partition in the request
+    fn list_offset(broker: &Broker, version: i16, isolation_level: i8, timestamp: i64) -> ListOffsetsPartitionResponse {
+        list_offsets(broker, version, isolation_level, &[(0, timestamp)]).remove(0)
+    }
+
+    fn found(response: &ListOffsetsPartitionResponse) -> (ErrorCode, i64, i64) {
+        (response.error_code, response.timestamp, response.offset)
+    }
+
+    #[test]
+    fn test_list_offsets() {
+        let (broker, _log_dir) = open_broker_with_records();
+        let cases = [
+            (LATEST_TIMESTAMP, (ErrorCode::NoError, -1, 3)),
+            (EARLIEST_TIMESTAMP, (ErrorCode::NoError, -1, 0)),
+            (MAX_TIMESTAMP, (ErrorCode::NoError, 3000, 1)),
+            (EARLIEST_LOCAL_TIMESTAMP, (ErrorCode::NoError, -1, 0)),
+            (LATEST_TIERED_TIMESTAMP, (ErrorCode::NoError, -1, -1)),
+            (1500, (ErrorCode::NoError, 3000, 1)),
+            (5000, (ErrorCode::NoError, -1, -1)),
+        ];
+        for (timestamp, expected) in cases {
+            assert_eq!(found(&list_offset(&broker, 9, 0, timestamp)), expected, "timestamp {timestamp}");
+        }
+        assert_eq!(list_offset(&broker, 9, 0, LATEST_TIMESTAMP).leader_epoch, 0);
+        let unknown = list_offsets(&broker, 9, 0, &[(1, LATEST_TIMESTAMP)]);
+        assert_eq!(unknown[0].error_code, ErrorCode::UnknownTopicOrPartition);
+
+        // each special timestamp is only supported from the version that added it
+        assert_eq!(list_offset(&broker, 6, 0, MAX_TIMESTAMP).error_code, ErrorCode::UnsupportedVersion);
+        assert_eq!(list_offset(&broker, 7, 0, EARLIEST_LOCAL_TIMESTAMP).error_code, ErrorCode::UnsupportedVersion);
+        assert_eq!(list_offset(&broker, 8, 0, LATEST_TIERED_TIMESTAMP).error_code, ErrorCode::UnsupportedVersion);
+    }
+
+    #[test]
+    fn test_read_committed_stops_at_last_stable_offset() {
+        let (broker, _log_dir) = open_broker_with_records();
+        let log = broker.log_manager().get_log(&TopicPartition::new("events", 0)).unwrap().unwrap();
+        let batch = RecordBatchBuilder::new()
+            .transactional(1000, 0)
+            .add_record(4000, None, Some(b"in a transaction".to_vec()))
+            .build();
+        log.append(batch, 0).unwrap();
+
+        assert_eq!(found(&list_offset(&broker, 5, 0, LATEST_TIMESTAMP)), (ErrorCode::NoError, -1, 4));
+        assert_eq!(found(&list_offset(&broker, 5, 0, 4000)), (ErrorCode::NoError, 4000, 3));
+        // the open transaction's records can't be read yet, so they aren't found by their timestamp either
+        assert_eq!(found(&list_offset(&broker, 5, 1, LATEST_TIMESTAMP)), (ErrorCode::NoError, -1, 3));
+        assert_eq!(found(&list_offset(&broker, 5, 1, 4000)), (ErrorCode::NoError, -1, -1));
+    }
+
+    #[test]
+    fn test_old_style_offsets() {
+        let (broker, _log_dir) = open_broker_with_records();
+        assert_eq!(list_offset(&broker, 0, 0, LATEST_TIMESTAMP).old_style_offsets, vec![3, 0]);
+        assert_eq!(list_offset(&broker, 0, 0, EARLIEST_TIMESTAMP).old_style_offsets, vec![0]);
+        // the only segment has a record after the timestamp, so only the log end offset is after it
+        assert_eq!(list_offset(&broker, 0, 0, 2500).old_style_offsets, Vec::<i64>::new());
+        assert_eq!(list_offset(&broker, 0, 0, 3000).old_style_offsets, vec![0]);
+    }
+
+    #[test]
+    fn test_duplicate_partitions_are_rejected() {
+        let (broker, _log_dir) = open_broker_with_records();
+        let responses = list_offsets(&broker, 5, 0, &[(0, LATEST_TIMESTAMP), (1, LATEST_TIMESTAMP), (0, EARLIEST_TIMESTAMP)]);
+        let error_codes: Vec<_> = responses.iter().map(|response| response.error_code).collect();
+        assert_eq!(error_codes, vec![ErrorCode::InvalidRequest, ErrorCode::UnknownTopicOrPartition, ErrorCode::InvalidRequest]);
+    }
+}
@@ -0,0 +1,121 @@
+use bytes::{Buf, BufMut};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::coordinator::transaction::transaction_metadata::TransactionState;
+use crate::coordinator::transaction::ListedTransaction;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+use crate::time::now_ms;
+
+#[derive(Debug)]
+pub struct ListTransactionsRequest {
+    /// Only list transactions in these states, or in any state if it's empty
+    state_filters: Vec<String>,
+    /// Only list transactions of these producers, or of any producer if it's empty
+    producer_id_filters: Vec<i64>,
+    /// Only list transactions that have been running for longer than this, or every transaction if it's negative
+    duration_filter_ms: i64,
+}
+
+impl ReadVersionedKafkaBytes for ListTransactionsRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let state_filters = Vec::read_versioned_kafka_bytes(buf, version)?;
+        let producer_id_filters = Vec::read_versioned_kafka_bytes(buf, version)?;
+        let duration_filter_ms = match version.version() {
+            1.. => i64::read_kafka_bytes(buf)?,
+            _ => -1,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(ListTransactionsRequest { state_filters, producer_id_filters, duration_filter_ms })
+    }
+}
+
+impl ListTransactionsRequest {
+    fn matches(&self, transaction: &ListedTransaction, now: i64) -> bool {
+        (self.state_filters.is_empty() || self.state_filters.iter().any(|state| state == transaction.state.name()))
+            && (self.producer_id_filters.is_empty() || self.producer_id_filters.contains(&transaction.producer_id))
+            && (self.duration_filter_ms < 0 || now - transaction.start_timestamp > self.duration_filter_ms)
+    }
+}
+
+#[derive(Debug)]
+pub struct ListTransactionsResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    error_code: ErrorCode,
+    /// The state filters that aren't the name of any state
+    unknown_state_filters: Vec<String>,
+    transaction_states: Vec<ListedTransactionResponse>,
+}
+
+impl ListTransactionsResponse {
+    pub fn process_request(request: &KafkaRequest, list_transactions: &ListTransactionsRequest, broker: &Broker) -> Self {
+        let now = now_ms();
+        let transaction_states = broker.transaction_coordinator()
+            .list_transactions()
+            .into_iter()
+            .filter(|transaction| list_transactions.matches(transaction, now))
+            .map(ListedTransactionResponse::from)
+            .collect();
+        let unknown_state_filters = list_transactions.state_filters.iter()
+            .filter(|state| !TransactionState::is_valid_name(state))
+            .cloned()
+            .collect();
+
+        ListTransactionsResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            error_code: ErrorCode::NoError,
+            unknown_state_filters,
+            transaction_states,
+        }
+    }
+}
+
+impl ApiResponse for ListTransactionsResponse {
+    fn error_code(&self) -> Option<ErrorCode> {
+        Some(self.error_code)
+    }
+}
+
+impl ToKafkaBytes for ListTransactionsResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        self.unknown_state_filters.write_versioned_kafka_bytes(buf, version);
+        self.transaction_states.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct ListedTransactionResponse {
+    transactional_id: String,
+    producer_id: i64,
+    transaction_state: String,
+}
+
+impl From<ListedTransaction> for ListedTransactionResponse {
+    fn from(transaction: ListedTransaction) -> Self {
+        ListedTransactionResponse {
+            transactional_id: transaction.transactional_id,
+            producer_id: transaction.producer_id,
+            transaction_state: transaction.state.name().to_string(),
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for ListedTransactionResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.transactional_id.write_versioned_kafka_bytes(buf, version);
+        self.producer_id.write_kafka_bytes(buf);
+        self.transaction_state.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
@@ -0,0 +1,367 @@
+use std::collections::HashSet;
+use bytes::{Buf, BufMut};
+use uuid::Uuid;
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::broker::topics::{is_internal_topic, TopicError};
+use crate::metadata::image::{PartitionMetadata, TopicMetadata};
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+/// Authorized operations are only included when they're asked for, otherwise this is sent instead
+pub const AUTHORIZED_OPERATIONS_OMITTED: i32 = i32::MIN;
+/// There's no authorizer, so every operation on topics is allowed:
+/// READ, WRITE, CREATE, DELETE, ALTER, DESCRIBE, DESCRIBE_CONFIGS and ALTER_CONFIGS
+const TOPIC_AUTHORIZED_OPERATIONS: i32 = 1 << 3 | 1 << 4 | 1 << 5 | 1 << 6 | 1 << 7 | 1 << 8 | 1 << 10 | 1 << 11;
+/// CREATE, ALTER, DESCRIBE, CLUSTER_ACTION, DESCRIBE_CONFIGS, ALTER_CONFIGS and IDEMPOTENT_WRITE
+const CLUSTER_AUTHORIZED_OPERATIONS: i32 = 1 << 5 | 1 << 7 | 1 << 8 | 1 << 9 | 1 << 10 | 1 << 11 | 1 << 12;
+
+#[derive(Debug)]
+pub struct MetadataRequest {
+    /// None requests the metadata of every topic
+    topics: Option<Vec<MetadataRequestTopic>>,
+    allow_auto_topic_creation: bool,
+    include_cluster_authorized_operations: bool,
+    include_topic_authorized_operations: bool,
+}
+
+impl ReadVersionedKafkaBytes for MetadataRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let topics = match version.version() {
+            1.. => Option::<Vec<_>>::read_versioned_kafka_bytes(buf, version)?,
+            // v0 can't send a null array, so asks for every topic with an empty one instead
+            _ => Some(Vec::read_versioned_kafka_bytes(buf, version)?)
+                .filter(|topics: &Vec<_>| !topics.is_empty()),
+        };
+        let allow_auto_topic_creation = match version.version() {
+            4.. => bool::read_kafka_bytes(buf)?,
+            _ => true,
+        };
+        let include_cluster_authorized_operations = match version.version() {
+            8..=10 => bool::read_kafka_bytes(buf)?,
+            _ => false,
+        };
+        let include_topic_authorized_operations = match version.version() {
+            8.. => bool::read_kafka_bytes(buf)?,
+            _ => false,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(MetadataRequest {
+            topics,
+            allow_auto_topic_creation,
+            include_cluster_authorized_operations,
+            include_topic_authorized_operations,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct MetadataRequestTopic {
+    /// Nil unless the topic is requested by id
+    topic_id: Uuid,
+    name: Option<String>,
+}
+
+impl ReadVersionedKafkaBytes for MetadataRequestTopic {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let topic_id = match version.version() {
+            10.. => Uuid::read_kafka_bytes(buf)?,
+            _ => Uuid::nil(),
+        };
+        let name = Option::<String>::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(MetadataRequestTopic { topic_id, name })
+    }
+}
+
+#[derive(Debug)]
+pub struct MetadataResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    brokers: Vec<MetadataResponseBroker>,
+    cluster_id: Option<String>,
+    controller_id: i32,
+    topics: Vec<MetadataResponseTopic>,
+    cluster_authorized_operations: i32,
+}
+
+impl MetadataResponse {
+    pub fn process_request(request: &KafkaRequest, metadata: &MetadataRequest, broker: &Broker) -> Self {
+        let topic_authorized_operations = match metadata.include_topic_authorized_operations {
+            true => TOPIC_AUTHORIZED_OPERATIONS,
+            false => AUTHORIZED_OPERATIONS_OMITTED,
+        };
+        let version = request.message_version().version();
+
+        let mut topics: Vec<MetadataResponseTopic> = match &metadata.topics {
+            None => broker.metadata().image()
+                .topics()
+                .map(MetadataResponseTopic::from)
+                .collect(),
+            Some(requested) => {
+                let mut seen = HashSet::new();
+                requested.iter()
+                    .filter(|topic| seen.insert((topic.topic_id, topic.name.as_deref())))
+                    .map(|topic| match &topic.name {
+                        Some(name) => Self::topic_by_name(name, metadata.allow_auto_topic_creation, broker),
+                        // topics can only be requested by id from v12
+                        None if version < 12 || topic.topic_id.is_nil() => {
+                            MetadataResponseTopic::error(ErrorCode::InvalidRequest, None, topic.topic_id)
+                        }
+                        None => match broker.metadata().image().topic_by_id(&topic.topic_id) {
+                            Some(found) => MetadataResponseTopic::from(found),
+                            None => MetadataResponseTopic::error(ErrorCode::UnknownTopicId, None, topic.topic_id),
+                        },
+                    })
+                    .collect()
+            }
+        };
+        for topic in &mut topics {
+            topic.topic_authorized_operations = topic_authorized_operations;
+        }
+
+        let endpoint = broker.config().advertised_listener();
+        MetadataResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            brokers: vec![MetadataResponseBroker {
+                node_id: broker.config().node_id(),
+                host: endpoint.advertised_host().to_string(),
+                port: endpoint.port() as i32,
+                rack: None,
+            }],
+            cluster_id: Some(broker.cluster_id().to_string()),
+            // the broker is also the cluster's only controller
+            controller_id: broker.config().node_id(),
+            topics,
+            cluster_authorized_operations: match metadata.include_cluster_authorized_operations {
+                true => CLUSTER_AUTHORIZED_OPERATIONS,
+                false => AUTHORIZED_OPERATIONS_OMITTED,
+            },
+        }
+    }
+
+    /// The metadata of the topic, creating it if it doesn't exist and auto creation is enabled
+    fn topic_by_name(name: &str, allow_auto_topic_creation: bool, broker: &Broker) -> MetadataResponseTopic {
+        if let Some(topic) = broker.metadata().image().topic(name) {
+            return MetadataResponseTopic::from(topic);
+        }
+        if !allow_auto_topic_creation || !broker.config().auto_create_topics_enable() {
+            return MetadataResponseTopic::error(ErrorCode::UnknownTopicOrPartition, Some(name.to_string()), Uuid::nil());
+        }
+        // like kafka, the topic's leader isn't reported until the next request
+        let error_code = match broker.auto_create_topic(name) {
+            Ok(_) | Err(TopicError::TopicAlreadyExists(_)) => ErrorCode::LeaderNotAvailable,
+            Err(err) => ErrorCode::from(&err),
+        };
+        MetadataResponseTopic::error(error_code, Some(name.to_string()), Uuid::nil())
+    }
+}
+
+impl ApiResponse for MetadataResponse {}
+
+impl ToKafkaBytes for MetadataResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        if version.version() >= 3 {
+            self.throttle_time_ms.write_kafka_bytes(buf);
+        }
+        self.brokers.write_versioned_kafka_bytes(buf, version);
+        if version.version() >= 2 {
+            self.cluster_id.write_versioned_kafka_bytes(buf, version);
+        }
+        if version.version() >= 1 {
+            self.controller_id.write_kafka_bytes(buf);
+        }
+        self.topics.write_versioned_kafka_bytes(buf, version);
+        if (8..=10).contains(&version.version()) {
+            self.cluster_authorized_operations.write_kafka_bytes(buf);
+        }
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct MetadataResponseBroker {
+    node_id: i32,
+    host: String,
+    port: i32,
+    rack: Option<String>,
+}
+
+impl ToVersionedKafkaBytes for MetadataResponseBroker {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.node_id.write_kafka_bytes(buf);
+        self.host.write_versioned_kafka_bytes(buf, version);
+        self.port.write_kafka_bytes(buf);
+        if version.version() >= 1 {
+            self.rack.write_versioned_kafka_bytes(buf, version);
+        }
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct MetadataResponseTopic {
+    error_code: ErrorCode,
+    /// Only null from v12, when a topic that doesn't exist was requested by id
+    name: Option<String>,
+    topic_id: Uuid,
+    is_internal: bool,
+    partitions: Vec<MetadataResponsePartition>,
+    topic_authorized_operations: i32,
+}
+
+impl MetadataResponseTopic {
+    fn error(error_code: ErrorCode, name: Option<String>, topic_id: Uuid) -> Self {
+        MetadataResponseTopic {
+            error_code,
+            name,
+            topic_id,
+            is_internal: false,
+            partitions: Vec::new(),
+            topic_authorized_operations: AUTHORIZED_OPERATIONS_OMITTED,
+        }
+    }
+}
+
+impl From<&TopicMetadata> for MetadataResponseTopic {
+    fn from(topic: &TopicMetadata) -> Self {
+        MetadataResponseTopic {
+            error_code: ErrorCode::NoError,
+            name: Some(topic.name().to_string()),
+            topic_id: topic.topic_id(),
+            is_internal: is_internal_topic(topic.name()),
+            partitions: topic.partitions().map(MetadataResponsePartition::from).collect(),
+            topic_authorized_operations: AUTHORIZED_OPERATIONS_OMITTED,
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for MetadataResponseTopic {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.error_code.write_kafka_bytes(buf);
+        match version.version() {
+            12.. => self.name.write_versioned_kafka_bytes(buf, version),
+            _ => self.name.unwrap_or_default().write_versioned_kafka_bytes(buf, version),
+        }
+        if version.version() >= 10 {
+            self.topic_id.write_kafka_bytes(buf);
+        }
+        if version.version() >= 1 {
+            self.is_internal.write_kafka_bytes(buf);
+        }
+        self.partitions.write_versioned_kafka_bytes(buf, version);
+        if version.version() >= 8 {
+            self.topic_authorized_operations.write_kafka_bytes(buf);
+        }
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct MetadataResponsePartition {
+    error_code: ErrorCode,
+    partition_index: i32,
+    leader_id: i32,
+    leader_epoch: i32,
+    replica_nodes: Vec<i32>,
+    isr_nodes: Vec<i32>,
+    offline_replicas: Vec<i32>,
+}
+
+impl From<&PartitionMetadata> for MetadataResponsePartition {
+    fn from(partition: &PartitionMetadata) -> Self {
+        MetadataResponsePartition {
+            error_code: ErrorCode::NoError,
+            partition_index: partition.partition_index(),
+            leader_id: partition.leader(),
+            leader_epoch: partition.leader_epoch(),
+            replica_nodes: partition.replicas().to_vec(),
+            isr_nodes: partition.isr().to_vec(),
+            offline_replicas: Vec::new(),
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for MetadataResponsePartition {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.error_code.write_kafka_bytes(buf);
+        self.partition_index.write_kafka_bytes(buf);
+        self.leader_id.write_kafka_bytes(buf);
+        if version.version() >= 7 {
+            self.leader_epoch.write_kafka_bytes(buf);
+        }
+        self.replica_nodes.write_versioned_kafka_bytes(buf, version);
+        self.isr_nodes.write_versioned_kafka_bytes(buf, version);
+        if version.version() >= 5 {
+            self.offline_replicas.write_versioned_kafka_bytes(buf, version);
+        }
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::api_key::ApiKey;
+    use crate::api::request::ApiRequest;
+    use crate::storage::topic_partition::TopicPartition;
+    use crate::testing::{open_broker, parse_request};
+
+    /// Request the metadata of the topics with a v4 request, which is the first that can disallow auto creation
+    fn metadata(broker: &Broker, topics: &[&str], allow_auto_topic_creation: bool) -> MetadataResponse {
+        let mut body = (topics.len() as i32).to_be_bytes().to_vec();
+        for name in topics {
+            body.extend((name.len() as i16).to_be_bytes());
+            body.extend(name.as_bytes());
+        }
+        body.push(allow_auto_topic_creation as u8);
+        let request = parse_request(ApiKey::Metadata, 4, &body);
+        let ApiRequest::Metadata(metadata) = request.api_request() else {
+            panic!("expected a Metadata request, got {:?}", request.api_request());
+        };
+        MetadataResponse::process_request(&request, metadata, broker)
+    }
+
+    #[test]
+    fn test_auto_create_topic() {
+        let (broker, _log_dir) = open_broker("num.partitions=3");
+        let response = metadata(&broker, &["events"], true);
+        assert_eq!(response.topics[0].error_code, ErrorCode::LeaderNotAvailable);
+        assert!(response.topics[0].partitions.is_empty());
+        assert_eq!(broker.metadata().image().topic("events").unwrap().num_partitions(), 3);
+        for partition in 0..3 {
+            assert!(broker.log_manager().get_log(&TopicPartition::new("events", partition)).unwrap().is_some());
+        }
+
+        // the leader is reported from the next request
+        let response = metadata(&broker, &["events"], true);
+        assert_eq!(response.topics[0].error_code, ErrorCode::NoError);
+        assert_eq!(response.topics[0].partitions.len(), 3);
+        assert_eq!(response.topics[0].partitions[0].leader_id, broker.config().node_id());
+    }
+
+    #[test]
+    fn test_auto_create_topic_disallowed() {
+        let (broker, _log_dir) = open_broker("");
+        let response = metadata(&broker, &["events"], false);
+        assert_eq!(response.topics[0].error_code, ErrorCode::UnknownTopicOrPartition);
+        assert!(broker.metadata().image().topic("events").is_none());
+        assert!(broker.log_manager().get_log(&TopicPartition::new("events", 0)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_auto_create_topics_disabled() {
+        let (broker, _log_dir) = open_broker("auto.create.topics.enable=false");
+        let response = metadata(&broker, &["events"], true);
+        assert_eq!(response.topics[0].error_code, ErrorCode::UnknownTopicOrPartition);
+        assert!(broker.metadata().image().topic("events").is_none());
+        assert!(broker.log_manager().get_log(&TopicPartition::new("events", 0)).unwrap().is_none());
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use bytes::{Buf, BufMut};
+use tracing::error;
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::coordinator::group::offsets::OffsetAndMetadata;
+use crate::coordinator::group::{GroupError, OffsetCommitParams};
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+use crate::storage::topic_partition::TopicPartition;
+use crate::time::now_ms;
+
+/// The commit timestamp that means the offset was committed when the coordinator received it
+const DEFAULT_TIMESTAMP: i64 = -1;
+
+#[derive(Debug)]
+pub struct OffsetCommitRequest {
+    group_id: String,
+    generation_id: i32,
+    member_id: String,
+    group_instance_id: Option<String>,
+    topics: Vec<OffsetCommitTopic>,
+}
+
+impl ReadVersionedKafkaBytes for OffsetCommitRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let group_id = String::read_versioned_kafka_bytes(buf, version)?;
+        // v0 commits are always from outside the group, since they don't say which member they're from
+        let (generation_id, member_id) = match version.version() {
+            1.. => (i32::read_kafka_bytes(buf)?, String::read_versioned_kafka_bytes(buf, version)?),
+            _ => (-1, String::new()),
+        };
+        let group_instance_id = match version.version() {
+            7.. => Option::<String>::read_versioned_kafka_bytes(buf, version)?,
+            _ => None,
+        };
+        if (2..=4).contains(&version.version()) {
+            // offsets are kept for as long as their group is, rather than for a retention time chosen by each commit
+            let _retention_time_ms = i64::read_kafka_bytes(buf)?;
+        }
+        let topics = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(OffsetCommitRequest { group_id, generation_id, member_id, group_instance_id, topics })
+    }
+}
+
+#[derive(Debug)]
+struct OffsetCommitTopic {
+    name: String,
+    partitions: Vec<OffsetCommitPartition>,
+}
+
+impl ReadVersionedKafkaBytes for OffsetCommitTopic {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let name = String::read_versioned_kafka_bytes(buf, version)?;
+        let partitions = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(OffsetCommitTopic { name, partitions })
+    }
+}
+
+#[derive(Debug)]
+struct OffsetCommitPartition {
+    partition_index: i32,
+    committed_offset: i64,
+    committed_leader_epoch: i32,
+    /// Only v1 lets the client choose when the offset was committed
+    commit_timestamp: i64,
+    committed_metadata: Option<String>,
+}
+
+impl ReadVersionedKafkaBytes for OffsetCommitPartition {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let partition_index = i32::read_kafka_bytes(buf)?;
+        let committed_offset = i64::read_kafka_bytes(buf)?;
+        let committed_leader_epoch = match version.version() {
+            6.. => i32::read_kafka_bytes(buf)?,
+            _ => -1,
+        };
+        let commit_timestamp = match version.version() {
+            1 => i64::read_kafka_bytes(buf)?,
+            _ => DEFAULT_TIMESTAMP,
+        };
+        let committed_metadata = Option::<String>::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(OffsetCommitPartition { partition_index, committed_offset, committed_leader_epoch, commit_timestamp, committed_metadata })
+    }
+}
+
+#[derive(Debug)]
+pub struct OffsetCommitResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    topics: Vec<OffsetCommitTopicResponse>,
+}
+
+impl OffsetCommitResponse {
+    pub fn process_request(request: &KafkaRequest, offset_commit: &OffsetCommitRequest, broker: &Broker) -> Self {
+        let now = now_ms();
+        // offsets can only be committed for partitions that exist
+        let offsets: Vec<(TopicPartition, OffsetAndMetadata)> = {
+            let image = broker.metadata().image();
+            offset_commit.topics.iter()
+                .filter_map(|topic| image.topic(&topic.name).map(|metadata| (topic, metadata)))
+                .flat_map(|(topic, metadata)| {
+                    topic.partitions.iter()
+                        .filter(|partition| metadata.partition(partition.partition_index).is_some())
+                        .map(|partition| (TopicPartition::new(topic.name.clone(), partition.partition_index), offset_and_metadata(partition, now)))
+                })
+                .collect()
+        };
+        let committing: Vec<TopicPartition> = offsets.iter().map(|(topic_partition, _)| topic_partition.clone()).collect();
+
+        let commit = OffsetCommitParams {
+            group_id: offset_commit.group_id.clone(),
+            generation_id: offset_commit.generation_id,
+            member_id: offset_commit.member_id.clone(),
+            group_instance_id: offset_commit.group_instance_id.clone(),
+            offsets,
+        };
+        let error_codes: HashMap<TopicPartition, ErrorCode> = match broker.group_coordinator().commit_offsets(commit) {
+            Ok(results) => results.into_iter()
+                .map(|(topic_partition, result)| (topic_partition, result.as_ref().err().map_or(ErrorCode::NoError, ErrorCode::from)))
+                .collect(),
+            // there's no error for the whole request, so every partition gets the group's error
+            Err(err) => {
+                if let GroupError::Storage(err) = &err {
+                    error!(group_id = %offset_commit.group_id, %err, "Failed to store offsets");
+                }
+                let error_code = ErrorCode::from(&err);
+                committing.into_iter().map(|topic_partition| (topic_partition, error_code)).collect()
+            }
+        };
+
+        let topics = offset_commit.topics.iter()
+            .map(|topic| OffsetCommitTopicResponse {
+                name: topic.name.clone(),
+                partitions: topic.partitions.iter()
+                    .map(|partition| OffsetCommitPartitionResponse {
+                        partition_index: partition.partition_index,
+                        error_code: error_codes.get(&TopicPartition::new(topic.name.clone(), partition.partition_index))
+                            .copied()
+                            .unwrap_or(ErrorCode::UnknownTopicOrPartition),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        OffsetCommitResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            topics,
+        }
+    }
+}
+
+fn offset_and_metadata(partition: &OffsetCommitPartition, now: i64) -> OffsetAndMetadata {
+    OffsetAndMetadata {
+        offset: partition.committed_offset,
+        leader_epoch: Some(partition.committed_leader_epoch).filter(|epoch| *epoch >= 0),
+        metadata: partition.committed_metadata.clone().unwrap_or_default(),
+        commit_timestamp: match partition.commit_timestamp {
+            DEFAULT_TIMESTAMP => now,
+            commit_timestamp => commit_timestamp,
+        },
+    }
+}
+
+impl ApiResponse for OffsetCommitResponse {}
+
+impl ToKafkaBytes for OffsetCommitResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        if version.version() >= 3 {
+            self.throttle_time_ms.write_kafka_bytes(buf);
+        }
+        self.topics.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct OffsetCommitTopicResponse {
+    name: String,
+    partitions: Vec<OffsetCommitPartitionResponse>,
+}
+
+impl ToVersionedKafkaBytes for OffsetCommitTopicResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.name.write_versioned_kafka_bytes(buf, version);
+        self.partitions.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct OffsetCommitPartitionResponse {
+    partition_index: i32,
+    error_code: ErrorCode,
+}
+
+impl ToVersionedKafkaBytes for OffsetCommitPartitionResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.partition_index.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, version);
+    }
+}
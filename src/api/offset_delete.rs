@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use bytes::{Buf, BufMut};
+use tracing::error;
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::coordinator::group::GroupError;
+use crate::serialisation::{MessageVersion, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+use crate::storage::topic_partition::TopicPartition;
+
+#[derive(Debug)]
+pub struct OffsetDeleteRequest {
+    group_id: String,
+    topics: Vec<OffsetDeleteRequestTopic>,
+}
+
+impl ReadVersionedKafkaBytes for OffsetDeleteRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let group_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let topics = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(OffsetDeleteRequest { group_id, topics })
+    }
+}
+
+#[derive(Debug)]
+struct OffsetDeleteRequestTopic {
+    name: String,
+    partitions: Vec<i32>,
+}
+
+impl ReadVersionedKafkaBytes for OffsetDeleteRequestTopic {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let name = String::read_versioned_kafka_bytes(buf, version)?;
+        let partitions = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(OffsetDeleteRequestTopic { name, partitions })
+    }
+}
+
+#[derive(Debug)]
+pub struct OffsetDeleteResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    error_code: ErrorCode,
+    throttle_time_ms: i32,
+    topics: Vec<OffsetDeleteResponseTopic>,
+}
+
+impl OffsetDeleteResponse {
+    pub fn process_request(request: &KafkaRequest, offset_delete: &OffsetDeleteRequest, broker: &Broker) -> Self {
+        // only the offsets of partitions that exist can be deleted
+        let partitions: Vec<TopicPartition> = {
+            let image = broker.metadata().image();
+            offset_delete.topics.iter()
+                .filter_map(|topic| image.topic(&topic.name).map(|metadata| (topic, metadata)))
+                .flat_map(|(topic, metadata)| {
+                    topic.partitions.iter()
+                        .filter(|partition| metadata.partition(**partition).is_some())
+                        .map(|partition| TopicPartition::new(topic.name.clone(), *partition))
+                })
+                .collect()
+        };
+
+        let (error_code, topics) = match broker.group_coordinator().delete_offsets(&offset_delete.group_id, partitions) {
+            Ok(results) => {
+                let error_codes: HashMap<TopicPartition, ErrorCode> = results.into_iter()
+                    .map(|(topic_partition, result)| (topic_partition, result.as_ref().err().map_or(ErrorCode::NoError, ErrorCode::from)))
+                    .collect();
+                let topics = offset_delete.topics.iter()
+                    .map(|topic| OffsetDeleteResponseTopic {
+                        name: topic.name.clone(),
+                        partitions: topic.partitions.iter()
+                            .map(|partition| OffsetDeleteResponsePartition {
+                                partition_index: *partition,
+                                error_code: error_codes.get(&TopicPartition::new(topic.name.clone(), *partition))
+                                    .copied()
+                                    .unwrap_or(ErrorCode::UnknownTopicOrPartition),
+                            })
+                            .collect(),
+                    })
+                    .collect();
+                (ErrorCode::NoError, topics)
+            }
+            Err(err) => {
+                if let GroupError::Storage(err) = &err {
+                    error!(group_id = %offset_delete.group_id, %err, "Failed to delete offsets");
+                }
+                (ErrorCode::from(&err), Vec::new())
+            }
+        };
+
+        OffsetDeleteResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            error_code,
+            throttle_time_ms: 0,
+            topics,
+        }
+    }
+}
+
+impl ApiResponse for OffsetDeleteResponse {
+    fn error_code(&self) -> Option<ErrorCode> {
+        Some(self.error_code)
+    }
+}
+
+impl ToKafkaBytes for OffsetDeleteResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        self.topics.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct OffsetDeleteResponseTopic {
+    name: String,
+    partitions: Vec<OffsetDeleteResponsePartition>,
+}
+
+impl ToVersionedKafkaBytes for OffsetDeleteResponseTopic {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.name.write_versioned_kafka_bytes(buf, version);
+        self.partitions.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct OffsetDeleteResponsePartition {
+    partition_index: i32,
+    error_code: ErrorCode,
+}
+
+impl ToVersionedKafkaBytes for OffsetDeleteResponsePartition {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.partition_index.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, version);
+    }
+}
@@ -0,0 +1,304 @@
+use bytes::{Buf, BufMut};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::coordinator::group::offsets::OffsetAndMetadata;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+use crate::storage::topic_partition::TopicPartition;
+
+#[derive(Debug)]
+pub struct OffsetFetchRequest {
+    /// Before v8 the offsets of a single group are fetched, after that groups can be fetched in batches
+    groups: Vec<OffsetFetchGroup>,
+    /// Whether partitions with offsets pending in a transaction should get an error rather than their committed offset, from v7
+    require_stable: bool,
+}
+
+impl ReadVersionedKafkaBytes for OffsetFetchRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let groups = match version.version() {
+            8.. => Vec::read_versioned_kafka_bytes(buf, version)?,
+            _ => {
+                let group_id = String::read_versioned_kafka_bytes(buf, version)?;
+                let topics = match version.version() {
+                    2.. => Option::<Vec<OffsetFetchTopic>>::read_versioned_kafka_bytes(buf, version)?,
+                    _ => Some(Vec::read_versioned_kafka_bytes(buf, version)?),
+                };
+                vec![OffsetFetchGroup { group_id, member_id: None, member_epoch: -1, topics }]
+            }
+        };
+        let require_stable = match version.version() {
+            7.. => bool::read_kafka_bytes(buf)?,
+            _ => false,
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(OffsetFetchRequest { groups, require_stable })
+    }
+}
+
+#[derive(Debug)]
+struct OffsetFetchGroup {
+    group_id: String,
+    /// Members of consumer groups fetch with their member id and epoch, from v9
+    member_id: Option<String>,
+    /// -1 when the offsets aren't fetched by a member of the group
+    member_epoch: i32,
+    /// Null to fetch every offset the group has committed
+    topics: Option<Vec<OffsetFetchTopic>>,
+}
+
+impl ReadVersionedKafkaBytes for OffsetFetchGroup {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let group_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let (member_id, member_epoch) = match version.version() {
+            9.. => (Option::<String>::read_versioned_kafka_bytes(buf, version)?, i32::read_kafka_bytes(buf)?),
+            _ => (None, -1),
+        };
+        let topics = Option::<Vec<OffsetFetchTopic>>::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(OffsetFetchGroup { group_id, member_id, member_epoch, topics })
+    }
+}
+
+#[derive(Debug)]
+struct OffsetFetchTopic {
+    name: String,
+    partition_indexes: Vec<i32>,
+}
+
+impl ReadVersionedKafkaBytes for OffsetFetchTopic {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let name = String::read_versioned_kafka_bytes(buf, version)?;
+        let partition_indexes = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(OffsetFetchTopic { name, partition_indexes })
+    }
+}
+
+#[derive(Debug)]
+pub struct OffsetFetchResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    groups: Vec<OffsetFetchGroupResponse>,
+}
+
+impl OffsetFetchResponse {
+    pub fn process_request(request: &KafkaRequest, offset_fetch: &OffsetFetchRequest, broker: &Broker) -> Self {
+        let groups = offset_fetch.groups.iter()
+            .map(|group| fetch_group_offsets(request.api_version(), group, offset_fetch.require_stable, broker))
+            .collect();
+
+        OffsetFetchResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            groups,
+        }
+    }
+}
+
+fn fetch_group_offsets(api_version: i16, group: &OffsetFetchGroup, require_stable: bool, broker: &Broker) -> OffsetFetchGroupResponse {
+    let partitions = group.topics.as_ref().map(|topics| {
+        topics.iter()
+            .flat_map(|topic| topic.partition_indexes.iter().map(|partition| TopicPartition::new(topic.name.clone(), *partition)))
+            .collect::<Vec<_>>()
+    });
+    let offsets = broker.group_coordinator()
+        .fetch_offsets(&group.group_id, group.member_id.as_deref(), group.member_epoch, partitions.clone(), require_stable);
+    match offsets {
+        Ok(offsets) => OffsetFetchGroupResponse {
+            group_id: group.group_id.clone(),
+            topics: topic_responses(offsets.into_iter().map(|(topic_partition, offset)| {
+                let partition = match offset {
+                    Ok(offset) => OffsetFetchPartitionResponse::new(topic_partition.partition(), offset, ErrorCode::NoError),
+                    Err(err) => OffsetFetchPartitionResponse::new(topic_partition.partition(), None, ErrorCode::from(&err)),
+                };
+                (topic_partition, partition)
+            })),
+            error_code: ErrorCode::NoError,
+        },
+        Err(err) => {
+            let error_code = ErrorCode::from(&err);
+            // before v2 there's no error for the whole group, so every partition gets the group's error
+            let topics = match api_version {
+                0..=1 => topic_responses(partitions.unwrap_or_default().into_iter().map(|topic_partition| {
+                    let partition = OffsetFetchPartitionResponse::new(topic_partition.partition(), None, error_code);
+                    (topic_partition, partition)
+                })),
+                _ => Vec::new(),
+            };
+            OffsetFetchGroupResponse { group_id: group.group_id.clone(), topics, error_code }
+        }
+    }
+}
+
+/// Group the partitions' responses by their topic, keeping the partitions in the order they're given
+fn topic_responses(partitions: impl Iterator<Item = (TopicPartition, OffsetFetchPartitionResponse)>) -> Vec<OffsetFetchTopicResponse> {
+    partitions.fold(Vec::new(), |mut topics: Vec<OffsetFetchTopicResponse>, (topic_partition, partition)| {
+        match topics.last_mut() {
+            Some(topic) if topic.name == topic_partition.topic() => topic.partitions.push(partition),
+            _ => topics.push(OffsetFetchTopicResponse { name: topic_partition.topic().to_string(), partitions: vec![partition] }),
+        }
+        topics
+    })
+}
+
+impl ApiResponse for OffsetFetchResponse {}
+
+impl ToKafkaBytes for OffsetFetchResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        if version.version() >= 3 {
+            self.throttle_time_ms.write_kafka_bytes(buf);
+        }
+        match version.version() {
+            8.. => self.groups.write_versioned_kafka_bytes(buf, version),
+            _ => {
+                let group = self.groups.into_iter().next().expect("requests before v8 have one group");
+                group.topics.write_versioned_kafka_bytes(buf, version);
+                if version.version() >= 2 {
+                    group.error_code.write_kafka_bytes(buf);
+                }
+            }
+        }
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct OffsetFetchGroupResponse {
+    group_id: String,
+    topics: Vec<OffsetFetchTopicResponse>,
+    error_code: ErrorCode,
+}
+
+impl ToVersionedKafkaBytes for OffsetFetchGroupResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.group_id.write_versioned_kafka_bytes(buf, version);
+        self.topics.write_versioned_kafka_bytes(buf, version);
+        self.error_code.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct OffsetFetchTopicResponse {
+    name: String,
+    partitions: Vec<OffsetFetchPartitionResponse>,
+}
+
+impl ToVersionedKafkaBytes for OffsetFetchTopicResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.name.write_versioned_kafka_bytes(buf, version);
+        self.partitions.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct OffsetFetchPartitionResponse {
+    partition_index: i32,
+    committed_offset: i64,
+    committed_leader_epoch: i32,
+    metadata: Option<String>,
+    error_code: ErrorCode,
+}
+
+impl OffsetFetchPartitionResponse {
+    /// The response for a partition, where a partition without a committed offset has an offset of -1
+    fn new(partition_index: i32, offset: Option<OffsetAndMetadata>, error_code: ErrorCode) -> Self {
+        OffsetFetchPartitionResponse {
+            partition_index,
+            committed_offset: offset.as_ref().map_or(-1, |offset| offset.offset),
+            committed_leader_epoch: offset.as_ref().and_then(|offset| offset.leader_epoch).unwrap_or(-1),
+            metadata: Some(offset.map(|offset| offset.metadata).unwrap_or_default()),
+            error_code,
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for OffsetFetchPartitionResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.partition_index.write_kafka_bytes(buf);
+        self.committed_offset.write_kafka_bytes(buf);
+        if version.version() >= 5 {
+            self.committed_leader_epoch.write_kafka_bytes(buf);
+        }
+        self.metadata.write_versioned_kafka_bytes(buf, version);
+        self.error_code.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::api_key::ApiKey;
+    use crate::api::request::ApiRequest;
+    use crate::coordinator::group::OffsetCommitParams;
+    use crate::testing::{open_broker, open_broker_in, parse_request};
+
+    fn commit(broker: &Broker, group_id: &str, offsets: &[(i32, i64)]) {
+        let offsets = offsets.iter()
+            .map(|(partition, offset)| {
+                let offset = OffsetAndMetadata { offset: *offset, leader_epoch: Some(0), metadata: "metadata".to_string(), commit_timestamp: 0 };
+                (TopicPartition::new("events", *partition), offset)
+            })
+            .collect();
+        let commit = OffsetCommitParams { group_id: group_id.to_string(), generation_id: -1, member_id: String::new(), group_instance_id: None, offsets };
+        let results = broker.group_coordinator().commit_offsets(commit).unwrap();
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+    }
+
+    /// Fetch every offset the group has committed, with a v5 request
+    fn offset_fetch(broker: &Broker, group_id: &str) -> OffsetFetchGroupResponse {
+        let mut body = (group_id.len() as i16).to_be_bytes().to_vec();
+        body.extend(group_id.as_bytes());
+        body.extend((-1i32).to_be_bytes());
+        let request = parse_request(ApiKey::OffsetFetch, 5, &body);
+        let ApiRequest::OffsetFetch(offset_fetch) = request.api_request() else {
+            panic!("expected an offset fetch request, got {:?}", request.api_request());
+        };
+        let mut response = OffsetFetchResponse::process_request(&request, offset_fetch, broker);
+        response.groups.remove(0)
+    }
+
+    fn committed_offsets(group: &OffsetFetchGroupResponse) -> Vec<(String, i32, i64, i32, Option<&str>)> {
+        group.topics.iter()
+            .flat_map(|topic| topic.partitions.iter().map(|partition| (
+                topic.name.clone(),
+                partition.partition_index,
+                partition.committed_offset,
+                partition.committed_leader_epoch,
+                partition.metadata.as_deref(),
+            )))
+            .collect()
+    }
+
+    #[test]
+    fn test_offsets_are_reloaded() {
+        let (broker, log_dir) = open_broker("");
+        commit(&broker, "kept", &[(0, 5), (1, 7)]);
+        commit(&broker, "kept", &[(0, 6)]);
+        commit(&broker, "deleted", &[(0, 3)]);
+        let results = broker.group_coordinator().delete_offsets("kept", vec![TopicPartition::new("events", 1)]).unwrap();
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+        assert!(broker.group_coordinator().delete_groups(&["deleted".to_string()])[0].is_ok());
+        broker.shutdown().unwrap();
+        drop(broker);
+
+        // the offsets are replayed from the offsets topic, including the tombstones the deletions wrote
+        let broker = open_broker_in(&log_dir, "");
+        let kept = offset_fetch(&broker, "kept");
+        assert_eq!(kept.error_code, ErrorCode::NoError);
+        assert_eq!(committed_offsets(&kept), vec![("events".to_string(), 0, 6, 0, Some("metadata"))]);
+        let deleted = offset_fetch(&broker, "deleted");
+        assert_eq!(deleted.error_code, ErrorCode::NoError);
+        assert!(committed_offsets(&deleted).is_empty());
+        assert!(broker.group_coordinator().list_groups().iter().all(|group| group.group_id != "deleted"));
+    }
+}
@@ -0,0 +1,417 @@
+use std::time::Duration;
+use bytes::{Buf, BufMut};
+use tracing::error;
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::broker::topics::{is_internal_topic, TopicError};
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+use crate::storage::log::{AppendError, AppendedBatch, Log};
+use crate::storage::producer_state::ProducerStateError;
+use crate::storage::record_batch::{set_log_append_time, RecordBatchHeader};
+use crate::storage::topic_partition::TopicPartition;
+use crate::time::now_ms;
+
+/// Acks the client can ask for, which is how many replicas must have the records before the broker responds
+const ACKS_ALL: i16 = -1;
+const ACKS_NONE: i16 = 0;
+const ACKS_LEADER: i16 = 1;
+
+#[derive(Debug)]
+pub struct ProduceRequest {
+    acks: i16,
+    /// How long to wait for the records to be replicated when all replicas have to acknowledge them
+    timeout_ms: i32,
+    topics: Vec<TopicProduceData>,
+}
+
+impl ProduceRequest {
+    /// Whether the client is waiting for a response, which it isn't when it doesn't want any acks
+    pub fn expects_response(&self) -> bool {
+        self.acks != ACKS_NONE
+    }
+}
+
+impl ReadVersionedKafkaBytes for ProduceRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        // each batch says whether it's part of a transaction, so the transactional id isn't needed
+        let _transactional_id = Option::<String>::read_versioned_kafka_bytes(buf, version)?;
+        let acks = i16::read_kafka_bytes(buf)?;
+        let timeout_ms = i32::read_kafka_bytes(buf)?;
+        let topics = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(ProduceRequest { acks, timeout_ms, topics })
+    }
+}
+
+#[derive(Debug)]
+struct TopicProduceData {
+    name: String,
+    partitions: Vec<PartitionProduceData>,
+}
+
+impl ReadVersionedKafkaBytes for TopicProduceData {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let name = String::read_versioned_kafka_bytes(buf, version)?;
+        let partitions = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(TopicProduceData { name, partitions })
+    }
+}
+
+#[derive(Debug)]
+struct PartitionProduceData {
+    index: i32,
+    /// Null when the producer has nothing to send to the partition
+    records: Option<Vec<u8>>,
+}
+
+impl ReadVersionedKafkaBytes for PartitionProduceData {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let index = i32::read_kafka_bytes(buf)?;
+        let records = Option::<Vec<u8>>::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(PartitionProduceData { index, records })
+    }
+}
+
+#[derive(Debug)]
+pub struct ProduceResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    responses: Vec<TopicProduceResponse>,
+    throttle_time_ms: i32,
+}
+
+impl ProduceResponse {
+    pub async fn process_request(request: &KafkaRequest, produce: &ProduceRequest, broker: &Broker) -> Self {
+        let valid_acks = matches!(produce.acks, ACKS_ALL | ACKS_NONE | ACKS_LEADER);
+        let mut responses: Vec<TopicProduceResponse> = produce.topics.iter()
+            .map(|topic| TopicProduceResponse {
+                name: topic.name.clone(),
+                partitions: topic.partitions.iter()
+                    .map(|partition| match valid_acks {
+                        true => produce_partition(broker, &topic.name, partition, produce.acks),
+                        false => PartitionProduceResponse::error(partition.index, ErrorCode::InvalidRequiredAcks, None),
+                    })
+                    .collect(),
+            })
+            .collect();
+        if produce.acks == ACKS_ALL {
+            await_replication(broker, &mut responses, produce.timeout_ms).await;
+        }
+
+        ProduceResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            responses,
+            throttle_time_ms: 0,
+        }
+    }
+}
+
+/// Wait until every partition that was appended to has replicated its records, which is when the high watermark
+/// reaches the end of the log as it was after the append. The partitions that don't in time are timed out
+async fn await_replication(broker: &Broker, responses: &mut [TopicProduceResponse], timeout_ms: i32) {
+    let mut appended = Vec::new();
+    for (topic_index, topic) in responses.iter().enumerate() {
+        for (partition_index, partition) in topic.partitions.iter().enumerate() {
+            let topic_partition = TopicPartition::new(topic.name.as_str(), partition.index);
+            if let (ErrorCode::NoError, Ok(Some(log))) = (partition.error_code, broker.log_manager().get_log(&topic_partition)) {
+                let required_offset = log.log_end_offset();
+                appended.push(((topic_index, partition_index), topic_partition, log, required_offset));
+            }
+        }
+    }
+
+    let keys: Vec<TopicPartition> = appended.iter().map(|(_, topic_partition, _, _)| topic_partition.clone()).collect();
+    let is_replicated = |log: &Log, required_offset: i64| log.high_watermark() >= required_offset;
+    let timeout = Duration::from_millis(timeout_ms.max(0) as u64);
+    let replicated = broker.produce_purgatory()
+        .try_complete_else_watch(&keys, timeout, || {
+            appended.iter()
+                .all(|(_, _, log, required_offset)| is_replicated(log, *required_offset))
+                .then_some(())
+        })
+        .await;
+    if replicated.is_none() {
+        for ((topic_index, partition_index), _, log, required_offset) in &appended {
+            if !is_replicated(log, *required_offset) {
+                let partition = &mut responses[*topic_index].partitions[*partition_index];
+                *partition = PartitionProduceResponse::error(partition.index, ErrorCode::RequestTimedOut, None);
+            }
+        }
+    }
+}
+
+/// Validate the batch sent to the partition and append it to the partition's log, waking the requests waiting for it.
+/// Since this broker is the only replica, the records are fully replicated as soon as they're appended.
+/// A topic that doesn't exist is created with the default partition count, if auto creation is enabled
+fn produce_partition(broker: &Broker, topic: &str, partition: &PartitionProduceData, acks: i16) -> PartitionProduceResponse {
+    let index = partition.index;
+    if is_internal_topic(topic) {
+        let message = format!("Producing to the internal topic {topic} isn't allowed");
+        return PartitionProduceResponse::error(index, ErrorCode::InvalidTopicException, Some(message));
+    }
+    if broker.config().auto_create_topics_enable() && broker.metadata().image().topic(topic).is_none() {
+        match broker.auto_create_topic(topic) {
+            // another request may have created it first
+            Ok(_) | Err(TopicError::TopicAlreadyExists(_)) => {}
+            Err(err) => return PartitionProduceResponse::error(index, ErrorCode::from(&err), Some(err.to_string())),
+        }
+    }
+    let (leader_epoch, isr_size) = {
+        let image = broker.metadata().image();
+        match image.topic(topic).and_then(|topic| topic.partition(index)) {
+            Some(partition) => (partition.leader_epoch(), partition.isr().len()),
+            None => return PartitionProduceResponse::error(index, ErrorCode::UnknownTopicOrPartition, None),
+        }
+    };
+    let Some(config) = broker.topic_config(topic) else {
+        return PartitionProduceResponse::error(index, ErrorCode::UnknownTopicOrPartition, None);
+    };
+    if acks == ACKS_ALL && (isr_size as i32) < config.min_insync_replicas() {
+        let message = format!(
+            "The size of the current ISR {isr_size} is insufficient to satisfy the min.isr requirement of {} for partition {topic}-{index}",
+            config.min_insync_replicas()
+        );
+        return PartitionProduceResponse::error(index, ErrorCode::NotEnoughReplicas, Some(message));
+    }
+
+    // from v3 every partition's records must be exactly one v2 batch
+    let mut batch = partition.records.clone().unwrap_or_default();
+    let header = match RecordBatchHeader::parse(&batch) {
+        Ok(header) => header,
+        Err(err) => return PartitionProduceResponse::error(index, ErrorCode::CorruptMessage, Some(err.to_string())),
+    };
+    if header.size_in_bytes() != batch.len() {
+        let message = "Produce requests must contain exactly one record batch per partition".to_string();
+        return PartitionProduceResponse::error(index, ErrorCode::InvalidRecord, Some(message));
+    }
+    if !header.has_valid_crc(&batch) {
+        return PartitionProduceResponse::error(index, ErrorCode::CorruptMessage, Some("Record batch has an invalid CRC".to_string()));
+    }
+    // like kafka's LogValidator, only the broker writes control batches, and a batch's offsets have to match its records,
+    // since the offsets and sequence numbers of everything after it are worked out from them
+    if header.is_control() {
+        let message = "Clients are not allowed to write control records".to_string();
+        return PartitionProduceResponse::error(index, ErrorCode::InvalidRecord, Some(message));
+    }
+    if header.last_offset_delta() < 0 || header.records_count() as i64 != header.last_offset_delta() as i64 + 1 {
+        let message = format!(
+            "The record batch has {} records, which doesn't match its last offset delta {}", header.records_count(), header.last_offset_delta()
+        );
+        return PartitionProduceResponse::error(index, ErrorCode::InvalidRecord, Some(message));
+    }
+    if batch.len() > config.max_message_bytes().max(0) as usize {
+        let message = format!("The record batch is {} bytes, which is larger than the topic's max.message.bytes", batch.len());
+        return PartitionProduceResponse::error(index, ErrorCode::MessageTooLarge, Some(message));
+    }
+    let log_append_time = match config.is_log_append_time() {
+        true => {
+            let timestamp = now_ms();
+            set_log_append_time(&mut batch, timestamp);
+            timestamp
+        }
+        false => -1,
+    };
+
+    let topic_partition = TopicPartition::new(topic, index);
+    let log = match broker.log_manager().get_log(&topic_partition) {
+        Ok(Some(log)) => log,
+        Ok(None) => return PartitionProduceResponse::error(index, ErrorCode::UnknownTopicOrPartition, None),
+        Err(err) => {
+            error!(%topic_partition, %err, "Failed to open log");
+            return PartitionProduceResponse::error(index, ErrorCode::KafkaStorageError, None);
+        }
+    };
+    let batch_size = batch.len();
+    let (base_offset, log_append_time) = match log.append_as_leader(batch, leader_epoch) {
+        Ok(AppendedBatch::Appended(header)) => {
+            broker.metrics().record_bytes_in(topic_partition.topic(), batch_size);
+            broker.fetch_purgatory().check_and_complete(&topic_partition);
+            broker.produce_purgatory().check_and_complete(&topic_partition);
+            (header.base_offset(), log_append_time)
+        }
+        // the producer is retrying a batch it didn't get a response for, so it gets the response it missed
+        Ok(AppendedBatch::Duplicate(batch)) => (batch.first_offset(), -1),
+        Err(AppendError::ProducerState(err)) => {
+            let error_code = match err {
+                ProducerStateError::InvalidProducerEpoch { .. } => ErrorCode::InvalidProducerEpoch,
+                ProducerStateError::OutOfOrderSequence { .. } => ErrorCode::OutOfOrderSequenceNumber,
+            };
+            return PartitionProduceResponse::error(index, error_code, Some(err.to_string()));
+        }
+        Err(AppendError::Storage(err)) => {
+            error!(%topic_partition, %err, "Failed to append to the log");
+            return PartitionProduceResponse::error(index, ErrorCode::KafkaStorageError, None);
+        }
+    };
+    PartitionProduceResponse {
+        base_offset,
+        log_append_time_ms: log_append_time,
+        log_start_offset: log.log_start_offset(),
+        ..PartitionProduceResponse::error(index, ErrorCode::NoError, None)
+    }
+}
+
+impl ApiResponse for ProduceResponse {}
+
+impl ToKafkaBytes for ProduceResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        self.responses.write_versioned_kafka_bytes(buf, version);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct TopicProduceResponse {
+    name: String,
+    partitions: Vec<PartitionProduceResponse>,
+}
+
+impl ToVersionedKafkaBytes for TopicProduceResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.name.write_versioned_kafka_bytes(buf, version);
+        self.partitions.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct PartitionProduceResponse {
+    index: i32,
+    error_code: ErrorCode,
+    base_offset: i64,
+    /// -1 unless the topic uses LogAppendTime
+    log_append_time_ms: i64,
+    log_start_offset: i64,
+    error_message: Option<String>,
+}
+
+impl PartitionProduceResponse {
+    /// A response for a partition nothing was appended to
+    fn error(index: i32, error_code: ErrorCode, error_message: Option<String>) -> Self {
+        PartitionProduceResponse {
+            index,
+            error_code,
+            base_offset: -1,
+            log_append_time_ms: -1,
+            log_start_offset: -1,
+            error_message,
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for PartitionProduceResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.index.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        self.base_offset.write_kafka_bytes(buf);
+        self.log_append_time_ms.write_kafka_bytes(buf);
+        if version.version() >= 5 {
+            self.log_start_offset.write_kafka_bytes(buf);
+        }
+        if version.version() >= 8 {
+            // the record errors, which are always empty since the whole batch is rejected together
+            Vec::<i32>::new().write_versioned_kafka_bytes(buf, version);
+            self.error_message.write_versioned_kafka_bytes(buf, version);
+        }
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::configs::{AlterConfigOp, ConfigResource};
+    use crate::storage::record_batch::{end_transaction_marker, ControlRecordType, RecordBatchBuilder};
+    use crate::testing::open_broker;
+
+    fn produce(broker: &Broker, records: Vec<u8>) -> PartitionProduceResponse {
+        produce_partition(broker, "events", &PartitionProduceData { index: 0, records: Some(records) }, 1)
+    }
+
+    fn log_end_offset(broker: &Broker) -> i64 {
+        broker.log_manager().get_log(&TopicPartition::new("events", 0)).unwrap().unwrap().log_end_offset()
+    }
+
+    #[test]
+    fn test_produce_rejects_control_batches() {
+        let (broker, _log_dir) = open_broker("");
+        broker.auto_create_topic("events").unwrap();
+        let batch = RecordBatchBuilder::new().add_record(0, None, Some(b"value".to_vec())).build();
+        assert_eq!(produce(&broker, batch).error_code, ErrorCode::NoError);
+
+        let marker = end_transaction_marker(1000, 0, 0, ControlRecordType::Commit, 0);
+        assert_eq!(produce(&broker, marker).error_code, ErrorCode::InvalidRecord);
+        assert_eq!(log_end_offset(&broker), 1);
+    }
+
+    #[test]
+    fn test_produce_rejects_mismatched_offset_delta() {
+        let (broker, _log_dir) = open_broker("");
+        broker.auto_create_topic("events").unwrap();
+        let batch = RecordBatchBuilder::new()
+            .add_record(0, None, Some(b"first".to_vec()))
+            .add_record(0, None, Some(b"second".to_vec()))
+            .build();
+        let with_last_offset_delta = |delta: i32| {
+            let mut batch = batch.clone();
+            batch[23..27].copy_from_slice(&delta.to_be_bytes());
+            let crc = crc32c::crc32c(&batch[21..]);
+            batch[17..21].copy_from_slice(&crc.to_be_bytes());
+            batch
+        };
+
+        assert_eq!(produce(&broker, with_last_offset_delta(-5)).error_code, ErrorCode::InvalidRecord);
+        assert_eq!(produce(&broker, with_last_offset_delta(5)).error_code, ErrorCode::InvalidRecord);
+        assert_eq!(log_end_offset(&broker), 0);
+        assert_eq!(produce(&broker, with_last_offset_delta(1)).error_code, ErrorCode::NoError);
+        assert_eq!(log_end_offset(&broker), 2);
+    }
+
+    #[test]
+    fn test_produce_creates_unknown_topic() {
+        let (broker, _log_dir) = open_broker("num.partitions=2");
+        let batch = RecordBatchBuilder::new().add_record(0, None, Some(b"value".to_vec())).build();
+        assert_eq!(produce(&broker, batch.clone()).error_code, ErrorCode::NoError);
+        assert_eq!(broker.metadata().image().topic("events").unwrap().num_partitions(), 2);
+        assert_eq!(log_end_offset(&broker), 1);
+
+        // the topic is created even if the partition is past the default partition count, but the partition isn't
+        let partition = PartitionProduceData { index: 5, records: Some(batch.clone()) };
+        assert_eq!(produce_partition(&broker, "clicks", &partition, 1).error_code, ErrorCode::UnknownTopicOrPartition);
+        assert!(broker.metadata().image().topic("clicks").is_some());
+        let partition = PartitionProduceData { index: 0, records: Some(batch) };
+        assert_eq!(produce_partition(&broker, "bad/name", &partition, 1).error_code, ErrorCode::InvalidTopicException);
+    }
+
+    #[test]
+    fn test_produce_to_unknown_topic_without_auto_creation() {
+        let (broker, _log_dir) = open_broker("auto.create.topics.enable=false");
+        let batch = RecordBatchBuilder::new().add_record(0, None, Some(b"value".to_vec())).build();
+        assert_eq!(produce(&broker, batch).error_code, ErrorCode::UnknownTopicOrPartition);
+        assert!(broker.metadata().image().topic("events").is_none());
+        assert!(broker.log_manager().get_log(&TopicPartition::new("events", 0)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_acks_all_requires_min_insync_replicas() {
+        let (broker, _log_dir) = open_broker("");
+        broker.auto_create_topic("events").unwrap();
+        let ops = [("min.insync.replicas".to_string(), AlterConfigOp::Set("2".to_string()))];
+        broker.incremental_alter_configs(ConfigResource::Topic("events"), &ops, false).unwrap();
+
+        let batch = RecordBatchBuilder::new().add_record(0, None, Some(b"value".to_vec())).build();
+        let partition = PartitionProduceData { index: 0, records: Some(batch) };
+        assert_eq!(produce_partition(&broker, "events", &partition, ACKS_ALL).error_code, ErrorCode::NotEnoughReplicas);
+        assert_eq!(log_end_offset(&broker), 0);
+        assert_eq!(produce_partition(&broker, "events", &partition, 1).error_code, ErrorCode::NoError);
+    }
+}
@@ -1,12 +1,53 @@
+use std::net::IpAddr;
+use std::sync::Arc;
 use std::string::FromUtf8Error;
+use bytes::Buf;
 use thiserror::Error;
 use tokio::io::{AsyncRead, AsyncReadExt};
 use crate::api::api_key::{ApiKey, ParseApiKeyError};
+use crate::api::add_offsets_to_txn::AddOffsetsToTxnRequest;
+use crate::api::add_partitions_to_txn::AddPartitionsToTxnRequest;
+use crate::api::alter_configs::AlterConfigsRequest;
+use crate::api::alter_user_scram_credentials::AlterUserScramCredentialsRequest;
 use crate::api::api_versions::ApiVersionsRequest;
+use crate::api::consumer_group_describe::ConsumerGroupDescribeRequest;
+use crate::api::consumer_group_heartbeat::ConsumerGroupHeartbeatRequest;
 use crate::api::correlation_id::CorrelationId;
-use crate::api::request::KafkaRequestParseError::MissingData;
-use crate::serialisation::ReadKafkaBytes;
+use crate::api::create_partitions::CreatePartitionsRequest;
+use crate::api::create_topics::CreateTopicsRequest;
+use crate::api::delete_groups::DeleteGroupsRequest;
+use crate::api::delete_topics::DeleteTopicsRequest;
+use crate::api::describe_groups::DescribeGroupsRequest;
+use crate::api::describe_configs::DescribeConfigsRequest;
+use crate::api::describe_producers::DescribeProducersRequest;
+use crate::api::describe_transactions::DescribeTransactionsRequest;
+use crate::api::describe_user_scram_credentials::DescribeUserScramCredentialsRequest;
+use crate::api::end_txn::EndTxnRequest;
+use crate::api::fetch::FetchRequest;
+use crate::api::find_coordinator::FindCoordinatorRequest;
+use crate::api::heartbeat::HeartbeatRequest;
+use crate::api::incremental_alter_configs::IncrementalAlterConfigsRequest;
+use crate::api::init_producer_id::InitProducerIdRequest;
+use crate::api::join_group::JoinGroupRequest;
+use crate::api::leave_group::LeaveGroupRequest;
+use crate::api::list_groups::ListGroupsRequest;
+use crate::api::list_offsets::ListOffsetsRequest;
+use crate::api::list_transactions::ListTransactionsRequest;
+use crate::api::metadata::MetadataRequest;
+use crate::api::offset_commit::OffsetCommitRequest;
+use crate::api::offset_delete::OffsetDeleteRequest;
+use crate::api::offset_fetch::OffsetFetchRequest;
+use crate::api::produce::ProduceRequest;
+use crate::api::request::KafkaRequestParseError::{InvalidRequestSize, MissingData, UnsupportedVersion};
+use crate::api::sasl_authenticate::SaslAuthenticateRequest;
+use crate::api::sasl_handshake::SaslHandshakeRequest;
+use crate::api::sync_group::SyncGroupRequest;
+use crate::api::txn_offset_commit::TxnOffsetCommitRequest;
+use crate::security::principal::KafkaPrincipal;
+use crate::security::session::Session;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes};
 use crate::serialisation::nullable_string::NullableString;
+use crate::serialisation::versioned::skip_tagged_fields;
 
 #[derive(Debug)]
 pub struct KafkaRequest {
@@ -15,32 +56,181 @@ pub struct KafkaRequest {
     api_version: i16,
     correlation_id: CorrelationId,
     client_id: NullableString,
+    /// The address of the client that sent the request
+    client_address: IpAddr,
+    /// Who the client had authenticated as when it sent the request
+    principal: KafkaPrincipal,
+    /// The security state of the connection the request was read from
+    session: Option<Arc<Session>>,
     api_request: ApiRequest
 }
 
 #[derive(Debug)]
 pub enum ApiRequest {
-    ApiVersions(ApiVersionsRequest)
+    Produce(ProduceRequest),
+    ApiVersions(ApiVersionsRequest),
+    Fetch(FetchRequest),
+    ListOffsets(ListOffsetsRequest),
+    Metadata(MetadataRequest),
+    OffsetCommit(OffsetCommitRequest),
+    OffsetFetch(OffsetFetchRequest),
+    FindCoordinator(FindCoordinatorRequest),
+    JoinGroup(JoinGroupRequest),
+    Heartbeat(HeartbeatRequest),
+    LeaveGroup(LeaveGroupRequest),
+    SyncGroup(SyncGroupRequest),
+    DescribeGroups(DescribeGroupsRequest),
+    ListGroups(ListGroupsRequest),
+    SaslHandshake(SaslHandshakeRequest),
+    CreateTopics(CreateTopicsRequest),
+    DeleteTopics(DeleteTopicsRequest),
+    InitProducerId(InitProducerIdRequest),
+    AddPartitionsToTxn(AddPartitionsToTxnRequest),
+    AddOffsetsToTxn(AddOffsetsToTxnRequest),
+    EndTxn(EndTxnRequest),
+    TxnOffsetCommit(TxnOffsetCommitRequest),
+    DescribeConfigs(DescribeConfigsRequest),
+    AlterConfigs(AlterConfigsRequest),
+    SaslAuthenticate(SaslAuthenticateRequest),
+    CreatePartitions(CreatePartitionsRequest),
+    DeleteGroups(DeleteGroupsRequest),
+    IncrementalAlterConfigs(IncrementalAlterConfigsRequest),
+    OffsetDelete(OffsetDeleteRequest),
+    DescribeUserScramCredentials(DescribeUserScramCredentialsRequest),
+    AlterUserScramCredentials(AlterUserScramCredentialsRequest),
+    DescribeProducers(DescribeProducersRequest),
+    DescribeTransactions(DescribeTransactionsRequest),
+    ListTransactions(ListTransactionsRequest),
+    ConsumerGroupHeartbeat(ConsumerGroupHeartbeatRequest),
+    ConsumerGroupDescribe(ConsumerGroupDescribeRequest),
 }
 
 impl KafkaRequest {
+    pub fn message_size(&self) -> i32 {
+        self.message_size
+    }
+
+    pub fn api_key(&self) -> ApiKey {
+        self.api_key
+    }
+
     pub fn api_version(&self) -> i16 {
         self.api_version
     }
 
+    /// The version of the request body, which responses to this request are also encoded with
+    pub fn message_version(&self) -> MessageVersion {
+        MessageVersion::new(self.api_version, self.api_key.is_flexible(self.api_version))
+    }
+
     pub fn correlation_id(&self) -> CorrelationId { self.correlation_id }
 
-    pub async fn try_read_from<T: AsyncRead + Unpin>(reader: &mut T) -> Result<Self, KafkaRequestParseError> {
-        let message_size = i32::read_kafka_bytes(reader).await?;
-        let api_key = ApiKey::read_kafka_bytes(reader).await?;
-        let api_version = i16::read_kafka_bytes(reader).await?;
-        let correlation_id = CorrelationId::read_kafka_bytes(reader).await?;
-        let client_id = NullableString::read_kafka_bytes(reader).await?;
-        reader.read_u8().await.map_err(|_| MissingData(1))?; // ignore the tag buffer for now
+    pub fn client_id(&self) -> &NullableString {
+        &self.client_id
+    }
+
+    pub fn client_address(&self) -> IpAddr {
+        self.client_address
+    }
+
+    pub fn principal(&self) -> &KafkaPrincipal {
+        &self.principal
+    }
+
+    pub fn session(&self) -> Option<&Session> {
+        self.session.as_deref()
+    }
 
+    /// The request as it was sent on the connection, by whoever the client had authenticated as,
+    /// since requests are parsed as anonymous ones
+    pub fn with_session(self, session: Arc<Session>) -> Self {
+        KafkaRequest { principal: session.principal(), session: Some(session), ..self }
+    }
+
+    pub fn api_request(&self) -> &ApiRequest {
+        &self.api_request
+    }
+
+    /// Read the next request on the connection. The whole message is read before any of it is parsed,
+    /// so parsing never waits on the socket, and if we stop parsing early the next request still starts at the right place.
+    /// The size the client claims is checked against `socket.request.max.bytes` before the message is allocated
+    pub async fn try_read_from<T: AsyncRead + Unpin>(reader: &mut T, client_address: IpAddr, max_size: i32) -> Result<Self, KafkaRequestParseError> {
+        let message_size = reader.read_i32().await
+            .map_err(|_| MissingData(size_of::<i32>()))?;
+        if !(0..=max_size).contains(&message_size) {
+            return Err(InvalidRequestSize(message_size, max_size));
+        }
+        let mut message = vec![0u8; message_size as usize];
+        reader.read_exact(&mut message).await
+            .map_err(|_| MissingData(message.len()))?;
+        Self::parse(&mut message.as_slice(), client_address)
+    }
+
+    /// Parse a request from its message, which is everything that comes after the message size
+    pub fn parse<B: Buf>(buf: &mut B, client_address: IpAddr) -> Result<Self, KafkaRequestParseError> {
+        let message_size = buf.remaining() as i32;
+        let api_key = ApiKey::read_kafka_bytes(buf)?;
+        let api_version = i16::read_kafka_bytes(buf)?;
+        let correlation_id = CorrelationId::read_kafka_bytes(buf)?;
+        let client_id = NullableString::read_kafka_bytes(buf)?;
+        let version = MessageVersion::new(api_version, api_key.is_flexible(api_version));
+        skip_tagged_fields(buf, version)?;
+
+        let supported = api_key.supported_versions()
+            .is_some_and(|versions| versions.contains(&api_version));
         let api_request = match api_key {
+            // unsupported ApiVersions requests still get a response, telling the client which versions to use
+            ApiKey::ApiVersions if !supported => ApiRequest::ApiVersions(ApiVersionsRequest::default()),
+            _ if !supported => return Err(UnsupportedVersion(api_key, api_version)),
+            ApiKey::ApiVersions => ApiRequest::ApiVersions(ApiVersionsRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::Produce => ApiRequest::Produce(ProduceRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::Fetch => ApiRequest::Fetch(FetchRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::ListOffsets => ApiRequest::ListOffsets(ListOffsetsRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::Metadata => ApiRequest::Metadata(MetadataRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::OffsetCommit => ApiRequest::OffsetCommit(OffsetCommitRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::OffsetFetch => ApiRequest::OffsetFetch(OffsetFetchRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::FindCoordinator => ApiRequest::FindCoordinator(FindCoordinatorRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::JoinGroup => ApiRequest::JoinGroup(JoinGroupRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::Heartbeat => ApiRequest::Heartbeat(HeartbeatRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::LeaveGroup => ApiRequest::LeaveGroup(LeaveGroupRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::SyncGroup => ApiRequest::SyncGroup(SyncGroupRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::DescribeGroups => ApiRequest::DescribeGroups(DescribeGroupsRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::ListGroups => ApiRequest::ListGroups(ListGroupsRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::SaslHandshake => ApiRequest::SaslHandshake(SaslHandshakeRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::CreateTopics => ApiRequest::CreateTopics(CreateTopicsRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::DeleteTopics => ApiRequest::DeleteTopics(DeleteTopicsRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::InitProducerId => ApiRequest::InitProducerId(InitProducerIdRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::AddPartitionsToTxn => ApiRequest::AddPartitionsToTxn(AddPartitionsToTxnRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::AddOffsetsToTxn => ApiRequest::AddOffsetsToTxn(AddOffsetsToTxnRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::EndTxn => ApiRequest::EndTxn(EndTxnRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::TxnOffsetCommit => ApiRequest::TxnOffsetCommit(TxnOffsetCommitRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::DescribeConfigs => ApiRequest::DescribeConfigs(DescribeConfigsRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::AlterConfigs => ApiRequest::AlterConfigs(AlterConfigsRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::SaslAuthenticate => ApiRequest::SaslAuthenticate(SaslAuthenticateRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::CreatePartitions => ApiRequest::CreatePartitions(CreatePartitionsRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::DeleteGroups => ApiRequest::DeleteGroups(DeleteGroupsRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::OffsetDelete => ApiRequest::OffsetDelete(OffsetDeleteRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::IncrementalAlterConfigs => {
+                ApiRequest::IncrementalAlterConfigs(IncrementalAlterConfigsRequest::read_versioned_kafka_bytes(buf, version)?)
+            }
+            ApiKey::DescribeUserScramCredentials => {
+                ApiRequest::DescribeUserScramCredentials(DescribeUserScramCredentialsRequest::read_versioned_kafka_bytes(buf, version)?)
+            }
+            ApiKey::AlterUserScramCredentials => {
+                ApiRequest::AlterUserScramCredentials(AlterUserScramCredentialsRequest::read_versioned_kafka_bytes(buf, version)?)
+            }
+            ApiKey::DescribeProducers => ApiRequest::DescribeProducers(DescribeProducersRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::DescribeTransactions => {
+                ApiRequest::DescribeTransactions(DescribeTransactionsRequest::read_versioned_kafka_bytes(buf, version)?)
+            }
+            ApiKey::ListTransactions => ApiRequest::ListTransactions(ListTransactionsRequest::read_versioned_kafka_bytes(buf, version)?),
+            ApiKey::ConsumerGroupHeartbeat => {
+                ApiRequest::ConsumerGroupHeartbeat(ConsumerGroupHeartbeatRequest::read_versioned_kafka_bytes(buf, version)?)
+            }
+            ApiKey::ConsumerGroupDescribe => {
+                ApiRequest::ConsumerGroupDescribe(ConsumerGroupDescribeRequest::read_versioned_kafka_bytes(buf, version)?)
+            }
             ApiKey::DescribeTopicPartitions => { todo!("parse things")},
-            _ => ApiRequest::ApiVersions(ApiVersionsRequest{})
         };
 
         Ok(KafkaRequest {
@@ -49,6 +239,9 @@ impl KafkaRequest {
             api_version,
             correlation_id,
             client_id,
+            client_address,
+            principal: KafkaPrincipal::anonymous(),
+            session: None,
             api_request
         })
     }
@@ -58,10 +251,35 @@ impl KafkaRequest {
 pub enum KafkaRequestParseError {
     #[error("Missing data, received insufficient bytes, fewer than: {0}")]
     MissingData(usize),
+    #[error("Invalid receive (size = {0} larger than {1})")]
+    InvalidRequestSize(i32, i32),
     #[error("Invalid Api Key requested: {0}")]
     InvalidApiKey(#[from] ParseApiKeyError),
+    #[error("Unsupported version {1} of Api Key {0:?}")]
+    UnsupportedVersion(ApiKey, i16),
     #[error("Invalid String Length: {0}")]
     InvalidStringLength(i32),
+    #[error("Invalid Array Length: {0}")]
+    InvalidArrayLength(i32),
     #[error("Invalid String: {0}")]
     InvalidString(#[from] FromUtf8Error)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_oversized_request_is_rejected() {
+        let client_address = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        // only the size is sent, so reading any further would fail with missing data instead
+        let mut reader: &[u8] = &i32::MAX.to_be_bytes();
+        let result = KafkaRequest::try_read_from(&mut reader, client_address, 104857600).await;
+        assert!(matches!(result, Err(InvalidRequestSize(i32::MAX, 104857600))));
+
+        let mut reader: &[u8] = &(-1i32).to_be_bytes();
+        let result = KafkaRequest::try_read_from(&mut reader, client_address, 104857600).await;
+        assert!(matches!(result, Err(InvalidRequestSize(-1, _))));
+    }
+}
@@ -1,23 +1,113 @@
+use std::fmt::Debug;
+use bytes::BufMut;
+use crate::api::api_key::ApiKey;
 use crate::api::correlation_id::CorrelationId;
+use crate::api::error_code::ErrorCode;
 use super::request::KafkaRequest;
-use crate::serialisation::ToKafkaBytes;
+use crate::serialisation::{to_response_message, ToKafkaBytes};
+use crate::storage::file_records::FileRecords;
 
 #[derive(Debug)]
 pub struct BaseKafkaResponse {
     correlation_id: CorrelationId,
+    /// Flexible responses use response header v1, which adds tagged fields
+    flexible: bool,
 }
 
 impl BaseKafkaResponse {
     pub fn new(request: &KafkaRequest) -> BaseKafkaResponse {
         BaseKafkaResponse {
             correlation_id: request.correlation_id(),
+            // ApiVersions always uses response header v0, so clients can parse it before they know which versions we support
+            flexible: request.api_key() != ApiKey::ApiVersions && request.message_version().is_flexible(),
         }
     }
 }
 
 impl ToKafkaBytes for BaseKafkaResponse {
     /// Convert the message to bytes that can be returned in the response
-    fn to_kafka_bytes(self) -> impl IntoIterator<Item = u8> {
-        self.correlation_id.to_kafka_bytes()
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        self.correlation_id.write_kafka_bytes(buf);
+        // if we support tags in the future, then we shouldn't hardcode the number of tags as 0 here
+        if self.flexible {
+            buf.put_u8(0);
+        }
+    }
+}
+
+/// The response to a request, which is encoded and sent back to the client
+pub trait ApiResponse: ToKafkaBytes + Debug {
+    /// The error of the request as a whole, which is logged with the request.
+    /// None for responses whose errors are only for each topic, partition or group
+    fn error_code(&self) -> Option<ErrorCode> {
+        None
+    }
+}
+
+/// A response message ready to be sent to the client, starting with its size.
+/// Records read from the log are left in the segment files, so they can be sent from there without copying them
+#[derive(Debug, Default)]
+pub struct ResponseMessage {
+    parts: Vec<ResponsePart>,
+    error_code: Option<ErrorCode>,
+}
+
+#[derive(Debug)]
+pub enum ResponsePart {
+    Bytes(Vec<u8>),
+    Records(FileRecords),
+}
+
+impl ResponseMessage {
+    /// The message for a response that's entirely encoded in memory
+    pub fn encode<T: ApiResponse>(response: T) -> Self {
+        let error_code = response.error_code();
+        ResponseMessage { parts: vec![ResponsePart::Bytes(to_response_message(response))], error_code }
+    }
+
+    pub fn with_error_code(mut self, error_code: ErrorCode) -> Self {
+        self.error_code = Some(error_code);
+        self
+    }
+
+    /// The buffer at the end of the message, which the next fields are written into
+    pub fn buffer(&mut self) -> &mut Vec<u8> {
+        if !matches!(self.parts.last(), Some(ResponsePart::Bytes(_))) {
+            self.parts.push(ResponsePart::Bytes(Vec::new()));
+        }
+        match self.parts.last_mut() {
+            Some(ResponsePart::Bytes(last)) => last,
+            _ => unreachable!("a buffer was just pushed"),
+        }
+    }
+
+    pub fn push_records(&mut self, records: FileRecords) {
+        if !records.is_empty() {
+            self.parts.push(ResponsePart::Records(records));
+        }
+    }
+
+    /// Put the size of the message before it, once everything else has been added
+    pub fn with_size(mut self) -> Self {
+        let size = self.size() as i32;
+        self.parts.insert(0, ResponsePart::Bytes(size.to_be_bytes().to_vec()));
+        self
+    }
+
+    pub fn size(&self) -> usize {
+        self.parts.iter()
+            .map(|part| match part {
+                ResponsePart::Bytes(bytes) => bytes.len(),
+                ResponsePart::Records(records) => records.size(),
+            })
+            .sum()
+    }
+
+    pub fn parts(&self) -> &[ResponsePart] {
+        &self.parts
+    }
+
+    pub fn error_code(&self) -> Option<ErrorCode> {
+        self.error_code
     }
 }
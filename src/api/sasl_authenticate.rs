@@ -0,0 +1,84 @@
+use bytes::{Buf, BufMut};
+use tracing::{debug, info};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::security::sasl::SaslError;
+use crate::serialisation::{MessageVersion, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+/// One of the messages of the SASL mechanism the client chose in its handshake
+#[derive(Debug)]
+pub struct SaslAuthenticateRequest {
+    auth_bytes: Vec<u8>,
+}
+
+impl ReadVersionedKafkaBytes for SaslAuthenticateRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let auth_bytes = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(SaslAuthenticateRequest { auth_bytes })
+    }
+}
+
+#[derive(Debug)]
+pub struct SaslAuthenticateResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    error_code: ErrorCode,
+    error_message: Option<String>,
+    auth_bytes: Vec<u8>,
+    /// How long until the client has to re-authenticate, where 0 means it doesn't have to
+    session_lifetime_ms: i64,
+}
+
+impl SaslAuthenticateResponse {
+    pub fn process_request(request: &KafkaRequest, authenticate: &SaslAuthenticateRequest, broker: &Broker) -> Self {
+        let result = match request.session().and_then(|session| session.sasl()) {
+            Some(mut sasl) => {
+                let result = sasl.authenticate(&authenticate.auth_bytes, broker.credentials());
+                if let (Ok(_), Some(principal)) = (&result, sasl.principal()) {
+                    debug!(%principal, "Authenticated with SASL");
+                }
+                result
+            }
+            None => Err(SaslError::IllegalState("SaslAuthenticate request received on a listener that doesn't use SASL".to_string())),
+        };
+        if let Err(SaslError::AuthenticationFailed(reason)) = &result {
+            info!(reason, "Failed authentication");
+        }
+        let (error_code, error_message, auth_bytes, session_lifetime_ms) = match result {
+            Ok(response) => (ErrorCode::NoError, None, response.auth_bytes().to_vec(), response.session_lifetime_ms()),
+            Err(err) => (ErrorCode::from(&err), Some(err.to_string()), Vec::new(), 0),
+        };
+        SaslAuthenticateResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            error_code,
+            error_message,
+            auth_bytes,
+            session_lifetime_ms,
+        }
+    }
+}
+
+impl ApiResponse for SaslAuthenticateResponse {
+    fn error_code(&self) -> Option<ErrorCode> {
+        Some(self.error_code)
+    }
+}
+
+impl ToKafkaBytes for SaslAuthenticateResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        self.error_message.write_versioned_kafka_bytes(buf, version);
+        self.auth_bytes.write_versioned_kafka_bytes(buf, version);
+        if version.version() >= 1 {
+            self.session_lifetime_ms.write_kafka_bytes(buf);
+        }
+        write_empty_tagged_fields(buf, version);
+    }
+}
@@ -0,0 +1,60 @@
+use bytes::{Buf, BufMut};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::serialisation::{MessageVersion, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+
+/// The client chooses the mechanism it's going to authenticate with, before sending SaslAuthenticate requests
+#[derive(Debug)]
+pub struct SaslHandshakeRequest {
+    mechanism: String,
+}
+
+impl ReadVersionedKafkaBytes for SaslHandshakeRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let mechanism = String::read_versioned_kafka_bytes(buf, version)?;
+        Ok(SaslHandshakeRequest { mechanism })
+    }
+}
+
+#[derive(Debug)]
+pub struct SaslHandshakeResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    error_code: ErrorCode,
+    /// The mechanisms the listener has enabled, so a client that asked for another one knows what it can use
+    mechanisms: Vec<String>,
+}
+
+impl SaslHandshakeResponse {
+    pub fn process_request(request: &KafkaRequest, handshake: &SaslHandshakeRequest, broker: &Broker) -> Self {
+        let sasl = request.session().and_then(|session| session.sasl());
+        let error_code = match sasl {
+            Some(mut sasl) => sasl.handshake(&handshake.mechanism).err().as_ref().map_or(ErrorCode::NoError, ErrorCode::from),
+            // only SASL listeners authenticate with SASL
+            None => ErrorCode::IllegalSaslState,
+        };
+        SaslHandshakeResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            error_code,
+            mechanisms: broker.config().sasl_enabled_mechanisms().iter().map(|mechanism| mechanism.name().to_string()).collect(),
+        }
+    }
+}
+
+impl ApiResponse for SaslHandshakeResponse {
+    fn error_code(&self) -> Option<ErrorCode> {
+        Some(self.error_code)
+    }
+}
+
+impl ToKafkaBytes for SaslHandshakeResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        self.mechanisms.write_versioned_kafka_bytes(buf, version);
+    }
+}
@@ -1,62 +1,539 @@
+use std::future::{poll_fn, Future};
 use std::io;
-use tokio::io::{AsyncWriteExt, BufReader};
+use std::net::IpAddr;
+#[cfg(target_os = "linux")]
+use std::os::fd::AsRawFd;
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+#[cfg(target_os = "linux")]
+use tokio::io::Interest;
+use tokio::io::{AsyncRead, AsyncWriteExt, BufReader, WriteHalf};
+use tokio::net::tcp::OwnedWriteHalf;
 use tokio::net::{TcpListener, TcpStream};
-use crate::api::api_versions::ApiVersionsResponse;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::task::JoinSet;
+use tokio_rustls::server::TlsStream;
+use tracing::{debug, error, info, info_span, trace, warn, Instrument, Span};
+use crate::api::handler::handle_request;
 use crate::api::request::KafkaRequest;
-use crate::serialisation::to_response_message;
+use crate::api::api_key::ApiKey;
+use crate::api::error_code::ErrorCode;
+use crate::api::response::{ResponseMessage, ResponsePart};
+use crate::broker::Broker;
+use crate::logging::REQUEST_LOGGER;
+use crate::metrics::endpoint::serve_metrics;
+use crate::security::principal::KafkaPrincipal;
+use crate::security::sasl::SaslAuthenticator;
+use crate::security::session::Session;
+use crate::security::tls::TlsConfig;
+use crate::storage::file_records::FileSlice;
+use crate::time::now_ms;
+
+/// How long connections get to finish the requests they've already read once the server is shutting down
+const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a client on an SSL listener gets to finish the TLS handshake, so a client that stalls doesn't hold its connection open
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often an SSL listener checks whether its key store or trust store has changed
+const TLS_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
 
 pub struct Server {
-    listener: TcpListener
+    listener: TcpListener,
+    broker: Arc<Broker>,
+    /// The TLS config of an SSL or SASL_SSL listener, which is None for a plaintext one
+    tls: Option<Arc<TlsConfig>>,
+    shutdown: Arc<watch::Sender<bool>>,
+}
+
+/// Shuts down the server it came from
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle(Arc<watch::Sender<bool>>);
+
+impl ShutdownHandle {
+    /// Stop the server accepting connections and reading requests, which makes it finish up and return from `serve`
+    pub fn shutdown(&self) {
+        self.0.send_replace(true);
+    }
 }
 
 impl Server {
-    pub async fn new(address: &str) -> io::Result<Server> {
+    pub async fn new(address: &str, broker: Broker) -> io::Result<Server> {
+        let tls = match broker.config().security_protocol().is_tls() {
+            true => {
+                let tls = TlsConfig::load(broker.config()).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+                Some(Arc::new(tls))
+            }
+            false => None,
+        };
         TcpListener::bind(address)
             .await
-            .map(|listener| Server { listener })
+            .map(|listener| Server { listener, broker: Arc::new(broker), tls, shutdown: Arc::new(watch::Sender::new(false)) })
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle(self.shutdown.clone())
     }
 
-    /// Serve incoming Kafka Protocol Requests
-    pub async fn serve(&self) {
+    /// Serve incoming Kafka Protocol Requests until the server is shut down. Then it stops accepting connections,
+    /// gives the requests that were already read a while to finish, and shuts the broker down
+    pub async fn serve(self) {
+        let mut shutdown = self.shutdown.subscribe();
+        let mut connections = JoinSet::new();
+        let metrics_endpoint = self.broker.config().metrics_listener()
+            .map(|listener| tokio::spawn(serve_metrics(listener.bind_address(), self.broker.clone())));
+        let tls_reloader = self.tls.clone().map(|tls| tokio::spawn(reload_tls_config(tls)));
+        let retention = tokio::spawn(delete_old_segments(self.broker.clone()));
         loop {
-            match self.listener.accept().await {
-                Ok((stream, _)) => {
-                    println!("Received new request");
-                    tokio::spawn(Server::handle_connection(stream));
-                    // note this doesn't have graceful shutdown.
-                    // the server could be shutdown, and in progress requests might not be handled
-                }
-                Err(e) => {
-                    eprintln!("error: {}", e);
-                }
+            tokio::select! {
+                accepted = self.listener.accept() => match accepted {
+                    Ok((stream, address)) => {
+                        let span = info_span!("connection", client_address = %address);
+                        debug!(parent: &span, "Accepted connection");
+                        let connection = Server::handle_connection(stream, address.ip(), self.broker.clone(), self.tls.clone(), self.shutdown.subscribe());
+                        connections.spawn(connection.instrument(span));
+                    }
+                    Err(err) => {
+                        error!(%err, "Failed to accept a connection");
+                    }
+                },
+                // reap the connections that have closed, so only the open ones are waited for when shutting down
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                _ = shutdown.wait_for(|shutdown| *shutdown) => break,
+            }
+        }
+
+        drop(self.listener);
+        if let Some(metrics_endpoint) = metrics_endpoint {
+            metrics_endpoint.abort();
+        }
+        if let Some(tls_reloader) = tls_reloader {
+            tls_reloader.abort();
+        }
+        retention.abort();
+        info!(connections = connections.len(), "Shutting down, waiting for connections to finish their requests");
+        let drained = tokio::time::timeout(SHUTDOWN_DRAIN_TIMEOUT, async {
+            while connections.join_next().await.is_some() {}
+        }).await;
+        if drained.is_err() {
+            warn!(connections = connections.len(), "Closing connections that didn't finish their requests in time");
+            connections.shutdown().await;
+        }
+        if let Err(err) = self.broker.shutdown() {
+            error!(%err, "Failed to shut down cleanly");
+        }
+    }
+
+    /// Serve the connection, after the TLS handshake on an SSL listener, which is where the client's principal comes from.
+    /// On a SASL listener, the client then has to authenticate before it can send anything but ApiVersions
+    async fn handle_connection(
+        stream: TcpStream,
+        client_address: IpAddr,
+        broker: Arc<Broker>,
+        tls: Option<Arc<TlsConfig>>,
+        shutdown: watch::Receiver<bool>,
+    ) {
+        let _active_connection = broker.metrics().connection_opened();
+        let Some(tls) = tls else {
+            let (stream_reader, stream_writer) = stream.into_split();
+            let session = Server::session(KafkaPrincipal::anonymous(), &broker);
+            let writer = ResponseWriter::Plaintext(stream_writer);
+            return Server::serve_requests(stream_reader, writer, client_address, session, broker, shutdown).await;
+        };
+
+        let stream = match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, tls.acceptor().accept(stream)).await {
+            Ok(Ok(stream)) => stream,
+            Ok(Err(err)) => {
+                debug!(%err, "Closing the connection, since the TLS handshake failed");
+                return;
+            }
+            Err(_) => {
+                debug!(%client_address, "Closing the connection, since the TLS handshake timed out");
+                return;
             }
+        };
+        let principal = match tls.principal(stream.get_ref().1) {
+            Ok(principal) => principal,
+            Err(err) => {
+                warn!(%err, "Closing the connection, since the client's certificate doesn't map to a principal");
+                return;
+            }
+        };
+        let (stream_reader, stream_writer) = tokio::io::split(stream);
+        let session = Server::session(principal, &broker);
+        let writer = ResponseWriter::Tls(stream_writer);
+        Server::serve_requests(stream_reader, writer, client_address, session, broker, shutdown).await
+    }
+
+    fn session(transport_principal: KafkaPrincipal, broker: &Broker) -> Arc<Session> {
+        let config = broker.config();
+        let session = Session::new(transport_principal);
+        if !config.security_protocol().is_sasl() {
+            return Arc::new(session);
         }
+        let max_session_lifetime = (config.connections_max_reauth_ms() > 0)
+            .then(|| Duration::from_millis(config.connections_max_reauth_ms() as u64));
+        Arc::new(session.with_sasl(SaslAuthenticator::new(config.sasl_enabled_mechanisms().to_vec(), max_session_lifetime)))
     }
 
-    /// Read a KafkaRequest and send response
-    /// until the Kafka Request from the connection is invalid / missing
-    async fn handle_connection(mut stream: TcpStream) {
-        let (stream_read, mut stream_writer) = stream.split();
-        let mut stream_reader = BufReader::new(stream_read);
-        
+    /// Read requests from the connection until one of them is invalid or missing, starting to handle each one as it arrives.
+    /// A handler that has to wait, such as a long-poll fetch, waits in a task of its own so it doesn't hold up the requests
+    /// after it, while another task writes the responses in the order the requests were sent, as the protocol requires
+    async fn serve_requests<R: AsyncRead + Unpin>(
+        stream_reader: R,
+        stream_writer: ResponseWriter,
+        client_address: IpAddr,
+        session: Arc<Session>,
+        broker: Arc<Broker>,
+        mut shutdown: watch::Receiver<bool>,
+    ) {
+        let mut stream_reader = BufReader::new(stream_reader);
+        let max_request_size = broker.config().socket_request_max_bytes();
+        let (responses, pending_responses) = mpsc::channel(broker.config().queued_max_requests().max(1) as usize);
+        let writer = tokio::spawn(Server::write_responses(stream_writer, pending_responses, broker.clone()).in_current_span());
+
         loop {
-            println!("Waiting to parse request");
-            let request = match KafkaRequest::try_read_from(&mut stream_reader).await {
-                Ok(request) => {
-                    println!("Received Request: {request:?}");
-                    request
+            let request = tokio::select! {
+                request = KafkaRequest::try_read_from(&mut stream_reader, client_address, max_request_size) => match request {
+                    Ok(request) => request,
+                    Err(err) => {
+                        debug!(%err, "Closing the connection, since there isn't another valid request");
+                        break;
+                    }
+                },
+                // no more requests are read once the server is shutting down
+                _ = shutdown.wait_for(|shutdown| *shutdown) => break,
+            };
+
+            // like kafka, a client that sends a request before it's authenticated, or once its session has expired, is disconnected
+            if !session.allows(request.api_key()) {
+                debug!(api_key = ?request.api_key(), "Closing the connection, since the client isn't authenticated");
+                break;
+            }
+            let request = request.with_session(session.clone());
+
+            // the channel is full while the client has too many requests in flight, so wait for room before handling more
+            let Ok(permit) = responses.reserve().await else {
+                // the writer only stops early if it couldn't write to the connection
+                break;
+            };
+            permit.send(Server::start_request(request, broker.clone()).await);
+        }
+
+        // the requests that were already read still get their responses
+        drop(responses);
+        let _ = writer.await;
+    }
+
+    /// Start handling the request, returning where its response will be sent once it's ready.
+    /// The handler runs here until it first has to wait, so anything it does straight away, such as appending produced records
+    /// or committing offsets, happens in the order the requests were sent. Only the waiting happens in a task of its own
+    async fn start_request(request: KafkaRequest, broker: Arc<Broker>) -> PendingResponse {
+        let received = Instant::now();
+        let (api_key, api_version) = (request.api_key(), request.api_version());
+        // the request span is part of the request log, so it's enabled whenever completed requests are logged
+        let span = info_span!(
+            target: REQUEST_LOGGER,
+            "request",
+            api_key = ?request.api_key(),
+            api_version = request.api_version(),
+            correlation_id = i32::from(request.correlation_id()),
+            client_id = request.client_id().as_str(),
+            principal = %request.principal(),
+        );
+        trace!(target: REQUEST_LOGGER, parent: &span, ?request, "Received request");
+        let (sender, receiver) = oneshot::channel();
+        let mut handler = Box::pin(async move { handle_request(&request, &broker).await }.instrument(span.clone()));
+        match poll_fn(|cx| Poll::Ready(handler.as_mut().poll(cx))).await {
+            Poll::Ready(response) => {
+                let _ = sender.send(response);
+            }
+            Poll::Pending => {
+                tokio::spawn(async move {
+                    let _ = sender.send(handler.await);
+                });
+            }
+        }
+        PendingResponse { span, api_key, api_version, received, response: receiver }
+    }
+
+    /// Write each response once it's ready, in the order the requests were read
+    async fn write_responses(mut writer: ResponseWriter, mut pending_responses: mpsc::Receiver<PendingResponse>, broker: Arc<Broker>) {
+        while let Some(PendingResponse { span, api_key, api_version, received, response }) = pending_responses.recv().await {
+            let Ok(response) = response.await else {
+                error!(parent: &span, "Stopped handling the request before it had a response, closing the connection");
+                return;
+            };
+            let Some(response) = response else {
+                broker.metrics().record_request(api_key, api_version, received.elapsed(), None);
+                debug!(target: REQUEST_LOGGER, parent: &span, total_time_ms = elapsed_ms(received), "Completed request without a response");
+                continue;
+            };
+            if let Err(err) = send_response(&mut writer, &response).await {
+                warn!(parent: &span, %err, "Failed to send response");
+                return;
+            }
+            broker.metrics().record_request(api_key, api_version, received.elapsed(), response.error_code());
+            debug!(
+                target: REQUEST_LOGGER,
+                parent: &span,
+                response_size = response.size(),
+                error_code = response.error_code().map(ErrorCode::code),
+                total_time_ms = elapsed_ms(received),
+                "Completed request",
+            );
+        }
+    }
+}
+
+/// A request that's being handled, and where its response will be sent once it's ready,
+/// which is None if the client isn't expecting a response
+struct PendingResponse {
+    span: Span,
+    api_key: ApiKey,
+    api_version: i16,
+    received: Instant,
+    response: oneshot::Receiver<Option<ResponseMessage>>,
+}
+
+/// Where a connection's responses are written
+enum ResponseWriter {
+    Plaintext(OwnedWriteHalf),
+    Tls(WriteHalf<TlsStream<TcpStream>>),
+}
+
+/// Kafka logs how long requests took in fractional milliseconds
+fn elapsed_ms(since: Instant) -> f64 {
+    since.elapsed().as_secs_f64() * 1000.0
+}
+
+/// Reload the TLS config whenever its files change, until the server shuts down
+async fn reload_tls_config(tls: Arc<TlsConfig>) {
+    let mut interval = tokio::time::interval(TLS_RELOAD_INTERVAL);
+    loop {
+        interval.tick().await;
+        match tls.reload_if_changed() {
+            Ok(true) => info!("Reloaded the SSL key store and trust store"),
+            Ok(false) => {}
+            Err(err) => error!(%err, "Failed to reload the SSL key store and trust store, keeping the previous ones"),
+        }
+    }
+}
+
+/// Delete the log segments that are past their topic's retention every `log.retention.check.interval.ms`, until the server shuts down
+async fn delete_old_segments(broker: Arc<Broker>) {
+    let mut interval = tokio::time::interval(Duration::from_millis(broker.config().log_retention_check_interval_ms() as u64));
+    loop {
+        interval.tick().await;
+        let broker = broker.clone();
+        // deleting files blocks, so it's kept off the threads serving requests
+        let deleted = tokio::task::spawn_blocking(move || broker.log_manager().delete_old_segments(now_ms())).await;
+        if let Ok(Err(err)) = deleted {
+            error!(%err, "Failed to delete log segments past their retention");
+        }
+    }
+}
+
+/// Write the message to the client. Over plaintext, any records are sent straight from the segment files they're stored in,
+/// but over TLS they have to be read into memory to be encrypted
+async fn send_response(writer: &mut ResponseWriter, message: &ResponseMessage) -> io::Result<()> {
+    for part in message.parts() {
+        match (part, &mut *writer) {
+            (ResponsePart::Bytes(bytes), ResponseWriter::Plaintext(writer)) => writer.write_all(bytes).await?,
+            (ResponsePart::Bytes(bytes), ResponseWriter::Tls(writer)) => writer.write_all(bytes).await?,
+            (ResponsePart::Records(records), ResponseWriter::Plaintext(writer)) => {
+                for slice in records.slices() {
+                    send_file_slice(writer, slice).await?;
                 }
-                Err(err) => {
-                    eprintln!("Received incorrect request: {err}");
-                    return;
+            }
+            (ResponsePart::Records(records), ResponseWriter::Tls(writer)) => {
+                for slice in records.slices() {
+                    writer.write_all(&slice.read()?).await?;
                 }
-            };
+            }
+        }
+    }
+    if let ResponseWriter::Tls(writer) = writer {
+        writer.flush().await?;
+    }
+    Ok(())
+}
 
-            let response = ApiVersionsResponse::process_request(&request);
-            println!("Sending Response: {response:?}");
-            let response_bytes: Box<[u8]> = to_response_message(response).collect();
-            stream_writer.write_all(&response_bytes).await.unwrap();
-            println!("Sent response bytes: {response_bytes:?}");
+/// Send the slice with sendfile, so the kernel copies it from the page cache to the socket
+/// without the records ever being copied into the broker's memory
+#[cfg(target_os = "linux")]
+async fn send_file_slice(writer: &mut OwnedWriteHalf, slice: &FileSlice) -> io::Result<()> {
+    let socket: &TcpStream = writer.as_ref();
+    let mut offset = slice.position() as libc::off_t;
+    let end = offset + slice.size() as libc::off_t;
+    while offset < end {
+        socket.writable().await?;
+        let sent = socket.try_io(Interest::WRITABLE, || {
+            // SAFETY: both file descriptors are open for the whole call, and sendfile only writes to the offset it's given
+            let sent = unsafe { libc::sendfile(socket.as_raw_fd(), slice.file().as_raw_fd(), &mut offset, (end - offset) as usize) };
+            match sent {
+                ..0 => Err(io::Error::last_os_error()),
+                sent => Ok(sent),
+            }
+        });
+        match sent {
+            Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the segment ended before all of its records were sent")),
+            Ok(_) => {}
+            // the socket's buffer filled up again, so wait until there's room
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+            Err(err) => return Err(err),
         }
     }
-}
\ No newline at end of file
+    Ok(())
+}
+
+/// Without sendfile the records have to be read into memory before they're written to the socket
+#[cfg(not(target_os = "linux"))]
+async fn send_file_slice(writer: &mut OwnedWriteHalf, slice: &FileSlice) -> io::Result<()> {
+    writer.write_all(&slice.read()?).await
+}
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use tokio::io::AsyncReadExt;
+    use tracing_subscriber::filter::filter_fn;
+    use tracing_subscriber::prelude::*;
+    use tracing_subscriber::fmt;
+    use super::*;
+    use crate::storage::record_batch::RecordBatchBuilder;
+    use crate::storage::topic_partition::TopicPartition;
+    use crate::testing::{open_broker, request_bytes};
+
+    /// Serve a plaintext connection to the broker, returning the client's end of it.
+    /// The connection is served until the returned sender is dropped
+    async fn connect(broker: Arc<Broker>) -> (TcpStream, watch::Sender<bool>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, address) = listener.accept().await.unwrap();
+        let shutdown = watch::Sender::new(false);
+        tokio::spawn(Server::handle_connection(stream, address.ip(), broker, None, shutdown.subscribe()));
+        (client, shutdown)
+    }
+
+    /// A v4 Fetch of partition 0 of `events` from its start
+    fn fetch_request(correlation_id: i32, max_wait_ms: i32, min_bytes: i32) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend((-1i32).to_be_bytes());
+        body.extend(max_wait_ms.to_be_bytes());
+        body.extend(min_bytes.to_be_bytes());
+        body.extend(i32::MAX.to_be_bytes());
+        body.push(0);
+        body.extend(1i32.to_be_bytes());
+        body.extend(6i16.to_be_bytes());
+        body.extend(b"events");
+        body.extend(1i32.to_be_bytes());
+        body.extend(0i32.to_be_bytes());
+        body.extend(0i64.to_be_bytes());
+        body.extend(1_048_576i32.to_be_bytes());
+        request_bytes(ApiKey::Fetch, 4, correlation_id, &body)
+    }
+
+    /// Everything that's been logged, so a test can check what was written
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Read the next response, including its size
+    async fn read_response(stream: &mut TcpStream) -> Vec<u8> {
+        let size = stream.read_i32().await.unwrap();
+        let mut response = size.to_be_bytes().to_vec();
+        response.resize(4 + size as usize, 0);
+        stream.read_exact(&mut response[4..]).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_sendfile_matches_in_memory_encoding() {
+        let (broker, _log_dir) = open_broker("");
+        broker.auto_create_topic("events").unwrap();
+        let log = broker.log_manager().get_log(&TopicPartition::new("events", 0)).unwrap().unwrap();
+        for i in 0..3 {
+            let value = vec![b'a' + i as u8; 10_000];
+            log.append(RecordBatchBuilder::new().add_record(i, None, Some(value)).build(), 0).unwrap();
+        }
+        let broker = Arc::new(broker);
+
+        let fetch = fetch_request(1, 0, 1);
+        let request = KafkaRequest::parse(&mut &fetch[4..], IpAddr::from([127, 0, 0, 1])).unwrap();
+        let message = handle_request(&request, &broker).await.unwrap();
+        let mut in_memory = Vec::new();
+        for part in message.parts() {
+            match part {
+                ResponsePart::Bytes(bytes) => in_memory.extend(bytes),
+                ResponsePart::Records(records) => {
+                    assert!(!records.slices().is_empty());
+                    for slice in records.slices() {
+                        in_memory.extend(slice.read().unwrap());
+                    }
+                }
+            }
+        }
+
+        let (mut client, _shutdown) = connect(broker).await;
+        client.write_all(&fetch).await.unwrap();
+        let sent = read_response(&mut client).await;
+        assert_eq!(sent.len(), message.size());
+        assert!(sent == in_memory, "the response sent with sendfile differs from its in-memory encoding");
+    }
+
+    #[tokio::test]
+    async fn test_responses_are_in_request_order_while_fetch_waits() {
+        let (broker, _log_dir) = open_broker("");
+        broker.auto_create_topic("events").unwrap();
+        let (mut client, _shutdown) = connect(Arc::new(broker)).await;
+
+        // the partition is empty, so the fetch waits in the purgatory for its max wait, while the ApiVersions after it is ready straight away
+        let sent = Instant::now();
+        let pipelined = [fetch_request(1, 200, 1), request_bytes(ApiKey::ApiVersions, 0, 2, &[])].concat();
+        client.write_all(&pipelined).await.unwrap();
+
+        let fetch = read_response(&mut client).await;
+        assert_eq!(fetch[4..8], 1i32.to_be_bytes());
+        assert!(sent.elapsed() >= Duration::from_millis(200));
+        let api_versions = read_response(&mut client).await;
+        assert_eq!(api_versions[4..8], 2i32.to_be_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_request_log() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let request_log = fmt::layer()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .with_filter(filter_fn(|metadata| metadata.is_span() || metadata.target() == REQUEST_LOGGER));
+        // the runtime of a tokio test runs every task on this thread, so the connection logs to this subscriber too
+        let _subscriber = tracing::subscriber::set_default(tracing_subscriber::registry().with(request_log));
+
+        let (broker, _log_dir) = open_broker("");
+        let (mut client, _shutdown) = connect(Arc::new(broker)).await;
+        client.write_all(&[request_bytes(ApiKey::ApiVersions, 0, 7, &[]), request_bytes(ApiKey::ApiVersions, 0, 8, &[])].concat()).await.unwrap();
+        read_response(&mut client).await;
+        // the first request is logged before the second one's response is written
+        read_response(&mut client).await;
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let completed = logs.lines()
+            .find(|line| line.contains("correlation_id=7") && line.contains("Completed request"))
+            .unwrap_or_else(|| panic!("no request log line for the first request in:\n{logs}"));
+        for field in ["api_key=ApiVersions", "api_version=0", "client_id=\"test\"", "response_size=", "error_code=0", "total_time_ms="] {
+            assert!(completed.contains(field), "{field} is missing from {completed}");
+        }
+    }
+}
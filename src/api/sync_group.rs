@@ -0,0 +1,125 @@
+use bytes::{Buf, BufMut};
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::coordinator::group::SyncGroupParams;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+#[derive(Debug)]
+pub struct SyncGroupRequest {
+    group_id: String,
+    generation_id: i32,
+    member_id: String,
+    group_instance_id: Option<String>,
+    protocol_type: Option<String>,
+    protocol_name: Option<String>,
+    /// Only the leader sends the assignments
+    assignments: Vec<SyncGroupRequestAssignment>,
+}
+
+impl ReadVersionedKafkaBytes for SyncGroupRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let group_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let generation_id = i32::read_kafka_bytes(buf)?;
+        let member_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let group_instance_id = match version.version() {
+            3.. => Option::<String>::read_versioned_kafka_bytes(buf, version)?,
+            _ => None,
+        };
+        let (protocol_type, protocol_name) = match version.version() {
+            5.. => (
+                Option::<String>::read_versioned_kafka_bytes(buf, version)?,
+                Option::<String>::read_versioned_kafka_bytes(buf, version)?,
+            ),
+            _ => (None, None),
+        };
+        let assignments = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(SyncGroupRequest { group_id, generation_id, member_id, group_instance_id, protocol_type, protocol_name, assignments })
+    }
+}
+
+#[derive(Debug)]
+struct SyncGroupRequestAssignment {
+    member_id: String,
+    assignment: Vec<u8>,
+}
+
+impl ReadVersionedKafkaBytes for SyncGroupRequestAssignment {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let member_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let assignment = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(SyncGroupRequestAssignment { member_id, assignment })
+    }
+}
+
+#[derive(Debug)]
+pub struct SyncGroupResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    error_code: ErrorCode,
+    protocol_type: Option<String>,
+    protocol_name: Option<String>,
+    assignment: Vec<u8>,
+}
+
+impl SyncGroupResponse {
+    /// Get the member's assignment, which waits for the leader to send the assignments after a rebalance
+    pub async fn process_request(request: &KafkaRequest, sync_group: &SyncGroupRequest, broker: &Broker) -> Self {
+        let sync = SyncGroupParams {
+            group_id: sync_group.group_id.clone(),
+            generation_id: sync_group.generation_id,
+            member_id: sync_group.member_id.clone(),
+            group_instance_id: sync_group.group_instance_id.clone(),
+            protocol_type: sync_group.protocol_type.clone(),
+            protocol_name: sync_group.protocol_name.clone(),
+            assignments: sync_group.assignments.iter()
+                .map(|assignment| (assignment.member_id.clone(), assignment.assignment.clone()))
+                .collect(),
+        };
+        let (error_code, synced) = match broker.group_coordinator().sync_group(sync).await {
+            Ok(synced) => (ErrorCode::NoError, Some(synced)),
+            Err(err) => (ErrorCode::from(&err), None),
+        };
+        let (protocol_type, protocol_name, assignment) = synced
+            .map(|synced| (synced.protocol_type, synced.protocol_name, synced.assignment))
+            .unwrap_or_default();
+
+        SyncGroupResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            error_code,
+            protocol_type,
+            protocol_name,
+            assignment,
+        }
+    }
+}
+
+impl ApiResponse for SyncGroupResponse {
+    fn error_code(&self) -> Option<ErrorCode> {
+        Some(self.error_code)
+    }
+}
+
+impl ToKafkaBytes for SyncGroupResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        if version.version() >= 1 {
+            self.throttle_time_ms.write_kafka_bytes(buf);
+        }
+        self.error_code.write_kafka_bytes(buf);
+        if version.version() >= 5 {
+            self.protocol_type.write_versioned_kafka_bytes(buf, version);
+            self.protocol_name.write_versioned_kafka_bytes(buf, version);
+        }
+        self.assignment.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
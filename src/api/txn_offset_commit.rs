@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use bytes::{Buf, BufMut};
+use tracing::error;
+use super::response::{ApiResponse, BaseKafkaResponse};
+use crate::api::error_code::ErrorCode;
+use crate::api::request::{KafkaRequest, KafkaRequestParseError};
+use crate::broker::Broker;
+use crate::coordinator::group::offsets::OffsetAndMetadata;
+use crate::coordinator::group::{GroupError, OffsetCommitParams};
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+use crate::storage::topic_partition::TopicPartition;
+use crate::time::now_ms;
+
+#[derive(Debug)]
+pub struct TxnOffsetCommitRequest {
+    transactional_id: String,
+    group_id: String,
+    producer_id: i64,
+    producer_epoch: i16,
+    /// From v3 the producer sends the consumer's group metadata, so the commit can be checked against the group
+    generation_id: i32,
+    member_id: String,
+    group_instance_id: Option<String>,
+    topics: Vec<TxnOffsetCommitTopic>,
+}
+
+impl ReadVersionedKafkaBytes for TxnOffsetCommitRequest {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let transactional_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let group_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let producer_id = i64::read_kafka_bytes(buf)?;
+        let producer_epoch = i16::read_kafka_bytes(buf)?;
+        let (generation_id, member_id, group_instance_id) = match version.version() {
+            3.. => (
+                i32::read_kafka_bytes(buf)?,
+                String::read_versioned_kafka_bytes(buf, version)?,
+                Option::<String>::read_versioned_kafka_bytes(buf, version)?,
+            ),
+            _ => (-1, String::new(), None),
+        };
+        let topics = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(TxnOffsetCommitRequest { transactional_id, group_id, producer_id, producer_epoch, generation_id, member_id, group_instance_id, topics })
+    }
+}
+
+#[derive(Debug)]
+struct TxnOffsetCommitTopic {
+    name: String,
+    partitions: Vec<TxnOffsetCommitPartition>,
+}
+
+impl ReadVersionedKafkaBytes for TxnOffsetCommitTopic {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let name = String::read_versioned_kafka_bytes(buf, version)?;
+        let partitions = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(TxnOffsetCommitTopic { name, partitions })
+    }
+}
+
+#[derive(Debug)]
+struct TxnOffsetCommitPartition {
+    partition_index: i32,
+    committed_offset: i64,
+    committed_leader_epoch: i32,
+    committed_metadata: Option<String>,
+}
+
+impl ReadVersionedKafkaBytes for TxnOffsetCommitPartition {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let partition_index = i32::read_kafka_bytes(buf)?;
+        let committed_offset = i64::read_kafka_bytes(buf)?;
+        let committed_leader_epoch = match version.version() {
+            2.. => i32::read_kafka_bytes(buf)?,
+            _ => -1,
+        };
+        let committed_metadata = Option::<String>::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(TxnOffsetCommitPartition { partition_index, committed_offset, committed_leader_epoch, committed_metadata })
+    }
+}
+
+#[derive(Debug)]
+pub struct TxnOffsetCommitResponse {
+    base_response: BaseKafkaResponse,
+    version: MessageVersion,
+    throttle_time_ms: i32,
+    topics: Vec<TxnOffsetCommitTopicResponse>,
+}
+
+impl TxnOffsetCommitResponse {
+    pub fn process_request(request: &KafkaRequest, txn_offset_commit: &TxnOffsetCommitRequest, broker: &Broker) -> Self {
+        let now = now_ms();
+        // offsets can only be committed for partitions that exist
+        let offsets: Vec<(TopicPartition, OffsetAndMetadata)> = {
+            let image = broker.metadata().image();
+            txn_offset_commit.topics.iter()
+                .filter_map(|topic| image.topic(&topic.name).map(|metadata| (topic, metadata)))
+                .flat_map(|(topic, metadata)| {
+                    topic.partitions.iter()
+                        .filter(|partition| metadata.partition(partition.partition_index).is_some())
+                        .map(|partition| (TopicPartition::new(topic.name.clone(), partition.partition_index), offset_and_metadata(partition, now)))
+                })
+                .collect()
+        };
+        let committing: Vec<TopicPartition> = offsets.iter().map(|(topic_partition, _)| topic_partition.clone()).collect();
+
+        let commit = OffsetCommitParams {
+            group_id: txn_offset_commit.group_id.clone(),
+            generation_id: txn_offset_commit.generation_id,
+            member_id: txn_offset_commit.member_id.clone(),
+            group_instance_id: txn_offset_commit.group_instance_id.clone(),
+            offsets,
+        };
+        let result = broker.group_coordinator()
+            .commit_transactional_offsets(commit, txn_offset_commit.producer_id, txn_offset_commit.producer_epoch);
+        let error_codes: HashMap<TopicPartition, ErrorCode> = match result {
+            Ok(results) => results.into_iter()
+                .map(|(topic_partition, result)| (topic_partition, result.as_ref().err().map_or(ErrorCode::NoError, ErrorCode::from)))
+                .collect(),
+            // there's no error for the whole request, so every partition gets the group's error
+            Err(err) => {
+                if let GroupError::Storage(err) = &err {
+                    error!(transactional_id = %txn_offset_commit.transactional_id, %err, "Failed to store offsets for transaction");
+                }
+                let error_code = ErrorCode::from(&err);
+                committing.into_iter().map(|topic_partition| (topic_partition, error_code)).collect()
+            }
+        };
+
+        let topics = txn_offset_commit.topics.iter()
+            .map(|topic| TxnOffsetCommitTopicResponse {
+                name: topic.name.clone(),
+                partitions: topic.partitions.iter()
+                    .map(|partition| TxnOffsetCommitPartitionResponse {
+                        partition_index: partition.partition_index,
+                        error_code: error_codes.get(&TopicPartition::new(topic.name.clone(), partition.partition_index))
+                            .copied()
+                            .unwrap_or(ErrorCode::UnknownTopicOrPartition),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        TxnOffsetCommitResponse {
+            base_response: BaseKafkaResponse::new(request),
+            version: request.message_version(),
+            throttle_time_ms: 0,
+            topics,
+        }
+    }
+}
+
+fn offset_and_metadata(partition: &TxnOffsetCommitPartition, now: i64) -> OffsetAndMetadata {
+    OffsetAndMetadata {
+        offset: partition.committed_offset,
+        leader_epoch: Some(partition.committed_leader_epoch).filter(|epoch| *epoch >= 0),
+        metadata: partition.committed_metadata.clone().unwrap_or_default(),
+        commit_timestamp: now,
+    }
+}
+
+impl ApiResponse for TxnOffsetCommitResponse {}
+
+impl ToKafkaBytes for TxnOffsetCommitResponse {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let version = self.version;
+        self.base_response.write_kafka_bytes(buf);
+        self.throttle_time_ms.write_kafka_bytes(buf);
+        self.topics.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct TxnOffsetCommitTopicResponse {
+    name: String,
+    partitions: Vec<TxnOffsetCommitPartitionResponse>,
+}
+
+impl ToVersionedKafkaBytes for TxnOffsetCommitTopicResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.name.write_versioned_kafka_bytes(buf, version);
+        self.partitions.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[derive(Debug)]
+struct TxnOffsetCommitPartitionResponse {
+    partition_index: i32,
+    error_code: ErrorCode,
+}
+
+impl ToVersionedKafkaBytes for TxnOffsetCommitPartitionResponse {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.partition_index.write_kafka_bytes(buf);
+        self.error_code.write_kafka_bytes(buf);
+        write_empty_tagged_fields(buf, version);
+    }
+}
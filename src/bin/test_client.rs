@@ -17,8 +17,8 @@ fn main() {
 
         println!("Reading Response");
         let mut response_bytes = [0; 100];
-        stream.read(&mut response_bytes).unwrap();
-        println!("Read response: {:?}", response_bytes);
+        let bytes_read = stream.read(&mut response_bytes).unwrap();
+        println!("Read response: {:?}", &response_bytes[..bytes_read]);
         stream
     }
 
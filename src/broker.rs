@@ -0,0 +1,144 @@
+pub mod config;
+pub mod config_def;
+pub mod configs;
+pub mod fetch_session;
+pub mod meta_properties;
+pub mod scram_credentials;
+pub mod topics;
+
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Arc;
+use crate::broker::config::BrokerConfig;
+use crate::broker::fetch_session::FetchSessionCache;
+use crate::broker::meta_properties::MetaProperties;
+use crate::coordinator::group::offsets::OFFSETS_TOPIC;
+use crate::coordinator::group::GroupCoordinator;
+use crate::coordinator::producer_id::ProducerIdManager;
+use crate::coordinator::transaction::transaction_log::TRANSACTION_STATE_TOPIC;
+use crate::coordinator::transaction::TransactionCoordinator;
+use crate::metadata::store::MetadataStore;
+use crate::metrics::Metrics;
+use crate::security::credentials::CredentialStore;
+use crate::storage::log_manager::LogManager;
+use crate::storage::topic_partition::TopicPartition;
+use crate::time::purgatory::Purgatory;
+
+/// State shared by every connection to the broker
+#[derive(Debug)]
+pub struct Broker {
+    config: BrokerConfig,
+    meta_properties: MetaProperties,
+    log_manager: Arc<LogManager>,
+    metadata: MetadataStore,
+    group_coordinator: Arc<GroupCoordinator>,
+    transaction_coordinator: Arc<TransactionCoordinator>,
+    producer_ids: ProducerIdManager,
+    /// Fetches waiting for records to be appended to the partitions they're reading
+    fetch_purgatory: Arc<Purgatory<TopicPartition>>,
+    /// Produce requests waiting for their records to be replicated before they're acknowledged
+    produce_purgatory: Purgatory<TopicPartition>,
+    fetch_sessions: FetchSessionCache,
+    metrics: Metrics,
+    /// The users that can authenticate on a SASL listener
+    credentials: CredentialStore,
+}
+
+impl Broker {
+    /// Start the broker, loading its metadata from the log directory
+    pub fn open(config: BrokerConfig) -> io::Result<Broker> {
+        let meta_properties = MetaProperties::load_or_create(config.log_dir(), config.node_id())?;
+        let log_manager = Arc::new(LogManager::load(config.log_dir())?);
+        let metadata = MetadataStore::load(&log_manager)?;
+        log_manager.remove_deleted_logs()?;
+        let group_coordinator = GroupCoordinator::new(&config);
+        let fetch_purgatory = Arc::new(Purgatory::new());
+        let transaction_coordinator = TransactionCoordinator::new(&config, log_manager.clone(), fetch_purgatory.clone(), group_coordinator.clone());
+        let producer_ids = ProducerIdManager::new(config.node_id());
+        let fetch_sessions = FetchSessionCache::new(config.max_incremental_fetch_session_cache_slots().max(0) as usize);
+        let credentials = CredentialStore::new(&config);
+        credentials.load_scram_credentials(&metadata);
+        let broker = Broker {
+            config,
+            meta_properties,
+            log_manager,
+            metadata,
+            group_coordinator,
+            transaction_coordinator,
+            producer_ids,
+            fetch_purgatory,
+            produce_purgatory: Purgatory::new(),
+            fetch_sessions,
+            metrics: Metrics::new(),
+            credentials,
+        };
+        broker.update_log_configs();
+
+        // offsets are only ever looked up by key, so only the latest offset for each partition has to be kept
+        let configs = BTreeMap::from([("cleanup.policy".to_string(), Some("compact".to_string()))]);
+        let offsets_logs = broker.internal_topic_logs(OFFSETS_TOPIC, broker.config.offsets_topic_num_partitions(), configs.clone())
+            .map_err(|err| io::Error::other(err.to_string()))?;
+        broker.group_coordinator.load_groups(offsets_logs)?;
+        // transactions complete the offsets committed in them, so they're loaded after the groups
+        let transaction_logs = broker.internal_topic_logs(TRANSACTION_STATE_TOPIC, broker.config.transaction_state_log_num_partitions(), configs)
+            .map_err(|err| io::Error::other(err.to_string()))?;
+        broker.transaction_coordinator.load_transactions(transaction_logs)?;
+        Ok(broker)
+    }
+
+    /// Flush and checkpoint every log, marking the shutdown as clean, once no more requests are being handled.
+    /// The coordinators' delayed operations are stopped first, since they'd otherwise keep writing to the logs
+    pub fn shutdown(&self) -> io::Result<()> {
+        self.transaction_coordinator.shutdown();
+        self.group_coordinator.shutdown();
+        self.log_manager.shutdown()
+    }
+
+    pub fn config(&self) -> &BrokerConfig {
+        &self.config
+    }
+
+    pub fn cluster_id(&self) -> &str {
+        self.meta_properties.cluster_id()
+    }
+
+    pub fn log_manager(&self) -> &LogManager {
+        &self.log_manager
+    }
+
+    pub fn metadata(&self) -> &MetadataStore {
+        &self.metadata
+    }
+
+    pub fn group_coordinator(&self) -> &GroupCoordinator {
+        &self.group_coordinator
+    }
+
+    pub fn transaction_coordinator(&self) -> &TransactionCoordinator {
+        &self.transaction_coordinator
+    }
+
+    pub fn producer_ids(&self) -> &ProducerIdManager {
+        &self.producer_ids
+    }
+
+    pub fn fetch_purgatory(&self) -> &Purgatory<TopicPartition> {
+        &self.fetch_purgatory
+    }
+
+    pub fn produce_purgatory(&self) -> &Purgatory<TopicPartition> {
+        &self.produce_purgatory
+    }
+
+    pub fn fetch_sessions(&self) -> &FetchSessionCache {
+        &self.fetch_sessions
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn credentials(&self) -> &CredentialStore {
+        &self.credentials
+    }
+}
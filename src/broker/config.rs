@@ -0,0 +1,453 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use crate::broker::config_def::{broker_config_key, list_items, InvalidConfigValue};
+use crate::security::credentials::jaas_users;
+use crate::security::principal::{PrincipalError, PrincipalMappingRule};
+use crate::security::sasl::SaslMechanism;
+use crate::security::tls::ClientAuth;
+use crate::security::SecurityProtocol;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to read config file: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid value for {key}: {value}")]
+    InvalidValue { key: String, value: String },
+    #[error(transparent)]
+    InvalidConfig(#[from] InvalidConfigValue),
+    #[error(transparent)]
+    InvalidPrincipalMapping(#[from] PrincipalError),
+}
+
+/// The name, host and port of a listener
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    listener_name: String,
+    host: String,
+    port: u16,
+}
+
+impl Endpoint {
+    pub fn new(host: impl Into<String>, port: u16) -> Endpoint {
+        Endpoint { listener_name: "PLAINTEXT".to_string(), host: host.into(), port }
+    }
+
+    /// Parse the first listener from a list like `PLAINTEXT://localhost:9092,CONTROLLER://:9093`,
+    /// skipping the controller listener since the controller doesn't serve clients
+    fn parse_listeners(listeners: &str) -> Option<Endpoint> {
+        listeners.split(',')
+            .filter_map(|listener| listener.trim().split_once("://"))
+            .filter(|(name, _)| *name != "CONTROLLER")
+            .find_map(|(name, address)| {
+                let (host, port) = address.rsplit_once(':')?;
+                Some(Endpoint { listener_name: name.to_string(), host: host.to_string(), port: port.parse().ok()? })
+            })
+    }
+
+    /// The name of the listener, which is its security protocol unless `listener.security.protocol.map` maps it to one
+    pub fn listener_name(&self) -> &str {
+        &self.listener_name
+    }
+
+    /// The host, where an empty host means every interface
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The host to tell clients to connect to, where listening on every interface is advertised as localhost
+    pub fn advertised_host(&self) -> &str {
+        match self.host.as_str() {
+            "" => "localhost",
+            host => host,
+        }
+    }
+
+    /// The address to bind a listener to
+    pub fn bind_address(&self) -> String {
+        match self.host.as_str() {
+            "" => format!("0.0.0.0:{}", self.port),
+            host => format!("{host}:{}", self.port),
+        }
+    }
+}
+
+/// Configuration of the broker, read from a `server.properties` file
+#[derive(Debug, Clone)]
+pub struct BrokerConfig {
+    /// Every entry in the properties file, which are the static broker configs
+    properties: BTreeMap<String, String>,
+    node_id: i32,
+    log_dir: PathBuf,
+    listener: Endpoint,
+    /// How clients connect to the listener
+    security_protocol: SecurityProtocol,
+    /// The listener clients are told to connect to, if it's different to the one we bind to
+    advertised_listener: Option<Endpoint>,
+    /// Whether topics that don't exist are created when a client asks for their metadata or produces to them
+    auto_create_topics_enable: bool,
+    /// Defaults for topics created without a partition count or replication factor
+    num_partitions: i32,
+    default_replication_factor: i16,
+    /// The bounds on the session timeout of consumer group members
+    group_min_session_timeout_ms: i32,
+    group_max_session_timeout_ms: i32,
+    /// How long the first rebalance of a group waits for more members to join
+    group_initial_rebalance_delay_ms: i32,
+    group_max_size: i32,
+    /// The timeouts of members of groups using the consumer rebalance protocol
+    group_consumer_session_timeout_ms: i32,
+    group_consumer_heartbeat_interval_ms: i32,
+    group_consumer_max_size: i32,
+    /// The assignors consumer groups can use, where the first is the default
+    group_consumer_assignors: Vec<String>,
+    /// The number of partitions of the topic that committed offsets are stored in
+    offsets_topic_num_partitions: i32,
+    offset_metadata_max_bytes: i32,
+    /// The number of partitions of the topic that transactions' state is stored in
+    transaction_state_log_num_partitions: i32,
+    /// The longest timeout a transactional producer can ask for
+    transaction_max_timeout_ms: i32,
+    /// The most fetch sessions that are kept at once
+    max_incremental_fetch_session_cache_slots: i32,
+    /// The most requests a connection can have waiting for a response before we stop reading more of them
+    queued_max_requests: i32,
+    /// The largest request a client can send, which is checked before anything is allocated for it
+    socket_request_max_bytes: i32,
+    /// How often logs are checked for segments that are past their topic's retention
+    log_retention_check_interval_ms: i64,
+    /// Where metrics are served from, if they're served at all
+    metrics_listener: Option<Endpoint>,
+    /// The PEM file with the certificate chain and private key of an SSL listener
+    ssl_keystore_location: Option<PathBuf>,
+    /// The PEM file with the certificates that client certificates are verified against
+    ssl_truststore_location: Option<PathBuf>,
+    ssl_client_auth: ClientAuth,
+    /// How the distinguished names of client certificates are turned into principal names
+    ssl_principal_mapping_rules: Vec<PrincipalMappingRule>,
+    /// The mechanisms clients of a SASL listener can authenticate with
+    sasl_enabled_mechanisms: Vec<SaslMechanism>,
+    /// The users in the JAAS config of each of the listener's mechanisms, with their passwords
+    sasl_users: Vec<(SaslMechanism, String, String)>,
+    /// How long clients of a SASL listener can go before they have to re-authenticate, where 0 means they never have to
+    connections_max_reauth_ms: i64,
+}
+
+impl Default for BrokerConfig {
+    fn default() -> Self {
+        BrokerConfig {
+            properties: BTreeMap::new(),
+            node_id: 1,
+            log_dir: PathBuf::from("/tmp/kraft-combined-logs"),
+            listener: Endpoint::new("127.0.0.1", 9092),
+            security_protocol: SecurityProtocol::Plaintext,
+            advertised_listener: None,
+            auto_create_topics_enable: true,
+            num_partitions: 1,
+            default_replication_factor: 1,
+            group_min_session_timeout_ms: 6000,
+            group_max_session_timeout_ms: 1800000,
+            group_initial_rebalance_delay_ms: 3000,
+            group_max_size: i32::MAX,
+            group_consumer_session_timeout_ms: 45000,
+            group_consumer_heartbeat_interval_ms: 5000,
+            group_consumer_max_size: i32::MAX,
+            group_consumer_assignors: vec!["uniform".to_string(), "range".to_string()],
+            offsets_topic_num_partitions: 50,
+            offset_metadata_max_bytes: 4096,
+            transaction_state_log_num_partitions: 50,
+            transaction_max_timeout_ms: 900000,
+            max_incremental_fetch_session_cache_slots: 1000,
+            queued_max_requests: 500,
+            socket_request_max_bytes: 104857600,
+            log_retention_check_interval_ms: 300000,
+            metrics_listener: None,
+            ssl_keystore_location: None,
+            ssl_truststore_location: None,
+            ssl_client_auth: ClientAuth::None,
+            ssl_principal_mapping_rules: vec![PrincipalMappingRule::Default],
+            sasl_enabled_mechanisms: vec![SaslMechanism::Plain],
+            sasl_users: Vec::new(),
+            connections_max_reauth_ms: 0,
+        }
+    }
+}
+
+impl BrokerConfig {
+    pub fn from_properties_file(path: &Path) -> Result<BrokerConfig, ConfigError> {
+        BrokerConfig::from_properties(&fs::read_to_string(path)?)
+    }
+
+    /// Parse the config from java properties style `key=value` lines, unknown keys are ignored
+    pub fn from_properties(properties: &str) -> Result<BrokerConfig, ConfigError> {
+        let mut config = BrokerConfig::default();
+        let mut security_protocol_map = HashMap::new();
+        for (key, value) in properties_entries(properties) {
+            if let Some(config_key) = broker_config_key(key) {
+                config_key.validate(value)?;
+            }
+            config.properties.insert(key.to_string(), value.to_string());
+            let invalid_value = || ConfigError::InvalidValue { key: key.to_string(), value: value.to_string() };
+            match key {
+                "node.id" | "broker.id" => config.node_id = value.parse().map_err(|_| invalid_value())?,
+                // we only support a single log directory, so use the first one
+                "log.dirs" | "log.dir" => config.log_dir = value.split(',')
+                    .next()
+                    .filter(|dir| !dir.is_empty())
+                    .map(PathBuf::from)
+                    .ok_or_else(invalid_value)?,
+                "listeners" => config.listener = Endpoint::parse_listeners(value).ok_or_else(invalid_value)?,
+                "advertised.listeners" => config.advertised_listener = Some(Endpoint::parse_listeners(value).ok_or_else(invalid_value)?),
+                "auto.create.topics.enable" => config.auto_create_topics_enable = value.parse().map_err(|_| invalid_value())?,
+                "num.partitions" => config.num_partitions = value.parse().map_err(|_| invalid_value())?,
+                "default.replication.factor" => config.default_replication_factor = value.parse().map_err(|_| invalid_value())?,
+                "group.min.session.timeout.ms" => config.group_min_session_timeout_ms = value.parse().map_err(|_| invalid_value())?,
+                "group.max.session.timeout.ms" => config.group_max_session_timeout_ms = value.parse().map_err(|_| invalid_value())?,
+                "group.initial.rebalance.delay.ms" => config.group_initial_rebalance_delay_ms = value.parse().map_err(|_| invalid_value())?,
+                "group.max.size" => config.group_max_size = value.parse().map_err(|_| invalid_value())?,
+                "group.consumer.session.timeout.ms" => config.group_consumer_session_timeout_ms = value.parse().map_err(|_| invalid_value())?,
+                "group.consumer.heartbeat.interval.ms" => config.group_consumer_heartbeat_interval_ms = value.parse().map_err(|_| invalid_value())?,
+                "group.consumer.max.size" => config.group_consumer_max_size = value.parse().map_err(|_| invalid_value())?,
+                "group.consumer.assignors" => {
+                    config.group_consumer_assignors = list_items(value).map(str::to_string).collect();
+                    if config.group_consumer_assignors.is_empty() {
+                        return Err(invalid_value());
+                    }
+                }
+                "offsets.topic.num.partitions" => config.offsets_topic_num_partitions = value.parse().map_err(|_| invalid_value())?,
+                "offset.metadata.max.bytes" => config.offset_metadata_max_bytes = value.parse().map_err(|_| invalid_value())?,
+                "transaction.state.log.num.partitions" => config.transaction_state_log_num_partitions = value.parse().map_err(|_| invalid_value())?,
+                "transaction.max.timeout.ms" => config.transaction_max_timeout_ms = value.parse().map_err(|_| invalid_value())?,
+                "max.incremental.fetch.session.cache.slots" => {
+                    config.max_incremental_fetch_session_cache_slots = value.parse().map_err(|_| invalid_value())?
+                }
+                "queued.max.requests" => config.queued_max_requests = value.parse().map_err(|_| invalid_value())?,
+                "socket.request.max.bytes" => config.socket_request_max_bytes = value.parse().map_err(|_| invalid_value())?,
+                "log.retention.check.interval.ms" => config.log_retention_check_interval_ms = value.parse().map_err(|_| invalid_value())?,
+                "metrics.listener" => config.metrics_listener = Some(Endpoint::parse_listeners(value).ok_or_else(invalid_value)?),
+                "listener.security.protocol.map" => {
+                    for entry in list_items(value) {
+                        let (name, protocol) = entry.split_once(':').ok_or_else(invalid_value)?;
+                        let protocol = SecurityProtocol::from_name(protocol).ok_or_else(invalid_value)?;
+                        security_protocol_map.insert(name.to_string(), protocol);
+                    }
+                }
+                "ssl.keystore.location" => config.ssl_keystore_location = Some(PathBuf::from(value)),
+                "ssl.truststore.location" => config.ssl_truststore_location = Some(PathBuf::from(value)),
+                "ssl.client.auth" => config.ssl_client_auth = ClientAuth::from_name(value).ok_or_else(invalid_value)?,
+                "ssl.principal.mapping.rules" => config.ssl_principal_mapping_rules = PrincipalMappingRule::parse_rules(value)?,
+                "sasl.enabled.mechanisms" => {
+                    config.sasl_enabled_mechanisms = list_items(value)
+                        .map(|mechanism| SaslMechanism::from_name(mechanism).ok_or_else(invalid_value))
+                        .collect::<Result<_, _>>()?
+                }
+                "connections.max.reauth.ms" => config.connections_max_reauth_ms = value.parse().map_err(|_| invalid_value())?,
+                _ => {}
+            }
+        }
+        // the listener's name can be mapped to its protocol after it, so the protocol is only known once every entry is read
+        let listener_name = config.listener.listener_name();
+        config.security_protocol = security_protocol_map.get(listener_name).copied()
+            .or_else(|| SecurityProtocol::from_name(listener_name))
+            .ok_or_else(|| ConfigError::InvalidValue {
+                key: "listeners".to_string(),
+                value: format!("{listener_name} has no security protocol"),
+            })?;
+        // like kafka, each of a listener's mechanisms is configured with listener.name.<listener>.<mechanism>.sasl.jaas.config
+        for mechanism in &config.sasl_enabled_mechanisms {
+            let key = format!("listener.name.{}.{}.sasl.jaas.config", listener_name.to_lowercase(), mechanism.name().to_lowercase());
+            if let Some(jaas_config) = config.properties.get(&key) {
+                config.sasl_users.extend(jaas_users(jaas_config).into_iter().map(|(username, password)| (*mechanism, username, password)));
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn properties(&self) -> &BTreeMap<String, String> {
+        &self.properties
+    }
+
+    pub fn node_id(&self) -> i32 {
+        self.node_id
+    }
+
+    pub fn log_dir(&self) -> &Path {
+        &self.log_dir
+    }
+
+    pub fn listener(&self) -> &Endpoint {
+        &self.listener
+    }
+
+    pub fn security_protocol(&self) -> SecurityProtocol {
+        self.security_protocol
+    }
+
+    pub fn advertised_listener(&self) -> &Endpoint {
+        self.advertised_listener.as_ref().unwrap_or(&self.listener)
+    }
+
+    /// Whether Metadata and Produce requests create the topics they ask for that don't exist
+    pub fn auto_create_topics_enable(&self) -> bool {
+        self.auto_create_topics_enable
+    }
+
+    pub fn num_partitions(&self) -> i32 {
+        self.num_partitions
+    }
+
+    pub fn default_replication_factor(&self) -> i16 {
+        self.default_replication_factor
+    }
+
+    pub fn group_min_session_timeout_ms(&self) -> i32 {
+        self.group_min_session_timeout_ms
+    }
+
+    pub fn group_max_session_timeout_ms(&self) -> i32 {
+        self.group_max_session_timeout_ms
+    }
+
+    pub fn group_initial_rebalance_delay_ms(&self) -> i32 {
+        self.group_initial_rebalance_delay_ms
+    }
+
+    pub fn group_max_size(&self) -> i32 {
+        self.group_max_size
+    }
+
+    pub fn group_consumer_session_timeout_ms(&self) -> i32 {
+        self.group_consumer_session_timeout_ms
+    }
+
+    pub fn group_consumer_heartbeat_interval_ms(&self) -> i32 {
+        self.group_consumer_heartbeat_interval_ms
+    }
+
+    pub fn group_consumer_max_size(&self) -> i32 {
+        self.group_consumer_max_size
+    }
+
+    pub fn group_consumer_assignors(&self) -> &[String] {
+        &self.group_consumer_assignors
+    }
+
+    pub fn offsets_topic_num_partitions(&self) -> i32 {
+        self.offsets_topic_num_partitions
+    }
+
+    pub fn offset_metadata_max_bytes(&self) -> i32 {
+        self.offset_metadata_max_bytes
+    }
+
+    pub fn transaction_state_log_num_partitions(&self) -> i32 {
+        self.transaction_state_log_num_partitions
+    }
+
+    pub fn transaction_max_timeout_ms(&self) -> i32 {
+        self.transaction_max_timeout_ms
+    }
+
+    pub fn max_incremental_fetch_session_cache_slots(&self) -> i32 {
+        self.max_incremental_fetch_session_cache_slots
+    }
+
+    pub fn queued_max_requests(&self) -> i32 {
+        self.queued_max_requests
+    }
+
+    pub fn socket_request_max_bytes(&self) -> i32 {
+        self.socket_request_max_bytes
+    }
+
+    pub fn log_retention_check_interval_ms(&self) -> i64 {
+        self.log_retention_check_interval_ms
+    }
+
+    pub fn metrics_listener(&self) -> Option<&Endpoint> {
+        self.metrics_listener.as_ref()
+    }
+
+    pub fn ssl_keystore_location(&self) -> Option<&Path> {
+        self.ssl_keystore_location.as_deref()
+    }
+
+    pub fn ssl_truststore_location(&self) -> Option<&Path> {
+        self.ssl_truststore_location.as_deref()
+    }
+
+    pub fn ssl_client_auth(&self) -> ClientAuth {
+        self.ssl_client_auth
+    }
+
+    pub fn ssl_principal_mapping_rules(&self) -> &[PrincipalMappingRule] {
+        &self.ssl_principal_mapping_rules
+    }
+
+    pub fn sasl_enabled_mechanisms(&self) -> &[SaslMechanism] {
+        &self.sasl_enabled_mechanisms
+    }
+
+    pub fn sasl_users(&self) -> &[(SaslMechanism, String, String)] {
+        &self.sasl_users
+    }
+
+    pub fn connections_max_reauth_ms(&self) -> i64 {
+        self.connections_max_reauth_ms
+    }
+}
+
+/// The `key=value` entries of a java properties file, skipping blank lines and comments
+pub fn properties_entries(properties: &str) -> impl Iterator<Item = (&str, &str)> {
+    properties.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('!'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_properties() {
+        let config = BrokerConfig::from_properties(
+            "# comment\nnode.id=3\nlisteners=PLAINTEXT://:9092,CONTROLLER://:9093\nadvertised.listeners=PLAINTEXT://localhost:19092\nauto.create.topics.enable=false\n"
+        ).unwrap();
+        assert_eq!(config.node_id(), 3);
+        assert_eq!(config.listener(), &Endpoint::new("", 9092));
+        assert_eq!(config.listener().bind_address(), "0.0.0.0:9092");
+        assert_eq!(config.advertised_listener(), &Endpoint::new("localhost", 19092));
+        assert!(!config.auto_create_topics_enable());
+        assert_eq!(config.security_protocol(), SecurityProtocol::Plaintext);
+
+        let config = BrokerConfig::from_properties(
+            "listeners=CLIENT://:9093\nlistener.security.protocol.map=CLIENT:SSL,CONTROLLER:PLAINTEXT\nssl.client.auth=required\n"
+        ).unwrap();
+        assert_eq!(config.security_protocol(), SecurityProtocol::Ssl);
+        assert_eq!(config.ssl_client_auth(), ClientAuth::Required);
+        assert!(BrokerConfig::from_properties("listeners=CLIENT://:9093").is_err());
+
+        let config = BrokerConfig::from_properties(concat!(
+            "listeners=SASL_PLAINTEXT://:9094\nsasl.enabled.mechanisms=PLAIN,SCRAM-SHA-256\n",
+            "listener.name.sasl_plaintext.plain.sasl.jaas.config=org.apache.kafka.common.security.plain.PlainLoginModule required ",
+            "username=\"admin\" password=\"admin-secret\" user_admin=\"admin-secret\" user_alice=\"alice-secret\";\n",
+        )).unwrap();
+        assert_eq!(config.security_protocol(), SecurityProtocol::SaslPlaintext);
+        assert_eq!(config.sasl_users(), &[
+            (SaslMechanism::Plain, "admin".to_string(), "admin-secret".to_string()),
+            (SaslMechanism::Plain, "alice".to_string(), "alice-secret".to_string()),
+        ]);
+        assert!(BrokerConfig::from_properties("sasl.enabled.mechanisms=GSSAPI").is_err());
+
+        assert!(BrokerConfig::from_properties("listeners=CONTROLLER://:9093").is_err());
+        assert!(BrokerConfig::from_properties("num.partitions=many").is_err());
+    }
+}
@@ -0,0 +1,427 @@
+use bytes::BufMut;
+use thiserror::Error;
+use crate::serialisation::ToKafkaBytes;
+
+const LONG_MAX: &str = "9223372036854775807";
+const MS_PER_MINUTE: i64 = 60 * 1000;
+const MS_PER_HOUR: i64 = 60 * MS_PER_MINUTE;
+
+/// The definitions of every topic config, with the broker configs that are used when a topic doesn't override them
+pub static TOPIC_CONFIGS: &[ConfigKey] = &[
+    ConfigKey::new("cleanup.policy", ConfigType::List, Some("delete"), Validator::ValidList(&["compact", "delete"]),
+                   "The retention policy to use on old log segments, either delete or compact or both")
+        .with_synonyms(&[Synonym::new("log.cleanup.policy")]),
+    ConfigKey::new("compression.type", ConfigType::String, Some("producer"),
+                   Validator::ValidString(&["uncompressed", "zstd", "lz4", "snappy", "gzip", "producer"]),
+                   "The final compression type for the topic, where producer means retain the codec set by the producer")
+        .with_synonyms(&[Synonym::new("compression.type")]),
+    ConfigKey::new("delete.retention.ms", ConfigType::Long, Some("86400000"), Validator::AtLeast(0),
+                   "The amount of time to retain delete tombstone markers for log compacted topics")
+        .with_synonyms(&[Synonym::new("log.cleaner.delete.retention.ms")]),
+    ConfigKey::new("file.delete.delay.ms", ConfigType::Long, Some("60000"), Validator::AtLeast(0),
+                   "The time to wait before deleting a file from the filesystem")
+        .with_synonyms(&[Synonym::new("log.segment.delete.delay.ms")]),
+    ConfigKey::new("flush.messages", ConfigType::Long, Some(LONG_MAX), Validator::AtLeast(1),
+                   "The number of messages written to the log before an fsync is forced")
+        .with_synonyms(&[Synonym::new("log.flush.interval.messages")]),
+    ConfigKey::new("flush.ms", ConfigType::Long, Some(LONG_MAX), Validator::AtLeast(0),
+                   "The time between fsyncs of the log")
+        .with_synonyms(&[Synonym::new("log.flush.interval.ms")]),
+    ConfigKey::new("index.interval.bytes", ConfigType::Int, Some("4096"), Validator::AtLeast(0),
+                   "How frequently an entry is added to the offset index")
+        .with_synonyms(&[Synonym::new("log.index.interval.bytes")]),
+    ConfigKey::new("max.compaction.lag.ms", ConfigType::Long, Some(LONG_MAX), Validator::AtLeast(1),
+                   "The maximum time a message will remain ineligible for compaction in the log")
+        .with_synonyms(&[Synonym::new("log.cleaner.max.compaction.lag.ms")]),
+    ConfigKey::new("max.message.bytes", ConfigType::Int, Some("1048588"), Validator::AtLeast(0),
+                   "The largest record batch size allowed by the topic")
+        .with_synonyms(&[Synonym::new("message.max.bytes")]),
+    ConfigKey::new("message.timestamp.type", ConfigType::String, Some("CreateTime"), Validator::ValidString(&["CreateTime", "LogAppendTime"]),
+                   "Whether the timestamp in the message is the message create time or the log append time")
+        .with_synonyms(&[Synonym::new("log.message.timestamp.type")]),
+    ConfigKey::new("min.cleanable.dirty.ratio", ConfigType::Double, Some("0.5"), Validator::Between(0.0, 1.0),
+                   "The minimum ratio of dirty log to total log for a log to be eligible for cleaning")
+        .with_synonyms(&[Synonym::new("log.cleaner.min.cleanable.ratio")]),
+    ConfigKey::new("min.compaction.lag.ms", ConfigType::Long, Some("0"), Validator::AtLeast(0),
+                   "The minimum time a message will remain uncompacted in the log")
+        .with_synonyms(&[Synonym::new("log.cleaner.min.compaction.lag.ms")]),
+    ConfigKey::new("min.insync.replicas", ConfigType::Int, Some("1"), Validator::AtLeast(1),
+                   "The minimum number of replicas that must acknowledge a write with acks=all")
+        .with_synonyms(&[Synonym::new("min.insync.replicas")]),
+    ConfigKey::new("preallocate", ConfigType::Boolean, Some("false"), Validator::Any,
+                   "Whether to preallocate the file on disk when creating a new log segment")
+        .with_synonyms(&[Synonym::new("log.preallocate")]),
+    ConfigKey::new("retention.bytes", ConfigType::Long, Some("-1"), Validator::Any,
+                   "The maximum size a partition can grow to before old log segments are discarded, -1 for no limit")
+        .with_synonyms(&[Synonym::new("log.retention.bytes")]),
+    ConfigKey::new("retention.ms", ConfigType::Long, Some("604800000"), Validator::AtLeast(-1),
+                   "The maximum time a log is retained before old log segments are discarded, -1 for no limit")
+        .with_synonyms(&[
+            Synonym::new("log.retention.ms"),
+            Synonym::with_multiplier("log.retention.minutes", MS_PER_MINUTE),
+            Synonym::with_multiplier("log.retention.hours", MS_PER_HOUR),
+        ]),
+    ConfigKey::new("segment.bytes", ConfigType::Int, Some("1073741824"), Validator::AtLeast(14),
+                   "The segment file size for the log")
+        .with_synonyms(&[Synonym::new("log.segment.bytes")]),
+    ConfigKey::new("segment.index.bytes", ConfigType::Int, Some("10485760"), Validator::AtLeast(4),
+                   "The size of the index that maps offsets to file positions")
+        .with_synonyms(&[Synonym::new("log.index.size.max.bytes")]),
+    ConfigKey::new("segment.jitter.ms", ConfigType::Long, Some("0"), Validator::AtLeast(0),
+                   "The maximum random jitter subtracted from the scheduled segment roll time")
+        .with_synonyms(&[Synonym::new("log.roll.jitter.ms"), Synonym::with_multiplier("log.roll.jitter.hours", MS_PER_HOUR)]),
+    ConfigKey::new("segment.ms", ConfigType::Long, Some("604800000"), Validator::AtLeast(1),
+                   "The period of time after which the log is forced to roll even if the segment file isn't full")
+        .with_synonyms(&[Synonym::new("log.roll.ms"), Synonym::with_multiplier("log.roll.hours", MS_PER_HOUR)]),
+    ConfigKey::new("unclean.leader.election.enable", ConfigType::Boolean, Some("false"), Validator::Any,
+                   "Whether replicas not in the ISR can be elected as leader as a last resort")
+        .with_synonyms(&[Synonym::new("unclean.leader.election.enable")]),
+];
+
+/// The definitions of every broker config, only the dynamic ones can be altered while the broker is running
+pub static BROKER_CONFIGS: &[ConfigKey] = &[
+    ConfigKey::new("node.id", ConfigType::Int, Some("1"), Validator::Any,
+                   "The node id of this broker"),
+    ConfigKey::new("log.dirs", ConfigType::List, Some("/tmp/kraft-combined-logs"), Validator::Any,
+                   "The directory the log data is kept in"),
+    ConfigKey::new("listeners", ConfigType::List, Some("PLAINTEXT://127.0.0.1:9092"), Validator::Any,
+                   "The listener the broker accepts connections on"),
+    ConfigKey::new("advertised.listeners", ConfigType::String, None, Validator::Any,
+                   "The listener clients are told to connect to, if it's different to listeners"),
+    ConfigKey::new("auto.create.topics.enable", ConfigType::Boolean, Some("true"), Validator::Any,
+                   "Whether topics that don't exist are created when their metadata is requested"),
+    ConfigKey::new("num.partitions", ConfigType::Int, Some("1"), Validator::AtLeast(1),
+                   "The default number of partitions per topic"),
+    ConfigKey::new("default.replication.factor", ConfigType::Int, Some("1"), Validator::Any,
+                   "The default replication factor of topics"),
+    ConfigKey::new("group.min.session.timeout.ms", ConfigType::Int, Some("6000"), Validator::Any,
+                   "The minimum session timeout a consumer group member can have"),
+    ConfigKey::new("group.max.session.timeout.ms", ConfigType::Int, Some("1800000"), Validator::Any,
+                   "The maximum session timeout a consumer group member can have"),
+    ConfigKey::new("group.initial.rebalance.delay.ms", ConfigType::Int, Some("3000"), Validator::AtLeast(0),
+                   "How long to wait for more members to join a new group before its first rebalance"),
+    ConfigKey::new("group.max.size", ConfigType::Int, Some("2147483647"), Validator::AtLeast(1),
+                   "The maximum number of members a consumer group can have"),
+    ConfigKey::new("group.consumer.session.timeout.ms", ConfigType::Int, Some("45000"), Validator::AtLeast(1),
+                   "The timeout to detect client failures when using the consumer group protocol"),
+    ConfigKey::new("group.consumer.heartbeat.interval.ms", ConfigType::Int, Some("5000"), Validator::AtLeast(1),
+                   "The heartbeat interval given to the members of a consumer group"),
+    ConfigKey::new("group.consumer.max.size", ConfigType::Int, Some("2147483647"), Validator::AtLeast(1),
+                   "The maximum number of consumers that a single consumer group can accommodate"),
+    ConfigKey::new("group.consumer.assignors", ConfigType::List, Some("uniform,range"), Validator::ValidList(&["uniform", "range"]),
+                   "The server side assignors, where the first one is used by default"),
+    ConfigKey::new("offsets.topic.num.partitions", ConfigType::Int, Some("50"), Validator::AtLeast(1),
+                   "The number of partitions for the offset commit topic"),
+    ConfigKey::new("offset.metadata.max.bytes", ConfigType::Int, Some("4096"), Validator::AtLeast(0),
+                   "The maximum size for a metadata entry associated with an offset commit"),
+    ConfigKey::new("transaction.state.log.num.partitions", ConfigType::Int, Some("50"), Validator::AtLeast(1),
+                   "The number of partitions for the transaction topic"),
+    ConfigKey::new("transaction.max.timeout.ms", ConfigType::Int, Some("900000"), Validator::AtLeast(1),
+                   "The maximum allowed timeout for transactions"),
+    ConfigKey::new("max.incremental.fetch.session.cache.slots", ConfigType::Int, Some("1000"), Validator::AtLeast(0),
+                   "The maximum number of incremental fetch sessions that we will maintain"),
+    ConfigKey::new("queued.max.requests", ConfigType::Int, Some("500"), Validator::AtLeast(1),
+                   "The number of requests a connection can have queued, before we stop reading more requests from it"),
+    ConfigKey::new("socket.request.max.bytes", ConfigType::Int, Some("104857600"), Validator::AtLeast(1),
+                   "The maximum number of bytes in a socket request"),
+    ConfigKey::new("log.retention.check.interval.ms", ConfigType::Long, Some("300000"), Validator::AtLeast(1),
+                   "The frequency in milliseconds that logs are checked for segments to delete by their retention"),
+    ConfigKey::new("metrics.listener", ConfigType::String, None, Validator::Any,
+                   "The listener Prometheus metrics are served from over HTTP at /metrics, such as http://:9404"),
+    ConfigKey::new("listener.security.protocol.map", ConfigType::String, Some("PLAINTEXT:PLAINTEXT,SSL:SSL"), Validator::Any,
+                   "Map between listener names and security protocols, for listeners that aren't named after their protocol"),
+    ConfigKey::new("ssl.keystore.type", ConfigType::String, Some("PEM"), Validator::ValidString(&["PEM"]),
+                   "The file format of the key store file"),
+    ConfigKey::new("ssl.keystore.location", ConfigType::String, None, Validator::Any,
+                   "The location of the key store file, with the certificate chain and private key of SSL listeners"),
+    ConfigKey::new("ssl.truststore.type", ConfigType::String, Some("PEM"), Validator::ValidString(&["PEM"]),
+                   "The file format of the trust store file"),
+    ConfigKey::new("ssl.truststore.location", ConfigType::String, None, Validator::Any,
+                   "The location of the trust store file, with the certificates client certificates are verified against"),
+    ConfigKey::new("ssl.client.auth", ConfigType::String, Some("none"), Validator::ValidString(&["required", "requested", "none"]),
+                   "Whether clients of SSL listeners have to authenticate with a certificate"),
+    ConfigKey::new("ssl.principal.mapping.rules", ConfigType::String, Some("DEFAULT"), Validator::Any,
+                   "Rules for mapping the distinguished name of a client certificate to a principal name"),
+    ConfigKey::new("sasl.enabled.mechanisms", ConfigType::List, Some("PLAIN"),
+                   Validator::ValidList(&["PLAIN", "SCRAM-SHA-256", "SCRAM-SHA-512"]),
+                   "The SASL mechanisms clients of SASL listeners can authenticate with"),
+    ConfigKey::new("connections.max.reauth.ms", ConfigType::Long, Some("0"), Validator::AtLeast(0),
+                   "How long a SASL session lasts before the client has to re-authenticate, where 0 means it never has to"),
+    ConfigKey::new("log.cleanup.policy", ConfigType::List, Some("delete"), Validator::ValidList(&["compact", "delete"]),
+                   "The default cleanup policy for segments beyond the retention window").dynamic(),
+    ConfigKey::new("compression.type", ConfigType::String, Some("producer"),
+                   Validator::ValidString(&["uncompressed", "zstd", "lz4", "snappy", "gzip", "producer"]),
+                   "The default final compression type for topics").dynamic(),
+    ConfigKey::new("log.cleaner.delete.retention.ms", ConfigType::Long, Some("86400000"), Validator::AtLeast(0),
+                   "The amount of time to retain delete tombstone markers for log compacted topics").dynamic(),
+    ConfigKey::new("log.segment.delete.delay.ms", ConfigType::Long, Some("60000"), Validator::AtLeast(0),
+                   "The amount of time to wait before deleting a file from the filesystem").dynamic(),
+    ConfigKey::new("log.flush.interval.messages", ConfigType::Long, Some(LONG_MAX), Validator::AtLeast(1),
+                   "The number of messages accumulated on a log partition before messages are flushed to disk").dynamic(),
+    ConfigKey::new("log.flush.interval.ms", ConfigType::Long, None, Validator::Any,
+                   "The maximum time a message is kept in memory before it's flushed to disk").dynamic(),
+    ConfigKey::new("log.index.interval.bytes", ConfigType::Int, Some("4096"), Validator::AtLeast(0),
+                   "The interval with which an entry is added to the offset index").dynamic(),
+    ConfigKey::new("log.cleaner.max.compaction.lag.ms", ConfigType::Long, Some(LONG_MAX), Validator::AtLeast(1),
+                   "The maximum time a message will remain ineligible for compaction in the log").dynamic(),
+    ConfigKey::new("message.max.bytes", ConfigType::Int, Some("1048588"), Validator::AtLeast(0),
+                   "The largest record batch size allowed").dynamic(),
+    ConfigKey::new("log.message.timestamp.type", ConfigType::String, Some("CreateTime"), Validator::ValidString(&["CreateTime", "LogAppendTime"]),
+                   "Whether the timestamp in the message is the message create time or the log append time").dynamic(),
+    ConfigKey::new("log.cleaner.min.cleanable.ratio", ConfigType::Double, Some("0.5"), Validator::Between(0.0, 1.0),
+                   "The minimum ratio of dirty log to total log for a log to be eligible for cleaning").dynamic(),
+    ConfigKey::new("log.cleaner.min.compaction.lag.ms", ConfigType::Long, Some("0"), Validator::AtLeast(0),
+                   "The minimum time a message will remain uncompacted in the log").dynamic(),
+    ConfigKey::new("min.insync.replicas", ConfigType::Int, Some("1"), Validator::AtLeast(1),
+                   "The minimum number of replicas that must acknowledge a write with acks=all").dynamic(),
+    ConfigKey::new("log.preallocate", ConfigType::Boolean, Some("false"), Validator::Any,
+                   "Whether to preallocate the file on disk when creating a new log segment").dynamic(),
+    ConfigKey::new("log.retention.bytes", ConfigType::Long, Some("-1"), Validator::Any,
+                   "The maximum size of the log before deleting it").dynamic(),
+    ConfigKey::new("log.retention.ms", ConfigType::Long, None, Validator::AtLeast(-1),
+                   "The number of milliseconds to keep a log file before deleting it, overriding log.retention.minutes").dynamic(),
+    ConfigKey::new("log.retention.minutes", ConfigType::Int, None, Validator::AtLeast(-1),
+                   "The number of minutes to keep a log file before deleting it, overriding log.retention.hours").dynamic(),
+    ConfigKey::new("log.retention.hours", ConfigType::Int, Some("168"), Validator::AtLeast(-1),
+                   "The number of hours to keep a log file before deleting it").dynamic(),
+    ConfigKey::new("log.segment.bytes", ConfigType::Int, Some("1073741824"), Validator::AtLeast(14),
+                   "The maximum size of a single log file").dynamic(),
+    ConfigKey::new("log.index.size.max.bytes", ConfigType::Int, Some("10485760"), Validator::AtLeast(4),
+                   "The maximum size in bytes of the offset index").dynamic(),
+    ConfigKey::new("log.roll.jitter.ms", ConfigType::Long, None, Validator::AtLeast(0),
+                   "The maximum jitter to subtract from the log roll time, overriding log.roll.jitter.hours").dynamic(),
+    ConfigKey::new("log.roll.jitter.hours", ConfigType::Int, Some("0"), Validator::AtLeast(0),
+                   "The maximum jitter to subtract from the log roll time").dynamic(),
+    ConfigKey::new("log.roll.ms", ConfigType::Long, None, Validator::AtLeast(1),
+                   "The maximum time before a new log segment is rolled out, overriding log.roll.hours").dynamic(),
+    ConfigKey::new("log.roll.hours", ConfigType::Int, Some("168"), Validator::AtLeast(1),
+                   "The maximum time before a new log segment is rolled out").dynamic(),
+    ConfigKey::new("unclean.leader.election.enable", ConfigType::Boolean, Some("false"), Validator::Any,
+                   "Whether replicas not in the ISR can be elected as leader as a last resort").dynamic(),
+    ConfigKey::new("ssl.keystore.password", ConfigType::Password, None, Validator::Any,
+                   "The store password for the key store file").dynamic(),
+    ConfigKey::new("ssl.key.password", ConfigType::Password, None, Validator::Any,
+                   "The password of the private key in the key store file").dynamic(),
+    ConfigKey::new("ssl.truststore.password", ConfigType::Password, None, Validator::Any,
+                   "The password for the trust store file").dynamic(),
+];
+
+pub fn topic_config_key(name: &str) -> Option<&'static ConfigKey> {
+    TOPIC_CONFIGS.iter().find(|key| key.name == name)
+}
+
+pub fn broker_config_key(name: &str) -> Option<&'static ConfigKey> {
+    BROKER_CONFIGS.iter().find(|key| key.name == name)
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid value {value} for configuration {name}: {reason}")]
+pub struct InvalidConfigValue {
+    name: String,
+    value: String,
+    reason: String,
+}
+
+/// The type of a config's value
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConfigType {
+    Boolean,
+    String,
+    Int,
+    Long,
+    Double,
+    /// A comma separated list
+    List,
+    /// A string that is never shown to clients
+    Password,
+}
+
+impl ToKafkaBytes for ConfigType {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let config_type: i8 = match self {
+            ConfigType::Boolean => 1,
+            ConfigType::String => 2,
+            ConfigType::Int => 3,
+            ConfigType::Long => 5,
+            ConfigType::Double => 6,
+            ConfigType::List => 7,
+            ConfigType::Password => 9,
+        };
+        config_type.write_kafka_bytes(buf)
+    }
+}
+
+/// Extra checks a config's value has to pass, on top of having the right type
+#[derive(Debug, Copy, Clone)]
+pub enum Validator {
+    Any,
+    AtLeast(i64),
+    Between(f64, f64),
+    ValidString(&'static [&'static str]),
+    /// Every item of the list has to be one of these
+    ValidList(&'static [&'static str]),
+}
+
+/// A broker config that a topic config falls back to when the topic doesn't override it
+#[derive(Debug, Copy, Clone)]
+pub struct Synonym {
+    name: &'static str,
+    /// Some synonyms are in a coarser unit, so their value is multiplied by this to get the topic config's value
+    multiplier: i64,
+}
+
+impl Synonym {
+    const fn new(name: &'static str) -> Synonym {
+        Synonym { name, multiplier: 1 }
+    }
+
+    const fn with_multiplier(name: &'static str, multiplier: i64) -> Synonym {
+        Synonym { name, multiplier }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Convert the synonym's value to the value of the topic config
+    pub fn to_topic_value(&self, value: &str) -> String {
+        match self.multiplier {
+            1 => value.to_string(),
+            multiplier => value.parse::<i64>()
+                .map_or_else(|_| value.to_string(), |value| value.saturating_mul(multiplier).to_string()),
+        }
+    }
+}
+
+/// The definition of a config
+#[derive(Debug, Clone)]
+pub struct ConfigKey {
+    name: &'static str,
+    config_type: ConfigType,
+    /// None when the config has no value unless it's set
+    default: Option<&'static str>,
+    validator: Validator,
+    documentation: &'static str,
+    /// The broker configs a topic config falls back to, in order of precedence
+    synonyms: &'static [Synonym],
+    /// Whether a broker config can be altered while the broker is running, topic configs always can be
+    dynamic: bool,
+}
+
+impl ConfigKey {
+    const fn new(name: &'static str, config_type: ConfigType, default: Option<&'static str>, validator: Validator, documentation: &'static str) -> ConfigKey {
+        ConfigKey { name, config_type, default, validator, documentation, synonyms: &[], dynamic: false }
+    }
+
+    const fn with_synonyms(self, synonyms: &'static [Synonym]) -> ConfigKey {
+        ConfigKey { synonyms, dynamic: true, ..self }
+    }
+
+    const fn dynamic(self) -> ConfigKey {
+        ConfigKey { dynamic: true, ..self }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn config_type(&self) -> ConfigType {
+        self.config_type
+    }
+
+    pub fn default(&self) -> Option<&'static str> {
+        self.default
+    }
+
+    pub fn documentation(&self) -> &'static str {
+        self.documentation
+    }
+
+    pub fn synonyms(&self) -> &'static [Synonym] {
+        self.synonyms
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.dynamic
+    }
+
+    pub fn is_sensitive(&self) -> bool {
+        self.config_type == ConfigType::Password
+    }
+
+    /// Check the value can be parsed as the config's type, and passes its validator
+    pub fn validate(&self, value: &str) -> Result<(), InvalidConfigValue> {
+        let invalid = |reason: String| InvalidConfigValue { name: self.name.to_string(), value: value.to_string(), reason };
+        let trimmed = value.trim();
+        let type_error = match self.config_type {
+            ConfigType::Boolean if !trimmed.eq_ignore_ascii_case("true") && !trimmed.eq_ignore_ascii_case("false") => {
+                Some("Expected value to be either true or false")
+            }
+            ConfigType::Int if trimmed.parse::<i32>().is_err() => Some("Not a number of type INT"),
+            ConfigType::Long if trimmed.parse::<i64>().is_err() => Some("Not a number of type LONG"),
+            ConfigType::Double if trimmed.parse::<f64>().is_err() => Some("Not a number of type DOUBLE"),
+            _ => None,
+        };
+        if let Some(reason) = type_error {
+            return Err(invalid(reason.to_string()));
+        }
+
+        match self.validator {
+            Validator::Any => Ok(()),
+            Validator::AtLeast(min) => match trimmed.parse::<i64>() {
+                Ok(number) if number < min => Err(invalid(format!("Value must be at least {min}"))),
+                _ => Ok(()),
+            },
+            Validator::Between(min, max) => match trimmed.parse::<f64>() {
+                Ok(number) if number < min => Err(invalid(format!("Value must be at least {min:?}"))),
+                Ok(number) if number > max => Err(invalid(format!("Value must be no more than {max:?}"))),
+                _ => Ok(()),
+            },
+            Validator::ValidString(valid) if !valid.contains(&trimmed) => {
+                Err(invalid(format!("String must be one of: {}", valid.join(", "))))
+            }
+            Validator::ValidList(valid) if !list_items(trimmed).all(|item| valid.contains(&item)) => {
+                Err(invalid(format!("String must be one of: {}", valid.join(", "))))
+            }
+            Validator::ValidString(_) | Validator::ValidList(_) => Ok(()),
+        }
+    }
+}
+
+/// The items of a comma separated list config
+pub fn list_items(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let retention = topic_config_key("retention.ms").unwrap();
+        assert!(retention.validate("-1").is_ok());
+        assert!(retention.validate("-2").is_err());
+        assert!(retention.validate("forever").is_err());
+
+        let cleanup_policy = topic_config_key("cleanup.policy").unwrap();
+        assert!(cleanup_policy.validate("compact,delete").is_ok());
+        assert!(cleanup_policy.validate("compact, archive").is_err());
+
+        let ratio = topic_config_key("min.cleanable.dirty.ratio").unwrap();
+        assert!(ratio.validate("0.25").is_ok());
+        assert!(ratio.validate("1.5").is_err());
+
+        assert!(topic_config_key("preallocate").unwrap().validate("TRUE").is_ok());
+        assert!(topic_config_key("preallocate").unwrap().validate("yes").is_err());
+    }
+
+    #[test]
+    fn test_synonym_to_topic_value() {
+        let hours = Synonym::with_multiplier("log.retention.hours", MS_PER_HOUR);
+        assert_eq!(hours.to_topic_value("168"), "604800000");
+        assert_eq!(Synonym::new("log.retention.ms").to_topic_value("1000"), "1000");
+    }
+}
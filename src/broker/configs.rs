@@ -0,0 +1,429 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::str::FromStr;
+use bytes::BufMut;
+use thiserror::Error;
+use crate::broker::Broker;
+use crate::broker::config_def::{broker_config_key, list_items, topic_config_key, ConfigKey, ConfigType, BROKER_CONFIGS, TOPIC_CONFIGS};
+use crate::metadata::image::MetadataImage;
+use crate::metadata::records::{ConfigRecord, MetadataRecord, BROKER_RESOURCE_TYPE, TOPIC_RESOURCE_TYPE};
+use crate::serialisation::ToKafkaBytes;
+use crate::storage::log::LogConfig;
+
+const NO_CONFIGS: &BTreeMap<String, String> = &BTreeMap::new();
+
+#[derive(Debug, Error)]
+pub enum ConfigsError {
+    #[error("{0}")]
+    InvalidRequest(String),
+    #[error("{0}")]
+    InvalidConfig(String),
+    #[error("This server does not host this topic-partition.")]
+    UnknownTopic(String),
+    #[error("Storage error: {0}")]
+    Storage(#[from] io::Error),
+}
+
+/// Something that has configs
+#[derive(Debug, Copy, Clone)]
+pub enum ConfigResource<'a> {
+    Topic(&'a str),
+    /// A broker by its node id, or the defaults of every broker in the cluster if the name is empty
+    Broker(&'a str),
+}
+
+/// Where the value of a config came from
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    TopicConfig,
+    DynamicBrokerConfig,
+    DynamicDefaultBrokerConfig,
+    StaticBrokerConfig,
+    DefaultConfig,
+}
+
+impl ToKafkaBytes for ConfigSource {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        let source: i8 = match self {
+            ConfigSource::TopicConfig => 1,
+            ConfigSource::DynamicBrokerConfig => 2,
+            ConfigSource::DynamicDefaultBrokerConfig => 3,
+            ConfigSource::StaticBrokerConfig => 4,
+            ConfigSource::DefaultConfig => 5,
+        };
+        source.write_kafka_bytes(buf)
+    }
+}
+
+/// A value a config has been given at one level, sensitive values are hidden
+#[derive(Debug, Clone)]
+pub struct ConfigSynonym {
+    pub name: String,
+    pub value: Option<String>,
+    pub source: ConfigSource,
+}
+
+/// The current value of a config, sensitive values are hidden
+#[derive(Debug, Clone)]
+pub struct DescribedConfig {
+    pub name: String,
+    pub value: Option<String>,
+    pub source: ConfigSource,
+    pub read_only: bool,
+    pub is_sensitive: bool,
+    /// Every value the config has been given, in order of precedence, so the first one is in use
+    pub synonyms: Vec<ConfigSynonym>,
+    pub config_type: ConfigType,
+    pub documentation: &'static str,
+}
+
+/// A change to a single config of a resource
+#[derive(Debug, Clone)]
+pub enum AlterConfigOp {
+    Set(String),
+    /// Remove the override, so the config goes back to its default
+    Delete,
+    /// Add items to a list config
+    Append(String),
+    /// Remove items from a list config
+    Subtract(String),
+}
+
+/// The configs a topic is using, from its own overrides or else the broker's configs
+#[derive(Debug, Clone)]
+pub struct TopicConfig {
+    values: BTreeMap<&'static str, String>,
+}
+
+impl TopicConfig {
+    fn parse<T: FromStr>(&self, name: &str) -> T {
+        self.values.get(name)
+            .and_then(|value| value.trim().parse().ok())
+            .or_else(|| topic_config_key(name)?.default()?.parse().ok())
+            .expect("topic configs have valid defaults")
+    }
+
+    pub fn cleanup_policy(&self) -> Vec<&str> {
+        let value = self.values.get("cleanup.policy")
+            .map(String::as_str)
+            .or_else(|| topic_config_key("cleanup.policy")?.default())
+            .expect("topic configs have valid defaults");
+        list_items(value).collect()
+    }
+
+    pub fn retention_ms(&self) -> i64 {
+        self.parse("retention.ms")
+    }
+
+    pub fn retention_bytes(&self) -> i64 {
+        self.parse("retention.bytes")
+    }
+
+    pub fn is_log_append_time(&self) -> bool {
+        self.values.get("message.timestamp.type").is_some_and(|value| value == "LogAppendTime")
+    }
+
+    pub fn max_message_bytes(&self) -> i32 {
+        self.parse("max.message.bytes")
+    }
+
+    pub fn segment_bytes(&self) -> i32 {
+        self.parse("segment.bytes")
+    }
+
+    pub fn index_interval_bytes(&self) -> i32 {
+        self.parse("index.interval.bytes")
+    }
+
+    pub fn min_insync_replicas(&self) -> i32 {
+        self.parse("min.insync.replicas")
+    }
+
+    /// The configs that decide how the topic's logs are stored
+    pub fn log_config(&self) -> LogConfig {
+        LogConfig {
+            segment_bytes: self.segment_bytes() as u64,
+            index_interval_bytes: self.index_interval_bytes() as u64,
+            delete_retained: self.cleanup_policy().contains(&"delete"),
+            retention_ms: self.retention_ms(),
+            retention_bytes: self.retention_bytes(),
+        }
+    }
+}
+
+impl Broker {
+    /// Describe every config of the resource
+    pub fn describe_configs(&self, resource: ConfigResource) -> Result<Vec<DescribedConfig>, ConfigsError> {
+        let image = self.metadata.image();
+        match resource {
+            ConfigResource::Topic(name) => {
+                let topic = image.topic(name).ok_or_else(|| ConfigsError::UnknownTopic(name.to_string()))?;
+                Ok(self.describe_topic(&image, topic.configs()))
+            }
+            ConfigResource::Broker(name) => {
+                self.validate_broker_resource(name)?;
+                let levels = self.broker_levels(&image);
+                // the cluster's defaults only include the configs that are set as defaults
+                let (levels, only_source) = match name {
+                    "" => (&levels[1..], Some(ConfigSource::DynamicDefaultBrokerConfig)),
+                    _ => (&levels[..], None),
+                };
+                Ok(BROKER_CONFIGS.iter()
+                    .map(|key| describe_broker_config(key, levels))
+                    .filter(|config| only_source.map_or(true, |source| config.source == source))
+                    .collect())
+            }
+        }
+    }
+
+    /// Describe the configs of a topic with the given overrides, which doesn't have to exist yet
+    pub fn describe_topic_configs(&self, overrides: &BTreeMap<String, String>) -> Vec<DescribedConfig> {
+        self.describe_topic(&self.metadata.image(), overrides)
+    }
+
+    /// The configs in use by the topic, or None if the topic doesn't exist
+    pub fn topic_config(&self, name: &str) -> Option<TopicConfig> {
+        let image = self.metadata.image();
+        let overrides = image.topic(name)?.configs();
+        let values = self.describe_topic(&image, overrides)
+            .into_iter()
+            .zip(TOPIC_CONFIGS)
+            .filter_map(|(config, key)| Some((key.name(), config.value?)))
+            .collect();
+        Some(TopicConfig { values })
+    }
+
+    /// Give the logs of every topic the configs the topic is using, after topics or configs have changed
+    pub fn update_log_configs(&self) {
+        let topics: Vec<String> = self.metadata.image().topics().map(|topic| topic.name().to_string()).collect();
+        let configs: HashMap<_, _> = topics.into_iter()
+            .filter_map(|topic| {
+                let config = self.topic_config(&topic)?.log_config();
+                Some((topic, config))
+            })
+            .collect();
+        self.log_manager.update_configs(configs);
+    }
+
+    /// Replace every config set on the resource
+    pub fn alter_configs(&self, resource: ConfigResource, configs: BTreeMap<String, String>, validate_only: bool) -> Result<(), ConfigsError> {
+        self.update_configs(resource, validate_only, |current| {
+            *current = configs;
+            Ok(())
+        })
+    }
+
+    /// Change some of the configs set on the resource, leaving the rest as they are
+    pub fn incremental_alter_configs(&self, resource: ConfigResource, ops: &[(String, AlterConfigOp)], validate_only: bool) -> Result<(), ConfigsError> {
+        self.update_configs(resource, validate_only, |current| {
+            for (name, op) in ops {
+                let (value, remove) = match op {
+                    AlterConfigOp::Set(value) => {
+                        current.insert(name.clone(), value.clone());
+                        continue;
+                    }
+                    AlterConfigOp::Delete => {
+                        current.remove(name);
+                        continue;
+                    }
+                    AlterConfigOp::Append(value) => (value, false),
+                    AlterConfigOp::Subtract(value) => (value, true),
+                };
+                let key = config_key(resource, name)?;
+                if key.config_type() != ConfigType::List {
+                    let op = if remove { "SUBTRACT from" } else { "APPEND to" };
+                    return Err(ConfigsError::InvalidConfig(format!("Can't {op} key {name} because its type is not LIST.")));
+                }
+                let existing = current.get(name).map(String::as_str).or(key.default()).unwrap_or_default();
+                let mut items: Vec<&str> = list_items(existing).collect();
+                for item in list_items(value) {
+                    match remove {
+                        true => items.retain(|existing| *existing != item),
+                        false if !items.contains(&item) => items.push(item),
+                        false => {}
+                    }
+                }
+                current.insert(name.clone(), items.join(","));
+            }
+            Ok(())
+        })
+    }
+
+    /// Change the configs set on the resource, writing the changes to the metadata log once they're validated
+    fn update_configs(&self, resource: ConfigResource, validate_only: bool,
+                      change: impl FnOnce(&mut BTreeMap<String, String>) -> Result<(), ConfigsError>) -> Result<(), ConfigsError> {
+        self.metadata.update(|image| -> Result<_, ConfigsError> {
+            let (resource_type, resource_name, current) = match resource {
+                ConfigResource::Topic(name) => {
+                    let topic = image.topic(name).ok_or_else(|| ConfigsError::UnknownTopic(name.to_string()))?;
+                    (TOPIC_RESOURCE_TYPE, name, topic.configs())
+                }
+                ConfigResource::Broker(name) => {
+                    self.validate_broker_resource(name)?;
+                    (BROKER_RESOURCE_TYPE, name, image.broker_configs(name).unwrap_or(NO_CONFIGS))
+                }
+            };
+            let mut updated = current.clone();
+            change(&mut updated)?;
+
+            let set = updated.iter()
+                .filter(|(name, value)| current.get(*name) != Some(value))
+                .map(|(name, value)| (name.clone(), Some(value.clone())));
+            let removed = current.keys()
+                .filter(|name| !updated.contains_key(*name))
+                .map(|name| (name.clone(), None));
+            let changes: Vec<(String, Option<String>)> = set.chain(removed).collect();
+            for (name, value) in &changes {
+                if let Some(value) = value {
+                    validate_config(resource, name, value)?;
+                }
+            }
+            if validate_only {
+                return Ok((Vec::new(), ()));
+            }
+            let records = changes.into_iter()
+                .map(|(name, value)| MetadataRecord::Config(ConfigRecord {
+                    resource_type,
+                    resource_name: resource_name.to_string(),
+                    name,
+                    value,
+                }))
+                .collect();
+            Ok((records, ()))
+        })?;
+        if !validate_only {
+            self.update_log_configs();
+        }
+        Ok(())
+    }
+
+    fn validate_broker_resource(&self, name: &str) -> Result<(), ConfigsError> {
+        let node_id = self.config.node_id();
+        if !name.is_empty() && name != node_id.to_string() {
+            return Err(ConfigsError::InvalidRequest(format!(
+                "Unexpected broker id, expected {node_id} or empty string, but received {name}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// The broker configs set at each level, in order of precedence
+    fn broker_levels<'a>(&'a self, image: &'a MetadataImage) -> [(ConfigSource, &'a BTreeMap<String, String>); 3] {
+        [
+            (ConfigSource::DynamicBrokerConfig, image.broker_configs(&self.config.node_id().to_string()).unwrap_or(NO_CONFIGS)),
+            (ConfigSource::DynamicDefaultBrokerConfig, image.broker_configs("").unwrap_or(NO_CONFIGS)),
+            (ConfigSource::StaticBrokerConfig, self.config.properties()),
+        ]
+    }
+
+    fn describe_topic(&self, image: &MetadataImage, overrides: &BTreeMap<String, String>) -> Vec<DescribedConfig> {
+        let broker_levels = self.broker_levels(image);
+        TOPIC_CONFIGS.iter()
+            .map(|key| {
+                let mut values = Vec::new();
+                if let Some(value) = overrides.get(key.name()) {
+                    values.push((synonym(key.name(), value, ConfigSource::TopicConfig), value.clone()));
+                }
+                for (source, configs) in broker_levels {
+                    for broker_synonym in key.synonyms() {
+                        if let Some(value) = configs.get(broker_synonym.name()) {
+                            values.push((synonym(broker_synonym.name(), value, source), broker_synonym.to_topic_value(value)));
+                        }
+                    }
+                }
+                if let Some(default) = key.default() {
+                    values.push((synonym(key.name(), default, ConfigSource::DefaultConfig), default.to_string()));
+                }
+                describe_config(key, false, values)
+            })
+            .collect()
+    }
+}
+
+/// Describe a broker config from the levels it could be set at, in order of precedence
+fn describe_broker_config(key: &ConfigKey, levels: &[(ConfigSource, &BTreeMap<String, String>)]) -> DescribedConfig {
+    let values = levels.iter()
+        .filter_map(|(source, configs)| configs.get(key.name()).map(|value| (synonym(key.name(), value, *source), value.clone())))
+        .chain(key.default().map(|default| (synonym(key.name(), default, ConfigSource::DefaultConfig), default.to_string())))
+        .collect();
+    describe_config(key, !key.is_dynamic(), values)
+}
+
+/// Describe a config from every value it's been given along with that value converted for the config,
+/// in order of precedence so the first one is in use
+fn describe_config(key: &ConfigKey, read_only: bool, values: Vec<(ConfigSynonym, String)>) -> DescribedConfig {
+    let (value, source) = values.first()
+        .map_or((None, ConfigSource::DefaultConfig), |(synonym, value)| (Some(value.clone()), synonym.source));
+    let synonyms = values.into_iter()
+        .map(|(synonym, _)| ConfigSynonym {
+            value: synonym.value.filter(|_| !key.is_sensitive()),
+            ..synonym
+        })
+        .collect();
+    DescribedConfig {
+        name: key.name().to_string(),
+        value: value.filter(|_| !key.is_sensitive()),
+        source,
+        read_only,
+        is_sensitive: key.is_sensitive(),
+        synonyms,
+        config_type: key.config_type(),
+        documentation: key.documentation(),
+    }
+}
+
+fn synonym(name: &str, value: &str, source: ConfigSource) -> ConfigSynonym {
+    ConfigSynonym { name: name.to_string(), value: Some(value.to_string()), source }
+}
+
+/// The definition of a config that can be altered on the resource
+fn config_key(resource: ConfigResource, name: &str) -> Result<&'static ConfigKey, ConfigsError> {
+    match resource {
+        ConfigResource::Topic(_) => topic_config_key(name)
+            .ok_or_else(|| ConfigsError::InvalidConfig(format!("Unknown topic config name: {name}"))),
+        ConfigResource::Broker(_) => broker_config_key(name)
+            .filter(|key| key.is_dynamic())
+            .ok_or_else(|| ConfigsError::InvalidConfig(format!("Cannot update these configs dynamically: {name}"))),
+    }
+}
+
+/// Check the config can be set to the value on the resource
+pub fn validate_config(resource: ConfigResource, name: &str, value: &str) -> Result<(), ConfigsError> {
+    config_key(resource, name)?
+        .validate(value)
+        .map_err(|err| ConfigsError::InvalidConfig(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::record_batch::RecordBatchBuilder;
+    use crate::storage::topic_partition::TopicPartition;
+    use crate::testing::open_broker;
+
+    #[test]
+    fn test_topic_configs_are_applied_to_logs() {
+        let (broker, _log_dir) = open_broker("log.retention.bytes=0");
+        broker.auto_create_topic("events").unwrap();
+        let config = broker.topic_config("events").unwrap();
+        assert_eq!(config.cleanup_policy(), vec!["delete"]);
+        assert_eq!(config.log_config().retention_bytes, 0);
+
+        let ops = [("segment.bytes".to_string(), AlterConfigOp::Set("100".to_string()))];
+        broker.incremental_alter_configs(ConfigResource::Topic("events"), &ops, false).unwrap();
+        let log = broker.log_manager().get_log(&TopicPartition::new("events", 0)).unwrap().unwrap();
+        for _ in 0..3 {
+            log.append(RecordBatchBuilder::new().add_record(0, None, Some(vec![0u8; 100])).build(), 0).unwrap();
+        }
+        broker.log_manager().delete_old_segments(0).unwrap();
+        assert_eq!(log.log_start_offset(), 2);
+
+        // compacted topics aren't deleted by retention
+        let ops = [("cleanup.policy".to_string(), AlterConfigOp::Set("compact".to_string()))];
+        broker.incremental_alter_configs(ConfigResource::Topic("events"), &ops, false).unwrap();
+        assert!(!broker.topic_config("events").unwrap().log_config().delete_retained);
+        log.append(RecordBatchBuilder::new().add_record(0, None, Some(vec![0u8; 100])).build(), 0).unwrap();
+        broker.log_manager().delete_old_segments(0).unwrap();
+        assert_eq!(log.log_start_offset(), 2);
+    }
+}
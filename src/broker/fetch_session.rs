@@ -0,0 +1,273 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use uuid::Uuid;
+use tracing::info;
+use crate::storage::topic_partition::TopicPartition;
+
+/// The session id of a fetch that isn't part of a session
+pub const INVALID_SESSION_ID: i32 = 0;
+/// The epoch of a fetch creating a session, which lists every partition it reads
+pub const INITIAL_EPOCH: i32 = 0;
+/// The epoch of a fetch closing its session, or of a fetch that doesn't want a session at all
+pub const FINAL_EPOCH: i32 = -1;
+
+/// How long a session has to go unused before a new session can evict it, however many partitions each of them has
+const MIN_EVICTION_TIME: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Error)]
+pub enum FetchSessionError {
+    #[error("The fetch session ID was not found.")]
+    SessionIdNotFound(i32),
+    #[error("The fetch session epoch is invalid.")]
+    InvalidEpoch { session_id: i32, expected: i32, epoch: i32 },
+}
+
+/// A partition in a fetch session, along with what the client was last told about it
+#[derive(Debug, Clone)]
+pub struct SessionPartition {
+    topic_partition: TopicPartition,
+    /// Nil before v13, when topics are requested by name
+    topic_id: Uuid,
+    fetch_offset: i64,
+    max_bytes: i32,
+    high_watermark: i64,
+    last_stable_offset: i64,
+    log_start_offset: i64,
+}
+
+impl SessionPartition {
+    /// A partition the client hasn't been told anything about yet
+    pub fn new(topic_partition: TopicPartition, topic_id: Uuid, fetch_offset: i64, max_bytes: i32) -> Self {
+        SessionPartition {
+            topic_partition,
+            topic_id,
+            fetch_offset,
+            max_bytes,
+            high_watermark: -1,
+            last_stable_offset: -1,
+            log_start_offset: -1,
+        }
+    }
+
+    pub fn topic_partition(&self) -> &TopicPartition {
+        &self.topic_partition
+    }
+
+    pub fn topic_id(&self) -> Uuid {
+        self.topic_id
+    }
+
+    pub fn fetch_offset(&self) -> i64 {
+        self.fetch_offset
+    }
+
+    pub fn max_bytes(&self) -> i32 {
+        self.max_bytes
+    }
+}
+
+/// The partitions a client is fetching, so its fetches only have to list the partitions that have changed,
+/// and the responses only have to include the partitions with something new
+#[derive(Debug)]
+struct FetchSession {
+    /// The epoch the client's next fetch in the session has to have
+    epoch: i32,
+    partitions: BTreeMap<TopicPartition, SessionPartition>,
+    last_used: Instant,
+}
+
+/// The fetch sessions of every client, evicting the sessions that are least worth keeping once it's full
+#[derive(Debug)]
+pub struct FetchSessionCache {
+    max_slots: usize,
+    sessions: Mutex<HashMap<i32, FetchSession>>,
+}
+
+impl FetchSessionCache {
+    pub fn new(max_slots: usize) -> Self {
+        FetchSessionCache { max_slots, sessions: Mutex::new(HashMap::new()) }
+    }
+
+    /// Start a session reading the partitions, returning its id,
+    /// or INVALID_SESSION_ID if there's no room for it even after evicting another session
+    pub fn create_session(&self, partitions: Vec<SessionPartition>) -> i32 {
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        if sessions.len() >= self.max_slots && !Self::try_evict(&mut sessions, partitions.len(), now) {
+            return INVALID_SESSION_ID;
+        }
+
+        let session_id = loop {
+            let session_id = (Uuid::new_v4().as_u128() as i32) & i32::MAX;
+            if session_id != INVALID_SESSION_ID && !sessions.contains_key(&session_id) {
+                break session_id;
+            }
+        };
+        let partitions = partitions.into_iter()
+            .map(|partition| (partition.topic_partition.clone(), partition))
+            .collect();
+        sessions.insert(session_id, FetchSession { epoch: INITIAL_EPOCH + 1, partitions, last_used: now });
+        session_id
+    }
+
+    /// Make room for a new session with the given number of partitions, by evicting the least recently used session
+    /// if it's been unused for long enough, otherwise the smallest session if it's smaller than the new one
+    fn try_evict(sessions: &mut HashMap<i32, FetchSession>, size: usize, now: Instant) -> bool {
+        let stale = sessions.iter()
+            .min_by_key(|(_, session)| session.last_used)
+            .filter(|(_, session)| now.duration_since(session.last_used) >= MIN_EVICTION_TIME)
+            .map(|(session_id, _)| *session_id);
+        let smaller = || sessions.iter()
+            .min_by_key(|(_, session)| (session.partitions.len(), session.last_used))
+            .filter(|(_, session)| session.partitions.len() < size)
+            .map(|(session_id, _)| *session_id);
+        match stale.or_else(smaller) {
+            Some(session_id) => {
+                info!("Evicting fetch session {session_id} to make room for a new session with {size} partitions");
+                sessions.remove(&session_id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Apply an incremental fetch's changes to its session, adding or updating the partitions it lists
+    /// and then removing the ones it's forgotten, returning every partition in the session
+    pub fn update_session(
+        &self,
+        session_id: i32,
+        epoch: i32,
+        updated: Vec<SessionPartition>,
+        forgotten: &[TopicPartition],
+    ) -> Result<Vec<SessionPartition>, FetchSessionError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.get_mut(&session_id).ok_or(FetchSessionError::SessionIdNotFound(session_id))?;
+        if session.epoch != epoch {
+            return Err(FetchSessionError::InvalidEpoch { session_id, expected: session.epoch, epoch });
+        }
+
+        for partition in updated {
+            match session.partitions.get_mut(&partition.topic_partition) {
+                Some(existing) => {
+                    existing.fetch_offset = partition.fetch_offset;
+                    existing.max_bytes = partition.max_bytes;
+                }
+                None => {
+                    session.partitions.insert(partition.topic_partition.clone(), partition);
+                }
+            }
+        }
+        for topic_partition in forgotten {
+            session.partitions.remove(topic_partition);
+        }
+        session.epoch = next_epoch(session.epoch);
+        session.last_used = Instant::now();
+        Ok(session.partitions.values().cloned().collect())
+    }
+
+    /// Remove the session, returning whether there was one to remove
+    pub fn close_session(&self, session_id: i32) -> bool {
+        self.sessions.lock().unwrap().remove(&session_id).is_some()
+    }
+
+    /// Remember the offsets the session's partition was last sent with, returning whether an incremental response
+    /// has to include it, which it does if it has records or an error or if any of its offsets have changed
+    pub fn record_response(
+        &self,
+        session_id: i32,
+        topic_partition: &TopicPartition,
+        high_watermark: i64,
+        last_stable_offset: i64,
+        log_start_offset: i64,
+        has_records_or_error: bool,
+    ) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        let Some(partition) = sessions.get_mut(&session_id).and_then(|session| session.partitions.get_mut(topic_partition)) else {
+            return true;
+        };
+        let changed = partition.high_watermark != high_watermark
+            || partition.last_stable_offset != last_stable_offset
+            || partition.log_start_offset != log_start_offset;
+        partition.high_watermark = high_watermark;
+        partition.last_stable_offset = last_stable_offset;
+        partition.log_start_offset = log_start_offset;
+        changed || has_records_or_error
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The epoch after the given one, which wraps around to 1 since 0 and -1 are reserved
+fn next_epoch(epoch: i32) -> i32 {
+    match epoch {
+        i32::MAX => 1,
+        _ => epoch + 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn partitions(topic: &str, count: i32) -> Vec<SessionPartition> {
+        (0..count)
+            .map(|partition| SessionPartition::new(TopicPartition::new(topic, partition), Uuid::nil(), 0, 1024))
+            .collect()
+    }
+
+    #[test]
+    fn test_incremental_updates() {
+        let cache = FetchSessionCache::new(10);
+        let session_id = cache.create_session(partitions("a", 2));
+        assert_ne!(session_id, INVALID_SESSION_ID);
+
+        assert!(matches!(cache.update_session(session_id + 1, 1, Vec::new(), &[]), Err(FetchSessionError::SessionIdNotFound(_))));
+        assert!(matches!(cache.update_session(session_id, 2, Vec::new(), &[]), Err(FetchSessionError::InvalidEpoch { expected: 1, .. })));
+
+        let forgotten = [TopicPartition::new("a", 0)];
+        let updated = vec![SessionPartition::new(TopicPartition::new("a", 1), Uuid::nil(), 5, 1024)];
+        let session = cache.update_session(session_id, 1, [updated, partitions("b", 1)].concat(), &forgotten).unwrap();
+        let offsets: Vec<(String, i64)> = session.iter().map(|partition| (partition.topic_partition().to_string(), partition.fetch_offset())).collect();
+        assert_eq!(offsets, vec![("a-1".to_string(), 5), ("b-0".to_string(), 0)]);
+
+        let a1 = TopicPartition::new("a", 1);
+        assert!(cache.record_response(session_id, &a1, 10, 10, 0, false));
+        assert!(!cache.record_response(session_id, &a1, 10, 10, 0, false));
+        assert!(cache.record_response(session_id, &a1, 10, 10, 0, true));
+        assert!(cache.record_response(session_id, &a1, 11, 10, 0, false));
+
+        assert!(cache.close_session(session_id));
+        assert!(matches!(cache.update_session(session_id, 2, Vec::new(), &[]), Err(FetchSessionError::SessionIdNotFound(_))));
+    }
+
+    #[test]
+    fn test_evict_smaller_sessions() {
+        let cache = FetchSessionCache::new(2);
+        let small = cache.create_session(partitions("a", 1));
+        let large = cache.create_session(partitions("a", 3));
+        // no session is smaller than a new session with one partition, and none of them are stale yet
+        assert_eq!(cache.create_session(partitions("b", 1)), INVALID_SESSION_ID);
+
+        let larger = cache.create_session(partitions("b", 2));
+        assert_ne!(larger, INVALID_SESSION_ID);
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.close_session(small));
+        assert!(cache.close_session(large));
+        assert!(cache.close_session(larger));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_next_epoch_skips_reserved_epochs() {
+        assert_eq!(next_epoch(1), 2);
+        assert_eq!(next_epoch(i32::MAX), 1);
+    }
+}
@@ -0,0 +1,52 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use uuid::Uuid;
+use crate::broker::config::properties_entries;
+
+const META_PROPERTIES_FILE: &str = "meta.properties";
+
+/// The identity of the cluster and node that a log directory belongs to, stored in its `meta.properties` file
+#[derive(Debug, Clone)]
+pub struct MetaProperties {
+    cluster_id: String,
+}
+
+impl MetaProperties {
+    /// Load the properties of the log directory. If the directory hasn't been formatted yet,
+    /// it's formatted for a new cluster with a random id
+    pub fn load_or_create(log_dir: &Path, node_id: i32) -> io::Result<MetaProperties> {
+        let path = log_dir.join(META_PROPERTIES_FILE);
+        if !path.exists() {
+            let properties = MetaProperties { cluster_id: URL_SAFE_NO_PAD.encode(Uuid::new_v4().as_bytes()) };
+            fs::create_dir_all(log_dir)?;
+            fs::write(&path, format!("version=1\ncluster.id={}\nnode.id={node_id}\n", properties.cluster_id))?;
+            return Ok(properties);
+        }
+
+        let contents = fs::read_to_string(&path)?;
+        let mut cluster_id = None;
+        let mut stored_node_id = None;
+        for (key, value) in properties_entries(&contents) {
+            match key {
+                "cluster.id" => cluster_id = Some(value.to_string()),
+                "node.id" => stored_node_id = value.parse::<i32>().ok(),
+                _ => {}
+            }
+        }
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let cluster_id = cluster_id.ok_or_else(|| invalid(format!("No cluster.id in {}", path.display())))?;
+        if let Some(stored_node_id) = stored_node_id.filter(|stored_node_id| *stored_node_id != node_id) {
+            return Err(invalid(format!(
+                "Stored node id {stored_node_id} doesn't match the configured node id {node_id} in {}", path.display()
+            )));
+        }
+        Ok(MetaProperties { cluster_id })
+    }
+
+    pub fn cluster_id(&self) -> &str {
+        &self.cluster_id
+    }
+}
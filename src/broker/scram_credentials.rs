@@ -0,0 +1,193 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use thiserror::Error;
+use crate::broker::Broker;
+use crate::metadata::image::MetadataImage;
+use crate::metadata::records::{MetadataRecord, RemoveUserScramCredentialRecord, UserScramCredentialRecord};
+use crate::security::credentials::CredentialStore;
+use crate::security::scram::{ScramCredential, ScramMechanism};
+
+#[derive(Debug, Error)]
+pub enum ScramCredentialError {
+    #[error("Unknown SCRAM mechanism")]
+    UnsupportedMechanism,
+    #[error("{0}")]
+    UnacceptableCredential(String),
+    #[error("{0}")]
+    ResourceNotFound(String),
+    #[error("{0}")]
+    DuplicateResource(String),
+    #[error("Storage error: {0}")]
+    Storage(#[from] io::Error),
+}
+
+/// A change to one of a user's SCRAM credentials, with the mechanism as its type in the protocol
+#[derive(Debug, Clone)]
+pub enum ScramCredentialAlteration {
+    Delete { mechanism: i8 },
+    /// Clients salt the password themselves, so the broker never sees it
+    Upsert { mechanism: i8, iterations: i32, salt: Vec<u8>, salted_password: Vec<u8> },
+}
+
+impl Broker {
+    /// Every user with SCRAM credentials in the metadata or the JAAS configs, sorted by name
+    pub fn scram_users(&self) -> Vec<String> {
+        let image = self.metadata.image();
+        let users: BTreeSet<&str> = image.scram_users().map(|(user, _)| user.as_str())
+            .chain(self.credentials.static_scram_users())
+            .collect();
+        users.into_iter().map(str::to_string).collect()
+    }
+
+    /// The mechanisms a user has SCRAM credentials for, with their iteration counts.
+    /// A credential in the metadata takes the place of one for the same mechanism in the JAAS configs, like it does when authenticating
+    pub fn describe_scram_credentials(&self, user: &str) -> Result<Vec<(ScramMechanism, i32)>, ScramCredentialError> {
+        let mut credentials: BTreeMap<ScramMechanism, i32> = self.credentials.static_scram_credentials(user).into_iter()
+            .map(|(mechanism, credential)| (mechanism, credential.iterations()))
+            .collect();
+        if let Some(stored) = self.metadata.image().scram_credentials(user) {
+            credentials.extend(stored.iter().map(|(mechanism, credential)| (*mechanism, credential.iterations())));
+        }
+        match credentials.is_empty() {
+            true => Err(ScramCredentialError::ResourceNotFound("Attempt to describe a user credential that does not exist".to_string())),
+            false => Ok(credentials.into_iter().collect()),
+        }
+    }
+
+    /// Alter the SCRAM credentials of each user, returning whether each user's alterations succeeded.
+    /// A user's alterations are only made if all of them are valid, but the other users are altered either way
+    pub fn alter_scram_credentials(&self, alterations: &[(String, Vec<ScramCredentialAlteration>)]) -> Vec<Result<(), ScramCredentialError>> {
+        let result = self.metadata.update(|image| -> Result<_, io::Error> {
+            let mut records = Vec::new();
+            let results = alterations.iter()
+                .map(|(user, alterations)| {
+                    let user_records = alterations.iter()
+                        .map(|alteration| alteration_record(user, alteration, image, &self.credentials))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    records.extend(user_records);
+                    Ok(())
+                })
+                .collect();
+            Ok((records, results))
+        });
+        match result {
+            Ok(results) => {
+                self.credentials.load_scram_credentials(&self.metadata);
+                results
+            }
+            Err(err) => alterations.iter()
+                .map(|_| Err(ScramCredentialError::Storage(io::Error::new(err.kind(), err.to_string()))))
+                .collect(),
+        }
+    }
+}
+
+fn alteration_record(user: &str, alteration: &ScramCredentialAlteration, image: &MetadataImage, credentials: &CredentialStore) -> Result<MetadataRecord, ScramCredentialError> {
+    if user.is_empty() {
+        return Err(ScramCredentialError::UnacceptableCredential("Username must not be empty".to_string()));
+    }
+    match alteration {
+        ScramCredentialAlteration::Delete { mechanism } => {
+            let scram_mechanism = ScramMechanism::from_type(*mechanism).ok_or(ScramCredentialError::UnsupportedMechanism)?;
+            if !image.scram_credentials(user).is_some_and(|credentials| credentials.contains_key(&scram_mechanism)) {
+                // the JAAS configs are only read when the broker starts, so there's nothing to delete them from
+                if credentials.static_scram_credentials(user).contains_key(&scram_mechanism) {
+                    return Err(ScramCredentialError::UnacceptableCredential(
+                        format!("The {} credential of user {user} is defined in the broker's JAAS config and can't be deleted", scram_mechanism.name())
+                    ));
+                }
+                return Err(ScramCredentialError::ResourceNotFound("Attempt to delete a user credential that does not exist".to_string()));
+            }
+            Ok(MetadataRecord::RemoveUserScramCredential(RemoveUserScramCredentialRecord { name: user.to_string(), mechanism: *mechanism }))
+        }
+        ScramCredentialAlteration::Upsert { mechanism, iterations, salt, salted_password } => {
+            let scram_mechanism = ScramMechanism::from_type(*mechanism).ok_or(ScramCredentialError::UnsupportedMechanism)?;
+            if *iterations < scram_mechanism.min_iterations() {
+                return Err(ScramCredentialError::UnacceptableCredential("Too few iterations".to_string()));
+            }
+            if *iterations > scram_mechanism.max_iterations() {
+                return Err(ScramCredentialError::UnacceptableCredential("Too many iterations".to_string()));
+            }
+            if salt.is_empty() || salted_password.is_empty() {
+                return Err(ScramCredentialError::UnacceptableCredential("Salt and salted password must not be empty".to_string()));
+            }
+            let credential = ScramCredential::from_salted_password(scram_mechanism, salted_password, salt.clone(), *iterations);
+            Ok(MetadataRecord::UserScramCredential(UserScramCredentialRecord {
+                name: user.to_string(),
+                mechanism: *mechanism,
+                salt: credential.salt().to_vec(),
+                stored_key: credential.stored_key().to_vec(),
+                server_key: credential.server_key().to_vec(),
+                iterations: *iterations,
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::scram::salted_password;
+    use crate::testing::open_broker;
+
+    #[test]
+    fn test_alteration_records() {
+        let mechanism = ScramMechanism::ScramSha256;
+        let salt = b"salt".to_vec();
+        let salted_password = salted_password(mechanism, b"secret", &salt, 4096);
+        let upsert = |iterations| ScramCredentialAlteration::Upsert {
+            mechanism: mechanism.mechanism_type(),
+            iterations,
+            salt: salt.clone(),
+            salted_password: salted_password.clone(),
+        };
+
+        let credentials = CredentialStore::default();
+        let mut image = MetadataImage::default();
+        assert!(matches!(alteration_record("alice", &upsert(4095), &image, &credentials), Err(ScramCredentialError::UnacceptableCredential(_))));
+        assert!(matches!(alteration_record("alice", &upsert(16385), &image, &credentials), Err(ScramCredentialError::UnacceptableCredential(_))));
+        assert!(matches!(alteration_record("", &upsert(4096), &image, &credentials), Err(ScramCredentialError::UnacceptableCredential(_))));
+        let unknown_mechanism = ScramCredentialAlteration::Delete { mechanism: 0 };
+        assert!(matches!(alteration_record("alice", &unknown_mechanism, &image, &credentials), Err(ScramCredentialError::UnsupportedMechanism)));
+        let delete = ScramCredentialAlteration::Delete { mechanism: mechanism.mechanism_type() };
+        assert!(matches!(alteration_record("alice", &delete, &image, &credentials), Err(ScramCredentialError::ResourceNotFound(_))));
+
+        image.apply(&alteration_record("alice", &upsert(4096), &image, &credentials).unwrap());
+        let expected = ScramCredential::from_salted_password(mechanism, &salted_password, salt.clone(), 4096);
+        assert_eq!(image.scram_credentials("alice").and_then(|credentials| credentials.get(&mechanism)), Some(&expected));
+
+        image.apply(&alteration_record("alice", &delete, &image, &credentials).unwrap());
+        assert!(image.scram_credentials("alice").is_none());
+    }
+
+    #[test]
+    fn test_static_credentials() {
+        let (broker, _log_dir) = open_broker(concat!(
+            "listeners=SASL_PLAINTEXT://:9094\nsasl.enabled.mechanisms=SCRAM-SHA-256\n",
+            "listener.name.sasl_plaintext.scram-sha-256.sasl.jaas.config=org.apache.kafka.common.security.scram.ScramLoginModule required ",
+            "username=\"alice\" password=\"alice-secret\" user_alice=\"alice-secret\";\n",
+        ));
+        let mechanism = ScramMechanism::ScramSha256;
+        assert_eq!(broker.scram_users(), vec!["alice".to_string()]);
+        assert_eq!(broker.describe_scram_credentials("alice").unwrap(), vec![(mechanism, 4096)]);
+
+        let delete = vec![("alice".to_string(), vec![ScramCredentialAlteration::Delete { mechanism: mechanism.mechanism_type() }])];
+        let [result] = broker.alter_scram_credentials(&delete).try_into().unwrap();
+        assert!(matches!(result, Err(ScramCredentialError::UnacceptableCredential(_))));
+        assert!(broker.credentials().scram_credential("alice", mechanism).is_some());
+
+        // a stored credential overrides the static one, and deleting it goes back to the static one
+        let salt = b"salt".to_vec();
+        let upsert = vec![("alice".to_string(), vec![ScramCredentialAlteration::Upsert {
+            mechanism: mechanism.mechanism_type(),
+            iterations: 8192,
+            salt: salt.clone(),
+            salted_password: salted_password(mechanism, b"new-secret", &salt, 8192),
+        }])];
+        assert!(broker.alter_scram_credentials(&upsert)[0].is_ok());
+        assert_eq!(broker.scram_users(), vec!["alice".to_string()]);
+        assert_eq!(broker.describe_scram_credentials("alice").unwrap(), vec![(mechanism, 8192)]);
+        assert!(broker.alter_scram_credentials(&delete)[0].is_ok());
+        assert_eq!(broker.describe_scram_credentials("alice").unwrap(), vec![(mechanism, 4096)]);
+    }
+}
@@ -0,0 +1,527 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+use tracing::error;
+use crate::broker::Broker;
+use crate::broker::configs::{validate_config, ConfigResource};
+use crate::coordinator::group::offsets::OFFSETS_TOPIC;
+use crate::coordinator::transaction::transaction_log::TRANSACTION_STATE_TOPIC;
+use crate::metadata::image::{MetadataImage, TopicMetadata};
+use crate::metadata::records::{ConfigRecord, MetadataRecord, PartitionRecord, RemoveTopicRecord, TopicRecord, TOPIC_RESOURCE_TYPE};
+use crate::metadata::store::METADATA_TOPIC;
+use crate::storage::log::Log;
+use crate::storage::topic_partition::TopicPartition;
+
+const MAX_TOPIC_NAME_LENGTH: usize = 249;
+
+#[derive(Debug, Error)]
+pub enum TopicError {
+    #[error("{0}")]
+    InvalidTopic(String),
+    #[error("Topic '{0}' already exists.")]
+    TopicAlreadyExists(String),
+    #[error("This server does not host this topic-partition.")]
+    UnknownTopic(String),
+    #[error("This server does not host this topic ID.")]
+    UnknownTopicId(Uuid),
+    #[error("{0}")]
+    InvalidPartitions(String),
+    #[error("{0}")]
+    InvalidReplicationFactor(String),
+    #[error("{0}")]
+    InvalidReplicaAssignment(String),
+    #[error("{0}")]
+    InvalidConfig(String),
+    #[error("{0}")]
+    InvalidRequest(String),
+    #[error("Storage error: {0}")]
+    Storage(#[from] io::Error),
+}
+
+/// A topic to create, where -1 for the partition count or replication factor means use the broker's default
+#[derive(Debug, Clone)]
+pub struct NewTopic {
+    pub name: String,
+    pub num_partitions: i32,
+    pub replication_factor: i16,
+    /// Manually chosen replicas for each partition, by partition index
+    pub assignments: BTreeMap<i32, Vec<i32>>,
+    pub configs: BTreeMap<String, Option<String>>,
+}
+
+/// The details of a topic that was created
+#[derive(Debug, Clone)]
+pub struct CreatedTopic {
+    pub topic_id: Uuid,
+    pub num_partitions: i32,
+    pub replication_factor: i16,
+    pub configs: BTreeMap<String, String>,
+}
+
+/// A topic, identified by either its name or its id
+#[derive(Debug, Copy, Clone)]
+pub enum TopicRef<'a> {
+    Name(&'a str),
+    Id(Uuid),
+}
+
+/// The name and id of a topic that was deleted
+#[derive(Debug, Clone)]
+pub struct DeletedTopic {
+    pub name: String,
+    pub topic_id: Uuid,
+}
+
+impl Broker {
+    /// Create a topic, along with the logs of its partitions.
+    /// If the request is only to validate the topic then nothing is created
+    pub fn create_topic(&self, topic: &NewTopic, validate_only: bool) -> Result<CreatedTopic, TopicError> {
+        let created = self.metadata.update(|image| -> Result<_, TopicError> {
+            validate_new_topic_name(image, &topic.name)?;
+            let assignments = self.assign_replicas(topic)?;
+            let configs = topic.configs.iter()
+                .map(|(name, value)| match value {
+                    Some(value) => {
+                        validate_config(ConfigResource::Topic(&topic.name), name, value)
+                            .map_err(|err| TopicError::InvalidConfig(err.to_string()))?;
+                        Ok((name.clone(), value.clone()))
+                    }
+                    None => Err(TopicError::InvalidConfig(format!("Null value not supported for topic configs: {name}"))),
+                })
+                .collect::<Result<BTreeMap<_, _>, _>>()?;
+
+            let created = CreatedTopic {
+                topic_id: Uuid::new_v4(),
+                num_partitions: assignments.len() as i32,
+                replication_factor: assignments.first().map_or(0, Vec::len) as i16,
+                configs,
+            };
+            if validate_only {
+                return Ok((Vec::new(), created));
+            }
+            Ok((new_topic_records(&topic.name, &created, assignments), created))
+        })?;
+
+        if !validate_only {
+            self.update_log_configs();
+            for partition in 0..created.num_partitions {
+                self.log_manager.create_log(&TopicPartition::new(topic.name.clone(), partition), created.topic_id)?;
+            }
+        }
+        Ok(created)
+    }
+
+    /// Create a topic that a client asked for but doesn't exist, with the default partition count and replication factor
+    pub fn auto_create_topic(&self, name: &str) -> Result<CreatedTopic, TopicError> {
+        let topic = NewTopic {
+            name: name.to_string(),
+            num_partitions: -1,
+            replication_factor: -1,
+            assignments: BTreeMap::new(),
+            configs: BTreeMap::new(),
+        };
+        self.create_topic(&topic, false)
+    }
+
+    /// Get the logs of every partition of a topic the broker uses internally, creating the topic the first time
+    pub fn internal_topic_logs(&self, name: &str, num_partitions: i32, configs: BTreeMap<String, Option<String>>) -> Result<Vec<Arc<Log>>, TopicError> {
+        let existing = self.metadata.image()
+            .topic(name)
+            .map(|topic| (topic.topic_id(), topic.num_partitions() as i32));
+        let (topic_id, num_partitions) = match existing {
+            Some(existing) => existing,
+            None => {
+                let topic = NewTopic {
+                    name: name.to_string(),
+                    num_partitions,
+                    replication_factor: -1,
+                    assignments: BTreeMap::new(),
+                    configs,
+                };
+                let created = self.create_topic(&topic, false)?;
+                (created.topic_id, created.num_partitions)
+            }
+        };
+        (0..num_partitions)
+            .map(|partition| {
+                let topic_partition = TopicPartition::new(name, partition);
+                match self.log_manager.get_log(&topic_partition)? {
+                    Some(log) => Ok(log),
+                    None => Ok(self.log_manager.create_log(&topic_partition, topic_id)?),
+                }
+            })
+            .collect()
+    }
+
+    /// Delete a topic, along with its partitions and configs.
+    /// The logs of its partitions are removed in the background
+    pub fn delete_topic(&self, topic: TopicRef) -> Result<DeletedTopic, TopicError> {
+        let (deleted, num_partitions) = self.metadata.update(|image| -> Result<_, TopicError> {
+            let metadata = find_topic(image, topic)?;
+            // the coordinators keep using the logs of the topics they store their state in
+            if is_internal_topic(metadata.name()) {
+                return Err(TopicError::InvalidTopic(format!("Deleting the internal topic {} isn't allowed", metadata.name())));
+            }
+            let deleted = DeletedTopic { name: metadata.name().to_string(), topic_id: metadata.topic_id() };
+            let record = MetadataRecord::RemoveTopic(RemoveTopicRecord { topic_id: deleted.topic_id });
+            Ok((vec![record], (deleted, metadata.num_partitions() as i32)))
+        })?;
+
+        // the topic is already gone from the metadata, so failing to delete its logs doesn't fail the deletion
+        for partition in 0..num_partitions {
+            let topic_partition = TopicPartition::new(deleted.name.clone(), partition);
+            if let Err(err) = self.log_manager.delete_log(&topic_partition) {
+                error!(%topic_partition, %err, "Failed to delete the log");
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Increase the number of partitions of a topic to the given count, optionally choosing the replicas
+    /// of each new partition. If the request is only to validate the change then nothing is created
+    pub fn create_partitions(&self, name: &str, count: i32, assignments: Option<&[Vec<i32>]>, validate_only: bool) -> Result<(), TopicError> {
+        let (topic_id, new_partitions) = self.metadata.update(|image| -> Result<_, TopicError> {
+            let topic = find_topic(image, TopicRef::Name(name))?;
+            let new_partitions = self.assign_new_partitions(topic, count, assignments)?;
+            let records = match validate_only {
+                true => Vec::new(),
+                false => new_partitions.iter()
+                    .map(|(partition, replicas)| partition_record(topic.topic_id(), *partition, replicas.clone()))
+                    .collect(),
+            };
+            Ok((records, (topic.topic_id(), new_partitions)))
+        })?;
+
+        if !validate_only {
+            for (partition, _) in new_partitions {
+                self.log_manager.create_log(&TopicPartition::new(name, partition), topic_id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Choose the replicas of each partition being added to the topic, along with the index of the partition
+    fn assign_new_partitions(&self, topic: &TopicMetadata, count: i32, assignments: Option<&[Vec<i32>]>) -> Result<Vec<(i32, Vec<i32>)>, TopicError> {
+        let current = topic.num_partitions() as i32;
+        if count < current {
+            return Err(TopicError::InvalidPartitions(format!(
+                "Topic currently has {current} partitions, which is higher than the requested {count}."
+            )));
+        }
+        if count == current {
+            return Err(TopicError::InvalidPartitions(format!("Topic already has {current} partitions.")));
+        }
+
+        let node_id = self.config.node_id();
+        let replication_factor = topic.replication_factor();
+        let Some(assignments) = assignments else {
+            return Ok((current..count).map(|partition| (partition, vec![node_id; replication_factor])).collect());
+        };
+        let additional = count - current;
+        if assignments.len() != additional as usize {
+            return Err(TopicError::InvalidReplicaAssignment(format!(
+                "Attempted to add {additional} additional partition(s), but only {} assignment(s) were specified.", assignments.len()
+            )));
+        }
+        (current..count)
+            .zip(assignments)
+            .map(|(partition, replicas)| {
+                validate_replicas(partition, replicas, node_id)?;
+                if replicas.len() != replication_factor {
+                    return Err(TopicError::InvalidReplicaAssignment(format!(
+                        "The manual partition assignment includes a partition with {} replica(s), but this is not consistent with previous partitions, which have {replication_factor} replica(s).",
+                        replicas.len()
+                    )));
+                }
+                Ok((partition, replicas.clone()))
+            })
+            .collect()
+    }
+
+    /// Choose the replicas of each partition of the new topic, indexed by partition
+    fn assign_replicas(&self, topic: &NewTopic) -> Result<Vec<Vec<i32>>, TopicError> {
+        let node_id = self.config.node_id();
+        if !topic.assignments.is_empty() {
+            if topic.num_partitions != -1 || topic.replication_factor != -1 {
+                return Err(TopicError::InvalidRequest(
+                    "Both numPartitions or replicationFactor and replicasAssignments were set. Both cannot be used at the same time.".to_string()
+                ));
+            }
+            return validate_assignments(&topic.assignments, node_id);
+        }
+
+        let num_partitions = match topic.num_partitions {
+            -1 => self.config.num_partitions(),
+            num_partitions => num_partitions,
+        };
+        if num_partitions <= 0 {
+            return Err(TopicError::InvalidPartitions("Number of partitions was set to an invalid non-positive value.".to_string()));
+        }
+        let replication_factor = match topic.replication_factor {
+            -1 => self.config.default_replication_factor(),
+            replication_factor => replication_factor,
+        };
+        if replication_factor <= 0 {
+            return Err(TopicError::InvalidReplicationFactor(
+                "Replication factor must be larger than 0, or -1 to use the default value.".to_string()
+            ));
+        }
+        // this broker is the only one in the cluster
+        if replication_factor > 1 {
+            return Err(TopicError::InvalidReplicationFactor(format!(
+                "Unable to replicate the partition {replication_factor} time(s): The target replication factor of {replication_factor} cannot be reached because only 1 broker(s) are registered."
+            )));
+        }
+        Ok(vec![vec![node_id]; num_partitions as usize])
+    }
+}
+
+/// Check manual replica assignments cover consecutive partitions, and only use brokers that exist
+fn validate_assignments(assignments: &BTreeMap<i32, Vec<i32>>, node_id: i32) -> Result<Vec<Vec<i32>>, TopicError> {
+    let consecutive = assignments.keys()
+        .enumerate()
+        .all(|(idx, partition)| idx as i32 == *partition);
+    if !consecutive {
+        return Err(TopicError::InvalidReplicaAssignment(
+            "Partitions should be a consecutive 0-based integer sequence".to_string()
+        ));
+    }
+    for (partition, replicas) in assignments {
+        validate_replicas(*partition, replicas, node_id)?;
+    }
+    Ok(assignments.values().cloned().collect())
+}
+
+/// Check a partition has replicas, and they're all on brokers that exist
+fn validate_replicas(partition: i32, replicas: &[i32], node_id: i32) -> Result<(), TopicError> {
+    if replicas.is_empty() {
+        return Err(TopicError::InvalidReplicaAssignment(format!("Partition {partition} has no replicas assigned")));
+    }
+    if let Some(broker) = replicas.iter().find(|broker| **broker != node_id) {
+        return Err(TopicError::InvalidReplicaAssignment(format!("Partition {partition} is assigned to broker {broker}, which doesn't exist")));
+    }
+    if replicas.len() > 1 {
+        return Err(TopicError::InvalidReplicaAssignment(format!("Partition {partition} has duplicate replicas assigned")));
+    }
+    Ok(())
+}
+
+fn find_topic<'a>(image: &'a MetadataImage, topic: TopicRef) -> Result<&'a TopicMetadata, TopicError> {
+    match topic {
+        TopicRef::Name(name) => image.topic(name).ok_or_else(|| TopicError::UnknownTopic(name.to_string())),
+        TopicRef::Id(topic_id) => image.topic_by_id(&topic_id).ok_or(TopicError::UnknownTopicId(topic_id)),
+    }
+}
+
+/// Whether the topic is one the broker stores its own state in
+pub fn is_internal_topic(name: &str) -> bool {
+    name == OFFSETS_TOPIC || name == TRANSACTION_STATE_TOPIC
+}
+
+/// Check the name is legal, and doesn't clash with an existing topic
+fn validate_new_topic_name(image: &MetadataImage, name: &str) -> Result<(), TopicError> {
+    validate_topic_name(name)?;
+    if name == METADATA_TOPIC {
+        return Err(TopicError::InvalidTopic(format!("Creation of internal topic {name} is prohibited.")));
+    }
+    if image.topic(name).is_some() {
+        return Err(TopicError::TopicAlreadyExists(name.to_string()));
+    }
+    // '.' and '_' are interchangeable in metric names, so topics can't differ only by them
+    let unified_name = unify_collision_chars(name);
+    let colliding = image.topics()
+        .find(|topic| unify_collision_chars(topic.name()) == unified_name);
+    match colliding {
+        Some(colliding) => Err(TopicError::InvalidTopic(format!("Topic '{name}' collides with existing topic: {}", colliding.name()))),
+        None => Ok(()),
+    }
+}
+
+/// Check the topic name only contains legal characters, and isn't too long
+pub fn validate_topic_name(name: &str) -> Result<(), TopicError> {
+    if name.is_empty() {
+        return Err(TopicError::InvalidTopic("Topic name is illegal, it can't be empty".to_string()));
+    }
+    if name == "." || name == ".." {
+        return Err(TopicError::InvalidTopic("Topic name cannot be \".\" or \"..\"".to_string()));
+    }
+    if name.len() > MAX_TOPIC_NAME_LENGTH {
+        return Err(TopicError::InvalidTopic(format!(
+            "Topic name is illegal, it can't be longer than {MAX_TOPIC_NAME_LENGTH} characters, topic name: {name}"
+        )));
+    }
+    let is_legal = |char: char| char.is_ascii_alphanumeric() || char == '.' || char == '_' || char == '-';
+    if !name.chars().all(is_legal) {
+        return Err(TopicError::InvalidTopic(format!(
+            "Topic name \"{name}\" is illegal, it contains a character other than ASCII alphanumerics, '.', '_' and '-'"
+        )));
+    }
+    Ok(())
+}
+
+fn unify_collision_chars(name: &str) -> String {
+    name.replace('.', "_")
+}
+
+/// The metadata records that create the topic, its partitions and its configs
+fn new_topic_records(name: &str, topic: &CreatedTopic, assignments: Vec<Vec<i32>>) -> Vec<MetadataRecord> {
+    let topic_record = MetadataRecord::Topic(TopicRecord { name: name.to_string(), topic_id: topic.topic_id });
+    let partition_records = assignments.into_iter()
+        .enumerate()
+        .map(|(partition, replicas)| partition_record(topic.topic_id, partition as i32, replicas));
+    let config_records = topic.configs.iter()
+        .map(|(config_name, value)| MetadataRecord::Config(ConfigRecord {
+            resource_type: TOPIC_RESOURCE_TYPE,
+            resource_name: name.to_string(),
+            name: config_name.clone(),
+            value: Some(value.clone()),
+        }));
+    std::iter::once(topic_record)
+        .chain(partition_records)
+        .chain(config_records)
+        .collect()
+}
+
+/// The record of a new partition, with its first replica as the leader
+fn partition_record(topic_id: Uuid, partition: i32, replicas: Vec<i32>) -> MetadataRecord {
+    MetadataRecord::Partition(PartitionRecord {
+        partition_id: partition,
+        topic_id,
+        leader: replicas[0],
+        isr: replicas.clone(),
+        replicas,
+        removing_replicas: Vec::new(),
+        adding_replicas: Vec::new(),
+        leader_epoch: 0,
+        partition_epoch: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use base64::Engine;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use super::*;
+    use crate::storage::record_batch::RecordBatchBuilder;
+    use crate::testing::open_broker;
+
+    #[test]
+    fn test_validate_topic_name() {
+        assert!(validate_topic_name("foo").is_ok());
+        assert!(validate_topic_name("foo.bar_baz-1").is_ok());
+        assert!(validate_topic_name(&"a".repeat(MAX_TOPIC_NAME_LENGTH)).is_ok());
+
+        assert!(validate_topic_name("").is_err());
+        assert!(validate_topic_name(".").is_err());
+        assert!(validate_topic_name("..").is_err());
+        assert!(validate_topic_name(&"a".repeat(MAX_TOPIC_NAME_LENGTH + 1)).is_err());
+        assert!(validate_topic_name("foo bar").is_err());
+        assert!(validate_topic_name("foo/bar").is_err());
+        assert!(validate_topic_name("föo").is_err());
+    }
+
+    #[test]
+    fn test_validate_assignments() {
+        let assignments = BTreeMap::from([(0, vec![1]), (1, vec![1])]);
+        assert_eq!(validate_assignments(&assignments, 1).unwrap(), vec![vec![1], vec![1]]);
+
+        let gap = BTreeMap::from([(0, vec![1]), (2, vec![1])]);
+        assert!(validate_assignments(&gap, 1).is_err());
+        let unknown_broker = BTreeMap::from([(0, vec![2])]);
+        assert!(validate_assignments(&unknown_broker, 1).is_err());
+        let no_replicas = BTreeMap::from([(0, vec![])]);
+        assert!(validate_assignments(&no_replicas, 1).is_err());
+    }
+
+    #[test]
+    fn test_delete_and_recreate_topic() {
+        let (broker, _log_dir) = open_broker("");
+        let topic_partition = TopicPartition::new("events", 0);
+        let created = broker.auto_create_topic("events").unwrap();
+        let log = broker.log_manager().get_log(&topic_partition).unwrap().unwrap();
+        log.append(RecordBatchBuilder::new().add_record(0, None, Some(b"value".to_vec())).build(), 0).unwrap();
+        let log_dir = log.dir().to_path_buf();
+        drop(log);
+
+        let deleted = broker.delete_topic(TopicRef::Name("events")).unwrap();
+        assert_eq!(deleted.topic_id, created.topic_id);
+        assert!(broker.metadata().image().topic("events").is_none());
+        // the directory is renamed straight away, then removed in the background, which may have already happened
+        assert!(!log_dir.exists());
+        for entry in fs::read_dir(broker.config().log_dir()).unwrap() {
+            let name = entry.unwrap().file_name().into_string().unwrap();
+            assert!(!name.starts_with("events-0") || (name.starts_with("events-0.") && name.ends_with("-delete")), "{name}");
+        }
+
+        let recreated = broker.auto_create_topic("events").unwrap();
+        assert_ne!(recreated.topic_id, created.topic_id);
+        let log = broker.log_manager().get_log(&topic_partition).unwrap().unwrap();
+        assert_eq!(log.log_end_offset(), 0);
+        let partition_metadata = fs::read_to_string(log_dir.join("partition.metadata")).unwrap();
+        assert!(partition_metadata.contains(&URL_SAFE_NO_PAD.encode(recreated.topic_id.as_bytes())));
+    }
+
+    #[test]
+    fn test_create_partitions() {
+        let (broker, _log_dir) = open_broker("");
+        let created = broker.auto_create_topic("events").unwrap();
+        let num_partitions = || broker.metadata().image().topic("events").unwrap().num_partitions();
+        assert_eq!(num_partitions(), 1);
+
+        // the count is the total number of partitions, which has to grow
+        assert!(matches!(broker.create_partitions("events", 1, None, false), Err(TopicError::InvalidPartitions(_))));
+        assert!(matches!(broker.create_partitions("events", 0, None, false), Err(TopicError::InvalidPartitions(_))));
+        assert!(matches!(broker.create_partitions("unknown", 2, None, false), Err(TopicError::UnknownTopic(_))));
+
+        broker.create_partitions("events", 3, None, true).unwrap();
+        assert_eq!(num_partitions(), 1);
+        assert!(broker.log_manager().get_log(&TopicPartition::new("events", 1)).unwrap().is_none());
+
+        broker.create_partitions("events", 3, None, false).unwrap();
+        assert_eq!(num_partitions(), 3);
+        for partition in 1..3 {
+            let log = broker.log_manager().get_log(&TopicPartition::new("events", partition)).unwrap().unwrap();
+            assert_eq!(log.log_end_offset(), 0);
+            let partition_metadata = fs::read_to_string(log.dir().join("partition.metadata")).unwrap();
+            assert!(partition_metadata.contains(&URL_SAFE_NO_PAD.encode(created.topic_id.as_bytes())));
+        }
+    }
+
+    #[test]
+    fn test_create_partitions_with_assignments() {
+        let (broker, _log_dir) = open_broker("");
+        broker.auto_create_topic("events").unwrap();
+        let node_id = broker.config().node_id();
+
+        // there has to be an assignment for each new partition, using only brokers that exist
+        let too_few = [vec![node_id]];
+        assert!(matches!(broker.create_partitions("events", 3, Some(&too_few), false), Err(TopicError::InvalidReplicaAssignment(_))));
+        let unknown_broker = [vec![node_id], vec![node_id + 1]];
+        assert!(matches!(broker.create_partitions("events", 3, Some(&unknown_broker), false), Err(TopicError::InvalidReplicaAssignment(_))));
+        let too_many_replicas = [vec![node_id, node_id], vec![node_id, node_id]];
+        assert!(matches!(broker.create_partitions("events", 3, Some(&too_many_replicas), false), Err(TopicError::InvalidReplicaAssignment(_))));
+        assert_eq!(broker.metadata().image().topic("events").unwrap().num_partitions(), 1);
+
+        broker.create_partitions("events", 3, Some(&[vec![node_id], vec![node_id]]), false).unwrap();
+        let image = broker.metadata().image();
+        let topic = image.topic("events").unwrap();
+        assert_eq!(topic.num_partitions(), 3);
+        assert_eq!(topic.partition(2).unwrap().replicas(), &[node_id]);
+        assert!(broker.log_manager().get_log(&TopicPartition::new("events", 2)).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_internal_topics_cant_be_deleted() {
+        let (broker, _log_dir) = open_broker("");
+        for name in [OFFSETS_TOPIC, TRANSACTION_STATE_TOPIC] {
+            let err = broker.delete_topic(TopicRef::Name(name)).unwrap_err();
+            assert!(matches!(err, TopicError::InvalidTopic(_)), "{err:?}");
+            let topic_id = broker.metadata().image().topic(name).unwrap().topic_id();
+            assert!(matches!(broker.delete_topic(TopicRef::Id(topic_id)), Err(TopicError::InvalidTopic(_))));
+            assert!(broker.config().log_dir().join(format!("{name}-0")).is_dir());
+        }
+    }
+}
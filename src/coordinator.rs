@@ -0,0 +1,3 @@
+pub mod group;
+pub mod producer_id;
+pub mod transaction;
@@ -0,0 +1,1754 @@
+pub mod assignor;
+pub mod consumer_group;
+pub mod consumer_protocol;
+pub mod group_metadata;
+pub mod member;
+pub mod offsets;
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+use tracing::{error, info};
+use crate::broker::config::BrokerConfig;
+use crate::coordinator::group::assignor::Assignor;
+use crate::coordinator::group::consumer_group::{
+    is_subset, Assignment, ConsumerGroup, ConsumerGroupMember, ConsumerGroupState, MemberState, TopicInfo,
+    LEAVE_GROUP_STATIC_MEMBER_EPOCH,
+};
+use crate::coordinator::group::consumer_protocol::CONSUMER_PROTOCOL_TYPE;
+use crate::coordinator::group::group_metadata::{GroupMetadata, GroupState};
+use crate::coordinator::group::member::{JoinProtocol, MemberMetadata};
+use crate::coordinator::group::offsets::{partition_for, GroupMetadataValue, OffsetAndMetadata, OffsetsKey, OFFSETS_TOPIC};
+use crate::storage::log::Log;
+use crate::storage::record_batch::{ControlRecordType, RecordBatchBuilder};
+use crate::storage::topic_partition::TopicPartition;
+use crate::time::now_ms;
+use crate::time::purgatory::Purgatory;
+use crate::time::timer::Timer;
+
+#[derive(Debug, Error)]
+pub enum GroupError {
+    #[error("The configured groupId is invalid.")]
+    InvalidGroupId,
+    #[error("The coordinator is not available.")]
+    CoordinatorNotAvailable,
+    #[error("The coordinator is not aware of this member.")]
+    UnknownMemberId,
+    #[error("Specified group generation id is not valid.")]
+    IllegalGeneration,
+    #[error("The group member's supported protocols are incompatible with those of existing members \
+             or first group member tried to join with empty protocol type or empty protocol list.")]
+    InconsistentGroupProtocol,
+    #[error("The session timeout is not within the range allowed by the broker \
+             (as configured by group.min.session.timeout.ms and group.max.session.timeout.ms).")]
+    InvalidSessionTimeout,
+    #[error("The group is rebalancing, so a rejoin is needed.")]
+    RebalanceInProgress,
+    /// New members have to rejoin with the member id they're given
+    #[error("The group member needs to have a valid member id before actually entering a consumer group.")]
+    MemberIdRequired(String),
+    #[error("The consumer group has reached its max size.")]
+    GroupMaxSizeReached,
+    #[error("The broker rejected this static consumer since another consumer with the same group.instance.id has registered with a different member.id.")]
+    FencedInstanceId,
+    #[error("The metadata field of the offset request was too large.")]
+    OffsetMetadataTooLarge,
+    #[error("The group is not empty.")]
+    NonEmptyGroup,
+    #[error("The group id does not exist.")]
+    GroupIdNotFound,
+    #[error("The consumer group is actively subscribed to the topic.")]
+    GroupSubscribedToTopic,
+    #[error("{0}")]
+    InvalidRequest(String),
+    #[error("The assignor or its version range is not supported by the consumer group.")]
+    UnsupportedAssignor,
+    #[error("The instance ID is still used by another member in the consumer group. That member must leave first.")]
+    UnreleasedInstanceId,
+    #[error("The member epoch is fenced by the group coordinator. The member must abandon all its partitions and rejoin.")]
+    FencedMemberEpoch,
+    #[error("The member epoch is stale. The member must retry after receiving its updated member epoch via the ConsumerGroupHeartbeat API.")]
+    StaleMemberEpoch,
+    #[error("The regular expression is not valid: {0}")]
+    InvalidRegularExpression(String),
+    #[error("There are unstable offsets that need to be cleared.")]
+    UnstableOffsetCommit,
+    #[error("Storage error: {0}")]
+    Storage(#[from] io::Error),
+}
+
+/// A member's request to join a group
+#[derive(Debug)]
+pub struct JoinGroupParams {
+    pub group_id: String,
+    /// Empty for a member joining for the first time
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    pub client_id: String,
+    pub client_host: String,
+    pub session_timeout_ms: i32,
+    pub rebalance_timeout_ms: i32,
+    pub protocol_type: String,
+    pub protocols: Vec<JoinProtocol>,
+    /// Whether a new member has to rejoin with the member id it's given before it's added to the group
+    pub require_known_member_id: bool,
+    pub reason: Option<String>,
+}
+
+/// The generation a member has joined
+#[derive(Debug, Clone)]
+pub struct JoinedGroup {
+    pub generation_id: i32,
+    pub protocol_type: Option<String>,
+    pub protocol_name: Option<String>,
+    pub leader: String,
+    pub member_id: String,
+    /// Only the leader is sent the members, since it's the one that assigns them their work
+    pub members: Vec<JoinedMember>,
+    /// Whether the leader should skip assigning the members, because a static leader rejoined
+    /// without changing the group and the current assignments still stand
+    pub skip_assignment: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct JoinedMember {
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    /// The member's metadata for the chosen protocol
+    pub metadata: Vec<u8>,
+}
+
+/// A member's request for its assignment, which the leader sends with every member's assignment
+#[derive(Debug)]
+pub struct SyncGroupParams {
+    pub group_id: String,
+    pub generation_id: i32,
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    pub protocol_type: Option<String>,
+    pub protocol_name: Option<String>,
+    pub assignments: Vec<(String, Vec<u8>)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncedGroup {
+    pub protocol_type: Option<String>,
+    pub protocol_name: Option<String>,
+    pub assignment: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct LeavingMember {
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    pub reason: Option<String>,
+}
+
+/// A request to commit offsets for a group
+#[derive(Debug)]
+pub struct OffsetCommitParams {
+    pub group_id: String,
+    /// Negative for commits from outside the group, such as by a consumer that assigns itself partitions
+    pub generation_id: i32,
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    pub offsets: Vec<(TopicPartition, OffsetAndMetadata)>,
+}
+
+/// The rebalance protocol a group uses
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GroupType {
+    /// Members join the group, and the leader assigns them their work
+    Classic,
+    /// The coordinator assigns the members their partitions, from KIP-848
+    Consumer,
+}
+
+impl GroupType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GroupType::Classic => "classic",
+            GroupType::Consumer => "consumer",
+        }
+    }
+}
+
+/// A group as it's listed to admin clients
+#[derive(Debug, Clone)]
+pub struct ListedGroup {
+    pub group_id: String,
+    pub protocol_type: String,
+    pub state: &'static str,
+    pub group_type: GroupType,
+}
+
+/// A group and its members, as it's described to admin clients
+#[derive(Debug, Clone)]
+pub struct DescribedGroup {
+    pub state: GroupState,
+    pub protocol_type: String,
+    /// The protocol of the current generation, which is only described once the group is stable
+    pub protocol_name: String,
+    pub members: Vec<DescribedMember>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DescribedMember {
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    pub client_id: String,
+    pub client_host: String,
+    pub metadata: Vec<u8>,
+    pub assignment: Vec<u8>,
+}
+
+/// A heartbeat from a member of a consumer group, where the optional fields are only sent when the member joins or they change
+#[derive(Debug)]
+pub struct ConsumerGroupHeartbeatParams {
+    pub group_id: String,
+    /// Empty for a new member that's left it to the coordinator to choose its member id
+    pub member_id: String,
+    /// 0 to join the group, or the epoch the member is leaving the group with
+    pub member_epoch: i32,
+    pub group_instance_id: Option<String>,
+    pub rack_id: Option<String>,
+    pub rebalance_timeout_ms: i32,
+    pub subscribed_topic_names: Option<Vec<String>>,
+    pub subscribed_topic_regex: Option<String>,
+    pub server_assignor: Option<String>,
+    /// The partitions the member owns
+    pub owned_partitions: Option<Assignment>,
+    pub client_id: String,
+    pub client_host: String,
+}
+
+#[derive(Debug)]
+pub struct ConsumerGroupHeartbeatResult {
+    pub member_id: String,
+    pub member_epoch: i32,
+    pub heartbeat_interval_ms: i32,
+    /// The partitions the member should own, which are only sent when they've changed or the member sent every field
+    pub assignment: Option<Assignment>,
+}
+
+/// A consumer group and its members, as it's described to admin clients
+#[derive(Debug, Clone)]
+pub struct DescribedConsumerGroup {
+    pub state: ConsumerGroupState,
+    pub group_epoch: i32,
+    pub assignment_epoch: i32,
+    pub assignor_name: String,
+    pub members: Vec<DescribedConsumerMember>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DescribedConsumerMember {
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    pub rack_id: Option<String>,
+    pub member_epoch: i32,
+    pub client_id: String,
+    pub client_host: String,
+    pub subscribed_topic_names: Vec<String>,
+    pub subscribed_topic_regex: Option<String>,
+    pub assignment: Assignment,
+    pub target_assignment: Assignment,
+}
+
+/// Whether an operation on each of a group's partitions succeeded
+pub type PartitionResults = Vec<(TopicPartition, Result<(), GroupError>)>;
+
+/// The offset each partition has committed, if it can be fetched
+pub type PartitionOffsets = Vec<(TopicPartition, Result<Option<OffsetAndMetadata>, GroupError>)>;
+
+/// Operations the coordinator delays, keyed so they can be rescheduled or cancelled
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum DelayedOperation {
+    /// Remove a member that hasn't heartbeat within its session timeout
+    MemberSession(String, String),
+    /// Remove a member of a consumer group that hasn't heartbeat within its session timeout
+    ConsumerMemberSession(String, String),
+    /// Remove a member of a consumer group that hasn't revoked its partitions within its rebalance timeout
+    ConsumerMemberRevocation(String, String),
+}
+
+/// Coordinates groups of members using the classic rebalance protocol.
+/// This broker is the coordinator of every group, since it's the only broker in the cluster
+#[derive(Debug)]
+pub struct GroupCoordinator {
+    min_session_timeout_ms: i32,
+    max_session_timeout_ms: i32,
+    initial_rebalance_delay: Duration,
+    max_size: usize,
+    offset_metadata_max_bytes: usize,
+    consumer_session_timeout: Duration,
+    consumer_heartbeat_interval_ms: i32,
+    consumer_max_size: usize,
+    /// The assignors consumer groups can choose from, where the first is the default
+    consumer_assignors: Vec<Assignor>,
+    /// Classic groups, including the groups that only exist to hold committed offsets.
+    /// When both are locked, this is locked before the consumer groups
+    groups: Mutex<HashMap<String, GroupMetadata>>,
+    /// Groups using the consumer rebalance protocol, which are only kept in memory
+    consumer_groups: Mutex<HashMap<String, ConsumerGroup>>,
+    /// The partitions of the offsets topic, which are set once the committed offsets have been loaded from them
+    offsets_logs: OnceLock<Vec<Arc<Log>>>,
+    timer: Timer<DelayedOperation>,
+    /// Rebalances waiting for their members to rejoin, keyed by group id
+    rebalance_purgatory: Arc<Purgatory<String>>,
+    /// Delayed operations run after the request that scheduled them has finished, so they need their own reference
+    this: Weak<GroupCoordinator>,
+}
+
+impl GroupCoordinator {
+    pub fn new(config: &BrokerConfig) -> Arc<GroupCoordinator> {
+        Arc::new_cyclic(|this| GroupCoordinator {
+            min_session_timeout_ms: config.group_min_session_timeout_ms(),
+            max_session_timeout_ms: config.group_max_session_timeout_ms(),
+            initial_rebalance_delay: Duration::from_millis(config.group_initial_rebalance_delay_ms() as u64),
+            max_size: config.group_max_size() as usize,
+            offset_metadata_max_bytes: config.offset_metadata_max_bytes() as usize,
+            consumer_session_timeout: Duration::from_millis(config.group_consumer_session_timeout_ms() as u64),
+            consumer_heartbeat_interval_ms: config.group_consumer_heartbeat_interval_ms(),
+            consumer_max_size: config.group_consumer_max_size() as usize,
+            consumer_assignors: config.group_consumer_assignors().iter()
+                .filter_map(|name| Assignor::from_name(name))
+                .collect(),
+            groups: Mutex::new(HashMap::new()),
+            consumer_groups: Mutex::new(HashMap::new()),
+            offsets_logs: OnceLock::new(),
+            timer: Timer::new(),
+            rebalance_purgatory: Arc::new(Purgatory::new()),
+            this: this.clone(),
+        })
+    }
+
+    /// Join the group, waiting until the rebalance it's part of has completed
+    pub async fn join_group(&self, join: JoinGroupParams) -> Result<JoinedGroup, GroupError> {
+        let awaiting_join = self.start_join(join)?;
+        // the member was removed from the group before the rebalance completed
+        awaiting_join.await.unwrap_or(Err(GroupError::UnknownMemberId))
+    }
+
+    fn start_join(&self, join: JoinGroupParams) -> Result<oneshot::Receiver<Result<JoinedGroup, GroupError>>, GroupError> {
+        if join.group_id.is_empty() {
+            return Err(GroupError::InvalidGroupId);
+        }
+        if !(self.min_session_timeout_ms..=self.max_session_timeout_ms).contains(&join.session_timeout_ms) {
+            return Err(GroupError::InvalidSessionTimeout);
+        }
+
+        let mut groups = self.groups.lock().unwrap();
+        if self.consumer_groups.lock().unwrap().contains_key(&join.group_id) {
+            return Err(GroupError::InconsistentGroupProtocol);
+        }
+        let group = match groups.entry(join.group_id.clone()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            // only new members can create a group
+            Entry::Vacant(_) if !join.member_id.is_empty() => return Err(GroupError::UnknownMemberId),
+            Entry::Vacant(entry) => entry.insert(GroupMetadata::new(join.group_id.clone())),
+        };
+        if group.state() == GroupState::Dead {
+            return Err(GroupError::CoordinatorNotAvailable);
+        }
+        if !group.supports_protocols(&join.protocol_type, &join.protocols) {
+            return Err(GroupError::InconsistentGroupProtocol);
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        match join.member_id.is_empty() {
+            true => self.join_new_member(group, join, sender)?,
+            false => self.join_existing_member(group, join, sender)?,
+        }
+        Ok(receiver)
+    }
+
+    fn join_new_member(
+        &self,
+        group: &mut GroupMetadata,
+        join: JoinGroupParams,
+        awaiting_join: oneshot::Sender<Result<JoinedGroup, GroupError>>,
+    ) -> Result<(), GroupError> {
+        let known_static_member_id = join.group_instance_id.as_deref()
+            .and_then(|group_instance_id| group.static_member_id(group_instance_id))
+            .map(str::to_string);
+        let member_id = match &join.group_instance_id {
+            Some(group_instance_id) => format!("{group_instance_id}-{}", Uuid::new_v4()),
+            None => format!("{}-{}", join.client_id, Uuid::new_v4()),
+        };
+        if let Some(old_member_id) = known_static_member_id {
+            return self.update_static_member_and_rebalance(group, old_member_id, member_id, join, awaiting_join);
+        }
+        if group.size() >= self.max_size {
+            return Err(GroupError::GroupMaxSizeReached);
+        }
+        // static members are identified by their instance id, so they don't need to know their member id before joining
+        if join.require_known_member_id && join.group_instance_id.is_none() {
+            // the member is only added once it rejoins with its id, so a member that never gets the response isn't left in the group
+            group.add_pending_member(member_id.clone());
+            self.schedule_session_expiry(group.group_id(), &member_id, Duration::from_millis(join.session_timeout_ms as u64));
+            return Err(GroupError::MemberIdRequired(member_id));
+        }
+        self.add_member_and_rebalance(group, member_id, join, awaiting_join);
+        Ok(())
+    }
+
+    fn join_existing_member(
+        &self,
+        group: &mut GroupMetadata,
+        join: JoinGroupParams,
+        awaiting_join: oneshot::Sender<Result<JoinedGroup, GroupError>>,
+    ) -> Result<(), GroupError> {
+        if group.remove_pending_member(&join.member_id) {
+            self.timer.cancel(&DelayedOperation::MemberSession(join.group_id.clone(), join.member_id.clone()));
+            let member_id = join.member_id.clone();
+            self.add_member_and_rebalance(group, member_id, join, awaiting_join);
+            return Ok(());
+        }
+        Self::validate_instance(group, &join.member_id, join.group_instance_id.as_deref())?;
+        let Some(member) = group.member(&join.member_id) else {
+            return Err(GroupError::UnknownMemberId);
+        };
+
+        match group.state() {
+            GroupState::Empty | GroupState::Dead => Err(GroupError::UnknownMemberId),
+            GroupState::PreparingRebalance => {
+                self.update_member_and_rebalance(group, join, awaiting_join);
+                Ok(())
+            }
+            // a follower rejoining with the same protocols probably missed the response to its join, so gets the same one again
+            GroupState::CompletingRebalance | GroupState::Stable
+            if member.matches(&join.protocols) && (group.state() == GroupState::CompletingRebalance || !group.is_leader(&join.member_id)) => {
+                let _ = awaiting_join.send(Ok(Self::joined_group(group, &join.member_id)));
+                Ok(())
+            }
+            // the leader rejoining, or a member with new metadata, means the members need new assignments
+            GroupState::CompletingRebalance | GroupState::Stable => {
+                self.update_member_and_rebalance(group, join, awaiting_join);
+                Ok(())
+            }
+        }
+    }
+
+    fn add_member_and_rebalance(
+        &self,
+        group: &mut GroupMetadata,
+        member_id: String,
+        join: JoinGroupParams,
+        awaiting_join: oneshot::Sender<Result<JoinedGroup, GroupError>>,
+    ) {
+        let reason = join.reason.clone();
+        let mut member = MemberMetadata::new(member_id.clone(), join);
+        member.await_join(awaiting_join);
+        group.add_member(member);
+
+        match group.state() {
+            GroupState::PreparingRebalance => match group.initial_rebalance_deadline() {
+                // give the first rebalance of the group a little longer to see if even more members are about to join
+                Some(deadline) => {
+                    let delay = self.initial_rebalance_delay.min(deadline.saturating_duration_since(Instant::now()));
+                    self.schedule_complete_join(group, delay);
+                }
+                None => self.try_complete_join(group),
+            },
+            _ => {
+                let reason = format!("Adding new member {member_id} with group instance id {:?}; client reason: {}",
+                                     group.member(&member_id).and_then(MemberMetadata::group_instance_id),
+                                     reason.as_deref().unwrap_or("not provided"));
+                self.prepare_rebalance(group, &reason);
+            }
+        }
+    }
+
+    fn update_member_and_rebalance(
+        &self,
+        group: &mut GroupMetadata,
+        join: JoinGroupParams,
+        awaiting_join: oneshot::Sender<Result<JoinedGroup, GroupError>>,
+    ) {
+        // members waiting to rejoin are kept in the group until the rebalance completes, rather than expiring
+        self.timer.cancel(&DelayedOperation::MemberSession(join.group_id.clone(), join.member_id.clone()));
+        if let Some(member) = group.member_mut(&join.member_id) {
+            member.update(join.session_timeout_ms, join.rebalance_timeout_ms, join.protocols);
+            member.await_join(awaiting_join);
+        }
+        match group.state() {
+            GroupState::PreparingRebalance => self.try_complete_join(group),
+            _ => {
+                let reason = format!("Updating metadata for member {} during {}; client reason: {}",
+                                     join.member_id, group.state().name(), join.reason.as_deref().unwrap_or("not provided"));
+                self.prepare_rebalance(group, &reason);
+            }
+        }
+    }
+
+    /// Replace a static member that's rejoined without its member id, such as after it restarted.
+    /// The group only rebalances if the member could change the protocol, or it may have missed its assignment
+    fn update_static_member_and_rebalance(
+        &self,
+        group: &mut GroupMetadata,
+        old_member_id: String,
+        new_member_id: String,
+        join: JoinGroupParams,
+        awaiting_join: oneshot::Sender<Result<JoinedGroup, GroupError>>,
+    ) -> Result<(), GroupError> {
+        let Some(member) = group.member(&old_member_id) else {
+            return Err(GroupError::UnknownMemberId);
+        };
+        let (old_session_timeout_ms, old_rebalance_timeout_ms) = (member.session_timeout_ms(), member.rebalance_timeout_ms());
+        let old_protocols = member.protocols().to_vec();
+
+        self.timer.cancel(&DelayedOperation::MemberSession(group.group_id().to_string(), old_member_id.clone()));
+        group.replace_static_member(&old_member_id, new_member_id.clone());
+        if let Some(member) = group.member_mut(&new_member_id) {
+            member.update(join.session_timeout_ms, join.rebalance_timeout_ms, join.protocols);
+        }
+        info!("Static member with group instance id {:?} in group {} replaced member {old_member_id} with {new_member_id}",
+                 join.group_instance_id, group.group_id());
+
+        match group.state() {
+            GroupState::Stable if group.select_protocol().as_deref() == group.protocol_name() => {
+                if let Err(err) = self.store_group(group) {
+                    // the old member id is still the one the rest of the group knows about
+                    if let Some(member) = group.member_mut(&new_member_id) {
+                        member.update(old_session_timeout_ms, old_rebalance_timeout_ms, old_protocols);
+                    }
+                    group.replace_static_member(&new_member_id, old_member_id);
+                    return Err(err);
+                }
+                self.heartbeat_member(group, &new_member_id);
+                let mut joined = Self::joined_group(group, &new_member_id);
+                joined.skip_assignment = group.is_leader(&new_member_id);
+                let _ = awaiting_join.send(Ok(joined));
+            }
+            GroupState::PreparingRebalance => {
+                if let Some(member) = group.member_mut(&new_member_id) {
+                    member.await_join(awaiting_join);
+                }
+                self.try_complete_join(group);
+            }
+            // the other members may have already been sent the old member id, so they have to rejoin to learn the new one
+            _ => {
+                if let Some(member) = group.member_mut(&new_member_id) {
+                    member.await_join(awaiting_join);
+                }
+                let reason = format!("Updating metadata for static member {new_member_id} with group instance id {:?}; client reason: {}",
+                                     join.group_instance_id, join.reason.as_deref().unwrap_or("not provided"));
+                self.prepare_rebalance(group, &reason);
+            }
+        }
+        Ok(())
+    }
+
+    /// Start a rebalance, where every member has to rejoin the group before the rebalance timeout
+    fn prepare_rebalance(&self, group: &mut GroupMetadata, reason: &str) {
+        // members waiting for their assignment from the previous rebalance have to rejoin instead
+        if group.state() == GroupState::CompletingRebalance {
+            for member_id in group.member_ids() {
+                if let Some(member) = group.member_mut(&member_id) {
+                    member.set_assignment(Vec::new());
+                    member.complete_sync(Err(GroupError::RebalanceInProgress));
+                }
+            }
+        }
+
+        let rebalance_timeout = group.rebalance_timeout();
+        let delay = match group.state() {
+            // wait a little while before the first rebalance of a group, since the other members are probably starting too
+            GroupState::Empty => {
+                group.set_initial_rebalance_deadline(Some(Instant::now() + rebalance_timeout));
+                self.initial_rebalance_delay.min(rebalance_timeout)
+            }
+            _ => rebalance_timeout,
+        };
+        info!("Preparing to rebalance group {} in state {} with old generation {} (reason: {reason})",
+                 group.group_id(), group.state().name(), group.generation_id());
+        group.transition_to(GroupState::PreparingRebalance);
+        self.schedule_complete_join(group, delay);
+        self.try_complete_join(group);
+    }
+
+    /// Complete the rebalance early if every member has already rejoined
+    fn try_complete_join(&self, group: &mut GroupMetadata) {
+        let waiting_for_initial_members = group.initial_rebalance_deadline().is_some();
+        if group.state() == GroupState::PreparingRebalance && !waiting_for_initial_members && group.has_all_members_joined() {
+            self.complete_join(group);
+        }
+    }
+
+    /// Start the next generation with the members that rejoined, sending each of them the result of their join
+    fn complete_join(&self, group: &mut GroupMetadata) {
+        group.set_initial_rebalance_deadline(None);
+        group.set_join_deadline(None);
+        // the delayed join has nothing left to wait for
+        self.rebalance_purgatory.check_and_complete(&group.group_id().to_string());
+        let missing: Vec<String> = group.members()
+            .filter(|member| !member.is_awaiting_join())
+            .map(|member| member.member_id().to_string())
+            .collect();
+        for member_id in missing {
+            info!("Removing member {member_id} from group {} since it didn't rejoin before the rebalance timeout",
+                     group.group_id());
+            self.timer.cancel(&DelayedOperation::MemberSession(group.group_id().to_string(), member_id.clone()));
+            group.remove_member(&member_id);
+        }
+
+        group.init_next_generation();
+        if group.state() == GroupState::Empty {
+            info!("Group {} with generation {} is now empty", group.group_id(), group.generation_id());
+            // so the members that left aren't loaded again
+            if let Err(err) = self.store_group(group) {
+                error!("Failed to store the empty group {}: {err}", group.group_id());
+            }
+            return;
+        }
+        info!("Stabilized group {} generation {} with {} members",
+                 group.group_id(), group.generation_id(), group.member_ids().len());
+        for member_id in group.member_ids() {
+            let joined = Self::joined_group(group, &member_id);
+            if let Some(member) = group.member_mut(&member_id) {
+                member.complete_join(Ok(joined));
+                member.extend_session();
+                let session_timeout = member.session_timeout();
+                self.schedule_session_expiry(group.group_id(), &member_id, session_timeout);
+            }
+        }
+    }
+
+    /// The generation of the group, as it's sent to a member that joined it
+    fn joined_group(group: &GroupMetadata, member_id: &str) -> JoinedGroup {
+        let protocol_name = group.protocol_name().unwrap_or_default();
+        let members = match group.is_leader(member_id) {
+            true => group.members()
+                .map(|member| JoinedMember {
+                    member_id: member.member_id().to_string(),
+                    group_instance_id: member.group_instance_id().map(str::to_string),
+                    metadata: member.metadata(protocol_name).unwrap_or_default().to_vec(),
+                })
+                .collect(),
+            false => Vec::new(),
+        };
+        JoinedGroup {
+            generation_id: group.generation_id(),
+            protocol_type: group.protocol_type().map(str::to_string),
+            protocol_name: group.protocol_name().map(str::to_string),
+            leader: group.leader_id().unwrap_or_default().to_string(),
+            member_id: member_id.to_string(),
+            members,
+            skip_assignment: false,
+        }
+    }
+
+    /// Get the member's assignment, waiting for the leader to send it if this is a new generation
+    pub async fn sync_group(&self, sync: SyncGroupParams) -> Result<SyncedGroup, GroupError> {
+        let awaiting_sync = self.start_sync(sync)?;
+        awaiting_sync.await.unwrap_or(Err(GroupError::UnknownMemberId))
+    }
+
+    fn start_sync(&self, sync: SyncGroupParams) -> Result<oneshot::Receiver<Result<SyncedGroup, GroupError>>, GroupError> {
+        if sync.group_id.is_empty() {
+            return Err(GroupError::InvalidGroupId);
+        }
+        let mut groups = self.groups.lock().unwrap();
+        let group = Self::validate_member(&mut groups, &sync.group_id, &sync.member_id, sync.group_instance_id.as_deref(), sync.generation_id)?;
+        if sync.protocol_type.as_ref().is_some_and(|protocol_type| group.protocol_type() != Some(protocol_type))
+            || sync.protocol_name.as_ref().is_some_and(|protocol_name| group.protocol_name() != Some(protocol_name)) {
+            return Err(GroupError::InconsistentGroupProtocol);
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        match group.state() {
+            GroupState::Empty | GroupState::Dead => return Err(GroupError::UnknownMemberId),
+            GroupState::PreparingRebalance => return Err(GroupError::RebalanceInProgress),
+            GroupState::CompletingRebalance => {
+                if let Some(member) = group.member_mut(&sync.member_id) {
+                    member.await_sync(sender);
+                }
+                if group.is_leader(&sync.member_id) {
+                    info!("Assignment received from leader {} for group {} for generation {}",
+                             sync.member_id, group.group_id(), group.generation_id());
+                    self.complete_sync(group, sync.assignments);
+                }
+            }
+            GroupState::Stable => {
+                let _ = sender.send(Ok(Self::synced_group(group, &sync.member_id)));
+            }
+        }
+        self.heartbeat_member(group, &sync.member_id);
+        Ok(receiver)
+    }
+
+    /// Store the leader's assignments, sending every member waiting for its assignment what it's been given
+    fn complete_sync(&self, group: &mut GroupMetadata, assignments: Vec<(String, Vec<u8>)>) {
+        let mut assignments: HashMap<String, Vec<u8>> = assignments.into_iter().collect();
+        for member_id in group.member_ids() {
+            // members the leader didn't give anything to get an empty assignment
+            let assignment = assignments.remove(&member_id).unwrap_or_default();
+            if let Some(member) = group.member_mut(&member_id) {
+                member.set_assignment(assignment);
+            }
+        }
+        if let Err(err) = self.store_group(group) {
+            let reason = format!("failed to store the assignments of generation {}: {err}", group.generation_id());
+            self.prepare_rebalance(group, &reason);
+            return;
+        }
+        for member_id in group.member_ids() {
+            let synced = Self::synced_group(group, &member_id);
+            if let Some(member) = group.member_mut(&member_id) {
+                member.complete_sync(Ok(synced));
+            }
+        }
+        group.transition_to(GroupState::Stable);
+    }
+
+    fn synced_group(group: &GroupMetadata, member_id: &str) -> SyncedGroup {
+        SyncedGroup {
+            protocol_type: group.protocol_type().map(str::to_string),
+            protocol_name: group.protocol_name().map(str::to_string),
+            assignment: group.member(member_id).map(|member| member.assignment().to_vec()).unwrap_or_default(),
+        }
+    }
+
+    /// Keep the member in the group, telling it whether it has to rejoin because the group is rebalancing
+    pub fn heartbeat(&self, group_id: &str, member_id: &str, group_instance_id: Option<&str>, generation_id: i32) -> Result<(), GroupError> {
+        let mut groups = self.groups.lock().unwrap();
+        let group = Self::validate_member(&mut groups, group_id, member_id, group_instance_id, generation_id)?;
+        match group.state() {
+            GroupState::Empty | GroupState::Dead => Err(GroupError::UnknownMemberId),
+            GroupState::PreparingRebalance => {
+                self.heartbeat_member(group, member_id);
+                Err(GroupError::RebalanceInProgress)
+            }
+            // members can start heartbeating as soon as they've joined, before they have their assignment
+            GroupState::CompletingRebalance | GroupState::Stable => {
+                self.heartbeat_member(group, member_id);
+                Ok(())
+            }
+        }
+    }
+
+    /// Remove the members from the group, which rebalances the members that are left
+    pub fn leave_group(&self, group_id: &str, leaving: &[LeavingMember]) -> Result<Vec<Result<(), GroupError>>, GroupError> {
+        if group_id.is_empty() {
+            return Err(GroupError::InvalidGroupId);
+        }
+        let mut groups = self.groups.lock().unwrap();
+        let Some(group) = groups.get_mut(group_id) else {
+            return Ok(leaving.iter().map(|_| Err(GroupError::UnknownMemberId)).collect());
+        };
+        if group.state() == GroupState::Dead {
+            return Err(GroupError::CoordinatorNotAvailable);
+        }
+
+        let results = leaving.iter()
+            .map(|member| {
+                if group.remove_pending_member(&member.member_id) {
+                    self.timer.cancel(&DelayedOperation::MemberSession(group_id.to_string(), member.member_id.clone()));
+                    return Ok(());
+                }
+                let member_id = match member.group_instance_id.as_deref() {
+                    // static members can leave by their instance id alone
+                    Some(group_instance_id) if member.member_id.is_empty() => group.static_member_id(group_instance_id)
+                        .ok_or(GroupError::UnknownMemberId)?
+                        .to_string(),
+                    group_instance_id => {
+                        Self::validate_instance(group, &member.member_id, group_instance_id)?;
+                        member.member_id.clone()
+                    }
+                };
+                let reason = format!("Removing member {member_id} on LeaveGroup; client reason: {}",
+                                     member.reason.as_deref().unwrap_or("not provided"));
+                self.remove_member_and_rebalance(group, &member_id, &reason);
+                Ok(())
+            })
+            .collect();
+        Ok(results)
+    }
+
+    /// Join, heartbeat or leave a consumer group, moving the member towards its target assignment.
+    /// The topics are every topic in the cluster, by name, which the members' subscriptions are resolved against
+    pub fn consumer_group_heartbeat(
+        &self,
+        heartbeat: ConsumerGroupHeartbeatParams,
+        topics: &BTreeMap<String, TopicInfo>,
+    ) -> Result<ConsumerGroupHeartbeatResult, GroupError> {
+        self.validate_consumer_heartbeat(&heartbeat)?;
+        let groups = self.groups.lock().unwrap();
+        // a classic group with members can't be taken over by a consumer group
+        if groups.get(&heartbeat.group_id).is_some_and(|group| group.state() != GroupState::Empty) {
+            return Err(GroupError::GroupIdNotFound);
+        }
+        let mut consumer_groups = self.consumer_groups.lock().unwrap();
+        if heartbeat.member_epoch < 0 {
+            return self.leave_consumer_group(&mut consumer_groups, &heartbeat);
+        }
+        let group = match consumer_groups.entry(heartbeat.group_id.clone()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) if heartbeat.member_epoch == 0 => entry.insert(ConsumerGroup::new(heartbeat.group_id.clone())),
+            // consumer groups are only kept in memory, so their members have to rejoin after a restart
+            Entry::Vacant(_) => return Err(GroupError::UnknownMemberId),
+        };
+
+        let (member_id, member_changed) = match heartbeat.member_epoch {
+            0 => self.join_consumer_group(group, &heartbeat)?,
+            _ => {
+                Self::validate_consumer_member(group, &heartbeat)?;
+                let member = group.member_mut(&heartbeat.member_id).ok_or(GroupError::UnknownMemberId)?;
+                (heartbeat.member_id.clone(), member.update(&heartbeat))
+            }
+        };
+        // a change to the members or to the topics they're subscribed to needs a new assignment
+        let topics_changed = group.update_subscribed_topics(topics);
+        if member_changed || topics_changed {
+            group.bump_group_epoch();
+        }
+        group.update_target_assignment(self.consumer_assignors.first().copied().unwrap_or(Assignor::Uniform));
+        let previous_assignment = group.member(&member_id).map(|member| member.assigned_partitions().clone());
+        group.reconcile(&member_id, heartbeat.owned_partitions.as_ref());
+        let member = group.member(&member_id).ok_or(GroupError::UnknownMemberId)?;
+
+        let revocation_key = DelayedOperation::ConsumerMemberRevocation(heartbeat.group_id.clone(), member_id.clone());
+        match member.state() {
+            // keep the deadline the member was first given to revoke its partitions
+            MemberState::UnrevokedPartitions if self.timer.is_scheduled(&revocation_key) => {}
+            MemberState::UnrevokedPartitions => self.schedule_revocation_timeout(
+                &heartbeat.group_id, &member_id, member.member_epoch(), Duration::from_millis(member.rebalance_timeout_ms().max(0) as u64),
+            ),
+            MemberState::Stable | MemberState::UnreleasedPartitions => {
+                self.timer.cancel(&revocation_key);
+            }
+        }
+        self.schedule_consumer_session_expiry(&heartbeat.group_id, &member_id);
+
+        // members that sent every field may have lost their assignment, so they're always sent it
+        let full_request = heartbeat.member_epoch == 0 || heartbeat.rebalance_timeout_ms != -1
+            && heartbeat.subscribed_topic_names.is_some() && heartbeat.owned_partitions.is_some();
+        let assignment = match full_request || previous_assignment.as_ref() != Some(member.assigned_partitions()) {
+            true => Some(member.assigned_partitions().clone()),
+            false => None,
+        };
+        Ok(ConsumerGroupHeartbeatResult {
+            member_id,
+            member_epoch: member.member_epoch(),
+            heartbeat_interval_ms: self.consumer_heartbeat_interval_ms,
+            assignment,
+        })
+    }
+
+    fn validate_consumer_heartbeat(&self, heartbeat: &ConsumerGroupHeartbeatParams) -> Result<(), GroupError> {
+        let invalid = |message: &str| Err(GroupError::InvalidRequest(message.to_string()));
+        if heartbeat.group_id.is_empty() {
+            return invalid("GroupId can't be empty.");
+        }
+        if heartbeat.group_instance_id.as_deref() == Some("") {
+            return invalid("InstanceId can't be empty.");
+        }
+        if heartbeat.rack_id.as_deref() == Some("") {
+            return invalid("RackId can't be empty.");
+        }
+        match heartbeat.member_epoch {
+            0 => {
+                if heartbeat.rebalance_timeout_ms == -1 {
+                    return invalid("RebalanceTimeoutMs must be provided in first request.");
+                }
+                if heartbeat.subscribed_topic_names.is_none() && heartbeat.subscribed_topic_regex.is_none() {
+                    return invalid("SubscribedTopicNames or SubscribedTopicRegex must be set in first request.");
+                }
+                if heartbeat.owned_partitions.as_ref().map_or(true, |owned| !owned.is_empty()) {
+                    return invalid("TopicPartitions must be empty when (re-)joining.");
+                }
+            }
+            LEAVE_GROUP_STATIC_MEMBER_EPOCH if heartbeat.group_instance_id.is_none() => {
+                return invalid("InstanceId can't be null when leaving the group with a static member epoch.");
+            }
+            epoch if epoch < LEAVE_GROUP_STATIC_MEMBER_EPOCH => return invalid("MemberEpoch is invalid."),
+            _ if heartbeat.member_id.is_empty() => return invalid("MemberId can't be empty."),
+            _ => {}
+        }
+        if let Some(server_assignor) = &heartbeat.server_assignor {
+            let supported = Assignor::from_name(server_assignor).is_some_and(|assignor| self.consumer_assignors.contains(&assignor));
+            if !supported {
+                return Err(GroupError::UnsupportedAssignor);
+            }
+        }
+        if let Some(regex) = heartbeat.subscribed_topic_regex.as_deref().filter(|regex| !regex.is_empty()) {
+            if let Err(err) = regex::Regex::new(regex) {
+                return Err(GroupError::InvalidRegularExpression(err.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the member to the group, or rejoin an existing member,
+    /// returning its member id and whether the group needs a new assignment
+    fn join_consumer_group(&self, group: &mut ConsumerGroup, heartbeat: &ConsumerGroupHeartbeatParams) -> Result<(String, bool), GroupError> {
+        let member_id = match heartbeat.member_id.is_empty() {
+            true => Uuid::new_v4().to_string(),
+            false => heartbeat.member_id.clone(),
+        };
+        if let Some(group_instance_id) = heartbeat.group_instance_id.as_deref() {
+            if let Some(static_member_id) = group.static_member_id(group_instance_id).filter(|id| *id != member_id).map(str::to_string) {
+                // the previous instance has to have left temporarily, so the new one can take over its assignment
+                let released = group.member(&static_member_id)
+                    .is_some_and(|member| member.member_epoch() == LEAVE_GROUP_STATIC_MEMBER_EPOCH);
+                if !released {
+                    return Err(GroupError::UnreleasedInstanceId);
+                }
+                group.replace_static_member(&static_member_id, member_id.clone());
+                self.cancel_consumer_member_timers(group.group_id(), &static_member_id);
+            }
+        }
+        match group.member_mut(&member_id) {
+            Some(member) => {
+                let changed = member.update(heartbeat);
+                Ok((member_id, changed))
+            }
+            None => {
+                if group.size() >= self.consumer_max_size {
+                    return Err(GroupError::GroupMaxSizeReached);
+                }
+                group.add_member(ConsumerGroupMember::new(member_id.clone(), heartbeat));
+                Ok((member_id, true))
+            }
+        }
+    }
+
+    /// Check the member is in the group with the epoch it's heartbeating with,
+    /// or its previous epoch when it's missed the response that bumped it
+    fn validate_consumer_member(group: &ConsumerGroup, heartbeat: &ConsumerGroupHeartbeatParams) -> Result<(), GroupError> {
+        let member = group.member(&heartbeat.member_id).ok_or(GroupError::UnknownMemberId)?;
+        if heartbeat.group_instance_id.as_deref() != member.group_instance_id() {
+            return Err(GroupError::FencedInstanceId);
+        }
+        if heartbeat.member_epoch == member.member_epoch() {
+            return Ok(());
+        }
+        let missed_bump = heartbeat.member_epoch == member.previous_member_epoch()
+            && heartbeat.owned_partitions.as_ref().is_some_and(|owned| is_subset(owned, member.assigned_partitions()));
+        match missed_bump && heartbeat.member_epoch < member.member_epoch() {
+            true => Ok(()),
+            false => Err(GroupError::FencedMemberEpoch),
+        }
+    }
+
+    fn leave_consumer_group(
+        &self,
+        consumer_groups: &mut HashMap<String, ConsumerGroup>,
+        heartbeat: &ConsumerGroupHeartbeatParams,
+    ) -> Result<ConsumerGroupHeartbeatResult, GroupError> {
+        let group = consumer_groups.get_mut(&heartbeat.group_id).ok_or(GroupError::UnknownMemberId)?;
+        let member_id = match heartbeat.group_instance_id.as_deref() {
+            Some(group_instance_id) => {
+                let static_member_id = group.static_member_id(group_instance_id).ok_or(GroupError::UnknownMemberId)?;
+                if static_member_id != heartbeat.member_id {
+                    return Err(GroupError::FencedInstanceId);
+                }
+                static_member_id.to_string()
+            }
+            None => group.member(&heartbeat.member_id).ok_or(GroupError::UnknownMemberId)?.member_id().to_string(),
+        };
+        if heartbeat.member_epoch == LEAVE_GROUP_STATIC_MEMBER_EPOCH {
+            // the member keeps its assignment until it's replaced or its session expires
+            if let Some(member) = group.member_mut(&member_id) {
+                member.leave_temporarily();
+            }
+            self.timer.cancel(&DelayedOperation::ConsumerMemberRevocation(heartbeat.group_id.clone(), member_id.clone()));
+            info!("Static member {member_id} of consumer group {} left temporarily", heartbeat.group_id);
+        } else {
+            group.remove_member(&member_id);
+            group.bump_group_epoch();
+            self.cancel_consumer_member_timers(&heartbeat.group_id, &member_id);
+            info!("Member {member_id} left consumer group {}", heartbeat.group_id);
+        }
+        Ok(ConsumerGroupHeartbeatResult {
+            member_id,
+            member_epoch: heartbeat.member_epoch,
+            heartbeat_interval_ms: 0,
+            assignment: None,
+        })
+    }
+
+    /// Describe the consumer group and its members
+    pub fn describe_consumer_group(&self, group_id: &str) -> Result<DescribedConsumerGroup, GroupError> {
+        let consumer_groups = self.consumer_groups.lock().unwrap();
+        let group = consumer_groups.get(group_id).ok_or(GroupError::GroupIdNotFound)?;
+        let default_assignor = self.consumer_assignors.first().copied().unwrap_or(Assignor::Uniform);
+        let members = group.members()
+            .map(|member| DescribedConsumerMember {
+                member_id: member.member_id().to_string(),
+                group_instance_id: member.group_instance_id().map(str::to_string),
+                rack_id: member.rack_id().map(str::to_string),
+                member_epoch: member.member_epoch(),
+                client_id: member.client_id().to_string(),
+                client_host: member.client_host().to_string(),
+                subscribed_topic_names: member.subscribed_topic_names().to_vec(),
+                subscribed_topic_regex: member.subscribed_topic_regex().map(str::to_string),
+                assignment: member.assigned_partitions().clone(),
+                target_assignment: group.target_assignment(member.member_id()).cloned().unwrap_or_default(),
+            })
+            .collect();
+        Ok(DescribedConsumerGroup {
+            state: group.state(),
+            group_epoch: group.group_epoch(),
+            assignment_epoch: group.assignment_epoch(),
+            assignor_name: group.assignor().unwrap_or(default_assignor).name().to_string(),
+            members,
+        })
+    }
+
+    /// The offset every group has committed for each partition, which is what consumer lag is measured from
+    pub fn committed_offsets(&self) -> Vec<(String, TopicPartition, i64)> {
+        self.groups.lock().unwrap().values()
+            .filter(|group| group.state() != GroupState::Dead)
+            .flat_map(|group| group.offsets().map(|(topic_partition, offset)| (group.group_id().to_string(), topic_partition.clone(), offset.offset)))
+            .collect()
+    }
+
+    /// Stop the member session timers and the delayed rebalances, so nothing is written to the offsets topic
+    /// by them once the broker is shutting down. The members' sessions start again when the groups are next loaded
+    pub fn shutdown(&self) {
+        self.timer.shutdown();
+        self.rebalance_purgatory.shutdown();
+    }
+
+    pub fn rebalance_purgatory(&self) -> &Purgatory<String> {
+        &self.rebalance_purgatory
+    }
+
+    /// Every group the coordinator knows about, including groups that only have committed offsets
+    pub fn list_groups(&self) -> Vec<ListedGroup> {
+        let groups = self.groups.lock().unwrap();
+        let consumer_groups = self.consumer_groups.lock().unwrap();
+        let mut listed: Vec<ListedGroup> = groups.values()
+            // a consumer group's offsets are held by an empty classic group, which isn't a group of its own
+            .filter(|group| group.state() != GroupState::Dead && !consumer_groups.contains_key(group.group_id()))
+            .map(|group| ListedGroup {
+                group_id: group.group_id().to_string(),
+                protocol_type: group.protocol_type().unwrap_or_default().to_string(),
+                state: group.state().name(),
+                group_type: GroupType::Classic,
+            })
+            .chain(consumer_groups.values().map(|group| ListedGroup {
+                group_id: group.group_id().to_string(),
+                protocol_type: CONSUMER_PROTOCOL_TYPE.to_string(),
+                state: group.state().name(),
+                group_type: GroupType::Consumer,
+            }))
+            .collect();
+        listed.sort_by(|a, b| a.group_id.cmp(&b.group_id));
+        listed
+    }
+
+    /// Describe the group and its members, where a group that doesn't exist is described as dead
+    pub fn describe_group(&self, group_id: &str) -> DescribedGroup {
+        let groups = self.groups.lock().unwrap();
+        let Some(group) = groups.get(group_id) else {
+            return DescribedGroup { state: GroupState::Dead, protocol_type: String::new(), protocol_name: String::new(), members: Vec::new() };
+        };
+        // the members' metadata and assignments are only settled once the group is stable
+        let protocol_name = match group.state() {
+            GroupState::Stable => group.protocol_name(),
+            _ => None,
+        };
+        let members = group.members()
+            .map(|member| DescribedMember {
+                member_id: member.member_id().to_string(),
+                group_instance_id: member.group_instance_id().map(str::to_string),
+                client_id: member.client_id().to_string(),
+                client_host: member.client_host().to_string(),
+                metadata: protocol_name.and_then(|protocol_name| member.metadata(protocol_name)).unwrap_or_default().to_vec(),
+                assignment: match protocol_name {
+                    Some(_) => member.assignment().to_vec(),
+                    None => Vec::new(),
+                },
+            })
+            .collect();
+        DescribedGroup {
+            state: group.state(),
+            protocol_type: group.protocol_type().unwrap_or_default().to_string(),
+            protocol_name: protocol_name.unwrap_or_default().to_string(),
+            members,
+        }
+    }
+
+    /// Delete groups that have no members, along with their committed offsets
+    pub fn delete_groups(&self, group_ids: &[String]) -> Vec<Result<(), GroupError>> {
+        let mut groups = self.groups.lock().unwrap();
+        let mut consumer_groups = self.consumer_groups.lock().unwrap();
+        group_ids.iter()
+            .map(|group_id| {
+                if group_id.is_empty() {
+                    return Err(GroupError::InvalidGroupId);
+                }
+                if let Some(consumer_group) = consumer_groups.get(group_id) {
+                    if consumer_group.size() > 0 {
+                        return Err(GroupError::NonEmptyGroup);
+                    }
+                    consumer_groups.remove(group_id);
+                    // the group may never have committed any offsets
+                    if !groups.contains_key(group_id) {
+                        info!("Consumer group {group_id} was removed");
+                        return Ok(());
+                    }
+                }
+                let group = groups.get_mut(group_id)
+                    .filter(|group| group.state() != GroupState::Dead)
+                    .ok_or(GroupError::GroupIdNotFound)?;
+                if group.state() != GroupState::Empty {
+                    return Err(GroupError::NonEmptyGroup);
+                }
+
+                let mut records: Vec<(OffsetsKey, Option<Vec<u8>>)> = group.offsets()
+                    .map(|(topic_partition, _)| {
+                        let key = OffsetsKey::OffsetCommit { group_id: group_id.clone(), topic_partition: topic_partition.clone() };
+                        (key, None)
+                    })
+                    .collect();
+                records.push((OffsetsKey::GroupMetadata { group_id: group_id.clone() }, None));
+                self.append_to_offsets_topic(group_id, records, None)?;
+                group.transition_to(GroupState::Dead);
+                groups.remove(group_id);
+                info!("Group {group_id} transitioned to Dead and was removed");
+                Ok(())
+            })
+            .collect()
+    }
+
+    /// Delete the group's committed offsets for the partitions, unless its members are consuming them
+    pub fn delete_offsets(&self, group_id: &str, partitions: Vec<TopicPartition>) -> Result<PartitionResults, GroupError> {
+        if group_id.is_empty() {
+            return Err(GroupError::InvalidGroupId);
+        }
+        let mut groups = self.groups.lock().unwrap();
+        let group = groups.get_mut(group_id)
+            .filter(|group| group.state() != GroupState::Dead)
+            .ok_or(GroupError::GroupIdNotFound)?;
+        let is_consumer_group = group.protocol_type() == Some(CONSUMER_PROTOCOL_TYPE);
+        if group.state() != GroupState::Empty && !is_consumer_group {
+            return Err(GroupError::NonEmptyGroup);
+        }
+
+        let subscribed_topics = match self.consumer_groups.lock().unwrap().get(group_id) {
+            Some(consumer_group) => consumer_group.subscribed_topic_names().cloned().collect(),
+            None => group.subscribed_topics().unwrap_or_default(),
+        };
+        let (subscribed, unsubscribed): (Vec<_>, Vec<_>) = partitions.into_iter()
+            .partition(|topic_partition| subscribed_topics.contains(topic_partition.topic()));
+        self.remove_offsets(group, &unsubscribed)?;
+        let results = unsubscribed.into_iter()
+            .map(|topic_partition| (topic_partition, Ok(())))
+            .chain(subscribed.into_iter().map(|topic_partition| (topic_partition, Err(GroupError::GroupSubscribedToTopic))))
+            .collect();
+        Ok(results)
+    }
+
+    /// Load the groups and the offsets they've committed by replaying the offsets topic, which is then written to.
+    /// Loaded members are removed unless they heartbeat within their session timeout, as if they had just joined
+    pub fn load_groups(&self, logs: Vec<Arc<Log>>) -> io::Result<()> {
+        let mut loaded_offsets = Vec::new();
+        let mut loaded_groups = HashMap::new();
+        // offsets committed in transactions by each producer, which are only loaded once the transaction is committed
+        let mut transactional_offsets: HashMap<i64, Vec<_>> = HashMap::new();
+        for log in &logs {
+            for (header, batch) in log.read_all_batches()? {
+                if header.is_control() {
+                    let marker = header.control_record_type(&batch)?;
+                    if let Some(offsets) = transactional_offsets.remove(&header.producer_id()) {
+                        if marker == Some(ControlRecordType::Commit) {
+                            loaded_offsets.extend(offsets);
+                        }
+                    }
+                    continue;
+                }
+                for record in header.records(&batch)? {
+                    let Some(key) = record.key() else {
+                        continue;
+                    };
+                    let key = OffsetsKey::parse(key)
+                        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                    // a record without a value is a tombstone for an offset or group that was deleted
+                    match key {
+                        OffsetsKey::OffsetCommit { group_id, topic_partition } => {
+                            let offset = match record.value() {
+                                Some(value) => Some(OffsetAndMetadata::parse(value)
+                                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?),
+                                None => None,
+                            };
+                            match header.is_transactional() {
+                                true => transactional_offsets.entry(header.producer_id()).or_default().push((group_id, topic_partition, offset)),
+                                false => loaded_offsets.push((group_id, topic_partition, offset)),
+                            }
+                        }
+                        OffsetsKey::GroupMetadata { group_id } => {
+                            let group = match record.value() {
+                                Some(value) => Some(GroupMetadataValue::parse(value)
+                                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?),
+                                None => None,
+                            };
+                            loaded_groups.insert(group_id, group);
+                        }
+                        OffsetsKey::Unknown(_) => {}
+                    }
+                }
+            }
+        }
+
+        let mut groups = self.groups.lock().unwrap();
+        for (group_id, group) in loaded_groups {
+            if let Some(group) = group {
+                groups.insert(group_id.clone(), GroupMetadata::load(group_id, group));
+            }
+        }
+        for (group_id, topic_partition, offset) in loaded_offsets {
+            let group = groups.entry(group_id.clone()).or_insert_with(|| GroupMetadata::new(group_id));
+            match offset {
+                Some(offset) => group.commit_offset(topic_partition, offset),
+                None => {
+                    group.remove_offset(&topic_partition);
+                }
+            }
+        }
+        // transactions that haven't completed are completed by the transaction coordinator once it's loaded
+        for (producer_id, offsets) in transactional_offsets {
+            for (group_id, topic_partition, offset) in offsets {
+                let group = groups.entry(group_id.clone()).or_insert_with(|| GroupMetadata::new(group_id));
+                if let Some(offset) = offset {
+                    group.add_pending_transactional_offset(producer_id, topic_partition, offset);
+                }
+            }
+        }
+        // a group that was only created to hold offsets has been deleted once it has none left
+        groups.retain(|_, group| group.has_offsets() || group.has_pending_transactional_offsets() || group.generation_id() > 0);
+        for group in groups.values() {
+            for member in group.members() {
+                self.schedule_session_expiry(group.group_id(), member.member_id(), member.session_timeout());
+            }
+        }
+        info!("Loaded {} groups and their committed offsets from {} partitions", groups.len(), logs.len());
+        self.offsets_logs.set(logs).expect("groups should only be loaded once");
+        Ok(())
+    }
+
+    /// Commit offsets for the group, writing them to the offsets topic.
+    /// Returns whether each partition's offset was committed, in the order they were given
+    pub fn commit_offsets(&self, commit: OffsetCommitParams) -> Result<PartitionResults, GroupError> {
+        let mut groups = self.groups.lock().unwrap();
+        let consumer_groups = self.consumer_groups.lock().unwrap();
+        let group = match consumer_groups.get(&commit.group_id) {
+            Some(consumer_group) => {
+                Self::validate_consumer_commit(consumer_group, &commit)?;
+                // the offsets of a consumer group are held by an empty classic group
+                groups.entry(commit.group_id.clone()).or_insert_with(|| GroupMetadata::new(commit.group_id.clone()))
+            }
+            None => {
+                let group = match groups.entry(commit.group_id.clone()) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    // groups that manage their own membership only exist to store their offsets
+                    Entry::Vacant(entry) if commit.generation_id < 0 => entry.insert(GroupMetadata::new(commit.group_id.clone())),
+                    Entry::Vacant(_) => return Err(GroupError::IllegalGeneration),
+                };
+                Self::validate_commit(group, &commit)?;
+                if commit.generation_id >= 0 {
+                    self.heartbeat_member(group, &commit.member_id);
+                }
+                group
+            }
+        };
+
+        let (results, committed) = self.check_offset_metadata(commit.offsets);
+        self.store_offsets(&commit.group_id, &committed, None)?;
+        for (topic_partition, offset) in committed {
+            group.commit_offset(topic_partition, offset);
+        }
+        Ok(results)
+    }
+
+    /// Commit offsets for the group as part of a producer's transaction. They're written to the offsets topic
+    /// straight away, but aren't committed until the transaction coordinator completes the transaction
+    pub fn commit_transactional_offsets(&self, commit: OffsetCommitParams, producer_id: i64, producer_epoch: i16) -> Result<PartitionResults, GroupError> {
+        let mut groups = self.groups.lock().unwrap();
+        let consumer_groups = self.consumer_groups.lock().unwrap();
+        // producers that don't send the consumer's group metadata commit without being checked against the group's members
+        let from_member = commit.generation_id >= 0 || !commit.member_id.is_empty() || commit.group_instance_id.is_some();
+        match consumer_groups.get(&commit.group_id) {
+            Some(consumer_group) if from_member => Self::validate_consumer_commit(consumer_group, &commit)?,
+            Some(_) => {}
+            None if from_member && !groups.contains_key(&commit.group_id) => return Err(GroupError::UnknownMemberId),
+            None => {}
+        }
+        let group = groups.entry(commit.group_id.clone()).or_insert_with(|| GroupMetadata::new(commit.group_id.clone()));
+        if group.state() == GroupState::Dead {
+            return Err(GroupError::CoordinatorNotAvailable);
+        }
+        if from_member && !consumer_groups.contains_key(&commit.group_id) {
+            Self::validate_commit(group, &commit)?;
+        }
+
+        let (results, committed) = self.check_offset_metadata(commit.offsets);
+        self.store_offsets(&commit.group_id, &committed, Some((producer_id, producer_epoch)))?;
+        for (topic_partition, offset) in committed {
+            group.add_pending_transactional_offset(producer_id, topic_partition, offset);
+        }
+        Ok(results)
+    }
+
+    /// Commit or discard the offsets committed in the producer's transaction, once its markers have been written
+    pub fn complete_transactional_offsets(&self, producer_id: i64, commit: bool) {
+        let mut groups = self.groups.lock().unwrap();
+        for group in groups.values_mut() {
+            group.complete_transactional_offsets(producer_id, commit);
+        }
+    }
+
+    /// The partition of the offsets topic the group's offsets are stored in, which transactions committing offsets write to
+    pub fn offsets_partition(&self, group_id: &str) -> Result<TopicPartition, GroupError> {
+        let logs = self.offsets_logs.get().ok_or(GroupError::CoordinatorNotAvailable)?;
+        Ok(TopicPartition::new(OFFSETS_TOPIC, partition_for(group_id, logs.len()) as i32))
+    }
+
+    /// Reject offsets whose metadata is too large, returning each partition's result along with the offsets that can be committed
+    fn check_offset_metadata(&self, offsets: Vec<(TopicPartition, OffsetAndMetadata)>) -> (PartitionResults, Vec<(TopicPartition, OffsetAndMetadata)>) {
+        let mut committed = Vec::new();
+        let results = offsets.into_iter()
+            .map(|(topic_partition, offset)| match offset.metadata.len() > self.offset_metadata_max_bytes {
+                true => (topic_partition, Err(GroupError::OffsetMetadataTooLarge)),
+                false => {
+                    committed.push((topic_partition.clone(), offset));
+                    (topic_partition, Ok(()))
+                }
+            })
+            .collect();
+        (results, committed)
+    }
+
+    /// The offsets the group has committed for the partitions, or for every partition if none are given.
+    /// Partitions the group hasn't committed to have no offset.
+    /// Members of consumer groups fetch with their member epoch, which has to be their current one.
+    /// When stable offsets are required, partitions with offsets pending in a transaction get an error instead
+    pub fn fetch_offsets(
+        &self,
+        group_id: &str,
+        member_id: Option<&str>,
+        member_epoch: i32,
+        partitions: Option<Vec<TopicPartition>>,
+        require_stable: bool,
+    ) -> Result<PartitionOffsets, GroupError> {
+        let groups = self.groups.lock().unwrap();
+        if let Some(consumer_group) = self.consumer_groups.lock().unwrap().get(group_id).filter(|_| member_epoch >= 0) {
+            let member = consumer_group.member(member_id.unwrap_or_default()).ok_or(GroupError::UnknownMemberId)?;
+            if member.member_epoch() != member_epoch {
+                return Err(GroupError::StaleMemberEpoch);
+            }
+        }
+        let group = groups.get(group_id);
+        if group.is_some_and(|group| group.state() == GroupState::Dead) {
+            return Err(GroupError::CoordinatorNotAvailable);
+        }
+        let is_unstable = |topic_partition: &TopicPartition| {
+            require_stable && group.is_some_and(|group| group.has_pending_transactional_offset(topic_partition))
+        };
+        let offsets = match partitions {
+            Some(partitions) => partitions.into_iter()
+                .map(|topic_partition| {
+                    let offset = match is_unstable(&topic_partition) {
+                        true => Err(GroupError::UnstableOffsetCommit),
+                        false => Ok(group.and_then(|group| group.offset(&topic_partition)).cloned()),
+                    };
+                    (topic_partition, offset)
+                })
+                .collect(),
+            None => group.into_iter()
+                .flat_map(GroupMetadata::offsets)
+                .map(|(topic_partition, offset)| {
+                    let offset = match is_unstable(topic_partition) {
+                        true => Err(GroupError::UnstableOffsetCommit),
+                        false => Ok(Some(offset.clone())),
+                    };
+                    (topic_partition.clone(), offset)
+                })
+                .collect(),
+        };
+        Ok(offsets)
+    }
+
+    /// Check the offsets are committed by a member of the group's current generation,
+    /// or from outside the group while it has no members
+    fn validate_commit(group: &GroupMetadata, commit: &OffsetCommitParams) -> Result<(), GroupError> {
+        match group.state() {
+            GroupState::Dead => return Err(GroupError::CoordinatorNotAvailable),
+            GroupState::Empty if commit.generation_id < 0 => return Ok(()),
+            // the members' assignments may be about to change
+            GroupState::CompletingRebalance => return Err(GroupError::RebalanceInProgress),
+            _ => {}
+        }
+        Self::validate_instance(group, &commit.member_id, commit.group_instance_id.as_deref())?;
+        if commit.generation_id != group.generation_id() {
+            return Err(GroupError::IllegalGeneration);
+        }
+        Ok(())
+    }
+
+    /// Check the offsets are committed by a member of the consumer group with its current member epoch,
+    /// or from outside the group while it has no members
+    fn validate_consumer_commit(group: &ConsumerGroup, commit: &OffsetCommitParams) -> Result<(), GroupError> {
+        if commit.generation_id < 0 && group.size() == 0 {
+            return Ok(());
+        }
+        let member = group.member(&commit.member_id).ok_or(GroupError::UnknownMemberId)?;
+        if commit.generation_id != member.member_epoch() {
+            return Err(GroupError::StaleMemberEpoch);
+        }
+        Ok(())
+    }
+
+    /// Write the offsets to the group's partition of the offsets topic, as part of the producer's transaction if there is one
+    fn store_offsets(&self, group_id: &str, offsets: &[(TopicPartition, OffsetAndMetadata)], producer: Option<(i64, i16)>) -> Result<(), GroupError> {
+        let records = offsets.iter()
+            .map(|(topic_partition, offset)| {
+                let key = OffsetsKey::OffsetCommit { group_id: group_id.to_string(), topic_partition: topic_partition.clone() };
+                (key, Some(offset.to_bytes()))
+            })
+            .collect();
+        self.append_to_offsets_topic(group_id, records, producer)
+    }
+
+    /// Write tombstones for the group's offsets, so they aren't loaded again
+    fn remove_offsets(&self, group: &mut GroupMetadata, partitions: &[TopicPartition]) -> Result<(), GroupError> {
+        let records = partitions.iter()
+            .map(|topic_partition| {
+                let key = OffsetsKey::OffsetCommit { group_id: group.group_id().to_string(), topic_partition: topic_partition.clone() };
+                (key, None)
+            })
+            .collect();
+        self.append_to_offsets_topic(group.group_id(), records, None)?;
+        for topic_partition in partitions {
+            group.remove_offset(topic_partition);
+        }
+        Ok(())
+    }
+
+    /// Write the group's current generation to the offsets topic, so its static members are known after a restart
+    fn store_group(&self, group: &GroupMetadata) -> Result<(), GroupError> {
+        let key = OffsetsKey::GroupMetadata { group_id: group.group_id().to_string() };
+        let value = group.to_value(now_ms()).to_bytes();
+        self.append_to_offsets_topic(group.group_id(), vec![(key, Some(value))], None)
+    }
+
+    fn append_to_offsets_topic(
+        &self,
+        group_id: &str,
+        records: Vec<(OffsetsKey, Option<Vec<u8>>)>,
+        producer: Option<(i64, i16)>,
+    ) -> Result<(), GroupError> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        let logs = self.offsets_logs.get().ok_or(GroupError::CoordinatorNotAvailable)?;
+        let timestamp = now_ms();
+        let builder = match producer {
+            Some((producer_id, producer_epoch)) => RecordBatchBuilder::new().transactional(producer_id, producer_epoch),
+            None => RecordBatchBuilder::new(),
+        };
+        let batch = records.into_iter()
+            .fold(builder, |batch, (key, value)| batch.add_record(timestamp, Some(key.to_bytes()), value))
+            .build();
+        logs[partition_for(group_id, logs.len())].append(batch, 0)?;
+        Ok(())
+    }
+
+    /// Find the member of the group, checking it's in the generation it thinks it is
+    fn validate_member<'a>(
+        groups: &'a mut HashMap<String, GroupMetadata>,
+        group_id: &str,
+        member_id: &str,
+        group_instance_id: Option<&str>,
+        generation_id: i32,
+    ) -> Result<&'a mut GroupMetadata, GroupError> {
+        let group = groups.get_mut(group_id).ok_or(GroupError::UnknownMemberId)?;
+        if group.state() == GroupState::Dead {
+            return Err(GroupError::CoordinatorNotAvailable);
+        }
+        Self::validate_instance(group, member_id, group_instance_id)?;
+        if generation_id != group.generation_id() {
+            return Err(GroupError::IllegalGeneration);
+        }
+        Ok(group)
+    }
+
+    /// Check the member is in the group, and that a static member is the latest one to join with its instance id
+    fn validate_instance(group: &GroupMetadata, member_id: &str, group_instance_id: Option<&str>) -> Result<(), GroupError> {
+        match group_instance_id.map(|group_instance_id| group.static_member_id(group_instance_id)) {
+            // another instance has joined with the same instance id since, replacing this one
+            Some(Some(static_member_id)) if static_member_id != member_id => Err(GroupError::FencedInstanceId),
+            Some(Some(_)) => Ok(()),
+            Some(None) => Err(GroupError::UnknownMemberId),
+            None => group.member(member_id).map(|_| ()).ok_or(GroupError::UnknownMemberId),
+        }
+    }
+
+    fn remove_member_and_rebalance(&self, group: &mut GroupMetadata, member_id: &str, reason: &str) {
+        self.timer.cancel(&DelayedOperation::MemberSession(group.group_id().to_string(), member_id.to_string()));
+        if let Some(mut member) = group.remove_member(member_id) {
+            member.complete_join(Err(GroupError::UnknownMemberId));
+            member.complete_sync(Err(GroupError::UnknownMemberId));
+        }
+        match group.state() {
+            GroupState::Stable | GroupState::CompletingRebalance => self.prepare_rebalance(group, reason),
+            GroupState::PreparingRebalance => self.try_complete_join(group),
+            GroupState::Empty | GroupState::Dead => {}
+        }
+    }
+
+    /// Give the member another session timeout before it's removed from the group
+    fn heartbeat_member(&self, group: &mut GroupMetadata, member_id: &str) {
+        let Some(member) = group.member_mut(member_id) else {
+            return;
+        };
+        member.extend_session();
+        let session_timeout = member.session_timeout();
+        // members waiting to rejoin aren't expired until the rebalance completes
+        if !member.is_awaiting_join() {
+            self.schedule_session_expiry(group.group_id(), member_id, session_timeout);
+        }
+    }
+
+    fn schedule_session_expiry(&self, group_id: &str, member_id: &str, session_timeout: Duration) {
+        let coordinator = self.this.clone();
+        let (group_id, member_id) = (group_id.to_string(), member_id.to_string());
+        let key = DelayedOperation::MemberSession(group_id.clone(), member_id.clone());
+        self.timer.schedule(key, session_timeout, move || {
+            if let Some(coordinator) = coordinator.upgrade() {
+                coordinator.expire_member(&group_id, &member_id);
+            }
+        });
+    }
+
+    /// Remove a member that's stopped heartbeating
+    fn expire_member(&self, group_id: &str, member_id: &str) {
+        let mut groups = self.groups.lock().unwrap();
+        let Some(group) = groups.get_mut(group_id) else {
+            return;
+        };
+        if group.remove_pending_member(member_id) {
+            info!("Pending member {member_id} in group {group_id} has been removed after session timeout expiration");
+            return;
+        }
+        // the member may have heartbeat just as its session was expiring
+        let expired = group.member(member_id)
+            .is_some_and(|member| !member.is_awaiting_join() && member.session_deadline() <= Instant::now());
+        if expired {
+            info!("Member {member_id} in group {group_id} has failed, removing it from the group");
+            let reason = format!("removing member {member_id} on heartbeat expiration");
+            self.remove_member_and_rebalance(group, member_id, &reason);
+        }
+    }
+
+    fn schedule_consumer_session_expiry(&self, group_id: &str, member_id: &str) {
+        let coordinator = self.this.clone();
+        let (group_id, member_id) = (group_id.to_string(), member_id.to_string());
+        let key = DelayedOperation::ConsumerMemberSession(group_id.clone(), member_id.clone());
+        self.timer.schedule(key.clone(), self.consumer_session_timeout, move || {
+            if let Some(coordinator) = coordinator.upgrade() {
+                let mut consumer_groups = coordinator.consumer_groups.lock().unwrap();
+                // the member may have heartbeat just as its session was expiring
+                if coordinator.timer.is_scheduled(&key) {
+                    return;
+                }
+                if let Some(group) = consumer_groups.get_mut(&group_id) {
+                    if group.remove_member(&member_id).is_some() {
+                        info!("Member {member_id} in consumer group {group_id} has failed, removing it from the group");
+                        group.bump_group_epoch();
+                        coordinator.timer.cancel(&DelayedOperation::ConsumerMemberRevocation(group_id.clone(), member_id.clone()));
+                    }
+                }
+            }
+        });
+    }
+
+    /// Remove the member if it's still revoking the partitions it was asked to in the epoch once its rebalance timeout has passed
+    fn schedule_revocation_timeout(&self, group_id: &str, member_id: &str, member_epoch: i32, rebalance_timeout: Duration) {
+        let coordinator = self.this.clone();
+        let (group_id, member_id) = (group_id.to_string(), member_id.to_string());
+        let key = DelayedOperation::ConsumerMemberRevocation(group_id.clone(), member_id.clone());
+        self.timer.schedule(key, rebalance_timeout, move || {
+            if let Some(coordinator) = coordinator.upgrade() {
+                let mut consumer_groups = coordinator.consumer_groups.lock().unwrap();
+                let Some(group) = consumer_groups.get_mut(&group_id) else {
+                    return;
+                };
+                let unrevoked = group.member(&member_id)
+                    .is_some_and(|member| member.state() == MemberState::UnrevokedPartitions && member.member_epoch() == member_epoch);
+                if unrevoked {
+                    info!("Member {member_id} in consumer group {group_id} failed to revoke partitions within its rebalance timeout, removing it from the group");
+                    group.remove_member(&member_id);
+                    group.bump_group_epoch();
+                    coordinator.timer.cancel(&DelayedOperation::ConsumerMemberSession(group_id.clone(), member_id.clone()));
+                }
+            }
+        });
+    }
+
+    fn cancel_consumer_member_timers(&self, group_id: &str, member_id: &str) {
+        self.timer.cancel(&DelayedOperation::ConsumerMemberSession(group_id.to_string(), member_id.to_string()));
+        self.timer.cancel(&DelayedOperation::ConsumerMemberRevocation(group_id.to_string(), member_id.to_string()));
+    }
+
+    /// Complete the group's rebalance once the delay has passed, even if some members haven't rejoined.
+    /// The delayed join waits in the rebalance purgatory, so scheduling it again only moves its deadline
+    fn schedule_complete_join(&self, group: &mut GroupMetadata, delay: Duration) {
+        let already_delayed = group.join_deadline().is_some();
+        group.set_join_deadline(Some(Instant::now() + delay));
+        if already_delayed {
+            return;
+        }
+
+        let coordinator = self.this.clone();
+        let purgatory = self.rebalance_purgatory.clone();
+        let (group_id, generation_id) = (group.group_id().to_string(), group.generation_id());
+        tokio::spawn(async move {
+            let keys = [group_id.clone()];
+            let try_complete = || coordinator.upgrade()
+                .map_or(Ok(()), |coordinator| coordinator.try_complete_delayed_join(&group_id, generation_id));
+            // the deadline may have moved while it was waiting, in which case it waits again
+            while let Err(deadline) = try_complete() {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                let completed = purgatory.try_complete_else_watch(&keys, timeout, || try_complete().ok()).await.is_some();
+                if completed || purgatory.is_shut_down() {
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Complete the rebalance of the generation if every member has rejoined or its deadline has passed,
+    /// otherwise returning the deadline it's still waiting until
+    fn try_complete_delayed_join(&self, group_id: &str, generation_id: i32) -> Result<(), Instant> {
+        let mut groups = self.groups.lock().unwrap();
+        let Some(group) = groups.get_mut(group_id)
+            .filter(|group| group.state() == GroupState::PreparingRebalance && group.generation_id() == generation_id) else {
+            return Ok(());
+        };
+        match group.join_deadline() {
+            Some(deadline) if deadline <= Instant::now() => self.complete_join(group),
+            Some(_) => self.try_complete_join(group),
+            None => {}
+        }
+        group.join_deadline().map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::open_broker;
+
+    const PROPERTIES: &str = "group.initial.rebalance.delay.ms=0\ngroup.min.session.timeout.ms=100\n";
+
+    fn join_params(member_id: &str, session_timeout_ms: i32) -> JoinGroupParams {
+        JoinGroupParams {
+            group_id: "group".to_string(),
+            member_id: member_id.to_string(),
+            group_instance_id: None,
+            client_id: "client".to_string(),
+            client_host: "/127.0.0.1".to_string(),
+            session_timeout_ms,
+            rebalance_timeout_ms: 5000,
+            protocol_type: "consumer".to_string(),
+            protocols: vec![JoinProtocol { name: "range".to_string(), metadata: member_id.as_bytes().to_vec() }],
+            require_known_member_id: false,
+            reason: None,
+        }
+    }
+
+    fn sync_params(joined: &JoinedGroup, assignments: Vec<(String, Vec<u8>)>) -> SyncGroupParams {
+        SyncGroupParams {
+            group_id: "group".to_string(),
+            generation_id: joined.generation_id,
+            member_id: joined.member_id.clone(),
+            group_instance_id: None,
+            protocol_type: Some("consumer".to_string()),
+            protocol_name: Some("range".to_string()),
+            assignments,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_join_sync_and_heartbeat() {
+        let (broker, _log_dir) = open_broker(PROPERTIES);
+        let coordinator = broker.group_coordinator();
+
+        let joined = coordinator.join_group(join_params("", 10_000)).await.unwrap();
+        assert_eq!(joined.generation_id, 1);
+        assert_eq!(joined.leader, joined.member_id);
+        assert_eq!(joined.protocol_name.as_deref(), Some("range"));
+        assert_eq!(joined.members.len(), 1);
+        assert_eq!(coordinator.describe_group("group").state, GroupState::CompletingRebalance);
+
+        let assignments = vec![(joined.member_id.clone(), b"assignment".to_vec())];
+        let synced = coordinator.sync_group(sync_params(&joined, assignments)).await.unwrap();
+        assert_eq!(synced.assignment, b"assignment");
+        assert_eq!(coordinator.describe_group("group").state, GroupState::Stable);
+
+        coordinator.heartbeat("group", &joined.member_id, None, joined.generation_id).unwrap();
+        let stale = coordinator.heartbeat("group", &joined.member_id, None, joined.generation_id - 1);
+        assert!(matches!(stale, Err(GroupError::IllegalGeneration)));
+        assert!(matches!(coordinator.heartbeat("group", "unknown", None, joined.generation_id), Err(GroupError::UnknownMemberId)));
+    }
+
+    #[tokio::test]
+    async fn test_rebalance_when_member_joins() {
+        let (broker, _log_dir) = open_broker(PROPERTIES);
+        let coordinator = broker.group_coordinator();
+        let first = coordinator.join_group(join_params("", 10_000)).await.unwrap();
+        coordinator.sync_group(sync_params(&first, vec![(first.member_id.clone(), b"everything".to_vec())])).await.unwrap();
+
+        // the second member's join only completes once the first has found out about the rebalance and rejoined
+        let (second, rejoined) = tokio::join!(
+            coordinator.join_group(join_params("", 10_000)),
+            async {
+                let heartbeat = coordinator.heartbeat("group", &first.member_id, None, first.generation_id);
+                assert!(matches!(heartbeat, Err(GroupError::RebalanceInProgress)));
+                coordinator.join_group(join_params(&first.member_id, 10_000)).await
+            },
+        );
+        let (second, rejoined) = (second.unwrap(), rejoined.unwrap());
+        assert_eq!((second.generation_id, rejoined.generation_id), (2, 2));
+        assert_eq!(rejoined.leader, first.member_id);
+        assert_eq!(rejoined.members.len(), 2);
+        assert!(second.members.is_empty());
+
+        let assignments = vec![(first.member_id.clone(), b"half".to_vec()), (second.member_id.clone(), b"other half".to_vec())];
+        let (follower, leader) = tokio::join!(
+            coordinator.sync_group(sync_params(&second, Vec::new())),
+            coordinator.sync_group(sync_params(&rejoined, assignments)),
+        );
+        assert_eq!(follower.unwrap().assignment, b"other half");
+        assert_eq!(leader.unwrap().assignment, b"half");
+        assert_eq!(coordinator.describe_group("group").state, GroupState::Stable);
+    }
+
+    #[tokio::test]
+    async fn test_session_timeout_expires_member() {
+        let (broker, _log_dir) = open_broker(PROPERTIES);
+        let coordinator = broker.group_coordinator();
+        let joined = coordinator.join_group(join_params("", 200)).await.unwrap();
+        coordinator.sync_group(sync_params(&joined, Vec::new())).await.unwrap();
+
+        // heartbeats keep the member in the group past its session timeout
+        for _ in 0..3 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            coordinator.heartbeat("group", &joined.member_id, None, joined.generation_id).unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let described = coordinator.describe_group("group");
+        assert_eq!(described.state, GroupState::Empty);
+        assert!(described.members.is_empty());
+        assert!(matches!(coordinator.heartbeat("group", &joined.member_id, None, joined.generation_id), Err(GroupError::UnknownMemberId)));
+    }
+}
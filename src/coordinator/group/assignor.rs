@@ -0,0 +1,171 @@
+use std::collections::{BTreeMap, BTreeSet};
+use uuid::Uuid;
+use crate::coordinator::group::consumer_group::Assignment;
+
+/// The assignors the coordinator runs to assign partitions to the members of a consumer group
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Assignor {
+    /// Spreads every subscribed partition evenly over the members, keeping partitions where they are when it can
+    Uniform,
+    /// Gives each member a contiguous range of each topic's partitions
+    Range,
+}
+
+impl Assignor {
+    pub fn from_name(name: &str) -> Option<Assignor> {
+        match name {
+            "uniform" => Some(Assignor::Uniform),
+            "range" => Some(Assignor::Range),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Assignor::Uniform => "uniform",
+            Assignor::Range => "range",
+        }
+    }
+
+    /// Assign the partitions of the topics to the members subscribed to them.
+    /// The subscriptions are the ids of the topics each member is subscribed to, and the topics are their partition counts.
+    /// The current assignment is the previous target assignment, which the uniform assignor tries to stick to
+    pub fn assign(
+        &self,
+        subscriptions: &BTreeMap<String, BTreeSet<Uuid>>,
+        topics: &BTreeMap<Uuid, i32>,
+        current: &BTreeMap<String, Assignment>,
+    ) -> BTreeMap<String, Assignment> {
+        let mut assignment: BTreeMap<String, Assignment> = subscriptions.keys()
+            .map(|member_id| (member_id.clone(), Assignment::new()))
+            .collect();
+        match self {
+            Assignor::Uniform => assign_uniform(subscriptions, topics, current, &mut assignment),
+            Assignor::Range => assign_range(subscriptions, topics, &mut assignment),
+        }
+        assignment
+    }
+}
+
+fn assign_range(subscriptions: &BTreeMap<String, BTreeSet<Uuid>>, topics: &BTreeMap<Uuid, i32>, assignment: &mut BTreeMap<String, Assignment>) {
+    for (topic_id, num_partitions) in topics {
+        let members: Vec<&String> = subscriptions.iter()
+            .filter(|(_, subscribed)| subscribed.contains(topic_id))
+            .map(|(member_id, _)| member_id)
+            .collect();
+        if members.is_empty() {
+            continue;
+        }
+        // the first members get one more partition when they can't be shared evenly
+        let (quota, extra) = (*num_partitions as usize / members.len(), *num_partitions as usize % members.len());
+        let mut start = 0;
+        for (i, member_id) in members.into_iter().enumerate() {
+            let count = quota + usize::from(i < extra);
+            if count > 0 {
+                let partitions = (start..start + count).map(|partition| partition as i32).collect();
+                assignment.entry(member_id.clone()).or_default().insert(*topic_id, partitions);
+            }
+            start += count;
+        }
+    }
+}
+
+fn assign_uniform(
+    subscriptions: &BTreeMap<String, BTreeSet<Uuid>>,
+    topics: &BTreeMap<Uuid, i32>,
+    current: &BTreeMap<String, Assignment>,
+    assignment: &mut BTreeMap<String, Assignment>,
+) {
+    let partitions: BTreeSet<(Uuid, i32)> = topics.iter()
+        .filter(|(topic_id, _)| subscriptions.values().any(|subscribed| subscribed.contains(*topic_id)))
+        .flat_map(|(topic_id, num_partitions)| (0..*num_partitions).map(|partition| (*topic_id, partition)))
+        .collect();
+    let subscribed_members = subscriptions.values().filter(|subscribed| !subscribed.is_empty()).count();
+    if subscribed_members == 0 {
+        return;
+    }
+    let min_quota = partitions.len() / subscribed_members;
+    let mut extra = partitions.len() % subscribed_members;
+
+    // members keep the partitions they already had, up to their share
+    let mut unassigned = partitions.clone();
+    let mut counts: BTreeMap<&String, usize> = BTreeMap::new();
+    for (member_id, subscribed) in subscriptions {
+        let kept: Vec<(Uuid, i32)> = current.get(member_id)
+            .into_iter()
+            .flatten()
+            .filter(|(topic_id, _)| subscribed.contains(*topic_id))
+            .flat_map(|(topic_id, partitions)| partitions.iter().map(|partition| (*topic_id, *partition)))
+            .filter(|topic_partition| unassigned.contains(topic_partition))
+            .collect();
+        let quota = match kept.len() > min_quota && extra > 0 {
+            true => {
+                extra -= 1;
+                min_quota + 1
+            }
+            false => min_quota,
+        };
+        for (topic_id, partition) in kept.into_iter().take(quota) {
+            unassigned.remove(&(topic_id, partition));
+            assignment.entry(member_id.clone()).or_default().entry(topic_id).or_default().insert(partition);
+            *counts.entry(member_id).or_default() += 1;
+        }
+    }
+
+    // the rest go to whichever subscribed member has the fewest partitions
+    for (topic_id, partition) in unassigned {
+        let member_id = subscriptions.iter()
+            .filter(|(_, subscribed)| subscribed.contains(&topic_id))
+            .map(|(member_id, _)| member_id)
+            .min_by_key(|member_id| counts.get(member_id).copied().unwrap_or_default());
+        if let Some(member_id) = member_id {
+            assignment.entry(member_id.clone()).or_default().entry(topic_id).or_default().insert(partition);
+            *counts.entry(member_id).or_default() += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn partitions(assignment: &Assignment) -> usize {
+        assignment.values().map(BTreeSet::len).sum()
+    }
+
+    #[test]
+    fn test_range() {
+        let (foo, bar) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let subscriptions = BTreeMap::from([
+            ("a".to_string(), BTreeSet::from([foo, bar])),
+            ("b".to_string(), BTreeSet::from([foo])),
+        ]);
+        let topics = BTreeMap::from([(foo, 3), (bar, 2)]);
+        let assignment = Assignor::Range.assign(&subscriptions, &topics, &BTreeMap::new());
+        assert_eq!(assignment["a"], BTreeMap::from([(foo, BTreeSet::from([0, 1])), (bar, BTreeSet::from([0, 1]))]));
+        assert_eq!(assignment["b"], BTreeMap::from([(foo, BTreeSet::from([2]))]));
+    }
+
+    #[test]
+    fn test_uniform_is_balanced_and_sticky() {
+        let foo = Uuid::from_u128(1);
+        let topics = BTreeMap::from([(foo, 6)]);
+        let subscriptions = BTreeMap::from([
+            ("a".to_string(), BTreeSet::from([foo])),
+            ("b".to_string(), BTreeSet::from([foo])),
+        ]);
+        let first = Assignor::Uniform.assign(&subscriptions, &topics, &BTreeMap::new());
+        assert_eq!(partitions(&first["a"]), 3);
+        assert_eq!(partitions(&first["b"]), 3);
+
+        // a new member only takes partitions from the existing members, which keep the rest
+        let mut subscriptions = subscriptions;
+        subscriptions.insert("c".to_string(), BTreeSet::from([foo]));
+        let second = Assignor::Uniform.assign(&subscriptions, &topics, &first);
+        for member_id in ["a", "b"] {
+            assert_eq!(partitions(&second[member_id]), 2);
+            assert!(second[member_id][&foo].is_subset(&first[member_id][&foo]));
+        }
+        assert_eq!(partitions(&second["c"]), 2);
+    }
+}
@@ -0,0 +1,527 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use regex::Regex;
+use uuid::Uuid;
+use crate::broker::topics::is_internal_topic;
+use crate::coordinator::group::assignor::Assignor;
+use crate::coordinator::group::ConsumerGroupHeartbeatParams;
+
+/// The partitions of each topic, by topic id
+pub type Assignment = BTreeMap<Uuid, BTreeSet<i32>>;
+
+/// The member epoch a member leaves the group with
+pub const LEAVE_GROUP_MEMBER_EPOCH: i32 = -1;
+/// The member epoch a static member leaves the group with when it's coming back,
+/// so it keeps its assignment until its session times out
+pub const LEAVE_GROUP_STATIC_MEMBER_EPOCH: i32 = -2;
+
+/// A topic the members of a consumer group can subscribe to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicInfo {
+    pub topic_id: Uuid,
+    pub num_partitions: i32,
+}
+
+/// The states of a group using the consumer rebalance protocol from KIP-848
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConsumerGroupState {
+    Empty,
+    /// The members or their subscriptions have changed, so the target assignment has to be recomputed
+    Assigning,
+    /// Some members haven't reached their target assignment yet
+    Reconciling,
+    Stable,
+}
+
+impl ConsumerGroupState {
+    /// The name of the state, as it's described to clients
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConsumerGroupState::Empty => "Empty",
+            ConsumerGroupState::Assigning => "Assigning",
+            ConsumerGroupState::Reconciling => "Reconciling",
+            ConsumerGroupState::Stable => "Stable",
+        }
+    }
+}
+
+/// Where a member is in moving from its current assignment to its target assignment
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MemberState {
+    /// The member has its target assignment for its epoch
+    Stable,
+    /// The member has to revoke partitions before it can move to the next epoch
+    UnrevokedPartitions,
+    /// The member is waiting for other members to revoke partitions it's been assigned
+    UnreleasedPartitions,
+}
+
+/// A member of a group using the consumer rebalance protocol
+#[derive(Debug, Clone)]
+pub struct ConsumerGroupMember {
+    member_id: String,
+    group_instance_id: Option<String>,
+    rack_id: Option<String>,
+    client_id: String,
+    client_host: String,
+    rebalance_timeout_ms: i32,
+    subscribed_topic_names: Vec<String>,
+    subscribed_topic_regex: Option<String>,
+    server_assignor: Option<String>,
+    state: MemberState,
+    member_epoch: i32,
+    /// The epoch before the member's last epoch bump, which is still accepted in case the member missed the bump
+    previous_member_epoch: i32,
+    assigned_partitions: Assignment,
+    /// Partitions the member still owns, but has been asked to revoke
+    partitions_pending_revocation: Assignment,
+}
+
+impl ConsumerGroupMember {
+    pub fn new(member_id: String, heartbeat: &ConsumerGroupHeartbeatParams) -> ConsumerGroupMember {
+        let mut member = ConsumerGroupMember {
+            member_id,
+            group_instance_id: heartbeat.group_instance_id.clone(),
+            rack_id: None,
+            client_id: heartbeat.client_id.clone(),
+            client_host: heartbeat.client_host.clone(),
+            rebalance_timeout_ms: heartbeat.rebalance_timeout_ms,
+            subscribed_topic_names: Vec::new(),
+            subscribed_topic_regex: None,
+            server_assignor: None,
+            state: MemberState::Stable,
+            member_epoch: 0,
+            previous_member_epoch: 0,
+            assigned_partitions: Assignment::new(),
+            partitions_pending_revocation: Assignment::new(),
+        };
+        member.update(heartbeat);
+        member
+    }
+
+    /// Update the member with the fields the heartbeat set, which are only sent when they change.
+    /// Returns whether the change means the group needs a new target assignment
+    pub fn update(&mut self, heartbeat: &ConsumerGroupHeartbeatParams) -> bool {
+        let mut changed = false;
+        if let Some(subscribed_topic_names) = &heartbeat.subscribed_topic_names {
+            changed |= self.subscribed_topic_names != *subscribed_topic_names;
+            self.subscribed_topic_names = subscribed_topic_names.clone();
+        }
+        if let Some(subscribed_topic_regex) = &heartbeat.subscribed_topic_regex {
+            // an empty regex unsubscribes the member from its regex
+            let subscribed_topic_regex = Some(subscribed_topic_regex.clone()).filter(|regex| !regex.is_empty());
+            changed |= self.subscribed_topic_regex != subscribed_topic_regex;
+            self.subscribed_topic_regex = subscribed_topic_regex;
+        }
+        if let Some(server_assignor) = &heartbeat.server_assignor {
+            changed |= self.server_assignor.as_ref() != Some(server_assignor);
+            self.server_assignor = Some(server_assignor.clone());
+        }
+        if heartbeat.rack_id.is_some() {
+            changed |= self.rack_id != heartbeat.rack_id;
+            self.rack_id = heartbeat.rack_id.clone();
+        }
+        if heartbeat.rebalance_timeout_ms != -1 {
+            self.rebalance_timeout_ms = heartbeat.rebalance_timeout_ms;
+        }
+        self.client_id = heartbeat.client_id.clone();
+        self.client_host = heartbeat.client_host.clone();
+        changed
+    }
+
+    pub fn member_id(&self) -> &str {
+        &self.member_id
+    }
+
+    pub fn group_instance_id(&self) -> Option<&str> {
+        self.group_instance_id.as_deref()
+    }
+
+    pub fn rack_id(&self) -> Option<&str> {
+        self.rack_id.as_deref()
+    }
+
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    pub fn client_host(&self) -> &str {
+        &self.client_host
+    }
+
+    pub fn rebalance_timeout_ms(&self) -> i32 {
+        self.rebalance_timeout_ms
+    }
+
+    pub fn subscribed_topic_names(&self) -> &[String] {
+        &self.subscribed_topic_names
+    }
+
+    pub fn subscribed_topic_regex(&self) -> Option<&str> {
+        self.subscribed_topic_regex.as_deref()
+    }
+
+    pub fn state(&self) -> MemberState {
+        self.state
+    }
+
+    pub fn member_epoch(&self) -> i32 {
+        self.member_epoch
+    }
+
+    pub fn previous_member_epoch(&self) -> i32 {
+        self.previous_member_epoch
+    }
+
+    pub fn assigned_partitions(&self) -> &Assignment {
+        &self.assigned_partitions
+    }
+
+    /// A static member leaving temporarily keeps its assignment for the instance that replaces it
+    pub fn leave_temporarily(&mut self) {
+        self.previous_member_epoch = self.member_epoch;
+        self.member_epoch = LEAVE_GROUP_STATIC_MEMBER_EPOCH;
+    }
+
+    /// Whether the member still owns the partition, including while it's revoking it
+    fn owns(&self, topic_id: &Uuid, partition: i32) -> bool {
+        [&self.assigned_partitions, &self.partitions_pending_revocation]
+            .iter()
+            .any(|assignment| assignment.get(topic_id).is_some_and(|partitions| partitions.contains(&partition)))
+    }
+
+    /// Whether the member matches the topic, by name or by its regex, which never matches internal topics
+    fn is_subscribed_to(&self, topic: &str) -> bool {
+        self.subscribed_topic_names.iter().any(|name| name == topic)
+            || !is_internal_topic(topic) && self.subscribed_topic_regex.as_deref()
+                .and_then(|regex| Regex::new(&format!("^(?:{regex})$")).ok())
+                .is_some_and(|regex| regex.is_match(topic))
+    }
+}
+
+/// A group of consumers using the consumer rebalance protocol from KIP-848, where the coordinator assigns the partitions.
+/// Every change to the members bumps the group epoch, the coordinator computes a target assignment for that epoch,
+/// and each member is moved towards its target on its heartbeats, revoking partitions before they're given to another member
+#[derive(Debug)]
+pub struct ConsumerGroup {
+    group_id: String,
+    group_epoch: i32,
+    /// The group epoch the target assignment was computed for
+    assignment_epoch: i32,
+    members: BTreeMap<String, ConsumerGroupMember>,
+    /// The member id of each static member, by its group instance id
+    static_members: HashMap<String, String>,
+    target_assignment: BTreeMap<String, Assignment>,
+    /// The topics the members were subscribed to when the group epoch was last bumped,
+    /// so topics that are created or get more partitions cause a new assignment
+    subscribed_topics: BTreeMap<String, TopicInfo>,
+    /// The assignor that computed the target assignment
+    assignor: Option<Assignor>,
+}
+
+impl ConsumerGroup {
+    pub fn new(group_id: String) -> ConsumerGroup {
+        ConsumerGroup {
+            group_id,
+            group_epoch: 0,
+            assignment_epoch: 0,
+            members: BTreeMap::new(),
+            static_members: HashMap::new(),
+            target_assignment: BTreeMap::new(),
+            subscribed_topics: BTreeMap::new(),
+            assignor: None,
+        }
+    }
+
+    pub fn group_id(&self) -> &str {
+        &self.group_id
+    }
+
+    pub fn group_epoch(&self) -> i32 {
+        self.group_epoch
+    }
+
+    pub fn assignment_epoch(&self) -> i32 {
+        self.assignment_epoch
+    }
+
+    pub fn assignor(&self) -> Option<Assignor> {
+        self.assignor
+    }
+
+    pub fn state(&self) -> ConsumerGroupState {
+        if self.members.is_empty() {
+            ConsumerGroupState::Empty
+        } else if self.group_epoch > self.assignment_epoch {
+            ConsumerGroupState::Assigning
+        } else if self.members.values().any(|member| member.state != MemberState::Stable || member.member_epoch != self.assignment_epoch) {
+            ConsumerGroupState::Reconciling
+        } else {
+            ConsumerGroupState::Stable
+        }
+    }
+
+    pub fn members(&self) -> impl Iterator<Item = &ConsumerGroupMember> {
+        self.members.values()
+    }
+
+    pub fn member(&self, member_id: &str) -> Option<&ConsumerGroupMember> {
+        self.members.get(member_id)
+    }
+
+    pub fn member_mut(&mut self, member_id: &str) -> Option<&mut ConsumerGroupMember> {
+        self.members.get_mut(member_id)
+    }
+
+    pub fn size(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn static_member_id(&self, group_instance_id: &str) -> Option<&str> {
+        self.static_members.get(group_instance_id).map(String::as_str)
+    }
+
+    pub fn target_assignment(&self, member_id: &str) -> Option<&Assignment> {
+        self.target_assignment.get(member_id)
+    }
+
+    /// The names of the topics the members are subscribed to that exist
+    pub fn subscribed_topic_names(&self) -> impl Iterator<Item = &String> {
+        self.subscribed_topics.keys()
+    }
+
+    /// Start a new group epoch, which needs a new target assignment
+    pub fn bump_group_epoch(&mut self) {
+        self.group_epoch += 1;
+    }
+
+    pub fn add_member(&mut self, member: ConsumerGroupMember) {
+        if let Some(group_instance_id) = member.group_instance_id() {
+            self.static_members.insert(group_instance_id.to_string(), member.member_id().to_string());
+        }
+        self.members.insert(member.member_id().to_string(), member);
+    }
+
+    pub fn remove_member(&mut self, member_id: &str) -> Option<ConsumerGroupMember> {
+        let member = self.members.remove(member_id)?;
+        if let Some(group_instance_id) = member.group_instance_id() {
+            if self.static_member_id(group_instance_id) == Some(member_id) {
+                self.static_members.remove(group_instance_id);
+            }
+        }
+        self.target_assignment.remove(member_id);
+        Some(member)
+    }
+
+    /// Replace a static member that left temporarily with the new instance, which takes over its assignment
+    pub fn replace_static_member(&mut self, old_member_id: &str, new_member_id: String) {
+        let Some(mut member) = self.members.remove(old_member_id) else {
+            return;
+        };
+        member.member_id = new_member_id.clone();
+        member.member_epoch = member.previous_member_epoch;
+        if let Some(group_instance_id) = member.group_instance_id() {
+            self.static_members.insert(group_instance_id.to_string(), new_member_id.clone());
+        }
+        if let Some(target) = self.target_assignment.remove(old_member_id) {
+            self.target_assignment.insert(new_member_id.clone(), target);
+        }
+        self.members.insert(new_member_id, member);
+    }
+
+    /// Resolve the members' subscriptions against the topics that exist,
+    /// returning whether the topics or their partitions have changed, which needs a new group epoch
+    pub fn update_subscribed_topics(&mut self, topics: &BTreeMap<String, TopicInfo>) -> bool {
+        let subscribed_topics: BTreeMap<String, TopicInfo> = topics.iter()
+            .filter(|(name, _)| self.members.values().any(|member| member.is_subscribed_to(name)))
+            .map(|(name, topic)| (name.clone(), topic.clone()))
+            .collect();
+        let changed = subscribed_topics != self.subscribed_topics;
+        self.subscribed_topics = subscribed_topics;
+        changed
+    }
+
+    /// Compute the target assignment for the group epoch, if it hasn't been already.
+    /// The assignor is the one most of the members prefer, or the default one
+    pub fn update_target_assignment(&mut self, default_assignor: Assignor) {
+        if self.assignment_epoch >= self.group_epoch {
+            return;
+        }
+        let mut votes: BTreeMap<&str, usize> = BTreeMap::new();
+        for member in self.members.values() {
+            if let Some(server_assignor) = &member.server_assignor {
+                *votes.entry(server_assignor.as_str()).or_default() += 1;
+            }
+        }
+        let assignor = votes.into_iter()
+            .max_by_key(|(_, count)| *count)
+            .and_then(|(name, _)| Assignor::from_name(name))
+            .unwrap_or(default_assignor);
+
+        let subscriptions = self.members.values()
+            .map(|member| {
+                let topic_ids = self.subscribed_topics.iter()
+                    .filter(|(name, _)| member.is_subscribed_to(name))
+                    .map(|(_, topic)| topic.topic_id)
+                    .collect();
+                (member.member_id.clone(), topic_ids)
+            })
+            .collect();
+        let topics = self.subscribed_topics.values()
+            .map(|topic| (topic.topic_id, topic.num_partitions))
+            .collect();
+        self.target_assignment = assignor.assign(&subscriptions, &topics, &self.target_assignment);
+        self.assignment_epoch = self.group_epoch;
+        self.assignor = Some(assignor);
+    }
+
+    /// Move the member towards its target assignment, given the partitions it says it owns, if it sent them.
+    /// A member has to revoke the partitions it's losing before it can move to the new epoch,
+    /// and it's only given partitions once the members that had them have revoked them
+    pub fn reconcile(&mut self, member_id: &str, owned_partitions: Option<&Assignment>) {
+        let Some(member) = self.members.get(member_id) else {
+            return;
+        };
+        let next_epoch = match member.state {
+            MemberState::Stable if member.member_epoch == self.assignment_epoch => return,
+            MemberState::UnrevokedPartitions => {
+                // without the partitions it owns, we can't tell whether the member has revoked them yet
+                let still_owned = owned_partitions.map_or(true, |owned| intersects(owned, &member.partitions_pending_revocation));
+                if still_owned {
+                    return;
+                }
+                (member.member_epoch + 1).min(self.assignment_epoch)
+            }
+            MemberState::Stable | MemberState::UnreleasedPartitions => member.member_epoch,
+        };
+        let target = self.target_assignment.get(member_id).cloned().unwrap_or_default();
+
+        let mut kept = Assignment::new();
+        let mut revoking = Assignment::new();
+        let mut newly_assigned = Assignment::new();
+        let mut has_unreleased = false;
+        for (topic_id, partitions) in &member.assigned_partitions {
+            for partition in partitions {
+                let destination = match target.get(topic_id).is_some_and(|target| target.contains(partition)) {
+                    true => &mut kept,
+                    false => &mut revoking,
+                };
+                destination.entry(*topic_id).or_default().insert(*partition);
+            }
+        }
+        for (topic_id, partitions) in &target {
+            for partition in partitions {
+                if kept.get(topic_id).is_some_and(|kept| kept.contains(partition)) {
+                    continue;
+                }
+                let owned_by_another = self.members.values()
+                    .any(|other| other.member_id != member_id && other.owns(topic_id, *partition));
+                match owned_by_another {
+                    true => has_unreleased = true,
+                    false => {
+                        newly_assigned.entry(*topic_id).or_default().insert(*partition);
+                    }
+                }
+            }
+        }
+
+        let assignment_epoch = self.assignment_epoch;
+        let Some(member) = self.members.get_mut(member_id) else {
+            return;
+        };
+        let (state, epoch, assigned) = if !revoking.is_empty() {
+            (MemberState::UnrevokedPartitions, next_epoch, kept)
+        } else {
+            let mut assigned = kept;
+            for (topic_id, partitions) in newly_assigned {
+                assigned.entry(topic_id).or_default().extend(partitions);
+            }
+            let state = match has_unreleased {
+                true => MemberState::UnreleasedPartitions,
+                false => MemberState::Stable,
+            };
+            (state, assignment_epoch, assigned)
+        };
+        if epoch != member.member_epoch {
+            member.previous_member_epoch = member.member_epoch;
+        }
+        member.state = state;
+        member.member_epoch = epoch;
+        member.assigned_partitions = assigned;
+        member.partitions_pending_revocation = revoking;
+    }
+}
+
+fn intersects(a: &Assignment, b: &Assignment) -> bool {
+    a.iter().any(|(topic_id, partitions)| b.get(topic_id).is_some_and(|other| !partitions.is_disjoint(other)))
+}
+
+/// Whether every partition of the first assignment is also in the second
+pub fn is_subset(a: &Assignment, b: &Assignment) -> bool {
+    a.iter().all(|(topic_id, partitions)| partitions.is_empty() || b.get(topic_id).is_some_and(|other| partitions.is_subset(other)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heartbeat(topics: &[&str]) -> ConsumerGroupHeartbeatParams {
+        ConsumerGroupHeartbeatParams {
+            group_id: "group".to_string(),
+            member_id: String::new(),
+            member_epoch: 0,
+            group_instance_id: None,
+            rack_id: None,
+            rebalance_timeout_ms: 30000,
+            subscribed_topic_names: Some(topics.iter().map(|topic| topic.to_string()).collect()),
+            subscribed_topic_regex: None,
+            server_assignor: Some("range".to_string()),
+            owned_partitions: Some(Assignment::new()),
+            client_id: "client".to_string(),
+            client_host: "/127.0.0.1".to_string(),
+        }
+    }
+
+    fn partitions(topic_id: Uuid, partitions: &[i32]) -> Assignment {
+        BTreeMap::from([(topic_id, partitions.iter().copied().collect())])
+    }
+
+    #[test]
+    fn test_reconciliation() {
+        let foo = Uuid::from_u128(1);
+        let topics = BTreeMap::from([("foo".to_string(), TopicInfo { topic_id: foo, num_partitions: 2 })]);
+        let mut group = ConsumerGroup::new("group".to_string());
+        group.add_member(ConsumerGroupMember::new("a".to_string(), &heartbeat(&["foo"])));
+        assert!(group.update_subscribed_topics(&topics));
+        group.bump_group_epoch();
+        group.update_target_assignment(Assignor::Uniform);
+        group.reconcile("a", None);
+        let a = group.member("a").unwrap();
+        assert_eq!((a.state(), a.member_epoch()), (MemberState::Stable, 1));
+        assert_eq!(a.assigned_partitions(), &partitions(foo, &[0, 1]));
+        assert_eq!(group.state(), ConsumerGroupState::Stable);
+
+        // b has to wait for a to revoke the partition it's being given
+        group.add_member(ConsumerGroupMember::new("b".to_string(), &heartbeat(&["foo"])));
+        group.bump_group_epoch();
+        group.update_target_assignment(Assignor::Uniform);
+        group.reconcile("b", Some(&Assignment::new()));
+        let b = group.member("b").unwrap();
+        assert_eq!((b.state(), b.member_epoch()), (MemberState::UnreleasedPartitions, 2));
+        assert!(b.assigned_partitions().is_empty());
+
+        group.reconcile("a", Some(&partitions(foo, &[0, 1])));
+        let a = group.member("a").unwrap();
+        assert_eq!((a.state(), a.member_epoch()), (MemberState::UnrevokedPartitions, 1));
+        assert_eq!(a.assigned_partitions(), &partitions(foo, &[0]));
+        assert_eq!(group.state(), ConsumerGroupState::Reconciling);
+
+        // once a has revoked the partition it moves to the new epoch, and b can have it
+        group.reconcile("a", Some(&partitions(foo, &[0])));
+        assert_eq!(group.member("a").unwrap().member_epoch(), 2);
+        group.reconcile("b", Some(&Assignment::new()));
+        let b = group.member("b").unwrap();
+        assert_eq!((b.state(), b.member_epoch()), (MemberState::Stable, 2));
+        assert_eq!(b.assigned_partitions(), &partitions(foo, &[1]));
+        assert_eq!(group.state(), ConsumerGroupState::Stable);
+    }
+}
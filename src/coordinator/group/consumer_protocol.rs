@@ -0,0 +1,43 @@
+/// The protocol type of groups of consumers, whose member metadata is a subscription to topics
+pub const CONSUMER_PROTOCOL_TYPE: &str = "consumer";
+
+/// The topics a consumer is subscribed to, from the start of its metadata for the consumer protocol,
+/// which every version of the subscription shares. Returns None if the metadata isn't a subscription
+pub fn subscribed_topics(mut metadata: &[u8]) -> Option<Vec<String>> {
+    let _version = i16::from_be_bytes(take(&mut metadata, 2)?.try_into().ok()?);
+    let num_topics = i32::from_be_bytes(take(&mut metadata, 4)?.try_into().ok()?);
+    (0..num_topics.max(0))
+        .map(|_| {
+            let length = i16::from_be_bytes(take(&mut metadata, 2)?.try_into().ok()?);
+            let topic = take(&mut metadata, usize::try_from(length).ok()?)?;
+            String::from_utf8(topic.to_vec()).ok()
+        })
+        .collect()
+}
+
+fn take<'a>(bytes: &mut &'a [u8], length: usize) -> Option<&'a [u8]> {
+    if bytes.len() < length {
+        return None;
+    }
+    let (taken, rest) = bytes.split_at(length);
+    *bytes = rest;
+    Some(taken)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscribed_topics() {
+        // a v1 subscription to two topics, with no user data and no owned partitions
+        let mut metadata = vec![0, 1, 0, 0, 0, 2, 0, 3];
+        metadata.extend(b"foo");
+        metadata.extend([0, 3]);
+        metadata.extend(b"bar");
+        metadata.extend([255, 255, 255, 255, 0, 0, 0, 0]);
+        assert_eq!(subscribed_topics(&metadata), Some(vec!["foo".to_string(), "bar".to_string()]));
+
+        assert_eq!(subscribed_topics(&[0, 1, 0, 0, 0, 1, 0, 3]), None);
+    }
+}
@@ -0,0 +1,479 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, Instant};
+use crate::coordinator::group::GroupError;
+use crate::coordinator::group::consumer_protocol::{subscribed_topics, CONSUMER_PROTOCOL_TYPE};
+use crate::coordinator::group::member::{JoinProtocol, MemberMetadata};
+use crate::coordinator::group::offsets::{GroupMetadataValue, OffsetAndMetadata};
+use crate::storage::topic_partition::TopicPartition;
+
+/// The states of a group using the classic rebalance protocol
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GroupState {
+    /// The group has no members, but may still have committed offsets
+    Empty,
+    /// The group is waiting for its members to rejoin
+    PreparingRebalance,
+    /// Every member has rejoined, and the group is waiting for the leader's assignment
+    CompletingRebalance,
+    /// Every member has been sent its assignment
+    Stable,
+    /// The group has been removed, and is waiting for the requests still using it to finish
+    Dead,
+}
+
+impl GroupState {
+    /// The name of the state, as it's described to clients
+    pub fn name(&self) -> &'static str {
+        match self {
+            GroupState::Empty => "Empty",
+            GroupState::PreparingRebalance => "PreparingRebalance",
+            GroupState::CompletingRebalance => "CompletingRebalance",
+            GroupState::Stable => "Stable",
+            GroupState::Dead => "Dead",
+        }
+    }
+
+    /// The states a group can be in immediately before moving to this state
+    fn valid_previous_states(&self) -> &'static [GroupState] {
+        match self {
+            GroupState::Empty => &[GroupState::PreparingRebalance],
+            GroupState::PreparingRebalance => &[GroupState::Stable, GroupState::CompletingRebalance, GroupState::Empty],
+            GroupState::CompletingRebalance => &[GroupState::PreparingRebalance],
+            GroupState::Stable => &[GroupState::CompletingRebalance],
+            GroupState::Dead => &[
+                GroupState::Stable,
+                GroupState::PreparingRebalance,
+                GroupState::CompletingRebalance,
+                GroupState::Empty,
+                GroupState::Dead,
+            ],
+        }
+    }
+}
+
+/// A group of members using the classic rebalance protocol, where the members join the group,
+/// then the leader assigns every member its share of the work and syncs the assignments through the coordinator
+#[derive(Debug)]
+pub struct GroupMetadata {
+    group_id: String,
+    state: GroupState,
+    generation_id: i32,
+    protocol_type: Option<String>,
+    /// The protocol chosen for the current generation
+    protocol_name: Option<String>,
+    leader_id: Option<String>,
+    members: BTreeMap<String, MemberMetadata>,
+    /// The member id of each static member, by the group instance id it joined with
+    static_members: HashMap<String, String>,
+    /// Members that have been given a member id, but haven't joined with it yet
+    pending_members: HashSet<String>,
+    /// While the first rebalance of a new group is delayed waiting for more members, the latest it can be delayed until
+    initial_rebalance_deadline: Option<Instant>,
+    /// While the group is preparing to rebalance, when the rebalance completes even if some members haven't rejoined
+    join_deadline: Option<Instant>,
+    /// The latest offset the group has committed for each partition
+    offsets: BTreeMap<TopicPartition, OffsetAndMetadata>,
+    /// Offsets committed as part of transactions, by the producer id of the transaction.
+    /// They aren't committed until the producer commits its transaction
+    pending_transactional_offsets: HashMap<i64, BTreeMap<TopicPartition, OffsetAndMetadata>>,
+}
+
+impl GroupMetadata {
+    pub fn new(group_id: String) -> GroupMetadata {
+        GroupMetadata {
+            group_id,
+            state: GroupState::Empty,
+            generation_id: 0,
+            protocol_type: None,
+            protocol_name: None,
+            leader_id: None,
+            members: BTreeMap::new(),
+            static_members: HashMap::new(),
+            pending_members: HashSet::new(),
+            initial_rebalance_deadline: None,
+            join_deadline: None,
+            offsets: BTreeMap::new(),
+            pending_transactional_offsets: HashMap::new(),
+        }
+    }
+
+    /// The group as it was when it was stored in the offsets topic, which is stable if it had any members
+    pub fn load(group_id: String, value: GroupMetadataValue) -> GroupMetadata {
+        let mut group = GroupMetadata::new(group_id);
+        group.generation_id = value.generation;
+        group.protocol_type = Some(value.protocol_type).filter(|protocol_type| !protocol_type.is_empty());
+        group.protocol_name = value.protocol;
+        for member in value.members {
+            let member = MemberMetadata::load(
+                member,
+                group.protocol_type.clone().unwrap_or_default(),
+                group.protocol_name.clone().unwrap_or_default(),
+            );
+            group.add_member(member);
+        }
+        if let Some(leader) = value.leader.filter(|leader| group.members.contains_key(leader)) {
+            group.leader_id = Some(leader);
+        }
+        if !group.members.is_empty() {
+            group.state = GroupState::Stable;
+        }
+        group
+    }
+
+    /// The current generation of the group, as it's stored in the offsets topic
+    pub fn to_value(&self, current_state_timestamp: i64) -> GroupMetadataValue {
+        GroupMetadataValue {
+            protocol_type: self.protocol_type.clone().unwrap_or_default(),
+            generation: self.generation_id,
+            protocol: self.protocol_name.clone(),
+            leader: self.leader_id.clone(),
+            current_state_timestamp,
+            members: self.members.values()
+                .map(|member| member.to_value(self.protocol_name.as_deref()))
+                .collect(),
+        }
+    }
+
+    pub fn group_id(&self) -> &str {
+        &self.group_id
+    }
+
+    pub fn state(&self) -> GroupState {
+        self.state
+    }
+
+    /// Move the group to a new state, which has to follow on from its current one
+    pub fn transition_to(&mut self, state: GroupState) {
+        assert!(
+            state.valid_previous_states().contains(&self.state),
+            "Group {} should be in one of {:?} before moving to {state:?}, but is {:?}",
+            self.group_id, state.valid_previous_states(), self.state,
+        );
+        self.state = state;
+    }
+
+    pub fn generation_id(&self) -> i32 {
+        self.generation_id
+    }
+
+    pub fn protocol_type(&self) -> Option<&str> {
+        self.protocol_type.as_deref()
+    }
+
+    pub fn protocol_name(&self) -> Option<&str> {
+        self.protocol_name.as_deref()
+    }
+
+    pub fn leader_id(&self) -> Option<&str> {
+        self.leader_id.as_deref()
+    }
+
+    pub fn is_leader(&self, member_id: &str) -> bool {
+        self.leader_id.as_deref() == Some(member_id)
+    }
+
+    pub fn members(&self) -> impl Iterator<Item = &MemberMetadata> {
+        self.members.values()
+    }
+
+    pub fn member_ids(&self) -> Vec<String> {
+        self.members.keys().cloned().collect()
+    }
+
+    pub fn member(&self, member_id: &str) -> Option<&MemberMetadata> {
+        self.members.get(member_id)
+    }
+
+    pub fn member_mut(&mut self, member_id: &str) -> Option<&mut MemberMetadata> {
+        self.members.get_mut(member_id)
+    }
+
+    /// The number of members, including the ones that haven't joined with their member id yet
+    pub fn size(&self) -> usize {
+        self.members.len() + self.pending_members.len()
+    }
+
+    pub fn add_member(&mut self, member: MemberMetadata) {
+        if self.members.is_empty() {
+            self.protocol_type = Some(member.protocol_type().to_string());
+        }
+        if self.leader_id.is_none() {
+            self.leader_id = Some(member.member_id().to_string());
+        }
+        if let Some(group_instance_id) = member.group_instance_id() {
+            self.static_members.insert(group_instance_id.to_string(), member.member_id().to_string());
+        }
+        self.members.insert(member.member_id().to_string(), member);
+    }
+
+    /// Remove the member, choosing a new leader if it was the leader
+    pub fn remove_member(&mut self, member_id: &str) -> Option<MemberMetadata> {
+        let member = self.members.remove(member_id)?;
+        if self.is_leader(member_id) {
+            self.leader_id = self.members.keys().next().cloned();
+        }
+        if let Some(group_instance_id) = member.group_instance_id() {
+            if self.static_member_id(group_instance_id) == Some(member_id) {
+                self.static_members.remove(group_instance_id);
+            }
+        }
+        Some(member)
+    }
+
+    /// The member id of the static member with the group instance id
+    pub fn static_member_id(&self, group_instance_id: &str) -> Option<&str> {
+        self.static_members.get(group_instance_id).map(String::as_str)
+    }
+
+    /// Give a static member a new member id, when it's rejoined without knowing its old one.
+    /// Anything still waiting on the old member id is fenced, since it's from an instance that's been replaced
+    pub fn replace_static_member(&mut self, old_member_id: &str, new_member_id: String) {
+        let Some(mut member) = self.members.remove(old_member_id) else {
+            return;
+        };
+        member.complete_join(Err(GroupError::FencedInstanceId));
+        member.complete_sync(Err(GroupError::FencedInstanceId));
+        member.set_member_id(new_member_id.clone());
+        if let Some(group_instance_id) = member.group_instance_id() {
+            self.static_members.insert(group_instance_id.to_string(), new_member_id.clone());
+        }
+        if self.is_leader(old_member_id) {
+            self.leader_id = Some(new_member_id.clone());
+        }
+        self.members.insert(new_member_id, member);
+    }
+
+    pub fn add_pending_member(&mut self, member_id: String) {
+        self.pending_members.insert(member_id);
+    }
+
+    /// Remove the member from the pending members, returning whether it was pending
+    pub fn remove_pending_member(&mut self, member_id: &str) -> bool {
+        self.pending_members.remove(member_id)
+    }
+
+    pub fn is_pending_member(&self, member_id: &str) -> bool {
+        self.pending_members.contains(member_id)
+    }
+
+    /// Whether every member has rejoined, so the rebalance can complete without waiting for the timeout
+    pub fn has_all_members_joined(&self) -> bool {
+        self.pending_members.is_empty() && self.members.values().all(MemberMetadata::is_awaiting_join)
+    }
+
+    /// The longest a rebalance can wait for the members to rejoin
+    pub fn rebalance_timeout(&self) -> Duration {
+        self.members.values()
+            .map(MemberMetadata::rebalance_timeout)
+            .max()
+            .unwrap_or_default()
+    }
+
+    pub fn initial_rebalance_deadline(&self) -> Option<Instant> {
+        self.initial_rebalance_deadline
+    }
+
+    pub fn set_initial_rebalance_deadline(&mut self, deadline: Option<Instant>) {
+        self.initial_rebalance_deadline = deadline;
+    }
+
+    pub fn join_deadline(&self) -> Option<Instant> {
+        self.join_deadline
+    }
+
+    pub fn set_join_deadline(&mut self, deadline: Option<Instant>) {
+        self.join_deadline = deadline;
+    }
+
+    /// Whether a member with the protocols can join, they have to be compatible with every existing member's
+    pub fn supports_protocols(&self, protocol_type: &str, protocols: &[JoinProtocol]) -> bool {
+        if self.members.is_empty() {
+            return !protocol_type.is_empty() && !protocols.is_empty();
+        }
+        let candidates = self.candidate_protocols();
+        self.protocol_type.as_deref() == Some(protocol_type)
+            && protocols.iter().any(|protocol| candidates.contains(protocol.name.as_str()))
+    }
+
+    /// The protocols every member supports
+    fn candidate_protocols(&self) -> HashSet<&str> {
+        let mut members = self.members.values();
+        let Some(first) = members.next() else {
+            return HashSet::new();
+        };
+        let mut candidates: HashSet<&str> = first.protocols().iter().map(|protocol| protocol.name.as_str()).collect();
+        for member in members {
+            candidates.retain(|candidate| member.metadata(candidate).is_some());
+        }
+        candidates
+    }
+
+    /// Choose the protocol every member supports that's preferred by the most members
+    pub fn select_protocol(&self) -> Option<String> {
+        let candidates = self.candidate_protocols();
+        let mut votes: BTreeMap<&str, usize> = BTreeMap::new();
+        for member in self.members.values() {
+            if let Some(vote) = member.protocols().iter().find(|protocol| candidates.contains(protocol.name.as_str())) {
+                *votes.entry(vote.name.as_str()).or_default() += 1;
+            }
+        }
+        votes.into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(protocol, _)| protocol.to_string())
+    }
+
+    /// Start the next generation once every member has rejoined, which is empty if none of them did
+    pub fn init_next_generation(&mut self) {
+        self.generation_id += 1;
+        if self.members.is_empty() {
+            self.protocol_name = None;
+            self.transition_to(GroupState::Empty);
+        } else {
+            self.protocol_name = self.select_protocol();
+            self.transition_to(GroupState::CompletingRebalance);
+        }
+    }
+
+    /// The topics the members of a group of consumers are subscribed to.
+    /// None if the members aren't consumers, or their subscriptions can't be read
+    pub fn subscribed_topics(&self) -> Option<HashSet<String>> {
+        if self.protocol_type.as_deref() != Some(CONSUMER_PROTOCOL_TYPE) {
+            return None;
+        }
+        let protocol_name = self.protocol_name.as_deref()?;
+        self.members.values()
+            .map(|member| member.metadata(protocol_name).and_then(subscribed_topics))
+            .try_fold(HashSet::new(), |mut topics, subscribed| {
+                topics.extend(subscribed?);
+                Some(topics)
+            })
+    }
+
+    pub fn offset(&self, topic_partition: &TopicPartition) -> Option<&OffsetAndMetadata> {
+        self.offsets.get(topic_partition)
+    }
+
+    pub fn offsets(&self) -> impl Iterator<Item = (&TopicPartition, &OffsetAndMetadata)> {
+        self.offsets.iter()
+    }
+
+    pub fn commit_offset(&mut self, topic_partition: TopicPartition, offset: OffsetAndMetadata) {
+        self.offsets.insert(topic_partition, offset);
+    }
+
+    pub fn remove_offset(&mut self, topic_partition: &TopicPartition) -> Option<OffsetAndMetadata> {
+        self.offsets.remove(topic_partition)
+    }
+
+    pub fn has_offsets(&self) -> bool {
+        !self.offsets.is_empty()
+    }
+
+    pub fn add_pending_transactional_offset(&mut self, producer_id: i64, topic_partition: TopicPartition, offset: OffsetAndMetadata) {
+        self.pending_transactional_offsets.entry(producer_id).or_default().insert(topic_partition, offset);
+    }
+
+    /// Commit or discard the offsets the producer committed in its transaction, once the transaction has completed
+    pub fn complete_transactional_offsets(&mut self, producer_id: i64, commit: bool) {
+        let Some(offsets) = self.pending_transactional_offsets.remove(&producer_id) else {
+            return;
+        };
+        if commit {
+            self.offsets.extend(offsets);
+        }
+    }
+
+    /// Whether a transaction that hasn't completed yet has committed an offset for the partition
+    pub fn has_pending_transactional_offset(&self, topic_partition: &TopicPartition) -> bool {
+        self.pending_transactional_offsets.values().any(|offsets| offsets.contains_key(topic_partition))
+    }
+
+    pub fn has_pending_transactional_offsets(&self) -> bool {
+        !self.pending_transactional_offsets.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinator::group::JoinGroupParams;
+
+    fn member(member_id: &str, protocols: &[&str]) -> MemberMetadata {
+        static_member(member_id, None, protocols)
+    }
+
+    fn static_member(member_id: &str, group_instance_id: Option<&str>, protocols: &[&str]) -> MemberMetadata {
+        let protocols = protocols.iter()
+            .map(|name| JoinProtocol { name: name.to_string(), metadata: name.as_bytes().to_vec() })
+            .collect();
+        let join = JoinGroupParams {
+            group_id: "group".to_string(),
+            member_id: member_id.to_string(),
+            group_instance_id: group_instance_id.map(str::to_string),
+            client_id: "client".to_string(),
+            client_host: "/127.0.0.1".to_string(),
+            session_timeout_ms: 10000,
+            rebalance_timeout_ms: 30000,
+            protocol_type: "consumer".to_string(),
+            protocols,
+            require_known_member_id: true,
+            reason: None,
+        };
+        MemberMetadata::new(member_id.to_string(), join)
+    }
+
+    #[test]
+    fn test_protocol_selection() {
+        let mut group = GroupMetadata::new("group".to_string());
+        assert!(!group.supports_protocols("", member("a", &["range"]).protocols()));
+        assert!(!group.supports_protocols("consumer", &[]));
+        group.add_member(member("a", &["range", "roundrobin"]));
+        group.add_member(member("b", &["roundrobin", "range", "sticky"]));
+        group.add_member(member("c", &["roundrobin", "range"]));
+        assert_eq!(group.leader_id(), Some("a"));
+
+        assert!(group.supports_protocols("consumer", member("d", &["sticky", "range"]).protocols()));
+        assert!(!group.supports_protocols("consumer", member("d", &["sticky"]).protocols()));
+        assert!(!group.supports_protocols("connect", member("d", &["range"]).protocols()));
+
+        group.transition_to(GroupState::PreparingRebalance);
+        group.init_next_generation();
+        assert_eq!(group.state(), GroupState::CompletingRebalance);
+        assert_eq!(group.generation_id(), 1);
+        assert_eq!(group.protocol_name(), Some("roundrobin"));
+
+        group.remove_member("a");
+        assert_eq!(group.leader_id(), Some("b"));
+    }
+
+    #[test]
+    fn test_static_members() {
+        let mut group = GroupMetadata::new("group".to_string());
+        group.add_member(static_member("a", Some("instance-a"), &["range"]));
+        group.add_member(member("b", &["range"]));
+        assert_eq!(group.static_member_id("instance-a"), Some("a"));
+
+        group.replace_static_member("a", "a2".to_string());
+        assert_eq!(group.static_member_id("instance-a"), Some("a2"));
+        assert_eq!(group.leader_id(), Some("a2"));
+        assert!(group.member("a").is_none());
+
+        group.transition_to(GroupState::PreparingRebalance);
+        group.init_next_generation();
+        let loaded = GroupMetadata::load("group".to_string(), group.to_value(0));
+        assert_eq!(loaded.state(), GroupState::Stable);
+        assert_eq!(loaded.generation_id(), 1);
+        assert_eq!(loaded.static_member_id("instance-a"), Some("a2"));
+        assert_eq!(loaded.leader_id(), Some("a2"));
+
+        group.remove_member("a2");
+        assert_eq!(group.static_member_id("instance-a"), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_invalid_transition() {
+        let mut group = GroupMetadata::new("group".to_string());
+        group.transition_to(GroupState::Stable);
+    }
+}
@@ -0,0 +1,194 @@
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use crate::coordinator::group::{GroupError, JoinGroupParams, JoinedGroup, SyncedGroup};
+use crate::coordinator::group::offsets::MemberMetadataValue;
+
+/// One of the protocols a member can use, such as a partition assignor of a consumer,
+/// with the member's metadata for that protocol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinProtocol {
+    pub name: String,
+    pub metadata: Vec<u8>,
+}
+
+/// A member of a group using the classic rebalance protocol
+#[derive(Debug)]
+pub struct MemberMetadata {
+    member_id: String,
+    group_instance_id: Option<String>,
+    client_id: String,
+    client_host: String,
+    session_timeout_ms: i32,
+    rebalance_timeout_ms: i32,
+    protocol_type: String,
+    /// The protocols the member supports, in order of preference
+    protocols: Vec<JoinProtocol>,
+    /// The member's assignment from the leader in the current generation
+    assignment: Vec<u8>,
+    /// Where to send the result of the join once the rebalance has completed
+    awaiting_join: Option<oneshot::Sender<Result<JoinedGroup, GroupError>>>,
+    /// Where to send the member's assignment once the leader has sent it
+    awaiting_sync: Option<oneshot::Sender<Result<SyncedGroup, GroupError>>>,
+    /// When the member is removed from the group, unless it heartbeats first
+    session_deadline: Instant,
+}
+
+impl MemberMetadata {
+    /// A member joining with the member id it's been given
+    pub fn new(member_id: String, join: JoinGroupParams) -> MemberMetadata {
+        MemberMetadata {
+            member_id,
+            group_instance_id: join.group_instance_id,
+            client_id: join.client_id,
+            client_host: join.client_host,
+            session_timeout_ms: join.session_timeout_ms,
+            rebalance_timeout_ms: join.rebalance_timeout_ms,
+            protocol_type: join.protocol_type,
+            protocols: join.protocols,
+            assignment: Vec::new(),
+            awaiting_join: None,
+            awaiting_sync: None,
+            session_deadline: Instant::now() + Duration::from_millis(join.session_timeout_ms as u64),
+        }
+    }
+
+    /// A member of the generation the group was in when it was stored in the offsets topic
+    pub fn load(value: MemberMetadataValue, protocol_type: String, protocol_name: String) -> MemberMetadata {
+        MemberMetadata {
+            member_id: value.member_id,
+            group_instance_id: value.group_instance_id,
+            client_id: value.client_id,
+            client_host: value.client_host,
+            session_timeout_ms: value.session_timeout_ms,
+            rebalance_timeout_ms: value.rebalance_timeout_ms,
+            protocol_type,
+            // only the metadata for the group's protocol is stored
+            protocols: vec![JoinProtocol { name: protocol_name, metadata: value.subscription }],
+            assignment: value.assignment,
+            awaiting_join: None,
+            awaiting_sync: None,
+            session_deadline: Instant::now() + Duration::from_millis(value.session_timeout_ms as u64),
+        }
+    }
+
+    /// The member as it's stored in the offsets topic, with its metadata for the group's protocol
+    pub fn to_value(&self, protocol_name: Option<&str>) -> MemberMetadataValue {
+        MemberMetadataValue {
+            member_id: self.member_id.clone(),
+            group_instance_id: self.group_instance_id.clone(),
+            client_id: self.client_id.clone(),
+            client_host: self.client_host.clone(),
+            rebalance_timeout_ms: self.rebalance_timeout_ms,
+            session_timeout_ms: self.session_timeout_ms,
+            subscription: protocol_name.and_then(|protocol_name| self.metadata(protocol_name)).unwrap_or_default().to_vec(),
+            assignment: self.assignment.clone(),
+        }
+    }
+
+    pub fn member_id(&self) -> &str {
+        &self.member_id
+    }
+
+    pub fn set_member_id(&mut self, member_id: String) {
+        self.member_id = member_id;
+    }
+
+    pub fn group_instance_id(&self) -> Option<&str> {
+        self.group_instance_id.as_deref()
+    }
+
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    pub fn client_host(&self) -> &str {
+        &self.client_host
+    }
+
+    pub fn session_timeout(&self) -> Duration {
+        Duration::from_millis(self.session_timeout_ms as u64)
+    }
+
+    pub fn rebalance_timeout(&self) -> Duration {
+        Duration::from_millis(self.rebalance_timeout_ms as u64)
+    }
+
+    pub fn protocol_type(&self) -> &str {
+        &self.protocol_type
+    }
+
+    pub fn protocols(&self) -> &[JoinProtocol] {
+        &self.protocols
+    }
+
+    pub fn session_timeout_ms(&self) -> i32 {
+        self.session_timeout_ms
+    }
+
+    pub fn rebalance_timeout_ms(&self) -> i32 {
+        self.rebalance_timeout_ms
+    }
+
+    /// The member's metadata for the protocol, if it supports it
+    pub fn metadata(&self, protocol_name: &str) -> Option<&[u8]> {
+        self.protocols.iter()
+            .find(|protocol| protocol.name == protocol_name)
+            .map(|protocol| protocol.metadata.as_slice())
+    }
+
+    /// Whether the member is joining with the same protocols it joined the current generation with
+    pub fn matches(&self, protocols: &[JoinProtocol]) -> bool {
+        self.protocols == protocols
+    }
+
+    /// Update the member when it rejoins the group
+    pub fn update(&mut self, session_timeout_ms: i32, rebalance_timeout_ms: i32, protocols: Vec<JoinProtocol>) {
+        self.session_timeout_ms = session_timeout_ms;
+        self.rebalance_timeout_ms = rebalance_timeout_ms;
+        self.protocols = protocols;
+    }
+
+    pub fn assignment(&self) -> &[u8] {
+        &self.assignment
+    }
+
+    pub fn set_assignment(&mut self, assignment: Vec<u8>) {
+        self.assignment = assignment;
+    }
+
+    pub fn is_awaiting_join(&self) -> bool {
+        self.awaiting_join.is_some()
+    }
+
+    pub fn await_join(&mut self, awaiting_join: oneshot::Sender<Result<JoinedGroup, GroupError>>) {
+        self.awaiting_join = Some(awaiting_join);
+    }
+
+    /// Send the result of the join to the member, if it's waiting for one
+    pub fn complete_join(&mut self, result: Result<JoinedGroup, GroupError>) {
+        if let Some(awaiting_join) = self.awaiting_join.take() {
+            // the member may have disconnected, in which case there's nobody to tell
+            let _ = awaiting_join.send(result);
+        }
+    }
+
+    pub fn await_sync(&mut self, awaiting_sync: oneshot::Sender<Result<SyncedGroup, GroupError>>) {
+        self.awaiting_sync = Some(awaiting_sync);
+    }
+
+    /// Send the result of the sync to the member, if it's waiting for one
+    pub fn complete_sync(&mut self, result: Result<SyncedGroup, GroupError>) {
+        if let Some(awaiting_sync) = self.awaiting_sync.take() {
+            let _ = awaiting_sync.send(result);
+        }
+    }
+
+    pub fn session_deadline(&self) -> Instant {
+        self.session_deadline
+    }
+
+    /// Keep the member in the group for another session timeout
+    pub fn extend_session(&mut self) {
+        self.session_deadline = Instant::now() + self.session_timeout();
+    }
+}
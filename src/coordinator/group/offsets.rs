@@ -0,0 +1,275 @@
+use bytes::{Buf, BufMut};
+use crate::api::request::KafkaRequestParseError;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+use crate::storage::topic_partition::TopicPartition;
+
+/// The internal topic that groups' committed offsets are stored in
+pub const OFFSETS_TOPIC: &str = "__consumer_offsets";
+
+/// The key and value schemas of the offsets topic aren't flexible until the value's v4
+const NOT_FLEXIBLE: MessageVersion = MessageVersion::new(0, false);
+/// The version of the OffsetCommitKey we write, v0 and v1 are the same
+const OFFSET_COMMIT_KEY_VERSION: i16 = 1;
+const GROUP_METADATA_KEY_VERSION: i16 = 2;
+/// The version of the OffsetCommitValue we write, which is the first with the leader epoch
+const OFFSET_COMMIT_VALUE_VERSION: i16 = 3;
+/// The version of the GroupMetadataValue we write, which is the first with the members' group instance ids
+const GROUP_METADATA_VALUE_VERSION: i16 = 3;
+
+/// The partition of the offsets topic a group's offsets are stored in, which matches how kafka chooses it
+/// by using the java hash code of the group id
+pub fn partition_for(group_id: &str, num_partitions: usize) -> usize {
+    let hash_code = group_id.encode_utf16()
+        .fold(0i32, |hash, char| hash.wrapping_mul(31).wrapping_add(char as i32));
+    // kafka's Utils.abs maps i32::MIN to 0 rather than overflowing
+    let positive = match hash_code {
+        i32::MIN => 0,
+        hash_code => hash_code.abs(),
+    };
+    positive as usize % num_partitions
+}
+
+/// The key of a record in the offsets topic
+#[derive(Debug, Clone, PartialEq)]
+pub enum OffsetsKey {
+    OffsetCommit { group_id: String, topic_partition: TopicPartition },
+    GroupMetadata { group_id: String },
+    /// A key version we don't use, which is skipped when loading the offsets
+    Unknown(i16),
+}
+
+impl OffsetsKey {
+    pub fn parse(mut key: &[u8]) -> Result<OffsetsKey, KafkaRequestParseError> {
+        let buf = &mut key;
+        let version = i16::read_kafka_bytes(buf)?;
+        let key = match version {
+            0 | 1 => {
+                let group_id = String::read_versioned_kafka_bytes(buf, NOT_FLEXIBLE)?;
+                let topic = String::read_versioned_kafka_bytes(buf, NOT_FLEXIBLE)?;
+                let partition = i32::read_kafka_bytes(buf)?;
+                OffsetsKey::OffsetCommit { group_id, topic_partition: TopicPartition::new(topic, partition) }
+            }
+            2 => OffsetsKey::GroupMetadata { group_id: String::read_versioned_kafka_bytes(buf, NOT_FLEXIBLE)? },
+            _ => OffsetsKey::Unknown(version),
+        };
+        Ok(key)
+    }
+
+    pub fn to_bytes(self) -> Vec<u8> {
+        match self {
+            OffsetsKey::OffsetCommit { group_id, topic_partition } => {
+                let mut bytes = Vec::new();
+                OFFSET_COMMIT_KEY_VERSION.write_kafka_bytes(&mut bytes);
+                group_id.write_versioned_kafka_bytes(&mut bytes, NOT_FLEXIBLE);
+                topic_partition.topic().to_string().write_versioned_kafka_bytes(&mut bytes, NOT_FLEXIBLE);
+                topic_partition.partition().write_kafka_bytes(&mut bytes);
+                bytes
+            }
+            OffsetsKey::GroupMetadata { group_id } => {
+                let mut bytes = Vec::new();
+                GROUP_METADATA_KEY_VERSION.write_kafka_bytes(&mut bytes);
+                group_id.write_versioned_kafka_bytes(&mut bytes, NOT_FLEXIBLE);
+                bytes
+            }
+            OffsetsKey::Unknown(version) => version.to_kafka_bytes(),
+        }
+    }
+}
+
+/// An offset committed by a group, which is the value of an OffsetCommit record in the offsets topic
+#[derive(Debug, Clone, PartialEq)]
+pub struct OffsetAndMetadata {
+    pub offset: i64,
+    pub leader_epoch: Option<i32>,
+    pub metadata: String,
+    pub commit_timestamp: i64,
+}
+
+impl OffsetAndMetadata {
+    pub fn parse(mut value: &[u8]) -> Result<OffsetAndMetadata, KafkaRequestParseError> {
+        let buf = &mut value;
+        let version = i16::read_kafka_bytes(buf)?;
+        let message_version = MessageVersion::new(version, version >= 4);
+        let offset = i64::read_kafka_bytes(buf)?;
+        let leader_epoch = match version {
+            3.. => Some(i32::read_kafka_bytes(buf)?).filter(|epoch| *epoch >= 0),
+            _ => None,
+        };
+        let metadata = String::read_versioned_kafka_bytes(buf, message_version)?;
+        let commit_timestamp = i64::read_kafka_bytes(buf)?;
+        if version == 1 {
+            // offsets don't expire individually anymore, so the expire timestamp is ignored
+            let _expire_timestamp = i64::read_kafka_bytes(buf)?;
+        }
+        skip_tagged_fields(buf, message_version)?;
+        Ok(OffsetAndMetadata { offset, leader_epoch, metadata, commit_timestamp })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let version = MessageVersion::new(OFFSET_COMMIT_VALUE_VERSION, false);
+        let mut bytes = Vec::new();
+        OFFSET_COMMIT_VALUE_VERSION.write_kafka_bytes(&mut bytes);
+        self.offset.write_kafka_bytes(&mut bytes);
+        self.leader_epoch.unwrap_or(-1).write_kafka_bytes(&mut bytes);
+        self.metadata.clone().write_versioned_kafka_bytes(&mut bytes, version);
+        self.commit_timestamp.write_kafka_bytes(&mut bytes);
+        write_empty_tagged_fields(&mut bytes, version);
+        bytes
+    }
+}
+
+/// The members of a group's current generation, which is the value of a GroupMetadata record in the offsets topic.
+/// It's stored so static members keep their member ids when the coordinator restarts
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupMetadataValue {
+    pub protocol_type: String,
+    pub generation: i32,
+    pub protocol: Option<String>,
+    pub leader: Option<String>,
+    pub current_state_timestamp: i64,
+    pub members: Vec<MemberMetadataValue>,
+}
+
+impl GroupMetadataValue {
+    pub fn parse(mut value: &[u8]) -> Result<GroupMetadataValue, KafkaRequestParseError> {
+        let buf = &mut value;
+        let version = i16::read_kafka_bytes(buf)?;
+        let message_version = MessageVersion::new(version, version >= 4);
+        let protocol_type = String::read_versioned_kafka_bytes(buf, message_version)?;
+        let generation = i32::read_kafka_bytes(buf)?;
+        let protocol = Option::<String>::read_versioned_kafka_bytes(buf, message_version)?;
+        let leader = Option::<String>::read_versioned_kafka_bytes(buf, message_version)?;
+        let current_state_timestamp = match version {
+            2.. => i64::read_kafka_bytes(buf)?,
+            _ => -1,
+        };
+        let members = Vec::read_versioned_kafka_bytes(buf, message_version)?;
+        skip_tagged_fields(buf, message_version)?;
+        Ok(GroupMetadataValue { protocol_type, generation, protocol, leader, current_state_timestamp, members })
+    }
+
+    pub fn to_bytes(self) -> Vec<u8> {
+        let version = MessageVersion::new(GROUP_METADATA_VALUE_VERSION, false);
+        let mut bytes = Vec::new();
+        GROUP_METADATA_VALUE_VERSION.write_kafka_bytes(&mut bytes);
+        self.protocol_type.write_versioned_kafka_bytes(&mut bytes, version);
+        self.generation.write_kafka_bytes(&mut bytes);
+        self.protocol.write_versioned_kafka_bytes(&mut bytes, version);
+        self.leader.write_versioned_kafka_bytes(&mut bytes, version);
+        self.current_state_timestamp.write_kafka_bytes(&mut bytes);
+        self.members.write_versioned_kafka_bytes(&mut bytes, version);
+        write_empty_tagged_fields(&mut bytes, version);
+        bytes
+    }
+}
+
+/// A member of a group, as it's stored in the offsets topic
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemberMetadataValue {
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    pub client_id: String,
+    pub client_host: String,
+    pub rebalance_timeout_ms: i32,
+    pub session_timeout_ms: i32,
+    /// The member's metadata for the group's protocol
+    pub subscription: Vec<u8>,
+    pub assignment: Vec<u8>,
+}
+
+impl ReadVersionedKafkaBytes for MemberMetadataValue {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let member_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let group_instance_id = match version.version() {
+            3.. => Option::<String>::read_versioned_kafka_bytes(buf, version)?,
+            _ => None,
+        };
+        let client_id = String::read_versioned_kafka_bytes(buf, version)?;
+        let client_host = String::read_versioned_kafka_bytes(buf, version)?;
+        let rebalance_timeout_ms = match version.version() {
+            1.. => Some(i32::read_kafka_bytes(buf)?),
+            _ => None,
+        };
+        let session_timeout_ms = i32::read_kafka_bytes(buf)?;
+        let subscription = Vec::read_versioned_kafka_bytes(buf, version)?;
+        let assignment = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(MemberMetadataValue {
+            member_id,
+            group_instance_id,
+            client_id,
+            client_host,
+            // before the rebalance timeout was stored, the session timeout was used for both
+            rebalance_timeout_ms: rebalance_timeout_ms.unwrap_or(session_timeout_ms),
+            session_timeout_ms,
+            subscription,
+            assignment,
+        })
+    }
+}
+
+impl ToVersionedKafkaBytes for MemberMetadataValue {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.member_id.write_versioned_kafka_bytes(buf, version);
+        self.group_instance_id.write_versioned_kafka_bytes(buf, version);
+        self.client_id.write_versioned_kafka_bytes(buf, version);
+        self.client_host.write_versioned_kafka_bytes(buf, version);
+        self.rebalance_timeout_ms.write_kafka_bytes(buf);
+        self.session_timeout_ms.write_kafka_bytes(buf);
+        self.subscription.write_versioned_kafka_bytes(buf, version);
+        self.assignment.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partition_for() {
+        // the partitions kafka chooses with the default of 50 partitions
+        assert_eq!(partition_for("my-group", 50), 12);
+        assert_eq!(partition_for("console-consumer-1234", 50), 9);
+        assert_eq!(partition_for("", 50), 0);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let key = OffsetsKey::OffsetCommit { group_id: "group".to_string(), topic_partition: TopicPartition::new("topic", 3) };
+        assert_eq!(OffsetsKey::parse(&key.clone().to_bytes()).unwrap(), key);
+
+        let value = OffsetAndMetadata { offset: 42, leader_epoch: Some(1), metadata: "meta".to_string(), commit_timestamp: 1000 };
+        assert_eq!(OffsetAndMetadata::parse(&value.to_bytes()).unwrap(), value);
+
+        // a v1 value with an expire timestamp, from before the leader epoch was added
+        let mut v1 = 1i16.to_be_bytes().to_vec();
+        v1.extend(7i64.to_be_bytes());
+        v1.extend(0i16.to_be_bytes());
+        v1.extend(1000i64.to_be_bytes());
+        v1.extend(2000i64.to_be_bytes());
+        let parsed = OffsetAndMetadata::parse(&v1).unwrap();
+        assert_eq!(parsed, OffsetAndMetadata { offset: 7, leader_epoch: None, metadata: String::new(), commit_timestamp: 1000 });
+
+        let member = MemberMetadataValue {
+            member_id: "instance-1-abc".to_string(),
+            group_instance_id: Some("instance-1".to_string()),
+            client_id: "client".to_string(),
+            client_host: "/127.0.0.1".to_string(),
+            rebalance_timeout_ms: 30000,
+            session_timeout_ms: 10000,
+            subscription: vec![1, 2, 3],
+            assignment: vec![4, 5],
+        };
+        let group = GroupMetadataValue {
+            protocol_type: "consumer".to_string(),
+            generation: 2,
+            protocol: Some("range".to_string()),
+            leader: Some("instance-1-abc".to_string()),
+            current_state_timestamp: 1000,
+            members: vec![member],
+        };
+        assert_eq!(GroupMetadataValue::parse(&group.clone().to_bytes()).unwrap(), group);
+    }
+}
@@ -0,0 +1,42 @@
+use std::io;
+use std::ops::Range;
+use std::sync::Mutex;
+use tracing::info;
+use crate::metadata::records::{MetadataRecord, ProducerIdsRecord};
+use crate::metadata::store::MetadataStore;
+
+/// The number of producer ids allocated to the broker at a time, so most ids don't need a write to the metadata log
+const PRODUCER_ID_BLOCK_SIZE: i64 = 1000;
+
+/// Hands out unique producer ids, from blocks of ids that are allocated in the metadata log
+/// so they're never reused, even after the broker restarts
+#[derive(Debug)]
+pub struct ProducerIdManager {
+    node_id: i32,
+    /// The ids left in the block currently allocated to this broker
+    block: Mutex<Range<i64>>,
+}
+
+impl ProducerIdManager {
+    pub fn new(node_id: i32) -> ProducerIdManager {
+        ProducerIdManager { node_id, block: Mutex::new(0..0) }
+    }
+
+    /// The next unused producer id, allocating a new block of ids once the current one runs out
+    pub fn generate(&self, metadata: &MetadataStore) -> io::Result<i64> {
+        let mut block = self.block.lock().unwrap();
+        if block.is_empty() {
+            *block = metadata.update(|image| {
+                let start = image.next_producer_id();
+                let record = MetadataRecord::ProducerIds(ProducerIdsRecord {
+                    broker_id: self.node_id,
+                    broker_epoch: 0,
+                    next_producer_id: start + PRODUCER_ID_BLOCK_SIZE,
+                });
+                Ok::<_, io::Error>((vec![record], start..start + PRODUCER_ID_BLOCK_SIZE))
+            })?;
+            info!("Allocated producer ids {block:?}");
+        }
+        Ok(block.next().expect("a new block of producer ids isn't empty"))
+    }
+}
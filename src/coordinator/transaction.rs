@@ -0,0 +1,393 @@
+pub mod transaction_log;
+pub mod transaction_metadata;
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{error, info};
+use crate::broker::config::BrokerConfig;
+use crate::coordinator::group::offsets::{partition_for, OFFSETS_TOPIC};
+use crate::coordinator::group::GroupCoordinator;
+use crate::coordinator::transaction::transaction_log::{parse_transaction_log_key, transaction_log_key, TransactionLogValue};
+use crate::coordinator::transaction::transaction_metadata::{TransactionMetadata, TransactionState};
+use crate::storage::log::Log;
+use crate::storage::log_manager::LogManager;
+use crate::storage::record_batch::{end_transaction_marker, ControlRecordType, RecordBatchBuilder};
+use crate::storage::topic_partition::TopicPartition;
+use crate::time::now_ms;
+use crate::time::purgatory::Purgatory;
+use crate::time::timer::Timer;
+
+/// The epoch of the coordinator written in the markers, which never changes since this broker is the only coordinator
+const COORDINATOR_EPOCH: i32 = 0;
+
+#[derive(Debug, Error)]
+pub enum TransactionError {
+    #[error("{0}")]
+    InvalidRequest(String),
+    #[error("The transaction timeout is larger than the maximum value allowed by the broker (as configured by transaction.max.timeout.ms).")]
+    InvalidTransactionTimeout,
+    #[error("The producer attempted to use a producer id which is not currently assigned to its transactional id.")]
+    InvalidProducerIdMapping,
+    #[error("There is a newer producer with the same transactionalId which fences the current one.")]
+    ProducerFenced,
+    #[error("The producer attempted to update a transaction while another concurrent operation on the same transaction was ongoing.")]
+    ConcurrentTransactions,
+    #[error("The producer attempted a transactional operation in an invalid state.")]
+    InvalidTxnState,
+    #[error("The coordinator is not available.")]
+    CoordinatorNotAvailable,
+    #[error("The transactionalId could not be found.")]
+    TransactionalIdNotFound,
+    #[error(transparent)]
+    Storage(#[from] io::Error),
+}
+
+/// A transactional id as it's listed to admin clients
+#[derive(Debug, Clone)]
+pub struct ListedTransaction {
+    pub transactional_id: String,
+    pub producer_id: i64,
+    pub state: TransactionState,
+    /// When the current transaction started, or -1 if there isn't one
+    pub start_timestamp: i64,
+}
+
+/// A transactional id's current transaction, as it's described to admin clients
+#[derive(Debug, Clone)]
+pub struct DescribedTransaction {
+    pub state: TransactionState,
+    pub timeout_ms: i32,
+    /// When the current transaction started, or -1 if there isn't one
+    pub start_timestamp: i64,
+    pub producer_id: i64,
+    pub producer_epoch: i16,
+    pub partitions: Vec<TopicPartition>,
+}
+
+/// Coordinates the transactions of transactional producers, writing their state to the transaction state topic
+/// and the markers that complete them to their partitions.
+/// This broker is the coordinator of every transactional id, since it's the only broker in the cluster
+#[derive(Debug)]
+pub struct TransactionCoordinator {
+    max_timeout_ms: i32,
+    /// Every transactional id's current transaction.
+    /// When both are locked, this is locked before the group coordinator's groups
+    transactions: Mutex<HashMap<String, TransactionMetadata>>,
+    /// The partitions of the transaction state topic, which are set once the transactions have been loaded from them
+    state_logs: OnceLock<Vec<Arc<Log>>>,
+    log_manager: Arc<LogManager>,
+    /// Writing a marker can advance a partition's last stable offset, which read_committed fetches may be waiting for
+    fetch_purgatory: Arc<Purgatory<TopicPartition>>,
+    /// Offsets committed in transactions are completed along with the transaction's markers
+    group_coordinator: Arc<GroupCoordinator>,
+    /// Aborts transactions that have been ongoing for longer than their timeout, keyed by transactional id
+    timer: Timer<String>,
+    /// Delayed operations run after the request that scheduled them has finished, so they need their own reference
+    this: Weak<TransactionCoordinator>,
+}
+
+impl TransactionCoordinator {
+    pub fn new(
+        config: &BrokerConfig,
+        log_manager: Arc<LogManager>,
+        fetch_purgatory: Arc<Purgatory<TopicPartition>>,
+        group_coordinator: Arc<GroupCoordinator>,
+    ) -> Arc<TransactionCoordinator> {
+        Arc::new_cyclic(|this| TransactionCoordinator {
+            max_timeout_ms: config.transaction_max_timeout_ms(),
+            transactions: Mutex::new(HashMap::new()),
+            state_logs: OnceLock::new(),
+            log_manager,
+            fetch_purgatory,
+            group_coordinator,
+            timer: Timer::new(),
+            this: this.clone(),
+        })
+    }
+
+    /// Give the transactional id a producer id and epoch, fencing any earlier producer with the same transactional id
+    /// and aborting its ongoing transaction. A producer that's reinitialising sends its current producer id and epoch
+    pub fn init_producer_id(
+        &self,
+        transactional_id: &str,
+        timeout_ms: i32,
+        expected: Option<(i64, i16)>,
+        generate_producer_id: impl FnOnce() -> io::Result<i64>,
+    ) -> Result<(i64, i16), TransactionError> {
+        if transactional_id.is_empty() {
+            return Err(TransactionError::InvalidRequest("The transactional id can't be empty".to_string()));
+        }
+        if timeout_ms <= 0 || timeout_ms > self.max_timeout_ms {
+            return Err(TransactionError::InvalidTransactionTimeout);
+        }
+        let mut transactions = self.transactions.lock().unwrap();
+        let Some(transaction) = transactions.get_mut(transactional_id) else {
+            let transaction = TransactionMetadata::new(transactional_id.to_string(), generate_producer_id()?, timeout_ms, now_ms());
+            self.store(&transaction)?;
+            let producer = (transaction.producer_id(), transaction.producer_epoch());
+            transactions.insert(transactional_id.to_string(), transaction);
+            return Ok(producer);
+        };
+        if expected.is_some_and(|expected| expected != (transaction.producer_id(), transaction.producer_epoch())) {
+            return Err(TransactionError::ProducerFenced);
+        }
+        match transaction.state() {
+            // the transaction was being completed when the broker stopped
+            TransactionState::PrepareCommit | TransactionState::PrepareAbort => self.complete_pending(transaction)?,
+            TransactionState::Ongoing => {
+                transaction.fence_producer();
+                self.complete_transaction(transaction, ControlRecordType::Abort)?;
+            }
+            _ => {}
+        }
+
+        let (producer_id, producer_epoch) = match transaction.is_epoch_exhausted() {
+            true => (generate_producer_id()?, 0),
+            false => (transaction.producer_id(), transaction.producer_epoch() + 1),
+        };
+        transaction.init_producer(producer_id, producer_epoch, timeout_ms, now_ms());
+        self.store(transaction)?;
+        Ok((producer_id, producer_epoch))
+    }
+
+    /// Add partitions to the producer's transaction, starting a new transaction if there isn't one ongoing
+    pub fn add_partitions(
+        &self,
+        transactional_id: &str,
+        producer_id: i64,
+        producer_epoch: i16,
+        partitions: Vec<TopicPartition>,
+    ) -> Result<(), TransactionError> {
+        let mut transactions = self.transactions.lock().unwrap();
+        let transaction = Self::validate_producer(&mut transactions, transactional_id, producer_id, producer_epoch)?;
+        match transaction.state() {
+            TransactionState::PrepareCommit | TransactionState::PrepareAbort | TransactionState::PrepareEpochFence => {
+                return Err(TransactionError::ConcurrentTransactions);
+            }
+            TransactionState::Dead => return Err(TransactionError::InvalidTxnState),
+            _ => {}
+        }
+        let was_ongoing = transaction.state() == TransactionState::Ongoing;
+        if was_ongoing && partitions.iter().all(|partition| transaction.partitions().contains(partition)) {
+            return Ok(());
+        }
+
+        transaction.add_partitions(partitions, now_ms());
+        self.store(transaction)?;
+        if !was_ongoing {
+            self.schedule_timeout(transaction);
+        }
+        Ok(())
+    }
+
+    /// Commit or abort the producer's transaction, writing a marker to each of its partitions
+    pub fn end_transaction(&self, transactional_id: &str, producer_id: i64, producer_epoch: i16, commit: bool) -> Result<(), TransactionError> {
+        let mut transactions = self.transactions.lock().unwrap();
+        let transaction = Self::validate_producer(&mut transactions, transactional_id, producer_id, producer_epoch)?;
+        let marker = match commit {
+            true => ControlRecordType::Commit,
+            false => ControlRecordType::Abort,
+        };
+        match (transaction.state(), marker) {
+            (TransactionState::Ongoing, _) => self.complete_transaction(transaction, marker),
+            // the producer is retrying an end it didn't get a response for
+            (TransactionState::CompleteCommit, ControlRecordType::Commit) | (TransactionState::CompleteAbort, ControlRecordType::Abort) => Ok(()),
+            // writing the markers failed part way through, so it's tried again
+            (TransactionState::PrepareCommit, ControlRecordType::Commit) | (TransactionState::PrepareAbort, ControlRecordType::Abort) => {
+                self.complete_pending(transaction)
+            }
+            _ => Err(TransactionError::InvalidTxnState),
+        }
+    }
+
+    /// The partition of the offsets topic the group's offsets are written to, which is added to a transaction committing them
+    pub fn offsets_partition(&self, group_id: &str) -> Result<TopicPartition, TransactionError> {
+        self.group_coordinator.offsets_partition(group_id).map_err(|_| TransactionError::CoordinatorNotAvailable)
+    }
+
+    /// Every transactional id the coordinator knows about, ordered by transactional id
+    pub fn list_transactions(&self) -> Vec<ListedTransaction> {
+        let transactions = self.transactions.lock().unwrap();
+        let mut listed: Vec<ListedTransaction> = transactions.values()
+            .filter(|transaction| transaction.state() != TransactionState::Dead)
+            .map(|transaction| ListedTransaction {
+                transactional_id: transaction.transactional_id().to_string(),
+                producer_id: transaction.producer_id(),
+                state: transaction.state(),
+                start_timestamp: transaction.start_timestamp(),
+            })
+            .collect();
+        listed.sort_by(|a, b| a.transactional_id.cmp(&b.transactional_id));
+        listed
+    }
+
+    /// Describe the transactional id's current transaction
+    pub fn describe_transaction(&self, transactional_id: &str) -> Result<DescribedTransaction, TransactionError> {
+        let transactions = self.transactions.lock().unwrap();
+        let transaction = transactions.get(transactional_id)
+            .filter(|transaction| transaction.state() != TransactionState::Dead)
+            .ok_or(TransactionError::TransactionalIdNotFound)?;
+        Ok(DescribedTransaction {
+            state: transaction.state(),
+            timeout_ms: transaction.timeout_ms(),
+            start_timestamp: transaction.start_timestamp(),
+            producer_id: transaction.producer_id(),
+            producer_epoch: transaction.producer_epoch(),
+            partitions: transaction.partitions().iter().cloned().collect(),
+        })
+    }
+
+    /// Stop timing transactions out, so they aren't aborted once the broker is shutting down.
+    /// Ongoing transactions time out from when they started once they're loaded again
+    pub fn shutdown(&self) {
+        self.timer.shutdown();
+    }
+
+    /// Load the transactions by replaying the transaction state topic, which is then written to.
+    /// Transactions that were being completed are completed, and ongoing ones time out as if they had just been loaded
+    pub fn load_transactions(&self, logs: Vec<Arc<Log>>) -> io::Result<()> {
+        let mut loaded = HashMap::new();
+        for log in &logs {
+            for (header, batch) in log.read_all_batches()? {
+                if header.is_control() {
+                    continue;
+                }
+                for record in header.records(&batch)? {
+                    let Some(key) = record.key() else {
+                        continue;
+                    };
+                    let Some(transactional_id) = parse_transaction_log_key(key)
+                        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))? else {
+                        continue;
+                    };
+                    // a record without a value is a tombstone for a transactional id that's expired
+                    match record.value() {
+                        Some(value) => {
+                            let value = TransactionLogValue::parse(value)
+                                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                            loaded.insert(transactional_id.clone(), TransactionMetadata::load(transactional_id, value));
+                        }
+                        None => {
+                            loaded.remove(&transactional_id);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.state_logs.set(logs).map_err(|_| io::Error::other("Transactions have already been loaded"))?;
+        let mut transactions = self.transactions.lock().unwrap();
+        for mut transaction in loaded.into_values() {
+            match transaction.state() {
+                TransactionState::PrepareCommit | TransactionState::PrepareAbort => {
+                    if let Err(err) = self.complete_pending(&mut transaction) {
+                        error!("Failed to complete transaction {}: {err}", transaction.transactional_id());
+                    }
+                }
+                TransactionState::Ongoing => self.schedule_timeout(&transaction),
+                _ => {}
+            }
+            transactions.insert(transaction.transactional_id().to_string(), transaction);
+        }
+        Ok(())
+    }
+
+    /// Find the transaction, checking the producer is the latest one with its transactional id
+    fn validate_producer<'a>(
+        transactions: &'a mut HashMap<String, TransactionMetadata>,
+        transactional_id: &str,
+        producer_id: i64,
+        producer_epoch: i16,
+    ) -> Result<&'a mut TransactionMetadata, TransactionError> {
+        let transaction = transactions.get_mut(transactional_id).ok_or(TransactionError::InvalidProducerIdMapping)?;
+        if transaction.producer_id() != producer_id {
+            return Err(TransactionError::InvalidProducerIdMapping);
+        }
+        if transaction.producer_epoch() != producer_epoch {
+            return Err(TransactionError::ProducerFenced);
+        }
+        Ok(transaction)
+    }
+
+    /// Finish completing a transaction whose markers may not all have been written
+    fn complete_pending(&self, transaction: &mut TransactionMetadata) -> Result<(), TransactionError> {
+        match transaction.pending_marker() {
+            Some(marker) => self.complete_transaction(transaction, marker),
+            None => Ok(()),
+        }
+    }
+
+    /// Commit or abort the transaction. It's prepared before the markers are written,
+    /// so if writing them fails the transaction is completed when the producer retries or the broker restarts
+    fn complete_transaction(&self, transaction: &mut TransactionMetadata, marker: ControlRecordType) -> Result<(), TransactionError> {
+        transaction.prepare_complete(marker, now_ms());
+        self.store(transaction)?;
+
+        let mut has_offsets = false;
+        for topic_partition in transaction.partitions() {
+            has_offsets |= topic_partition.topic() == OFFSETS_TOPIC;
+            // the partition's topic has been deleted since it was added to the transaction
+            let Some(log) = self.log_manager.get_log(topic_partition)? else {
+                continue;
+            };
+            let batch = end_transaction_marker(transaction.producer_id(), transaction.producer_epoch(), COORDINATOR_EPOCH, marker, now_ms());
+            log.append(batch, log.latest_epoch()?.unwrap_or(0))?;
+            self.fetch_purgatory.check_and_complete(topic_partition);
+        }
+        if has_offsets {
+            self.group_coordinator.complete_transactional_offsets(transaction.producer_id(), marker == ControlRecordType::Commit);
+        }
+
+        transaction.complete(now_ms());
+        self.store(transaction)?;
+        self.timer.cancel(&transaction.transactional_id().to_string());
+        Ok(())
+    }
+
+    /// Abort the transaction if the producer hasn't ended it within its timeout
+    fn schedule_timeout(&self, transaction: &TransactionMetadata) {
+        let coordinator = self.this.clone();
+        let transactional_id = transaction.transactional_id().to_string();
+        let producer = (transaction.producer_id(), transaction.producer_epoch());
+        // loaded transactions have already been running for some of their timeout
+        let elapsed = Duration::from_millis((now_ms() - transaction.start_timestamp()).max(0) as u64);
+        let delay = transaction.timeout().saturating_sub(elapsed);
+        self.timer.schedule(transactional_id.clone(), delay, move || {
+            if let Some(coordinator) = coordinator.upgrade() {
+                coordinator.abort_timed_out_transaction(&transactional_id, producer);
+            }
+        });
+    }
+
+    /// Fence the producer and abort its transaction, unless it's been ended since it timed out
+    fn abort_timed_out_transaction(&self, transactional_id: &str, (producer_id, producer_epoch): (i64, i16)) {
+        let mut transactions = self.transactions.lock().unwrap();
+        let Some(transaction) = transactions.get_mut(transactional_id) else {
+            return;
+        };
+        if transaction.state() != TransactionState::Ongoing
+            || transaction.producer_id() != producer_id
+            || transaction.producer_epoch() != producer_epoch {
+            return;
+        }
+        info!("Aborting transaction {transactional_id} with producer id {producer_id}, which has timed out");
+        transaction.fence_producer();
+        if let Err(err) = self.complete_transaction(transaction, ControlRecordType::Abort) {
+            error!("Failed to abort transaction {transactional_id}: {err}");
+        }
+    }
+
+    /// Write the transaction's current state to its partition of the transaction state topic
+    fn store(&self, transaction: &TransactionMetadata) -> Result<(), TransactionError> {
+        let logs = self.state_logs.get().ok_or(TransactionError::CoordinatorNotAvailable)?;
+        let key = transaction_log_key(transaction.transactional_id());
+        let batch = RecordBatchBuilder::new()
+            .add_record(now_ms(), Some(key), Some(transaction.to_value().to_bytes()))
+            .build();
+        logs[partition_for(transaction.transactional_id(), logs.len())].append(batch, 0)?;
+        Ok(())
+    }
+}
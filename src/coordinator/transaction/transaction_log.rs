@@ -0,0 +1,144 @@
+use std::collections::{BTreeMap, BTreeSet};
+use bytes::{Buf, BufMut};
+use crate::api::request::KafkaRequestParseError;
+use crate::coordinator::transaction::transaction_metadata::TransactionState;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+use crate::storage::topic_partition::TopicPartition;
+
+/// The internal topic that the state of every transaction is stored in
+pub const TRANSACTION_STATE_TOPIC: &str = "__transaction_state";
+
+/// The version of the TransactionLogKey we write, which is the only version
+const TRANSACTION_LOG_KEY_VERSION: i16 = 0;
+/// The version of the TransactionLogValue we write, v1 only adds tagged fields
+const TRANSACTION_LOG_VALUE_VERSION: i16 = 0;
+
+/// The key of a record in the transaction state topic, or None if it's a version we don't know
+pub fn parse_transaction_log_key(mut key: &[u8]) -> Result<Option<String>, KafkaRequestParseError> {
+    let buf = &mut key;
+    match i16::read_kafka_bytes(buf)? {
+        TRANSACTION_LOG_KEY_VERSION => Ok(Some(String::read_versioned_kafka_bytes(buf, MessageVersion::new(0, false))?)),
+        _ => Ok(None),
+    }
+}
+
+pub fn transaction_log_key(transactional_id: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    TRANSACTION_LOG_KEY_VERSION.write_kafka_bytes(&mut bytes);
+    transactional_id.to_string().write_versioned_kafka_bytes(&mut bytes, MessageVersion::new(0, false));
+    bytes
+}
+
+/// A transaction's state, which is the value of a record in the transaction state topic
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionLogValue {
+    pub producer_id: i64,
+    pub producer_epoch: i16,
+    pub transaction_timeout_ms: i32,
+    pub state: TransactionState,
+    pub partitions: BTreeSet<TopicPartition>,
+    pub last_update_timestamp: i64,
+    pub start_timestamp: i64,
+}
+
+impl TransactionLogValue {
+    pub fn parse(mut value: &[u8]) -> Result<TransactionLogValue, KafkaRequestParseError> {
+        let buf = &mut value;
+        let version = i16::read_kafka_bytes(buf)?;
+        let message_version = MessageVersion::new(version, version >= 1);
+        let producer_id = i64::read_kafka_bytes(buf)?;
+        let producer_epoch = i16::read_kafka_bytes(buf)?;
+        let transaction_timeout_ms = i32::read_kafka_bytes(buf)?;
+        let state = TransactionState::from_id(i8::read_kafka_bytes(buf)?);
+        let partitions = Option::<Vec<TransactionPartitions>>::read_versioned_kafka_bytes(buf, message_version)?
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|topic| {
+                let TransactionPartitions { topic, partition_ids } = topic;
+                partition_ids.into_iter().map(move |partition| TopicPartition::new(topic.clone(), partition))
+            })
+            .collect();
+        let last_update_timestamp = i64::read_kafka_bytes(buf)?;
+        let start_timestamp = i64::read_kafka_bytes(buf)?;
+        skip_tagged_fields(buf, message_version)?;
+        Ok(TransactionLogValue {
+            producer_id,
+            producer_epoch,
+            transaction_timeout_ms,
+            state,
+            partitions,
+            last_update_timestamp,
+            start_timestamp,
+        })
+    }
+
+    pub fn to_bytes(self) -> Vec<u8> {
+        let version = MessageVersion::new(TRANSACTION_LOG_VALUE_VERSION, false);
+        let mut topics: BTreeMap<String, Vec<i32>> = BTreeMap::new();
+        for topic_partition in &self.partitions {
+            topics.entry(topic_partition.topic().to_string()).or_default().push(topic_partition.partition());
+        }
+        let partitions: Vec<TransactionPartitions> = topics.into_iter()
+            .map(|(topic, partition_ids)| TransactionPartitions { topic, partition_ids })
+            .collect();
+
+        let mut bytes = Vec::new();
+        TRANSACTION_LOG_VALUE_VERSION.write_kafka_bytes(&mut bytes);
+        self.producer_id.write_kafka_bytes(&mut bytes);
+        self.producer_epoch.write_kafka_bytes(&mut bytes);
+        self.transaction_timeout_ms.write_kafka_bytes(&mut bytes);
+        self.state.id().write_kafka_bytes(&mut bytes);
+        Some(partitions).write_versioned_kafka_bytes(&mut bytes, version);
+        self.last_update_timestamp.write_kafka_bytes(&mut bytes);
+        self.start_timestamp.write_kafka_bytes(&mut bytes);
+        write_empty_tagged_fields(&mut bytes, version);
+        bytes
+    }
+}
+
+/// The partitions of a topic that are part of a transaction
+#[derive(Debug)]
+struct TransactionPartitions {
+    topic: String,
+    partition_ids: Vec<i32>,
+}
+
+impl ReadVersionedKafkaBytes for TransactionPartitions {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let topic = String::read_versioned_kafka_bytes(buf, version)?;
+        let partition_ids = Vec::read_versioned_kafka_bytes(buf, version)?;
+        skip_tagged_fields(buf, version)?;
+        Ok(TransactionPartitions { topic, partition_ids })
+    }
+}
+
+impl ToVersionedKafkaBytes for TransactionPartitions {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        self.topic.write_versioned_kafka_bytes(buf, version);
+        self.partition_ids.write_versioned_kafka_bytes(buf, version);
+        write_empty_tagged_fields(buf, version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let key = transaction_log_key("my-transaction");
+        assert_eq!(parse_transaction_log_key(&key).unwrap().as_deref(), Some("my-transaction"));
+
+        let value = TransactionLogValue {
+            producer_id: 1000,
+            producer_epoch: 3,
+            transaction_timeout_ms: 60000,
+            state: TransactionState::Ongoing,
+            partitions: BTreeSet::from([TopicPartition::new("a", 0), TopicPartition::new("a", 2), TopicPartition::new("b", 1)]),
+            last_update_timestamp: 2000,
+            start_timestamp: 1000,
+        };
+        assert_eq!(TransactionLogValue::parse(&value.clone().to_bytes()).unwrap(), value);
+    }
+}
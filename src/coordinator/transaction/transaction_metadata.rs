@@ -0,0 +1,266 @@
+use std::collections::BTreeSet;
+use std::time::Duration;
+use crate::coordinator::transaction::transaction_log::TransactionLogValue;
+use crate::storage::record_batch::ControlRecordType;
+use crate::storage::topic_partition::TopicPartition;
+
+/// The states of a transaction, which are stored in the transaction state topic by their ids
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TransactionState {
+    /// The producer has no transaction in progress
+    Empty,
+    /// The producer has added partitions to its transaction
+    Ongoing,
+    /// The producer has committed its transaction, and the markers are being written to its partitions
+    PrepareCommit,
+    /// The transaction is being aborted, and the markers are being written to its partitions
+    PrepareAbort,
+    /// The transaction was committed, and every partition has its marker
+    CompleteCommit,
+    /// The transaction was aborted, and every partition has its marker
+    CompleteAbort,
+    /// The transactional id has expired
+    Dead,
+    /// The producer's epoch is being bumped to fence it, before its transaction is aborted
+    PrepareEpochFence,
+}
+
+impl TransactionState {
+    pub fn id(&self) -> i8 {
+        match self {
+            TransactionState::Empty => 0,
+            TransactionState::Ongoing => 1,
+            TransactionState::PrepareCommit => 2,
+            TransactionState::PrepareAbort => 3,
+            TransactionState::CompleteCommit => 4,
+            TransactionState::CompleteAbort => 5,
+            TransactionState::Dead => 6,
+            TransactionState::PrepareEpochFence => 7,
+        }
+    }
+
+    /// The name of the state, as it's shown to admin clients
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransactionState::Empty => "Empty",
+            TransactionState::Ongoing => "Ongoing",
+            TransactionState::PrepareCommit => "PrepareCommit",
+            TransactionState::PrepareAbort => "PrepareAbort",
+            TransactionState::CompleteCommit => "CompleteCommit",
+            TransactionState::CompleteAbort => "CompleteAbort",
+            TransactionState::Dead => "Dead",
+            TransactionState::PrepareEpochFence => "PrepareEpochFence",
+        }
+    }
+
+    /// Whether there's a state with the name, which is case sensitive
+    pub fn is_valid_name(name: &str) -> bool {
+        (0..=7).any(|id| TransactionState::from_id(id).name() == name)
+    }
+
+    /// The state with the id, where an unknown id is treated as a transactional id that's gone
+    pub fn from_id(id: i8) -> TransactionState {
+        match id {
+            0 => TransactionState::Empty,
+            1 => TransactionState::Ongoing,
+            2 => TransactionState::PrepareCommit,
+            3 => TransactionState::PrepareAbort,
+            4 => TransactionState::CompleteCommit,
+            5 => TransactionState::CompleteAbort,
+            7 => TransactionState::PrepareEpochFence,
+            _ => TransactionState::Dead,
+        }
+    }
+}
+
+/// The state of a transactional producer's current transaction, which the coordinator keeps for each transactional id
+#[derive(Debug)]
+pub struct TransactionMetadata {
+    transactional_id: String,
+    producer_id: i64,
+    producer_epoch: i16,
+    timeout_ms: i32,
+    state: TransactionState,
+    /// The partitions the producer has added to its current transaction
+    partitions: BTreeSet<TopicPartition>,
+    /// When the producer first added partitions to its current transaction, or -1 if it has none
+    start_timestamp: i64,
+    last_update_timestamp: i64,
+}
+
+impl TransactionMetadata {
+    /// A transactional id that's just been given a producer id
+    pub fn new(transactional_id: String, producer_id: i64, timeout_ms: i32, timestamp: i64) -> TransactionMetadata {
+        TransactionMetadata {
+            transactional_id,
+            producer_id,
+            producer_epoch: 0,
+            timeout_ms,
+            state: TransactionState::Empty,
+            partitions: BTreeSet::new(),
+            start_timestamp: -1,
+            last_update_timestamp: timestamp,
+        }
+    }
+
+    /// The transaction as it was when it was stored in the transaction state topic
+    pub fn load(transactional_id: String, value: TransactionLogValue) -> TransactionMetadata {
+        TransactionMetadata {
+            transactional_id,
+            producer_id: value.producer_id,
+            producer_epoch: value.producer_epoch,
+            timeout_ms: value.transaction_timeout_ms,
+            state: value.state,
+            partitions: value.partitions,
+            start_timestamp: value.start_timestamp,
+            last_update_timestamp: value.last_update_timestamp,
+        }
+    }
+
+    /// The transaction as it's stored in the transaction state topic
+    pub fn to_value(&self) -> TransactionLogValue {
+        TransactionLogValue {
+            producer_id: self.producer_id,
+            producer_epoch: self.producer_epoch,
+            transaction_timeout_ms: self.timeout_ms,
+            state: self.state,
+            partitions: self.partitions.clone(),
+            last_update_timestamp: self.last_update_timestamp,
+            start_timestamp: self.start_timestamp,
+        }
+    }
+
+    pub fn transactional_id(&self) -> &str {
+        &self.transactional_id
+    }
+
+    pub fn producer_id(&self) -> i64 {
+        self.producer_id
+    }
+
+    pub fn producer_epoch(&self) -> i16 {
+        self.producer_epoch
+    }
+
+    pub fn timeout_ms(&self) -> i32 {
+        self.timeout_ms
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms.max(0) as u64)
+    }
+
+    pub fn state(&self) -> TransactionState {
+        self.state
+    }
+
+    pub fn partitions(&self) -> &BTreeSet<TopicPartition> {
+        &self.partitions
+    }
+
+    pub fn start_timestamp(&self) -> i64 {
+        self.start_timestamp
+    }
+
+    /// The marker being written to the transaction's partitions, if it's being completed
+    pub fn pending_marker(&self) -> Option<ControlRecordType> {
+        match self.state {
+            TransactionState::PrepareCommit => Some(ControlRecordType::Commit),
+            TransactionState::PrepareAbort => Some(ControlRecordType::Abort),
+            _ => None,
+        }
+    }
+
+    /// Whether the producer's epoch can't be bumped any further, so it needs a new producer id
+    pub fn is_epoch_exhausted(&self) -> bool {
+        self.producer_epoch >= i16::MAX - 1
+    }
+
+    /// Give the transactional id to a new instance of the producer, which fences older instances with the same id
+    pub fn init_producer(&mut self, producer_id: i64, producer_epoch: i16, timeout_ms: i32, timestamp: i64) {
+        self.producer_id = producer_id;
+        self.producer_epoch = producer_epoch;
+        self.timeout_ms = timeout_ms;
+        self.state = TransactionState::Empty;
+        self.partitions.clear();
+        self.start_timestamp = -1;
+        self.last_update_timestamp = timestamp;
+    }
+
+    /// Bump the epoch, so the producer that started the transaction can't make any more changes to it
+    pub fn fence_producer(&mut self) {
+        if !self.is_epoch_exhausted() {
+            self.producer_epoch += 1;
+        }
+    }
+
+    /// Add partitions to the transaction, starting it if it isn't already ongoing
+    pub fn add_partitions(&mut self, partitions: impl IntoIterator<Item = TopicPartition>, timestamp: i64) {
+        if self.state != TransactionState::Ongoing {
+            self.assert_state(&[TransactionState::Empty, TransactionState::CompleteCommit, TransactionState::CompleteAbort]);
+            self.state = TransactionState::Ongoing;
+            self.start_timestamp = timestamp;
+        }
+        self.partitions.extend(partitions);
+        self.last_update_timestamp = timestamp;
+    }
+
+    /// Start completing the transaction, before its markers are written
+    pub fn prepare_complete(&mut self, marker: ControlRecordType, timestamp: i64) {
+        self.assert_state(&[TransactionState::Ongoing, TransactionState::PrepareCommit, TransactionState::PrepareAbort]);
+        self.state = match marker {
+            ControlRecordType::Commit => TransactionState::PrepareCommit,
+            ControlRecordType::Abort => TransactionState::PrepareAbort,
+        };
+        self.last_update_timestamp = timestamp;
+    }
+
+    /// Finish completing the transaction once every partition has its marker
+    pub fn complete(&mut self, timestamp: i64) {
+        self.state = match self.pending_marker() {
+            Some(ControlRecordType::Commit) => TransactionState::CompleteCommit,
+            Some(ControlRecordType::Abort) => TransactionState::CompleteAbort,
+            None => panic!("Transaction {} can't be completed from state {:?}", self.transactional_id, self.state),
+        };
+        self.partitions.clear();
+        self.last_update_timestamp = timestamp;
+    }
+
+    fn assert_state(&self, valid_states: &[TransactionState]) {
+        if !valid_states.contains(&self.state) {
+            panic!("Transaction {} should be in one of the states {valid_states:?}, but is {:?}", self.transactional_id, self.state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transaction_lifecycle() {
+        let mut transaction = TransactionMetadata::new("txn".to_string(), 7, 60000, 1000);
+        transaction.add_partitions([TopicPartition::new("a", 0)], 2000);
+        transaction.add_partitions([TopicPartition::new("a", 1)], 3000);
+        assert_eq!(transaction.state(), TransactionState::Ongoing);
+        assert_eq!(transaction.start_timestamp(), 2000);
+        assert_eq!(transaction.partitions().len(), 2);
+
+        transaction.prepare_complete(ControlRecordType::Commit, 4000);
+        assert_eq!(transaction.pending_marker(), Some(ControlRecordType::Commit));
+        transaction.complete(5000);
+        assert_eq!(transaction.state(), TransactionState::CompleteCommit);
+        assert!(transaction.partitions().is_empty());
+
+        transaction.fence_producer();
+        assert_eq!(transaction.producer_epoch(), 1);
+        let loaded = TransactionMetadata::load("txn".to_string(), transaction.to_value());
+        assert_eq!(loaded.to_value(), transaction.to_value());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_invalid_transition() {
+        TransactionMetadata::new("txn".to_string(), 7, 60000, 1000).complete(2000);
+    }
+}
@@ -1,2 +1,12 @@
 pub mod api;
+pub mod broker;
+pub mod coordinator;
+pub mod logging;
+pub mod metadata;
+pub mod metrics;
+pub mod security;
 pub mod serialisation;
+pub mod storage;
+pub mod time;
+#[cfg(test)]
+mod testing;
@@ -0,0 +1,59 @@
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal};
+use std::sync::Mutex;
+use tracing::Subscriber;
+use tracing_subscriber::filter::{filter_fn, EnvFilter};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{fmt, Layer};
+
+/// The target of the request log, named after kafka's logger so the same filters work for both
+pub const REQUEST_LOGGER: &str = "kafka.request.logger";
+
+/// Which levels and targets are logged, using `EnvFilter` directives such as `info,kafka.request.logger=debug`
+const LOG_FILTER_VAR: &str = "KAFKA_LOG";
+/// `json` logs each event as a line of JSON, anything else logs them as text
+const LOG_FORMAT_VAR: &str = "KAFKA_LOG_FORMAT";
+/// A file the request log is written to instead of the rest of the logs, like kafka's kafka-request.log
+const REQUEST_LOG_FILE_VAR: &str = "KAFKA_REQUEST_LOG";
+
+/// Completed requests are logged at debug, and the whole of each request and response at trace,
+/// so the request log is off unless it's enabled by the filter
+const DEFAULT_FILTER: &str = "info";
+
+/// Start logging to stdout, configured by the environment
+pub fn init() -> io::Result<()> {
+    let filter = EnvFilter::try_from_env(LOG_FILTER_VAR).unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let json = env::var(LOG_FORMAT_VAR).is_ok_and(|format| format.eq_ignore_ascii_case("json"));
+
+    let (logs, request_log) = match env::var_os(REQUEST_LOG_FILE_VAR) {
+        Some(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            // both logs keep every span, so their events have the context of the connection and request they're part of
+            let logs = format_layer(json, io::stdout().is_terminal(), io::stdout)
+                .with_filter(filter_fn(|metadata| metadata.is_span() || metadata.target() != REQUEST_LOGGER));
+            let request_log = format_layer(json, false, Mutex::new(file))
+                .with_filter(filter_fn(|metadata| metadata.is_span() || metadata.target() == REQUEST_LOGGER));
+            (logs.boxed(), Some(request_log))
+        }
+        None => (format_layer(json, io::stdout().is_terminal(), io::stdout), None),
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(logs)
+        .with(request_log)
+        .init();
+    Ok(())
+}
+
+fn format_layer<S, W>(json: bool, ansi: bool, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    W: for<'writer> fmt::MakeWriter<'writer> + Send + Sync + 'static,
+{
+    match json {
+        true => fmt::layer().json().with_current_span(true).with_span_list(true).with_writer(writer).boxed(),
+        false => fmt::layer().with_ansi(ansi).with_writer(writer).boxed(),
+    }
+}
@@ -1,8 +1,43 @@
+use std::path::Path;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::info;
 use codecrafters_kafka::api::server::Server;
+use codecrafters_kafka::broker::Broker;
+use codecrafters_kafka::broker::config::BrokerConfig;
+use codecrafters_kafka::logging;
 
 #[tokio::main]
 async fn main() {
-    let server = Server::new("127.0.0.1:9092").await.unwrap();
-    println!("Server created, starting to serve...");
+    logging::init().unwrap();
+    // the broker is started with the path to its server.properties file
+    let config = match std::env::args().nth(1) {
+        Some(path) => BrokerConfig::from_properties_file(Path::new(&path)).unwrap(),
+        None => BrokerConfig::default(),
+    };
+    let address = config.listener().bind_address();
+    let broker = Broker::open(config).unwrap();
+    let server = Server::new(&address, broker).await.unwrap();
+    let shutdown = server.shutdown_handle();
+    tokio::spawn(async move {
+        wait_for_shutdown_signal().await;
+        shutdown.shutdown();
+    });
+    info!(%address, "Server created, starting to serve");
     server.serve().await;
+    info!("Server stopped");
+}
+
+/// Wait for SIGINT, or SIGTERM on unix, which is how the broker is asked to stop
+async fn wait_for_shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate = signal(SignalKind::terminate()).unwrap();
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
 }
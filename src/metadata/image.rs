@@ -0,0 +1,205 @@
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+use crate::metadata::records::{
+    ConfigRecord, MetadataRecord, PartitionRecord, ProducerIdsRecord, RemoveTopicRecord, RemoveUserScramCredentialRecord, TopicRecord,
+    UserScramCredentialRecord, BROKER_RESOURCE_TYPE, TOPIC_RESOURCE_TYPE,
+};
+use crate::security::scram::{ScramCredential, ScramMechanism};
+
+/// The current state of the cluster's metadata, built by replaying the records in the metadata log
+#[derive(Debug, Default)]
+pub struct MetadataImage {
+    topics: BTreeMap<String, TopicMetadata>,
+    topic_names: HashMap<Uuid, String>,
+    /// Dynamic broker configs by resource name, which is either a node id or empty for the cluster's defaults
+    broker_configs: HashMap<String, BTreeMap<String, String>>,
+    /// The first producer id that hasn't been allocated to a broker yet
+    next_producer_id: i64,
+    /// The SCRAM credentials of each user, by mechanism
+    scram_credentials: BTreeMap<String, BTreeMap<ScramMechanism, ScramCredential>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TopicMetadata {
+    name: String,
+    topic_id: Uuid,
+    partitions: BTreeMap<i32, PartitionMetadata>,
+    configs: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PartitionMetadata {
+    partition_index: i32,
+    replicas: Vec<i32>,
+    isr: Vec<i32>,
+    leader: i32,
+    leader_epoch: i32,
+    partition_epoch: i32,
+}
+
+impl MetadataImage {
+    pub fn apply(&mut self, record: &MetadataRecord) {
+        match record {
+            MetadataRecord::Topic(TopicRecord { name, topic_id }) => {
+                self.topic_names.insert(*topic_id, name.clone());
+                self.topics.insert(name.clone(), TopicMetadata {
+                    name: name.clone(),
+                    topic_id: *topic_id,
+                    partitions: BTreeMap::new(),
+                    configs: BTreeMap::new(),
+                });
+            }
+            MetadataRecord::Partition(partition) => {
+                let topic = self.topic_names.get(&partition.topic_id)
+                    .and_then(|name| self.topics.get_mut(name));
+                if let Some(topic) = topic {
+                    topic.partitions.insert(partition.partition_id, PartitionMetadata::from(partition));
+                }
+            }
+            MetadataRecord::Config(ConfigRecord { resource_type: TOPIC_RESOURCE_TYPE, resource_name, name, value }) => {
+                if let Some(topic) = self.topics.get_mut(resource_name) {
+                    apply_config(&mut topic.configs, name, value);
+                }
+            }
+            MetadataRecord::Config(ConfigRecord { resource_type: BROKER_RESOURCE_TYPE, resource_name, name, value }) => {
+                let configs = self.broker_configs.entry(resource_name.clone()).or_default();
+                apply_config(configs, name, value);
+            }
+            MetadataRecord::RemoveTopic(RemoveTopicRecord { topic_id }) => {
+                // the topic's partitions and configs are removed along with it
+                if let Some(name) = self.topic_names.remove(topic_id) {
+                    self.topics.remove(&name);
+                }
+            }
+            MetadataRecord::UserScramCredential(UserScramCredentialRecord { name, mechanism, salt, stored_key, server_key, iterations }) => {
+                if let Some(mechanism) = ScramMechanism::from_type(*mechanism) {
+                    let credential = ScramCredential::new(salt.clone(), *iterations, stored_key.clone(), server_key.clone());
+                    self.scram_credentials.entry(name.clone()).or_default().insert(mechanism, credential);
+                }
+            }
+            MetadataRecord::RemoveUserScramCredential(RemoveUserScramCredentialRecord { name, mechanism }) => {
+                if let (Some(credentials), Some(mechanism)) = (self.scram_credentials.get_mut(name), ScramMechanism::from_type(*mechanism)) {
+                    credentials.remove(&mechanism);
+                    if credentials.is_empty() {
+                        self.scram_credentials.remove(name);
+                    }
+                }
+            }
+            MetadataRecord::ProducerIds(ProducerIdsRecord { next_producer_id, .. }) => {
+                self.next_producer_id = *next_producer_id;
+            }
+            MetadataRecord::Config(_) | MetadataRecord::Unknown(_) => {}
+        }
+    }
+
+    pub fn topic(&self, name: &str) -> Option<&TopicMetadata> {
+        self.topics.get(name)
+    }
+
+    pub fn topic_by_id(&self, topic_id: &Uuid) -> Option<&TopicMetadata> {
+        self.topic_names.get(topic_id).and_then(|name| self.topics.get(name))
+    }
+
+    /// The dynamic configs of a broker, or of the cluster's defaults if the resource name is empty
+    pub fn broker_configs(&self, resource_name: &str) -> Option<&BTreeMap<String, String>> {
+        self.broker_configs.get(resource_name)
+    }
+
+    pub fn next_producer_id(&self) -> i64 {
+        self.next_producer_id
+    }
+
+    /// All the topics, sorted by name
+    pub fn topics(&self) -> impl Iterator<Item = &TopicMetadata> {
+        self.topics.values()
+    }
+
+    /// The SCRAM credentials of a user, by mechanism
+    pub fn scram_credentials(&self, user: &str) -> Option<&BTreeMap<ScramMechanism, ScramCredential>> {
+        self.scram_credentials.get(user)
+    }
+
+    /// Every user with SCRAM credentials, sorted by name, with their credentials by mechanism
+    pub fn scram_users(&self) -> impl Iterator<Item = (&String, &BTreeMap<ScramMechanism, ScramCredential>)> {
+        self.scram_credentials.iter()
+    }
+}
+
+fn apply_config(configs: &mut BTreeMap<String, String>, name: &str, value: &Option<String>) {
+    match value {
+        Some(value) => configs.insert(name.to_string(), value.clone()),
+        None => configs.remove(name),
+    };
+}
+
+impl TopicMetadata {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn topic_id(&self) -> Uuid {
+        self.topic_id
+    }
+
+    pub fn partition(&self, partition_index: i32) -> Option<&PartitionMetadata> {
+        self.partitions.get(&partition_index)
+    }
+
+    /// All the partitions, sorted by index
+    pub fn partitions(&self) -> impl Iterator<Item = &PartitionMetadata> {
+        self.partitions.values()
+    }
+
+    pub fn num_partitions(&self) -> usize {
+        self.partitions.len()
+    }
+
+    /// The replication factor of the topic, which is the number of replicas of its first partition
+    pub fn replication_factor(&self) -> usize {
+        self.partitions.values().next().map_or(0, |partition| partition.replicas.len())
+    }
+
+    /// The configs set for this topic, which override the broker's defaults
+    pub fn configs(&self) -> &BTreeMap<String, String> {
+        &self.configs
+    }
+}
+
+impl PartitionMetadata {
+    pub fn partition_index(&self) -> i32 {
+        self.partition_index
+    }
+
+    pub fn replicas(&self) -> &[i32] {
+        &self.replicas
+    }
+
+    pub fn isr(&self) -> &[i32] {
+        &self.isr
+    }
+
+    pub fn leader(&self) -> i32 {
+        self.leader
+    }
+
+    pub fn leader_epoch(&self) -> i32 {
+        self.leader_epoch
+    }
+
+    pub fn partition_epoch(&self) -> i32 {
+        self.partition_epoch
+    }
+}
+
+impl From<&PartitionRecord> for PartitionMetadata {
+    fn from(record: &PartitionRecord) -> Self {
+        PartitionMetadata {
+            partition_index: record.partition_id,
+            replicas: record.replicas.clone(),
+            isr: record.isr.clone(),
+            leader: record.leader,
+            leader_epoch: record.leader_epoch,
+            partition_epoch: record.partition_epoch,
+        }
+    }
+}
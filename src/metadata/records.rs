@@ -0,0 +1,226 @@
+use uuid::Uuid;
+use crate::api::request::KafkaRequestParseError;
+use crate::serialisation::{MessageVersion, ReadKafkaBytes, ReadVersionedKafkaBytes, ToKafkaBytes, ToVersionedKafkaBytes};
+use crate::serialisation::varint::VarInt;
+use crate::serialisation::versioned::{skip_tagged_fields, write_empty_tagged_fields};
+
+/// Metadata records are always encoded with the flexible encoding
+const FLEXIBLE: MessageVersion = MessageVersion::new(0, true);
+/// Metadata records are framed with their type and version, this is the version of that frame
+const FRAME_VERSION: u32 = 1;
+
+/// Config resource types used in ConfigRecords
+pub const TOPIC_RESOURCE_TYPE: i8 = 2;
+pub const BROKER_RESOURCE_TYPE: i8 = 4;
+
+/// A record in the `__cluster_metadata` log, which the cluster's metadata is built up from
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetadataRecord {
+    Topic(TopicRecord),
+    Partition(PartitionRecord),
+    Config(ConfigRecord),
+    RemoveTopic(RemoveTopicRecord),
+    UserScramCredential(UserScramCredentialRecord),
+    RemoveUserScramCredential(RemoveUserScramCredentialRecord),
+    ProducerIds(ProducerIdsRecord),
+    /// A record type we don't use, which is skipped when replaying the log
+    Unknown(u32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopicRecord {
+    pub name: String,
+    pub topic_id: Uuid,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartitionRecord {
+    pub partition_id: i32,
+    pub topic_id: Uuid,
+    pub replicas: Vec<i32>,
+    pub isr: Vec<i32>,
+    pub removing_replicas: Vec<i32>,
+    pub adding_replicas: Vec<i32>,
+    pub leader: i32,
+    pub leader_epoch: i32,
+    pub partition_epoch: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigRecord {
+    pub resource_type: i8,
+    pub resource_name: String,
+    pub name: String,
+    /// None removes the config
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoveTopicRecord {
+    pub topic_id: Uuid,
+}
+
+/// Creates or replaces a user's credential for a SCRAM mechanism, which is the mechanism's type as the protocol has it
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserScramCredentialRecord {
+    pub name: String,
+    pub mechanism: i8,
+    pub salt: Vec<u8>,
+    pub stored_key: Vec<u8>,
+    pub server_key: Vec<u8>,
+    pub iterations: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoveUserScramCredentialRecord {
+    pub name: String,
+    pub mechanism: i8,
+}
+
+/// Allocates a block of producer ids to a broker, up to the next producer id
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProducerIdsRecord {
+    pub broker_id: i32,
+    pub broker_epoch: i64,
+    pub next_producer_id: i64,
+}
+
+impl MetadataRecord {
+    fn record_type(&self) -> u32 {
+        match self {
+            MetadataRecord::Topic(_) => 2,
+            MetadataRecord::Partition(_) => 3,
+            MetadataRecord::Config(_) => 4,
+            MetadataRecord::RemoveTopic(_) => 9,
+            MetadataRecord::UserScramCredential(_) => 11,
+            MetadataRecord::RemoveUserScramCredential(_) => 12,
+            MetadataRecord::ProducerIds(_) => 15,
+            MetadataRecord::Unknown(record_type) => *record_type,
+        }
+    }
+
+    /// Parse a record from the value of a record in the metadata log
+    pub fn parse(mut value: &[u8]) -> Result<MetadataRecord, KafkaRequestParseError> {
+        let buf = &mut value;
+        let _frame_version = VarInt::read_kafka_bytes(buf)?;
+        let record_type = VarInt::read_kafka_bytes(buf)?.value();
+        let record_version = VarInt::read_kafka_bytes(buf)?.value();
+        let version = MessageVersion::new(record_version as i16, true);
+        let record = match record_type {
+            2 => MetadataRecord::Topic(TopicRecord {
+                name: String::read_versioned_kafka_bytes(buf, version)?,
+                topic_id: Uuid::read_kafka_bytes(buf)?,
+            }),
+            3 => {
+                let partition_id = i32::read_kafka_bytes(buf)?;
+                let topic_id = Uuid::read_kafka_bytes(buf)?;
+                let replicas = Vec::read_versioned_kafka_bytes(buf, version)?;
+                let isr = Vec::read_versioned_kafka_bytes(buf, version)?;
+                let removing_replicas = Vec::read_versioned_kafka_bytes(buf, version)?;
+                let adding_replicas = Vec::read_versioned_kafka_bytes(buf, version)?;
+                let leader = i32::read_kafka_bytes(buf)?;
+                let leader_epoch = i32::read_kafka_bytes(buf)?;
+                let partition_epoch = i32::read_kafka_bytes(buf)?;
+                if record_version >= 1 {
+                    // we only have one log directory, so the directory each replica is in doesn't matter
+                    let _directories: Vec<Uuid> = Vec::read_versioned_kafka_bytes(buf, version)?;
+                }
+                MetadataRecord::Partition(PartitionRecord {
+                    partition_id,
+                    topic_id,
+                    replicas,
+                    isr,
+                    removing_replicas,
+                    adding_replicas,
+                    leader,
+                    leader_epoch,
+                    partition_epoch,
+                })
+            }
+            4 => MetadataRecord::Config(ConfigRecord {
+                resource_type: i8::read_kafka_bytes(buf)?,
+                resource_name: String::read_versioned_kafka_bytes(buf, version)?,
+                name: String::read_versioned_kafka_bytes(buf, version)?,
+                value: Option::<String>::read_versioned_kafka_bytes(buf, version)?,
+            }),
+            9 => MetadataRecord::RemoveTopic(RemoveTopicRecord {
+                topic_id: Uuid::read_kafka_bytes(buf)?,
+            }),
+            11 => MetadataRecord::UserScramCredential(UserScramCredentialRecord {
+                name: String::read_versioned_kafka_bytes(buf, version)?,
+                mechanism: i8::read_kafka_bytes(buf)?,
+                salt: Vec::read_versioned_kafka_bytes(buf, version)?,
+                stored_key: Vec::read_versioned_kafka_bytes(buf, version)?,
+                server_key: Vec::read_versioned_kafka_bytes(buf, version)?,
+                iterations: i32::read_kafka_bytes(buf)?,
+            }),
+            12 => MetadataRecord::RemoveUserScramCredential(RemoveUserScramCredentialRecord {
+                name: String::read_versioned_kafka_bytes(buf, version)?,
+                mechanism: i8::read_kafka_bytes(buf)?,
+            }),
+            15 => MetadataRecord::ProducerIds(ProducerIdsRecord {
+                broker_id: i32::read_kafka_bytes(buf)?,
+                broker_epoch: i64::read_kafka_bytes(buf)?,
+                next_producer_id: i64::read_kafka_bytes(buf)?,
+            }),
+            _ => return Ok(MetadataRecord::Unknown(record_type)),
+        };
+        skip_tagged_fields(buf, version)?;
+        Ok(record)
+    }
+
+    /// Encode the record as the value of a record in the metadata log
+    pub fn to_bytes(self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        VarInt::new(FRAME_VERSION).write_kafka_bytes(&mut bytes);
+        VarInt::new(self.record_type()).write_kafka_bytes(&mut bytes);
+        // we always write version 0 of each record
+        VarInt::new(0).write_kafka_bytes(&mut bytes);
+        match self {
+            MetadataRecord::Topic(topic) => {
+                topic.name.write_versioned_kafka_bytes(&mut bytes, FLEXIBLE);
+                topic.topic_id.write_kafka_bytes(&mut bytes);
+            }
+            MetadataRecord::Partition(partition) => {
+                partition.partition_id.write_kafka_bytes(&mut bytes);
+                partition.topic_id.write_kafka_bytes(&mut bytes);
+                partition.replicas.write_versioned_kafka_bytes(&mut bytes, FLEXIBLE);
+                partition.isr.write_versioned_kafka_bytes(&mut bytes, FLEXIBLE);
+                partition.removing_replicas.write_versioned_kafka_bytes(&mut bytes, FLEXIBLE);
+                partition.adding_replicas.write_versioned_kafka_bytes(&mut bytes, FLEXIBLE);
+                partition.leader.write_kafka_bytes(&mut bytes);
+                partition.leader_epoch.write_kafka_bytes(&mut bytes);
+                partition.partition_epoch.write_kafka_bytes(&mut bytes);
+            }
+            MetadataRecord::Config(config) => {
+                config.resource_type.write_kafka_bytes(&mut bytes);
+                config.resource_name.write_versioned_kafka_bytes(&mut bytes, FLEXIBLE);
+                config.name.write_versioned_kafka_bytes(&mut bytes, FLEXIBLE);
+                config.value.write_versioned_kafka_bytes(&mut bytes, FLEXIBLE);
+            }
+            MetadataRecord::RemoveTopic(remove_topic) => {
+                remove_topic.topic_id.write_kafka_bytes(&mut bytes);
+            }
+            MetadataRecord::UserScramCredential(credential) => {
+                credential.name.write_versioned_kafka_bytes(&mut bytes, FLEXIBLE);
+                credential.mechanism.write_kafka_bytes(&mut bytes);
+                credential.salt.write_versioned_kafka_bytes(&mut bytes, FLEXIBLE);
+                credential.stored_key.write_versioned_kafka_bytes(&mut bytes, FLEXIBLE);
+                credential.server_key.write_versioned_kafka_bytes(&mut bytes, FLEXIBLE);
+                credential.iterations.write_kafka_bytes(&mut bytes);
+            }
+            MetadataRecord::RemoveUserScramCredential(remove_credential) => {
+                remove_credential.name.write_versioned_kafka_bytes(&mut bytes, FLEXIBLE);
+                remove_credential.mechanism.write_kafka_bytes(&mut bytes);
+            }
+            MetadataRecord::ProducerIds(producer_ids) => {
+                producer_ids.broker_id.write_kafka_bytes(&mut bytes);
+                producer_ids.broker_epoch.write_kafka_bytes(&mut bytes);
+                producer_ids.next_producer_id.write_kafka_bytes(&mut bytes);
+            }
+            MetadataRecord::Unknown(record_type) => unreachable!("Unknown metadata record {record_type} can't be written"),
+        }
+        write_empty_tagged_fields(&mut bytes, FLEXIBLE);
+        bytes
+    }
+}
@@ -0,0 +1,73 @@
+use std::io;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+use uuid::Uuid;
+use crate::metadata::image::MetadataImage;
+use crate::metadata::records::MetadataRecord;
+use crate::storage::log::Log;
+use crate::storage::log_manager::LogManager;
+use crate::storage::record_batch::RecordBatchBuilder;
+use crate::storage::topic_partition::TopicPartition;
+use crate::time::now_ms;
+
+/// The internal topic that the cluster's metadata is stored in
+pub const METADATA_TOPIC: &str = "__cluster_metadata";
+/// The metadata topic's id, which is fixed rather than generated like every other topic's
+const METADATA_TOPIC_ID: Uuid = Uuid::from_u128(1);
+
+/// Stores the cluster's metadata in the metadata log, keeping an image of the current metadata in memory
+#[derive(Debug)]
+pub struct MetadataStore {
+    log: Arc<Log>,
+    image: RwLock<MetadataImage>,
+}
+
+impl MetadataStore {
+    /// Load the metadata by replaying the metadata log, creating the log if it doesn't exist
+    pub fn load(log_manager: &LogManager) -> io::Result<MetadataStore> {
+        let topic_partition = TopicPartition::new(METADATA_TOPIC, 0);
+        let log = match log_manager.get_log(&topic_partition)? {
+            Some(log) => log,
+            None => log_manager.create_log(&topic_partition, METADATA_TOPIC_ID)?,
+        };
+
+        let mut image = MetadataImage::default();
+        for (header, batch) in log.read_all_batches()? {
+            // control batches only matter to the raft implementation
+            if header.is_control() {
+                continue;
+            }
+            for record in header.records(&batch)? {
+                let value = record.value().unwrap_or_default();
+                let metadata_record = MetadataRecord::parse(value)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                image.apply(&metadata_record);
+            }
+        }
+        Ok(MetadataStore { log, image: RwLock::new(image) })
+    }
+
+    pub fn image(&self) -> RwLockReadGuard<'_, MetadataImage> {
+        self.image.read().unwrap()
+    }
+
+    /// Check the current metadata to decide which records to write, then write and apply those records.
+    /// No other updates can happen in between, so the records are always based on the latest metadata
+    pub fn update<T, E: From<io::Error>>(&self, decide: impl FnOnce(&MetadataImage) -> Result<(Vec<MetadataRecord>, T), E>) -> Result<T, E> {
+        let mut image = self.image.write().unwrap();
+        let (records, result) = decide(&image)?;
+        if records.is_empty() {
+            return Ok(result);
+        }
+
+        let timestamp = now_ms();
+        let batch = records.iter()
+            .cloned()
+            .fold(RecordBatchBuilder::new(), |batch, record| batch.add_record(timestamp, None, Some(record.to_bytes())))
+            .build();
+        self.log.append(batch, 0)?;
+        for record in &records {
+            image.apply(record);
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,270 @@
+pub mod endpoint;
+
+use std::collections::HashMap;
+use std::fmt::{Display, Write};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::api::api_key::ApiKey;
+use crate::api::error_code::ErrorCode;
+use crate::broker::Broker;
+use crate::storage::log::Log;
+
+/// The upper bounds of the request latency histogram's buckets, in seconds
+const LATENCY_BUCKETS: [f64; 12] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Statistics the broker collects as it handles requests, which are exported in the Prometheus text format.
+/// Anything that can be read from the broker's state, such as the size of each log, is read when the metrics are scraped
+#[derive(Debug, Default)]
+pub struct Metrics {
+    requests: Mutex<HashMap<(ApiKey, i16), RequestMetrics>>,
+    errors: Mutex<HashMap<(ApiKey, ErrorCode), u64>>,
+    active_connections: Arc<AtomicI64>,
+    bytes_in: Mutex<HashMap<String, u64>>,
+    bytes_out: Mutex<HashMap<String, u64>>,
+}
+
+/// How many requests there have been with an API key and version, and how long they took
+#[derive(Debug, Default)]
+struct RequestMetrics {
+    count: u64,
+    total_seconds: f64,
+    /// The number of requests in each latency bucket, where the last one is for requests slower than every bucket
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+}
+
+/// Counts a connection as active until it's dropped
+#[derive(Debug)]
+pub struct ActiveConnection(Arc<AtomicI64>);
+
+impl Drop for ActiveConnection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    pub fn connection_opened(&self) -> ActiveConnection {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+        ActiveConnection(self.active_connections.clone())
+    }
+
+    /// Record a request once its response has been sent, along with the error of the request as a whole if it has one
+    pub fn record_request(&self, api_key: ApiKey, api_version: i16, elapsed: Duration, error_code: Option<ErrorCode>) {
+        let seconds = elapsed.as_secs_f64();
+        let mut requests = self.requests.lock().unwrap();
+        let request = requests.entry((api_key, api_version)).or_default();
+        request.count += 1;
+        request.total_seconds += seconds;
+        let bucket = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound).unwrap_or(LATENCY_BUCKETS.len());
+        request.buckets[bucket] += 1;
+        drop(requests);
+
+        if let Some(error_code) = error_code {
+            *self.errors.lock().unwrap().entry((api_key, error_code)).or_default() += 1;
+        }
+    }
+
+    pub fn record_bytes_in(&self, topic: &str, bytes: usize) {
+        add_bytes(&self.bytes_in, topic, bytes);
+    }
+
+    pub fn record_bytes_out(&self, topic: &str, bytes: usize) {
+        add_bytes(&self.bytes_out, topic, bytes);
+    }
+
+    /// Render every metric in the Prometheus text exposition format
+    pub fn render(&self, broker: &Broker) -> String {
+        let mut out = String::new();
+        self.render_requests(&mut out);
+
+        write_header(&mut out, "kafka_server_active_connections", "gauge", "The number of open client connections");
+        write_sample(&mut out, "kafka_server_active_connections", &[], self.active_connections.load(Ordering::Relaxed));
+
+        for (name, help, bytes) in [
+            ("kafka_server_bytes_in_total", "Bytes of record batches produced to each topic", &self.bytes_in),
+            ("kafka_server_bytes_out_total", "Bytes of record batches fetched from each topic", &self.bytes_out),
+        ] {
+            write_header(&mut out, name, "counter", help);
+            let mut bytes: Vec<_> = bytes.lock().unwrap().iter().map(|(topic, bytes)| (topic.clone(), *bytes)).collect();
+            bytes.sort();
+            for (topic, bytes) in bytes {
+                write_sample(&mut out, name, &[("topic", &topic)], bytes);
+            }
+        }
+
+        render_logs(&mut out, broker);
+        render_consumer_lag(&mut out, broker);
+
+        write_header(&mut out, "kafka_server_purgatory_size", "gauge", "The number of delayed operations waiting in each purgatory");
+        for (operation, size) in [
+            ("Fetch", broker.fetch_purgatory().watched()),
+            ("Produce", broker.produce_purgatory().watched()),
+            ("Rebalance", broker.group_coordinator().rebalance_purgatory().watched()),
+        ] {
+            write_sample(&mut out, "kafka_server_purgatory_size", &[("delayed_operation", operation)], size);
+        }
+        out
+    }
+
+    fn render_requests(&self, out: &mut String) {
+        let requests = self.requests.lock().unwrap();
+        let mut keys: Vec<_> = requests.keys().copied().collect();
+        keys.sort_by_key(|(api_key, api_version)| (format!("{api_key:?}"), *api_version));
+
+        write_header(out, "kafka_network_requests_total", "counter", "The number of requests handled with each API key and version");
+        for key in &keys {
+            let (request, version) = request_labels(key);
+            write_sample(out, "kafka_network_requests_total", &[("request", &request), ("version", &version)], requests[key].count);
+        }
+
+        let name = "kafka_network_request_duration_seconds";
+        write_header(out, name, "histogram", "How long requests took from being read until their response was sent");
+        for key in &keys {
+            let (request, version) = request_labels(key);
+            let metrics = &requests[key];
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().map(f64::to_string).chain(["+Inf".to_string()]).zip(metrics.buckets) {
+                cumulative += count;
+                write_sample(out, &format!("{name}_bucket"), &[("request", &request), ("version", &version), ("le", bound.as_str())], cumulative);
+            }
+            write_sample(out, &format!("{name}_sum"), &[("request", &request), ("version", &version)], metrics.total_seconds);
+            write_sample(out, &format!("{name}_count"), &[("request", &request), ("version", &version)], metrics.count);
+        }
+        drop(requests);
+
+        write_header(out, "kafka_network_errors_total", "counter", "The number of requests that failed as a whole with each error code");
+        let mut errors: Vec<(String, String, u64)> = self.errors.lock().unwrap().iter()
+            .map(|((api_key, error_code), count)| (format!("{api_key:?}"), format!("{error_code:?}"), *count))
+            .collect();
+        errors.sort();
+        for (request, error, count) in errors {
+            write_sample(out, "kafka_network_errors_total", &[("request", &request), ("error", &error)], count);
+        }
+    }
+}
+
+fn add_bytes(bytes_per_topic: &Mutex<HashMap<String, u64>>, topic: &str, bytes: usize) {
+    let mut bytes_per_topic = bytes_per_topic.lock().unwrap();
+    match bytes_per_topic.get_mut(topic) {
+        Some(total) => *total += bytes as u64,
+        None => {
+            bytes_per_topic.insert(topic.to_string(), bytes as u64);
+        }
+    }
+}
+
+fn request_labels((api_key, api_version): &(ApiKey, i16)) -> (String, String) {
+    (format!("{api_key:?}"), api_version.to_string())
+}
+
+/// Reads one of a log's gauges
+type LogGauge = fn(&Log) -> i64;
+
+/// The size and offsets of every open log. Logs that haven't been used since the broker started aren't opened just to report them,
+/// since opening a log reads its segments and keeps their files open
+fn render_logs(out: &mut String, broker: &Broker) {
+    let mut logs = broker.log_manager().open_logs();
+    logs.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let logs: Vec<_> = logs.into_iter()
+        .map(|(topic_partition, log)| (topic_partition.topic().to_string(), topic_partition.partition().to_string(), log))
+        .collect();
+
+    let gauges: [(&str, &str, LogGauge); 4] = [
+        ("kafka_log_size_bytes", "The size of the partition's log segments", |log| log.size() as i64),
+        ("kafka_log_start_offset", "The first offset in the partition's log", Log::log_start_offset),
+        ("kafka_log_end_offset", "The offset the next record appended to the partition will have", Log::log_end_offset),
+        ("kafka_log_high_watermark", "The offset up to which records in the partition can be consumed", Log::high_watermark),
+    ];
+    for (name, help, value) in gauges {
+        write_header(out, name, "gauge", help);
+        for (topic, partition, log) in &logs {
+            write_sample(out, name, &[("topic", topic), ("partition", partition)], value(log));
+        }
+    }
+}
+
+/// How far each group's committed offset is behind the high watermark of each partition it has committed to,
+/// for the partitions whose logs are open
+fn render_consumer_lag(out: &mut String, broker: &Broker) {
+    write_header(out, "kafka_consumer_group_lag", "gauge", "The number of records after each group's committed offset for a partition");
+    let logs: HashMap<_, _> = broker.log_manager().open_logs().into_iter().collect();
+    let mut committed = broker.group_coordinator().committed_offsets();
+    committed.sort_by(|(a_group, a_partition, _), (b_group, b_partition, _)| (a_group, a_partition).cmp(&(b_group, b_partition)));
+    for (group_id, topic_partition, offset) in committed {
+        let Some(log) = logs.get(&topic_partition) else {
+            continue;
+        };
+        let labels = [("group", group_id.as_str()), ("topic", topic_partition.topic()), ("partition", &topic_partition.partition().to_string())];
+        write_sample(out, "kafka_consumer_group_lag", &labels, (log.high_watermark() - offset).max(0));
+    }
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn write_sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl Display) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        let labels: Vec<String> = labels.iter().map(|(label, value)| format!("{label}=\"{}\"", escape_label_value(value))).collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(out, " {value}");
+}
+
+/// Backslashes, quotes and newlines have to be escaped in label values
+fn escape_label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::topic_partition::TopicPartition;
+    use crate::testing::{open_broker, open_broker_in};
+
+    #[test]
+    fn test_write_sample() {
+        let mut out = String::new();
+        write_sample(&mut out, "kafka_server_active_connections", &[], 3);
+        write_sample(&mut out, "kafka_server_bytes_in_total", &[("topic", "a\"b\\c")], 42);
+        assert_eq!(out, "kafka_server_active_connections 3\nkafka_server_bytes_in_total{topic=\"a\\\"b\\\\c\"} 42\n");
+    }
+
+    #[test]
+    fn test_request_histogram() {
+        let metrics = Metrics::new();
+        metrics.record_request(ApiKey::Produce, 9, Duration::from_millis(3), Some(ErrorCode::NoError));
+        metrics.record_request(ApiKey::Produce, 9, Duration::from_secs(20), None);
+        let mut out = String::new();
+        metrics.render_requests(&mut out);
+        assert!(out.contains("kafka_network_requests_total{request=\"Produce\",version=\"9\"} 2\n"));
+        assert!(out.contains("kafka_network_request_duration_seconds_bucket{request=\"Produce\",version=\"9\",le=\"0.001\"} 0\n"));
+        assert!(out.contains("kafka_network_request_duration_seconds_bucket{request=\"Produce\",version=\"9\",le=\"0.005\"} 1\n"));
+        assert!(out.contains("kafka_network_request_duration_seconds_bucket{request=\"Produce\",version=\"9\",le=\"+Inf\"} 2\n"));
+        assert!(out.contains("kafka_network_errors_total{request=\"Produce\",error=\"NoError\"} 1\n"));
+    }
+
+    #[tokio::test]
+    async fn test_only_open_logs_are_rendered() {
+        let (broker, log_dir) = open_broker("");
+        broker.auto_create_topic("events").unwrap();
+        broker.shutdown().unwrap();
+        drop(broker);
+
+        // after a clean shutdown the logs are only opened once they're used
+        let broker = open_broker_in(&log_dir, "");
+        let topic_partition = TopicPartition::new("events", 0);
+        assert!(!broker.metrics().render(&broker).contains("topic=\"events\""));
+        assert!(broker.log_manager().open_logs().iter().all(|(open, _)| *open != topic_partition));
+
+        broker.log_manager().get_log(&topic_partition).unwrap().unwrap();
+        assert!(broker.metrics().render(&broker).contains("kafka_log_end_offset{topic=\"events\",partition=\"0\"} 0\n"));
+    }
+}
@@ -0,0 +1,62 @@
+use std::io;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, error, info};
+use crate::broker::Broker;
+
+/// Requests are only a request line and headers, anything bigger isn't a scrape
+const MAX_REQUEST_SIZE: usize = 8192;
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Serve the broker's metrics over HTTP at `/metrics`, for Prometheus to scrape
+pub async fn serve_metrics(address: String, broker: Arc<Broker>) {
+    let listener = match TcpListener::bind(&address).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!(%address, %err, "Failed to start the metrics endpoint");
+            return;
+        }
+    };
+    info!(%address, "Serving metrics at /metrics");
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let broker = broker.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = handle_scrape(stream, &broker).await {
+                        debug!(%err, "Failed to serve metrics");
+                    }
+                });
+            }
+            Err(err) => error!(%err, "Failed to accept a metrics connection"),
+        }
+    }
+}
+
+/// Answer a single HTTP request, then close the connection
+async fn handle_scrape(mut stream: TcpStream, broker: &Broker) -> io::Result<()> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 || request.len() + read > MAX_REQUEST_SIZE {
+            return Ok(());
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+
+    let request_line = String::from_utf8_lossy(&request);
+    let mut parts = request_line.split_whitespace();
+    let response = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => http_response("200 OK", CONTENT_TYPE, &broker.metrics().render(broker)),
+        (Some("GET"), _) => http_response("404 Not Found", "text/plain", "Not Found\n"),
+        _ => http_response("405 Method Not Allowed", "text/plain", "Method Not Allowed\n"),
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn http_response(status: &str, content_type: &str, body: &str) -> String {
+    format!("HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}", body.len())
+}
@@ -0,0 +1,84 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+use regex::Regex;
+use crate::broker::config::BrokerConfig;
+use crate::metadata::store::MetadataStore;
+use crate::security::sasl::SaslMechanism;
+use crate::security::scram::{constant_time_eq, ScramCredential, ScramMechanism};
+
+/// The users that can authenticate with SASL, with their passwords for PLAIN and their salted credentials for SCRAM
+#[derive(Debug, Default)]
+pub struct CredentialStore {
+    plain_passwords: HashMap<String, String>,
+    /// The SCRAM credentials derived from the JAAS configs
+    scram_credentials: HashMap<(String, ScramMechanism), ScramCredential>,
+    /// The SCRAM credentials stored in the metadata by AlterUserScramCredentials, which take precedence over the JAAS configs
+    stored_scram_credentials: RwLock<HashMap<(String, ScramMechanism), ScramCredential>>,
+}
+
+impl CredentialStore {
+    /// The users in the JAAS configs of the listener's mechanisms, whose SCRAM credentials are derived when the broker starts
+    pub fn new(config: &BrokerConfig) -> CredentialStore {
+        CredentialStore::from_users(config.sasl_users().iter().cloned())
+    }
+
+    pub fn from_users(users: impl IntoIterator<Item = (SaslMechanism, String, String)>) -> CredentialStore {
+        let mut plain_passwords = HashMap::new();
+        let mut scram_credentials = HashMap::new();
+        for (mechanism, username, password) in users {
+            match mechanism {
+                SaslMechanism::Plain => {
+                    plain_passwords.insert(username, password);
+                }
+                SaslMechanism::Scram(mechanism) => {
+                    let credential = ScramCredential::from_password(mechanism, &password, mechanism.min_iterations());
+                    scram_credentials.insert((username, mechanism), credential);
+                }
+            }
+        }
+        CredentialStore { plain_passwords, scram_credentials, stored_scram_credentials: RwLock::default() }
+    }
+
+    /// Replace the stored SCRAM credentials with those in the metadata, after they've been altered.
+    /// The image is read while the credentials are locked, so a slower update can't overwrite a newer one with older credentials
+    pub fn load_scram_credentials(&self, metadata: &MetadataStore) {
+        let mut stored = self.stored_scram_credentials.write().unwrap();
+        *stored = metadata.image().scram_users()
+            .flat_map(|(user, credentials)| credentials.iter()
+                .map(|(mechanism, credential)| ((user.clone(), *mechanism), credential.clone())))
+            .collect();
+    }
+
+    pub fn authenticate_plain(&self, username: &str, password: &str) -> bool {
+        self.plain_passwords.get(username)
+            .is_some_and(|expected| constant_time_eq(expected.as_bytes(), password.as_bytes()))
+    }
+
+    pub fn scram_credential(&self, username: &str, mechanism: ScramMechanism) -> Option<ScramCredential> {
+        let key = (username.to_string(), mechanism);
+        self.stored_scram_credentials.read().unwrap().get(&key)
+            .or_else(|| self.scram_credentials.get(&key))
+            .cloned()
+    }
+
+    /// The users with SCRAM credentials in the JAAS configs
+    pub fn static_scram_users(&self) -> impl Iterator<Item = &str> {
+        self.scram_credentials.keys().map(|(username, _)| username.as_str())
+    }
+
+    /// A user's SCRAM credentials from the JAAS configs, which AlterUserScramCredentials can override but not delete
+    pub fn static_scram_credentials(&self, username: &str) -> BTreeMap<ScramMechanism, &ScramCredential> {
+        ScramMechanism::ALL.into_iter()
+            .filter_map(|mechanism| self.scram_credentials.get(&(username.to_string(), mechanism)).map(|credential| (mechanism, credential)))
+            .collect()
+    }
+}
+
+/// The `user_<name>="<password>"` options of a JAAS config, such as
+/// `org.apache.kafka.common.security.plain.PlainLoginModule required user_alice="alice-secret";`
+pub fn jaas_users(jaas_config: &str) -> Vec<(String, String)> {
+    let option = Regex::new(r#"user_([^\s=]+)\s*=\s*"([^"]*)""#).unwrap();
+    option.captures_iter(jaas_config)
+        .map(|captures| (captures[1].to_string(), captures[2].to_string()))
+        .collect()
+}
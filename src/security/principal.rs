@@ -0,0 +1,215 @@
+use std::fmt;
+use regex::Regex;
+use thiserror::Error;
+use x509_parser::certificate::X509Certificate;
+use x509_parser::oid_registry::{
+    OID_DOMAIN_COMPONENT, OID_USERID, OID_X509_COMMON_NAME, OID_X509_COUNTRY_NAME, OID_X509_LOCALITY_NAME, OID_X509_ORGANIZATIONAL_UNIT,
+    OID_X509_ORGANIZATION_NAME, OID_X509_STATE_OR_PROVINCE_NAME, OID_X509_STREET_ADDRESS,
+};
+use x509_parser::prelude::FromDer;
+use x509_parser::x509::{AttributeTypeAndValue, X509Name};
+
+/// Who a client is authenticated as, such as `User:CN=client` for a client certificate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KafkaPrincipal {
+    principal_type: String,
+    name: String,
+}
+
+impl KafkaPrincipal {
+    pub const USER_TYPE: &'static str = "User";
+
+    pub fn user(name: impl Into<String>) -> KafkaPrincipal {
+        KafkaPrincipal { principal_type: KafkaPrincipal::USER_TYPE.to_string(), name: name.into() }
+    }
+
+    /// The principal of clients that haven't authenticated, such as those on a plaintext listener
+    pub fn anonymous() -> KafkaPrincipal {
+        KafkaPrincipal::user("ANONYMOUS")
+    }
+
+    pub fn principal_type(&self) -> &str {
+        &self.principal_type
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl fmt::Display for KafkaPrincipal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.principal_type, self.name)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PrincipalError {
+    #[error("Invalid principal mapping rule: {0}")]
+    InvalidRule(String),
+    #[error("No principal mapping rule matches the distinguished name {0}")]
+    NoMatchingRule(String),
+    #[error("Invalid client certificate: {0}")]
+    InvalidCertificate(String),
+}
+
+/// A rule from `ssl.principal.mapping.rules`, which turns the distinguished name of a client certificate into a principal name
+#[derive(Debug, Clone)]
+pub enum PrincipalMappingRule {
+    /// Use the whole distinguished name
+    Default,
+    /// Like kafka, a rule only applies if its pattern matches the whole distinguished name,
+    /// then every match of the pattern is replaced, optionally changing the case of the result
+    Rule { pattern: Regex, replacement: String, case: Option<Case> },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Case {
+    Lower,
+    Upper,
+}
+
+impl PrincipalMappingRule {
+    /// Parse a list of rules, such as `RULE:^CN=(.*?),OU=ServiceUsers.*$/$1/L,DEFAULT`.
+    /// The patterns can contain commas, so the list is split as the rules are read rather than on every comma
+    pub fn parse_rules(rules: &str) -> Result<Vec<PrincipalMappingRule>, PrincipalError> {
+        let invalid = || PrincipalError::InvalidRule(rules.to_string());
+        let mut parsed = Vec::new();
+        let mut rest = rules.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix("DEFAULT") {
+                parsed.push(PrincipalMappingRule::Default);
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("RULE:") {
+                let (pattern, after) = split_at_unescaped_slash(after).ok_or_else(invalid)?;
+                let (replacement, after) = split_at_unescaped_slash(after).ok_or_else(invalid)?;
+                let (case, after) = match after.chars().next() {
+                    Some('L') => (Some(Case::Lower), &after[1..]),
+                    Some('U') => (Some(Case::Upper), &after[1..]),
+                    _ => (None, after),
+                };
+                // the pattern is anchored, since a rule only applies if it matches the whole name
+                let pattern = Regex::new(&format!("^(?:{})$", pattern.replace("\\/", "/"))).map_err(|_| invalid())?;
+                parsed.push(PrincipalMappingRule::Rule { pattern, replacement: replacement.replace("\\/", "/"), case });
+                rest = after;
+            } else {
+                return Err(invalid());
+            }
+            if !rest.is_empty() && !rest.trim_start().starts_with(',') {
+                return Err(invalid());
+            }
+            rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        }
+        Ok(parsed)
+    }
+
+    fn apply(&self, distinguished_name: &str) -> Option<String> {
+        match self {
+            PrincipalMappingRule::Default => Some(distinguished_name.to_string()),
+            PrincipalMappingRule::Rule { pattern, replacement, case } => {
+                if !pattern.is_match(distinguished_name) {
+                    return None;
+                }
+                let name = pattern.replace_all(distinguished_name, replacement.as_str()).into_owned();
+                Some(match case {
+                    Some(Case::Lower) => name.to_lowercase(),
+                    Some(Case::Upper) => name.to_uppercase(),
+                    None => name,
+                })
+            }
+        }
+    }
+}
+
+/// Split off the text up to the next slash that isn't escaped with a backslash
+fn split_at_unescaped_slash(text: &str) -> Option<(&str, &str)> {
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            '/' if !escaped => return Some((&text[..index], &text[index + 1..])),
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// The principal of a client that authenticated with the certificate, named by the first mapping rule that matches its subject
+pub fn principal_from_certificate(certificate: &[u8], rules: &[PrincipalMappingRule]) -> Result<KafkaPrincipal, PrincipalError> {
+    let (_, certificate) = X509Certificate::from_der(certificate)
+        .map_err(|err| PrincipalError::InvalidCertificate(err.to_string()))?;
+    let distinguished_name = rfc2253_name(certificate.subject());
+    rules.iter()
+        .find_map(|rule| rule.apply(&distinguished_name))
+        .map(KafkaPrincipal::user)
+        .ok_or(PrincipalError::NoMatchingRule(distinguished_name))
+}
+
+/// Format the name the way java does for kafka's principals, which is RFC 2253, where the most specific name comes first
+fn rfc2253_name(name: &X509Name) -> String {
+    let mut rdns: Vec<String> = name.iter_rdn()
+        .map(|rdn| rdn.iter().map(rfc2253_attribute).collect::<Vec<_>>().join("+"))
+        .collect();
+    rdns.reverse();
+    rdns.join(",")
+}
+
+/// An attribute with the short name RFC 2253 gives its type, or the type's OID if it doesn't have one
+fn rfc2253_attribute(attribute: &AttributeTypeAndValue) -> String {
+    let oid = attribute.attr_type();
+    let attribute_type = [
+        (&OID_X509_COMMON_NAME, "CN"),
+        (&OID_X509_ORGANIZATIONAL_UNIT, "OU"),
+        (&OID_X509_ORGANIZATION_NAME, "O"),
+        (&OID_X509_LOCALITY_NAME, "L"),
+        (&OID_X509_STATE_OR_PROVINCE_NAME, "ST"),
+        (&OID_X509_COUNTRY_NAME, "C"),
+        (&OID_X509_STREET_ADDRESS, "STREET"),
+        (&OID_DOMAIN_COMPONENT, "DC"),
+        (&OID_USERID, "UID"),
+    ].into_iter()
+        .find(|(known, _)| *known == oid)
+        .map_or_else(|| oid.to_id_string(), |(_, short_name)| short_name.to_string());
+    let value = attribute.as_str().map(escape_rfc2253_value).unwrap_or_default();
+    format!("{attribute_type}={value}")
+}
+
+/// Escape the characters that are special in RFC 2253 names, along with spaces at either end and a leading #
+fn escape_rfc2253_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    let last = value.chars().count().saturating_sub(1);
+    for (index, c) in value.chars().enumerate() {
+        let special = matches!(c, ',' | '+' | '"' | '\\' | '<' | '>' | ';')
+            || (c == ' ' && (index == 0 || index == last))
+            || (c == '#' && index == 0);
+        if special {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mapping_rules() {
+        let rules = PrincipalMappingRule::parse_rules("RULE:^CN=(.*?),OU=ServiceUsers.*$/$1/L, RULE:^CN=([^,]*),O=(.*)$/$1@$2/,DEFAULT").unwrap();
+        let map = |name: &str| rules.iter().find_map(|rule| rule.apply(name));
+        assert_eq!(map("CN=Kafka-Client,OU=ServiceUsers,O=Org").as_deref(), Some("kafka-client"));
+        assert_eq!(map("CN=admin,O=Org").as_deref(), Some("admin@Org"));
+        assert_eq!(map("CN=other,OU=People").as_deref(), Some("CN=other,OU=People"));
+
+        assert!(PrincipalMappingRule::parse_rules("RULE:missing-slashes").is_err());
+        assert!(PrincipalMappingRule::parse_rules("DEFAULTS").is_err());
+    }
+
+    #[test]
+    fn test_escape_rfc2253_value() {
+        assert_eq!(escape_rfc2253_value("Acme, Inc"), "Acme\\, Inc");
+        assert_eq!(escape_rfc2253_value(" #x "), "\\ #x\\ ");
+        assert_eq!(escape_rfc2253_value("#x"), "\\#x");
+    }
+}
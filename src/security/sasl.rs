@@ -0,0 +1,249 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use crate::api::api_key::ApiKey;
+use crate::security::credentials::CredentialStore;
+use crate::security::principal::KafkaPrincipal;
+use crate::security::scram::{random_nonce, ClientFirstMessage, ScramMechanism, ScramServer};
+
+#[derive(Debug, Error)]
+pub enum SaslError {
+    #[error("Unsupported SASL mechanism {0}")]
+    UnsupportedMechanism(String),
+    #[error("{0}")]
+    IllegalState(String),
+    #[error("{0}")]
+    AuthenticationFailed(String),
+}
+
+/// The SASL mechanisms clients can authenticate with
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SaslMechanism {
+    Plain,
+    Scram(ScramMechanism),
+}
+
+impl SaslMechanism {
+    pub fn from_name(name: &str) -> Option<SaslMechanism> {
+        match name {
+            "PLAIN" => Some(SaslMechanism::Plain),
+            "SCRAM-SHA-256" => Some(SaslMechanism::Scram(ScramMechanism::ScramSha256)),
+            "SCRAM-SHA-512" => Some(SaslMechanism::Scram(ScramMechanism::ScramSha512)),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SaslMechanism::Plain => "PLAIN",
+            SaslMechanism::Scram(mechanism) => mechanism.name(),
+        }
+    }
+}
+
+impl fmt::Display for SaslMechanism {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The server's side of SASL authentication on a connection, which starts with a SaslHandshake choosing the mechanism,
+/// followed by as many SaslAuthenticate requests as the mechanism needs. Clients re-authenticate the same way (KIP-368)
+/// before their session expires, and once it has they can't send anything else until they do
+#[derive(Debug)]
+pub struct SaslAuthenticator {
+    enabled_mechanisms: Vec<SaslMechanism>,
+    /// How long a session lasts before the client has to re-authenticate, from `connections.max.reauth.ms`
+    max_session_lifetime: Option<Duration>,
+    state: SaslState,
+    authenticated: Option<Authenticated>,
+    /// Once authentication has failed, the connection is closed rather than letting the client try again on it
+    failed: bool,
+}
+
+#[derive(Debug)]
+enum SaslState {
+    AwaitingHandshake,
+    AwaitingAuthenticate(SaslMechanism),
+    Scram(ScramMechanism, ScramServer),
+}
+
+#[derive(Debug)]
+struct Authenticated {
+    principal: KafkaPrincipal,
+    mechanism: SaslMechanism,
+    expires: Option<Instant>,
+}
+
+/// What to send back to a SaslAuthenticate request that succeeded
+#[derive(Debug)]
+pub struct AuthenticateResponse {
+    auth_bytes: Vec<u8>,
+    /// How long until the client has to re-authenticate, which is 0 until it's authenticated or if it never has to
+    session_lifetime_ms: i64,
+}
+
+impl AuthenticateResponse {
+    pub fn auth_bytes(&self) -> &[u8] {
+        &self.auth_bytes
+    }
+
+    pub fn session_lifetime_ms(&self) -> i64 {
+        self.session_lifetime_ms
+    }
+}
+
+impl SaslAuthenticator {
+    pub fn new(enabled_mechanisms: Vec<SaslMechanism>, max_session_lifetime: Option<Duration>) -> SaslAuthenticator {
+        SaslAuthenticator {
+            enabled_mechanisms,
+            max_session_lifetime,
+            state: SaslState::AwaitingHandshake,
+            authenticated: None,
+            failed: false,
+        }
+    }
+
+    /// The principal the client authenticated as, if it has
+    pub fn principal(&self) -> Option<&KafkaPrincipal> {
+        self.authenticated.as_ref().map(|authenticated| &authenticated.principal)
+    }
+
+    /// Only ApiVersions and the SASL APIs can be sent before the client has authenticated, or once its session has expired
+    pub fn allows(&self, api_key: ApiKey, now: Instant) -> bool {
+        if self.failed {
+            return false;
+        }
+        match api_key {
+            ApiKey::ApiVersions | ApiKey::SaslHandshake | ApiKey::SaslAuthenticate => true,
+            _ => self.authenticated.as_ref()
+                .is_some_and(|authenticated| authenticated.expires.map_or(true, |expires| now < expires)),
+        }
+    }
+
+    pub fn handshake(&mut self, mechanism: &str) -> Result<(), SaslError> {
+        if !matches!(self.state, SaslState::AwaitingHandshake) {
+            return Err(SaslError::IllegalState("Unexpected SaslHandshake request while authenticating".to_string()));
+        }
+        let mechanism = SaslMechanism::from_name(mechanism)
+            .filter(|mechanism| self.enabled_mechanisms.contains(mechanism))
+            .ok_or_else(|| SaslError::UnsupportedMechanism(mechanism.to_string()))?;
+        self.state = SaslState::AwaitingAuthenticate(mechanism);
+        Ok(())
+    }
+
+    pub fn authenticate(&mut self, auth_bytes: &[u8], credentials: &CredentialStore) -> Result<AuthenticateResponse, SaslError> {
+        let result = self.step(auth_bytes, credentials);
+        if let Err(SaslError::AuthenticationFailed(_)) = result {
+            self.failed = true;
+        }
+        if result.is_err() {
+            self.state = SaslState::AwaitingHandshake;
+        }
+        result
+    }
+
+    fn step(&mut self, auth_bytes: &[u8], credentials: &CredentialStore) -> Result<AuthenticateResponse, SaslError> {
+        let message = std::str::from_utf8(auth_bytes)
+            .map_err(|_| SaslError::AuthenticationFailed("SASL message isn't valid UTF-8".to_string()))?;
+        match std::mem::replace(&mut self.state, SaslState::AwaitingHandshake) {
+            SaslState::AwaitingHandshake => {
+                let message = match self.authenticated {
+                    Some(_) => "SaslAuthenticate request received after successful authentication",
+                    None => "SaslAuthenticate request received before SaslHandshake",
+                };
+                Err(SaslError::IllegalState(message.to_string()))
+            }
+            SaslState::AwaitingAuthenticate(SaslMechanism::Plain) => {
+                let username = authenticate_plain(message, credentials)?;
+                self.complete(SaslMechanism::Plain, username, Vec::new())
+            }
+            SaslState::AwaitingAuthenticate(SaslMechanism::Scram(mechanism)) => {
+                let client_first = ClientFirstMessage::parse(message)?;
+                let credential = credentials.scram_credential(client_first.username(), mechanism)
+                    .ok_or_else(|| SaslError::AuthenticationFailed("Authentication failed: Invalid user credentials".to_string()))?;
+                let mut server = ScramServer::new(random_nonce());
+                let server_first = server.client_first(client_first, credential)?;
+                self.state = SaslState::Scram(mechanism, server);
+                Ok(AuthenticateResponse { auth_bytes: server_first.into_bytes(), session_lifetime_ms: 0 })
+            }
+            SaslState::Scram(mechanism, mut server) => {
+                let (username, server_final) = server.client_final(mechanism, message)?;
+                self.complete(SaslMechanism::Scram(mechanism), username, server_final.into_bytes())
+            }
+        }
+    }
+
+    /// The client has proved who it is. When it's re-authenticating, it has to be as the same principal with the same mechanism
+    fn complete(&mut self, mechanism: SaslMechanism, username: String, auth_bytes: Vec<u8>) -> Result<AuthenticateResponse, SaslError> {
+        let principal = KafkaPrincipal::user(username);
+        if let Some(authenticated) = &self.authenticated {
+            if authenticated.principal != principal {
+                return Err(SaslError::AuthenticationFailed(format!(
+                    "Cannot change principals during re-authentication from {}: {principal}", authenticated.principal
+                )));
+            }
+            if authenticated.mechanism != mechanism {
+                return Err(SaslError::AuthenticationFailed(format!(
+                    "Cannot change mechanisms during re-authentication from {}: {mechanism}", authenticated.mechanism
+                )));
+            }
+        }
+        let session_lifetime_ms = self.max_session_lifetime.map_or(0, |lifetime| lifetime.as_millis() as i64);
+        self.authenticated = Some(Authenticated {
+            principal,
+            mechanism,
+            expires: self.max_session_lifetime.map(|lifetime| Instant::now() + lifetime),
+        });
+        Ok(AuthenticateResponse { auth_bytes, session_lifetime_ms })
+    }
+}
+
+/// A PLAIN message is the authorization id, user name and password, separated by NULs,
+/// where the authorization id has to be empty or the same as the user name
+fn authenticate_plain(message: &str, credentials: &CredentialStore) -> Result<String, SaslError> {
+    let mut tokens = message.split('\0');
+    let (Some(authorization_id), Some(username), Some(password), None) = (tokens.next(), tokens.next(), tokens.next(), tokens.next()) else {
+        return Err(SaslError::AuthenticationFailed("Invalid SASL/PLAIN response: expected 3 tokens".to_string()));
+    };
+    if username.is_empty() || password.is_empty() {
+        return Err(SaslError::AuthenticationFailed("Authentication failed: username and password not specified".to_string()));
+    }
+    if !authorization_id.is_empty() && authorization_id != username {
+        return Err(SaslError::AuthenticationFailed("Authentication failed: Client requested an authorization id that is different from username".to_string()));
+    }
+    if !credentials.authenticate_plain(username, password) {
+        return Err(SaslError::AuthenticationFailed("Authentication failed: Invalid username or password".to_string()));
+    }
+    Ok(username.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_authentication() {
+        let credentials = CredentialStore::from_users([
+            (SaslMechanism::Plain, "alice".to_string(), "alice-secret".to_string()),
+            (SaslMechanism::Plain, "bob".to_string(), "bob-secret".to_string()),
+        ]);
+        let mut authenticator = SaslAuthenticator::new(vec![SaslMechanism::Plain], Some(Duration::from_secs(60)));
+        assert!(authenticator.allows(ApiKey::ApiVersions, Instant::now()));
+        assert!(!authenticator.allows(ApiKey::Metadata, Instant::now()));
+        assert!(matches!(authenticator.authenticate(b"\0alice\0alice-secret", &credentials), Err(SaslError::IllegalState(_))));
+        assert!(matches!(authenticator.handshake("SCRAM-SHA-256"), Err(SaslError::UnsupportedMechanism(_))));
+
+        authenticator.handshake("PLAIN").unwrap();
+        let response = authenticator.authenticate(b"\0alice\0alice-secret", &credentials).unwrap();
+        assert_eq!(response.session_lifetime_ms(), 60000);
+        assert_eq!(authenticator.principal(), Some(&KafkaPrincipal::user("alice")));
+        assert!(authenticator.allows(ApiKey::Metadata, Instant::now()));
+        assert!(!authenticator.allows(ApiKey::Metadata, Instant::now() + Duration::from_secs(61)));
+
+        // re-authenticating as someone else fails, and then nothing else is allowed on the connection
+        authenticator.handshake("PLAIN").unwrap();
+        assert!(authenticator.authenticate(b"\0bob\0bob-secret", &credentials).is_err());
+        assert!(!authenticator.allows(ApiKey::SaslHandshake, Instant::now()));
+    }
+}
@@ -0,0 +1,339 @@
+use std::fmt;
+use std::num::NonZeroU32;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ring::{digest, hmac, pbkdf2};
+use ring::rand::{SecureRandom, SystemRandom};
+use crate::security::sasl::SaslError;
+
+/// The hash functions SCRAM can be used with
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ScramMechanism {
+    ScramSha256,
+    ScramSha512,
+}
+
+impl ScramMechanism {
+    pub const ALL: [ScramMechanism; 2] = [ScramMechanism::ScramSha256, ScramMechanism::ScramSha512];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScramMechanism::ScramSha256 => "SCRAM-SHA-256",
+            ScramMechanism::ScramSha512 => "SCRAM-SHA-512",
+        }
+    }
+
+    /// The mechanism with the id kafka's protocol and metadata records use for it
+    pub fn from_type(mechanism_type: i8) -> Option<ScramMechanism> {
+        match mechanism_type {
+            1 => Some(ScramMechanism::ScramSha256),
+            2 => Some(ScramMechanism::ScramSha512),
+            _ => None,
+        }
+    }
+
+    pub fn mechanism_type(&self) -> i8 {
+        match self {
+            ScramMechanism::ScramSha256 => 1,
+            ScramMechanism::ScramSha512 => 2,
+        }
+    }
+
+    /// The lowest iteration count credentials can be created with, which is what RFC 7677 recommends for both
+    pub fn min_iterations(&self) -> i32 {
+        4096
+    }
+
+    pub fn max_iterations(&self) -> i32 {
+        16384
+    }
+
+    fn digest_algorithm(&self) -> &'static digest::Algorithm {
+        match self {
+            ScramMechanism::ScramSha256 => &digest::SHA256,
+            ScramMechanism::ScramSha512 => &digest::SHA512,
+        }
+    }
+
+    fn hmac_algorithm(&self) -> hmac::Algorithm {
+        match self {
+            ScramMechanism::ScramSha256 => hmac::HMAC_SHA256,
+            ScramMechanism::ScramSha512 => hmac::HMAC_SHA512,
+        }
+    }
+
+    fn pbkdf2_algorithm(&self) -> pbkdf2::Algorithm {
+        match self {
+            ScramMechanism::ScramSha256 => pbkdf2::PBKDF2_HMAC_SHA256,
+            ScramMechanism::ScramSha512 => pbkdf2::PBKDF2_HMAC_SHA512,
+        }
+    }
+
+    fn hash(&self, data: &[u8]) -> Vec<u8> {
+        digest::digest(self.digest_algorithm(), data).as_ref().to_vec()
+    }
+
+    fn hmac(&self, key: &[u8], data: &[u8]) -> Vec<u8> {
+        hmac::sign(&hmac::Key::new(self.hmac_algorithm(), key), data).as_ref().to_vec()
+    }
+}
+
+impl fmt::Display for ScramMechanism {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What the broker stores for a user of a SCRAM mechanism, from which it can check a client's proof but not recover its password
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScramCredential {
+    salt: Vec<u8>,
+    iterations: i32,
+    stored_key: Vec<u8>,
+    server_key: Vec<u8>,
+}
+
+impl ScramCredential {
+    pub fn new(salt: Vec<u8>, iterations: i32, stored_key: Vec<u8>, server_key: Vec<u8>) -> ScramCredential {
+        ScramCredential { salt, iterations, stored_key, server_key }
+    }
+
+    /// Derive the credential from a password with a random salt
+    pub fn from_password(mechanism: ScramMechanism, password: &str, iterations: i32) -> ScramCredential {
+        let mut salt = vec![0; 32];
+        SystemRandom::new().fill(&mut salt).expect("the system's random number generator failed");
+        ScramCredential::from_salted_password(mechanism, &salted_password(mechanism, password.as_bytes(), &salt, iterations), salt, iterations)
+    }
+
+    /// Derive the credential from a password that's already been salted, which is how clients send them to be stored
+    pub fn from_salted_password(mechanism: ScramMechanism, salted_password: &[u8], salt: Vec<u8>, iterations: i32) -> ScramCredential {
+        let client_key = mechanism.hmac(salted_password, b"Client Key");
+        ScramCredential {
+            salt,
+            iterations,
+            stored_key: mechanism.hash(&client_key),
+            server_key: mechanism.hmac(salted_password, b"Server Key"),
+        }
+    }
+
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
+    pub fn iterations(&self) -> i32 {
+        self.iterations
+    }
+
+    pub fn stored_key(&self) -> &[u8] {
+        &self.stored_key
+    }
+
+    pub fn server_key(&self) -> &[u8] {
+        &self.server_key
+    }
+}
+
+/// Hi() from RFC 5802, which is PBKDF2 with the mechanism's HMAC
+pub fn salted_password(mechanism: ScramMechanism, password: &[u8], salt: &[u8], iterations: i32) -> Vec<u8> {
+    let mut salted = vec![0; mechanism.digest_algorithm().output_len()];
+    let iterations = NonZeroU32::new(iterations.max(1) as u32).unwrap_or(NonZeroU32::MIN);
+    pbkdf2::derive(mechanism.pbkdf2_algorithm(), iterations, salt, password, &mut salted);
+    salted
+}
+
+/// A random nonce of printable characters other than the comma that separates SCRAM's attributes
+pub fn random_nonce() -> String {
+    let mut bytes = [0; 24];
+    SystemRandom::new().fill(&mut bytes).expect("the system's random number generator failed");
+    STANDARD.encode(bytes)
+}
+
+/// Compare without returning early, so the time taken doesn't give away how much of a secret matched
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+/// The server's side of a SCRAM exchange from RFC 5802, which takes the client's first and final messages
+#[derive(Debug)]
+pub enum ScramServer {
+    AwaitingClientFirst { server_nonce: String },
+    AwaitingClientFinal {
+        username: String,
+        credential: ScramCredential,
+        gs2_header: String,
+        client_first_bare: String,
+        server_first: String,
+        nonce: String,
+    },
+    Complete,
+}
+
+/// The client's first message, split into the parts the rest of the exchange needs
+#[derive(Debug, PartialEq, Eq)]
+pub struct ClientFirstMessage {
+    gs2_header: String,
+    bare: String,
+    username: String,
+    nonce: String,
+}
+
+impl ClientFirstMessage {
+    pub fn parse(message: &str) -> Result<ClientFirstMessage, SaslError> {
+        let invalid = || SaslError::AuthenticationFailed(format!("Invalid SCRAM client first message: {message}"));
+        let (channel_binding, rest) = message.split_once(',').ok_or_else(invalid)?;
+        // we don't support channel binding, and a client that only wants it if we do ("y") is fine
+        if channel_binding != "n" && channel_binding != "y" {
+            return Err(invalid());
+        }
+        let (authzid, bare) = rest.split_once(',').ok_or_else(invalid)?;
+        let mut username = None;
+        let mut nonce = None;
+        for attribute in bare.split(',') {
+            match attribute.split_once('=') {
+                Some(("n", value)) => username = Some(unescape_username(value).ok_or_else(invalid)?),
+                Some(("r", value)) => nonce = Some(value.to_string()),
+                // the mandatory extension attribute isn't supported, but other extensions can be ignored
+                Some(("m", _)) => return Err(invalid()),
+                _ => {}
+            }
+        }
+        let username = username.ok_or_else(invalid)?;
+        if let Some(authzid) = authzid.strip_prefix("a=") {
+            if unescape_username(authzid).as_deref() != Some(username.as_str()) {
+                return Err(SaslError::AuthenticationFailed("Authorization id doesn't match the user name".to_string()));
+            }
+        }
+        Ok(ClientFirstMessage {
+            gs2_header: format!("{channel_binding},{authzid},"),
+            bare: bare.to_string(),
+            username,
+            nonce: nonce.filter(|nonce| !nonce.is_empty()).ok_or_else(invalid)?,
+        })
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+}
+
+/// Names in SCRAM messages escape , and = as =2C and =3D
+fn unescape_username(name: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(name.len());
+    let mut rest = name;
+    while let Some(index) = rest.find('=') {
+        unescaped.push_str(&rest[..index]);
+        match rest.get(index..index + 3) {
+            Some("=2C") => unescaped.push(','),
+            Some("=3D") => unescaped.push('='),
+            _ => return None,
+        }
+        rest = &rest[index + 3..];
+    }
+    unescaped.push_str(rest);
+    Some(unescaped)
+}
+
+impl ScramServer {
+    pub fn new(server_nonce: String) -> ScramServer {
+        ScramServer::AwaitingClientFirst { server_nonce }
+    }
+
+    /// Reply to the client's first message with the salt and iteration count of the user's credential
+    pub fn client_first(&mut self, client_first: ClientFirstMessage, credential: ScramCredential) -> Result<String, SaslError> {
+        let ScramServer::AwaitingClientFirst { server_nonce } = self else {
+            return Err(SaslError::IllegalState("Unexpected SCRAM client first message".to_string()));
+        };
+        let nonce = format!("{}{}", client_first.nonce, server_nonce);
+        let server_first = format!("r={nonce},s={},i={}", STANDARD.encode(credential.salt()), credential.iterations());
+        *self = ScramServer::AwaitingClientFinal {
+            username: client_first.username,
+            credential,
+            gs2_header: client_first.gs2_header,
+            client_first_bare: client_first.bare,
+            server_first: server_first.clone(),
+            nonce,
+        };
+        Ok(server_first)
+    }
+
+    /// Check the client's proof, returning the name it authenticated as and the server's final message, which proves we know the credential too
+    pub fn client_final(&mut self, mechanism: ScramMechanism, client_final: &str) -> Result<(String, String), SaslError> {
+        let ScramServer::AwaitingClientFinal { username, credential, gs2_header, client_first_bare, server_first, nonce } =
+            std::mem::replace(self, ScramServer::Complete) else {
+            return Err(SaslError::IllegalState("Unexpected SCRAM client final message".to_string()));
+        };
+        let invalid = || SaslError::AuthenticationFailed(format!("Invalid SCRAM client final message: {client_final}"));
+        let (without_proof, proof) = client_final.rsplit_once(",p=").ok_or_else(invalid)?;
+        let proof = STANDARD.decode(proof).map_err(|_| invalid())?;
+        let mut channel_binding = None;
+        let mut final_nonce = None;
+        for attribute in without_proof.split(',') {
+            match attribute.split_once('=') {
+                Some(("c", value)) => channel_binding = Some(value),
+                Some(("r", value)) => final_nonce = Some(value),
+                _ => {}
+            }
+        }
+        if channel_binding != Some(STANDARD.encode(&gs2_header).as_str()) {
+            return Err(invalid());
+        }
+        if final_nonce != Some(nonce.as_str()) {
+            return Err(SaslError::AuthenticationFailed("Invalid nonce in SCRAM client final message".to_string()));
+        }
+
+        let auth_message = format!("{client_first_bare},{server_first},{without_proof}");
+        let client_signature = mechanism.hmac(credential.stored_key(), auth_message.as_bytes());
+        if proof.len() != client_signature.len() {
+            return Err(invalid());
+        }
+        let client_key: Vec<u8> = proof.iter().zip(&client_signature).map(|(proof, signature)| proof ^ signature).collect();
+        if !constant_time_eq(&mechanism.hash(&client_key), credential.stored_key()) {
+            return Err(SaslError::AuthenticationFailed(format!(
+                "Authentication failed during authentication due to invalid credentials with SASL mechanism {mechanism}"
+            )));
+        }
+        let server_signature = mechanism.hmac(credential.server_key(), auth_message.as_bytes());
+        Ok((username, format!("v={}", STANDARD.encode(server_signature))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The example exchange from RFC 7677
+    #[test]
+    fn test_scram_sha_256_exchange() {
+        let mechanism = ScramMechanism::ScramSha256;
+        let salt = STANDARD.decode("W22ZaJ0SNY7soEsUEjb6gQ==").unwrap();
+        let credential = ScramCredential::from_salted_password(mechanism, &salted_password(mechanism, b"pencil", &salt, 4096), salt, 4096);
+        let mut server = ScramServer::new("%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0".to_string());
+
+        let client_first = ClientFirstMessage::parse("n,,n=user,r=rOprNGfwEbeRWgbNEkqO").unwrap();
+        assert_eq!(client_first.username(), "user");
+        let server_first = server.client_first(client_first, credential).unwrap();
+        assert_eq!(server_first, "r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096");
+
+        let client_final = "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ=";
+        let (username, server_final) = server.client_final(mechanism, client_final).unwrap();
+        assert_eq!(username, "user");
+        assert_eq!(server_final, "v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=");
+    }
+
+    #[test]
+    fn test_wrong_proof() {
+        let mechanism = ScramMechanism::ScramSha256;
+        let salt = STANDARD.decode("W22ZaJ0SNY7soEsUEjb6gQ==").unwrap();
+        let credential = ScramCredential::from_salted_password(mechanism, &salted_password(mechanism, b"pen", &salt, 4096), salt, 4096);
+        let mut server = ScramServer::new("%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0".to_string());
+        server.client_first(ClientFirstMessage::parse("n,,n=user,r=rOprNGfwEbeRWgbNEkqO").unwrap(), credential).unwrap();
+        let client_final = "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ=";
+        assert!(matches!(server.client_final(mechanism, client_final), Err(SaslError::AuthenticationFailed(_))));
+    }
+
+    #[test]
+    fn test_unescape_username() {
+        assert_eq!(unescape_username("a=2Cb=3Dc").as_deref(), Some("a,b=c"));
+        assert_eq!(unescape_username("a=b"), None);
+    }
+}
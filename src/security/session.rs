@@ -0,0 +1,42 @@
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+use crate::api::api_key::ApiKey;
+use crate::security::principal::KafkaPrincipal;
+use crate::security::sasl::SaslAuthenticator;
+
+/// The security state of a connection, which every request read from it shares
+#[derive(Debug)]
+pub struct Session {
+    /// Who the transport says the client is, which is from its certificate on an SSL listener, and anonymous otherwise
+    transport_principal: KafkaPrincipal,
+    /// On a SASL listener, the client has to authenticate before it can send anything other than ApiVersions
+    sasl: Option<Mutex<SaslAuthenticator>>,
+}
+
+impl Session {
+    pub fn new(transport_principal: KafkaPrincipal) -> Session {
+        Session { transport_principal, sasl: None }
+    }
+
+    pub fn with_sasl(self, authenticator: SaslAuthenticator) -> Session {
+        Session { sasl: Some(Mutex::new(authenticator)), ..self }
+    }
+
+    /// Who the client is, which on a SASL listener is who it authenticated as
+    pub fn principal(&self) -> KafkaPrincipal {
+        match &self.sasl {
+            Some(sasl) => sasl.lock().unwrap().principal().cloned().unwrap_or_else(KafkaPrincipal::anonymous),
+            None => self.transport_principal.clone(),
+        }
+    }
+
+    /// Whether the client can send a request to the API yet
+    pub fn allows(&self, api_key: ApiKey) -> bool {
+        self.sasl.as_ref().map_or(true, |sasl| sasl.lock().unwrap().allows(api_key, Instant::now()))
+    }
+
+    /// The connection's SASL authentication, which is None unless it's on a SASL listener
+    pub fn sasl(&self) -> Option<MutexGuard<'_, SaslAuthenticator>> {
+        self.sasl.as_ref().map(|sasl| sasl.lock().unwrap())
+    }
+}
@@ -0,0 +1,170 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+use thiserror::Error;
+use tokio_rustls::rustls::crypto::{ring, CryptoProvider};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::danger::ClientCertVerifier;
+use tokio_rustls::rustls::server::{ServerConnection, VerifierBuilderError, WebPkiClientVerifier};
+use tokio_rustls::rustls::{self, RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use crate::broker::config::BrokerConfig;
+use crate::security::principal::{principal_from_certificate, KafkaPrincipal, PrincipalError, PrincipalMappingRule};
+
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("{0} has to be set for an SSL listener")]
+    MissingConfig(&'static str),
+    #[error("Failed to read {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+    #[error("No certificates in {}", .0.display())]
+    NoCertificates(PathBuf),
+    #[error("No private key in {}", .0.display())]
+    NoPrivateKey(PathBuf),
+    #[error("Invalid certificate: {0}")]
+    InvalidCertificate(#[from] rustls::Error),
+    #[error("Invalid trust store: {0}")]
+    InvalidTrustStore(#[from] VerifierBuilderError),
+}
+
+/// Whether clients of an SSL listener have to authenticate with a certificate, from `ssl.client.auth`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClientAuth {
+    Required,
+    /// Clients can authenticate with a certificate, but they're anonymous if they don't
+    Requested,
+    None,
+}
+
+impl ClientAuth {
+    pub fn from_name(name: &str) -> Option<ClientAuth> {
+        match name {
+            "required" => Some(ClientAuth::Required),
+            "requested" => Some(ClientAuth::Requested),
+            "none" => Some(ClientAuth::None),
+            _ => None,
+        }
+    }
+}
+
+/// The TLS config of an SSL listener, which is rebuilt when its key store or trust store changes,
+/// so certificates can be rotated without restarting the broker. Connections that are already open keep the config they started with
+#[derive(Debug)]
+pub struct TlsConfig {
+    keystore_location: PathBuf,
+    truststore_location: Option<PathBuf>,
+    client_auth: ClientAuth,
+    principal_mapping_rules: Vec<PrincipalMappingRule>,
+    server_config: RwLock<Arc<ServerConfig>>,
+    /// When the key store and trust store were last modified as of the last time they were loaded
+    modified: Mutex<Vec<Option<SystemTime>>>,
+}
+
+impl TlsConfig {
+    pub fn load(config: &BrokerConfig) -> Result<TlsConfig, TlsError> {
+        let keystore_location = config.ssl_keystore_location()
+            .ok_or(TlsError::MissingConfig("ssl.keystore.location"))?
+            .to_path_buf();
+        let truststore_location = config.ssl_truststore_location().map(Path::to_path_buf);
+        if config.ssl_client_auth() != ClientAuth::None && truststore_location.is_none() {
+            return Err(TlsError::MissingConfig("ssl.truststore.location"));
+        }
+        let modified = stores_modified(&keystore_location, truststore_location.as_deref());
+        let server_config = build_server_config(&keystore_location, truststore_location.as_deref(), config.ssl_client_auth())?;
+        Ok(TlsConfig {
+            keystore_location,
+            truststore_location,
+            client_auth: config.ssl_client_auth(),
+            principal_mapping_rules: config.ssl_principal_mapping_rules().to_vec(),
+            server_config: RwLock::new(Arc::new(server_config)),
+            modified: Mutex::new(modified),
+        })
+    }
+
+    /// An acceptor for the TLS handshake of a new connection, with the current certificates
+    pub fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(self.server_config.read().unwrap().clone())
+    }
+
+    /// Rebuild the config if the key store or trust store has changed since it was loaded, returning whether it was.
+    /// If the new files are invalid, the old config is kept, and they're tried again once they change again
+    pub fn reload_if_changed(&self) -> Result<bool, TlsError> {
+        let modified = stores_modified(&self.keystore_location, self.truststore_location.as_deref());
+        let mut last_modified = self.modified.lock().unwrap();
+        if *last_modified == modified {
+            return Ok(false);
+        }
+        *last_modified = modified;
+        drop(last_modified);
+        let server_config = build_server_config(&self.keystore_location, self.truststore_location.as_deref(), self.client_auth)?;
+        *self.server_config.write().unwrap() = Arc::new(server_config);
+        Ok(true)
+    }
+
+    /// Who the client authenticated as during the handshake, which is anonymous if it didn't send a certificate
+    pub fn principal(&self, connection: &ServerConnection) -> Result<KafkaPrincipal, PrincipalError> {
+        match connection.peer_certificates().and_then(|certificates| certificates.first()) {
+            Some(certificate) => principal_from_certificate(certificate, &self.principal_mapping_rules),
+            None => Ok(KafkaPrincipal::anonymous()),
+        }
+    }
+}
+
+fn stores_modified(keystore_location: &Path, truststore_location: Option<&Path>) -> Vec<Option<SystemTime>> {
+    [Some(keystore_location), truststore_location].into_iter()
+        .flatten()
+        .map(|path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+        .collect()
+}
+
+fn build_server_config(keystore_location: &Path, truststore_location: Option<&Path>, client_auth: ClientAuth) -> Result<ServerConfig, TlsError> {
+    let provider = Arc::new(ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone()).with_safe_default_protocol_versions()?;
+    let builder = match (truststore_location, client_auth) {
+        (Some(truststore_location), ClientAuth::Required | ClientAuth::Requested) => {
+            builder.with_client_cert_verifier(client_verifier(truststore_location, client_auth, provider)?)
+        }
+        _ => builder.with_no_client_auth(),
+    };
+    let (certificates, private_key) = read_keystore(keystore_location)?;
+    Ok(builder.with_single_cert(certificates, private_key)?)
+}
+
+fn client_verifier(truststore_location: &Path, client_auth: ClientAuth, provider: Arc<CryptoProvider>) -> Result<Arc<dyn ClientCertVerifier>, TlsError> {
+    let mut roots = RootCertStore::empty();
+    for certificate in read_certificates(truststore_location)? {
+        roots.add(certificate)?;
+    }
+    let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+    let verifier = match client_auth {
+        ClientAuth::Requested => verifier.allow_unauthenticated(),
+        _ => verifier,
+    };
+    Ok(verifier.build()?)
+}
+
+/// Read a PEM key store, which has the certificate chain followed by the private key
+fn read_keystore(path: &Path) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), TlsError> {
+    let certificates = read_certificates(path)?;
+    let contents = read_file(path)?;
+    let private_key = rustls_pemfile::private_key(&mut contents.as_slice())
+        .map_err(|source| TlsError::Io { path: path.to_path_buf(), source })?
+        .ok_or_else(|| TlsError::NoPrivateKey(path.to_path_buf()))?;
+    Ok((certificates, private_key))
+}
+
+fn read_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let certificates = rustls_pemfile::certs(&mut read_file(path)?.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|source| TlsError::Io { path: path.to_path_buf(), source })?;
+    if certificates.is_empty() {
+        return Err(TlsError::NoCertificates(path.to_path_buf()));
+    }
+    Ok(certificates)
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, TlsError> {
+    fs::read(path).map_err(|source| TlsError::Io { path: path.to_path_buf(), source })
+}
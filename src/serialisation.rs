@@ -1,7 +1,9 @@
 pub mod varint;
 pub mod nullable_string;
+pub mod versioned;
 mod from_kafka_bytes;
 mod to_kafka_bytes;
 
 pub use from_kafka_bytes::ReadKafkaBytes;
 pub use to_kafka_bytes::{ToKafkaBytes, to_response_message};
+pub use versioned::{MessageVersion, ReadVersionedKafkaBytes, ToVersionedKafkaBytes};
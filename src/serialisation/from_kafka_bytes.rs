@@ -1,33 +1,73 @@
-use tokio::io::{AsyncRead, AsyncReadExt};
+use bytes::Buf;
+use uuid::Uuid;
 use crate::api::request::KafkaRequestParseError;
 use crate::api::request::KafkaRequestParseError::MissingData;
 
-/// Trait that supports reading a type from the kafka protocol bytes that represent it
+/// Trait that supports reading a type from the kafka protocol bytes that represent it.
+/// Types are read from a buffer that already holds the whole message, so reading never has to wait on the socket
 pub trait ReadKafkaBytes: Sized {
-    #[allow(async_fn_in_trait)]
-    async fn read_kafka_bytes<T: AsyncRead + Unpin>(reader: &mut T) -> Result<Self, KafkaRequestParseError>;
+    fn read_kafka_bytes<B: Buf>(buf: &mut B) -> Result<Self, KafkaRequestParseError>;
 }
 
-impl ReadKafkaBytes for u8 {
-    async fn read_kafka_bytes<T: AsyncRead + Unpin>(reader: &mut T) -> Result<Self, KafkaRequestParseError> {
-        reader.read_u8()
-            .await
-            .map_err(|_| MissingData(size_of::<u8>()))
+/// Check there are enough bytes left to read, since reading past the end of a buffer panics
+pub fn ensure_remaining<B: Buf>(buf: &B, size: usize) -> Result<(), KafkaRequestParseError> {
+    if buf.remaining() < size {
+        return Err(MissingData(size));
     }
+    Ok(())
 }
 
-impl ReadKafkaBytes for i16 {
-    async fn read_kafka_bytes<T: AsyncRead + Unpin>(reader: &mut T) -> Result<Self, KafkaRequestParseError> {
-        reader.read_i16()
-            .await
-            .map_err(|_| MissingData(size_of::<i16>()))
+/// Read the given number of raw bytes, such as the contents of a string
+pub fn read_bytes<B: Buf>(buf: &mut B, length: usize) -> Result<Vec<u8>, KafkaRequestParseError> {
+    ensure_remaining(buf, length)?;
+    let mut bytes = vec![0u8; length];
+    buf.copy_to_slice(&mut bytes);
+    Ok(bytes)
+}
+
+/// Fixed size integers are big endian
+macro_rules! read_integer {
+    ($($t:ty => $get:ident),*) => {
+        $(
+            impl ReadKafkaBytes for $t {
+                fn read_kafka_bytes<B: Buf>(buf: &mut B) -> Result<Self, KafkaRequestParseError> {
+                    ensure_remaining(buf, size_of::<$t>())?;
+                    Ok(buf.$get())
+                }
+            }
+        )*
+    };
+}
+
+read_integer!(u8 => get_u8, i8 => get_i8, i16 => get_i16, i32 => get_i32, i64 => get_i64);
+
+impl ReadKafkaBytes for bool {
+    fn read_kafka_bytes<B: Buf>(buf: &mut B) -> Result<Self, KafkaRequestParseError> {
+        u8::read_kafka_bytes(buf)
+            .map(|byte| byte != 0)
     }
 }
 
-impl ReadKafkaBytes for i32 {
-    async fn read_kafka_bytes<T: AsyncRead + Unpin>(reader: &mut T) -> Result<Self, KafkaRequestParseError> {
-        reader.read_i32()
-            .await
-            .map_err(|_| MissingData(size_of::<i32>()))
+impl ReadKafkaBytes for Uuid {
+    fn read_kafka_bytes<B: Buf>(buf: &mut B) -> Result<Self, KafkaRequestParseError> {
+        let mut bytes = [0u8; 16];
+        ensure_remaining(buf, bytes.len())?;
+        buf.copy_to_slice(&mut bytes);
+        Ok(Uuid::from_bytes(bytes))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_past_end() {
+        let mut buf: &[u8] = &[0, 0, 0, 42, 7];
+        assert_eq!(i32::read_kafka_bytes(&mut buf).unwrap(), 42);
+        assert!(matches!(i16::read_kafka_bytes(&mut buf), Err(MissingData(2))));
+        // a failed read doesn't consume anything
+        assert_eq!(u8::read_kafka_bytes(&mut buf).unwrap(), 7);
+        assert!(matches!(read_bytes(&mut buf, 1), Err(MissingData(1))));
+    }
+}
@@ -1,23 +1,26 @@
-use tokio::io::{AsyncRead, AsyncReadExt};
+use bytes::Buf;
 use crate::api::request::KafkaRequestParseError;
-use crate::api::request::KafkaRequestParseError::{InvalidStringLength, MissingData};
-use crate::serialisation::from_kafka_bytes::ReadKafkaBytes;
+use crate::api::request::KafkaRequestParseError::InvalidStringLength;
+use crate::serialisation::from_kafka_bytes::{read_bytes, ReadKafkaBytes};
 
 /// Represents a nullable string read from the Kafka Protocol
 #[derive(Debug)]
 pub struct NullableString(Option<Box<str>>);
 
+impl NullableString {
+    pub fn as_str(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+}
+
 impl ReadKafkaBytes for NullableString {
-    async fn read_kafka_bytes<T: AsyncRead + Unpin>(reader: &mut T) -> Result<Self, KafkaRequestParseError> {
-        let length = i16::read_kafka_bytes(reader).await?;
+    fn read_kafka_bytes<B: Buf>(buf: &mut B) -> Result<Self, KafkaRequestParseError> {
+        let length = i16::read_kafka_bytes(buf)?;
         match length {
             -1 => Ok(NullableString(None)),
             ..-1 => Err(InvalidStringLength(length as i32)),
             _ => {
-                let mut string_bytes = vec![0u8; length as usize];
-                reader.read_exact(&mut string_bytes).await
-                    .map_err(|_| MissingData(length as usize))?;
-                let string = String::from_utf8(string_bytes)?;
+                let string = String::from_utf8(read_bytes(buf, length as usize)?)?;
                 Ok(NullableString(Some(string.into())))
             }
         }
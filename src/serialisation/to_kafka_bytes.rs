@@ -1,41 +1,74 @@
+use bytes::BufMut;
+use uuid::Uuid;
 use crate::serialisation::varint::VarInt;
 
 /// Types that can be serialised and used in the Kafka API
 pub trait ToKafkaBytes {
+    /// Write the bytes straight into a buffer, which is how responses are encoded
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B);
+
     /// Convert the data to bytes that can be returned in a Kafka API Protocol Response
-    fn to_kafka_bytes(self) -> impl IntoIterator<Item = u8>;
+    fn to_kafka_bytes(self) -> Vec<u8> where Self: Sized {
+        let mut bytes = Vec::new();
+        self.write_kafka_bytes(&mut bytes);
+        bytes
+    }
 }
 
-impl ToKafkaBytes for u8 {
-    fn to_kafka_bytes(self) -> impl IntoIterator<Item = u8> {
-        self.to_be_bytes()
-    }
+/// Fixed size integers are big endian
+macro_rules! write_integer {
+    ($($t:ty => $put:ident),*) => {
+        $(
+            impl ToKafkaBytes for $t {
+                fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+                    buf.$put(self);
+                }
+            }
+        )*
+    };
 }
 
-impl ToKafkaBytes for i16 {
-    fn to_kafka_bytes(self) -> impl IntoIterator<Item = u8> {
-        self.to_be_bytes()
+write_integer!(u8 => put_u8, i8 => put_i8, i16 => put_i16, i32 => put_i32, i64 => put_i64);
+
+impl ToKafkaBytes for bool {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        buf.put_u8(self as u8);
     }
 }
 
-impl ToKafkaBytes for i32 {
-    fn to_kafka_bytes(self) -> impl IntoIterator<Item = u8> {
-        self.to_be_bytes()
+impl ToKafkaBytes for Uuid {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        buf.put_slice(self.as_bytes());
     }
 }
 
 impl<T: ToKafkaBytes> ToKafkaBytes for Vec<T> {
     // write the length of the array, then each item in the array
-    fn to_kafka_bytes(self) -> impl IntoIterator<Item = u8> {
-        let length = VarInt::new((self.len() + 1) as u32);
-        let vec_as_bytes = self.into_iter().flat_map(|item| item.to_kafka_bytes());
-        length.to_kafka_bytes().into_iter().chain(vec_as_bytes)
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        VarInt::new((self.len() + 1) as u32).write_kafka_bytes(buf);
+        for item in self {
+            item.write_kafka_bytes(buf);
+        }
     }
 }
 
 /// Converts to bytes and adds the message size
-pub fn to_response_message<T: ToKafkaBytes>(response: T) -> impl Iterator<Item = u8> {
-    let bytes: Box<[u8]> = response.to_kafka_bytes().into_iter().collect();
-    let size = bytes.len() as i32;
-    size.to_be_bytes().into_iter().chain(bytes)
+pub fn to_response_message<T: ToKafkaBytes>(response: T) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    // the size isn't known until the response has been written, so it's filled in afterwards
+    bytes.put_i32(0);
+    response.write_kafka_bytes(&mut bytes);
+    let size = (bytes.len() - size_of::<i32>()) as i32;
+    bytes[..size_of::<i32>()].copy_from_slice(&size.to_be_bytes());
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_message_size() {
+        assert_eq!(to_response_message(vec![7i16, -1]), vec![0, 0, 0, 5, 3, 0, 7, 255, 255]);
+    }
 }
@@ -1,4 +1,4 @@
-use tokio::io::AsyncRead;
+use bytes::{Buf, BufMut};
 use crate::api::request::KafkaRequestParseError;
 use crate::serialisation::{ReadKafkaBytes, ToKafkaBytes};
 
@@ -28,11 +28,11 @@ impl VarInt {
 }
 
 impl ReadKafkaBytes for VarInt {
-    async fn read_kafka_bytes<T: AsyncRead + Unpin>(reader: &mut T) -> Result<Self, KafkaRequestParseError> {
+    fn read_kafka_bytes<B: Buf>(buf: &mut B) -> Result<Self, KafkaRequestParseError> {
         // read all bytes in the varint
         let mut int_bytes = Vec::with_capacity(1);
         while int_bytes.last().map_or(true, |byte| has_continuation(*byte)) {
-            int_bytes.push(u8::read_kafka_bytes(reader).await?);
+            int_bytes.push(u8::read_kafka_bytes(buf)?);
         }
 
         Ok(VarInt::from_bytes(&int_bytes))
@@ -49,7 +49,7 @@ fn mask_top_bits(n: u8) -> u8 {
     let mut result = 0u8;
     for _ in 0..n {
         result >>= 1;
-        result = result | 0b1000_0000;
+        result |= 0b1000_0000;
     }
     result
 }
@@ -66,9 +66,19 @@ fn shift_bits_up(x: u8, offset: u8) -> (u8, u8) {
     }
 }
 
-// Follows Zig-Zag encoding described in https://protobuf.dev/programming-guides/encoding/#varints
 impl ToKafkaBytes for VarInt {
-    fn to_kafka_bytes(self) -> impl IntoIterator<Item = u8> {
+    fn write_kafka_bytes<B: BufMut>(self, buf: &mut B) {
+        // most varints are short lengths, which are a single byte
+        match self.0 {
+            ..0b1000_0000 => buf.put_u8(self.0 as u8),
+            _ => buf.put_slice(&self.encode()),
+        }
+    }
+}
+
+impl VarInt {
+    // Follows Zig-Zag encoding described in https://protobuf.dev/programming-guides/encoding/#varints
+    fn encode(self) -> Vec<u8> {
         if self.0 == 0 {
             return vec![0];
         }
@@ -117,12 +127,39 @@ impl ToKafkaBytes for VarInt {
     }
 }
 
+/// Read a zig-zag encoded signed varint or varlong from the front of the bytes, advancing past it.
+/// These are used for the fields of records, returns None if the bytes end before the varint does
+pub fn read_signed_varint(bytes: &mut &[u8]) -> Option<i64> {
+    let mut result = 0u64;
+    for (idx, byte) in bytes.iter().enumerate().take(10) {
+        result |= ((byte & 0b0111_1111) as u64) << (7 * idx);
+        if !has_continuation(*byte) {
+            *bytes = &bytes[idx + 1..];
+            // undo the zig-zag encoding, which maps 0, -1, 1, -2... to 0, 1, 2, 3...
+            return Some((result >> 1) as i64 ^ -((result & 1) as i64));
+        }
+    }
+    None
+}
+
+/// Zig-zag encode a signed varint or varlong
+pub fn signed_varint_bytes(value: i64) -> Vec<u8> {
+    let mut zig_zagged = ((value << 1) ^ (value >> 63)) as u64;
+    let mut bytes = Vec::with_capacity(1);
+    while zig_zagged >= 0b1000_0000 {
+        bytes.push((zig_zagged as u8 & 0b0111_1111) | 0b1000_0000);
+        zig_zagged >>= 7;
+    }
+    bytes.push(zig_zagged as u8);
+    bytes
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     fn convert_to_bytes(x: u32) -> Vec<u8> {
-        VarInt::new(x).to_kafka_bytes().into_iter().collect()
+        VarInt::new(x).to_kafka_bytes()
     }
 
     #[test]
@@ -186,4 +223,23 @@ mod tests {
         assert_eq!(roundtrip(513), 513);
         assert_eq!(roundtrip(0b0100_0011_1000_0101), 0b0100_0011_1000_0101);
     }
+
+    #[test]
+    fn test_signed_varint_roundtrip() {
+        fn roundtrip(x: i64) -> i64 {
+            let bytes = signed_varint_bytes(x);
+            let mut slice = bytes.as_slice();
+            let result = read_signed_varint(&mut slice).unwrap();
+            assert!(slice.is_empty());
+            result
+        }
+
+        assert_eq!(signed_varint_bytes(0), vec![0]);
+        assert_eq!(signed_varint_bytes(-1), vec![1]);
+        assert_eq!(signed_varint_bytes(1), vec![2]);
+        assert_eq!(signed_varint_bytes(64), vec![0b1000_0000, 0b0000_0001]);
+        for x in [0, 1, -1, 63, -64, 64, 300, -300, i32::MAX as i64, i64::MIN, i64::MAX] {
+            assert_eq!(roundtrip(x), x);
+        }
+    }
 }
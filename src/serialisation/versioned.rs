@@ -0,0 +1,190 @@
+use bytes::{Buf, BufMut};
+use uuid::Uuid;
+use crate::api::request::KafkaRequestParseError;
+use crate::api::request::KafkaRequestParseError::{InvalidArrayLength, InvalidStringLength};
+use crate::serialisation::{ReadKafkaBytes, ToKafkaBytes};
+use crate::serialisation::from_kafka_bytes::{ensure_remaining, read_bytes};
+use crate::serialisation::varint::VarInt;
+
+/// The version of a message, and whether that version uses the "flexible" encoding
+/// (compact strings and arrays, plus tagged fields) introduced in KIP-482
+#[derive(Debug, Copy, Clone)]
+pub struct MessageVersion {
+    version: i16,
+    flexible: bool,
+}
+
+impl MessageVersion {
+    pub const fn new(version: i16, flexible: bool) -> MessageVersion {
+        MessageVersion { version, flexible }
+    }
+
+    pub fn version(&self) -> i16 {
+        self.version
+    }
+
+    pub fn is_flexible(&self) -> bool {
+        self.flexible
+    }
+}
+
+/// Trait for types whose encoding in the kafka protocol depends on the version of the message they are in
+pub trait ReadVersionedKafkaBytes: Sized {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError>;
+}
+
+/// Types which are encoded the same way in every version are trivially versioned
+impl<R: ReadKafkaBytes> ReadVersionedKafkaBytes for R {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, _version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        R::read_kafka_bytes(buf)
+    }
+}
+
+/// Types that can be serialised differently depending on the version of the message they are in
+pub trait ToVersionedKafkaBytes {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion);
+
+    fn to_versioned_kafka_bytes(self, version: MessageVersion) -> Vec<u8> where Self: Sized {
+        let mut bytes = Vec::new();
+        self.write_versioned_kafka_bytes(&mut bytes, version);
+        bytes
+    }
+}
+
+/// Read the length of a string or array, returning None if it is null
+fn read_length<B: Buf>(buf: &mut B, version: MessageVersion, short: bool) -> Result<Option<usize>, KafkaRequestParseError> {
+    let length = if version.is_flexible() {
+        // compact lengths are stored as length + 1, so that 0 can represent null
+        VarInt::read_kafka_bytes(buf)?.value() as i64 - 1
+    } else if short {
+        i16::read_kafka_bytes(buf)? as i64
+    } else {
+        i32::read_kafka_bytes(buf)? as i64
+    };
+    match length {
+        -1 => Ok(None),
+        ..-1 if short => Err(InvalidStringLength(length as i32)),
+        ..-1 => Err(InvalidArrayLength(length as i32)),
+        _ => Ok(Some(length as usize)),
+    }
+}
+
+impl ReadVersionedKafkaBytes for Option<String> {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let Some(length) = read_length(buf, version, true)? else {
+            return Ok(None);
+        };
+        Ok(Some(String::from_utf8(read_bytes(buf, length)?)?))
+    }
+}
+
+impl ReadVersionedKafkaBytes for String {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        Option::<String>::read_versioned_kafka_bytes(buf, version)?
+            .ok_or(InvalidStringLength(-1))
+    }
+}
+
+impl<E: ReadVersionedKafkaBytes> ReadVersionedKafkaBytes for Option<Vec<E>> {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        let Some(length) = read_length(buf, version, false)? else {
+            return Ok(None);
+        };
+        // don't trust the length for the allocation, a corrupt request shouldn't cause a huge allocation
+        let mut items = Vec::with_capacity(length.min(1024));
+        for _ in 0..length {
+            items.push(E::read_versioned_kafka_bytes(buf, version)?);
+        }
+        Ok(Some(items))
+    }
+}
+
+impl<E: ReadVersionedKafkaBytes> ReadVersionedKafkaBytes for Vec<E> {
+    fn read_versioned_kafka_bytes<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<Self, KafkaRequestParseError> {
+        Option::<Vec<E>>::read_versioned_kafka_bytes(buf, version)
+            .map(Option::unwrap_or_default)
+    }
+}
+
+/// Skip over the tagged fields at the end of a structure in a flexible message, we don't use any of them yet
+pub fn skip_tagged_fields<B: Buf>(buf: &mut B, version: MessageVersion) -> Result<(), KafkaRequestParseError> {
+    if !version.is_flexible() {
+        return Ok(());
+    }
+    let num_fields = VarInt::read_kafka_bytes(buf)?.value();
+    for _ in 0..num_fields {
+        let _tag = VarInt::read_kafka_bytes(buf)?;
+        let size = VarInt::read_kafka_bytes(buf)?.value() as usize;
+        ensure_remaining(buf, size)?;
+        buf.advance(size);
+    }
+    Ok(())
+}
+
+/// Write an empty set of tagged fields, which are only present in flexible messages
+pub fn write_empty_tagged_fields<B: BufMut>(buf: &mut B, version: MessageVersion) {
+    if version.is_flexible() {
+        buf.put_u8(0);
+    }
+}
+
+/// Write the length that comes before the items of an array, or the bytes of a bytes field, when they're encoded separately
+pub fn write_length_prefix<B: BufMut>(buf: &mut B, length: usize, version: MessageVersion) {
+    write_length(buf, Some(length), version, false);
+}
+
+fn write_length<B: BufMut>(buf: &mut B, length: Option<usize>, version: MessageVersion, short: bool) {
+    match (version.is_flexible(), length) {
+        (true, None) => VarInt::new(0).write_kafka_bytes(buf),
+        (true, Some(length)) => VarInt::new(length as u32 + 1).write_kafka_bytes(buf),
+        (false, None) if short => buf.put_i16(-1),
+        (false, None) => buf.put_i32(-1),
+        (false, Some(length)) if short => buf.put_i16(length as i16),
+        (false, Some(length)) => buf.put_i32(length as i32),
+    }
+}
+
+impl ToVersionedKafkaBytes for Option<String> {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        write_length(buf, self.as_ref().map(String::len), version, true);
+        if let Some(string) = self {
+            buf.put_slice(string.as_bytes());
+        }
+    }
+}
+
+impl ToVersionedKafkaBytes for String {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        Some(self).write_versioned_kafka_bytes(buf, version)
+    }
+}
+
+impl<E: ToVersionedKafkaBytes> ToVersionedKafkaBytes for Option<Vec<E>> {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        write_length(buf, self.as_ref().map(Vec::len), version, false);
+        for item in self.into_iter().flatten() {
+            item.write_versioned_kafka_bytes(buf, version);
+        }
+    }
+}
+
+impl<E: ToVersionedKafkaBytes> ToVersionedKafkaBytes for Vec<E> {
+    fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, version: MessageVersion) {
+        Some(self).write_versioned_kafka_bytes(buf, version)
+    }
+}
+
+/// Primitives are encoded the same way in every version
+macro_rules! unversioned {
+    ($($t:ty),*) => {
+        $(
+            impl ToVersionedKafkaBytes for $t {
+                fn write_versioned_kafka_bytes<B: BufMut>(self, buf: &mut B, _version: MessageVersion) {
+                    self.write_kafka_bytes(buf)
+                }
+            }
+        )*
+    };
+}
+
+unversioned!(u8, i8, bool, i16, i32, i64, Uuid);
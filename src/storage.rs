@@ -0,0 +1,8 @@
+pub mod file_records;
+pub mod log;
+pub mod log_manager;
+pub mod producer_state;
+pub mod record_batch;
+pub mod topic_partition;
+mod index;
+mod segment;
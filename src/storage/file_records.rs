@@ -0,0 +1,98 @@
+use std::fs::File;
+use std::io;
+use std::sync::Arc;
+
+/// Batches read from a log, left in the segment files they're stored in so they can be sent to a client
+/// straight from the files, rather than being copied through memory first
+#[derive(Debug, Default)]
+pub struct FileRecords {
+    slices: Vec<FileSlice>,
+}
+
+impl FileRecords {
+    pub fn new() -> Self {
+        FileRecords::default()
+    }
+
+    pub fn push(&mut self, slice: FileSlice) {
+        self.slices.push(slice);
+    }
+
+    pub fn slices(&self) -> &[FileSlice] {
+        &self.slices
+    }
+
+    /// The number of bytes of batches in every slice
+    pub fn size(&self) -> usize {
+        self.slices.iter().map(FileSlice::size).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+
+    /// Read every slice into memory, for when the records can't be sent from the files
+    pub fn read_all(&self) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(self.size());
+        for slice in &self.slices {
+            bytes.extend(slice.read()?);
+        }
+        Ok(bytes)
+    }
+}
+
+/// A range of a segment's log file. The slice shares the segment's open file,
+/// so it can still be sent if the segment is deleted or replaced in the meantime
+#[derive(Debug)]
+pub struct FileSlice {
+    file: Arc<File>,
+    position: u64,
+    size: usize,
+}
+
+impl FileSlice {
+    pub fn new(file: Arc<File>, position: u64, size: usize) -> Self {
+        FileSlice { file, position, size }
+    }
+
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn read(&self) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![0u8; self.size];
+        read_exact_at(&self.file, &mut bytes, self.position)?;
+        Ok(bytes)
+    }
+}
+
+/// Fill the buffer from the position in the file. This doesn't use the file's cursor,
+/// so the same file can be read from by several threads at once
+#[cfg(unix)]
+pub(super) fn read_exact_at(file: &File, buf: &mut [u8], position: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(buf, position)
+}
+
+#[cfg(windows)]
+pub(super) fn read_exact_at(file: &File, mut buf: &mut [u8], mut position: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_read(buf, position)? {
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            read => {
+                buf = &mut buf[read..];
+                position += read as u64;
+            }
+        }
+    }
+    Ok(())
+}
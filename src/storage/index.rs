@@ -0,0 +1,231 @@
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use crate::storage::log::AbortedTxn;
+
+const OFFSET_INDEX_ENTRY_SIZE: usize = 8;
+const TIME_INDEX_ENTRY_SIZE: usize = 12;
+const TRANSACTION_INDEX_ENTRY_SIZE: usize = 34;
+/// The version of the transaction index entries we write, which matches kafka's
+const TRANSACTION_INDEX_VERSION: i16 = 0;
+
+/// Read an index file, returning no bytes if it doesn't exist since indexes are optional
+fn read_index_file(path: &Path) -> io::Result<Vec<u8>> {
+    match fs::read(path) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        result => result,
+    }
+}
+
+/// Open an index file to append entries to, cutting off anything after its valid entries,
+/// such as the zeroes kafka preallocates index files with, or an entry that was only partially written
+fn open_index_file(path: &Path, valid_size: usize) -> io::Result<File> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    if file.metadata()?.len() != valid_size as u64 {
+        file.set_len(valid_size as u64)?;
+    }
+    Ok(file)
+}
+
+/// Sparse index from offsets to the position of the batch containing them in the segment file,
+/// stored in the `.index` file as (offset relative to the segment's base offset: i32, position: i32) pairs.
+/// Each entry is for the last offset of a batch, so looking up any later offset starts from that batch or after it
+#[derive(Debug)]
+pub(super) struct OffsetIndex {
+    base_offset: i64,
+    file: File,
+    entries: Vec<(i64, u64)>,
+}
+
+impl OffsetIndex {
+    pub fn load(path: &Path, base_offset: i64) -> io::Result<OffsetIndex> {
+        let bytes = read_index_file(path)?;
+        let mut entries: Vec<(i64, u64)> = Vec::new();
+        for entry in bytes.chunks_exact(OFFSET_INDEX_ENTRY_SIZE) {
+            let offset = base_offset + i32::from_be_bytes(entry[0..4].try_into().unwrap()) as i64;
+            let position = u32::from_be_bytes(entry[4..8].try_into().unwrap()) as u64;
+            // kafka preallocates index files, so the entries stop when the offsets stop increasing
+            if entries.last().is_some_and(|(last_offset, _)| offset <= *last_offset) {
+                break;
+            }
+            entries.push((offset, position));
+        }
+        let file = open_index_file(path, entries.len() * OFFSET_INDEX_ENTRY_SIZE)?;
+        Ok(OffsetIndex { base_offset, file, entries })
+    }
+
+    /// Find the largest indexed offset less than or equal to the target offset, and its position.
+    /// Falls back to the start of the segment if there's no such entry
+    pub fn lookup(&self, offset: i64) -> (i64, u64) {
+        let idx = self.entries.partition_point(|(entry_offset, _)| *entry_offset <= offset);
+        match idx {
+            0 => (self.base_offset, 0),
+            _ => self.entries[idx - 1],
+        }
+    }
+
+    pub fn last_entry(&self) -> (i64, u64) {
+        self.entries.last().copied().unwrap_or((self.base_offset, 0))
+    }
+
+    /// Index the batch at the position, whose last offset must be after every offset already indexed
+    pub fn append(&mut self, offset: i64, position: u64) -> io::Result<()> {
+        let mut entry = Vec::with_capacity(OFFSET_INDEX_ENTRY_SIZE);
+        entry.extend(((offset - self.base_offset) as i32).to_be_bytes());
+        entry.extend((position as u32).to_be_bytes());
+        self.file.write_all(&entry)?;
+        self.entries.push((offset, position));
+        Ok(())
+    }
+
+    /// Remove the entries for batches at or past the position, which are no longer in the segment
+    pub fn truncate_to_position(&mut self, position: u64) -> io::Result<()> {
+        let retained = self.entries.partition_point(|(_, entry_position)| *entry_position < position);
+        self.entries.truncate(retained);
+        self.file.set_len((retained * OFFSET_INDEX_ENTRY_SIZE) as u64)
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.file.sync_all()
+    }
+}
+
+/// Sparse index from timestamps to offsets, stored in the `.timeindex` file as
+/// (timestamp: i64, offset relative to the segment's base offset: i32) pairs.
+/// Each entry's timestamp is the largest timestamp seen in the segment up to that offset
+#[derive(Debug)]
+pub(super) struct TimeIndex {
+    base_offset: i64,
+    file: File,
+    entries: Vec<(i64, i64)>,
+}
+
+impl TimeIndex {
+    pub fn load(path: &Path, base_offset: i64) -> io::Result<TimeIndex> {
+        let bytes = read_index_file(path)?;
+        let mut entries: Vec<(i64, i64)> = Vec::new();
+        for entry in bytes.chunks_exact(TIME_INDEX_ENTRY_SIZE) {
+            let timestamp = i64::from_be_bytes(entry[0..8].try_into().unwrap());
+            let offset = base_offset + i32::from_be_bytes(entry[8..12].try_into().unwrap()) as i64;
+            if entries.last().is_some_and(|(last_timestamp, last_offset)| timestamp <= *last_timestamp || offset < *last_offset) {
+                break;
+            }
+            entries.push((timestamp, offset));
+        }
+        let file = open_index_file(path, entries.len() * TIME_INDEX_ENTRY_SIZE)?;
+        Ok(TimeIndex { base_offset, file, entries })
+    }
+
+    /// Find the offset to start searching from for the first record with a timestamp >= the target,
+    /// that's the offset of the largest indexed timestamp that is less than the target
+    pub fn lookup(&self, timestamp: i64) -> Option<i64> {
+        let idx = self.entries.partition_point(|(entry_timestamp, _)| *entry_timestamp < timestamp);
+        match idx {
+            0 => None,
+            _ => Some(self.entries[idx - 1].1),
+        }
+    }
+
+    pub fn last_entry(&self) -> Option<(i64, i64)> {
+        self.entries.last().copied()
+    }
+
+    /// Index the largest timestamp in the segment so far, along with the offset it was first seen at.
+    /// Nothing is written unless the timestamp is larger than the last indexed one
+    pub fn maybe_append(&mut self, timestamp: i64, offset: i64) -> io::Result<()> {
+        if self.entries.last().is_some_and(|(last_timestamp, _)| timestamp <= *last_timestamp) {
+            return Ok(());
+        }
+        let mut entry = Vec::with_capacity(TIME_INDEX_ENTRY_SIZE);
+        entry.extend(timestamp.to_be_bytes());
+        entry.extend(((offset - self.base_offset) as i32).to_be_bytes());
+        self.file.write_all(&entry)?;
+        self.entries.push((timestamp, offset));
+        Ok(())
+    }
+
+    /// Remove the entries for offsets at or past the end offset, which are no longer in the segment
+    pub fn truncate_to(&mut self, end_offset: i64) -> io::Result<()> {
+        let retained = self.entries.partition_point(|(_, offset)| *offset < end_offset);
+        self.entries.truncate(retained);
+        self.file.set_len((retained * TIME_INDEX_ENTRY_SIZE) as u64)
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.file.sync_all()
+    }
+}
+
+/// The transactions aborted in a segment, in the order their markers were written, stored in the `.txnindex` file as
+/// (version: i16, producer id: i64, first offset: i64, last offset: i64, last stable offset: i64) entries
+#[derive(Debug)]
+pub(super) struct TransactionIndex {
+    path: PathBuf,
+    entries: Vec<AbortedTxn>,
+}
+
+impl TransactionIndex {
+    pub fn load(path: &Path) -> io::Result<TransactionIndex> {
+        let bytes = read_index_file(path)?;
+        let mut entries = Vec::new();
+        for entry in bytes.chunks_exact(TRANSACTION_INDEX_ENTRY_SIZE) {
+            if i16::from_be_bytes(entry[0..2].try_into().unwrap()) != TRANSACTION_INDEX_VERSION {
+                break;
+            }
+            entries.push(AbortedTxn::new(
+                i64::from_be_bytes(entry[2..10].try_into().unwrap()),
+                i64::from_be_bytes(entry[10..18].try_into().unwrap()),
+                i64::from_be_bytes(entry[18..26].try_into().unwrap()),
+                i64::from_be_bytes(entry[26..34].try_into().unwrap()),
+            ));
+        }
+        Ok(TransactionIndex { path: path.to_path_buf(), entries })
+    }
+
+    /// Add an aborted transaction to the end of the index. Markers before the latest producer state snapshot
+    /// are replayed when the log is opened, so a transaction that's already been indexed is skipped
+    pub fn append(&mut self, txn: AbortedTxn) -> io::Result<()> {
+        if self.entries.last().is_some_and(|last| txn.last_offset() <= last.last_offset()) {
+            return Ok(());
+        }
+        let mut entry = Vec::with_capacity(TRANSACTION_INDEX_ENTRY_SIZE);
+        entry.extend(TRANSACTION_INDEX_VERSION.to_be_bytes());
+        entry.extend(txn.producer_id().to_be_bytes());
+        entry.extend(txn.first_offset().to_be_bytes());
+        entry.extend(txn.last_offset().to_be_bytes());
+        entry.extend(txn.last_stable_offset().to_be_bytes());
+        OpenOptions::new().create(true).append(true).open(&self.path)?.write_all(&entry)?;
+        self.entries.push(txn);
+        Ok(())
+    }
+
+    /// Remove the transactions whose abort markers are at or past the end offset, which are no longer in the segment
+    pub fn truncate_to(&mut self, end_offset: i64) -> io::Result<()> {
+        let retained = self.entries.iter().take_while(|txn| txn.last_offset() < end_offset).count();
+        if retained == self.entries.len() {
+            return Ok(());
+        }
+        self.entries.truncate(retained);
+        let file = OpenOptions::new().write(true).open(&self.path)?;
+        file.set_len((retained * TRANSACTION_INDEX_ENTRY_SIZE) as u64)?;
+        file.sync_all()
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        match File::open(&self.path) {
+            Ok(file) => file.sync_all(),
+            // the index file is only created once a transaction is aborted
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The aborted transactions with records in the range of offsets from start up to, but not including, end
+    pub fn overlapping(&self, start_offset: i64, end_offset: i64) -> impl Iterator<Item = AbortedTxn> + '_ {
+        self.entries.iter()
+            .filter(move |txn| txn.last_offset() >= start_offset && txn.first_offset() < end_offset)
+            .copied()
+    }
+}
@@ -0,0 +1,557 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock, RwLockReadGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use thiserror::Error;
+use crate::storage::file_records::FileRecords;
+use crate::storage::producer_state::{ActiveProducer, BatchMetadata, CompletedTxn, ProducerStateError, ProducerStateManager};
+use crate::storage::record_batch::{RecordBatchError, RecordBatchHeader};
+use crate::storage::segment::LogSegment;
+
+#[derive(Debug, Error)]
+pub enum AppendError {
+    #[error(transparent)]
+    Storage(#[from] io::Error),
+    #[error(transparent)]
+    ProducerState(#[from] ProducerStateError),
+}
+
+/// The outcome of a producer appending a batch to the log
+#[derive(Debug)]
+pub enum AppendedBatch {
+    /// The batch was written to the log, and this is its header as it was written
+    Appended(RecordBatchHeader),
+    /// The batch is a retry of one the producer already wrote, so it wasn't written again
+    Duplicate(BatchMetadata),
+}
+
+/// A record's timestamp and offset, along with the leader epoch of the batch it was written in
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TimestampAndOffset {
+    timestamp: i64,
+    offset: i64,
+    leader_epoch: Option<i32>,
+}
+
+impl TimestampAndOffset {
+    pub fn new(timestamp: i64, offset: i64, leader_epoch: Option<i32>) -> TimestampAndOffset {
+        TimestampAndOffset { timestamp, offset, leader_epoch }
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.offset
+    }
+
+    pub fn leader_epoch(&self) -> Option<i32> {
+        self.leader_epoch
+    }
+}
+
+/// The configs of a topic that decide how its logs are stored
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LogConfig {
+    /// The size a segment can grow to before a new one is rolled
+    pub segment_bytes: u64,
+    /// How many bytes are appended between index entries
+    pub index_interval_bytes: u64,
+    /// Whether old segments are deleted once they're past the retention time or size, from the topic's cleanup policy
+    pub delete_retained: bool,
+    /// How long a segment is kept after its last record was written, or -1 to keep it forever
+    pub retention_ms: i64,
+    /// The size the log can grow to before its oldest segments are deleted, or -1 for no limit
+    pub retention_bytes: i64,
+}
+
+impl Default for LogConfig {
+    /// The configs of logs that aren't a topic's, such as the metadata log, which are never deleted by retention
+    fn default() -> Self {
+        LogConfig {
+            segment_bytes: 1024 * 1024 * 1024,
+            index_interval_bytes: 4096,
+            delete_retained: false,
+            retention_ms: -1,
+            retention_bytes: -1,
+        }
+    }
+}
+
+/// A transaction that was aborted, which read_committed consumers filter out the records of
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AbortedTxn {
+    producer_id: i64,
+    first_offset: i64,
+    /// The offset of the abort marker
+    last_offset: i64,
+    /// The last stable offset once the transaction was aborted
+    last_stable_offset: i64,
+}
+
+impl AbortedTxn {
+    pub fn new(producer_id: i64, first_offset: i64, last_offset: i64, last_stable_offset: i64) -> AbortedTxn {
+        AbortedTxn { producer_id, first_offset, last_offset, last_stable_offset }
+    }
+
+    pub fn producer_id(&self) -> i64 {
+        self.producer_id
+    }
+
+    pub fn first_offset(&self) -> i64 {
+        self.first_offset
+    }
+
+    pub fn last_offset(&self) -> i64 {
+        self.last_offset
+    }
+
+    pub fn last_stable_offset(&self) -> i64 {
+        self.last_stable_offset
+    }
+}
+
+/// The log of a single partition, made up of segments stored in the partition's directory
+#[derive(Debug)]
+pub struct Log {
+    dir: PathBuf,
+    /// Sorted by base offset
+    segments: RwLock<Vec<LogSegment>>,
+    /// Only locked while the segments are locked, so appends see a consistent view of both
+    producer_state: Mutex<ProducerStateManager>,
+    config: RwLock<LogConfig>,
+    /// Set while the segments are locked, once the broker is shutting down and nothing more may be appended
+    closed: AtomicBool,
+}
+
+impl Log {
+    /// Open the log stored in the directory, loading each of its segments.
+    /// If the broker didn't shut down cleanly, the batches from the recovery point onwards are checked as they're loaded
+    pub fn open(dir: &Path, recovery_point: Option<i64>, config: LogConfig) -> io::Result<Log> {
+        let mut base_offsets = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let file_name = entry?.file_name();
+            let base_offset = file_name.to_str()
+                .and_then(|name| name.strip_suffix(".log"))
+                .and_then(|base_offset| base_offset.parse::<i64>().ok());
+            base_offsets.extend(base_offset);
+        }
+        base_offsets.sort();
+
+        let mut segments: Vec<LogSegment> = base_offsets.into_iter()
+            .map(|base_offset| LogSegment::open(dir, base_offset, recovery_point))
+            .collect::<io::Result<_>>()?;
+
+        // bring the producer state up to date by replaying the batches written after its latest snapshot
+        let log_end_offset = segments.last().map_or(0, LogSegment::next_offset);
+        let mut producer_state = ProducerStateManager::load(dir, log_end_offset)?;
+        let replay_from = producer_state.map_end_offset();
+        for segment in segments.iter_mut().filter(|segment| segment.next_offset() > replay_from) {
+            for (header, marker) in segment.replay_batches_from(replay_from)? {
+                if let Some(txn) = producer_state.update(&header, marker) {
+                    index_completed_txn(segment, &producer_state, txn)?;
+                }
+            }
+        }
+        Ok(Log {
+            dir: dir.to_path_buf(),
+            segments: RwLock::new(segments),
+            producer_state: Mutex::new(producer_state),
+            config: RwLock::new(config),
+            closed: AtomicBool::new(false),
+        })
+    }
+
+    /// Create an empty log in the directory
+    pub fn create(dir: &Path, config: LogConfig) -> io::Result<Log> {
+        fs::create_dir_all(dir)?;
+        let segment = LogSegment::create(dir, 0)?;
+        let producer_state = ProducerStateManager::load(dir, 0)?;
+        Ok(Log {
+            dir: dir.to_path_buf(),
+            segments: RwLock::new(vec![segment]),
+            producer_state: Mutex::new(producer_state),
+            config: RwLock::new(config),
+            closed: AtomicBool::new(false),
+        })
+    }
+
+    /// Use the topic's configs from now on, after they've been changed
+    pub fn update_config(&self, config: LogConfig) {
+        *self.config.write().unwrap() = config;
+    }
+
+    fn segments(&self) -> RwLockReadGuard<'_, Vec<LogSegment>> {
+        self.segments.read().unwrap()
+    }
+
+    /// Append an encoded record batch to the end of the log, assigning offsets to its records
+    /// and stamping it with the leader epoch. Returns the header of the batch as it was written.
+    /// This is for batches the broker writes itself, such as transaction markers, whose sequence numbers aren't checked
+    pub fn append(&self, batch: Vec<u8>, leader_epoch: i32) -> io::Result<RecordBatchHeader> {
+        match self.append_batch(batch, leader_epoch, false) {
+            Ok(AppendedBatch::Appended(header)) => Ok(header),
+            Ok(AppendedBatch::Duplicate(_)) => unreachable!("only batches from producers are checked for duplicates"),
+            Err(AppendError::Storage(err)) => Err(err),
+            Err(AppendError::ProducerState(err)) => Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+        }
+    }
+
+    /// Append a record batch a producer sent, like `append`, after checking its sequence numbers follow on from
+    /// the producer's previous batches. A retry of a batch that's already been written isn't written again
+    pub fn append_as_leader(&self, batch: Vec<u8>, leader_epoch: i32) -> Result<AppendedBatch, AppendError> {
+        self.append_batch(batch, leader_epoch, true)
+    }
+
+    fn append_batch(&self, mut batch: Vec<u8>, leader_epoch: i32, check_producer: bool) -> Result<AppendedBatch, AppendError> {
+        let mut segments = self.segments.write().unwrap();
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(io::Error::other(format!("The log in {} is closed, since the broker is shutting down", self.dir.display())).into());
+        }
+        if segments.is_empty() {
+            segments.push(LogSegment::create(&self.dir, 0)?);
+        }
+        let header = RecordBatchHeader::parse(&batch).map_err(io::Error::from)?;
+        if header.size_in_bytes() != batch.len() {
+            return Err(io::Error::from(RecordBatchError::Truncated(header.size_in_bytes())).into());
+        }
+        let mut producer_state = self.producer_state.lock().unwrap();
+        if check_producer {
+            if let Some(duplicate) = producer_state.check(&header)? {
+                return Ok(AppendedBatch::Duplicate(duplicate));
+            }
+        }
+        let config = *self.config.read().unwrap();
+        let base_offset = segments.last().unwrap().next_offset();
+        let last_offset = base_offset + header.last_offset_delta() as i64;
+        if segments.last().unwrap().should_roll(batch.len(), last_offset, config.segment_bytes) {
+            // like kafka, the producer state is snapshotted whenever a segment is rolled,
+            // so no more than the active segment has to be replayed when the log is opened
+            producer_state.take_snapshot()?;
+            segments.push(LogSegment::create(&self.dir, base_offset)?);
+        }
+        let segment = segments.last_mut().unwrap();
+
+        // neither the base offset or leader epoch are covered by the batch's CRC, so they can be overwritten
+        batch[0..8].copy_from_slice(&base_offset.to_be_bytes());
+        batch[12..16].copy_from_slice(&leader_epoch.to_be_bytes());
+        let header = RecordBatchHeader::parse(&batch).map_err(io::Error::from)?;
+        segment.append(&header, &batch, config.index_interval_bytes)?;
+
+        let marker = header.control_record_type(&batch).map_err(io::Error::from)?;
+        if let Some(txn) = producer_state.update(&header, marker) {
+            index_completed_txn(segment, &producer_state, txn)?;
+        }
+        Ok(AppendedBatch::Appended(header))
+    }
+
+    /// The producers that have state in this partition, which is what's kept to check their sequence numbers
+    pub fn active_producers(&self) -> Vec<ActiveProducer> {
+        let _segments = self.segments();
+        self.producer_state.lock().unwrap().active_producers()
+    }
+
+    /// Snapshot the producer state, so it doesn't have to be rebuilt from the log when it's next opened
+    pub fn take_producer_snapshot(&self) -> io::Result<()> {
+        let _segments = self.segments.write().unwrap();
+        self.producer_state.lock().unwrap().take_snapshot()
+    }
+
+    /// Reject every append from now on, waiting for any append in progress to finish
+    pub fn close(&self) {
+        let _segments = self.segments.write().unwrap();
+        self.closed.store(true, Ordering::SeqCst);
+    }
+
+    /// Make sure every batch appended to the log is on disk
+    pub fn flush(&self) -> io::Result<()> {
+        self.segments().iter().try_for_each(LogSegment::flush)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Read every batch in the log, this is for replaying small internal topics when the broker starts
+    pub fn read_all_batches(&self) -> io::Result<Vec<(RecordBatchHeader, Vec<u8>)>> {
+        let mut batches = Vec::new();
+        for segment in self.segments().iter() {
+            batches.extend(segment.read_all_batches()?);
+        }
+        Ok(batches)
+    }
+
+    /// The total size of the log's segment files
+    pub fn size(&self) -> u64 {
+        self.segments().iter().map(LogSegment::size).sum()
+    }
+
+    /// Delete the oldest segments while they're past the retention time, or the log is bigger than its retention size,
+    /// if the topic's cleanup policy is to delete them. The active segment is always kept.
+    /// Returns how many segments were deleted
+    pub fn delete_old_segments(&self, now: i64) -> io::Result<usize> {
+        let config = *self.config.read().unwrap();
+        if !config.delete_retained {
+            return Ok(0);
+        }
+        let mut segments = self.segments.write().unwrap();
+        let mut size: u64 = segments.iter().map(LogSegment::size).sum();
+        let mut num_to_delete = 0;
+        for segment in &segments[..segments.len().saturating_sub(1)] {
+            let expired = config.retention_ms >= 0 && now - segment.largest_timestamp()? > config.retention_ms;
+            let oversized = config.retention_bytes >= 0 && size - segment.size() >= config.retention_bytes as u64;
+            if !expired && !oversized {
+                break;
+            }
+            size -= segment.size();
+            num_to_delete += 1;
+        }
+        for segment in segments.drain(..num_to_delete) {
+            segment.delete()?;
+        }
+        Ok(num_to_delete)
+    }
+
+    /// The first offset that can be read from the log
+    pub fn log_start_offset(&self) -> i64 {
+        self.segments().first().map_or(0, LogSegment::base_offset)
+    }
+
+    /// The offset the next record appended to the log will get
+    pub fn log_end_offset(&self) -> i64 {
+        self.segments().last().map_or(0, LogSegment::next_offset)
+    }
+
+    /// The offset up to which records have been replicated,
+    /// since this broker is the only replica that's the whole log
+    pub fn high_watermark(&self) -> i64 {
+        self.log_end_offset()
+    }
+
+    /// The offset up to which all transactions are complete, which read_committed consumers can read up to.
+    /// Without any open transactions, that's the high watermark
+    pub fn last_stable_offset(&self) -> i64 {
+        let segments = self.segments();
+        let high_watermark = segments.last().map_or(0, LogSegment::next_offset);
+        self.producer_state.lock().unwrap()
+            .first_unstable_offset()
+            .map_or(high_watermark, |offset| offset.min(high_watermark))
+    }
+
+    /// Read the batches from the one containing the start offset, stopping before a batch with records at or past
+    /// the max offset, or one that would take the size past max bytes. The first batch is read whatever its size
+    /// if `min_one_batch` is set. Returns where the batches are in the segment files, along with the offset after the last batch read
+    pub fn read(&self, start_offset: i64, max_offset: i64, max_bytes: usize, min_one_batch: bool) -> io::Result<(FileRecords, i64)> {
+        let segments = self.segments();
+        let first_segment = segments.partition_point(|segment| segment.base_offset() <= start_offset).saturating_sub(1);
+        let mut records = FileRecords::new();
+        let mut next_offset = start_offset;
+        for segment in &segments[first_segment..] {
+            let (batches, segment_next_offset) = segment.read(
+                next_offset,
+                max_offset,
+                max_bytes.saturating_sub(records.size()),
+                min_one_batch && records.is_empty(),
+            )?;
+            if let Some(batches) = batches {
+                records.push(batches);
+            }
+            next_offset = segment_next_offset;
+            // the read stopped before the end of the segment, so there's no room for anything from the next one
+            if next_offset < segment.next_offset() {
+                break;
+            }
+        }
+        Ok((records, next_offset))
+    }
+
+    /// The aborted transactions with records from the start offset up to, but not including, the end offset
+    pub fn aborted_transactions(&self, start_offset: i64, end_offset: i64) -> Vec<AbortedTxn> {
+        self.segments().iter()
+            .filter(|segment| segment.next_offset() > start_offset && segment.base_offset() < end_offset)
+            .flat_map(|segment| segment.aborted_txns(start_offset, end_offset))
+            .collect()
+    }
+
+    /// The leader epoch the offset was written in, if the log contains it
+    pub fn epoch_for_offset(&self, offset: i64) -> io::Result<Option<i32>> {
+        let segments = self.segments();
+        let segment = segments.iter()
+            .rev()
+            .find(|segment| segment.base_offset() <= offset);
+        let Some(segment) = segment else {
+            return Ok(None);
+        };
+        Ok(segment.batch_containing(offset)?
+            .map(|batch| batch.partition_leader_epoch())
+            .filter(|epoch| *epoch >= 0))
+    }
+
+    /// The leader epoch of the last record in the log
+    pub fn latest_epoch(&self) -> io::Result<Option<i32>> {
+        self.epoch_for_offset(self.log_end_offset() - 1)
+    }
+
+    /// Find the first record with a timestamp greater than or equal to the target timestamp
+    pub fn find_offset_by_timestamp(&self, timestamp: i64) -> io::Result<Option<TimestampAndOffset>> {
+        let segments = self.segments();
+        let segment = segments.iter()
+            .find(|segment| segment.max_timestamp() >= timestamp);
+        match segment {
+            Some(segment) => segment.find_offset_by_timestamp(timestamp),
+            None => Ok(None),
+        }
+    }
+
+    /// Find the first record with the largest timestamp in the log
+    pub fn offset_of_max_timestamp(&self) -> io::Result<Option<TimestampAndOffset>> {
+        let segments = self.segments();
+        let max_timestamp = segments.iter()
+            .map(LogSegment::max_timestamp)
+            .max()
+            .unwrap_or(-1);
+        let segment = segments.iter()
+            .find(|segment| segment.max_timestamp() == max_timestamp && max_timestamp >= 0);
+        match segment {
+            Some(segment) => segment.offset_of_max_timestamp(),
+            None => Ok(None),
+        }
+    }
+
+    /// The offsets returned by v0 of ListOffsets, which are the base offsets of the segments
+    /// whose timestamps are all before the target timestamp, along with the log end offset, most recent first.
+    /// The special timestamps -1 and -2 request the latest and earliest offsets
+    pub fn legacy_offsets_before(&self, timestamp: i64, max_num_offsets: usize) -> Vec<i64> {
+        let segments = self.segments();
+        let mut offsets_and_timestamps: Vec<(i64, i64)> = segments.iter()
+            .map(|segment| (segment.base_offset(), segment.max_timestamp()))
+            .collect();
+        match segments.last() {
+            Some(segment) if segment.next_offset() > segment.base_offset() => offsets_and_timestamps.push((segment.next_offset(), i64::MAX)),
+            None => offsets_and_timestamps.push((0, i64::MAX)),
+            _ => {}
+        }
+
+        let num_candidates = match timestamp {
+            -1 => offsets_and_timestamps.len(),
+            -2 => offsets_and_timestamps.len().min(1),
+            _ => offsets_and_timestamps.iter()
+                .take_while(|(_, segment_timestamp)| *segment_timestamp <= timestamp)
+                .count(),
+        };
+        offsets_and_timestamps[..num_candidates]
+            .iter()
+            .rev()
+            .take(max_num_offsets)
+            .map(|(offset, _)| *offset)
+            .collect()
+    }
+}
+
+/// Add a transaction whose marker was appended to the segment to its transaction index, if it was aborted
+fn index_completed_txn(segment: &mut LogSegment, producer_state: &ProducerStateManager, txn: CompletedTxn) -> io::Result<()> {
+    if !txn.is_aborted() {
+        return Ok(());
+    }
+    // the transaction's marker is the last record that had been appended when it was aborted
+    let high_watermark = txn.last_offset() + 1;
+    let last_stable_offset = producer_state.first_unstable_offset()
+        .map_or(high_watermark, |offset| offset.min(high_watermark));
+    segment.append_aborted_txn(AbortedTxn::new(txn.producer_id(), txn.first_offset(), txn.last_offset(), last_stable_offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::record_batch::RecordBatchBuilder;
+    use crate::storage::segment::segment_file_name;
+    use crate::testing::TempDir;
+
+    /// Check the lookups that go through the indexes find the right batches in every segment
+    fn assert_lookups(log: &Log) {
+        for i in 0..50 {
+            let (records, _) = log.read(2 * i, i64::MAX, 1, true).unwrap();
+            let batch = records.read_all().unwrap();
+            assert_eq!(RecordBatchHeader::parse(&batch).unwrap().base_offset(), 2 * i);
+
+            let found = log.find_offset_by_timestamp(1000 + 10 * i + 3).unwrap().unwrap();
+            assert_eq!((found.offset(), found.timestamp(), found.leader_epoch()), (2 * i + 1, 1000 + 10 * i + 5, Some(i as i32 / 10)));
+            assert_eq!(log.epoch_for_offset(2 * i).unwrap(), Some(i as i32 / 10));
+        }
+        assert_eq!(log.latest_epoch().unwrap(), Some(4));
+        assert_eq!(log.offset_of_max_timestamp().unwrap().map(|found| found.offset()), Some(99));
+        assert!(log.find_offset_by_timestamp(2000).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_lookups_across_segments() {
+        let dir = TempDir::new("log");
+        let config = LogConfig { segment_bytes: 1024, index_interval_bytes: 100, ..LogConfig::default() };
+        let log = Log::create(dir.path(), config).unwrap();
+        for i in 0..50 {
+            let batch = RecordBatchBuilder::new()
+                .add_record(1000 + 10 * i, None, Some(vec![0u8; 40]))
+                .add_record(1000 + 10 * i + 5, None, Some(vec![1u8; 40]))
+                .build();
+            log.append(batch, i as i32 / 10).unwrap();
+        }
+
+        let segments = log.segments();
+        assert!(segments.len() > 3);
+        assert!(segments.iter().all(|segment| segment.size() <= 1024));
+        assert!(segments.windows(2).all(|pair| pair[0].next_offset() == pair[1].base_offset()));
+        let index_path = dir.path().join(segment_file_name(0, "index"));
+        assert!(fs::metadata(index_path).unwrap().len() > 0);
+        drop(segments);
+        assert_lookups(&log);
+
+        // the indexes written while appending are loaded when the log is opened again, with or without recovering it
+        drop(log);
+        assert_lookups(&Log::open(dir.path(), None, config).unwrap());
+        let log = Log::open(dir.path(), Some(0), config).unwrap();
+        assert_eq!(log.log_end_offset(), 100);
+        assert_lookups(&log);
+    }
+
+    #[test]
+    fn test_delete_old_segments() {
+        let dir = TempDir::new("log");
+        let config = LogConfig { segment_bytes: 200, delete_retained: true, retention_ms: 1000, ..LogConfig::default() };
+        let log = Log::create(dir.path(), LogConfig { delete_retained: false, ..config }).unwrap();
+        for timestamp in [1000, 2000, 3000, 4000] {
+            let batch = RecordBatchBuilder::new().add_record(timestamp, None, Some(vec![0u8; 100])).build();
+            log.append(batch, 0).unwrap();
+        }
+        assert_eq!(log.segments().len(), 4);
+        // nothing is deleted unless the cleanup policy includes delete
+        assert_eq!(log.delete_old_segments(10_000).unwrap(), 0);
+
+        log.update_config(config);
+        assert_eq!(log.delete_old_segments(3500).unwrap(), 2);
+        assert_eq!(log.log_start_offset(), 2);
+        assert!(!dir.path().join(segment_file_name(0, "log")).exists());
+
+        let segment_size = log.size() / 2;
+        log.update_config(LogConfig { retention_ms: -1, retention_bytes: segment_size as i64, ..config });
+        assert_eq!(log.delete_old_segments(3500).unwrap(), 1);
+        // the active segment is kept, however old it is
+        log.update_config(LogConfig { retention_ms: 0, ..config });
+        assert_eq!(log.delete_old_segments(i64::MAX).unwrap(), 0);
+        assert_eq!(log.log_start_offset(), 3);
+        assert_eq!(log.log_end_offset(), 4);
+    }
+
+    #[test]
+    fn test_closed_log_rejects_appends() {
+        let dir = TempDir::new("log");
+        let log = Log::create(dir.path(), LogConfig::default()).unwrap();
+        let batch = RecordBatchBuilder::new().add_record(0, None, Some(b"value".to_vec())).build();
+        log.append(batch.clone(), 0).unwrap();
+        log.close();
+        assert!(log.append(batch.clone(), 0).is_err());
+        assert!(matches!(log.append_as_leader(batch, 0), Err(AppendError::Storage(_))));
+        assert_eq!(log.log_end_offset(), 1);
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use uuid::Uuid;
+use tracing::{error, info};
+use crate::storage::log::{Log, LogConfig};
+use crate::storage::topic_partition::TopicPartition;
+
+/// Suffix of the directories of deleted logs that are waiting to be removed
+const DELETE_DIR_SUFFIX: &str = "-delete";
+/// Written to the log directory when the broker shuts down cleanly, so its logs don't have to be recovered when it next starts
+const CLEAN_SHUTDOWN_FILE: &str = ".kafka_cleanshutdown";
+/// How far each log is known to be on disk, which is where recovering it starts from
+const RECOVERY_POINT_CHECKPOINT_FILE: &str = "recovery-point-offset-checkpoint";
+/// The version of the checkpoint files we write, which matches kafka's
+const CHECKPOINT_VERSION: i32 = 0;
+
+/// Manages the logs of every partition stored in the broker's log directory
+#[derive(Debug)]
+pub struct LogManager {
+    log_dir: PathBuf,
+    logs: Mutex<HashMap<TopicPartition, Arc<Log>>>,
+    /// The recovery points the broker started with, which are kept for the logs that aren't opened before it shuts down
+    recovery_points: HashMap<TopicPartition, i64>,
+    /// The configs of each topic's logs, topics that aren't here use the defaults.
+    /// Only locked while the logs are, so a log is never opened with configs that have been replaced
+    configs: RwLock<HashMap<String, LogConfig>>,
+}
+
+impl LogManager {
+    /// Manage the logs in the directory. If the broker didn't shut down cleanly every log is opened straight away,
+    /// recovering it from its recovery point, otherwise each log is opened the first time it's used
+    pub fn load(log_dir: impl Into<PathBuf>) -> io::Result<LogManager> {
+        let log_dir = log_dir.into();
+        let recovery_points = read_checkpoint(&log_dir.join(RECOVERY_POINT_CHECKPOINT_FILE))?;
+        let log_manager = LogManager { log_dir, logs: Mutex::new(HashMap::new()), recovery_points, configs: RwLock::default() };
+
+        let clean_shutdown_file = log_manager.log_dir.join(CLEAN_SHUTDOWN_FILE);
+        if clean_shutdown_file.is_file() {
+            // the marker only vouches for the logs as they were when the broker stopped, not for anything written from now on
+            fs::remove_file(&clean_shutdown_file)?;
+        } else if log_manager.log_dir.is_dir() {
+            log_manager.recover_logs()?;
+        }
+        Ok(log_manager)
+    }
+
+    /// Open every log after an unclean shutdown, checking the batches written since each log's recovery point
+    fn recover_logs(&self) -> io::Result<()> {
+        let mut logs = self.logs.lock().unwrap();
+        for (topic_partition, path) in self.log_dirs()? {
+            let recovery_point = self.recovery_points.get(&topic_partition).copied().unwrap_or(0);
+            info!("Recovering log {topic_partition} from offset {recovery_point}");
+            let log = Log::open(&path, Some(recovery_point), self.config(topic_partition.topic()))?;
+            logs.insert(topic_partition, Arc::new(log));
+        }
+        Ok(())
+    }
+
+    /// Every partition with a log in the log directory, along with the log's own directory
+    fn log_dirs(&self) -> io::Result<Vec<(TopicPartition, PathBuf)>> {
+        let mut log_dirs = Vec::new();
+        for entry in fs::read_dir(&self.log_dir)? {
+            let path = entry?.path();
+            let topic_partition = path.file_name().and_then(|name| name.to_str()).and_then(parse_log_dir_name);
+            if let Some(topic_partition) = topic_partition.filter(|_| path.is_dir()) {
+                log_dirs.push((topic_partition, path));
+            }
+        }
+        Ok(log_dirs)
+    }
+
+    pub fn log_dir(&self) -> &Path {
+        &self.log_dir
+    }
+
+    fn config(&self, topic: &str) -> LogConfig {
+        self.configs.read().unwrap().get(topic).copied().unwrap_or_default()
+    }
+
+    /// Replace the configs of every topic's logs, updating the logs that are already open
+    pub fn update_configs(&self, configs: HashMap<String, LogConfig>) {
+        let logs = self.logs.lock().unwrap();
+        for (topic_partition, log) in logs.iter() {
+            log.update_config(configs.get(topic_partition.topic()).copied().unwrap_or_default());
+        }
+        *self.configs.write().unwrap() = configs;
+    }
+
+    /// Get the log for the partition, opening it if it hasn't been used yet.
+    /// Returns None if the partition has no log directory
+    pub fn get_log(&self, topic_partition: &TopicPartition) -> io::Result<Option<Arc<Log>>> {
+        let mut logs = self.logs.lock().unwrap();
+        if let Some(log) = logs.get(topic_partition) {
+            return Ok(Some(log.clone()));
+        }
+
+        let dir = self.log_dir.join(topic_partition.to_string());
+        if !dir.is_dir() {
+            return Ok(None);
+        }
+        // a log that wasn't recovered when the broker started has been untouched since it last shut down cleanly
+        let log = Arc::new(Log::open(&dir, None, self.config(topic_partition.topic()))?);
+        logs.insert(topic_partition.clone(), log.clone());
+        Ok(Some(log))
+    }
+
+    /// The logs that have been opened, without opening any others
+    pub fn open_logs(&self) -> Vec<(TopicPartition, Arc<Log>)> {
+        self.logs.lock().unwrap().iter().map(|(topic_partition, log)| (topic_partition.clone(), log.clone())).collect()
+    }
+
+    /// Create an empty log for a new partition, recording the partition's topic id in its directory
+    pub fn create_log(&self, topic_partition: &TopicPartition, topic_id: Uuid) -> io::Result<Arc<Log>> {
+        let mut logs = self.logs.lock().unwrap();
+        let dir = self.log_dir.join(topic_partition.to_string());
+        let log = Arc::new(Log::create(&dir, self.config(topic_partition.topic()))?);
+        // kafka formats topic ids as url safe base64
+        let partition_metadata = format!("version: 0\ntopic_id: {}\n", URL_SAFE_NO_PAD.encode(topic_id.as_bytes()));
+        fs::write(dir.join("partition.metadata"), partition_metadata)?;
+        logs.insert(topic_partition.clone(), log.clone());
+        Ok(log)
+    }
+
+    /// Delete the log of a partition. The log's directory is renamed straight away, so a new log
+    /// can be created for the partition, then removed in the background
+    pub fn delete_log(&self, topic_partition: &TopicPartition) -> io::Result<()> {
+        let mut logs = self.logs.lock().unwrap();
+        logs.remove(topic_partition);
+        let dir = self.log_dir.join(topic_partition.to_string());
+        if !dir.is_dir() {
+            return Ok(());
+        }
+        let deleted_dir = self.log_dir.join(format!("{topic_partition}.{}{DELETE_DIR_SUFFIX}", Uuid::new_v4().simple()));
+        fs::rename(&dir, &deleted_dir)?;
+        remove_in_background(vec![deleted_dir]);
+        Ok(())
+    }
+
+    /// Delete the segments of every log that are past its topic's retention, opening the logs that haven't been used yet
+    pub fn delete_old_segments(&self, now: i64) -> io::Result<()> {
+        if !self.log_dir.is_dir() {
+            return Ok(());
+        }
+        for (topic_partition, _) in self.log_dirs()? {
+            let Some(log) = self.get_log(&topic_partition)? else {
+                continue;
+            };
+            let deleted = log.delete_old_segments(now)?;
+            if deleted > 0 {
+                info!("Deleted {deleted} segments of {topic_partition} past its retention, the log now starts at offset {}", log.log_start_offset());
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the directories of deleted logs that weren't removed before the broker last stopped
+    pub fn remove_deleted_logs(&self) -> io::Result<()> {
+        if !self.log_dir.is_dir() {
+            return Ok(());
+        }
+        let mut deleted_dirs = Vec::new();
+        for entry in fs::read_dir(&self.log_dir)? {
+            let path = entry?.path();
+            let is_deleted = path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.ends_with(DELETE_DIR_SUFFIX));
+            if is_deleted && path.is_dir() {
+                deleted_dirs.push(path);
+            }
+        }
+        remove_in_background(deleted_dirs);
+        Ok(())
+    }
+
+    /// Close every open log so nothing more is appended to it, flush it and snapshot its producer state,
+    /// checkpoint how far each log is on disk, then mark the shutdown as clean
+    pub fn shutdown(&self) -> io::Result<()> {
+        let logs = self.logs.lock().unwrap();
+        for log in logs.values() {
+            log.close();
+        }
+        let mut recovery_points: HashMap<_, _> = self.recovery_points.iter()
+            .filter(|(topic_partition, _)| self.log_dir.join(topic_partition.to_string()).is_dir())
+            .map(|(topic_partition, offset)| (topic_partition.clone(), *offset))
+            .collect();
+        for (topic_partition, log) in logs.iter() {
+            log.flush()?;
+            log.take_producer_snapshot()?;
+            recovery_points.insert(topic_partition.clone(), log.log_end_offset());
+        }
+
+        fs::create_dir_all(&self.log_dir)?;
+        write_checkpoint(&self.log_dir.join(RECOVERY_POINT_CHECKPOINT_FILE), &recovery_points)?;
+        fs::write(self.log_dir.join(CLEAN_SHUTDOWN_FILE), "")?;
+        info!("Flushed {} logs and wrote the clean shutdown marker", logs.len());
+        Ok(())
+    }
+}
+
+/// The partition whose log is stored in a directory named `topic-partition`,
+/// or None if it's some other directory, such as a deleted log waiting to be removed
+fn parse_log_dir_name(name: &str) -> Option<TopicPartition> {
+    let (topic, partition) = name.rsplit_once('-')?;
+    let partition = partition.parse().ok()?;
+    (!topic.is_empty() && partition >= 0).then(|| TopicPartition::new(topic, partition))
+}
+
+/// Read a kafka offset checkpoint file, which is the version and the number of entries on their own lines,
+/// then a `topic partition offset` line for each entry. A missing file has no entries
+fn read_checkpoint(path: &Path) -> io::Result<HashMap<TopicPartition, i64>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(err) => return Err(err),
+    };
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("Invalid checkpoint file {}", path.display()));
+    let mut lines = contents.lines();
+    if lines.next().and_then(|version| version.trim().parse().ok()) != Some(CHECKPOINT_VERSION) {
+        return Err(invalid());
+    }
+    let count: usize = lines.next().and_then(|count| count.trim().parse().ok()).ok_or_else(invalid)?;
+    let offsets: HashMap<_, _> = lines
+        .map(|line| match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [topic, partition, offset] => Some((TopicPartition::new(topic, partition.parse().ok()?), offset.parse().ok()?)),
+            _ => None,
+        })
+        .collect::<Option<_>>()
+        .ok_or_else(invalid)?;
+    match offsets.len() == count {
+        true => Ok(offsets),
+        false => Err(invalid()),
+    }
+}
+
+fn write_checkpoint(path: &Path, offsets: &HashMap<TopicPartition, i64>) -> io::Result<()> {
+    let mut contents = format!("{CHECKPOINT_VERSION}\n{}\n", offsets.len());
+    for (topic_partition, offset) in offsets {
+        contents.push_str(&format!("{} {} {offset}\n", topic_partition.topic(), topic_partition.partition()));
+    }
+    // write to a temporary file first, so the checkpoint is never seen partially written
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, contents)?;
+    fs::rename(&temp_path, path)
+}
+
+fn remove_in_background(dirs: Vec<PathBuf>) {
+    if dirs.is_empty() {
+        return;
+    }
+    thread::spawn(move || {
+        for dir in dirs {
+            if let Err(err) = fs::remove_dir_all(&dir) {
+                error!("Failed to remove deleted log directory {}: {err}", dir.display());
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let dir = std::env::temp_dir().join(format!("checkpoint-{}", Uuid::new_v4().simple()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(RECOVERY_POINT_CHECKPOINT_FILE);
+        assert!(read_checkpoint(&path).unwrap().is_empty());
+
+        let offsets = HashMap::from([(TopicPartition::new("my-topic", 0), 42), (TopicPartition::new("my-topic", 3), 0)]);
+        write_checkpoint(&path, &offsets).unwrap();
+        assert_eq!(read_checkpoint(&path).unwrap(), offsets);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_log_dir_name() {
+        assert_eq!(parse_log_dir_name("my-topic-3"), Some(TopicPartition::new("my-topic", 3)));
+        assert_eq!(parse_log_dir_name("my-topic-3.0123abcd-delete"), None);
+        assert_eq!(parse_log_dir_name("-3"), None);
+        assert_eq!(parse_log_dir_name("my-topic"), None);
+    }
+}
@@ -0,0 +1,458 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tracing::warn;
+use crate::storage::record_batch::{decrement_sequence, ControlRecordType, RecordBatchHeader};
+use crate::storage::segment::segment_file_name;
+
+/// The most batches a producer can have in flight, which is how many are kept to recognise retried batches
+const NUM_BATCHES_TO_RETAIN: usize = 5;
+/// The version of the snapshot file format we write, which matches kafka's
+const SNAPSHOT_VERSION: i16 = 1;
+/// The version and CRC at the start of a snapshot file
+const SNAPSHOT_HEADER_SIZE: usize = 6;
+/// The size of each producer's entry in a snapshot file
+const SNAPSHOT_ENTRY_SIZE: usize = 46;
+/// The most snapshots kept in a partition's directory, older ones are deleted when a new one is taken
+const NUM_SNAPSHOTS_TO_RETAIN: usize = 2;
+
+#[derive(Debug, Error)]
+pub enum ProducerStateError {
+    #[error("Producer {producer_id}'s epoch {epoch} is older than its current epoch {current_epoch}")]
+    InvalidProducerEpoch { producer_id: i64, epoch: i16, current_epoch: i16 },
+    #[error("Out of order sequence number for producer {producer_id}: {sequence} (incoming seq. number), {last_sequence} (current end sequence number)")]
+    OutOfOrderSequence { producer_id: i64, sequence: i32, last_sequence: i32 },
+}
+
+/// The offsets and sequence numbers of a batch a producer has written
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BatchMetadata {
+    first_sequence: i32,
+    last_sequence: i32,
+    first_offset: i64,
+    last_offset: i64,
+    timestamp: i64,
+}
+
+impl BatchMetadata {
+    pub fn first_offset(&self) -> i64 {
+        self.first_offset
+    }
+
+    pub fn last_offset(&self) -> i64 {
+        self.last_offset
+    }
+}
+
+/// A transaction that's been ended by a marker in the partition
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CompletedTxn {
+    producer_id: i64,
+    first_offset: i64,
+    /// The offset of the marker
+    last_offset: i64,
+    is_aborted: bool,
+}
+
+impl CompletedTxn {
+    pub fn producer_id(&self) -> i64 {
+        self.producer_id
+    }
+
+    pub fn first_offset(&self) -> i64 {
+        self.first_offset
+    }
+
+    pub fn last_offset(&self) -> i64 {
+        self.last_offset
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.is_aborted
+    }
+}
+
+/// A producer that has written to the partition, as it's described to admin clients
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ActiveProducer {
+    producer_id: i64,
+    producer_epoch: i16,
+    /// -1 if the producer hasn't written any batches with its current epoch
+    last_sequence: i32,
+    /// -1 if the producer hasn't written any batches with its current epoch
+    last_timestamp: i64,
+    current_txn_first_offset: Option<i64>,
+}
+
+impl ActiveProducer {
+    pub fn producer_id(&self) -> i64 {
+        self.producer_id
+    }
+
+    pub fn producer_epoch(&self) -> i16 {
+        self.producer_epoch
+    }
+
+    pub fn last_sequence(&self) -> i32 {
+        self.last_sequence
+    }
+
+    pub fn last_timestamp(&self) -> i64 {
+        self.last_timestamp
+    }
+
+    /// The offset of the first batch of the producer's ongoing transaction, if it has one
+    pub fn current_txn_first_offset(&self) -> Option<i64> {
+        self.current_txn_first_offset
+    }
+}
+
+/// What's known about a producer that has written to the partition
+#[derive(Debug, Clone)]
+struct ProducerStateEntry {
+    producer_epoch: i16,
+    /// The producer's most recent batches, oldest first
+    batches: VecDeque<BatchMetadata>,
+    /// The offset of the producer's first batch in its ongoing transaction, if it has one
+    current_txn_first_offset: Option<i64>,
+}
+
+impl ProducerStateEntry {
+    fn last_sequence(&self) -> Option<i32> {
+        self.batches.back().map(|batch| batch.last_sequence)
+    }
+
+    /// The batch this one is a retry of, if the producer has already written it
+    fn find_duplicate(&self, header: &RecordBatchHeader) -> Option<BatchMetadata> {
+        if header.producer_epoch() != self.producer_epoch {
+            return None;
+        }
+        self.batches.iter()
+            .find(|batch| batch.first_sequence == header.base_sequence() && batch.last_sequence == header.last_sequence())
+            .copied()
+    }
+}
+
+/// Tracks the sequence numbers each producer has written to a partition, so idempotent producers can retry
+/// batches without them being written twice. The state is snapshotted to `.snapshot` files named after the offset
+/// they're up to, so it only has to be rebuilt from the batches after the latest snapshot when the log is opened
+#[derive(Debug)]
+pub struct ProducerStateManager {
+    dir: PathBuf,
+    producers: BTreeMap<i64, ProducerStateEntry>,
+    /// The offset the state is up to, which is the offset after the last batch it's been updated with
+    map_end_offset: i64,
+}
+
+impl ProducerStateManager {
+    /// Load the latest snapshot in the directory that isn't past the end of the log.
+    /// The batches from the returned offset onwards have to be replayed to bring the state up to date
+    pub fn load(dir: &Path, log_end_offset: i64) -> io::Result<ProducerStateManager> {
+        let mut state = ProducerStateManager {
+            dir: dir.to_path_buf(),
+            producers: BTreeMap::new(),
+            map_end_offset: 0,
+        };
+        for offset in state.snapshot_offsets()?.into_iter().rev() {
+            let path = state.snapshot_path(offset);
+            // the log may have lost records the snapshot was taken after, or the snapshot may have been partially written
+            if offset > log_end_offset {
+                fs::remove_file(&path)?;
+                continue;
+            }
+            match read_snapshot(&fs::read(&path)?) {
+                Some(producers) => {
+                    state.producers = producers;
+                    state.map_end_offset = offset;
+                    break;
+                }
+                None => {
+                    warn!("Deleting corrupt producer state snapshot {}", path.display());
+                    fs::remove_file(&path)?;
+                }
+            }
+        }
+        Ok(state)
+    }
+
+    /// The offset the state is up to, so batches from here on have to be replayed to bring it up to date
+    pub fn map_end_offset(&self) -> i64 {
+        self.map_end_offset
+    }
+
+    /// Check a batch a producer is about to append follows on from the producer's previous batches.
+    /// Returns the earlier batch if this is a retry of a batch that's already been written
+    pub fn check(&self, header: &RecordBatchHeader) -> Result<Option<BatchMetadata>, ProducerStateError> {
+        if !header.has_producer_id() {
+            return Ok(None);
+        }
+        let producer_id = header.producer_id();
+        let Some(entry) = self.producers.get(&producer_id) else {
+            // the producer's state may have been lost, such as when its batches were removed by retention
+            return Ok(None);
+        };
+        if let Some(duplicate) = entry.find_duplicate(header) {
+            return Ok(Some(duplicate));
+        }
+        if header.producer_epoch() < entry.producer_epoch {
+            return Err(ProducerStateError::InvalidProducerEpoch {
+                producer_id,
+                epoch: header.producer_epoch(),
+                current_epoch: entry.producer_epoch,
+            });
+        }
+        let last_sequence = entry.last_sequence().unwrap_or(-1);
+        let in_sequence = match header.producer_epoch() == entry.producer_epoch {
+            true => header.base_sequence() == last_sequence.wrapping_add(1) || header.base_sequence() == 0 && last_sequence == i32::MAX,
+            // a producer with a new epoch starts its sequence numbers again
+            false => header.base_sequence() == 0,
+        };
+        match in_sequence {
+            true => Ok(None),
+            false => Err(ProducerStateError::OutOfOrderSequence { producer_id, sequence: header.base_sequence(), last_sequence }),
+        }
+    }
+
+    /// The offset of the first batch of the oldest ongoing transaction, which read_committed consumers can't read past
+    pub fn first_unstable_offset(&self) -> Option<i64> {
+        self.producers.values()
+            .filter_map(|entry| entry.current_txn_first_offset)
+            .min()
+    }
+
+    /// Every producer the partition has state for, in producer id order
+    pub fn active_producers(&self) -> Vec<ActiveProducer> {
+        self.producers.iter()
+            .map(|(producer_id, entry)| ActiveProducer {
+                producer_id: *producer_id,
+                producer_epoch: entry.producer_epoch,
+                last_sequence: entry.last_sequence().unwrap_or(-1),
+                last_timestamp: entry.batches.back().map_or(-1, |batch| batch.timestamp),
+                current_txn_first_offset: entry.current_txn_first_offset,
+            })
+            .collect()
+    }
+
+    /// Record a batch that's been appended to the log, with the offsets it was given, along with the type of marker
+    /// it is if it's a control batch. Returns the transaction the batch completed, if it's a transaction marker
+    pub fn update(&mut self, header: &RecordBatchHeader, marker: Option<ControlRecordType>) -> Option<CompletedTxn> {
+        self.map_end_offset = self.map_end_offset.max(header.next_offset());
+        if !header.has_producer_id() {
+            return None;
+        }
+        let producer_id = header.producer_id();
+        let entry = self.producers.entry(producer_id).or_insert_with(|| ProducerStateEntry {
+            producer_epoch: header.producer_epoch(),
+            batches: VecDeque::new(),
+            current_txn_first_offset: None,
+        });
+        if header.producer_epoch() != entry.producer_epoch {
+            entry.producer_epoch = header.producer_epoch();
+            entry.batches.clear();
+        }
+        // transaction markers are written by the coordinator rather than the producer, so they have no sequence numbers
+        if header.is_control() {
+            let first_offset = entry.current_txn_first_offset.take()?;
+            return Some(CompletedTxn {
+                producer_id,
+                first_offset,
+                last_offset: header.base_offset(),
+                is_aborted: marker? == ControlRecordType::Abort,
+            });
+        }
+        if header.is_transactional() && entry.current_txn_first_offset.is_none() {
+            entry.current_txn_first_offset = Some(header.base_offset());
+        }
+        entry.batches.push_back(BatchMetadata {
+            first_sequence: header.base_sequence(),
+            last_sequence: header.last_sequence(),
+            first_offset: header.base_offset(),
+            last_offset: header.last_offset(),
+            timestamp: header.max_timestamp(),
+        });
+        if entry.batches.len() > NUM_BATCHES_TO_RETAIN {
+            entry.batches.pop_front();
+        }
+        None
+    }
+
+    /// Write the state to a snapshot named after the offset it's up to, deleting older snapshots
+    pub fn take_snapshot(&mut self) -> io::Result<()> {
+        let path = self.snapshot_path(self.map_end_offset);
+        // write to a temporary file first, so a snapshot is never seen partially written
+        let temp_path = path.with_extension("snapshot.tmp");
+        fs::write(&temp_path, write_snapshot(&self.producers))?;
+        fs::rename(&temp_path, &path)?;
+
+        let offsets = self.snapshot_offsets()?;
+        let num_to_delete = offsets.len().saturating_sub(NUM_SNAPSHOTS_TO_RETAIN);
+        for offset in &offsets[..num_to_delete] {
+            fs::remove_file(self.snapshot_path(*offset))?;
+        }
+        Ok(())
+    }
+
+    fn snapshot_path(&self, offset: i64) -> PathBuf {
+        self.dir.join(segment_file_name(offset, "snapshot"))
+    }
+
+    /// The offsets of the snapshots in the directory, oldest first
+    fn snapshot_offsets(&self) -> io::Result<Vec<i64>> {
+        let mut offsets = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let file_name = entry?.file_name();
+            let offset = file_name.to_str()
+                .and_then(|name| name.strip_suffix(".snapshot"))
+                .and_then(|offset| offset.parse::<i64>().ok());
+            offsets.extend(offset);
+        }
+        offsets.sort();
+        Ok(offsets)
+    }
+}
+
+/// Encode the producers the way kafka does, where only each producer's last batch is kept
+fn write_snapshot(producers: &BTreeMap<i64, ProducerStateEntry>) -> Vec<u8> {
+    let last_batches: Vec<(&i64, &ProducerStateEntry, &BatchMetadata)> = producers.iter()
+        .filter_map(|(producer_id, entry)| Some((producer_id, entry, entry.batches.back()?)))
+        .collect();
+    let mut entries = Vec::with_capacity(4 + last_batches.len() * SNAPSHOT_ENTRY_SIZE);
+    entries.extend((last_batches.len() as i32).to_be_bytes());
+    for (producer_id, entry, batch) in last_batches {
+        entries.extend(producer_id.to_be_bytes());
+        entries.extend(entry.producer_epoch.to_be_bytes());
+        entries.extend(batch.last_sequence.to_be_bytes());
+        entries.extend(batch.last_offset.to_be_bytes());
+        entries.extend(((batch.last_offset - batch.first_offset) as i32).to_be_bytes());
+        entries.extend(batch.timestamp.to_be_bytes());
+        // the coordinator epoch isn't tracked, only the first offset of the producer's current transaction
+        entries.extend((-1i32).to_be_bytes());
+        entries.extend(entry.current_txn_first_offset.unwrap_or(-1).to_be_bytes());
+    }
+    let mut snapshot = Vec::with_capacity(SNAPSHOT_HEADER_SIZE + entries.len());
+    snapshot.extend(SNAPSHOT_VERSION.to_be_bytes());
+    snapshot.extend(crc32c::crc32c(&entries).to_be_bytes());
+    snapshot.extend(entries);
+    snapshot
+}
+
+/// Decode a snapshot, or None if it's corrupt or a version we can't read
+fn read_snapshot(bytes: &[u8]) -> Option<BTreeMap<i64, ProducerStateEntry>> {
+    if bytes.len() < SNAPSHOT_HEADER_SIZE + 4 || i16::from_be_bytes(bytes[0..2].try_into().ok()?) != SNAPSHOT_VERSION {
+        return None;
+    }
+    let crc = u32::from_be_bytes(bytes[2..6].try_into().ok()?);
+    let entries = &bytes[SNAPSHOT_HEADER_SIZE..];
+    if crc32c::crc32c(entries) != crc {
+        return None;
+    }
+    let count = i32::from_be_bytes(entries[0..4].try_into().ok()?).max(0) as usize;
+    let entries = &entries[4..];
+    if entries.len() != count * SNAPSHOT_ENTRY_SIZE {
+        return None;
+    }
+    let producers = entries.chunks_exact(SNAPSHOT_ENTRY_SIZE)
+        .map(|entry| {
+            let producer_id = i64::from_be_bytes(entry[0..8].try_into().unwrap());
+            let producer_epoch = i16::from_be_bytes(entry[8..10].try_into().unwrap());
+            let last_sequence = i32::from_be_bytes(entry[10..14].try_into().unwrap());
+            let last_offset = i64::from_be_bytes(entry[14..22].try_into().unwrap());
+            let offset_delta = i32::from_be_bytes(entry[22..26].try_into().unwrap());
+            let timestamp = i64::from_be_bytes(entry[26..34].try_into().unwrap());
+            let current_txn_first_offset = i64::from_be_bytes(entry[38..46].try_into().unwrap());
+            let batch = BatchMetadata {
+                first_sequence: decrement_sequence(last_sequence, offset_delta),
+                last_sequence,
+                first_offset: last_offset - offset_delta as i64,
+                last_offset,
+                timestamp,
+            };
+            let entry = ProducerStateEntry {
+                producer_epoch,
+                batches: VecDeque::from([batch]),
+                current_txn_first_offset: Some(current_txn_first_offset).filter(|offset| *offset >= 0),
+            };
+            (producer_id, entry)
+        })
+        .collect();
+    Some(producers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::record_batch::{end_transaction_marker, RecordBatchBuilder};
+
+    /// The header of a batch of records from a producer, which has been appended at the offset
+    fn batch(offset: i64, producer_epoch: i16, base_sequence: i32, records: i64) -> RecordBatchHeader {
+        let mut batch = (0..records).fold(RecordBatchBuilder::new(), |batch, _| batch.add_record(0, None, None)).build();
+        batch[0..8].copy_from_slice(&offset.to_be_bytes());
+        batch[43..51].copy_from_slice(&7i64.to_be_bytes());
+        batch[51..53].copy_from_slice(&producer_epoch.to_be_bytes());
+        batch[53..57].copy_from_slice(&base_sequence.to_be_bytes());
+        RecordBatchHeader::parse(&batch).unwrap()
+    }
+
+    /// The header of the batch once it's been appended at the offset
+    fn appended(mut batch: Vec<u8>, offset: i64) -> RecordBatchHeader {
+        batch[0..8].copy_from_slice(&offset.to_be_bytes());
+        RecordBatchHeader::parse(&batch).unwrap()
+    }
+
+    #[test]
+    fn test_sequence_checks_and_snapshots() {
+        let dir = std::env::temp_dir().join(format!("producer-state-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut state = ProducerStateManager::load(&dir, 0).unwrap();
+        state.update(&batch(0, 0, 0, 2), None);
+        state.update(&batch(2, 0, 2, 3), None);
+
+        // a retry of either batch is a duplicate of the batch already written
+        assert_eq!(state.check(&batch(5, 0, 0, 2)).unwrap().map(|batch| batch.first_offset()), Some(0));
+        assert_eq!(state.check(&batch(5, 0, 2, 3)).unwrap().map(|batch| batch.first_offset()), Some(2));
+        assert!(state.check(&batch(5, 0, 5, 1)).unwrap().is_none());
+        assert!(matches!(state.check(&batch(5, 0, 6, 1)), Err(ProducerStateError::OutOfOrderSequence { .. })));
+        // a bumped epoch starts from sequence 0, and older epochs are fenced
+        assert!(state.check(&batch(5, 1, 0, 1)).unwrap().is_none());
+        assert!(matches!(state.check(&batch(5, 1, 3, 1)), Err(ProducerStateError::OutOfOrderSequence { .. })));
+        state.update(&batch(5, 1, 0, 1), None);
+        assert!(matches!(state.check(&batch(6, 0, 5, 1)), Err(ProducerStateError::InvalidProducerEpoch { .. })));
+
+        state.take_snapshot().unwrap();
+        let loaded = ProducerStateManager::load(&dir, 6).unwrap();
+        assert_eq!(loaded.map_end_offset(), 6);
+        assert_eq!(loaded.check(&batch(6, 1, 0, 1)).unwrap().map(|batch| batch.first_offset()), Some(5));
+        assert!(loaded.check(&batch(6, 1, 1, 1)).unwrap().is_none());
+        // a snapshot past the end of the log is discarded
+        assert_eq!(ProducerStateManager::load(&dir, 5).unwrap().map_end_offset(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_transactions() {
+        let dir = std::env::temp_dir().join(format!("producer-state-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut state = ProducerStateManager::load(&dir, 0).unwrap();
+        state.update(&batch(0, 0, 0, 2), None);
+        let transactional = RecordBatchBuilder::new().transactional(8, 0).add_record(0, None, None).build();
+        state.update(&appended(transactional.clone(), 2), None);
+        state.update(&appended(transactional, 3), None);
+        assert_eq!(state.first_unstable_offset(), Some(2));
+        let producers = state.active_producers();
+        assert_eq!(producers.iter().map(|producer| (producer.producer_id(), producer.current_txn_first_offset())).collect::<Vec<_>>(), [(7, None), (8, Some(2))]);
+
+        // the ongoing transaction survives a snapshot
+        state.take_snapshot().unwrap();
+        assert_eq!(ProducerStateManager::load(&dir, 4).unwrap().first_unstable_offset(), Some(2));
+
+        let marker = end_transaction_marker(8, 0, 0, ControlRecordType::Abort, 0);
+        let completed = state.update(&appended(marker.clone(), 4), Some(ControlRecordType::Abort)).unwrap();
+        assert_eq!((completed.producer_id(), completed.first_offset(), completed.last_offset(), completed.is_aborted()), (8, 2, 4, true));
+        assert_eq!(state.first_unstable_offset(), None);
+        // a marker for a producer without an ongoing transaction doesn't complete anything
+        assert!(state.update(&appended(marker, 5), Some(ControlRecordType::Abort)).is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,452 @@
+use std::io;
+use thiserror::Error;
+use crate::serialisation::varint::{read_signed_varint, signed_varint_bytes};
+
+/// The size of the header at the start of every v2 record batch, before the records themselves
+pub const BATCH_HEADER_SIZE: usize = 61;
+/// The base offset and batch length fields aren't included in the batch length
+const LOG_OVERHEAD: usize = 12;
+/// The CRC covers everything from the attributes onwards
+const CRC_START: usize = 21;
+
+const COMPRESSION_CODEC_MASK: i16 = 0b0111;
+const TIMESTAMP_TYPE_MASK: i16 = 0b1000;
+const TRANSACTIONAL_MASK: i16 = 0b1_0000;
+const CONTROL_MASK: i16 = 0b10_0000;
+
+#[derive(Debug, Error)]
+pub enum RecordBatchError {
+    #[error("Record batch is truncated, expected {0} bytes")]
+    Truncated(usize),
+    #[error("Unsupported record batch magic: {0}")]
+    UnsupportedMagic(i8),
+    #[error("Invalid record batch length: {0}")]
+    InvalidLength(i32),
+    #[error("Compressed record batches can't be read, compression codec: {0}")]
+    Compressed(i16),
+    #[error("Record is malformed")]
+    MalformedRecord,
+}
+
+impl From<RecordBatchError> for io::Error {
+    fn from(value: RecordBatchError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, value)
+    }
+}
+
+/// The header of a v2 record batch, as it is stored on disk and sent over the wire
+#[derive(Debug, Clone)]
+pub struct RecordBatchHeader {
+    base_offset: i64,
+    batch_length: i32,
+    partition_leader_epoch: i32,
+    magic: i8,
+    crc: u32,
+    attributes: i16,
+    last_offset_delta: i32,
+    base_timestamp: i64,
+    max_timestamp: i64,
+    producer_id: i64,
+    producer_epoch: i16,
+    base_sequence: i32,
+    records_count: i32,
+}
+
+impl RecordBatchHeader {
+    pub fn parse(bytes: &[u8]) -> Result<RecordBatchHeader, RecordBatchError> {
+        if bytes.len() < BATCH_HEADER_SIZE {
+            return Err(RecordBatchError::Truncated(BATCH_HEADER_SIZE));
+        }
+        let magic = bytes[16] as i8;
+        if magic != 2 {
+            return Err(RecordBatchError::UnsupportedMagic(magic));
+        }
+        let batch_length = i32::from_be_bytes(bytes[8..12].try_into().unwrap());
+        if (batch_length as i64) < (BATCH_HEADER_SIZE - LOG_OVERHEAD) as i64 {
+            return Err(RecordBatchError::InvalidLength(batch_length));
+        }
+        Ok(RecordBatchHeader {
+            base_offset: i64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            batch_length,
+            partition_leader_epoch: i32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+            magic,
+            crc: u32::from_be_bytes(bytes[17..21].try_into().unwrap()),
+            attributes: i16::from_be_bytes(bytes[21..23].try_into().unwrap()),
+            last_offset_delta: i32::from_be_bytes(bytes[23..27].try_into().unwrap()),
+            base_timestamp: i64::from_be_bytes(bytes[27..35].try_into().unwrap()),
+            max_timestamp: i64::from_be_bytes(bytes[35..43].try_into().unwrap()),
+            producer_id: i64::from_be_bytes(bytes[43..51].try_into().unwrap()),
+            producer_epoch: i16::from_be_bytes(bytes[51..53].try_into().unwrap()),
+            base_sequence: i32::from_be_bytes(bytes[53..57].try_into().unwrap()),
+            records_count: i32::from_be_bytes(bytes[57..61].try_into().unwrap()),
+        })
+    }
+
+    pub fn base_offset(&self) -> i64 {
+        self.base_offset
+    }
+
+    pub fn last_offset(&self) -> i64 {
+        self.base_offset + self.last_offset_delta as i64
+    }
+
+    /// How far the last record's offset is from the base offset, which is one less than the number of records
+    pub fn last_offset_delta(&self) -> i32 {
+        self.last_offset_delta
+    }
+
+    /// The offset of the first record after this batch
+    pub fn next_offset(&self) -> i64 {
+        self.last_offset() + 1
+    }
+
+    /// The total size of the batch, including the header
+    pub fn size_in_bytes(&self) -> usize {
+        LOG_OVERHEAD + self.batch_length as usize
+    }
+
+    pub fn partition_leader_epoch(&self) -> i32 {
+        self.partition_leader_epoch
+    }
+
+    pub fn magic(&self) -> i8 {
+        self.magic
+    }
+
+    pub fn crc(&self) -> u32 {
+        self.crc
+    }
+
+    pub fn base_timestamp(&self) -> i64 {
+        self.base_timestamp
+    }
+
+    pub fn max_timestamp(&self) -> i64 {
+        self.max_timestamp
+    }
+
+    pub fn compression_codec(&self) -> i16 {
+        self.attributes & COMPRESSION_CODEC_MASK
+    }
+
+    /// With LogAppendTime, the broker sets the timestamp of every record in the batch to the max timestamp
+    pub fn is_log_append_time(&self) -> bool {
+        self.attributes & TIMESTAMP_TYPE_MASK != 0
+    }
+
+    pub fn is_transactional(&self) -> bool {
+        self.attributes & TRANSACTIONAL_MASK != 0
+    }
+
+    pub fn is_control(&self) -> bool {
+        self.attributes & CONTROL_MASK != 0
+    }
+
+    /// Whether the batch was written by a producer with a producer id, so it has sequence numbers
+    pub fn has_producer_id(&self) -> bool {
+        self.producer_id >= 0
+    }
+
+    pub fn producer_id(&self) -> i64 {
+        self.producer_id
+    }
+
+    pub fn producer_epoch(&self) -> i16 {
+        self.producer_epoch
+    }
+
+    pub fn base_sequence(&self) -> i32 {
+        self.base_sequence
+    }
+
+    /// The sequence number of the last record in the batch
+    pub fn last_sequence(&self) -> i32 {
+        increment_sequence(self.base_sequence, self.last_offset_delta)
+    }
+
+    pub fn records_count(&self) -> i32 {
+        self.records_count
+    }
+
+    /// Whether the CRC matches the bytes it covers, given the bytes of the whole batch
+    pub fn has_valid_crc(&self, batch: &[u8]) -> bool {
+        batch.len() >= self.size_in_bytes() && crc32c::crc32c(&batch[CRC_START..self.size_in_bytes()]) == self.crc
+    }
+
+    /// Parse the records of the batch from the bytes of the whole batch, including this header
+    pub fn records(&self, batch: &[u8]) -> Result<Vec<Record>, RecordBatchError> {
+        if batch.len() < self.size_in_bytes() {
+            return Err(RecordBatchError::Truncated(self.size_in_bytes()));
+        }
+        if self.compression_codec() != 0 {
+            return Err(RecordBatchError::Compressed(self.compression_codec()));
+        }
+        let mut bytes = &batch[BATCH_HEADER_SIZE..self.size_in_bytes()];
+        (0..self.records_count)
+            .map(|_| Record::parse(&mut bytes, self))
+            .collect()
+    }
+
+    /// The type of transaction marker a control batch is, given the bytes of the whole batch.
+    /// None if the batch isn't a control batch, or its control record isn't a transaction marker
+    pub fn control_record_type(&self, batch: &[u8]) -> Result<Option<ControlRecordType>, RecordBatchError> {
+        if !self.is_control() {
+            return Ok(None);
+        }
+        Ok(self.records(batch)?
+            .first()
+            .and_then(|record| record.key().and_then(ControlRecordType::parse)))
+    }
+}
+
+/// Switch the batch to LogAppendTime, so every record in it has the timestamp it was appended at.
+/// The attributes and max timestamp are covered by the CRC, so it has to be recomputed
+pub fn set_log_append_time(batch: &mut [u8], timestamp: i64) {
+    let attributes = i16::from_be_bytes(batch[21..23].try_into().unwrap()) | TIMESTAMP_TYPE_MASK;
+    batch[21..23].copy_from_slice(&attributes.to_be_bytes());
+    batch[35..43].copy_from_slice(&timestamp.to_be_bytes());
+    let crc = crc32c::crc32c(&batch[CRC_START..]);
+    batch[17..21].copy_from_slice(&crc.to_be_bytes());
+}
+
+/// Add to a sequence number, which wraps around to 0 after i32::MAX
+pub fn increment_sequence(sequence: i32, increment: i32) -> i32 {
+    match sequence > i32::MAX - increment {
+        true => increment - (i32::MAX - sequence) - 1,
+        false => sequence + increment,
+    }
+}
+
+/// Subtract from a sequence number, which wraps around to i32::MAX before 0
+pub fn decrement_sequence(sequence: i32, decrement: i32) -> i32 {
+    match sequence < decrement {
+        true => i32::MAX - (decrement - sequence) + 1,
+        false => sequence - decrement,
+    }
+}
+
+/// The type of a control record, which is in its key
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ControlRecordType {
+    /// Marks the end of an aborted transaction
+    Abort,
+    /// Marks the end of a committed transaction
+    Commit,
+}
+
+impl ControlRecordType {
+    /// The type of the control record with the key, or None if it isn't a transaction marker
+    pub fn parse(key: &[u8]) -> Option<ControlRecordType> {
+        match i16::from_be_bytes(key.get(2..4)?.try_into().ok()?) {
+            0 => Some(ControlRecordType::Abort),
+            1 => Some(ControlRecordType::Commit),
+            _ => None,
+        }
+    }
+
+    fn id(&self) -> i16 {
+        match self {
+            ControlRecordType::Abort => 0,
+            ControlRecordType::Commit => 1,
+        }
+    }
+}
+
+/// Build the control batch that ends a producer's transaction in a partition
+pub fn end_transaction_marker(producer_id: i64, producer_epoch: i16, coordinator_epoch: i32, marker: ControlRecordType, timestamp: i64) -> Vec<u8> {
+    // both the key and value are a version, followed by the marker's type or the epoch of the coordinator that wrote it
+    let mut key = 0i16.to_be_bytes().to_vec();
+    key.extend(marker.id().to_be_bytes());
+    let mut value = 0i16.to_be_bytes().to_vec();
+    value.extend(coordinator_epoch.to_be_bytes());
+    RecordBatchBuilder::new()
+        .transactional(producer_id, producer_epoch)
+        .control()
+        .add_record(timestamp, Some(key), Some(value))
+        .build()
+}
+
+/// Builds an uncompressed v2 record batch, without any producer information unless it's transactional.
+/// The base offset and leader epoch are left for the log to fill in when the batch is appended
+#[derive(Debug)]
+pub struct RecordBatchBuilder {
+    records: Vec<Record>,
+    attributes: i16,
+    producer_id: i64,
+    producer_epoch: i16,
+}
+
+impl Default for RecordBatchBuilder {
+    fn default() -> Self {
+        RecordBatchBuilder { records: Vec::new(), attributes: 0, producer_id: -1, producer_epoch: -1 }
+    }
+}
+
+impl RecordBatchBuilder {
+    pub fn new() -> RecordBatchBuilder {
+        RecordBatchBuilder::default()
+    }
+
+    /// Make the batch part of the producer's transaction, such as offsets committed by the producer.
+    /// The batch has no sequence numbers, since it isn't written by the producer itself
+    pub fn transactional(mut self, producer_id: i64, producer_epoch: i16) -> RecordBatchBuilder {
+        self.attributes |= TRANSACTIONAL_MASK;
+        self.producer_id = producer_id;
+        self.producer_epoch = producer_epoch;
+        self
+    }
+
+    fn control(mut self) -> RecordBatchBuilder {
+        self.attributes |= CONTROL_MASK;
+        self
+    }
+
+    pub fn add_record(mut self, timestamp: i64, key: Option<Vec<u8>>, value: Option<Vec<u8>>) -> RecordBatchBuilder {
+        self.records.push(Record {
+            offset: self.records.len() as i64,
+            timestamp,
+            key,
+            value,
+            headers: Vec::new(),
+        });
+        self
+    }
+
+    pub fn build(self) -> Vec<u8> {
+        let base_timestamp = self.records.first().map_or(-1, Record::timestamp);
+        let max_timestamp = self.records.iter().map(Record::timestamp).max().unwrap_or(-1);
+        let records: Vec<u8> = self.records.iter()
+            .flat_map(|record| record.to_bytes(base_timestamp))
+            .collect();
+
+        let mut crc_covered = Vec::with_capacity(BATCH_HEADER_SIZE - CRC_START + records.len());
+        crc_covered.extend(self.attributes.to_be_bytes());
+        crc_covered.extend((self.records.len() as i32 - 1).max(0).to_be_bytes()); // last offset delta
+        crc_covered.extend(base_timestamp.to_be_bytes());
+        crc_covered.extend(max_timestamp.to_be_bytes());
+        crc_covered.extend(self.producer_id.to_be_bytes());
+        crc_covered.extend(self.producer_epoch.to_be_bytes());
+        crc_covered.extend((-1i32).to_be_bytes()); // base sequence
+        crc_covered.extend((self.records.len() as i32).to_be_bytes());
+        crc_covered.extend(records);
+
+        let batch_length = (CRC_START - LOG_OVERHEAD + crc_covered.len()) as i32;
+        let mut batch = Vec::with_capacity(LOG_OVERHEAD + batch_length as usize);
+        batch.extend(0i64.to_be_bytes()); // base offset
+        batch.extend(batch_length.to_be_bytes());
+        batch.extend((-1i32).to_be_bytes()); // partition leader epoch
+        batch.push(2); // magic
+        batch.extend(crc32c::crc32c(&crc_covered).to_be_bytes());
+        batch.extend(crc_covered);
+        batch
+    }
+}
+
+/// A single record in a record batch
+#[derive(Debug, Clone)]
+pub struct Record {
+    offset: i64,
+    timestamp: i64,
+    key: Option<Vec<u8>>,
+    value: Option<Vec<u8>>,
+    headers: Vec<(String, Option<Vec<u8>>)>,
+}
+
+impl Record {
+    fn parse(bytes: &mut &[u8], batch: &RecordBatchHeader) -> Result<Record, RecordBatchError> {
+        use RecordBatchError::MalformedRecord;
+
+        let length = read_signed_varint(bytes).ok_or(MalformedRecord)?;
+        if length < 0 || length as usize > bytes.len() {
+            return Err(MalformedRecord);
+        }
+        let (mut record, rest) = bytes.split_at(length as usize);
+        *bytes = rest;
+
+        let (_attributes, rest) = record.split_first().ok_or(MalformedRecord)?;
+        record = rest;
+        let timestamp_delta = read_signed_varint(&mut record).ok_or(MalformedRecord)?;
+        let offset_delta = read_signed_varint(&mut record).ok_or(MalformedRecord)?;
+        let key = read_varint_bytes(&mut record)?;
+        let value = read_varint_bytes(&mut record)?;
+        let num_headers = read_signed_varint(&mut record).ok_or(MalformedRecord)?;
+        let headers = (0..num_headers.max(0))
+            .map(|_| {
+                let header_key = read_varint_bytes(&mut record)?.ok_or(MalformedRecord)?;
+                let header_key = String::from_utf8(header_key).map_err(|_| MalformedRecord)?;
+                Ok((header_key, read_varint_bytes(&mut record)?))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let timestamp = if batch.is_log_append_time() {
+            batch.max_timestamp
+        } else {
+            batch.base_timestamp + timestamp_delta
+        };
+        Ok(Record {
+            offset: batch.base_offset + offset_delta,
+            timestamp,
+            key,
+            value,
+            headers,
+        })
+    }
+
+    /// Encode the record, with its offset and timestamp relative to the start of its batch
+    fn to_bytes(&self, base_timestamp: i64) -> Vec<u8> {
+        fn varint_bytes(bytes: Option<&[u8]>) -> Vec<u8> {
+            let mut result = signed_varint_bytes(bytes.map_or(-1, |bytes| bytes.len() as i64));
+            result.extend(bytes.unwrap_or_default());
+            result
+        }
+
+        let mut record = vec![0u8]; // attributes
+        record.extend(signed_varint_bytes(self.timestamp - base_timestamp));
+        record.extend(signed_varint_bytes(self.offset));
+        record.extend(varint_bytes(self.key()));
+        record.extend(varint_bytes(self.value()));
+        record.extend(signed_varint_bytes(self.headers.len() as i64));
+        for (header_key, header_value) in &self.headers {
+            record.extend(varint_bytes(Some(header_key.as_bytes())));
+            record.extend(varint_bytes(header_value.as_deref()));
+        }
+
+        let mut bytes = signed_varint_bytes(record.len() as i64);
+        bytes.extend(record);
+        bytes
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.offset
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+
+    pub fn key(&self) -> Option<&[u8]> {
+        self.key.as_deref()
+    }
+
+    pub fn value(&self) -> Option<&[u8]> {
+        self.value.as_deref()
+    }
+
+    pub fn headers(&self) -> &[(String, Option<Vec<u8>>)] {
+        &self.headers
+    }
+}
+
+/// Read bytes prefixed by their varint length, where a length of -1 means null
+fn read_varint_bytes(bytes: &mut &[u8]) -> Result<Option<Vec<u8>>, RecordBatchError> {
+    let length = read_signed_varint(bytes).ok_or(RecordBatchError::MalformedRecord)?;
+    match length {
+        -1 => Ok(None),
+        length if length < 0 || length as usize > bytes.len() => Err(RecordBatchError::MalformedRecord),
+        length => {
+            let (value, rest) = bytes.split_at(length as usize);
+            *bytes = rest;
+            Ok(Some(value.to_vec()))
+        }
+    }
+}
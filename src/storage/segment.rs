@@ -0,0 +1,361 @@
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use tracing::warn;
+use crate::storage::file_records::{read_exact_at, FileSlice};
+use crate::storage::index::{OffsetIndex, TimeIndex, TransactionIndex};
+use crate::storage::log::{AbortedTxn, TimestampAndOffset};
+use crate::storage::record_batch::{ControlRecordType, RecordBatchHeader, BATCH_HEADER_SIZE};
+
+/// Segment files are named after their base offset, padded so they sort in offset order
+pub(super) fn segment_file_name(base_offset: i64, extension: &str) -> String {
+    format!("{base_offset:020}.{extension}")
+}
+
+/// A contiguous range of a partition's log, stored in a `.log` file along with its indexes
+#[derive(Debug)]
+pub(super) struct LogSegment {
+    base_offset: i64,
+    log_path: PathBuf,
+    /// The log file is kept open while the segment is, batches are appended to its end and read from their positions
+    file: Arc<File>,
+    /// The size of the log file, which is where the next batch will be appended
+    size: u64,
+    offset_index: OffsetIndex,
+    time_index: TimeIndex,
+    txn_index: TransactionIndex,
+    next_offset: i64,
+    max_timestamp: i64,
+    /// The last offset of the first batch that had the largest timestamp
+    offset_of_max_timestamp: i64,
+    /// The bytes appended since the last batch that was indexed
+    bytes_since_last_index_entry: u64,
+}
+
+impl LogSegment {
+    /// Open the segment with the base offset. If the broker didn't shut down cleanly it's recovered
+    /// from the given offset first, so it doesn't end with a batch that was only partially written
+    pub fn open(dir: &Path, base_offset: i64, recover_from: Option<i64>) -> io::Result<LogSegment> {
+        let log_path = dir.join(segment_file_name(base_offset, "log"));
+        let file = OpenOptions::new().create(true).read(true).append(true).open(&log_path)?;
+        let size = file.metadata()?.len();
+        let offset_index = OffsetIndex::load(&dir.join(segment_file_name(base_offset, "index")), base_offset)?;
+        let time_index = TimeIndex::load(&dir.join(segment_file_name(base_offset, "timeindex")), base_offset)?;
+        let txn_index = TransactionIndex::load(&dir.join(segment_file_name(base_offset, "txnindex")))?;
+        let mut segment = LogSegment {
+            base_offset,
+            log_path,
+            file: Arc::new(file),
+            size,
+            offset_index,
+            time_index,
+            txn_index,
+            next_offset: base_offset,
+            max_timestamp: -1,
+            offset_of_max_timestamp: base_offset,
+            bytes_since_last_index_entry: 0,
+        };
+        let truncated = match recover_from {
+            Some(offset) => segment.truncate_invalid_batches(offset)?,
+            None => false,
+        };
+        if truncated {
+            segment.offset_index.truncate_to_position(segment.size)?;
+        }
+
+        // the indexes are sparse, so scan the batches after the last index entry to find the end of the segment
+        let (_, scan_start) = segment.offset_index.last_entry();
+        let mut scanned = Vec::new();
+        for batch in segment.batches_from(scan_start) {
+            scanned.push(batch?.1);
+        }
+        if let Some(batch) = scanned.last() {
+            segment.next_offset = batch.next_offset();
+        }
+        if truncated {
+            segment.time_index.truncate_to(segment.next_offset)?;
+            segment.txn_index.truncate_to(segment.next_offset)?;
+        }
+        (segment.max_timestamp, segment.offset_of_max_timestamp) = segment.time_index.last_entry()
+            .unwrap_or((-1, base_offset));
+        for batch in &scanned {
+            segment.update_max_timestamp(batch);
+        }
+        segment.bytes_since_last_index_entry = segment.size - scan_start;
+        Ok(segment)
+    }
+
+    /// Check the batches from the one containing the offset onwards, truncating the segment at the first batch that's
+    /// partially written or doesn't match its checksum, since nothing after it can be trusted either.
+    /// Returns whether anything was truncated
+    fn truncate_invalid_batches(&mut self, from_offset: i64) -> io::Result<bool> {
+        let (_, start) = self.offset_index.lookup(from_offset);
+        let mut position = start.min(self.size);
+        let mut header_bytes = [0u8; BATCH_HEADER_SIZE];
+        while position + BATCH_HEADER_SIZE as u64 <= self.size {
+            read_exact_at(&self.file, &mut header_bytes, position)?;
+            let Ok(header) = RecordBatchHeader::parse(&header_bytes) else {
+                break;
+            };
+            let size = header.size_in_bytes();
+            if size < BATCH_HEADER_SIZE || position + size as u64 > self.size {
+                break;
+            }
+            let mut batch = vec![0u8; size];
+            read_exact_at(&self.file, &mut batch, position)?;
+            if !header.has_valid_crc(&batch) {
+                break;
+            }
+            position += size as u64;
+        }
+
+        if position == self.size {
+            return Ok(false);
+        }
+        warn!("Truncating {} invalid bytes from the end of {}", self.size - position, self.log_path.display());
+        self.file.set_len(position)?;
+        self.file.sync_all()?;
+        self.size = position;
+        Ok(true)
+    }
+
+    /// Make sure everything written to the segment is on disk
+    pub fn flush(&self) -> io::Result<()> {
+        self.file.sync_all()?;
+        self.offset_index.flush()?;
+        self.time_index.flush()?;
+        self.txn_index.flush()
+    }
+
+    /// Create a new empty segment, along with empty index files
+    pub fn create(dir: &Path, base_offset: i64) -> io::Result<LogSegment> {
+        LogSegment::open(dir, base_offset, None)
+    }
+
+    /// Whether a batch of the given size ending at the last offset has to go in a new segment instead, because this one
+    /// would grow past the max size or the offset couldn't be indexed relative to this segment's base offset.
+    /// An empty segment takes any batch, however big it is
+    pub fn should_roll(&self, batch_size: usize, last_offset: i64, segment_bytes: u64) -> bool {
+        self.size > 0 && (self.size + batch_size as u64 > segment_bytes || last_offset - self.base_offset > i32::MAX as i64)
+    }
+
+    /// Append a batch, which must start at this segment's next offset, to the end of the segment file.
+    /// The batch is indexed if more than the index interval has been appended since the last indexed batch
+    pub fn append(&mut self, header: &RecordBatchHeader, batch: &[u8], index_interval_bytes: u64) -> io::Result<()> {
+        let position = self.size;
+        (&*self.file).write_all(batch)?;
+        self.size += batch.len() as u64;
+        self.next_offset = header.next_offset();
+        self.update_max_timestamp(header);
+        if self.bytes_since_last_index_entry > index_interval_bytes {
+            self.offset_index.append(header.last_offset(), position)?;
+            self.time_index.maybe_append(self.max_timestamp, self.offset_of_max_timestamp)?;
+            self.bytes_since_last_index_entry = 0;
+        }
+        self.bytes_since_last_index_entry += batch.len() as u64;
+        Ok(())
+    }
+
+    fn update_max_timestamp(&mut self, header: &RecordBatchHeader) {
+        if header.max_timestamp() > self.max_timestamp {
+            self.max_timestamp = header.max_timestamp();
+            self.offset_of_max_timestamp = header.last_offset();
+        }
+    }
+
+    /// Record a transaction whose abort marker was appended to this segment
+    pub fn append_aborted_txn(&mut self, txn: AbortedTxn) -> io::Result<()> {
+        self.txn_index.append(txn)
+    }
+
+    /// The transactions aborted in this segment with records from the start offset up to, but not including, the end offset
+    pub fn aborted_txns(&self, start_offset: i64, end_offset: i64) -> impl Iterator<Item = AbortedTxn> + '_ {
+        self.txn_index.overlapping(start_offset, end_offset)
+    }
+
+    pub fn base_offset(&self) -> i64 {
+        self.base_offset
+    }
+
+    /// The offset after the last record in this segment
+    pub fn next_offset(&self) -> i64 {
+        self.next_offset
+    }
+
+    /// The size of the segment file
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The largest timestamp of any record in this segment, or -1 if it is empty
+    pub fn max_timestamp(&self) -> i64 {
+        self.max_timestamp
+    }
+
+    /// The largest timestamp of any record in this segment, or when it was last written to if its records have no timestamps,
+    /// which is what decides when it's past the retention time
+    pub fn largest_timestamp(&self) -> io::Result<i64> {
+        if self.max_timestamp >= 0 {
+            return Ok(self.max_timestamp);
+        }
+        let modified = self.file.metadata()?.modified()?;
+        Ok(modified.duration_since(UNIX_EPOCH).map_or(0, |since_epoch| since_epoch.as_millis() as i64))
+    }
+
+    /// Remove the segment's files. Reads that already have a slice of the log file can still send it
+    pub fn delete(self) -> io::Result<()> {
+        let dir = self.log_path.parent().expect("segment files are in the log's directory");
+        for extension in ["log", "index", "timeindex", "txnindex"] {
+            match fs::remove_file(dir.join(segment_file_name(self.base_offset, extension))) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the headers of the batches starting at the position in the segment file, along with their positions.
+    /// Each header is only read when the iterator gets to it, so a lookup stops reading once it's found its batch.
+    /// A partially written batch at the end of the file is ignored
+    fn batches_from(&self, position: u64) -> impl Iterator<Item = io::Result<(u64, RecordBatchHeader)>> + '_ {
+        let mut position = position;
+        iter::from_fn(move || {
+            if position + BATCH_HEADER_SIZE as u64 > self.size {
+                return None;
+            }
+            let mut header_bytes = [0u8; BATCH_HEADER_SIZE];
+            let header = read_exact_at(&self.file, &mut header_bytes, position)
+                .and_then(|()| Ok(RecordBatchHeader::parse(&header_bytes)?));
+            let header = match header {
+                Ok(header) => header,
+                Err(err) => {
+                    position = self.size;
+                    return Some(Err(err));
+                }
+            };
+            let batch_position = position;
+            position += header.size_in_bytes() as u64;
+            (position <= self.size).then_some(Ok((batch_position, header)))
+        })
+    }
+
+    /// Read the full bytes of the batch at the position in the segment file
+    fn read_batch(&self, position: u64, header: &RecordBatchHeader) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![0u8; header.size_in_bytes()];
+        read_exact_at(&self.file, &mut bytes, position)?;
+        Ok(bytes)
+    }
+
+    /// Read the batches from the one containing the start offset, stopping before a batch with records at or past
+    /// the max offset, or one that would take the size past max bytes. The first batch is read whatever its size
+    /// if `min_one_batch` is set, so consumers aren't stuck behind a batch bigger than they asked for.
+    /// Returns the slice of the file with the batches, along with the offset after the last batch read
+    pub fn read(&self, start_offset: i64, max_offset: i64, max_bytes: usize, min_one_batch: bool) -> io::Result<(Option<FileSlice>, i64)> {
+        let (_, position) = self.offset_index.lookup(start_offset);
+        let mut start_position = None;
+        let mut size = 0;
+        let mut next_offset = start_offset;
+        for batch in self.batches_from(position) {
+            let (position, batch) = batch?;
+            if batch.last_offset() < start_offset {
+                continue;
+            }
+            if batch.last_offset() >= max_offset || size + batch.size_in_bytes() > max_bytes && !(size == 0 && min_one_batch) {
+                break;
+            }
+            start_position.get_or_insert(position);
+            size += batch.size_in_bytes();
+            next_offset = batch.next_offset();
+        }
+        let slice = start_position.map(|position| FileSlice::new(self.file.clone(), position, size));
+        Ok((slice, next_offset))
+    }
+
+    /// Read every batch in the segment, along with its header
+    pub fn read_all_batches(&self) -> io::Result<Vec<(RecordBatchHeader, Vec<u8>)>> {
+        self.batches_from(0)
+            .map(|batch| {
+                let (position, header) = batch?;
+                let batch = self.read_batch(position, &header)?;
+                Ok((header, batch))
+            })
+            .collect()
+    }
+
+    /// The headers of the batch containing the offset and every batch after it in this segment,
+    /// along with the type of marker each control batch is, which is what's needed to rebuild the producer state
+    pub fn replay_batches_from(&self, offset: i64) -> io::Result<Vec<(RecordBatchHeader, Option<ControlRecordType>)>> {
+        let (_, position) = self.offset_index.lookup(offset);
+        self.batches_from(position)
+            .filter(|batch| batch.as_ref().map_or(true, |(_, batch)| batch.last_offset() >= offset))
+            .map(|batch| {
+                let (position, batch) = batch?;
+                let marker = match batch.is_control() {
+                    true => batch.control_record_type(&self.read_batch(position, &batch)?)?,
+                    false => None,
+                };
+                Ok((batch, marker))
+            })
+            .collect()
+    }
+
+    /// Find the batch containing the offset, if it's in this segment
+    pub fn batch_containing(&self, offset: i64) -> io::Result<Option<RecordBatchHeader>> {
+        let (_, position) = self.offset_index.lookup(offset);
+        for batch in self.batches_from(position) {
+            let (_, batch) = batch?;
+            if batch.last_offset() >= offset {
+                return Ok(Some(batch));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Find the first record with a timestamp greater than or equal to the target timestamp
+    pub fn find_offset_by_timestamp(&self, timestamp: i64) -> io::Result<Option<TimestampAndOffset>> {
+        if self.max_timestamp < timestamp {
+            return Ok(None);
+        }
+        let start_offset = self.time_index.lookup(timestamp).unwrap_or(self.base_offset);
+        self.find_record_from(start_offset, |batch_timestamp| batch_timestamp >= timestamp)
+    }
+
+    /// Find the first record with the largest timestamp in this segment
+    pub fn offset_of_max_timestamp(&self) -> io::Result<Option<TimestampAndOffset>> {
+        if self.max_timestamp < 0 {
+            return Ok(None);
+        }
+        self.find_record_from(self.offset_of_max_timestamp, |batch_timestamp| batch_timestamp == self.max_timestamp)
+    }
+
+    /// Find the first record from the batch containing the offset onwards whose timestamp matches the predicate
+    fn find_record_from(&self, offset: i64, predicate: impl Fn(i64) -> bool) -> io::Result<Option<TimestampAndOffset>> {
+        let (_, position) = self.offset_index.lookup(offset);
+        for batch in self.batches_from(position) {
+            let (position, batch) = batch?;
+            if predicate(batch.max_timestamp()) {
+                return self.find_record(position, &batch, predicate);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Find the first record in the batch whose timestamp matches the predicate.
+    /// Compressed batches can't be searched, so their first offset is used instead
+    fn find_record(&self, position: u64, batch: &RecordBatchHeader, predicate: impl Fn(i64) -> bool) -> io::Result<Option<TimestampAndOffset>> {
+        let leader_epoch = Some(batch.partition_leader_epoch()).filter(|epoch| *epoch >= 0);
+        if batch.compression_codec() != 0 {
+            return Ok(Some(TimestampAndOffset::new(batch.max_timestamp(), batch.base_offset(), leader_epoch)));
+        }
+        let batch_bytes = self.read_batch(position, batch)?;
+        Ok(batch.records(&batch_bytes)?
+            .into_iter()
+            .find(|record| predicate(record.timestamp()))
+            .map(|record| TimestampAndOffset::new(record.timestamp(), record.offset(), leader_epoch)))
+    }
+}
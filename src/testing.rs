@@ -0,0 +1,63 @@
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+use crate::api::api_key::ApiKey;
+use crate::api::request::KafkaRequest;
+use crate::broker::Broker;
+use crate::broker::config::BrokerConfig;
+use crate::serialisation::ToKafkaBytes;
+
+/// A directory under the system's temp directory, which is removed when it's dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(prefix: &str) -> TempDir {
+        let path = std::env::temp_dir().join(format!("{prefix}-{}", Uuid::new_v4().simple()));
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Open a broker with its log directory in a new temp directory, configured with the given properties on top of the defaults
+pub fn open_broker(properties: &str) -> (Broker, TempDir) {
+    let log_dir = TempDir::new("broker");
+    (open_broker_in(&log_dir, properties), log_dir)
+}
+
+/// Open a broker with its log directory in an existing directory, such as one another broker was shut down in
+pub fn open_broker_in(log_dir: &TempDir, properties: &str) -> Broker {
+    let config = BrokerConfig::from_properties(&format!("log.dirs={}\n{properties}", log_dir.path().display())).unwrap();
+    Broker::open(config).unwrap()
+}
+
+/// A request with a v1 header, or a v2 header in flexible versions, preceded by its size, the way a client sends it
+pub fn request_bytes(api_key: ApiKey, api_version: i16, correlation_id: i32, body: &[u8]) -> Vec<u8> {
+    let mut message = api_key.to_kafka_bytes();
+    message.extend(api_version.to_be_bytes());
+    message.extend(correlation_id.to_be_bytes());
+    message.extend(4i16.to_be_bytes());
+    message.extend(b"test");
+    // flexible versions have a v2 header, which ends with its tagged fields
+    if api_key.is_flexible(api_version) {
+        message.push(0);
+    }
+    message.extend(body);
+    [(message.len() as i32).to_be_bytes().to_vec(), message].concat()
+}
+
+/// Parse a request with the given body, as if a client had sent it
+pub fn parse_request(api_key: ApiKey, api_version: i16, body: &[u8]) -> KafkaRequest {
+    let request = request_bytes(api_key, api_version, 1, body);
+    KafkaRequest::parse(&mut &request[4..], IpAddr::V4(Ipv4Addr::LOCALHOST)).unwrap()
+}
@@ -0,0 +1,16 @@
+pub mod purgatory;
+pub mod timer;
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The current time as milliseconds since the unix epoch, which is how kafka represents timestamps
+pub fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as i64)
+}
+
+/// The instant an operation given a timeout in milliseconds must finish by, where a timeout of zero or less has already expired
+pub fn deadline_after(timeout_ms: i32) -> Instant {
+    Instant::now() + Duration::from_millis(timeout_ms.max(0) as u64)
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Parks operations that can't complete yet, such as a fetch waiting for records to be produced,
+/// until something they're watching changes or they time out, like kafka's delayed operation purgatory.
+/// Each operation is tried again in the task waiting for it, so triggering a key never runs another request's work
+#[derive(Debug)]
+pub struct Purgatory<K> {
+    /// The operations watching each key, which are woken when the key is triggered
+    watchers: Mutex<HashMap<K, Vec<Arc<Notify>>>>,
+    /// Set while the watchers are locked, so an operation that starts watching afterwards sees it
+    shut_down: AtomicBool,
+}
+
+impl<K> Default for Purgatory<K> {
+    fn default() -> Self {
+        Purgatory { watchers: Mutex::new(HashMap::new()), shut_down: AtomicBool::new(false) }
+    }
+}
+
+impl<K: Hash + Eq + Clone> Purgatory<K> {
+    pub fn new() -> Self {
+        Purgatory::default()
+    }
+
+    /// Complete the operation if it can be already, otherwise watch the keys and try it again each time one of them
+    /// is triggered. Returns None if the operation still couldn't complete once the timeout expired,
+    /// or straight away once the purgatory has been shut down, without trying the operation again
+    pub async fn try_complete_else_watch<T>(
+        &self,
+        keys: &[K],
+        timeout: Duration,
+        mut try_complete: impl FnMut() -> Option<T>,
+    ) -> Option<T> {
+        if self.is_shut_down() {
+            return None;
+        }
+        if let Some(result) = try_complete() {
+            return Some(result);
+        }
+
+        let deadline = tokio::time::Instant::now() + timeout;
+        let watching = Watching::new(self, keys);
+        loop {
+            if self.is_shut_down() {
+                return None;
+            }
+            // it's tried again once it's watching, since a key could have been triggered before then.
+            // A key triggered while it's being tried leaves a permit, so the next wait returns straight away
+            if let Some(result) = try_complete() {
+                return Some(result);
+            }
+            if tokio::time::timeout_at(deadline, watching.notify.notified()).await.is_err() {
+                return None;
+            }
+        }
+    }
+
+    /// Wake the operations watching the key, so they can try to complete again
+    pub fn check_and_complete(&self, key: &K) {
+        if let Some(watchers) = self.watchers.lock().unwrap().get(key) {
+            for notify in watchers {
+                notify.notify_one();
+            }
+        }
+    }
+
+    /// Wake every waiting operation so it gives up, and stop operations from waiting from now on
+    pub fn shutdown(&self) {
+        let watchers = self.watchers.lock().unwrap();
+        self.shut_down.store(true, Ordering::SeqCst);
+        for notify in watchers.values().flatten() {
+            notify.notify_one();
+        }
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.shut_down.load(Ordering::SeqCst)
+    }
+
+    /// The number of operations watching any key, counting an operation once for each key it's watching
+    pub fn watched(&self) -> usize {
+        self.watchers.lock().unwrap().values().map(Vec::len).sum()
+    }
+}
+
+/// An operation watching keys, which stops watching them however its wait ends, including when it's dropped
+struct Watching<'a, K: Hash + Eq + Clone> {
+    purgatory: &'a Purgatory<K>,
+    keys: &'a [K],
+    notify: Arc<Notify>,
+}
+
+impl<'a, K: Hash + Eq + Clone> Watching<'a, K> {
+    fn new(purgatory: &'a Purgatory<K>, keys: &'a [K]) -> Self {
+        let notify = Arc::new(Notify::new());
+        let mut watchers = purgatory.watchers.lock().unwrap();
+        for key in keys {
+            watchers.entry(key.clone()).or_default().push(notify.clone());
+        }
+        Watching { purgatory, keys, notify }
+    }
+}
+
+impl<K: Hash + Eq + Clone> Drop for Watching<'_, K> {
+    fn drop(&mut self) {
+        let mut watchers = self.purgatory.watchers.lock().unwrap();
+        for key in self.keys {
+            if let Some(key_watchers) = watchers.get_mut(key) {
+                key_watchers.retain(|notify| !Arc::ptr_eq(notify, &self.notify));
+                if key_watchers.is_empty() {
+                    watchers.remove(key);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicI64;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_complete_when_triggered_or_expire() {
+        let purgatory = Arc::new(Purgatory::new());
+        let offset = Arc::new(AtomicI64::new(0));
+        let reached = |target| {
+            let offset = offset.clone();
+            move || (offset.load(Ordering::SeqCst) >= target).then_some(target)
+        };
+
+        assert_eq!(purgatory.try_complete_else_watch(&["a"], Duration::ZERO, reached(0)).await, Some(0));
+        assert_eq!(purgatory.try_complete_else_watch(&["a"], Duration::from_millis(10), reached(1)).await, None);
+        assert_eq!(purgatory.watched(), 0);
+
+        let waiting = tokio::spawn({
+            let purgatory = purgatory.clone();
+            let reached = reached(2);
+            async move { purgatory.try_complete_else_watch(&["a", "b"], Duration::from_secs(10), reached).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(purgatory.watched(), 2);
+        offset.store(2, Ordering::SeqCst);
+        purgatory.check_and_complete(&"b");
+        assert_eq!(tokio::time::timeout(Duration::from_secs(1), waiting).await.unwrap().unwrap(), Some(2));
+        assert_eq!(purgatory.watched(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_wakes_waiting_operations() {
+        let purgatory = Arc::new(Purgatory::new());
+        let waiting = tokio::spawn({
+            let purgatory = purgatory.clone();
+            async move { purgatory.try_complete_else_watch(&["a"], Duration::from_secs(10), || None::<()>).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        purgatory.shutdown();
+        assert_eq!(tokio::time::timeout(Duration::from_secs(1), waiting).await.unwrap().unwrap(), None);
+        assert_eq!(purgatory.try_complete_else_watch(&["a"], Duration::from_secs(10), || Some(())).await, None);
+        assert_eq!(purgatory.watched(), 0);
+    }
+}
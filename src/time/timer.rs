@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::task::AbortHandle;
+
+/// Runs delayed operations, each with a key so it can be cancelled or replaced before it runs
+#[derive(Debug)]
+pub struct Timer<K> {
+    scheduled: Arc<Mutex<HashMap<K, ScheduledOperation>>>,
+    next_id: AtomicU64,
+    /// Set while the scheduled operations are locked, so nothing is scheduled once the timer has been shut down
+    shut_down: AtomicBool,
+}
+
+#[derive(Debug)]
+struct ScheduledOperation {
+    /// Identifies which operation scheduled with the key this is, so a replaced one can tell it shouldn't run
+    id: u64,
+    handle: AbortHandle,
+}
+
+impl<K> Default for Timer<K> {
+    fn default() -> Self {
+        Timer { scheduled: Arc::new(Mutex::new(HashMap::new())), next_id: AtomicU64::new(0), shut_down: AtomicBool::new(false) }
+    }
+}
+
+impl<K: Hash + Eq + Clone + Debug + Send + 'static> Timer<K> {
+    pub fn new() -> Self {
+        Timer::default()
+    }
+
+    /// Run the operation once the delay has passed, replacing any operation already scheduled with the key.
+    /// This has to be called from within a tokio runtime. Once the timer has been shut down this does nothing
+    pub fn schedule(&self, key: K, delay: Duration, operation: impl FnOnce() + Send + 'static) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let scheduled = self.scheduled.clone();
+        let task_key = key.clone();
+        let mut operations = self.scheduled.lock().unwrap();
+        if self.shut_down.load(Ordering::SeqCst) {
+            return;
+        }
+        let handle = tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            // the operation could have been replaced just as it was aborted, in which case it mustn't run
+            let current = {
+                let mut operations = scheduled.lock().unwrap();
+                let current = operations.get(&task_key).is_some_and(|operation| operation.id == id);
+                if current {
+                    operations.remove(&task_key);
+                }
+                current
+            };
+            if current {
+                operation();
+            }
+        }).abort_handle();
+        if let Some(replaced) = operations.insert(key, ScheduledOperation { id, handle }) {
+            replaced.handle.abort();
+        }
+    }
+
+    /// Stop the operation with the key from running, returning whether there was one to stop
+    pub fn cancel(&self, key: &K) -> bool {
+        match self.scheduled.lock().unwrap().remove(key) {
+            Some(operation) => {
+                operation.handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_scheduled(&self, key: &K) -> bool {
+        self.scheduled.lock().unwrap().contains_key(key)
+    }
+
+    /// Stop every scheduled operation from running, and ignore any that are scheduled from now on
+    pub fn shutdown(&self) {
+        let mut operations = self.scheduled.lock().unwrap();
+        self.shut_down.store(true, Ordering::SeqCst);
+        for (_, operation) in operations.drain() {
+            operation.handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_schedule_replace_and_cancel() {
+        let timer = Timer::new();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = |amount| {
+            let runs = runs.clone();
+            move || { runs.fetch_add(amount, Ordering::SeqCst); }
+        };
+
+        timer.schedule("replaced", Duration::from_millis(10), counter(1));
+        timer.schedule("replaced", Duration::from_millis(10), counter(10));
+        timer.schedule("cancelled", Duration::from_millis(10), counter(100));
+        assert!(timer.cancel(&"cancelled"));
+        assert!(!timer.cancel(&"cancelled"));
+        assert!(timer.is_scheduled(&"replaced"));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 10);
+        assert!(!timer.is_scheduled(&"replaced"));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let timer = Timer::new();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = || {
+            let runs = runs.clone();
+            move || { runs.fetch_add(1, Ordering::SeqCst); }
+        };
+
+        timer.schedule("before", Duration::from_millis(10), counter());
+        timer.shutdown();
+        timer.schedule("after", Duration::from_millis(10), counter());
+        assert!(!timer.is_scheduled(&"before"));
+        assert!(!timer.is_scheduled(&"after"));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+    }
+}